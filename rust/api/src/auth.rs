@@ -1,15 +1,48 @@
 use crate::error::VssError;
 use async_trait::async_trait;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Response returned for [`Authorizer`] request if user is authenticated and authorized.
 #[derive(Debug, Clone)]
 pub struct AuthResponse {
 	/// A `user_token` identifying the authenticated and authorized user.
 	pub user_token: String,
+	/// The name of the limit profile selected by the user's credentials, e.g. their plan, if any.
+	pub limit_profile: Option<String>,
+	/// Further claims made by the user's credentials, e.g. the scalar claims of a JWT, which
+	/// [`AccessPolicy`] implementations may base their decisions on.
+	pub claims: HashMap<String, String>,
+	/// The kinds of access the user's credentials are limited to, e.g. reads and listings for a
+	/// watch-only app, or `None` if they permit all operations.
+	pub scopes: Option<Vec<AccessVerb>>,
+	/// The stores the user's credentials are limited to, e.g. `ldk-node` for the credentials of a
+	/// Lightning node kept apart from those of its app's settings, or `None` if they permit
+	/// accessing any store.
+	pub store_ids: Option<Vec<String>>,
+	/// The name of the authentication method the credentials were verified by, e.g. `jwt`, if the
+	/// authorizer names it.
+	pub auth_method: Option<&'static str>,
+}
+
+impl AuthResponse {
+	/// Whether the user's credentials permit operations needing the given kind of access.
+	pub fn permits(&self, verb: AccessVerb) -> bool {
+		self.scopes.as_ref().is_none_or(|scopes| scopes.contains(&verb))
+	}
+
+	/// Whether the user's credentials permit accessing the given store.
+	pub fn permits_store(&self, store_id: &str) -> bool {
+		self.store_ids.as_ref().is_none_or(|store_ids| store_ids.iter().any(|id| id == store_id))
+	}
 }
 
 /// Interface for authorizer that is run before executing every request.
+///
+/// An authorizer establishes who the caller is, and which kinds of access and which stores their
+/// credentials are limited to, if any, as [`AuthResponse::scopes`] and [`AuthResponse::store_ids`].
+/// These limits are enforced for each operation before consulting the [`AccessPolicy`], which
+/// decides whether the caller may perform it otherwise.
 #[async_trait]
 pub trait Authorizer: Send + Sync {
 	/// Verifies authentication and authorization based on request headers.
@@ -19,6 +52,137 @@ pub trait Authorizer: Send + Sync {
 		-> Result<AuthResponse, VssError>;
 }
 
+/// The kind of access an operation needs to the objects it addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessVerb {
+	/// Reads single objects.
+	Read,
+	/// Lists the keys of a store.
+	List,
+	/// Writes objects, possibly deleting others in the same transaction.
+	Write,
+	/// Deletes single objects.
+	Delete,
+}
+
+impl AccessVerb {
+	/// Parses the verb from its lowercase name, e.g. `write`.
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"read" => Some(AccessVerb::Read),
+			"list" => Some(AccessVerb::List),
+			"write" => Some(AccessVerb::Write),
+			"delete" => Some(AccessVerb::Delete),
+			_ => None,
+		}
+	}
+
+	/// The lowercase name of the verb, e.g. `write`.
+	pub fn name(&self) -> &'static str {
+		match self {
+			AccessVerb::Read => "read",
+			AccessVerb::List => "list",
+			AccessVerb::Write => "write",
+			AccessVerb::Delete => "delete",
+		}
+	}
+
+	/// Whether the operation changes the store.
+	pub fn is_mutation(&self) -> bool {
+		matches!(self, AccessVerb::Write | AccessVerb::Delete)
+	}
+}
+
+/// An operation an authenticated caller asks to perform, presented to an [`AccessPolicy`].
+#[derive(Debug)]
+pub struct AccessRequest<'a> {
+	/// The caller as identified by the [`Authorizer`].
+	pub caller: &'a AuthResponse,
+	/// The user owning the addressed store, which differs from the caller's `user_token` when
+	/// they act on the store of another user.
+	pub owner_user_token: &'a str,
+	/// The addressed store.
+	pub store_id: &'a str,
+	/// The keys the operation addresses, or for listings the listed key prefix.
+	pub keys: Vec<&'a str>,
+	/// Whether `keys` are prefixes of the keys the operation addresses, e.g. for listings, rather
+	/// than the keys themselves.
+	pub keys_are_prefixes: bool,
+	/// The kind of access the operation needs.
+	pub verb: AccessVerb,
+}
+
+/// Interface for deciding whether an authenticated caller may perform an operation, consulted
+/// before executing every request.
+///
+/// Policies such as ACLs, bans or read-only modes are implemented independently of how
+/// callers are authenticated, and combined with [`AllOfPolicies`].
+#[async_trait]
+pub trait AccessPolicy: Send + Sync {
+	/// Returns `Ok(())` if the operation is allowed, or [`VssError::AuthError`] explaining why it
+	/// is denied.
+	async fn check(&self, request: &AccessRequest<'_>) -> Result<(), VssError>;
+}
+
+/// An [`AccessPolicy`] allowing an operation only if each of its policies does, consulting them
+/// in order and returning the first denial.
+pub struct AllOfPolicies {
+	policies: Vec<Arc<dyn AccessPolicy>>,
+}
+
+impl AllOfPolicies {
+	/// Combines the given policies.
+	pub fn new(policies: Vec<Arc<dyn AccessPolicy>>) -> Self {
+		Self { policies }
+	}
+}
+
+#[async_trait]
+impl AccessPolicy for AllOfPolicies {
+	async fn check(&self, request: &AccessRequest<'_>) -> Result<(), VssError> {
+		for policy in &self.policies {
+			policy.check(request).await?;
+		}
+		Ok(())
+	}
+}
+
+/// An [`Authorizer`] trying each of its authorizers in order and accepting the credentials of the
+/// first one succeeding, e.g. to authenticate newer clients by JWT and older ones by signature.
+///
+/// A [`VssError::AuthError`] of an authorizer moves on to the next one, while any other error is
+/// returned right away. If all of them reject the request, the error of the first is returned.
+pub struct CompositeAuthorizer {
+	authorizers: Vec<Arc<dyn Authorizer>>,
+}
+
+impl CompositeAuthorizer {
+	/// Chains the given authorizers, in the order they are tried.
+	pub fn new(authorizers: Vec<Arc<dyn Authorizer>>) -> Self {
+		Self { authorizers }
+	}
+}
+
+#[async_trait]
+impl Authorizer for CompositeAuthorizer {
+	async fn verify(
+		&self, headers_map: &HashMap<String, String>,
+	) -> Result<AuthResponse, VssError> {
+		let mut first_error = None;
+		for authorizer in &self.authorizers {
+			match authorizer.verify(headers_map).await {
+				Ok(response) => return Ok(response),
+				Err(VssError::AuthError(message)) => {
+					first_error.get_or_insert(VssError::AuthError(message));
+				},
+				Err(e) => return Err(e),
+			}
+		}
+		Err(first_error
+			.unwrap_or_else(|| VssError::AuthError("No authorizer is configured".to_string())))
+	}
+}
+
 /// A no-operation authorizer, which lets any user-request go through.
 #[cfg(feature = "_test_utils")]
 pub struct NoopAuthorizer {}
@@ -32,6 +196,13 @@ impl Authorizer for NoopAuthorizer {
 	async fn verify(
 		&self, _headers_map: &HashMap<String, String>,
 	) -> Result<AuthResponse, VssError> {
-		Ok(AuthResponse { user_token: UNAUTHENTICATED_USER.to_string() })
+		Ok(AuthResponse {
+			user_token: UNAUTHENTICATED_USER.to_string(),
+			limit_profile: None,
+			claims: HashMap::new(),
+			scopes: None,
+			store_ids: None,
+			auth_method: None,
+		})
 	}
 }
@@ -32,6 +32,16 @@ pub enum VssError {
 	///
 	/// [`ErrorCode::InternalServerException`]: crate::types::ErrorCode::InternalServerException
 	InternalServerError(String),
+
+	/// Please refer to [`ErrorCode::QuotaExceededException`].
+	///
+	/// [`ErrorCode::QuotaExceededException`]: crate::types::ErrorCode::QuotaExceededException
+	QuotaExceededError(String),
+
+	/// Please refer to [`ErrorCode::ServiceUnavailableException`].
+	///
+	/// [`ErrorCode::ServiceUnavailableException`]: crate::types::ErrorCode::ServiceUnavailableException
+	ServiceUnavailableError(String),
 }
 
 impl Display for VssError {
@@ -52,6 +62,12 @@ impl Display for VssError {
 			VssError::InternalServerError(message) => {
 				write!(f, "InternalServerError: {}", message)
 			},
+			VssError::QuotaExceededError(message) => {
+				write!(f, "Storage quota exceeded: {}", message)
+			},
+			VssError::ServiceUnavailableError(message) => {
+				write!(f, "Service temporarily unavailable: {}", message)
+			},
 		}
 	}
 }
@@ -1,9 +1,12 @@
 use crate::error::VssError;
 use crate::types::{
-	DeleteObjectRequest, DeleteObjectResponse, GetObjectRequest, GetObjectResponse,
-	ListKeyVersionsRequest, ListKeyVersionsResponse, PutObjectRequest, PutObjectResponse,
+	AppendObjectRequest, AppendObjectResponse, DeleteObjectRequest, DeleteObjectResponse,
+	GetObjectRequest, GetObjectResponse, GetObjectVersionRequest, GetObjectsAtomicRequest,
+	GetObjectsAtomicResponse, KeyValue, ListKeyVersionsRequest, ListKeyVersionsResponse,
+	ListObjectsRequest, ListObjectsResponse, PutObjectRequest, PutObjectResponse,
 };
 use async_trait::async_trait;
+use std::time::Duration;
 
 /// The key used to store and retrieve the global version of the store.
 pub const GLOBAL_VERSION_KEY: &str = "global_version";
@@ -11,6 +14,27 @@ pub const GLOBAL_VERSION_KEY: &str = "global_version";
 /// The initial version number assigned to newly created records.
 pub const INITIAL_RECORD_VERSION: i32 = 1;
 
+/// The maximum size in bytes an object may grow to through appends.
+pub const MAX_APPENDED_VALUE_SIZE: usize = 8 * 1024 * 1024;
+
+/// The size in bytes of the values in a page of listed objects, once reached, at which the page
+/// ends. A page thus exceeds it by at most one object, and always holds at least one object.
+pub const LIST_OBJECTS_MAX_PAGE_BYTES: usize = 4 * 1024 * 1024;
+
+/// The maximum number of keys read by a single [`GetObjectsAtomicRequest`].
+pub const GET_OBJECTS_ATOMIC_MAX_KEYS: usize = 100;
+
+/// The number of times the default [`KvStore::get_objects_atomic`] reads the objects before giving
+/// up on the store being written to in the meantime.
+const GET_OBJECTS_ATOMIC_MAX_ATTEMPTS: usize = 5;
+
+/// The number of times [`append_by_rewriting`] writes an object back before giving up on it being
+/// written to concurrently.
+const APPEND_MAX_ATTEMPTS: u32 = 8;
+
+/// The time [`append_by_rewriting`] waits before its first retry, doubled on every further retry.
+const APPEND_INITIAL_BACKOFF: Duration = Duration::from_millis(5);
+
 /// An interface that must be implemented by every backend implementation of VSS.
 #[async_trait]
 pub trait KvStore: Send + Sync {
@@ -33,4 +57,211 @@ pub trait KvStore: Send + Sync {
 	async fn list_key_versions(
 		&self, user_token: String, request: ListKeyVersionsRequest,
 	) -> Result<ListKeyVersionsResponse, VssError>;
+
+	/// Lists objects, including their values, based on the provided request and user token.
+	///
+	/// The default implementation lists a page of key versions and reads the listed objects one by
+	/// one, skipping objects deleted in the meantime. Backends able to read a page of objects at
+	/// once should override it.
+	async fn list_objects(
+		&self, user_token: String, request: ListObjectsRequest,
+	) -> Result<ListObjectsResponse, VssError> {
+		let list_request = ListKeyVersionsRequest {
+			store_id: request.store_id.clone(),
+			key_prefix: request.key_prefix,
+			page_size: request.page_size,
+			page_token: request.page_token,
+		};
+		let listing = self.list_key_versions(user_token.clone(), list_request).await?;
+		let mut objects: Vec<KeyValue> = Vec::new();
+		let mut page_bytes = 0;
+		for key_version in listing.key_versions {
+			if page_bytes >= LIST_OBJECTS_MAX_PAGE_BYTES {
+				// Page tokens are the last key of a page, so the next page resumes after it.
+				let next_page_token = objects.last().map(|kv| kv.key.clone());
+				return Ok(ListObjectsResponse {
+					objects,
+					next_page_token,
+					global_version: listing.global_version,
+				});
+			}
+			let get_request =
+				GetObjectRequest { store_id: request.store_id.clone(), key: key_version.key };
+			match self.get(user_token.clone(), get_request).await {
+				Ok(response) => {
+					if let Some(kv) = response.value {
+						page_bytes += kv.value.len();
+						objects.push(kv);
+					}
+				},
+				Err(VssError::NoSuchKeyError(_)) => {},
+				Err(e) => return Err(e),
+			}
+		}
+		Ok(ListObjectsResponse {
+			objects,
+			next_page_token: listing.next_page_token,
+			global_version: listing.global_version,
+		})
+	}
+
+	/// Retrieves several objects along with the store's global version, all read from a single
+	/// snapshot of the store, based on the provided request and user token.
+	///
+	/// The default implementation reads the objects one by one between two reads of the global
+	/// version, and reads them again while a write changed the global version in the meantime. It
+	/// thus only guarantees a single snapshot for stores written with global versioning. Backends
+	/// able to read several objects at once should override it.
+	async fn get_objects_atomic(
+		&self, user_token: String, request: GetObjectsAtomicRequest,
+	) -> Result<GetObjectsAtomicResponse, VssError> {
+		validate_get_objects_atomic_request(&request)?;
+		let get_global_version = || async {
+			let get_request = GetObjectRequest {
+				store_id: request.store_id.clone(),
+				key: GLOBAL_VERSION_KEY.to_string(),
+			};
+			let response = self.get(user_token.clone(), get_request).await?;
+			// unwrap safety: get request always return a value when global_version is queried.
+			Ok::<_, VssError>(response.value.unwrap().version)
+		};
+		for _ in 0..GET_OBJECTS_ATOMIC_MAX_ATTEMPTS {
+			let global_version = get_global_version().await?;
+			let mut objects = Vec::with_capacity(request.keys.len());
+			for key in &request.keys {
+				let get_request =
+					GetObjectRequest { store_id: request.store_id.clone(), key: key.clone() };
+				match self.get(user_token.clone(), get_request).await {
+					Ok(response) => objects.extend(response.value),
+					Err(VssError::NoSuchKeyError(_)) => {},
+					Err(e) => return Err(e),
+				}
+			}
+			if get_global_version().await? == global_version {
+				return Ok(GetObjectsAtomicResponse { objects, global_version });
+			}
+		}
+		Err(VssError::ConflictError(
+			"The store was written to while reading the objects, please retry.".to_string(),
+		))
+	}
+
+	/// Retrieves an object as it was at the requested version, based on the provided request and
+	/// user token.
+	///
+	/// The default implementation keeps no previous versions, and only returns the current object
+	/// if it has the requested version. Backends keeping key history should override it.
+	async fn get_object_version(
+		&self, user_token: String, request: GetObjectVersionRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		let get_request = GetObjectRequest { store_id: request.store_id, key: request.key };
+		let response = self.get(user_token, get_request).await?;
+		match response.value {
+			Some(kv) if kv.version == request.version => Ok(GetObjectResponse { value: Some(kv) }),
+			_ => Err(VssError::NoSuchKeyError(format!(
+				"Requested version {} of the key is not available.",
+				request.version
+			))),
+		}
+	}
+
+	/// Deletes every object stored under `store_id` for the given user token, including its
+	/// global version. Returns the number of deleted objects.
+	async fn delete_store(&self, user_token: String, store_id: String) -> Result<u64, VssError>;
+
+	/// Deletes every object stored by the given user token across all of its stores.
+	/// Returns the number of deleted objects.
+	async fn delete_user(&self, user_token: String) -> Result<u64, VssError>;
+
+	/// Atomically appends bytes to an object, creating it if it does not exist, and returns the
+	/// object's new version.
+	///
+	/// The default implementation reads the object and writes it back conditionally, retrying
+	/// on conflicts, see [`append_by_rewriting`]. Backends able to append in place should override
+	/// it.
+	async fn append(
+		&self, user_token: String, request: AppendObjectRequest,
+	) -> Result<AppendObjectResponse, VssError> {
+		append_by_rewriting(self, user_token, request).await
+	}
+}
+
+/// Appends to an object by reading it and writing it back conditionally, retrying on conflicts
+/// with an exponential backoff, up to [`APPEND_MAX_ATTEMPTS`] times before returning the conflict.
+///
+/// This is the default implementation of [`KvStore::append`], for overrides falling back to it.
+pub async fn append_by_rewriting<S: KvStore + ?Sized>(
+	store: &S, user_token: String, request: AppendObjectRequest,
+) -> Result<AppendObjectResponse, VssError> {
+	validate_append_request(&request)?;
+	let mut attempt = 1;
+	loop {
+		let get_request =
+			GetObjectRequest { store_id: request.store_id.clone(), key: request.key.clone() };
+		let (mut value, version) = match store.get(user_token.clone(), get_request).await {
+			Ok(response) => response.value.map(|kv| (kv.value.to_vec(), kv.version)),
+			Err(VssError::NoSuchKeyError(_)) => None,
+			Err(e) => return Err(e),
+		}
+		.unwrap_or_default();
+		if value.len() + request.value.len() > MAX_APPENDED_VALUE_SIZE {
+			return Err(append_size_error());
+		}
+		value.extend_from_slice(&request.value);
+
+		let put_request = PutObjectRequest {
+			store_id: request.store_id.clone(),
+			global_version: None,
+			transaction_items: vec![KeyValue {
+				key: request.key.clone(),
+				version,
+				value: value.into(),
+			}],
+			delete_items: vec![],
+		};
+		match store.put(user_token.clone(), put_request).await {
+			Ok(_) => return Ok(AppendObjectResponse { version: version + 1 }),
+			Err(VssError::ConflictError(_)) if attempt < APPEND_MAX_ATTEMPTS => {
+				tokio::time::sleep(APPEND_INITIAL_BACKOFF * 2u32.pow(attempt - 1)).await;
+				attempt += 1;
+			},
+			Err(e) => return Err(e),
+		}
+	}
+}
+
+/// Rejects appends to the global version and appends exceeding [`MAX_APPENDED_VALUE_SIZE`] on
+/// their own.
+pub fn validate_append_request(request: &AppendObjectRequest) -> Result<(), VssError> {
+	if request.key == GLOBAL_VERSION_KEY {
+		return Err(VssError::InvalidRequestError(format!(
+			"Cannot append to the reserved key {}.",
+			GLOBAL_VERSION_KEY
+		)));
+	}
+	if request.value.len() > MAX_APPENDED_VALUE_SIZE {
+		return Err(append_size_error());
+	}
+	Ok(())
+}
+
+/// Rejects atomic reads of more than [`GET_OBJECTS_ATOMIC_MAX_KEYS`] keys.
+pub fn validate_get_objects_atomic_request(
+	request: &GetObjectsAtomicRequest,
+) -> Result<(), VssError> {
+	if request.keys.len() > GET_OBJECTS_ATOMIC_MAX_KEYS {
+		return Err(VssError::InvalidRequestError(format!(
+			"Number of keys per request should be less than equal to {}",
+			GET_OBJECTS_ATOMIC_MAX_KEYS
+		)));
+	}
+	Ok(())
+}
+
+/// The error returned when an append would grow an object beyond [`MAX_APPENDED_VALUE_SIZE`].
+pub fn append_size_error() -> VssError {
+	VssError::InvalidRequestError(format!(
+		"Appended objects must not exceed {} bytes.",
+		MAX_APPENDED_VALUE_SIZE
+	))
 }
@@ -0,0 +1,399 @@
+use crate::error::VssError;
+use crate::kv_store::{KvStore, INITIAL_RECORD_VERSION};
+use crate::types::{
+	AppendObjectRequest, DeleteObjectRequest, GetObjectRequest, KeyValue, ListKeyVersionsRequest,
+	PutObjectRequest,
+};
+use bytes::Bytes;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// The shape of the client sessions of a simulation.
+#[derive(Clone, Debug)]
+pub struct SimulationSettings {
+	/// The number of clients operating on the store concurrently.
+	pub clients: usize,
+	/// The number of operations each client performs.
+	pub operations_per_client: usize,
+	/// The number of keys the clients contend on.
+	pub keys: usize,
+}
+
+impl Default for SimulationSettings {
+	fn default() -> Self {
+		Self { clients: 4, operations_per_client: 6, keys: 3 }
+	}
+}
+
+/// An operation of a client session.
+#[derive(Clone, Debug)]
+enum Operation {
+	Get { key: String },
+	Put { global_version: Option<i64>, items: Vec<KeyValue>, delete_items: Vec<KeyValue> },
+	Delete { key_value: KeyValue },
+	Append { key: String, value: Bytes },
+	List,
+}
+
+/// The response of the store to an operation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Outcome {
+	/// The version and value of the read object, if it exists.
+	Value(Option<(i64, Bytes)>),
+	Written,
+	Conflict,
+	Appended(i64),
+	Listed {
+		global_version: i64,
+		key_versions: Vec<(String, i64)>,
+	},
+}
+
+/// An operation as observed by a client, on the virtual clock of the simulation.
+#[derive(Debug)]
+struct Entry {
+	client: usize,
+	operation: Operation,
+	outcome: Outcome,
+	invoked_at: u64,
+	completed_at: u64,
+}
+
+/// Runs the client sessions generated from `seed` against the store, and checks that the store's
+/// responses are linearizable with respect to the versioned key-value semantics of VSS.
+///
+/// Clients perform random reads, conditional and unconditional writes, deletes, appends and
+/// listings of a few contended keys in `store_id`, which must be empty. A scheduler driven by the
+/// same seed interleaves the invocation, execution and completion of their operations on a
+/// virtual clock, so that operations of different clients overlap. The history is linearizable if
+/// some order of its operations, consistent with their order on the clock, makes a sequential
+/// model of the store give the very responses the store gave. Returns the history if it is not,
+/// which the seed reproduces.
+pub async fn run_simulation(
+	store: &dyn KvStore, user_token: &str, store_id: &str, seed: u64, settings: &SimulationSettings,
+) -> Result<(), String> {
+	let operations = settings.clients * settings.operations_per_client;
+	assert!(operations <= 64, "Histories are limited to 64 operations");
+	let mut rng = StdRng::seed_from_u64(seed);
+	let keys: Vec<String> = (0..settings.keys).map(|i| format!("key{}", i)).collect();
+	let mut clients: Vec<Client> = (0..settings.clients)
+		.map(|id| Client {
+			id,
+			remaining: settings.operations_per_client,
+			state: ClientState::Idle,
+			invoked_at: 0,
+			known_versions: HashMap::new(),
+			known_global_version: 0,
+		})
+		.collect();
+
+	let mut history = Vec::with_capacity(operations);
+	let mut now = 0;
+	loop {
+		let active: Vec<usize> = clients
+			.iter()
+			.filter(|client| client.remaining > 0 || !matches!(client.state, ClientState::Idle))
+			.map(|client| client.id)
+			.collect();
+		let client = match active.choose(&mut rng) {
+			Some(id) => &mut clients[*id],
+			None => break,
+		};
+		now += 1;
+		match std::mem::replace(&mut client.state, ClientState::Idle) {
+			ClientState::Idle => {
+				client.state = ClientState::Invoked(client.next_operation(&mut rng, &keys, now));
+				client.invoked_at = now;
+				client.remaining -= 1;
+			},
+			ClientState::Invoked(operation) => {
+				let outcome =
+					execute(store, user_token, store_id, &operation).await.map_err(|e| {
+						format!("Unexpected error in simulation {}: {:?}: {}", seed, operation, e)
+					})?;
+				client.state = ClientState::Executed(operation, outcome);
+			},
+			ClientState::Executed(operation, outcome) => {
+				client.observe(&operation, &outcome);
+				history.push(Entry {
+					client: client.id,
+					operation,
+					outcome,
+					invoked_at: client.invoked_at,
+					completed_at: now,
+				});
+			},
+		}
+	}
+
+	let mut visited = HashSet::new();
+	if is_linearizable(&history, 0, &Model::default(), &mut visited) {
+		return Ok(());
+	}
+	let mut message = format!("History of simulation {} is not linearizable:\n", seed);
+	for entry in &history {
+		message.push_str(&format!(
+			"[{}, {}] client {}: {:?} -> {:?}\n",
+			entry.invoked_at, entry.completed_at, entry.client, entry.operation, entry.outcome
+		));
+	}
+	Err(message)
+}
+
+enum ClientState {
+	Idle,
+	Invoked(Operation),
+	Executed(Operation, Outcome),
+}
+
+/// A client, choosing versions of its writes from what it observed before, as real clients do.
+struct Client {
+	id: usize,
+	remaining: usize,
+	state: ClientState,
+	invoked_at: u64,
+	known_versions: HashMap<String, i64>,
+	known_global_version: i64,
+}
+
+impl Client {
+	fn next_operation(&self, rng: &mut StdRng, keys: &[String], now: u64) -> Operation {
+		// Values identify the write they stem from.
+		let value = Bytes::from(format!("c{}t{}", self.id, now));
+		let mut shuffled = keys.to_vec();
+		shuffled.shuffle(rng);
+		match rng.gen_range(0..10) {
+			0..=2 => Operation::Get { key: shuffled[0].clone() },
+			3..=6 => {
+				let written = rng.gen_range(1..=shuffled.len());
+				let deleted = rng.gen_range(0..=(shuffled.len() - written).min(1));
+				let items = shuffled[..written]
+					.iter()
+					.map(|key| KeyValue {
+						key: key.clone(),
+						version: self.choose_version(rng, key),
+						value: value.clone(),
+					})
+					.collect();
+				let delete_items = shuffled[written..written + deleted]
+					.iter()
+					.map(|key| KeyValue {
+						key: key.clone(),
+						version: self.choose_version(rng, key),
+						value: Bytes::new(),
+					})
+					.collect();
+				let global_version = rng.gen_bool(0.5).then_some(self.known_global_version);
+				Operation::Put { global_version, items, delete_items }
+			},
+			7 => {
+				let key = shuffled[0].clone();
+				let version = self.choose_version(rng, &key);
+				Operation::Delete { key_value: KeyValue { key, version, value: Bytes::new() } }
+			},
+			8 => Operation::Append { key: shuffled[0].clone(), value },
+			_ => Operation::List,
+		}
+	}
+
+	/// Mostly picks the version last observed, but sometimes writes unconditionally or with a
+	/// version which is likely stale.
+	fn choose_version(&self, rng: &mut StdRng, key: &str) -> i64 {
+		let known_version = self.known_versions.get(key).copied().unwrap_or(0);
+		match rng.gen_range(0..10) {
+			0 => -1,
+			1 => rng.gen_range(0..=known_version + 1),
+			_ => known_version,
+		}
+	}
+
+	fn observe(&mut self, operation: &Operation, outcome: &Outcome) {
+		match (operation, outcome) {
+			(Operation::Get { key }, Outcome::Value(value)) => {
+				let version = value.as_ref().map_or(0, |(version, _)| *version);
+				self.known_versions.insert(key.clone(), version);
+			},
+			(Operation::Put { global_version, items, delete_items }, Outcome::Written) => {
+				for item in items {
+					let version = if item.version == -1 {
+						INITIAL_RECORD_VERSION as i64
+					} else {
+						item.version + 1
+					};
+					self.known_versions.insert(item.key.clone(), version);
+				}
+				for item in delete_items {
+					self.known_versions.insert(item.key.clone(), 0);
+				}
+				if let Some(global_version) = global_version {
+					self.known_global_version = global_version + 1;
+				}
+			},
+			(Operation::Append { key, .. }, Outcome::Appended(version)) => {
+				self.known_versions.insert(key.clone(), *version);
+			},
+			(Operation::List, Outcome::Listed { global_version, key_versions }) => {
+				self.known_global_version = *global_version;
+				self.known_versions.clear();
+				self.known_versions.extend(key_versions.iter().cloned());
+			},
+			_ => {},
+		}
+	}
+}
+
+async fn execute(
+	store: &dyn KvStore, user_token: &str, store_id: &str, operation: &Operation,
+) -> Result<Outcome, VssError> {
+	let user_token = user_token.to_string();
+	let store_id = store_id.to_string();
+	let result = match operation.clone() {
+		Operation::Get { key } => {
+			match store.get(user_token, GetObjectRequest { store_id, key }).await {
+				Ok(response) => {
+					let value = response.value.map(|kv| (kv.version, kv.value));
+					Ok(Outcome::Value(value))
+				},
+				Err(VssError::NoSuchKeyError(_)) => Ok(Outcome::Value(None)),
+				Err(e) => Err(e),
+			}
+		},
+		Operation::Put { global_version, items, delete_items } => {
+			let request = PutObjectRequest {
+				store_id,
+				global_version,
+				transaction_items: items,
+				delete_items,
+			};
+			store.put(user_token, request).await.map(|_| Outcome::Written)
+		},
+		Operation::Delete { key_value } => {
+			let request = DeleteObjectRequest { store_id, key_value: Some(key_value) };
+			store.delete(user_token, request).await.map(|_| Outcome::Written)
+		},
+		Operation::Append { key, value } => {
+			let request = AppendObjectRequest { store_id, key, value };
+			store
+				.append(user_token, request)
+				.await
+				.map(|response| Outcome::Appended(response.version))
+		},
+		Operation::List => {
+			let request = ListKeyVersionsRequest {
+				store_id,
+				key_prefix: None,
+				page_size: None,
+				page_token: None,
+			};
+			store.list_key_versions(user_token, request).await.map(|response| Outcome::Listed {
+				global_version: response.global_version.unwrap_or_default(),
+				key_versions: response
+					.key_versions
+					.into_iter()
+					.map(|kv| (kv.key, kv.version))
+					.collect(),
+			})
+		},
+	};
+	match result {
+		Err(VssError::ConflictError(_)) => Ok(Outcome::Conflict),
+		result => result,
+	}
+}
+
+/// The sequential specification of a store.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+struct Model {
+	objects: BTreeMap<String, (i64, Bytes)>,
+	/// The global version of the store, 0 if it was never set.
+	global_version: i64,
+}
+
+impl Model {
+	fn apply(&mut self, operation: &Operation) -> Outcome {
+		match operation {
+			Operation::Get { key } => Outcome::Value(self.objects.get(key).cloned()),
+			Operation::Put { global_version, items, delete_items } => {
+				let global_version_matches =
+					global_version.is_none_or(|version| version == self.global_version);
+				let items_match = items.iter().all(|item| match self.objects.get(&item.key) {
+					_ if item.version == -1 => true,
+					Some((version, _)) => *version == item.version,
+					None => item.version == 0,
+				});
+				let deletes_match =
+					delete_items.iter().all(|item| match self.objects.get(&item.key) {
+						Some((version, _)) => item.version == -1 || *version == item.version,
+						None => false,
+					});
+				if !(global_version_matches && items_match && deletes_match) {
+					return Outcome::Conflict;
+				}
+				if let Some(version) = global_version {
+					self.global_version = version + 1;
+				}
+				for item in items {
+					let version = match item.version {
+						-1 => INITIAL_RECORD_VERSION as i64,
+						version => version + 1,
+					};
+					self.objects.insert(item.key.clone(), (version, item.value.clone()));
+				}
+				for item in delete_items {
+					self.objects.remove(&item.key);
+				}
+				Outcome::Written
+			},
+			Operation::Delete { key_value } => {
+				let matches = self.objects.get(&key_value.key).is_some_and(|(version, _)| {
+					key_value.version == -1 || *version == key_value.version
+				});
+				if matches {
+					self.objects.remove(&key_value.key);
+				}
+				Outcome::Written
+			},
+			Operation::Append { key, value } => {
+				let (version, appended) = match self.objects.get(key) {
+					Some((version, existing)) => {
+						(version + 1, Bytes::from([existing.as_ref(), value.as_ref()].concat()))
+					},
+					None => (INITIAL_RECORD_VERSION as i64, value.clone()),
+				};
+				self.objects.insert(key.clone(), (version, appended));
+				Outcome::Appended(version)
+			},
+			Operation::List => Outcome::Listed {
+				global_version: self.global_version,
+				key_versions: self
+					.objects
+					.iter()
+					.map(|(key, (version, _))| (key.clone(), *version))
+					.collect(),
+			},
+		}
+	}
+}
+
+/// Searches for a linearization of the operations not in `linearized` starting from `model`, in
+/// the manner of Wing and Gong, skipping states visited before.
+fn is_linearizable(
+	history: &[Entry], linearized: u64, model: &Model, visited: &mut HashSet<(u64, Model)>,
+) -> bool {
+	let pending = || (0..history.len()).filter(move |i| linearized & (1 << i) == 0);
+	// An operation may take effect next unless another pending one completed before it started.
+	let deadline = match pending().map(|i| history[i].completed_at).min() {
+		Some(deadline) => deadline,
+		None => return true,
+	};
+	if !visited.insert((linearized, model.clone())) {
+		return false;
+	}
+	pending().filter(|i| history[*i].invoked_at < deadline).any(|i| {
+		let mut next = model.clone();
+		next.apply(&history[i].operation) == history[i].outcome
+			&& is_linearizable(history, linearized | (1 << i), &next, visited)
+	})
+}
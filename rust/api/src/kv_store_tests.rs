@@ -1,8 +1,12 @@
 use crate::error::VssError;
-use crate::kv_store::{KvStore, GLOBAL_VERSION_KEY};
+use crate::kv_store::{
+	KvStore, GET_OBJECTS_ATOMIC_MAX_KEYS, GLOBAL_VERSION_KEY, LIST_OBJECTS_MAX_PAGE_BYTES,
+	MAX_APPENDED_VALUE_SIZE,
+};
 use crate::types::{
-	DeleteObjectRequest, GetObjectRequest, KeyValue, ListKeyVersionsRequest,
-	ListKeyVersionsResponse, PutObjectRequest,
+	AppendObjectRequest, DeleteObjectRequest, GetObjectRequest, GetObjectsAtomicRequest,
+	GetObjectsAtomicResponse, KeyValue, ListKeyVersionsRequest, ListKeyVersionsResponse,
+	ListObjectsRequest, ListObjectsResponse, PutObjectRequest,
 };
 use async_trait::async_trait;
 use bytes::Bytes;
@@ -13,9 +17,9 @@ use rand::{thread_rng, Rng};
 #[macro_export]
 macro_rules! define_kv_store_tests {
 	($test_suite_name:ident, $store_type:path, $create_store_expr: expr) => {
-		use crate::api::error::VssError;
-		use crate::api::kv_store_tests::KvStoreTestSuite;
 		use async_trait::async_trait;
+		use $crate::error::VssError;
+		use $crate::kv_store_tests::KvStoreTestSuite;
 		struct $test_suite_name;
 
 		#[async_trait]
@@ -53,6 +57,14 @@ macro_rules! define_kv_store_tests {
 		create_test!(list_should_honour_page_size_and_key_prefix_if_provided);
 		create_test!(list_should_return_zero_global_version_when_global_versioning_not_enabled);
 		create_test!(list_should_limit_max_page_size);
+		create_test!(list_objects_should_return_values_of_paginated_objects);
+		create_test!(list_objects_should_limit_page_bytes);
+		create_test!(get_objects_atomic_should_return_existing_objects_with_global_version);
+		create_test!(get_objects_atomic_should_limit_key_count);
+		create_test!(delete_store_should_remove_all_objects_in_store);
+		create_test!(delete_user_should_remove_all_stores_of_user);
+		create_test!(append_should_create_and_extend_object);
+		create_test!(append_should_fail_when_max_size_exceeded);
 	};
 }
 
@@ -540,6 +552,200 @@ pub trait KvStoreTestSuite {
 
 		Ok(())
 	}
+
+	async fn list_objects_should_return_values_of_paginated_objects() -> Result<(), VssError> {
+		let kv_store = Self::create_store().await;
+		let ctx = TestContext::new(&kv_store);
+
+		for i in 0..10 {
+			ctx.put_objects(Some(i), vec![kv(&format!("a{}", i), &format!("a{}v1", i), 0)]).await?;
+		}
+		ctx.put_objects(Some(10), vec![kv("a1", "a1v2", 1)]).await?;
+		ctx.put_objects(Some(11), vec![kv("b1", "b1v1", 0)]).await?;
+		ctx.delete_object(kv("a2", "", 1)).await?;
+
+		let mut next_page_token: Option<String> = None;
+		let mut all_objects: Vec<KeyValue> = Vec::new();
+		loop {
+			let page = ctx.list_objects(next_page_token.take(), Some(3), Some("a")).await?;
+			if all_objects.is_empty() {
+				assert_eq!(page.global_version, Some(12));
+			} else {
+				assert!(page.global_version.is_none());
+			}
+			assert!(page.objects.len() <= 3);
+			if page.objects.is_empty() {
+				break;
+			}
+			all_objects.extend(page.objects);
+			next_page_token = page.next_page_token;
+		}
+
+		assert_eq!(all_objects.len(), 9);
+		assert!(all_objects.iter().all(|kv| kv.key.starts_with('a') && kv.key != "a2"));
+		let a1 = all_objects.iter().find(|kv| kv.key == "a1").unwrap();
+		assert_eq!((a1.value.clone(), a1.version), (Bytes::from("a1v2"), 2));
+		let a9 = all_objects.iter().find(|kv| kv.key == "a9").unwrap();
+		assert_eq!((a9.value.clone(), a9.version), (Bytes::from("a9v1"), 1));
+
+		Ok(())
+	}
+
+	async fn list_objects_should_limit_page_bytes() -> Result<(), VssError> {
+		let kv_store = Self::create_store().await;
+		let ctx = TestContext::new(&kv_store);
+
+		let half = "a".repeat(LIST_OBJECTS_MAX_PAGE_BYTES / 2);
+		let key_values = vec![kv("k1", &half, 0), kv("k2", &half, 0), kv("k3", &half, 0)];
+		ctx.put_objects(None, key_values).await?;
+
+		// A page ends once its values reach the limit.
+		let page = ctx.list_objects(None, None, None).await?;
+		let keys: Vec<&str> = page.objects.iter().map(|kv| kv.key.as_str()).collect();
+		assert_eq!(keys, vec!["k1", "k2"]);
+		assert_eq!(page.objects[1].value.len(), half.len());
+
+		let page = ctx.list_objects(page.next_page_token, None, None).await?;
+		let keys: Vec<&str> = page.objects.iter().map(|kv| kv.key.as_str()).collect();
+		assert_eq!(keys, vec!["k3"]);
+
+		Ok(())
+	}
+
+	async fn get_objects_atomic_should_return_existing_objects_with_global_version(
+	) -> Result<(), VssError> {
+		let kv_store = Self::create_store().await;
+		let ctx = TestContext::new(&kv_store);
+
+		// Stores never written to have a global version of 0.
+		let response = ctx.get_objects_atomic(&["k1"]).await?;
+		assert!(response.objects.is_empty());
+		assert_eq!(response.global_version, 0);
+
+		ctx.put_objects(Some(0), vec![kv("k1", "k1v1", 0), kv("k2", "k2v1", 0)]).await?;
+		ctx.put_objects(Some(1), vec![kv("k2", "k2v2", 1)]).await?;
+
+		// Existing objects are returned in the requested order, missing ones left out.
+		let response = ctx.get_objects_atomic(&["k2", "missing", "k1"]).await?;
+		assert_eq!(response.objects, vec![kv("k2", "k2v2", 2), kv("k1", "k1v1", 1)]);
+		assert_eq!(response.global_version, 2);
+
+		let response = ctx.get_objects_atomic(&[]).await?;
+		assert!(response.objects.is_empty());
+		assert_eq!(response.global_version, 2);
+
+		Ok(())
+	}
+
+	async fn get_objects_atomic_should_limit_key_count() -> Result<(), VssError> {
+		let kv_store = Self::create_store().await;
+		let ctx = TestContext::new(&kv_store);
+
+		let keys: Vec<String> =
+			(0..=GET_OBJECTS_ATOMIC_MAX_KEYS).map(|i| format!("k{}", i)).collect();
+		let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+		let result = ctx.get_objects_atomic(&keys).await;
+		assert!(matches!(result, Err(VssError::InvalidRequestError(..))));
+
+		let response = ctx.get_objects_atomic(&keys[..GET_OBJECTS_ATOMIC_MAX_KEYS]).await?;
+		assert!(response.objects.is_empty());
+
+		Ok(())
+	}
+
+	async fn delete_store_should_remove_all_objects_in_store() -> Result<(), VssError> {
+		let kv_store = Self::create_store().await;
+		let ctx = TestContext::new(&kv_store);
+		let other_store_ctx = ctx.with_store_id(format!("{}-other", ctx.store_id));
+
+		ctx.put_objects(Some(0), vec![kv("k1", "k1v1", 0), kv("k2", "k2v1", 0)]).await?;
+		other_store_ctx.put_objects(None, vec![kv("k1", "k1v1", 0)]).await?;
+
+		let deleted = kv_store.delete_store(ctx.user_token.clone(), ctx.store_id.clone()).await?;
+		// Both keys and the global version record are removed.
+		assert_eq!(deleted, 3);
+
+		let result = ctx.get_object("k1").await;
+		assert!(matches!(result, Err(VssError::NoSuchKeyError(_))));
+		let response = ctx.get_object(GLOBAL_VERSION_KEY).await?;
+		assert_eq!(response.version, 0);
+
+		// Other stores of the same user are left untouched.
+		let response = other_store_ctx.get_object("k1").await?;
+		assert_eq!(response.value, Bytes::from("k1v1"));
+
+		// Deleting an empty store is a no-op.
+		let deleted = kv_store.delete_store(ctx.user_token.clone(), ctx.store_id.clone()).await?;
+		assert_eq!(deleted, 0);
+
+		Ok(())
+	}
+
+	async fn delete_user_should_remove_all_stores_of_user() -> Result<(), VssError> {
+		let kv_store = Self::create_store().await;
+		let ctx = TestContext::new(&kv_store);
+		let other_store_ctx = ctx.with_store_id(format!("{}-other", ctx.store_id));
+		let other_user_ctx = TestContext::new(&kv_store);
+
+		ctx.put_objects(None, vec![kv("k1", "k1v1", 0)]).await?;
+		other_store_ctx.put_objects(None, vec![kv("k1", "k1v1", 0)]).await?;
+		other_user_ctx.put_objects(None, vec![kv("k1", "k1v1", 0)]).await?;
+
+		let deleted = kv_store.delete_user(ctx.user_token.clone()).await?;
+		assert_eq!(deleted, 2);
+
+		let result = ctx.get_object("k1").await;
+		assert!(matches!(result, Err(VssError::NoSuchKeyError(_))));
+		let result = other_store_ctx.get_object("k1").await;
+		assert!(matches!(result, Err(VssError::NoSuchKeyError(_))));
+
+		// Other users are left untouched.
+		let response = other_user_ctx.get_object("k1").await?;
+		assert_eq!(response.value, Bytes::from("k1v1"));
+
+		Ok(())
+	}
+
+	async fn append_should_create_and_extend_object() -> Result<(), VssError> {
+		let kv_store = Self::create_store().await;
+		let ctx = TestContext::new(&kv_store);
+
+		assert_eq!(ctx.append_object("log", "a").await?, 1);
+		assert_eq!(ctx.append_object("log", "bc").await?, 2);
+
+		let response = ctx.get_object("log").await?;
+		assert_eq!(response.value, Bytes::from("abc"));
+		assert_eq!(response.version, 2);
+
+		// Appends bump the version like any other write.
+		let result = ctx.put_objects(None, vec![kv("log", "stale", 1)]).await;
+		assert!(matches!(result, Err(VssError::ConflictError(..))));
+		ctx.put_objects(None, vec![kv("log", "d", 2)]).await?;
+		assert_eq!(ctx.append_object("log", "e").await?, 4);
+		assert_eq!(ctx.get_object("log").await?.value, Bytes::from("de"));
+
+		let result = ctx.append_object(GLOBAL_VERSION_KEY, "a").await;
+		assert!(matches!(result, Err(VssError::InvalidRequestError(..))));
+
+		Ok(())
+	}
+
+	async fn append_should_fail_when_max_size_exceeded() -> Result<(), VssError> {
+		let kv_store = Self::create_store().await;
+		let ctx = TestContext::new(&kv_store);
+
+		let half = "a".repeat(MAX_APPENDED_VALUE_SIZE / 2);
+		ctx.append_object("log", &half).await?;
+		ctx.append_object("log", &half).await?;
+
+		let result = ctx.append_object("log", "a").await;
+		assert!(matches!(result, Err(VssError::InvalidRequestError(..))));
+		let response = ctx.get_object("log").await?;
+		assert_eq!(response.value.len(), MAX_APPENDED_VALUE_SIZE);
+		assert_eq!(response.version, 2);
+
+		Ok(())
+	}
 }
 
 /// Represents the context used for testing [`KvStore`] operations.
@@ -560,6 +766,11 @@ impl<'a> TestContext<'a> {
 		TestContext { kv_store, user_token, store_id }
 	}
 
+	/// Creates a new [`TestContext`] for the same user, operating on a different `store_id`.
+	pub fn with_store_id(&self, store_id: String) -> Self {
+		TestContext { kv_store: self.kv_store, user_token: self.user_token.clone(), store_id }
+	}
+
 	async fn get_object(&self, key: &str) -> Result<KeyValue, VssError> {
 		let request = GetObjectRequest { store_id: self.store_id.clone(), key: key.to_string() };
 		let response = self.kv_store.get(self.user_token.clone(), request).await?;
@@ -600,6 +811,38 @@ impl<'a> TestContext<'a> {
 		Ok(())
 	}
 
+	async fn append_object(&self, key: &str, value: &str) -> Result<i64, VssError> {
+		let request = AppendObjectRequest {
+			store_id: self.store_id.clone(),
+			key: key.to_string(),
+			value: Bytes::from(value.to_string()),
+		};
+		let response = self.kv_store.append(self.user_token.clone(), request).await?;
+		Ok(response.version)
+	}
+
+	async fn list_objects(
+		&self, next_page_token: Option<String>, page_size: Option<i32>, key_prefix: Option<&str>,
+	) -> Result<ListObjectsResponse, VssError> {
+		let request = ListObjectsRequest {
+			store_id: self.store_id.clone(),
+			page_token: next_page_token,
+			page_size,
+			key_prefix: key_prefix.map(str::to_string),
+		};
+		self.kv_store.list_objects(self.user_token.clone(), request).await
+	}
+
+	async fn get_objects_atomic(
+		&self, keys: &[&str],
+	) -> Result<GetObjectsAtomicResponse, VssError> {
+		let request = GetObjectsAtomicRequest {
+			store_id: self.store_id.clone(),
+			keys: keys.iter().map(|key| key.to_string()).collect(),
+		};
+		self.kv_store.get_objects_atomic(self.user_token.clone(), request).await
+	}
+
 	async fn list(
 		&self, next_page_token: Option<String>, page_size: Option<i32>, key_prefix: Option<String>,
 	) -> Result<ListKeyVersionsResponse, VssError> {
@@ -9,7 +9,8 @@
 #![deny(rustdoc::private_intra_doc_links)]
 #![deny(missing_docs)]
 
-/// Contains interface for authorizer that is run before every request, and its corresponding implementations.
+/// Contains interface for authorizer that is run before every request, the [`auth::AccessPolicy`] interface
+/// deciding which operations callers may perform, and their corresponding implementations.
 pub mod auth;
 /// Implements the error type ([`error::VssError`]) which is eventually converted to [`ErrorResponse`] and returned to the client.
 ///
@@ -19,9 +20,19 @@ pub mod error;
 /// Contains [`kv_store::KvStore`] interface which needs to be implemented by every backend implementation of VSS.
 pub mod kv_store;
 
+/// Contains the [`observer::KvStoreObserver`] interface notified about changes of stored objects.
+pub mod observer;
+
 /// Contains request/response types generated from the API definition of VSS.
 pub mod types;
 
 #[cfg(feature = "_test_utils")]
 /// Defines [`kv_store_tests::KvStoreTestSuite`] which is required for an implementation to be VSS protocol compliant.
 pub mod kv_store_tests;
+
+#[cfg(feature = "_test_utils")]
+/// Contains a deterministic simulation checking the linearizability of a [`kv_store::KvStore`] implementation.
+pub mod kv_store_simulation;
+
+#[cfg(test)]
+mod wire_compat_tests;
@@ -0,0 +1,25 @@
+use crate::types::{AppendObjectRequest, DeleteObjectRequest, PutObjectRequest};
+
+/// An interface for subsystems reacting to changes of stored objects, such as webhooks, change
+/// data capture, metrics or notifications.
+///
+/// Observers are called synchronously on the request path after the [`KvStore`] operation
+/// completed, so implementations must return quickly, spawning a task for any slow work such as
+/// network calls. All methods default to doing nothing, so an observer only implements the events
+/// it is interested in.
+///
+/// [`KvStore`]: crate::kv_store::KvStore
+pub trait KvStoreObserver: Send + Sync {
+	/// Called after the objects of `request` were written and deleted.
+	fn on_put(&self, _user_token: &str, _request: &PutObjectRequest) {}
+
+	/// Called after the object of `request` was deleted, or found not to exist.
+	fn on_delete(&self, _user_token: &str, _request: &DeleteObjectRequest) {}
+
+	/// Called after the value of `request` was appended to its object, which now has `version`.
+	fn on_append(&self, _user_token: &str, _request: &AppendObjectRequest, _version: i64) {}
+
+	/// Called after `request` was rejected because the global version or the version of one of
+	/// its objects did not match.
+	fn on_conflict(&self, _user_token: &str, _request: &PutObjectRequest) {}
+}
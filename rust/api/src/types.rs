@@ -171,6 +171,40 @@ pub struct DeleteObjectRequest {
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct DeleteObjectResponse {}
+/// Request payload to be used for `AppendObject` API call to server.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AppendObjectRequest {
+	/// `store_id` is a keyspace identifier.
+	/// Ref: <https://en.wikipedia.org/wiki/Keyspace_(distributed_data_store>)
+	/// All APIs operate within a single `store_id`.
+	/// It is up to clients to use single or multiple stores for their use-case.
+	/// This can be used for client-isolation/ rate-limiting / throttling on the server-side.
+	/// Authorization and billing can also be performed at the `store_id` level.
+	#[prost(string, tag = "1")]
+	pub store_id: ::prost::alloc::string::String,
+	/// The key of the object to append to. The object is created if it does not exist yet.
+	///
+	/// Appends are applied atomically without a version check, so concurrent appends to the same
+	/// `key` never conflict. Like any other write, each append increments the version of the `key`.
+	///
+	/// Fails with `INVALID_REQUEST_EXCEPTION` as the ErrorCode if the resulting value would exceed the
+	/// maximum size of appended values supported by the server.
+	#[prost(string, tag = "2")]
+	pub key: ::prost::alloc::string::String,
+	/// Bytes to be appended to the current value of the `key`.
+	#[prost(bytes = "bytes", tag = "3")]
+	pub value: ::prost::bytes::Bytes,
+}
+/// Server response for `AppendObject` API.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AppendObjectResponse {
+	/// The version of the `key` after the append, to be used in subsequent conditional
+	/// `PutObjectRequest`s for the `key`.
+	#[prost(int64, tag = "1")]
+	pub version: i64,
+}
 /// Request payload to be used for `ListKeyVersions` API call to server.
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -241,12 +275,183 @@ pub struct ListKeyVersionsResponse {
 	///    1. Read `global_version` from the first page of paginated response and save it as local variable.
 	///    2. Update all the `key_versions` on client-side from all the pages of paginated response.
 	///    3. Update `global_version` on client_side from the local variable saved in step-1.
+	///
 	/// This ensures that on client-side, all current `key_versions` were stored at `global_version` or later.
 	/// This guarantee is helpful for ensuring the versioning correctness if using the `global_version`
 	/// in `PutObject` API and can help avoid the race conditions related to it.
 	#[prost(int64, optional, tag = "3")]
 	pub global_version: ::core::option::Option<i64>,
 }
+/// Request payload to be used for `ListObjects` API call to server.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListObjectsRequest {
+	/// `store_id` is a keyspace identifier.
+	/// Ref: <https://en.wikipedia.org/wiki/Keyspace_(distributed_data_store>)
+	/// All APIs operate within a single `store_id`.
+	/// It is up to clients to use single or multiple stores for their use-case.
+	/// This can be used for client-isolation/ rate-limiting / throttling on the server-side.
+	/// Authorization and billing can also be performed at the `store_id` level.
+	#[prost(string, tag = "1")]
+	pub store_id: ::prost::alloc::string::String,
+	/// If `key_prefix` is specified, the response results will be limited to those keys that begin with
+	/// the specified prefix.
+	///
+	/// If no `key_prefix` is specified or it is empty (""), all the keys are eligible to be returned in
+	/// the response.
+	#[prost(string, optional, tag = "2")]
+	pub key_prefix: ::core::option::Option<::prost::alloc::string::String>,
+	/// `page_size` is used by clients to specify the maximum number of objects that can be returned by
+	/// the server.
+	/// The server may further constrain the maximum number of objects returned in a single page, and
+	/// ends a page early once the values in it reach the maximum page size in bytes supported by the
+	/// server.
+	/// If the `page_size` is 0 or not set, the server will decide the number of objects to be returned.
+	#[prost(int32, optional, tag = "3")]
+	pub page_size: ::core::option::Option<i32>,
+	/// `page_token` is a pagination token.
+	///
+	/// To query for the first page of `ListObjects`, `page_token` must not be specified.
+	///
+	/// For subsequent pages, use the value that was returned as `next_page_token` in the previous
+	/// page's `ListObjectsResponse`.
+	#[prost(string, optional, tag = "4")]
+	pub page_token: ::core::option::Option<::prost::alloc::string::String>,
+}
+/// Server response for `ListObjects` API.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListObjectsResponse {
+	/// Fetched keys, versions and values.
+	#[prost(message, repeated, tag = "1")]
+	pub objects: ::prost::alloc::vec::Vec<KeyValue>,
+	/// `next_page_token` is a pagination token, used to retrieve the next page of results, with the
+	/// same semantics as in `ListKeyVersionsResponse`.
+	///
+	/// If `next_page_token` is empty (""), then the "last page" of results has been processed and
+	/// there is no more data to be retrieved.
+	///
+	/// Caution: Clients must not assume a specific number of objects to be present in a page for
+	/// paginated response.
+	#[prost(string, optional, tag = "2")]
+	pub next_page_token: ::core::option::Option<::prost::alloc::string::String>,
+	/// `global_version` is a sequence-number/version of the whole store, with the same semantics as in
+	/// `ListKeyVersionsResponse`.
+	///
+	/// `global_version` is only returned in response for the first page of the `ListObjectsResponse`
+	/// and is guaranteed to be read before reading any objects.
+	#[prost(int64, optional, tag = "3")]
+	pub global_version: ::core::option::Option<i64>,
+}
+/// Request payload to be used for `GetObjectsAtomic` API call to server.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetObjectsAtomicRequest {
+	/// `store_id` is a keyspace identifier.
+	/// Ref: <https://en.wikipedia.org/wiki/Keyspace_(distributed_data_store>)
+	/// All APIs operate within a single `store_id`.
+	/// It is up to clients to use single or multiple stores for their use-case.
+	/// This can be used for client-isolation/ rate-limiting / throttling on the server-side.
+	/// Authorization and billing can also be performed at the `store_id` level.
+	#[prost(string, tag = "1")]
+	pub store_id: ::prost::alloc::string::String,
+	/// The keys of the values to be fetched, at most 100.
+	///
+	/// Read Isolation:
+	/// All keys are read from a single snapshot of the store, along with its `global_version`, so the
+	/// response never reflects only some of the items of a `PutObjectRequest`.
+	#[prost(string, repeated, tag = "2")]
+	pub keys: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// Server response for `GetObjectsAtomic` API.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetObjectsAtomicResponse {
+	/// Fetched `value` and `version` along with the corresponding `key` of the requested keys which
+	/// exist, in the order they were requested in. Keys which do not exist are left out.
+	#[prost(message, repeated, tag = "1")]
+	pub objects: ::prost::alloc::vec::Vec<KeyValue>,
+	/// `global_version` is a sequence-number/version of the whole store, read from the same snapshot
+	/// as `objects`. It can be used as the client-side `global_version` of subsequent
+	/// `PutObjectRequest`s based on the fetched objects.
+	#[prost(int64, tag = "2")]
+	pub global_version: i64,
+}
+/// Request payload to be used for `ExportStore` API call to server.
+///
+/// The response is not a single message but a stream of length-delimited `KeyValue` records, each
+/// prefixed by its length as a varint. The first record holds the store's `global_version` as the
+/// `version` of the key `global_version`, followed by a record for each object of the store.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportStoreRequest {
+	/// `store_id` is a keyspace identifier.
+	/// Ref: <https://en.wikipedia.org/wiki/Keyspace_(distributed_data_store>)
+	/// All APIs operate within a single `store_id`.
+	/// It is up to clients to use single or multiple stores for their use-case.
+	/// This can be used for client-isolation/ rate-limiting / throttling on the server-side.
+	/// Authorization and billing can also be performed at the `store_id` level.
+	#[prost(string, tag = "1")]
+	pub store_id: ::prost::alloc::string::String,
+}
+/// Request payload to be used for `ImportStore` API call to server.
+///
+/// Writes the objects of an archive returned by `ExportStore` into a store, in transactions of up
+/// to 1000 objects each. The imported objects are assigned new versions by the server, and every
+/// transaction increments the store's `global_version`.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ImportStoreRequest {
+	/// `store_id` is a keyspace identifier.
+	/// Ref: <https://en.wikipedia.org/wiki/Keyspace_(distributed_data_store>)
+	/// All APIs operate within a single `store_id`.
+	/// It is up to clients to use single or multiple stores for their use-case.
+	/// This can be used for client-isolation/ rate-limiting / throttling on the server-side.
+	/// Authorization and billing can also be performed at the `store_id` level.
+	#[prost(string, tag = "1")]
+	pub store_id: ::prost::alloc::string::String,
+	/// The stream of length-delimited `KeyValue` records returned by `ExportStore`, starting with
+	/// the `global_version` record.
+	#[prost(bytes = "bytes", tag = "2")]
+	pub archive: ::prost::bytes::Bytes,
+	/// Whether objects already existing in the store are overwritten. Otherwise, the import fails
+	/// with `CONFLICT_EXCEPTION` if any archived key already exists, before writing anything.
+	#[prost(bool, tag = "3")]
+	pub overwrite: bool,
+}
+/// Server response for `ImportStore` API.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ImportStoreResponse {
+	/// The number of objects written into the store.
+	#[prost(int64, tag = "1")]
+	pub imported_objects: i64,
+}
+/// Request payload to be used for `GetObjectVersion` API call to server.
+///
+/// Fetches a previous version of an object, as kept by servers with key history enabled. The
+/// response is a `GetObjectResponse` holding the object as it was at that version.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetObjectVersionRequest {
+	/// `store_id` is a keyspace identifier.
+	/// Ref: <https://en.wikipedia.org/wiki/Keyspace_(distributed_data_store>)
+	/// All APIs operate within a single `store_id`.
+	/// It is up to clients to use single or multiple stores for their use-case.
+	/// This can be used for client-isolation/ rate-limiting / throttling on the server-side.
+	/// Authorization and billing can also be performed at the `store_id` level.
+	#[prost(string, tag = "1")]
+	pub store_id: ::prost::alloc::string::String,
+	/// The key of the value to be fetched.
+	#[prost(string, tag = "2")]
+	pub key: ::prost::alloc::string::String,
+	/// The version of the value to be fetched.
+	///
+	/// If neither the current nor a kept previous version of `key` has this version, returns
+	/// `ErrorCode.NO_SUCH_KEY_EXCEPTION` in the `ErrorResponse`.
+	#[prost(int64, tag = "3")]
+	pub version: i64,
+}
 /// When HttpStatusCode is not ok (200), the response `content` contains a serialized `ErrorResponse`
 /// with the relevant `ErrorCode` and `message`
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -262,6 +467,32 @@ pub struct ErrorResponse {
 	/// programmatically. Client-side code may use it for logging only.
 	#[prost(string, tag = "2")]
 	pub message: ::prost::alloc::string::String,
+	/// For a `CONFLICT_EXCEPTION` of a `PutObjectRequest`, the keys whose server-side version differs
+	/// from the one in the request, along with their current version, so that clients can fetch and
+	/// merge just these keys instead of listing the whole store.
+	/// It is a best-effort description read after the conflict, and may be empty.
+	#[prost(message, repeated, tag = "3")]
+	pub conflicting_keys: ::prost::alloc::vec::Vec<ConflictingKey>,
+	/// For a `CONFLICT_EXCEPTION` of a `PutObjectRequest` whose `global_version` differs from the
+	/// server-side one, the current `global_version` of the store.
+	#[prost(int64, optional, tag = "4")]
+	pub global_version: ::core::option::Option<i64>,
+}
+/// A key of a conflicting request, as described in `ErrorResponse`.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ConflictingKey {
+	/// The conflicting key.
+	#[prost(string, tag = "1")]
+	pub key: ::prost::alloc::string::String,
+	/// The current server-side version of the key, or `0` if the key does not exist.
+	#[prost(int64, tag = "2")]
+	pub version: i64,
+	/// The SHA-256 hash of the current value of the key, letting clients tell whether their local
+	/// value already matches it. Empty if the key does not exist, or the credentials of the request
+	/// do not permit reading it.
+	#[prost(bytes = "bytes", tag = "3")]
+	pub value_sha256: ::prost::bytes::Bytes,
 }
 /// Represents a key-value pair to be stored or retrieved.
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -350,6 +581,13 @@ pub enum ErrorCode {
 	NoSuchKeyException = 4,
 	/// Used when authentication fails or in case of an unauthorized request.
 	AuthException = 5,
+	/// Used when a write is rejected because the user's stored data reached their storage quota.
+	/// Deleting objects frees up space, after which writes succeed again.
+	QuotaExceededException = 6,
+	/// Used when the server is temporarily unable to serve the request, e.g. while its database fails
+	/// over, all its database connections are in use or it is in maintenance. The request did not
+	/// fail for a fault of the client and can be retried after the `Retry-After` response header.
+	ServiceUnavailableException = 7,
 }
 impl ErrorCode {
 	/// String value of the enum field names used in the ProtoBuf definition.
@@ -364,6 +602,8 @@ impl ErrorCode {
 			ErrorCode::InternalServerException => "INTERNAL_SERVER_EXCEPTION",
 			ErrorCode::NoSuchKeyException => "NO_SUCH_KEY_EXCEPTION",
 			ErrorCode::AuthException => "AUTH_EXCEPTION",
+			ErrorCode::QuotaExceededException => "QUOTA_EXCEEDED_EXCEPTION",
+			ErrorCode::ServiceUnavailableException => "SERVICE_UNAVAILABLE_EXCEPTION",
 		}
 	}
 	/// Creates an enum from field names used in the ProtoBuf definition.
@@ -375,6 +615,8 @@ impl ErrorCode {
 			"INTERNAL_SERVER_EXCEPTION" => Some(Self::InternalServerException),
 			"NO_SUCH_KEY_EXCEPTION" => Some(Self::NoSuchKeyException),
 			"AUTH_EXCEPTION" => Some(Self::AuthException),
+			"QUOTA_EXCEEDED_EXCEPTION" => Some(Self::QuotaExceededException),
+			"SERVICE_UNAVAILABLE_EXCEPTION" => Some(Self::ServiceUnavailableException),
 			_ => None,
 		}
 	}
@@ -0,0 +1,236 @@
+//! Hosts a VSS protocol compliant [`Authorizer`] implementation using static API keys, for
+//! deployments which do not want to run a token issuer.
+//!
+//! [`Authorizer`]: api::auth::Authorizer
+
+use api::auth::{AccessVerb, AuthResponse, Authorizer};
+use api::error::VssError;
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+const BEARER_PREFIX: &str = "Bearer ";
+
+/// An authorizer which only allows requests carrying one of a configured set of API keys, granting
+/// access to the user the key belongs to.
+///
+/// Only the SHA-256 hash of each key is held, so a leaked configuration does not reveal the keys.
+/// The key is expected in the `Authorization` header in the form `Bearer <key>`, or as the whole
+/// value of a custom header set with [`ApiKeyAuthorizer::with_header`].
+pub struct ApiKeyAuthorizer {
+	/// The user token of each key, by the SHA-256 hash of the key.
+	keys: HashMap<[u8; 32], String>,
+	/// The scopes keys are limited to, by the SHA-256 hash of the key. Other keys permit all
+	/// operations.
+	key_scopes: HashMap<[u8; 32], Vec<AccessVerb>>,
+	/// The stores keys are limited to, by the SHA-256 hash of the key. Other keys permit accessing
+	/// any store.
+	key_store_ids: HashMap<[u8; 32], Vec<String>>,
+	/// The lowercase name of the custom header carrying the key, if any.
+	header: Option<String>,
+}
+
+impl ApiKeyAuthorizer {
+	/// Creates a new instance of [`ApiKeyAuthorizer`] accepting the keys with the given SHA-256
+	/// hashes, each mapped to the user token it grants access to.
+	///
+	/// Fails if the same key hash is mapped to different user tokens.
+	pub fn new(keys: impl IntoIterator<Item = ([u8; 32], String)>) -> Result<Self, String> {
+		let mut keys_by_hash: HashMap<[u8; 32], String> = HashMap::new();
+		for (key_sha256, user_token) in keys {
+			match keys_by_hash.get(&key_sha256) {
+				Some(existing) if *existing != user_token => {
+					return Err(format!(
+						"The same API key is configured for users {} and {}",
+						existing, user_token
+					));
+				},
+				_ => {
+					keys_by_hash.insert(key_sha256, user_token);
+				},
+			}
+		}
+		Ok(Self {
+			keys: keys_by_hash,
+			key_scopes: HashMap::new(),
+			key_store_ids: HashMap::new(),
+			header: None,
+		})
+	}
+
+	/// Reads the key from the whole value of the given header, e.g. `X-API-Key`, instead of the
+	/// `Authorization` header.
+	pub fn with_header(mut self, header: String) -> Self {
+		self.header = Some(header.to_ascii_lowercase());
+		self
+	}
+
+	/// Limits the key with the given SHA-256 hash to the given scopes, e.g. reads and listings for
+	/// the key of a watch-only app.
+	pub fn with_key_scopes(mut self, key_sha256: [u8; 32], scopes: Vec<AccessVerb>) -> Self {
+		self.key_scopes.insert(key_sha256, scopes);
+		self
+	}
+
+	/// Limits the key with the given SHA-256 hash to the given stores, e.g. `ldk-node` for the key
+	/// of a Lightning node, kept apart from the key its app stores its settings with.
+	pub fn with_key_store_ids(mut self, key_sha256: [u8; 32], store_ids: Vec<String>) -> Self {
+		self.key_store_ids.insert(key_sha256, store_ids);
+		self
+	}
+
+	/// The number of configured keys.
+	pub fn key_count(&self) -> usize {
+		self.keys.len()
+	}
+}
+
+/// Hashes an API key the way [`ApiKeyAuthorizer`] expects the configured keys to be hashed.
+pub fn hash_api_key(key: &str) -> [u8; 32] {
+	bitcoin_hashes::Sha256::hash(key.as_bytes()).to_byte_array()
+}
+
+#[async_trait]
+impl Authorizer for ApiKeyAuthorizer {
+	async fn verify(
+		&self, headers_map: &HashMap<String, String>,
+	) -> Result<AuthResponse, VssError> {
+		let key = match self.header.as_deref() {
+			Some(header) => headers_map
+				.get(header)
+				.ok_or_else(|| VssError::AuthError(format!("{} header not found.", header)))?,
+			None => headers_map
+				.get("authorization")
+				.ok_or_else(|| VssError::AuthError("Authorization header not found.".to_string()))?
+				.strip_prefix(BEARER_PREFIX)
+				.ok_or_else(|| VssError::AuthError("Invalid token format.".to_string()))?,
+		};
+
+		// Keys are looked up by their hash, so the lookup reveals nothing about the keys through
+		// its timing.
+		let key_sha256 = hash_api_key(key);
+		let user_token = self
+			.keys
+			.get(&key_sha256)
+			.ok_or_else(|| VssError::AuthError("Invalid API key.".to_string()))?;
+
+		Ok(AuthResponse {
+			user_token: user_token.clone(),
+			limit_profile: None,
+			claims: HashMap::new(),
+			scopes: self.key_scopes.get(&key_sha256).cloned(),
+			store_ids: self.key_store_ids.get(&key_sha256).cloned(),
+			auth_method: Some("apikey"),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::api_key::{hash_api_key, ApiKeyAuthorizer};
+	use api::auth::{AccessVerb, Authorizer};
+	use api::error::VssError;
+	use std::collections::HashMap;
+
+	fn headers(name: &str, value: &str) -> HashMap<String, String> {
+		HashMap::from([(name.to_string(), value.to_string())])
+	}
+
+	#[tokio::test]
+	async fn test_api_key() {
+		let auth = ApiKeyAuthorizer::new([
+			(hash_api_key("alice-key"), "alice".to_string()),
+			(hash_api_key("bob-key"), "bob".to_string()),
+		])
+		.unwrap();
+
+		// Each key grants access to the user it is mapped to.
+		let response = auth.verify(&headers("authorization", "Bearer alice-key")).await.unwrap();
+		assert_eq!(response.user_token, "alice");
+		let response = auth.verify(&headers("authorization", "Bearer bob-key")).await.unwrap();
+		assert_eq!(response.user_token, "bob");
+
+		// Unknown keys, keys without the bearer prefix and missing headers are rejected.
+		for headers in [
+			headers("authorization", "Bearer mallory-key"),
+			headers("authorization", "alice-key"),
+			headers("x-api-key", "alice-key"),
+		] {
+			assert!(matches!(auth.verify(&headers).await.unwrap_err(), VssError::AuthError(_)));
+		}
+
+		// A custom header carries the key without a prefix.
+		let auth = ApiKeyAuthorizer::new([(hash_api_key("alice-key"), "alice".to_string())])
+			.unwrap()
+			.with_header("X-API-Key".to_string());
+		let response = auth.verify(&headers("x-api-key", "alice-key")).await.unwrap();
+		assert_eq!(response.user_token, "alice");
+		let error = auth.verify(&headers("authorization", "Bearer alice-key")).await.unwrap_err();
+		assert!(matches!(error, VssError::AuthError(_)));
+	}
+
+	#[tokio::test]
+	async fn test_key_scopes() {
+		let auth = ApiKeyAuthorizer::new([
+			(hash_api_key("alice-key"), "alice".to_string()),
+			(hash_api_key("alice-watch-key"), "alice".to_string()),
+		])
+		.unwrap()
+		.with_key_scopes(hash_api_key("alice-watch-key"), vec![AccessVerb::Read, AccessVerb::List]);
+
+		// Keys without scopes permit all operations.
+		let response = auth.verify(&headers("authorization", "Bearer alice-key")).await.unwrap();
+		assert_eq!(response.scopes, None);
+		assert!(response.permits(AccessVerb::Write));
+
+		// Scoped keys only permit their scopes.
+		let response =
+			auth.verify(&headers("authorization", "Bearer alice-watch-key")).await.unwrap();
+		assert_eq!(response.user_token, "alice");
+		assert!(response.permits(AccessVerb::Read));
+		assert!(response.permits(AccessVerb::List));
+		assert!(!response.permits(AccessVerb::Write));
+		assert!(!response.permits(AccessVerb::Delete));
+	}
+
+	#[tokio::test]
+	async fn test_key_store_ids() {
+		let auth = ApiKeyAuthorizer::new([
+			(hash_api_key("alice-node-key"), "alice".to_string()),
+			(hash_api_key("alice-app-key"), "alice".to_string()),
+		])
+		.unwrap()
+		.with_key_store_ids(hash_api_key("alice-node-key"), vec!["ldk-node".to_string()])
+		.with_key_scopes(hash_api_key("alice-app-key"), vec![AccessVerb::Read])
+		.with_key_store_ids(hash_api_key("alice-app-key"), vec!["app-settings".to_string()]);
+
+		// Each key only permits its own stores, and its own scopes within them.
+		let response =
+			auth.verify(&headers("authorization", "Bearer alice-node-key")).await.unwrap();
+		assert_eq!(response.user_token, "alice");
+		assert!(response.permits_store("ldk-node"));
+		assert!(!response.permits_store("app-settings"));
+		assert!(response.permits(AccessVerb::Write));
+
+		let response =
+			auth.verify(&headers("authorization", "Bearer alice-app-key")).await.unwrap();
+		assert_eq!(response.user_token, "alice");
+		assert!(response.permits_store("app-settings"));
+		assert!(!response.permits_store("ldk-node"));
+		assert!(!response.permits(AccessVerb::Write));
+	}
+
+	#[test]
+	fn test_conflicting_keys() {
+		let keys = [
+			(hash_api_key("shared-key"), "alice".to_string()),
+			(hash_api_key("shared-key"), "alice".to_string()),
+		];
+		assert_eq!(ApiKeyAuthorizer::new(keys).unwrap().key_count(), 1);
+
+		let keys = [
+			(hash_api_key("shared-key"), "alice".to_string()),
+			(hash_api_key("shared-key"), "bob".to_string()),
+		];
+		assert!(ApiKeyAuthorizer::new(keys).is_err());
+	}
+}
@@ -0,0 +1,277 @@
+//! Fetches and caches the JSON Web Key Set of a token issuer, refreshing it so that the issuer can
+//! rotate its signing keys.
+
+use api::error::VssError;
+use base64::Engine;
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use jsonwebtoken::{decode_header, Algorithm, DecodingKey};
+use log::{debug, warn};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// The time the issuer has to serve a document before fetching it fails.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The minimum time between refreshes triggered by tokens signed with unknown keys, so that such
+/// tokens cannot make the server flood the issuer.
+const MIN_ON_DEMAND_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The asymmetric algorithms tokens may be signed with. Symmetric algorithms are never accepted, as
+/// a public key must not be usable as an HMAC secret.
+const ASYMMETRIC_ALGORITHMS: &[Algorithm] = &[
+	Algorithm::RS256,
+	Algorithm::RS384,
+	Algorithm::RS512,
+	Algorithm::PS256,
+	Algorithm::PS384,
+	Algorithm::PS512,
+	Algorithm::ES256,
+	Algorithm::ES384,
+	Algorithm::EdDSA,
+];
+
+struct Key {
+	kid: Option<String>,
+	decoding_key: DecodingKey,
+	/// The algorithm the key is restricted to, if the set names one.
+	algorithm: Option<Algorithm>,
+	/// The DER encoded certificate of the key, if the set carries one.
+	certificate: Option<Vec<u8>>,
+}
+
+/// The certificate a key of a JWKS is published with, as the first entry of its `x5c` parameter.
+pub struct KeyCertificate {
+	/// The id of the key, if it has one.
+	pub kid: Option<String>,
+	/// The DER encoded certificate.
+	pub der: Vec<u8>,
+}
+
+/// A cached JSON Web Key Set.
+pub(crate) struct Jwks {
+	url: String,
+	client: reqwest::Client,
+	keys: RwLock<Arc<Vec<Key>>>,
+	last_refresh: Mutex<Instant>,
+}
+
+impl Jwks {
+	/// Fetches the key set at `url`, failing if it cannot be fetched or holds no usable keys.
+	pub(crate) async fn fetch(url: String) -> Result<Arc<Self>, String> {
+		let client = http_client()?;
+		let keys = fetch_keys(&client, &url).await?;
+		Ok(Arc::new(Self {
+			url,
+			client,
+			keys: RwLock::new(Arc::new(keys)),
+			last_refresh: Mutex::new(Instant::now()),
+		}))
+	}
+
+	/// Refetches the key set every `interval` for as long as it is in use, keeping the cached keys
+	/// if refetching fails.
+	pub(crate) fn spawn_refresh(self: &Arc<Self>, interval: Duration) {
+		let jwks = Arc::downgrade(self);
+		tokio::spawn(async move {
+			let mut ticker = tokio::time::interval(interval);
+			// The first tick completes immediately, right after the keys were fetched.
+			ticker.tick().await;
+			loop {
+				ticker.tick().await;
+				let Some(jwks) = jwks.upgrade() else {
+					return;
+				};
+				if let Err(e) = jwks.refresh().await {
+					warn!("Failed to refresh JWKS from {}: {}", jwks.url, e);
+				}
+			}
+		});
+	}
+
+	async fn refresh(&self) -> Result<(), String> {
+		*self.last_refresh.lock().unwrap() = Instant::now();
+		let keys = fetch_keys(&self.client, &self.url).await?;
+		debug!("Refreshed JWKS from {} with {} key(s)", self.url, keys.len());
+		*self.keys.write().unwrap() = Arc::new(keys);
+		Ok(())
+	}
+
+	/// Returns the key verifying `token`, selected by the key id in its header, along with the
+	/// algorithm the token must be verified with. Tokens without a key id are only accepted if the
+	/// set holds a single key.
+	pub(crate) async fn key_for(&self, token: &str) -> Result<(DecodingKey, Algorithm), VssError> {
+		let header = decode_header(token)
+			.map_err(|e| VssError::AuthError(format!("Authentication failure. {}", e)))?;
+		if !ASYMMETRIC_ALGORITHMS.contains(&header.alg) {
+			return Err(auth_error("Token is not signed with an asymmetric algorithm"));
+		}
+		let (key, key_algorithm) = self
+			.key(header.kid.as_deref())
+			.await
+			.ok_or_else(|| auth_error("Token is signed by an unknown key"))?;
+		if key_algorithm.is_some_and(|key_algorithm| key_algorithm != header.alg) {
+			return Err(auth_error("Token is signed with the wrong algorithm for its key"));
+		}
+		Ok((key, header.alg))
+	}
+
+	/// Returns the key with the given id, along with the algorithm it is restricted to, if any.
+	///
+	/// If no key matches, the set is refetched first, unless it was fetched recently, as the issuer
+	/// may have rotated its keys.
+	async fn key(&self, kid: Option<&str>) -> Option<(DecodingKey, Option<Algorithm>)> {
+		if let Some(key) = self.find(kid) {
+			return Some(key);
+		}
+		let refresh_due =
+			self.last_refresh.lock().unwrap().elapsed() >= MIN_ON_DEMAND_REFRESH_INTERVAL;
+		if refresh_due {
+			if let Err(e) = self.refresh().await {
+				warn!("Failed to refresh JWKS from {}: {}", self.url, e);
+			}
+			return self.find(kid);
+		}
+		None
+	}
+
+	/// Returns the certificates of the cached keys published with one.
+	pub(crate) fn certificates(&self) -> Vec<KeyCertificate> {
+		let keys = Arc::clone(&self.keys.read().unwrap());
+		keys.iter()
+			.filter_map(|key| {
+				let der = key.certificate.clone()?;
+				Some(KeyCertificate { kid: key.kid.clone(), der })
+			})
+			.collect()
+	}
+
+	fn find(&self, kid: Option<&str>) -> Option<(DecodingKey, Option<Algorithm>)> {
+		let keys = Arc::clone(&self.keys.read().unwrap());
+		let key = match kid {
+			Some(kid) => keys.iter().find(|key| key.kid.as_deref() == Some(kid)),
+			None if keys.len() == 1 => keys.first(),
+			None => None,
+		}?;
+		Some((key.decoding_key.clone(), key.algorithm))
+	}
+}
+
+fn auth_error(msg: &str) -> VssError {
+	VssError::AuthError(msg.to_string())
+}
+
+pub(crate) fn http_client() -> Result<reqwest::Client, String> {
+	reqwest::Client::builder()
+		.timeout(FETCH_TIMEOUT)
+		.build()
+		.map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+/// Fetches the JSON document at `url`.
+pub(crate) async fn fetch_json<T: serde::de::DeserializeOwned>(
+	client: &reqwest::Client, url: &str,
+) -> Result<T, String> {
+	let response = client
+		.get(url)
+		.send()
+		.await
+		.and_then(|response| response.error_for_status())
+		.map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+	let body = response.bytes().await.map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+	serde_json::from_slice(&body).map_err(|e| format!("Invalid document at {}: {}", url, e))
+}
+
+async fn fetch_keys(client: &reqwest::Client, url: &str) -> Result<Vec<Key>, String> {
+	let set: JwkSet = fetch_json(client, url).await?;
+	// Keys of unsupported types or algorithms are skipped, as issuers may publish keys for other
+	// purposes along with their signing keys.
+	let keys: Vec<Key> = set.keys.iter().filter_map(signing_key).collect();
+	if keys.is_empty() {
+		return Err(format!("No usable signing keys in the JWKS at {}", url));
+	}
+	Ok(keys)
+}
+
+fn signing_key(jwk: &Jwk) -> Option<Key> {
+	let algorithm = match jwk.common.key_algorithm {
+		Some(key_algorithm) => {
+			let algorithm = key_algorithm.to_string().parse::<Algorithm>().ok()?;
+			if !ASYMMETRIC_ALGORITHMS.contains(&algorithm) {
+				return None;
+			}
+			Some(algorithm)
+		},
+		None => None,
+	};
+	let decoding_key = DecodingKey::from_jwk(jwk).ok()?;
+	// Certificates are encoded in standard base64, unlike the other binary parameters.
+	let certificate =
+		jwk.common.x509_chain.as_ref().and_then(|chain| {
+			base64::engine::general_purpose::STANDARD.decode(chain.first()?).ok()
+		});
+	Some(Key { kid: jwk.common.key_id.clone(), decoding_key, algorithm, certificate })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use serde_json::json;
+	use std::collections::HashMap;
+	use std::sync::{Arc, Mutex};
+	use tokio::io::{AsyncReadExt, AsyncWriteExt};
+	use tokio::net::TcpListener;
+
+	/// The modulus of the public key of [`VALID_PRIVATE_KEY_PEM`].
+	///
+	/// [`VALID_PRIVATE_KEY_PEM`]: crate::jwt::tests::VALID_PRIVATE_KEY_PEM
+	const MODULUS: &str = "ysGpKU-I9i9b-QZSANu_ExaA6w4qiQdFZaXeReiz49r1oDfABwKIFW9gK_kNnrnL9H8P-pYfj7jqUJ_glmgqMsvBshbbD2FhxytSS0mhsbh6QxUhlanymPcSUUyKBD6v7W0CGUhS5luHlsCFn4yslFk4pavcBtGap0DTUc8yz0j_xnmSQbdjWgm0awbHN48uItRO3UhLAOetG-BzlWCR8YsTa5piV8KgJpG_rwYTGXuu3lcCmnWwjmbeDq1zFFrCDDVkaIHkGJgRuFIDPXaHyUw5H2HvKlP94ySbvTDLXWZj6TyzHEHDbstqs4DgvurB_bIhi_dQ7zK3EIXL8KRBhw";
+
+	/// Returns the public key of [`VALID_PRIVATE_KEY_PEM`] as a JWK with the given key id.
+	///
+	/// [`VALID_PRIVATE_KEY_PEM`]: crate::jwt::tests::VALID_PRIVATE_KEY_PEM
+	pub(crate) fn test_jwk(kid: &str) -> serde_json::Value {
+		json!({ "kty": "RSA", "kid": kid, "alg": "RS256", "use": "sig", "n": MODULUS, "e": "AQAB" })
+	}
+
+	/// Serves JSON documents over HTTP, which can be replaced while the server runs.
+	pub(crate) struct TestServer {
+		pub(crate) url: String,
+		documents: Arc<Mutex<HashMap<String, serde_json::Value>>>,
+	}
+
+	impl TestServer {
+		pub(crate) async fn start() -> Self {
+			let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+			let url = format!("http://{}", listener.local_addr().unwrap());
+			let documents: Arc<Mutex<HashMap<String, serde_json::Value>>> = Arc::default();
+			let served = Arc::clone(&documents);
+			tokio::spawn(async move {
+				loop {
+					let (mut socket, _) = listener.accept().await.unwrap();
+					let mut request = vec![0; 4096];
+					let len = socket.read(&mut request).await.unwrap();
+					let request = String::from_utf8_lossy(&request[..len]);
+					let path = request.split(' ').nth(1).unwrap_or_default();
+					let response = match served.lock().unwrap().get(path) {
+						Some(document) => {
+							let body = document.to_string();
+							format!(
+								"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+								body.len(),
+								body
+							)
+						},
+						None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+							.to_string(),
+					};
+					socket.write_all(response.as_bytes()).await.unwrap();
+				}
+			});
+			Self { url, documents }
+		}
+
+		/// Serves `document` under `path`, replacing the document served before, if any.
+		pub(crate) fn serve(&self, path: &str, document: serde_json::Value) {
+			self.documents.lock().unwrap().insert(path.to_string(), document);
+		}
+	}
+}
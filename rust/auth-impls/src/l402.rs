@@ -0,0 +1,411 @@
+//! Hosts a VSS protocol compliant [`Authorizer`] implementation charging for access with
+//! [L402](https://github.com/lightninglabs/L402) (formerly LSAT) tokens.
+//!
+//! Clients without a token are challenged with a macaroon bound to a Lightning invoice. Once they
+//! paid the invoice, the macaroon along with the invoice's preimage grants access to the user
+//! identified by the token, so operators can charge sats for storage without running accounts.
+//!
+//! [`Authorizer`]: api::auth::Authorizer
+
+use api::auth::{AuthResponse, Authorizer};
+use api::error::VssError;
+use async_trait::async_trait;
+use base64::Engine;
+use bitcoin_hashes::{hmac, Hash, HashEngine, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+const L402_PREFIX: &str = "L402 ";
+/// The scheme L402 tokens were presented with before L402 was renamed.
+const LSAT_PREFIX: &str = "LSAT ";
+
+/// The version of the token identifiers minted, as defined by L402.
+const IDENTIFIER_VERSION: u16 = 0;
+const IDENTIFIER_LEN: usize = 2 + 32 + 32;
+
+/// The first-party caveat limiting the time until which a token is valid.
+const EXPIRES_AT_CAVEAT: &str = "expires_at=";
+
+/// A Lightning invoice a client is challenged to pay.
+pub struct Invoice {
+	/// The BOLT 11 payment request.
+	pub payment_request: String,
+	/// The hash of the preimage revealed to the payer once the invoice is paid.
+	pub payment_hash: [u8; 32],
+}
+
+/// Creates the invoices [`L402Authorizer`] challenges clients with, e.g. on a Lightning node.
+#[async_trait]
+pub trait InvoiceIssuer: Send + Sync {
+	/// Creates an invoice over `amount_sats` described by `memo`.
+	async fn create_invoice(&self, amount_sats: u64, memo: &str) -> Result<Invoice, VssError>;
+}
+
+/// A payment challenge, to be sent to clients in the `WWW-Authenticate` header of a
+/// `402 Payment Required` response.
+pub struct L402Challenge {
+	/// The base64-encoded macaroon, granting access once combined with the invoice's preimage.
+	pub macaroon: String,
+	/// The BOLT 11 payment request to pay.
+	pub invoice: String,
+}
+
+impl L402Challenge {
+	/// The value of the `WWW-Authenticate` header carrying the challenge.
+	pub fn www_authenticate(&self) -> String {
+		format!("L402 macaroon=\"{}\", invoice=\"{}\"", self.macaroon, self.invoice)
+	}
+}
+
+/// An authorizer which grants access to bearers of a paid L402 token.
+///
+/// Tokens are macaroons whose identifier holds the payment hash of the invoice they were issued
+/// with, and are expected in the `Authorization` header in the form `L402 <macaroon>:<preimage>`,
+/// with the base64-encoded macaroon and the hex-encoded preimage. The user token is the hex-encoded
+/// token id, derived from the payment hash, so each paid invoice opens a separate account.
+///
+/// Macaroons are signed with the root key the authorizer is created with, so tokens stay valid
+/// across restarts as long as the key is kept.
+pub struct L402Authorizer {
+	root_key: [u8; 32],
+	issuer: Arc<dyn InvoiceIssuer>,
+	price_sats: u64,
+	/// The time tokens are valid for after they were issued, if limited.
+	validity: Option<Duration>,
+}
+
+impl L402Authorizer {
+	/// Creates a new instance of [`L402Authorizer`] signing tokens with `root_key` and charging
+	/// `price_sats` for each, payable to invoices created by `issuer`.
+	pub fn new(root_key: [u8; 32], issuer: Arc<dyn InvoiceIssuer>, price_sats: u64) -> Self {
+		Self { root_key, issuer, price_sats, validity: None }
+	}
+
+	/// Limits the validity of tokens to `validity` after they were issued, so that access has to
+	/// be paid for again.
+	pub fn with_validity(mut self, validity: Duration) -> Self {
+		self.validity = Some(validity);
+		self
+	}
+
+	/// Creates an invoice and issues the challenge for a new token paid by it.
+	pub async fn issue_challenge(&self) -> Result<L402Challenge, VssError> {
+		let invoice = self.issuer.create_invoice(self.price_sats, "VSS storage access").await?;
+		let mut identifier = Vec::with_capacity(IDENTIFIER_LEN);
+		identifier.extend_from_slice(&IDENTIFIER_VERSION.to_be_bytes());
+		identifier.extend_from_slice(&invoice.payment_hash);
+		identifier.extend_from_slice(&hmac_sha256(&self.root_key, &invoice.payment_hash));
+		let caveats = match self.validity {
+			Some(validity) => {
+				let expires_at = unix_time_secs() + validity.as_secs();
+				vec![format!("{}{}", EXPIRES_AT_CAVEAT, expires_at).into_bytes()]
+			},
+			None => vec![],
+		};
+		let macaroon = Macaroon::mint(&self.root_key, identifier, caveats);
+		Ok(L402Challenge {
+			macaroon: base64::engine::general_purpose::STANDARD.encode(macaroon.serialize()),
+			invoice: invoice.payment_request,
+		})
+	}
+}
+
+fn auth_error(msg: &str) -> VssError {
+	VssError::AuthError(msg.to_string())
+}
+
+fn unix_time_secs() -> u64 {
+	SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs()
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+	let mut engine = hmac::HmacEngine::<bitcoin_hashes::sha256::HashEngine>::new(key);
+	engine.input(data);
+	engine.finalize().to_byte_array()
+}
+
+#[async_trait]
+impl Authorizer for L402Authorizer {
+	async fn verify(
+		&self, headers_map: &HashMap<String, String>,
+	) -> Result<AuthResponse, VssError> {
+		let auth_header = headers_map
+			.get("authorization")
+			.ok_or_else(|| auth_error("Authorization header not found."))?;
+		let token = auth_header
+			.strip_prefix(L402_PREFIX)
+			.or_else(|| auth_header.strip_prefix(LSAT_PREFIX))
+			.ok_or_else(|| auth_error("Invalid token format."))?;
+		let (macaroon, preimage) =
+			token.trim().rsplit_once(':').ok_or_else(|| auth_error("Invalid token format."))?;
+
+		let macaroon_bytes = base64::engine::general_purpose::STANDARD
+			.decode(macaroon)
+			.or_else(|_| base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(macaroon))
+			.map_err(|_| auth_error("Macaroon is not base64"))?;
+		let macaroon = Macaroon::deserialize(&macaroon_bytes)
+			.ok_or_else(|| auth_error("Macaroon is malformed"))?;
+		if !macaroon.is_signed_by(&self.root_key) {
+			return Err(auth_error("Invalid macaroon signature"));
+		}
+		if macaroon.identifier.len() != IDENTIFIER_LEN
+			|| macaroon.identifier[..2] != IDENTIFIER_VERSION.to_be_bytes()
+		{
+			return Err(auth_error("Unknown macaroon identifier version"));
+		}
+		let now = unix_time_secs();
+		for caveat in &macaroon.caveats {
+			let expires_at = std::str::from_utf8(caveat)
+				.ok()
+				.and_then(|caveat| caveat.strip_prefix(EXPIRES_AT_CAVEAT))
+				.and_then(|expires_at| expires_at.parse::<u64>().ok())
+				// Caveats which are not understood cannot be satisfied.
+				.ok_or_else(|| auth_error("Unknown macaroon caveat"))?;
+			if now >= expires_at {
+				return Err(auth_error("Token expired"));
+			}
+		}
+
+		let preimage: [u8; 32] = hex_conservative::decode_to_array(preimage)
+			.map_err(|_| auth_error("Preimage is not hex"))?;
+		let payment_hash = &macaroon.identifier[2..34];
+		if Sha256::hash(&preimage).as_byte_array() != payment_hash {
+			return Err(auth_error("Invoice not paid"));
+		}
+
+		let token_id = &macaroon.identifier[34..];
+		let user_token = token_id.iter().map(|b| format!("{:02x}", b)).collect();
+		Ok(AuthResponse {
+			user_token,
+			limit_profile: None,
+			claims: HashMap::new(),
+			scopes: None,
+			store_ids: None,
+			auth_method: Some("l402"),
+		})
+	}
+}
+
+/// A macaroon with first-party caveats only, in the V2 binary format of libmacaroons.
+struct Macaroon {
+	identifier: Vec<u8>,
+	caveats: Vec<Vec<u8>>,
+	signature: [u8; 32],
+}
+
+/// The field types of the V2 binary format.
+const FIELD_EOS: u8 = 0;
+const FIELD_LOCATION: u8 = 1;
+const FIELD_IDENTIFIER: u8 = 2;
+const FIELD_SIGNATURE: u8 = 6;
+const VERSION_2: u8 = 2;
+
+impl Macaroon {
+	fn mint(root_key: &[u8; 32], identifier: Vec<u8>, caveats: Vec<Vec<u8>>) -> Self {
+		let signature = Self::signature(root_key, &identifier, &caveats);
+		Self { identifier, caveats, signature }
+	}
+
+	/// Chains the HMACs of the identifier and each caveat, keyed by the derived root key.
+	fn signature(root_key: &[u8; 32], identifier: &[u8], caveats: &[Vec<u8>]) -> [u8; 32] {
+		let key = hmac_sha256(b"macaroons-key-generator", root_key);
+		let mut signature = hmac_sha256(&key, identifier);
+		for caveat in caveats {
+			signature = hmac_sha256(&signature, caveat);
+		}
+		signature
+	}
+
+	fn is_signed_by(&self, root_key: &[u8; 32]) -> bool {
+		let expected = Self::signature(root_key, &self.identifier, &self.caveats);
+		// Compared in constant time, to not reveal valid signatures through timing.
+		expected.iter().zip(&self.signature).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+	}
+
+	fn serialize(&self) -> Vec<u8> {
+		let mut bytes = vec![VERSION_2];
+		write_field(&mut bytes, FIELD_IDENTIFIER, &self.identifier);
+		bytes.push(FIELD_EOS);
+		for caveat in &self.caveats {
+			write_field(&mut bytes, FIELD_IDENTIFIER, caveat);
+			bytes.push(FIELD_EOS);
+		}
+		bytes.push(FIELD_EOS);
+		write_field(&mut bytes, FIELD_SIGNATURE, &self.signature);
+		bytes
+	}
+
+	fn deserialize(bytes: &[u8]) -> Option<Self> {
+		let mut reader = FieldReader { bytes, pos: 0 };
+		if reader.byte()? != VERSION_2 {
+			return None;
+		}
+		let mut field = reader.field()?;
+		if field.0 == FIELD_LOCATION {
+			field = reader.field()?;
+		}
+		if field.0 != FIELD_IDENTIFIER || reader.byte()? != FIELD_EOS {
+			return None;
+		}
+		let identifier = field.1.to_vec();
+		let mut caveats = Vec::new();
+		loop {
+			match reader.peek()? {
+				FIELD_EOS => {
+					reader.byte()?;
+					break;
+				},
+				_ => {
+					let mut field = reader.field()?;
+					if field.0 == FIELD_LOCATION {
+						field = reader.field()?;
+					}
+					// Third-party caveats carry further fields, and are not supported.
+					if field.0 != FIELD_IDENTIFIER || reader.byte()? != FIELD_EOS {
+						return None;
+					}
+					caveats.push(field.1.to_vec());
+				},
+			}
+		}
+		let (field_type, signature) = reader.field()?;
+		if field_type != FIELD_SIGNATURE || reader.pos != bytes.len() {
+			return None;
+		}
+		Some(Self { identifier, caveats, signature: signature.try_into().ok()? })
+	}
+}
+
+fn write_field(bytes: &mut Vec<u8>, field_type: u8, data: &[u8]) {
+	bytes.push(field_type);
+	let mut len = data.len();
+	while len >= 0x80 {
+		bytes.push((len as u8) | 0x80);
+		len >>= 7;
+	}
+	bytes.push(len as u8);
+	bytes.extend_from_slice(data);
+}
+
+struct FieldReader<'a> {
+	bytes: &'a [u8],
+	pos: usize,
+}
+
+impl<'a> FieldReader<'a> {
+	fn peek(&self) -> Option<u8> {
+		self.bytes.get(self.pos).copied()
+	}
+
+	fn byte(&mut self) -> Option<u8> {
+		let byte = self.peek()?;
+		self.pos += 1;
+		Some(byte)
+	}
+
+	fn field(&mut self) -> Option<(u8, &'a [u8])> {
+		let field_type = self.byte()?;
+		let mut len = 0usize;
+		for shift in (0..35).step_by(7) {
+			let byte = self.byte()?;
+			len |= ((byte & 0x7f) as usize) << shift;
+			if byte & 0x80 == 0 {
+				let data = self.bytes.get(self.pos..self.pos.checked_add(len)?)?;
+				self.pos += len;
+				return Some((field_type, data));
+			}
+		}
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::l402::{Invoice, InvoiceIssuer, L402Authorizer};
+	use api::auth::Authorizer;
+	use api::error::VssError;
+	use async_trait::async_trait;
+	use base64::Engine;
+	use bitcoin_hashes::Sha256;
+	use std::collections::HashMap;
+	use std::sync::{Arc, Mutex};
+	use std::time::Duration;
+
+	/// Issues invoices with preimages counting up from 1.
+	#[derive(Default)]
+	struct TestIssuer {
+		issued: Mutex<u8>,
+	}
+
+	#[async_trait]
+	impl InvoiceIssuer for TestIssuer {
+		async fn create_invoice(&self, amount_sats: u64, _memo: &str) -> Result<Invoice, VssError> {
+			let mut issued = self.issued.lock().unwrap();
+			*issued += 1;
+			Ok(Invoice {
+				payment_request: format!("lnbc{}n{}", amount_sats, issued),
+				payment_hash: Sha256::hash(&[*issued; 32]).to_byte_array(),
+			})
+		}
+	}
+
+	fn hex(bytes: &[u8]) -> String {
+		bytes.iter().map(|b| format!("{:02x}", b)).collect()
+	}
+
+	fn headers(macaroon: &str, preimage: &[u8]) -> HashMap<String, String> {
+		let value = format!("L402 {}:{}", macaroon, hex(preimage));
+		HashMap::from([("authorization".to_string(), value)])
+	}
+
+	#[tokio::test]
+	async fn test_l402() {
+		let auth = L402Authorizer::new([7; 32], Arc::new(TestIssuer::default()), 1000);
+
+		let challenge = auth.issue_challenge().await.unwrap();
+		assert_eq!(challenge.invoice, "lnbc1000n1");
+		assert!(challenge.www_authenticate().starts_with("L402 macaroon=\""));
+
+		// The macaroon grants access once the invoice is paid, i.e. with its preimage.
+		let response = auth.verify(&headers(&challenge.macaroon, &[1; 32])).await.unwrap();
+		let error = auth.verify(&headers(&challenge.macaroon, &[2; 32])).await.unwrap_err();
+		assert!(matches!(error, VssError::AuthError(_)));
+
+		// Each paid invoice opens a separate account.
+		let other_challenge = auth.issue_challenge().await.unwrap();
+		let other_response =
+			auth.verify(&headers(&other_challenge.macaroon, &[2; 32])).await.unwrap();
+		assert_ne!(response.user_token, other_response.user_token);
+
+		// Tokens presented with the legacy LSAT scheme are accepted too.
+		let value = format!("LSAT {}:{}", challenge.macaroon, hex(&[1; 32]));
+		let headers_map = HashMap::from([("authorization".to_string(), value)]);
+		assert_eq!(auth.verify(&headers_map).await.unwrap().user_token, response.user_token);
+
+		// Macaroons signed with another root key, or tampered with, are rejected.
+		let foreign = L402Authorizer::new([8; 32], Arc::new(TestIssuer::default()), 1000);
+		let error = foreign.verify(&headers(&challenge.macaroon, &[1; 32])).await.unwrap_err();
+		assert!(matches!(error, VssError::AuthError(_)));
+		let mut macaroon =
+			base64::engine::general_purpose::STANDARD.decode(&challenge.macaroon).unwrap();
+		macaroon[10] ^= 1;
+		let tampered = base64::engine::general_purpose::STANDARD.encode(macaroon);
+		let error = auth.verify(&headers(&tampered, &[1; 32])).await.unwrap_err();
+		assert!(matches!(error, VssError::AuthError(_)));
+	}
+
+	#[tokio::test]
+	async fn test_l402_expiry() {
+		let auth = L402Authorizer::new([7; 32], Arc::new(TestIssuer::default()), 1000)
+			.with_validity(Duration::from_secs(3600));
+		let challenge = auth.issue_challenge().await.unwrap();
+		auth.verify(&headers(&challenge.macaroon, &[1; 32])).await.unwrap();
+
+		let expired = L402Authorizer::new([7; 32], Arc::new(TestIssuer::default()), 1000)
+			.with_validity(Duration::ZERO);
+		let challenge = expired.issue_challenge().await.unwrap();
+		let error = expired.verify(&headers(&challenge.macaroon, &[1; 32])).await.unwrap_err();
+		assert!(matches!(error, VssError::AuthError(_)));
+	}
+}
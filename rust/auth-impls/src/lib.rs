@@ -1,4 +1,4 @@
-//! Hosts VSS protocol compliant [`Authorizer`] implementations.
+//! Hosts VSS protocol compliant [`Authorizer`] and [`AccessPolicy`] implementations.
 //!
 //! VSS is an open-source project designed to offer a server-side cloud storage solution specifically
 //! tailored for noncustodial Lightning supporting mobile wallets. Its primary objective is to
@@ -6,13 +6,31 @@
 //! and manage the essential state required for Lightning Network (LN) operations.
 //!
 //! [`Authorizer`]: api::auth::Authorizer
+//! [`AccessPolicy`]: api::auth::AccessPolicy
 
 #![deny(rustdoc::broken_intra_doc_links)]
 #![deny(rustdoc::private_intra_doc_links)]
 #![deny(missing_docs)]
 
+#[cfg(feature = "apikey")]
+pub mod api_key;
+
+#[cfg(feature = "jwks")]
+mod jwks;
+
 #[cfg(feature = "jwt")]
 pub mod jwt;
 
+#[cfg(feature = "l402")]
+pub mod l402;
+
+#[cfg(feature = "nostr")]
+pub mod nostr;
+
+#[cfg(feature = "oidc")]
+pub mod oidc;
+
+pub mod rules;
+
 #[cfg(feature = "sigs")]
 pub mod signature;
@@ -0,0 +1,253 @@
+//! Hosts a VSS protocol compliant [`Authorizer`] implementation using
+//! [NIP-98](https://github.com/nostr-protocol/nips/blob/master/98.md) Nostr HTTP auth events.
+//! Access is granted to the user defined by the Nostr public key which signed the event, so users
+//! holding a Nostr key need no separate token issuer.
+//!
+//! As with the signature-validating authorizer, there is no restriction of who is allowed to store
+//! data, so new user accounts must be rate-limited by other means.
+//!
+//! [`Authorizer`]: api::auth::Authorizer
+
+use api::auth::{AuthResponse, Authorizer};
+use api::error::VssError;
+use async_trait::async_trait;
+use base64::Engine;
+use bitcoin_hashes::Sha256;
+use secp256k1::{schnorr, XOnlyPublicKey, SECP256K1};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+const NOSTR_PREFIX: &str = "Nostr ";
+
+/// The kind of NIP-98 HTTP auth events.
+pub const HTTP_AUTH_EVENT_KIND: u16 = 27235;
+
+/// The maximum difference between the creation time of an event and the current time, in seconds.
+const MAX_CLOCK_SKEW_SECS: u64 = 60;
+
+/// The method all VSS requests are made with.
+const REQUEST_METHOD: &str = "POST";
+
+/// A signed Nostr event, as defined by NIP-01.
+#[derive(Deserialize)]
+struct Event {
+	id: String,
+	pubkey: String,
+	created_at: u64,
+	kind: u16,
+	tags: Vec<Vec<String>>,
+	content: String,
+	sig: String,
+}
+
+impl Event {
+	/// Returns the value of the first tag with the given name.
+	fn tag(&self, name: &str) -> Option<&str> {
+		self.tags
+			.iter()
+			.find(|tag| tag.first().map(String::as_str) == Some(name))?
+			.get(1)
+			.map(|v| v.as_str())
+	}
+}
+
+/// An authorizer which requires every request to carry a NIP-98 HTTP auth event signed by the
+/// user's Nostr key, granting access to the user identified by the key's `npub` encoding.
+///
+/// The event is expected base64-encoded in the `Authorization` header, in the form
+/// `Nostr <event>`. It must be of kind [`HTTP_AUTH_EVENT_KIND`], have been created within a minute
+/// of now and carry a `method` tag of `POST`. Authorizers only see the headers of a request, so
+/// the `u` tag is only checked to be under the prefix set with [`NostrAuthorizer::with_url_prefix`],
+/// if any, and the `payload` tag is not checked. An event can thus be replayed for other requests
+/// to the server for as long as it is fresh.
+pub struct NostrAuthorizer {
+	/// The prefix the URL of the `u` tag must start with, if any.
+	url_prefix: Option<String>,
+}
+
+impl NostrAuthorizer {
+	/// Creates a new instance of [`NostrAuthorizer`] accepting events for any URL.
+	pub fn new() -> Self {
+		Self { url_prefix: None }
+	}
+
+	/// Only accepts events whose `u` tag starts with `url_prefix`, e.g. `https://vss.example.com/`,
+	/// so that events made for other services are not accepted.
+	pub fn with_url_prefix(mut self, url_prefix: String) -> Self {
+		self.url_prefix = Some(url_prefix);
+		self
+	}
+}
+
+impl Default for NostrAuthorizer {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Encodes a Nostr public key in its `npub` form, the user token of its owner.
+pub fn npub(public_key: &XOnlyPublicKey) -> String {
+	let hrp = bech32::Hrp::parse_unchecked("npub");
+	// unwrap safety: 32 bytes are well below the maximum length of bech32 strings.
+	bech32::encode::<bech32::Bech32>(hrp, &public_key.serialize()).unwrap()
+}
+
+fn auth_error(msg: &str) -> VssError {
+	VssError::AuthError(msg.to_string())
+}
+
+#[async_trait]
+impl Authorizer for NostrAuthorizer {
+	async fn verify(
+		&self, headers_map: &HashMap<String, String>,
+	) -> Result<AuthResponse, VssError> {
+		let encoded_event = headers_map
+			.get("authorization")
+			.ok_or_else(|| auth_error("Authorization header not found."))?
+			.strip_prefix(NOSTR_PREFIX)
+			.ok_or_else(|| auth_error("Invalid token format."))?;
+		let event_json = base64::engine::general_purpose::STANDARD
+			.decode(encoded_event.trim())
+			.map_err(|_| auth_error("Authorization event is not base64"))?;
+		let event: Event = serde_json::from_slice(&event_json)
+			.map_err(|_| auth_error("Authorization event is malformed"))?;
+
+		if event.kind != HTTP_AUTH_EVENT_KIND {
+			return Err(auth_error("Authorization event has wrong kind"));
+		}
+		let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+		if event.created_at.abs_diff(now) > MAX_CLOCK_SKEW_SECS {
+			return Err(auth_error("Authorization event is too far from now"));
+		}
+		if event.tag("method") != Some(REQUEST_METHOD) {
+			return Err(auth_error("Authorization event has wrong method"));
+		}
+		let url = event.tag("u").ok_or_else(|| auth_error("Authorization event has no URL"))?;
+		if let Some(url_prefix) = self.url_prefix.as_deref() {
+			if !url.starts_with(url_prefix) {
+				return Err(auth_error("Authorization event has wrong URL"));
+			}
+		}
+
+		let pubkey_bytes: [u8; 32] = hex_conservative::decode_to_array(&event.pubkey)
+			.map_err(|_| auth_error("Authorization event has bad pubkey"))?;
+		let pubkey = XOnlyPublicKey::from_byte_array(pubkey_bytes)
+			.map_err(|_| auth_error("Authorization event has bad pubkey"))?;
+		let sig_bytes: [u8; 64] = hex_conservative::decode_to_array(&event.sig)
+			.map_err(|_| auth_error("Authorization event has bad sig"))?;
+		let sig = schnorr::Signature::from_byte_array(sig_bytes);
+
+		// The id commits to the event's contents as serialized by NIP-01, and is what is signed.
+		let serialized = serde_json::json!([
+			0,
+			event.pubkey.to_ascii_lowercase(),
+			event.created_at,
+			event.kind,
+			event.tags,
+			event.content
+		])
+		.to_string();
+		let id = Sha256::hash(serialized.as_bytes()).to_byte_array();
+		let claimed_id: [u8; 32] = hex_conservative::decode_to_array(&event.id)
+			.map_err(|_| auth_error("Authorization event has bad id"))?;
+		if id != claimed_id {
+			return Err(auth_error("Authorization event has wrong id"));
+		}
+		SECP256K1
+			.verify_schnorr(&sig, &id, &pubkey)
+			.map_err(|_| auth_error("Invalid signature"))?;
+
+		Ok(AuthResponse {
+			user_token: npub(&pubkey),
+			limit_profile: None,
+			claims: HashMap::new(),
+			scopes: None,
+			store_ids: None,
+			auth_method: Some("nostr"),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::nostr::{npub, NostrAuthorizer, HTTP_AUTH_EVENT_KIND};
+	use api::auth::Authorizer;
+	use api::error::VssError;
+	use base64::Engine;
+	use bitcoin_hashes::Sha256;
+	use secp256k1::{Keypair, SecretKey, SECP256K1};
+	use std::collections::HashMap;
+	use std::time::SystemTime;
+
+	fn hex(bytes: &[u8]) -> String {
+		bytes.iter().map(|b| format!("{:02x}", b)).collect()
+	}
+
+	fn now() -> u64 {
+		SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs()
+	}
+
+	fn signed_header(
+		keypair: &Keypair, created_at: u64, kind: u16, tags: Vec<Vec<String>>,
+	) -> HashMap<String, String> {
+		let pubkey = hex(&keypair.x_only_public_key().0.serialize());
+		let serialized = serde_json::json!([0, pubkey, created_at, kind, tags, ""]).to_string();
+		let id = Sha256::hash(serialized.as_bytes()).to_byte_array();
+		let sig = SECP256K1.sign_schnorr_no_aux_rand(&id, keypair);
+		let event = serde_json::json!({
+			"id": hex(&id),
+			"pubkey": pubkey,
+			"created_at": created_at,
+			"kind": kind,
+			"tags": tags,
+			"content": "",
+			"sig": hex(&sig.to_byte_array()),
+		});
+		let encoded = base64::engine::general_purpose::STANDARD.encode(event.to_string());
+		HashMap::from([("authorization".to_string(), format!("Nostr {}", encoded))])
+	}
+
+	fn tags(url: &str, method: &str) -> Vec<Vec<String>> {
+		vec![vec!["u".to_string(), url.to_string()], vec!["method".to_string(), method.to_string()]]
+	}
+
+	#[tokio::test]
+	async fn test_nostr_auth() {
+		let secret_key = SecretKey::from_byte_array([42; 32]).unwrap();
+		let keypair = Keypair::from_secret_key(SECP256K1, &secret_key);
+		let url = "https://vss.example.com/vss/getObject";
+		let auth = NostrAuthorizer::new().with_url_prefix("https://vss.example.com/".to_string());
+
+		let headers = signed_header(&keypair, now(), HTTP_AUTH_EVENT_KIND, tags(url, "POST"));
+		let response = auth.verify(&headers).await.unwrap();
+		assert_eq!(response.user_token, npub(&keypair.x_only_public_key().0));
+		assert!(response.user_token.starts_with("npub1"));
+
+		// Stale events, events of other kinds, methods or services are rejected.
+		for headers in [
+			signed_header(&keypair, now() - 120, HTTP_AUTH_EVENT_KIND, tags(url, "POST")),
+			signed_header(&keypair, now(), 1, tags(url, "POST")),
+			signed_header(&keypair, now(), HTTP_AUTH_EVENT_KIND, tags(url, "GET")),
+			signed_header(
+				&keypair,
+				now(),
+				HTTP_AUTH_EVENT_KIND,
+				tags("https://other.example.com/vss/getObject", "POST"),
+			),
+			HashMap::from([("authorization".to_string(), "Nostr bm90IGFuIGV2ZW50".to_string())]),
+			HashMap::new(),
+		] {
+			assert!(matches!(auth.verify(&headers).await.unwrap_err(), VssError::AuthError(_)));
+		}
+
+		// Tampering with a signed event invalidates it.
+		let mut headers = signed_header(&keypair, now(), HTTP_AUTH_EVENT_KIND, tags(url, "POST"));
+		let encoded = headers["authorization"].strip_prefix("Nostr ").unwrap();
+		let event = base64::engine::general_purpose::STANDARD.decode(encoded).unwrap();
+		let tampered = String::from_utf8(event).unwrap().replace("getObject", "putObjects");
+		let encoded = base64::engine::general_purpose::STANDARD.encode(tampered);
+		headers.insert("authorization".to_string(), format!("Nostr {}", encoded));
+		assert!(matches!(auth.verify(&headers).await.unwrap_err(), VssError::AuthError(_)));
+	}
+}
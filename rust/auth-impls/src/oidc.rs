@@ -0,0 +1,226 @@
+//! Hosts a VSS protocol compliant [`Authorizer`] implementation accepting the ID and access tokens
+//! of an [OpenID Connect](https://openid.net/specs/openid-connect-core-1_0.html) provider, such as
+//! Keycloak, Auth0 or Google.
+//!
+//! The provider's signing keys are located through its
+//! [discovery document](https://openid.net/specs/openid-connect-discovery-1_0.html) and refetched
+//! periodically, so that the provider can rotate them without the server being reconfigured.
+//!
+//! [`Authorizer`]: api::auth::Authorizer
+
+use crate::jwks::{fetch_json, http_client, Jwks, KeyCertificate};
+use crate::jwt::{ClaimNames, Claims, BEARER_PREFIX};
+use api::auth::{AuthResponse, Authorizer};
+use api::error::VssError;
+use async_trait::async_trait;
+use jsonwebtoken::{decode, Algorithm, Validation};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The path of the discovery document, relative to the issuer URL.
+const DISCOVERY_PATH: &str = "/.well-known/openid-configuration";
+
+/// The interval at which the provider's signing keys are refetched by default.
+pub const DEFAULT_KEY_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The fields of the provider's discovery document needed to verify its tokens.
+#[derive(Deserialize)]
+struct ProviderMetadata {
+	issuer: String,
+	jwks_uri: String,
+}
+
+/// An authorizer which only allows requests with a token issued by an OpenID Connect provider for
+/// the given audience, granting access to the user identified by the token's `sub` claim.
+///
+/// Tokens are expected in the `Authorization` header in the form `Bearer <token>`, and must carry
+/// `iss`, `aud` and `exp` claims matching the provider, the audience and the current time. They
+/// must be signed with an asymmetric algorithm, by a key of the provider's JWKS. Tokens signed by a
+/// key which is not known yet make the keys be refetched, at most once a minute.
+pub struct OidcAuthorizer {
+	jwks: Arc<Jwks>,
+	issuer: String,
+	audience: String,
+	claim_names: ClaimNames,
+}
+
+impl OidcAuthorizer {
+	/// Creates a new instance of [`OidcAuthorizer`] for the provider at `issuer_url` and tokens
+	/// issued for `audience`, usually the client id the server is registered with, refetching the
+	/// provider's keys every `key_refresh_interval`.
+	///
+	/// Fails if the provider's discovery document or keys cannot be fetched, or if the document
+	/// names another issuer. Must be called within a Tokio runtime.
+	pub async fn new(
+		issuer_url: &str, audience: String, key_refresh_interval: Duration,
+	) -> Result<Self, String> {
+		let client = http_client()?;
+		let discovery_url = format!("{}{}", issuer_url.trim_end_matches('/'), DISCOVERY_PATH);
+		let metadata: ProviderMetadata = fetch_json(&client, &discovery_url).await?;
+		if metadata.issuer != issuer_url {
+			return Err(format!(
+				"The discovery document of {} names another issuer: {}",
+				issuer_url, metadata.issuer
+			));
+		}
+		let jwks = Jwks::fetch(metadata.jwks_uri).await?;
+		jwks.spawn_refresh(key_refresh_interval);
+		Ok(Self { jwks, issuer: metadata.issuer, audience, claim_names: ClaimNames::default() })
+	}
+
+	/// Selects the limit profile of each user from the given string claim, e.g. `plan`. Tokens
+	/// lacking the claim get no profile.
+	pub fn with_profile_claim(mut self, claim: String) -> Self {
+		self.claim_names.profile = Some(claim);
+		self
+	}
+
+	/// Limits each user to the scopes named by the given claim, e.g. `scope`, as for
+	/// [`JWTAuthorizer::with_scope_claim`]. Tokens lacking the claim permit all operations.
+	///
+	/// [`JWTAuthorizer::with_scope_claim`]: crate::jwt::JWTAuthorizer::with_scope_claim
+	pub fn with_scope_claim(mut self, claim: String) -> Self {
+		self.claim_names.scope = Some(claim);
+		self
+	}
+
+	/// Limits each user to the stores named by the given claim, e.g. `stores`, as for
+	/// [`JWTAuthorizer::with_store_claim`]. Tokens lacking the claim permit accessing any store.
+	///
+	/// [`JWTAuthorizer::with_store_claim`]: crate::jwt::JWTAuthorizer::with_store_claim
+	pub fn with_store_claim(mut self, claim: String) -> Self {
+		self.claim_names.store = Some(claim);
+		self
+	}
+
+	/// Returns the certificates the provider's keys are currently published with, e.g. to monitor
+	/// their expiry.
+	pub fn key_certificates(&self) -> Vec<KeyCertificate> {
+		self.jwks.certificates()
+	}
+
+	fn validation(&self, algorithm: Algorithm) -> Validation {
+		let mut validation = Validation::new(algorithm);
+		validation.set_issuer(&[&self.issuer]);
+		validation.set_audience(&[&self.audience]);
+		validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+		validation
+	}
+}
+
+fn auth_error(msg: &str) -> VssError {
+	VssError::AuthError(msg.to_string())
+}
+
+#[async_trait]
+impl Authorizer for OidcAuthorizer {
+	async fn verify(
+		&self, headers_map: &HashMap<String, String>,
+	) -> Result<AuthResponse, VssError> {
+		let token = headers_map
+			.get("authorization")
+			.ok_or_else(|| auth_error("Authorization header not found."))?
+			.strip_prefix(BEARER_PREFIX)
+			.ok_or_else(|| auth_error("Invalid token format."))?;
+
+		let (key, algorithm) = self.jwks.key_for(token).await?;
+		let claims = decode::<Claims>(token, &key, &self.validation(algorithm))
+			.map_err(|e| VssError::AuthError(format!("Authentication failure. {}", e)))?
+			.claims;
+		let response = claims.into_auth_response(&self.claim_names)?;
+		Ok(AuthResponse { auth_method: Some("oidc"), ..response })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::jwks::tests::{test_jwk, TestServer};
+	use crate::jwt::tests::VALID_PRIVATE_KEY_PEM;
+	use crate::oidc::{OidcAuthorizer, DEFAULT_KEY_REFRESH_INTERVAL};
+	use api::auth::Authorizer;
+	use api::error::VssError;
+	use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+	use serde_json::json;
+	use std::collections::HashMap;
+	use std::time::SystemTime;
+
+	const KEY_ID: &str = "key-1";
+
+	/// Serves the discovery document and JWKS of a provider, returning its issuer URL.
+	async fn serve_provider() -> TestServer {
+		let server = TestServer::start().await;
+		let discovery = json!({ "issuer": server.url, "jwks_uri": format!("{}/jwks", server.url) });
+		server.serve("/.well-known/openid-configuration", discovery);
+		let hmac_key = json!({ "kty": "oct", "kid": "hmac", "alg": "HS256", "k": "c2VjcmV0" });
+		server.serve("/jwks", json!({ "keys": [hmac_key, test_jwk(KEY_ID)] }));
+		server
+	}
+
+	fn bearer(
+		header: Header, claims: serde_json::Value, key: &EncodingKey,
+	) -> HashMap<String, String> {
+		let token = encode(&header, &claims, key).unwrap();
+		HashMap::from([("authorization".to_string(), format!("Bearer {}", token))])
+	}
+
+	#[tokio::test]
+	async fn test_oidc_auth() {
+		let provider = serve_provider().await;
+		let issuer = provider.url.clone();
+		let auth = OidcAuthorizer::new(&issuer, "vss".to_string(), DEFAULT_KEY_REFRESH_INTERVAL)
+			.await
+			.unwrap()
+			.with_profile_claim("plan".to_string());
+
+		let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+		let key = EncodingKey::from_rsa_pem(VALID_PRIVATE_KEY_PEM.as_bytes()).unwrap();
+		let mut header = Header::new(Algorithm::RS256);
+		header.kid = Some(KEY_ID.to_string());
+		let claims = |iss: &str, aud: &str, exp: u64| json!({ "sub": "user", "iss": iss, "aud": aud, "exp": exp, "plan": "paid" });
+
+		let response = auth
+			.verify(&bearer(header.clone(), claims(&issuer, "vss", now + 60), &key))
+			.await
+			.unwrap();
+		assert_eq!(response.user_token, "user");
+		assert_eq!(response.limit_profile.as_deref(), Some("paid"));
+
+		// Tokens of other issuers or audiences, and expired tokens, are rejected.
+		for claims in [
+			claims("https://other.example.com", "vss", now + 60),
+			claims(&issuer, "other", now + 60),
+			claims(&issuer, "vss", now - 120),
+		] {
+			let error = auth.verify(&bearer(header.clone(), claims, &key)).await.unwrap_err();
+			assert!(matches!(error, VssError::AuthError(_)));
+		}
+
+		// Tokens signed by unknown keys, or with symmetric algorithms, are rejected.
+		let mut unknown_key_header = header.clone();
+		unknown_key_header.kid = Some("key-2".to_string());
+		let mut hmac_header = Header::new(Algorithm::HS256);
+		hmac_header.kid = Some("hmac".to_string());
+		for headers in [
+			bearer(unknown_key_header, claims(&issuer, "vss", now + 60), &key),
+			bearer(
+				hmac_header,
+				claims(&issuer, "vss", now + 60),
+				&EncodingKey::from_secret(b"secret"),
+			),
+		] {
+			assert!(matches!(auth.verify(&headers).await.unwrap_err(), VssError::AuthError(_)));
+		}
+
+		// Providers whose discovery document names another issuer are refused.
+		let other_issuer = issuer.replace("127.0.0.1", "localhost");
+		assert!(OidcAuthorizer::new(
+			&other_issuer,
+			"vss".to_string(),
+			DEFAULT_KEY_REFRESH_INTERVAL
+		)
+		.await
+		.is_err());
+	}
+}
@@ -0,0 +1,266 @@
+//! Hosts an [`AccessPolicy`] evaluating declarative rules written by operators, e.g. to deny writes
+//! under `monitors/` to callers whose credentials lack a claim, without changing code.
+//!
+//! Rules are evaluated in order and the first rule matching a request decides whether it is
+//! allowed. Requests matched by no rule get the default effect.
+//!
+//! [`AccessPolicy`]: api::auth::AccessPolicy
+
+use api::auth::{AccessPolicy, AccessRequest, AccessVerb};
+use api::error::VssError;
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+/// Whether a matching rule allows or denies a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleEffect {
+	/// Allows the request, subject to the other configured access policies.
+	Allow,
+	/// Denies the request.
+	Deny,
+}
+
+impl RuleEffect {
+	/// Parses the effect from its lowercase name, i.e. `allow` or `deny`.
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"allow" => Some(RuleEffect::Allow),
+			"deny" => Some(RuleEffect::Deny),
+			_ => None,
+		}
+	}
+}
+
+/// A rule deciding on the requests it matches. A request matches a rule if it satisfies every
+/// condition of the rule; conditions left empty match all requests.
+#[derive(Debug, Clone)]
+pub struct AccessRule {
+	/// A name identifying the rule in denials, defaulting to its position.
+	pub name: Option<String>,
+	/// Whether matched requests are allowed or denied.
+	pub effect: RuleEffect,
+	/// The verbs of the operations the rule applies to.
+	pub verbs: Vec<AccessVerb>,
+	/// The stores the rule applies to.
+	pub store_ids: Vec<String>,
+	/// The key prefixes the rule applies to. A request matches a denying rule if any key it
+	/// addresses starts with one of the prefixes, while a request addressing key prefixes, e.g. a
+	/// listing, matches if it may address such a key. A request only matches an allowing rule if
+	/// all keys it addresses, or all key prefixes, start with one of the prefixes.
+	pub key_prefixes: Vec<String>,
+	/// The claims the caller's credentials must make, with their exact values.
+	pub claims: HashMap<String, String>,
+	/// The claims the caller's credentials must not make.
+	pub missing_claims: Vec<String>,
+}
+
+impl AccessRule {
+	fn matches(&self, request: &AccessRequest<'_>) -> bool {
+		let caller_claims = &request.caller.claims;
+		(self.verbs.is_empty() || self.verbs.contains(&request.verb))
+			&& (self.store_ids.is_empty() || self.store_ids.iter().any(|id| id == request.store_id))
+			&& (self.key_prefixes.is_empty() || self.matches_keys(request))
+			&& self.claims.iter().all(|(name, value)| caller_claims.get(name) == Some(value))
+			&& self.missing_claims.iter().all(|name| !caller_claims.contains_key(name))
+	}
+
+	fn matches_keys(&self, request: &AccessRequest<'_>) -> bool {
+		match self.effect {
+			// Allowing a request addressing keys outside of the rule's prefixes would let it pass
+			// rules denying them further down.
+			RuleEffect::Allow => request
+				.keys
+				.iter()
+				.all(|key| self.key_prefixes.iter().any(|prefix| key.starts_with(prefix.as_str()))),
+			RuleEffect::Deny => request.keys.iter().any(|key| {
+				self.key_prefixes.iter().any(|prefix| {
+					// A listing of a prefix of the rule's prefix includes the keys under it.
+					key.starts_with(prefix.as_str())
+						|| (request.keys_are_prefixes && prefix.starts_with(key))
+				})
+			}),
+		}
+	}
+}
+
+/// An [`AccessPolicy`] deciding on requests by the first matching [`AccessRule`].
+pub struct RuleBasedPolicy {
+	rules: Vec<AccessRule>,
+	default_effect: RuleEffect,
+}
+
+impl RuleBasedPolicy {
+	/// Creates a policy evaluating `rules` in order, with `default_effect` deciding on requests
+	/// no rule matches.
+	pub fn new(rules: Vec<AccessRule>, default_effect: RuleEffect) -> Self {
+		Self { rules, default_effect }
+	}
+}
+
+#[async_trait]
+impl AccessPolicy for RuleBasedPolicy {
+	async fn check(&self, request: &AccessRequest<'_>) -> Result<(), VssError> {
+		let matched = self.rules.iter().enumerate().find(|(_, rule)| rule.matches(request));
+		let (effect, rule_name) = match matched {
+			Some((i, rule)) => {
+				(rule.effect, rule.name.clone().unwrap_or_else(|| format!("#{}", i + 1)))
+			},
+			None => (self.default_effect, "default".to_string()),
+		};
+		match effect {
+			RuleEffect::Allow => Ok(()),
+			RuleEffect::Deny => {
+				Err(VssError::AuthError(format!("Request denied by access rule {}.", rule_name)))
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::rules::{AccessRule, RuleBasedPolicy, RuleEffect};
+	use api::auth::{AccessPolicy, AccessRequest, AccessVerb, AuthResponse};
+	use api::error::VssError;
+	use std::collections::HashMap;
+
+	fn caller(claims: &[(&str, &str)]) -> AuthResponse {
+		AuthResponse {
+			user_token: "user".to_string(),
+			limit_profile: None,
+			claims: claims.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+			scopes: None,
+			store_ids: None,
+			auth_method: None,
+		}
+	}
+
+	fn rule(effect: RuleEffect) -> AccessRule {
+		AccessRule {
+			name: None,
+			effect,
+			verbs: vec![],
+			store_ids: vec![],
+			key_prefixes: vec![],
+			claims: HashMap::new(),
+			missing_claims: vec![],
+		}
+	}
+
+	async fn check_request(
+		policy: &RuleBasedPolicy, caller: &AuthResponse, keys: Vec<&str>, keys_are_prefixes: bool,
+		verb: AccessVerb,
+	) -> Result<(), VssError> {
+		let request = AccessRequest {
+			caller,
+			owner_user_token: "user",
+			store_id: "wallet",
+			keys,
+			keys_are_prefixes,
+			verb,
+		};
+		policy.check(&request).await
+	}
+
+	async fn check(
+		policy: &RuleBasedPolicy, caller: &AuthResponse, key: &str, verb: AccessVerb,
+	) -> Result<(), VssError> {
+		check_request(policy, caller, vec![key], false, verb).await
+	}
+
+	async fn check_prefix(
+		policy: &RuleBasedPolicy, caller: &AuthResponse, prefix: &str, verb: AccessVerb,
+	) -> Result<(), VssError> {
+		check_request(policy, caller, vec![prefix], true, verb).await
+	}
+
+	#[tokio::test]
+	async fn denies_writes_under_prefix_without_claim() {
+		let policy = RuleBasedPolicy::new(
+			vec![AccessRule {
+				name: Some("monitors".to_string()),
+				verbs: vec![AccessVerb::Write, AccessVerb::Delete],
+				key_prefixes: vec!["monitors/".to_string()],
+				missing_claims: vec!["monitor_writer".to_string()],
+				..rule(RuleEffect::Deny)
+			}],
+			RuleEffect::Allow,
+		);
+		let plain = caller(&[]);
+		let writer = caller(&[("monitor_writer", "true")]);
+
+		let result = check(&policy, &plain, "monitors/a", AccessVerb::Write).await;
+		assert!(matches!(result, Err(VssError::AuthError(msg)) if msg.contains("monitors")));
+		assert!(check(&policy, &plain, "monitors/a", AccessVerb::Read).await.is_ok());
+		assert!(check(&policy, &plain, "settings", AccessVerb::Write).await.is_ok());
+		assert!(check(&policy, &writer, "monitors/a", AccessVerb::Write).await.is_ok());
+	}
+
+	#[tokio::test]
+	async fn first_matching_rule_decides() {
+		let policy = RuleBasedPolicy::new(
+			vec![
+				AccessRule {
+					claims: HashMap::from([("role".to_string(), "auditor".to_string())]),
+					verbs: vec![AccessVerb::Read, AccessVerb::List],
+					..rule(RuleEffect::Allow)
+				},
+				AccessRule { key_prefixes: vec!["secrets/".to_string()], ..rule(RuleEffect::Deny) },
+			],
+			RuleEffect::Deny,
+		);
+		let auditor = caller(&[("role", "auditor")]);
+		let admin = caller(&[("role", "admin")]);
+
+		assert!(check(&policy, &auditor, "secrets/a", AccessVerb::Read).await.is_ok());
+		assert!(check(&policy, &auditor, "settings", AccessVerb::Write).await.is_err());
+		assert!(check(&policy, &admin, "secrets/a", AccessVerb::Read).await.is_err());
+		// Listing all keys would reveal those under the denied prefix.
+		assert!(check_prefix(&policy, &admin, "", AccessVerb::List).await.is_err());
+	}
+
+	#[tokio::test]
+	async fn allows_only_requests_with_all_keys_under_the_allowed_prefixes() {
+		let policy = RuleBasedPolicy::new(
+			vec![
+				AccessRule { key_prefixes: vec!["public/".to_string()], ..rule(RuleEffect::Allow) },
+				AccessRule {
+					key_prefixes: vec!["monitors/".to_string()],
+					..rule(RuleEffect::Deny)
+				},
+			],
+			RuleEffect::Allow,
+		);
+		let plain = caller(&[]);
+		let write = |keys| check_request(&policy, &plain, keys, false, AccessVerb::Write);
+
+		assert!(write(vec!["public/x"]).await.is_ok());
+		assert!(write(vec!["public/x", "settings"]).await.is_ok());
+		// A put writing keys under both prefixes must not pass by the first rule.
+		let result = write(vec!["public/x", "monitors/y"]).await;
+		assert!(matches!(result, Err(VssError::AuthError(msg)) if msg.contains("#2")));
+		// Listing all keys includes keys outside of the allowed prefix.
+		assert!(check_prefix(&policy, &plain, "", AccessVerb::List).await.is_err());
+		assert!(check_prefix(&policy, &plain, "public/", AccessVerb::List).await.is_ok());
+	}
+
+	#[tokio::test]
+	async fn denies_reads_of_prefixes_including_denied_keys() {
+		let policy = RuleBasedPolicy::new(
+			vec![AccessRule {
+				verbs: vec![AccessVerb::Read],
+				key_prefixes: vec!["monitors/".to_string()],
+				..rule(RuleEffect::Deny)
+			}],
+			RuleEffect::Allow,
+		);
+		let plain = caller(&[]);
+
+		// Listing objects reads the values under the listed prefix.
+		assert!(check_prefix(&policy, &plain, "", AccessVerb::Read).await.is_err());
+		assert!(check_prefix(&policy, &plain, "mon", AccessVerb::Read).await.is_err());
+		assert!(check_prefix(&policy, &plain, "monitors/a", AccessVerb::Read).await.is_err());
+		assert!(check_prefix(&policy, &plain, "settings/", AccessVerb::Read).await.is_ok());
+		// A key which is a prefix of the rule's prefix is only that key.
+		assert!(check(&policy, &plain, "mon", AccessVerb::Read).await.is_ok());
+	}
+}
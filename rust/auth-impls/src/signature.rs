@@ -18,7 +18,7 @@ use std::time::SystemTime;
 
 /// A 64-byte constant which, after appending the public key, is signed in order to prove knowledge
 /// of the corresponding private key.
-pub const SIGNING_CONSTANT: &'static [u8] =
+pub const SIGNING_CONSTANT: &[u8] =
 	b"VSS Signature Authorizer Signing Salt Constant..................";
 
 /// An authorizer that requires that every request come with a public key and proof of private key
@@ -76,14 +76,21 @@ impl Authorizer for SignatureValidatingAuthorizer {
 			.map_err(|_| VssError::AuthError("Authorization header has bad sig".to_string()))?;
 
 		let mut hash = bitcoin_hashes::Sha256::engine();
-		hash.input(&SIGNING_CONSTANT);
+		hash.input(SIGNING_CONSTANT);
 		hash.input(&pubkey_bytes);
 		hash.input(time_strng.as_bytes());
 		let signed_hash = secp256k1::Message::from_digest(hash.finalize().to_byte_array());
 		sig.verify(signed_hash, &pubkey)
 			.map_err(|_| VssError::AuthError("Signature was invalid".to_string()))?;
 
-		Ok(AuthResponse { user_token: pubkey_hex.to_owned() })
+		Ok(AuthResponse {
+			user_token: pubkey_hex.to_owned(),
+			limit_profile: None,
+			claims: HashMap::new(),
+			scopes: None,
+			store_ids: None,
+			auth_method: Some("signature"),
+		})
 	}
 }
 
@@ -92,7 +99,7 @@ mod tests {
 	use crate::signature::{SignatureValidatingAuthorizer, SIGNING_CONSTANT};
 	use api::auth::Authorizer;
 	use api::error::VssError;
-	use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+	use secp256k1::{Message, PublicKey, SecretKey};
 	use std::collections::HashMap;
 	use std::fmt::Write;
 	use std::time::SystemTime;
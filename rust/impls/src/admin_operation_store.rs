@@ -0,0 +1,50 @@
+use api::error::VssError;
+use async_trait::async_trait;
+
+/// A destructive operation requested through the admin API, held until a second admin approves it
+/// or its approval delay elapsed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingAdminOperation {
+	/// The token redeeming the operation, handed to the requesting admin.
+	pub approval_token: String,
+	/// The name of the operation, e.g. `wipe_store`.
+	pub operation: String,
+	/// The parameters of the operation, serialized by the server.
+	pub parameters: String,
+	/// The name of the admin who requested the operation.
+	pub requested_by: String,
+	/// The time the operation was requested, in seconds since the Unix epoch.
+	pub requested_at: u64,
+	/// The time after which the requesting admin may approve the operation themselves, in seconds
+	/// since the Unix epoch.
+	pub executable_after: u64,
+	/// The time after which the operation can no longer be approved, in seconds since the Unix
+	/// epoch.
+	pub expires_at: u64,
+}
+
+/// Persists [`PendingAdminOperation`]s, so that they survive restarts and can be approved through
+/// any server instance sharing the backend.
+#[async_trait]
+pub trait AdminOperationStore: Send + Sync {
+	/// Stores `operation`, removing the operations which expired by the time it was requested.
+	async fn add_pending_operation(
+		&self, operation: &PendingAdminOperation,
+	) -> Result<(), VssError>;
+
+	/// Returns the operation with the given approval token, or `None` if there is none or it
+	/// expired at `now`, in seconds since the Unix epoch.
+	async fn get_pending_operation(
+		&self, approval_token: &str, now: u64,
+	) -> Result<Option<PendingAdminOperation>, VssError>;
+
+	/// Removes the operation with the given approval token. Returns whether it was pending, in
+	/// which case the caller is the only one to execute it.
+	async fn remove_pending_operation(&self, approval_token: &str) -> Result<bool, VssError>;
+
+	/// Returns the operations not expired at `now`, in seconds since the Unix epoch, ordered by the
+	/// time they were requested.
+	async fn list_pending_operations(
+		&self, now: u64,
+	) -> Result<Vec<PendingAdminOperation>, VssError>;
+}
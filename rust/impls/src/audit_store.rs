@@ -0,0 +1,48 @@
+use api::error::VssError;
+use async_trait::async_trait;
+
+/// A key written or deleted by an audited operation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditedKey {
+	/// The key of the object.
+	pub key: String,
+	/// The version given for the key in the request, i.e. the version the operation was
+	/// conditional on, or `-1` if it was not.
+	pub version: i64,
+	/// Whether the operation deleted the object rather than writing it.
+	pub deleted: bool,
+}
+
+/// A mutating operation recorded in the audit log.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditRecord {
+	/// The time the operation completed at, in seconds since the Unix epoch.
+	pub recorded_at: u64,
+	/// The user owning the store the operation was performed on.
+	pub user_token: String,
+	/// The user performing the operation, if they are not the store owner, e.g. a collaborator.
+	pub caller_user_token: Option<String>,
+	/// The store the operation was performed on.
+	pub store_id: String,
+	/// The name of the operation, e.g. `putObjects`.
+	pub operation: String,
+	/// The keys written or deleted by the operation.
+	pub keys: Vec<AuditedKey>,
+	/// The IP address the request was made from.
+	pub source_ip: Option<String>,
+	/// The name of the authentication method the caller's credentials were verified by, if known.
+	pub auth_method: Option<String>,
+	/// Whether the operation succeeded. Failed operations are recorded too, as attempts.
+	pub succeeded: bool,
+}
+
+/// An append-only log of the mutating operations performed on stores, kept for compliance audits.
+#[async_trait]
+pub trait AuditStore: Send + Sync {
+	/// Appends `records` to the log, in a single transaction.
+	async fn append_audit_records(&self, records: &[AuditRecord]) -> Result<(), VssError>;
+
+	/// Deletes at most `limit` records which were recorded before `recorded_before`, in seconds
+	/// since the Unix epoch, oldest first. Returns the number of deleted records.
+	async fn purge_audit_records(&self, recorded_before: u64, limit: i64) -> Result<u64, VssError>;
+}
@@ -0,0 +1,28 @@
+use api::error::VssError;
+use async_trait::async_trait;
+
+/// The state of the continuous archiving a backend's database is backed up by, such as
+/// PostgreSQL's WAL archiving, which point-in-time recovery depends on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BackupStatus {
+	/// Whether the database is configured to archive its write-ahead log.
+	pub archiving_enabled: bool,
+	/// The number of WAL segments archived since the archiver's statistics were reset.
+	pub archived_count: u64,
+	/// The time the last WAL segment was archived, in seconds since the UNIX epoch.
+	pub last_archived_at: Option<u64>,
+	/// The number of failed attempts to archive a WAL segment.
+	pub failed_count: u64,
+	/// The time archiving last failed, in seconds since the UNIX epoch.
+	pub last_failed_at: Option<u64>,
+	/// The database's current time, in seconds since the UNIX epoch, which the above times are to
+	/// be compared with.
+	pub checked_at: u64,
+}
+
+/// Reports whether a backend's database is continuously backed up.
+#[async_trait]
+pub trait BackupStatusProvider: Send + Sync {
+	/// Queries the current state of archiving from the database.
+	async fn backup_status(&self) -> Result<BackupStatus, VssError>;
+}
@@ -0,0 +1,33 @@
+use api::error::VssError;
+use async_trait::async_trait;
+use tokio::sync::mpsc;
+
+/// A change of a stored object, as committed to a backend's database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjectChange {
+	/// The user the changed object belongs to.
+	pub user_token: String,
+	/// The store of the changed object.
+	pub store_id: String,
+	/// The key of the changed object.
+	pub key: String,
+	/// The version of the object after the change, or `None` if it was deleted.
+	pub version: Option<i64>,
+}
+
+/// Publishes the changes of stored objects as they are committed, by whichever server instance
+/// committed them, for change feeds spanning all instances of a deployment.
+///
+/// This is the storage-level counterpart of observing a single instance's writes with a
+/// [`KvStoreObserver`]. Changes committed while no subscription is open are not kept.
+///
+/// [`KvStoreObserver`]: api::observer::KvStoreObserver
+#[async_trait]
+pub trait ChangeFeed: Send + Sync {
+	/// Subscribes to the changes committed from now on.
+	///
+	/// The returned receiver yields the changes of each transaction once it is committed, and is
+	/// closed if the subscription is lost, e.g. because its database connection failed, in which
+	/// case changes may have been missed and the caller has to subscribe again.
+	async fn subscribe(&self) -> Result<mpsc::Receiver<ObjectChange>, VssError>;
+}
@@ -0,0 +1,231 @@
+use api::error::VssError;
+use std::collections::HashMap;
+
+/// Marks a value as a delta produced by [`encode_delta`].
+const DELTA_MAGIC: &[u8] = b"VSSDELTA";
+const DELTA_FORMAT_VERSION: u8 = 1;
+
+/// The length of the blocks of the base matched against the target. Shorter blocks find more
+/// matches at the cost of indexing more of them.
+const BLOCK_SIZE: usize = 16;
+
+const COPY_OP: u8 = 0;
+const INSERT_OP: u8 = 1;
+
+/// Encodes `target` as a delta against `base`, from which [`apply_delta`] reconstructs it.
+///
+/// Meant for storing old versions of an object as reverse deltas against its newest version:
+/// values changing in small parts between versions, like growing channel monitors, encode to a
+/// small fraction of their size. Unrelated values encode to slightly more than their size, so
+/// callers should compare the delta's length against the target's and keep the smaller.
+pub fn encode_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+	// Index the base's blocks at block-aligned offsets, keeping the first occurrence of each.
+	let mut blocks: HashMap<&[u8], usize> = HashMap::new();
+	for (i, block) in base.chunks_exact(BLOCK_SIZE).enumerate() {
+		blocks.entry(block).or_insert(i * BLOCK_SIZE);
+	}
+
+	let mut delta = DELTA_MAGIC.to_vec();
+	delta.push(DELTA_FORMAT_VERSION);
+	write_varint(&mut delta, base.len() as u64);
+	write_varint(&mut delta, target.len() as u64);
+
+	let mut literal_start = 0;
+	let mut position = 0;
+	while position + BLOCK_SIZE <= target.len() {
+		let base_offset = match blocks.get(&target[position..position + BLOCK_SIZE]) {
+			Some(base_offset) => *base_offset,
+			None => {
+				position += 1;
+				continue;
+			},
+		};
+		// Extend the match backwards into the pending literal and forwards as far as possible.
+		let mut start = position;
+		let mut base_start = base_offset;
+		while start > literal_start && base_start > 0 && target[start - 1] == base[base_start - 1] {
+			start -= 1;
+			base_start -= 1;
+		}
+		let mut end = position + BLOCK_SIZE;
+		let mut base_end = base_offset + BLOCK_SIZE;
+		while end < target.len() && base_end < base.len() && target[end] == base[base_end] {
+			end += 1;
+			base_end += 1;
+		}
+
+		write_insert(&mut delta, &target[literal_start..start]);
+		delta.push(COPY_OP);
+		write_varint(&mut delta, base_start as u64);
+		write_varint(&mut delta, (end - start) as u64);
+		position = end;
+		literal_start = end;
+	}
+	write_insert(&mut delta, &target[literal_start..]);
+	delta
+}
+
+/// Reconstructs the value a delta produced by [`encode_delta`] was encoded from, given the same
+/// base.
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, VssError> {
+	let mut reader = DeltaReader {
+		remaining: delta
+			.strip_prefix(DELTA_MAGIC)
+			.and_then(|delta| delta.strip_prefix(&[DELTA_FORMAT_VERSION]))
+			.ok_or_else(malformed_delta_error)?,
+	};
+	if reader.read_varint()? != base.len() as u64 {
+		return Err(VssError::InternalServerError(
+			"Delta was encoded against a different base".to_string(),
+		));
+	}
+	let target_len = reader.read_varint()?;
+
+	let mut target = Vec::with_capacity(target_len.min(delta.len() as u64 * 64) as usize);
+	while let Some(op) = reader.read_byte() {
+		match op {
+			COPY_OP => {
+				let offset = reader.read_varint()?;
+				let len = reader.read_varint()?;
+				let copied = offset
+					.checked_add(len)
+					.and_then(|end| base.get(offset as usize..end as usize))
+					.ok_or_else(malformed_delta_error)?;
+				target.extend_from_slice(copied);
+			},
+			INSERT_OP => {
+				let len = reader.read_varint()?;
+				target.extend_from_slice(reader.read_bytes(len)?);
+			},
+			_ => return Err(malformed_delta_error()),
+		}
+		if target.len() as u64 > target_len {
+			return Err(malformed_delta_error());
+		}
+	}
+	if target.len() as u64 != target_len {
+		return Err(malformed_delta_error());
+	}
+	Ok(target)
+}
+
+/// Whether `value` is a delta produced by [`encode_delta`].
+pub fn is_delta(value: &[u8]) -> bool {
+	value.strip_prefix(DELTA_MAGIC).is_some_and(|rest| rest.first() == Some(&DELTA_FORMAT_VERSION))
+}
+
+fn write_insert(delta: &mut Vec<u8>, literal: &[u8]) {
+	if literal.is_empty() {
+		return;
+	}
+	delta.push(INSERT_OP);
+	write_varint(delta, literal.len() as u64);
+	delta.extend_from_slice(literal);
+}
+
+/// Writes `value` as LEB128 variable-length integer.
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+	while value >= 0x80 {
+		out.push((value as u8) | 0x80);
+		value >>= 7;
+	}
+	out.push(value as u8);
+}
+
+struct DeltaReader<'a> {
+	remaining: &'a [u8],
+}
+
+impl<'a> DeltaReader<'a> {
+	fn read_byte(&mut self) -> Option<u8> {
+		let (byte, remaining) = self.remaining.split_first()?;
+		self.remaining = remaining;
+		Some(*byte)
+	}
+
+	fn read_bytes(&mut self, len: u64) -> Result<&'a [u8], VssError> {
+		if len > self.remaining.len() as u64 {
+			return Err(malformed_delta_error());
+		}
+		let (bytes, remaining) = self.remaining.split_at(len as usize);
+		self.remaining = remaining;
+		Ok(bytes)
+	}
+
+	fn read_varint(&mut self) -> Result<u64, VssError> {
+		let mut value = 0u64;
+		for shift in (0..64).step_by(7) {
+			let byte = self.read_byte().ok_or_else(malformed_delta_error)?;
+			value |= u64::from(byte & 0x7f) << shift;
+			if byte & 0x80 == 0 {
+				return Ok(value);
+			}
+		}
+		Err(malformed_delta_error())
+	}
+}
+
+fn malformed_delta_error() -> VssError {
+	VssError::InternalServerError("Malformed delta".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{apply_delta, encode_delta, is_delta};
+
+	fn pseudo_random_bytes(len: usize, seed: u64) -> Vec<u8> {
+		let mut state = seed;
+		(0..len)
+			.map(|_| {
+				state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+				(state >> 56) as u8
+			})
+			.collect()
+	}
+
+	#[test]
+	fn reconstructs_target_from_base() {
+		let newest = pseudo_random_bytes(1 << 20, 1);
+		let mut cases = vec![
+			(newest.clone(), Vec::new()),
+			(Vec::new(), newest.clone()),
+			(newest.clone(), newest.clone()),
+			(newest.clone(), pseudo_random_bytes(1000, 2)),
+			(newest.clone(), newest[..newest.len() - 100].to_vec()),
+			(newest.clone(), newest[7..].to_vec()),
+		];
+		// An old version differing from the newest one in a few edited and inserted bytes.
+		let mut edited = newest.clone();
+		edited[1000] ^= 1;
+		edited.splice(5000..5000, b"inserted".iter().copied());
+		edited.drain(300_000..300_050);
+		cases.push((newest.clone(), edited));
+
+		for (base, target) in cases {
+			let delta = encode_delta(&base, &target);
+			assert!(is_delta(&delta));
+			assert_eq!(apply_delta(&base, &delta).unwrap(), target);
+		}
+	}
+
+	#[test]
+	fn encodes_similar_versions_compactly() {
+		let newest = pseudo_random_bytes(4 << 20, 3);
+		let mut old = newest[..newest.len() - 4096].to_vec();
+		old[100_000] ^= 0xff;
+		let delta = encode_delta(&newest, &old);
+		assert!(delta.len() < 100, "delta of {} bytes", delta.len());
+	}
+
+	#[test]
+	fn rejects_malformed_deltas() {
+		let base = pseudo_random_bytes(1000, 4);
+		let target = pseudo_random_bytes(500, 5);
+		let delta = encode_delta(&base, &target);
+
+		assert!(apply_delta(&base[1..], &delta).is_err());
+		assert!(apply_delta(&base, &delta[..delta.len() - 1]).is_err());
+		assert!(apply_delta(&base, &target).is_err());
+		assert!(!is_delta(&target));
+	}
+}
@@ -0,0 +1,32 @@
+use api::error::VssError;
+use async_trait::async_trait;
+
+/// A device known to have accessed a store.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KnownDevice {
+	/// The store the device accessed.
+	pub store_id: String,
+	/// The opaque fingerprint identifying the device.
+	pub device_fingerprint: Vec<u8>,
+	/// The time the device first accessed the store, in seconds since the UNIX epoch.
+	pub first_seen_at: u64,
+}
+
+/// Remembers which devices accessed a store, allowing accesses from new devices to be detected.
+///
+/// Devices are identified by an opaque fingerprint derived from request metadata by the caller.
+#[async_trait]
+pub trait DeviceRegistry: Send + Sync {
+	/// Records that the device with the given fingerprint accessed the store.
+	///
+	/// Returns `true` if the device was not known before while at least one other device was, i.e.
+	/// if the access should be considered to come from a new device. The very first device to
+	/// access a store is recorded silently.
+	async fn record_device(
+		&self, user_token: &str, store_id: &str, device_fingerprint: &[u8],
+	) -> Result<bool, VssError>;
+
+	/// Returns the devices known to have accessed any store of the given user token, ordered by
+	/// store id and the time they were first seen.
+	async fn list_devices(&self, user_token: &str) -> Result<Vec<KnownDevice>, VssError>;
+}
@@ -0,0 +1,687 @@
+use api::error::VssError;
+use api::kv_store::{KvStore, GLOBAL_VERSION_KEY, INITIAL_RECORD_VERSION};
+use api::types::{
+	DeleteObjectRequest, DeleteObjectResponse, GetObjectRequest, GetObjectResponse, KeyValue,
+	ListKeyVersionsRequest, ListKeyVersionsResponse, PutObjectRequest, PutObjectResponse,
+};
+use async_trait::async_trait;
+use aws_sdk_dynamodb::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
+use aws_sdk_dynamodb::operation::delete_item::DeleteItemError;
+use aws_sdk_dynamodb::operation::transact_write_items::TransactWriteItemsError;
+use aws_sdk_dynamodb::primitives::Blob;
+use aws_sdk_dynamodb::types::{
+	AttributeDefinition, AttributeValue, BillingMode, Delete, DeleteRequest, KeySchemaElement,
+	KeyType, Put, ScalarAttributeType, TableStatus, TransactWriteItem, WriteRequest,
+};
+use aws_sdk_dynamodb::Client;
+use bytes::Bytes;
+use std::cmp::min;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::instrument;
+
+use crate::health_check::HealthCheck;
+use crate::postgres_store::LIST_KEY_VERSIONS_MAX_PAGE_SIZE;
+
+/// The partition key, identifying a store of a user.
+const PARTITION_KEY_ATTRIBUTE: &str = "pk";
+/// The sort key, holding the object key.
+const SORT_KEY_ATTRIBUTE: &str = "sk";
+const USER_TOKEN_ATTRIBUTE: &str = "user_token";
+const VALUE_ATTRIBUTE: &str = "value";
+const VERSION_ATTRIBUTE: &str = "version";
+
+/// The maximum number of items, including the global version, written or deleted by a single
+/// [`PutObjectRequest`], as bounded by DynamoDB transactions.
+pub const MAX_TRANSACTION_ITEM_COUNT: usize = 100;
+
+/// The maximum size of a single value.
+///
+/// DynamoDB items are limited to 400 KB, including attribute names and keys.
+pub const MAX_VALUE_SIZE: usize = 390 * 1024;
+
+/// The number of deletes DynamoDB accepts in a single batch.
+const BATCH_DELETE_SIZE: usize = 25;
+
+const TABLE_ACTIVE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// An [Amazon DynamoDB](https://aws.amazon.com/dynamodb/) based [`KvStore`] implementation.
+///
+/// All objects live in a single table, partitioned by user and store, with the object key as sort
+/// key. Conditional writes use condition expressions on the stored version, and puts spanning
+/// several objects are executed as a single transaction, preserving the versioning semantics of
+/// the PostgreSQL backend.
+///
+/// DynamoDB limits values to [`MAX_VALUE_SIZE`] and puts to [`MAX_TRANSACTION_ITEM_COUNT`] items,
+/// so this backend is only suitable for wallets storing small objects.
+pub struct DynamoDbBackend {
+	client: Client,
+	table_name: String,
+}
+
+impl DynamoDbBackend {
+	/// Constructs a [`DynamoDbBackend`] storing objects in the given table, which is expected to
+	/// have the key schema set up by [`DynamoDbBackend::create_table`].
+	pub fn new(client: Client, table_name: impl Into<String>) -> Self {
+		Self { client, table_name: table_name.into() }
+	}
+
+	/// Creates the table unless it exists already, and waits until it is active.
+	pub async fn create_table(&self) -> Result<(), VssError> {
+		let key_schema =
+			[(PARTITION_KEY_ATTRIBUTE, KeyType::Hash), (SORT_KEY_ATTRIBUTE, KeyType::Range)];
+		let mut request = self
+			.client
+			.create_table()
+			.table_name(&self.table_name)
+			.billing_mode(BillingMode::PayPerRequest);
+		for (name, key_type) in key_schema {
+			request = request
+				.key_schema(
+					KeySchemaElement::builder()
+						.attribute_name(name)
+						.key_type(key_type)
+						.build()
+						.map_err(build_error)?,
+				)
+				.attribute_definitions(
+					AttributeDefinition::builder()
+						.attribute_name(name)
+						.attribute_type(ScalarAttributeType::S)
+						.build()
+						.map_err(build_error)?,
+				);
+		}
+		match request.send().await {
+			Ok(_) => {},
+			Err(e) if e.code() == Some("ResourceInUseException") => {},
+			Err(e) => return Err(to_vss_error("CreateTable", e)),
+		}
+
+		loop {
+			let description = self
+				.client
+				.describe_table()
+				.table_name(&self.table_name)
+				.send()
+				.await
+				.map_err(|e| to_vss_error("DescribeTable", e))?;
+			if description.table().and_then(|table| table.table_status())
+				== Some(&TableStatus::Active)
+			{
+				return Ok(());
+			}
+			tokio::time::sleep(TABLE_ACTIVE_POLL_INTERVAL).await;
+		}
+	}
+
+	fn build_put(
+		&self, user_token: &str, partition_key: &str, kv: KeyValue,
+	) -> Result<TransactWriteItem, VssError> {
+		// Like in the PostgreSQL backend, a version of -1 writes unconditionally, 0 creates the
+		// object if it does not exist, and any other version must match the stored one.
+		let new_version = if kv.version <= 0 {
+			INITIAL_RECORD_VERSION as i64
+		} else {
+			kv.version.saturating_add(1)
+		};
+		let mut item = object_key(partition_key, &kv.key);
+		item.insert(USER_TOKEN_ATTRIBUTE.to_string(), AttributeValue::S(user_token.to_string()));
+		item.insert(VALUE_ATTRIBUTE.to_string(), AttributeValue::B(Blob::new(kv.value.to_vec())));
+		item.insert(VERSION_ATTRIBUTE.to_string(), AttributeValue::N(new_version.to_string()));
+		let put = Put::builder().table_name(&self.table_name).set_item(Some(item));
+		let put = match kv.version {
+			-1 => put,
+			0 => put.condition_expression(format!("attribute_not_exists({})", SORT_KEY_ATTRIBUTE)),
+			version => put
+				.condition_expression("#version = :version")
+				.expression_attribute_names("#version", VERSION_ATTRIBUTE)
+				.expression_attribute_values(":version", AttributeValue::N(version.to_string())),
+		};
+		Ok(TransactWriteItem::builder().put(put.build().map_err(build_error)?).build())
+	}
+
+	fn build_delete(
+		&self, partition_key: &str, kv: &KeyValue,
+	) -> Result<TransactWriteItem, VssError> {
+		let delete = Delete::builder()
+			.table_name(&self.table_name)
+			.set_key(Some(object_key(partition_key, &kv.key)));
+		// Deleting a missing object conflicts within a put, as in the PostgreSQL backend.
+		let delete = match kv.version {
+			-1 => delete.condition_expression(format!("attribute_exists({})", SORT_KEY_ATTRIBUTE)),
+			version => delete
+				.condition_expression("#version = :version")
+				.expression_attribute_names("#version", VERSION_ATTRIBUTE)
+				.expression_attribute_values(":version", AttributeValue::N(version.to_string())),
+		};
+		Ok(TransactWriteItem::builder().delete(delete.build().map_err(build_error)?).build())
+	}
+
+	/// Deletes the given items in batches, returning the number of deleted items.
+	async fn batch_delete(
+		&self, keys: Vec<HashMap<String, AttributeValue>>,
+	) -> Result<u64, VssError> {
+		let count = keys.len() as u64;
+		for chunk in keys.chunks(BATCH_DELETE_SIZE) {
+			let mut requests = chunk
+				.iter()
+				.map(|key| {
+					let delete = DeleteRequest::builder()
+						.set_key(Some(key.clone()))
+						.build()
+						.map_err(build_error)?;
+					Ok(WriteRequest::builder().delete_request(delete).build())
+				})
+				.collect::<Result<Vec<_>, VssError>>()?;
+			while !requests.is_empty() {
+				let output = self
+					.client
+					.batch_write_item()
+					.request_items(&self.table_name, requests)
+					.send()
+					.await
+					.map_err(|e| to_vss_error("BatchWriteItem", e))?;
+				// Throttled deletes are returned unprocessed and retried.
+				requests = output
+					.unprocessed_items
+					.and_then(|mut unprocessed| unprocessed.remove(&self.table_name))
+					.unwrap_or_default();
+			}
+		}
+		Ok(count)
+	}
+}
+
+#[async_trait]
+impl KvStore for DynamoDbBackend {
+	#[instrument(
+		name = "dynamodb.get",
+		skip(self, user_token, request),
+		fields(
+			db.system = "dynamodb",
+			db.operation = "GetItem",
+			span.type = "db",
+			store_id = %request.store_id,
+			key = %request.key
+		)
+	)]
+	async fn get(
+		&self, user_token: String, request: GetObjectRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		let partition_key = partition_key(&user_token, &request.store_id);
+		let output = self
+			.client
+			.get_item()
+			.table_name(&self.table_name)
+			.set_key(Some(object_key(&partition_key, &request.key)))
+			.consistent_read(true)
+			.send()
+			.await
+			.map_err(|e| to_vss_error("GetItem", e))?;
+
+		let key_value = match output.item {
+			Some(item) => KeyValue {
+				key: request.key,
+				value: match item.get(VALUE_ATTRIBUTE) {
+					Some(AttributeValue::B(value)) => Bytes::from(value.clone().into_inner()),
+					_ => Bytes::new(),
+				},
+				version: parse_version(&item)?,
+			},
+			None if request.key == GLOBAL_VERSION_KEY => {
+				KeyValue { key: GLOBAL_VERSION_KEY.to_string(), value: Bytes::new(), version: 0 }
+			},
+			None => return Err(VssError::NoSuchKeyError("Requested key not found.".to_string())),
+		};
+		Ok(GetObjectResponse { value: Some(key_value) })
+	}
+
+	#[instrument(
+		name = "dynamodb.put",
+		skip(self, user_token, request),
+		fields(
+			db.system = "dynamodb",
+			db.operation = "TransactWriteItems",
+			span.type = "db",
+			store_id = %request.store_id,
+			transaction_items_count = %request.transaction_items.len(),
+			delete_items_count = %request.delete_items.len()
+		)
+	)]
+	async fn put(
+		&self, user_token: String, request: PutObjectRequest,
+	) -> Result<PutObjectResponse, VssError> {
+		let item_count = request.transaction_items.len()
+			+ request.delete_items.len()
+			+ usize::from(request.global_version.is_some());
+		if item_count > MAX_TRANSACTION_ITEM_COUNT {
+			return Err(VssError::InvalidRequestError(format!(
+				"Number of write items per request should be less than equal to {}",
+				MAX_TRANSACTION_ITEM_COUNT
+			)));
+		}
+		if request.transaction_items.iter().any(|kv| kv.value.len() > MAX_VALUE_SIZE) {
+			return Err(VssError::InvalidRequestError(format!(
+				"Values must not exceed {} bytes.",
+				MAX_VALUE_SIZE
+			)));
+		}
+
+		let partition_key = partition_key(&user_token, &request.store_id);
+		let mut transact_items = Vec::with_capacity(item_count);
+		for kv in request.transaction_items {
+			transact_items.push(self.build_put(&user_token, &partition_key, kv)?);
+		}
+		if let Some(global_version) = request.global_version {
+			let kv = KeyValue {
+				key: GLOBAL_VERSION_KEY.to_string(),
+				value: Bytes::new(),
+				version: global_version,
+			};
+			transact_items.push(self.build_put(&user_token, &partition_key, kv)?);
+		}
+		for kv in &request.delete_items {
+			transact_items.push(self.build_delete(&partition_key, kv)?);
+		}
+		if transact_items.is_empty() {
+			return Ok(PutObjectResponse {});
+		}
+
+		match self
+			.client
+			.transact_write_items()
+			.set_transact_items(Some(transact_items))
+			.send()
+			.await
+		{
+			Ok(_) => Ok(PutObjectResponse {}),
+			Err(e) => {
+				if let Some(TransactWriteItemsError::TransactionCanceledException(canceled)) =
+					e.as_service_error()
+				{
+					let conflicted = canceled.cancellation_reasons().iter().any(|reason| {
+						matches!(
+							reason.code(),
+							Some("ConditionalCheckFailed" | "TransactionConflict")
+						)
+					});
+					if conflicted {
+						tracing::warn!("Transaction canceled due to conflict");
+						return Err(VssError::ConflictError(
+							"Transaction could not be completed due to a possible conflict"
+								.to_string(),
+						));
+					}
+				}
+				Err(to_vss_error("TransactWriteItems", e))
+			},
+		}
+	}
+
+	#[instrument(
+		name = "dynamodb.delete",
+		skip(self, user_token, request),
+		fields(
+			db.system = "dynamodb",
+			db.operation = "DeleteItem",
+			span.type = "db",
+			store_id = %request.store_id
+		)
+	)]
+	async fn delete(
+		&self, user_token: String, request: DeleteObjectRequest,
+	) -> Result<DeleteObjectResponse, VssError> {
+		let key_value = request.key_value.ok_or_else(|| {
+			VssError::InvalidRequestError("key_value missing in DeleteObjectRequest".to_string())
+		})?;
+		let partition_key = partition_key(&user_token, &request.store_id);
+		let delete = self
+			.client
+			.delete_item()
+			.table_name(&self.table_name)
+			.set_key(Some(object_key(&partition_key, &key_value.key)));
+		let delete = if key_value.version == -1 {
+			delete
+		} else {
+			delete
+				.condition_expression("#version = :version")
+				.expression_attribute_names("#version", VERSION_ATTRIBUTE)
+				.expression_attribute_values(
+					":version",
+					AttributeValue::N(key_value.version.to_string()),
+				)
+		};
+		match delete.send().await {
+			Ok(_) => Ok(DeleteObjectResponse {}),
+			// Deleting an object at a stale version is a no-op, as in the PostgreSQL backend.
+			Err(e)
+				if matches!(
+					e.as_service_error(),
+					Some(DeleteItemError::ConditionalCheckFailedException(_))
+				) =>
+			{
+				tracing::debug!("Object not deleted due to version mismatch");
+				Ok(DeleteObjectResponse {})
+			},
+			Err(e) => Err(to_vss_error("DeleteItem", e)),
+		}
+	}
+
+	#[instrument(
+		name = "dynamodb.list_key_versions",
+		skip(self, user_token, request),
+		fields(
+			db.system = "dynamodb",
+			db.operation = "Query",
+			span.type = "db",
+			store_id = %request.store_id,
+			key_prefix = ?request.key_prefix,
+			page_size = ?request.page_size
+		)
+	)]
+	async fn list_key_versions(
+		&self, user_token: String, request: ListKeyVersionsRequest,
+	) -> Result<ListKeyVersionsResponse, VssError> {
+		// Only fetch global_version for the first page, before fetching any key versions, to
+		// ensure all listed key versions were stored at global_version or later.
+		let mut global_version = None;
+		if request.page_token.is_none() {
+			let get_global_version_request = GetObjectRequest {
+				store_id: request.store_id.clone(),
+				key: GLOBAL_VERSION_KEY.to_string(),
+			};
+			let get_response = self.get(user_token.clone(), get_global_version_request).await?;
+			// unwrap safety: get request always return a value when global_version is queried.
+			global_version = Some(get_response.value.unwrap().version);
+		}
+
+		let limit =
+			min(request.page_size.unwrap_or(i32::MAX), LIST_KEY_VERSIONS_MAX_PAGE_SIZE).max(0);
+		let partition_key = partition_key(&user_token, &request.store_id);
+		let mut exclusive_start_key = request
+			.page_token
+			.filter(|page_token| !page_token.is_empty())
+			.map(|page_token| object_key(&partition_key, &page_token));
+		let mut evaluated = 0;
+		let mut key_versions = Vec::new();
+		while evaluated < limit {
+			let query = self
+				.client
+				.query()
+				.table_name(&self.table_name)
+				.consistent_read(true)
+				.projection_expression("#sk, #version")
+				.expression_attribute_names("#sk", SORT_KEY_ATTRIBUTE)
+				.expression_attribute_names("#version", VERSION_ATTRIBUTE)
+				.expression_attribute_values(":pk", AttributeValue::S(partition_key.clone()))
+				.limit(limit - evaluated)
+				.set_exclusive_start_key(exclusive_start_key);
+			let query = match request.key_prefix.as_deref().filter(|prefix| !prefix.is_empty()) {
+				Some(key_prefix) => query
+					.key_condition_expression(format!(
+						"{} = :pk AND begins_with(#sk, :prefix)",
+						PARTITION_KEY_ATTRIBUTE
+					))
+					.expression_attribute_values(
+						":prefix",
+						AttributeValue::S(key_prefix.to_string()),
+					),
+				None => {
+					query.key_condition_expression(format!("{} = :pk", PARTITION_KEY_ATTRIBUTE))
+				},
+			};
+			let output = query.send().await.map_err(|e| to_vss_error("Query", e))?;
+			let items = output.items.unwrap_or_default();
+			evaluated += items.len() as i32;
+			for item in items {
+				let key = match item.get(SORT_KEY_ATTRIBUTE) {
+					Some(AttributeValue::S(key)) => key.clone(),
+					_ => return Err(malformed_item_error()),
+				};
+				if key != GLOBAL_VERSION_KEY {
+					key_versions.push(KeyValue {
+						key,
+						value: Bytes::new(),
+						version: parse_version(&item)?,
+					});
+				}
+			}
+			exclusive_start_key = output.last_evaluated_key;
+			if exclusive_start_key.is_none() {
+				break;
+			}
+		}
+
+		let next_page_token = match key_versions.last() {
+			Some(kv) => Some(kv.key.clone()),
+			None => Some("".to_string()),
+		};
+		Ok(ListKeyVersionsResponse { key_versions, next_page_token, global_version })
+	}
+
+	#[instrument(
+		name = "dynamodb.delete_store",
+		skip(self, user_token),
+		fields(db.system = "dynamodb", db.operation = "BatchWriteItem", span.type = "db", store_id = %store_id)
+	)]
+	async fn delete_store(&self, user_token: String, store_id: String) -> Result<u64, VssError> {
+		let partition_key = partition_key(&user_token, &store_id);
+		let mut keys = Vec::new();
+		let mut exclusive_start_key = None;
+		loop {
+			let output = self
+				.client
+				.query()
+				.table_name(&self.table_name)
+				.consistent_read(true)
+				.key_condition_expression(format!("{} = :pk", PARTITION_KEY_ATTRIBUTE))
+				.expression_attribute_values(":pk", AttributeValue::S(partition_key.clone()))
+				.projection_expression(format!(
+					"{}, {}",
+					PARTITION_KEY_ATTRIBUTE, SORT_KEY_ATTRIBUTE
+				))
+				.set_exclusive_start_key(exclusive_start_key)
+				.send()
+				.await
+				.map_err(|e| to_vss_error("Query", e))?;
+			keys.extend(output.items.unwrap_or_default());
+			exclusive_start_key = output.last_evaluated_key;
+			if exclusive_start_key.is_none() {
+				break;
+			}
+		}
+		let num_items = self.batch_delete(keys).await?;
+		tracing::debug!(items_deleted = num_items, "Store deleted");
+		Ok(num_items)
+	}
+
+	/// Deletes all objects of the user.
+	///
+	/// As objects are partitioned by store, this scans the whole table.
+	#[instrument(
+		name = "dynamodb.delete_user",
+		skip(self, user_token),
+		fields(db.system = "dynamodb", db.operation = "Scan", span.type = "db")
+	)]
+	async fn delete_user(&self, user_token: String) -> Result<u64, VssError> {
+		let mut keys = Vec::new();
+		let mut exclusive_start_key = None;
+		loop {
+			let output = self
+				.client
+				.scan()
+				.table_name(&self.table_name)
+				.consistent_read(true)
+				.filter_expression(format!("{} = :user_token", USER_TOKEN_ATTRIBUTE))
+				.expression_attribute_values(":user_token", AttributeValue::S(user_token.clone()))
+				.projection_expression(format!(
+					"{}, {}",
+					PARTITION_KEY_ATTRIBUTE, SORT_KEY_ATTRIBUTE
+				))
+				.set_exclusive_start_key(exclusive_start_key)
+				.send()
+				.await
+				.map_err(|e| to_vss_error("Scan", e))?;
+			keys.extend(output.items.unwrap_or_default());
+			exclusive_start_key = output.last_evaluated_key;
+			if exclusive_start_key.is_none() {
+				break;
+			}
+		}
+		let num_items = self.batch_delete(keys).await?;
+		tracing::debug!(items_deleted = num_items, "User deleted");
+		Ok(num_items)
+	}
+}
+
+#[async_trait]
+impl HealthCheck for DynamoDbBackend {
+	async fn check_health(&self) -> Result<(), VssError> {
+		self.client
+			.describe_table()
+			.table_name(&self.table_name)
+			.send()
+			.await
+			.map_err(|e| to_vss_error("DescribeTable", e))?;
+		Ok(())
+	}
+}
+
+/// Identifies a store of a user. The user token is length-prefixed, so that no two pairs of user
+/// token and store id map to the same partition.
+fn partition_key(user_token: &str, store_id: &str) -> String {
+	format!("{}:{}{}", user_token.len(), user_token, store_id)
+}
+
+fn object_key(partition_key: &str, key: &str) -> HashMap<String, AttributeValue> {
+	HashMap::from([
+		(PARTITION_KEY_ATTRIBUTE.to_string(), AttributeValue::S(partition_key.to_string())),
+		(SORT_KEY_ATTRIBUTE.to_string(), AttributeValue::S(key.to_string())),
+	])
+}
+
+fn parse_version(item: &HashMap<String, AttributeValue>) -> Result<i64, VssError> {
+	match item.get(VERSION_ATTRIBUTE) {
+		Some(AttributeValue::N(version)) => version.parse().map_err(|_| malformed_item_error()),
+		_ => Err(malformed_item_error()),
+	}
+}
+
+fn malformed_item_error() -> VssError {
+	VssError::InternalServerError("Malformed item in DynamoDB table".to_string())
+}
+
+fn build_error(e: aws_sdk_dynamodb::error::BuildError) -> VssError {
+	VssError::InternalServerError(format!("Failed to build DynamoDB request: {}", e))
+}
+
+fn to_vss_error<E: ProvideErrorMetadata + std::error::Error + 'static>(
+	operation: &str, e: SdkError<E>,
+) -> VssError {
+	if e.code() == Some("ValidationException") {
+		return VssError::InvalidRequestError(e.message().unwrap_or("Invalid request").to_string());
+	}
+	VssError::InternalServerError(format!(
+		"DynamoDB {} failed: {}",
+		operation,
+		DisplayErrorContext(&e)
+	))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{DynamoDbBackend, MAX_VALUE_SIZE};
+	use api::error::VssError;
+	use api::kv_store::KvStore;
+	use api::kv_store_tests::KvStoreTestSuite;
+	use api::types::{GetObjectRequest, KeyValue, PutObjectRequest};
+
+	use async_trait::async_trait;
+	use aws_sdk_dynamodb::config::{BehaviorVersion, Credentials, Region};
+	use aws_sdk_dynamodb::{Client, Config};
+	use bytes::Bytes;
+	use tokio::sync::OnceCell;
+
+	/// A DynamoDB compatible endpoint, such as DynamoDB Local.
+	const DYNAMODB_ENDPOINT: &str = "http://localhost:8000";
+	const TABLE_NAME: &str = "vss_dynamodb_kv_store_tests";
+
+	static START: OnceCell<()> = OnceCell::const_new();
+
+	async fn create_backend() -> DynamoDbBackend {
+		let config = Config::builder()
+			.behavior_version(BehaviorVersion::latest())
+			.endpoint_url(DYNAMODB_ENDPOINT)
+			.region(Region::new("us-east-1"))
+			.credentials_provider(Credentials::new("test", "test", None, None, "test"))
+			.build();
+		let backend = DynamoDbBackend::new(Client::from_conf(config), TABLE_NAME);
+		START.get_or_init(|| async { backend.create_table().await.unwrap() }).await;
+		backend
+	}
+
+	struct DynamoDbKvStoreTest;
+
+	#[async_trait]
+	impl KvStoreTestSuite for DynamoDbKvStoreTest {
+		type Store = DynamoDbBackend;
+
+		async fn create_store() -> Self::Store {
+			create_backend().await
+		}
+	}
+
+	// Runs the shared test suite, except for appending values beyond the size DynamoDB supports.
+	macro_rules! create_test {
+		($test_fn:ident) => {
+			#[tokio::test]
+			async fn $test_fn() -> Result<(), VssError> {
+				DynamoDbKvStoreTest::$test_fn().await
+			}
+		};
+	}
+
+	create_test!(put_should_succeed_when_single_object_put_operation);
+	create_test!(put_should_succeed_when_multi_object_put_operation);
+	create_test!(put_should_fail_when_key_version_mismatched);
+	create_test!(put_multi_object_should_fail_when_single_key_version_mismatched);
+	create_test!(put_should_fail_when_global_version_mismatched);
+	create_test!(put_should_succeed_when_no_global_version_is_given);
+	create_test!(put_and_delete_should_succeed_as_atomic_transaction);
+	create_test!(delete_should_succeed_when_item_exists);
+	create_test!(delete_should_succeed_when_item_does_not_exist);
+	create_test!(delete_should_be_idempotent);
+	create_test!(get_should_throw_no_such_key_exception_when_key_does_not_exist);
+	create_test!(get_should_return_correct_value_when_key_exists);
+	create_test!(list_should_return_paginated_response);
+	create_test!(list_should_honour_page_size_and_key_prefix_if_provided);
+	create_test!(list_should_return_zero_global_version_when_global_versioning_not_enabled);
+	create_test!(list_should_limit_max_page_size);
+	create_test!(delete_store_should_remove_all_objects_in_store);
+	create_test!(delete_user_should_remove_all_stores_of_user);
+	create_test!(append_should_create_and_extend_object);
+
+	#[tokio::test]
+	async fn put_should_fail_when_value_exceeds_item_size() {
+		let backend = create_backend().await;
+		let put = |size: usize| PutObjectRequest {
+			store_id: "store".to_string(),
+			global_version: None,
+			transaction_items: vec![KeyValue {
+				key: "large".to_string(),
+				version: -1,
+				value: Bytes::from(vec![0; size]),
+			}],
+			delete_items: vec![],
+		};
+
+		let result = backend.put("token".to_string(), put(MAX_VALUE_SIZE + 1)).await;
+		assert!(matches!(result, Err(VssError::InvalidRequestError(_))));
+
+		backend.put("token".to_string(), put(MAX_VALUE_SIZE)).await.unwrap();
+		let request = GetObjectRequest { store_id: "store".to_string(), key: "large".to_string() };
+		let value = backend.get("token".to_string(), request).await.unwrap().value.unwrap();
+		assert_eq!(value.value.len(), MAX_VALUE_SIZE);
+	}
+}
@@ -0,0 +1,71 @@
+use std::str::FromStr;
+
+use api::error::VssError;
+
+/// The recipient a store export is encrypted to, given as an
+/// [age](https://age-encryption.org/v1) X25519 public key, e.g. `age1...`.
+///
+/// Clients encrypt object values themselves, so that the server never learns them. Exports only
+/// contain those encrypted values, but also reveal keys, versions and the shape of the store,
+/// which an export encrypted to a key held only by the user keeps from anyone the archive passes
+/// through. The user decrypts it with the matching identity, e.g. `age --decrypt -i key.txt`.
+#[derive(Clone)]
+pub struct ExportRecipient {
+	recipient: age::x25519::Recipient,
+}
+
+impl ExportRecipient {
+	/// Parses a Bech32-encoded age X25519 public key, as printed by `age-keygen`.
+	pub fn parse(public_key: &str) -> Result<Self, VssError> {
+		let recipient = age::x25519::Recipient::from_str(public_key.trim()).map_err(|e| {
+			VssError::InvalidRequestError(format!("Invalid age X25519 recipient: {}", e))
+		})?;
+		Ok(Self { recipient })
+	}
+
+	/// Encrypts `archive` to the recipient in the binary age format.
+	pub fn encrypt(&self, archive: &[u8]) -> Result<Vec<u8>, VssError> {
+		age::encrypt(&self.recipient, archive)
+			.map_err(|e| VssError::InternalServerError(format!("Failed to encrypt export: {}", e)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::ExportRecipient;
+	use age::secrecy::ExposeSecret;
+	use api::error::VssError;
+
+	#[test]
+	fn encrypts_exports_only_the_recipient_can_decrypt() {
+		let identity = age::x25519::Identity::generate();
+		let recipient = ExportRecipient::parse(&identity.to_public().to_string()).unwrap();
+		let archive = b"store export".repeat(100);
+
+		let encrypted = recipient.encrypt(&archive).unwrap();
+		assert!(!encrypted.windows(12).any(|window| window == b"store export"));
+		assert_eq!(age::decrypt(&identity, &encrypted).unwrap(), archive);
+
+		let other_identity = age::x25519::Identity::generate();
+		assert!(age::decrypt(&other_identity, &encrypted).is_err());
+
+		// Identities are secret and not accepted as recipients.
+		let secret_key = identity.to_string();
+		assert!(matches!(
+			ExportRecipient::parse(secret_key.expose_secret()),
+			Err(VssError::InvalidRequestError(_))
+		));
+	}
+
+	#[test]
+	fn rejects_invalid_recipients() {
+		for public_key in
+			["", "age1", "npub1qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqq"]
+		{
+			assert!(matches!(
+				ExportRecipient::parse(public_key),
+				Err(VssError::InvalidRequestError(_))
+			));
+		}
+	}
+}
@@ -0,0 +1,190 @@
+use api::error::VssError;
+use api::kv_store::{KvStore, GLOBAL_VERSION_KEY};
+use api::types::{
+	AppendObjectRequest, AppendObjectResponse, DeleteObjectRequest, DeleteObjectResponse,
+	GetObjectRequest, GetObjectResponse, GetObjectVersionRequest, GetObjectsAtomicRequest,
+	GetObjectsAtomicResponse, ListKeyVersionsRequest, ListKeyVersionsResponse, ListObjectsRequest,
+	ListObjectsResponse, PutObjectRequest, PutObjectResponse,
+};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// A [`KvStore`] decorator which retries puts that conflict solely because the global version of
+/// the store moved on, bumping the current global version instead of the one the client expected.
+///
+/// Clients behind flaky networks commonly resend a put whose response got lost after it had been
+/// applied, which then fails on the global version it already bumped. On a conflict, the put is
+/// retried against the current global version if that differs from the expected one. The retry
+/// still checks the versions of all objects it writes or deletes, so it only succeeds if none of
+/// them changed either.
+///
+/// This weakens the global version to a counter of writes, no longer detecting concurrent writers
+/// whose writes touch disjoint objects, so it should only be enabled for clients relying on object
+/// versions alone.
+pub struct GlobalVersionRetryingKvStore {
+	inner: Arc<dyn KvStore>,
+	max_retries: usize,
+}
+
+impl GlobalVersionRetryingKvStore {
+	/// Constructs a [`GlobalVersionRetryingKvStore`] wrapping `inner`, retrying a conflicting put
+	/// at most `max_retries` times before the conflict is returned to the client.
+	pub fn new(inner: Arc<dyn KvStore>, max_retries: usize) -> Self {
+		Self { inner, max_retries }
+	}
+
+	async fn get_global_version(&self, user_token: &str, store_id: &str) -> Result<i64, VssError> {
+		let request = GetObjectRequest {
+			store_id: store_id.to_string(),
+			key: GLOBAL_VERSION_KEY.to_string(),
+		};
+		match self.inner.get(user_token.to_string(), request).await {
+			Ok(response) => Ok(response.value.map(|kv| kv.version).unwrap_or(0)),
+			Err(VssError::NoSuchKeyError(_)) => Ok(0),
+			Err(e) => Err(e),
+		}
+	}
+}
+
+#[async_trait]
+impl KvStore for GlobalVersionRetryingKvStore {
+	async fn get(
+		&self, user_token: String, request: GetObjectRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		self.inner.get(user_token, request).await
+	}
+
+	async fn put(
+		&self, user_token: String, request: PutObjectRequest,
+	) -> Result<PutObjectResponse, VssError> {
+		if request.global_version.is_none() {
+			return self.inner.put(user_token, request).await;
+		}
+		let mut request = request;
+		let mut retries = 0;
+		loop {
+			let conflict = match self.inner.put(user_token.clone(), request.clone()).await {
+				Err(VssError::ConflictError(msg)) => msg,
+				result => return result,
+			};
+			if retries >= self.max_retries {
+				return Err(VssError::ConflictError(conflict));
+			}
+			retries += 1;
+			let current = self.get_global_version(&user_token, &request.store_id).await?;
+			// An unchanged global version means an object conflicted, which a retry cannot fix.
+			if request.global_version == Some(current) {
+				return Err(VssError::ConflictError(conflict));
+			}
+			request.global_version = Some(current);
+		}
+	}
+
+	async fn delete(
+		&self, user_token: String, request: DeleteObjectRequest,
+	) -> Result<DeleteObjectResponse, VssError> {
+		self.inner.delete(user_token, request).await
+	}
+
+	async fn list_key_versions(
+		&self, user_token: String, request: ListKeyVersionsRequest,
+	) -> Result<ListKeyVersionsResponse, VssError> {
+		self.inner.list_key_versions(user_token, request).await
+	}
+
+	async fn list_objects(
+		&self, user_token: String, request: ListObjectsRequest,
+	) -> Result<ListObjectsResponse, VssError> {
+		self.inner.list_objects(user_token, request).await
+	}
+
+	async fn get_objects_atomic(
+		&self, user_token: String, request: GetObjectsAtomicRequest,
+	) -> Result<GetObjectsAtomicResponse, VssError> {
+		self.inner.get_objects_atomic(user_token, request).await
+	}
+
+	async fn get_object_version(
+		&self, user_token: String, request: GetObjectVersionRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		self.inner.get_object_version(user_token, request).await
+	}
+
+	async fn append(
+		&self, user_token: String, request: AppendObjectRequest,
+	) -> Result<AppendObjectResponse, VssError> {
+		self.inner.append(user_token, request).await
+	}
+
+	async fn delete_store(&self, user_token: String, store_id: String) -> Result<u64, VssError> {
+		self.inner.delete_store(user_token, store_id).await
+	}
+
+	async fn delete_user(&self, user_token: String) -> Result<u64, VssError> {
+		self.inner.delete_user(user_token).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::GlobalVersionRetryingKvStore;
+	use crate::in_memory_store::InMemoryBackend;
+	use api::error::VssError;
+	use api::kv_store::{KvStore, GLOBAL_VERSION_KEY};
+	use api::types::{GetObjectRequest, KeyValue, PutObjectRequest};
+
+	use bytes::Bytes;
+	use std::sync::Arc;
+
+	fn put_request(global_version: i64, key: &str, version: i64) -> PutObjectRequest {
+		PutObjectRequest {
+			store_id: "store".to_string(),
+			global_version: Some(global_version),
+			transaction_items: vec![KeyValue {
+				key: key.to_string(),
+				version,
+				value: Bytes::from_static(b"value"),
+			}],
+			delete_items: vec![],
+		}
+	}
+
+	async fn get_version(store: &GlobalVersionRetryingKvStore, key: &str) -> i64 {
+		let request = GetObjectRequest { store_id: "store".to_string(), key: key.to_string() };
+		store.get("token".to_string(), request).await.unwrap().value.unwrap().version
+	}
+
+	#[tokio::test]
+	async fn retries_put_conflicting_only_on_global_version() {
+		let store = GlobalVersionRetryingKvStore::new(Arc::new(InMemoryBackend::new()), 1);
+		store.put("token".to_string(), put_request(0, "a", 0)).await.unwrap();
+
+		// A resent put based on the same global version still writes other objects.
+		store.put("token".to_string(), put_request(0, "b", 0)).await.unwrap();
+		assert_eq!(get_version(&store, "b").await, 1);
+		assert_eq!(get_version(&store, GLOBAL_VERSION_KEY).await, 2);
+	}
+
+	#[tokio::test]
+	async fn returns_object_conflicts() {
+		let store = GlobalVersionRetryingKvStore::new(Arc::new(InMemoryBackend::new()), 3);
+		store.put("token".to_string(), put_request(0, "a", 0)).await.unwrap();
+
+		// The object conflicts with or without the outdated global version.
+		let result = store.put("token".to_string(), put_request(0, "a", 0)).await;
+		assert!(matches!(result, Err(VssError::ConflictError(_))));
+		let result = store.put("token".to_string(), put_request(1, "a", 0)).await;
+		assert!(matches!(result, Err(VssError::ConflictError(_))));
+		assert_eq!(get_version(&store, "a").await, 1);
+		assert_eq!(get_version(&store, GLOBAL_VERSION_KEY).await, 1);
+	}
+
+	#[tokio::test]
+	async fn does_not_retry_without_retries_configured() {
+		let store = GlobalVersionRetryingKvStore::new(Arc::new(InMemoryBackend::new()), 0);
+		store.put("token".to_string(), put_request(0, "a", 0)).await.unwrap();
+
+		let result = store.put("token".to_string(), put_request(0, "b", 0)).await;
+		assert!(matches!(result, Err(VssError::ConflictError(_))));
+	}
+}
@@ -0,0 +1,9 @@
+use api::error::VssError;
+use async_trait::async_trait;
+
+/// Checks whether a backend can currently serve requests, e.g. for readiness probes.
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+	/// Makes a trivial round trip to the backend's database, failing if it is unreachable.
+	async fn check_health(&self) -> Result<(), VssError>;
+}
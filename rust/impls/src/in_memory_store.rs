@@ -0,0 +1,770 @@
+use crate::admin_operation_store::{AdminOperationStore, PendingAdminOperation};
+use crate::device_registry::{DeviceRegistry, KnownDevice};
+use crate::health_check::HealthCheck;
+use crate::pending_deletion_store::{PendingDeletion, PendingDeletionStore};
+use crate::pool_stats::{PoolStats, PoolStatsProvider};
+use crate::postgres_store::{LIST_KEY_VERSIONS_MAX_PAGE_SIZE, MAX_PUT_REQUEST_ITEM_COUNT};
+use crate::record_store::{RecordCursor, RecordStore, StoredRecord};
+use crate::share_token_store::{ShareToken, ShareTokenStore};
+use crate::store_acl::{StoreAccess, StoreAcl, StoreGrant};
+use crate::usage_store::{RankedUser, StorageUsage, StoreUsage, UsageStore, UserRanking};
+
+use api::error::VssError;
+use api::kv_store::{
+	append_size_error, validate_append_request, validate_get_objects_atomic_request, KvStore,
+	GLOBAL_VERSION_KEY, INITIAL_RECORD_VERSION, MAX_APPENDED_VALUE_SIZE,
+};
+use api::types::{
+	AppendObjectRequest, AppendObjectResponse, DeleteObjectRequest, DeleteObjectResponse,
+	GetObjectRequest, GetObjectResponse, GetObjectsAtomicRequest, GetObjectsAtomicResponse,
+	KeyValue, ListKeyVersionsRequest, ListKeyVersionsResponse, PutObjectRequest, PutObjectResponse,
+};
+use async_trait::async_trait;
+use bytes::Bytes;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Request counts are recorded per hour.
+const SECS_PER_HOUR: u64 = 60 * 60;
+
+/// Identifies an object by user token, store id and key, in this order.
+type ObjectKey = (String, String, String);
+
+#[derive(Clone)]
+struct Object {
+	value: Bytes,
+	version: i64,
+	/// The time of the last write, in seconds since the UNIX epoch.
+	last_updated_at: u64,
+}
+
+#[derive(Default)]
+struct State {
+	objects: BTreeMap<ObjectKey, Object>,
+	/// The time each device was first seen, by user token, store id and device fingerprint.
+	devices: HashMap<(String, String), HashMap<Vec<u8>, u64>>,
+	share_tokens: HashMap<[u8; 32], ShareToken>,
+	/// Grants by owner user token, store id and grantee user token.
+	grants: BTreeMap<ObjectKey, StoreAccess>,
+	pending_deletions: BTreeMap<ObjectKey, PendingDeletion>,
+	/// Pending admin operations by approval token.
+	admin_operations: HashMap<String, PendingAdminOperation>,
+	/// Request counts by user token and the start of the hour they were made in.
+	request_counts: HashMap<(String, u64), u64>,
+}
+
+/// A [`KvStore`] implementation keeping all objects in memory, following the versioning semantics
+/// of the PostgreSQL backend.
+///
+/// Objects are lost when the process exits, so this backend is only meant for tests, demos and
+/// local wallet development.
+#[derive(Default)]
+pub struct InMemoryBackend {
+	state: Mutex<State>,
+}
+
+impl InMemoryBackend {
+	/// Constructs an empty [`InMemoryBackend`].
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn lock(&self) -> MutexGuard<'_, State> {
+		// The state is consistent at any point a panic might occur.
+		self.state.lock().unwrap_or_else(|e| e.into_inner())
+	}
+}
+
+fn now() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+fn object_key(user_token: &str, store_id: &str, key: &str) -> ObjectKey {
+	(user_token.to_string(), store_id.to_string(), key.to_string())
+}
+
+fn conflict_error() -> VssError {
+	VssError::ConflictError(
+		"Transaction could not be completed due to a possible conflict".to_string(),
+	)
+}
+
+/// Applies a conditional write of `kv`, returning whether its version check passed.
+fn put_object(objects: &mut BTreeMap<ObjectKey, Object>, key: ObjectKey, kv: KeyValue) -> bool {
+	let version = match (kv.version, objects.get(&key)) {
+		(-1, _) | (0, None) => INITIAL_RECORD_VERSION as i64,
+		(version, Some(object)) if version > 0 && object.version == version => {
+			version.saturating_add(1)
+		},
+		_ => return false,
+	};
+	objects.insert(key, Object { value: kv.value, version, last_updated_at: now() });
+	true
+}
+
+/// Applies a conditional delete of `kv`, returning whether an object was deleted.
+fn delete_object(
+	objects: &mut BTreeMap<ObjectKey, Object>, key: &ObjectKey, kv: &KeyValue,
+) -> bool {
+	match objects.get(key) {
+		Some(object) if kv.version == -1 || object.version == kv.version => {
+			objects.remove(key);
+			true
+		},
+		_ => false,
+	}
+}
+
+#[async_trait]
+impl KvStore for InMemoryBackend {
+	async fn get(
+		&self, user_token: String, request: GetObjectRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		let state = self.lock();
+		let key = object_key(&user_token, &request.store_id, &request.key);
+		let key_value = match state.objects.get(&key) {
+			Some(object) => {
+				KeyValue { key: request.key, value: object.value.clone(), version: object.version }
+			},
+			None if request.key == GLOBAL_VERSION_KEY => {
+				KeyValue { key: GLOBAL_VERSION_KEY.to_string(), value: Bytes::new(), version: 0 }
+			},
+			None => return Err(VssError::NoSuchKeyError("Requested key not found.".to_string())),
+		};
+		Ok(GetObjectResponse { value: Some(key_value) })
+	}
+
+	async fn put(
+		&self, user_token: String, request: PutObjectRequest,
+	) -> Result<PutObjectResponse, VssError> {
+		if request.transaction_items.len() + request.delete_items.len() > MAX_PUT_REQUEST_ITEM_COUNT
+		{
+			return Err(VssError::InvalidRequestError(format!(
+				"Number of write items per request should be less than equal to {}",
+				MAX_PUT_REQUEST_ITEM_COUNT
+			)));
+		}
+		let store_id = request.store_id;
+		let mut put_items = request.transaction_items;
+		if let Some(global_version) = request.global_version {
+			put_items.push(KeyValue {
+				key: GLOBAL_VERSION_KEY.to_string(),
+				value: Bytes::new(),
+				version: global_version,
+			});
+		}
+
+		let mut state = self.lock();
+		// Previous objects of all written keys, restored in reverse order on conflict.
+		let mut undo_log = Vec::new();
+		let mut succeeded = true;
+		for kv in put_items {
+			let key = object_key(&user_token, &store_id, &kv.key);
+			undo_log.push((key.clone(), state.objects.get(&key).cloned()));
+			if !put_object(&mut state.objects, key, kv) {
+				succeeded = false;
+				break;
+			}
+		}
+		if succeeded {
+			for kv in &request.delete_items {
+				let key = object_key(&user_token, &store_id, &kv.key);
+				let previous = state.objects.get(&key).cloned();
+				// Deleting a missing object conflicts within a put, as in the PostgreSQL backend.
+				if !delete_object(&mut state.objects, &key, kv) {
+					succeeded = false;
+					break;
+				}
+				undo_log.push((key, previous));
+			}
+		}
+		if !succeeded {
+			for (key, previous) in undo_log.into_iter().rev() {
+				match previous {
+					Some(object) => state.objects.insert(key, object),
+					None => state.objects.remove(&key),
+				};
+			}
+			return Err(conflict_error());
+		}
+		Ok(PutObjectResponse {})
+	}
+
+	async fn delete(
+		&self, user_token: String, request: DeleteObjectRequest,
+	) -> Result<DeleteObjectResponse, VssError> {
+		let key_value = request.key_value.ok_or_else(|| {
+			VssError::InvalidRequestError("key_value missing in DeleteObjectRequest".to_string())
+		})?;
+		let key = object_key(&user_token, &request.store_id, &key_value.key);
+		// Deleting an object at a stale version is a no-op, as in the PostgreSQL backend.
+		delete_object(&mut self.lock().objects, &key, &key_value);
+		Ok(DeleteObjectResponse {})
+	}
+
+	async fn list_key_versions(
+		&self, user_token: String, request: ListKeyVersionsRequest,
+	) -> Result<ListKeyVersionsResponse, VssError> {
+		let state = self.lock();
+		// Only fetch global_version for the first page.
+		let global_version = request.page_token.is_none().then(|| {
+			let key = object_key(&user_token, &request.store_id, GLOBAL_VERSION_KEY);
+			state.objects.get(&key).map_or(0, |object| object.version)
+		});
+
+		let limit = request.page_size.unwrap_or(i32::MAX).clamp(0, LIST_KEY_VERSIONS_MAX_PAGE_SIZE)
+			as usize;
+		let key_prefix = request.key_prefix.unwrap_or_default();
+		let start = object_key(
+			&user_token,
+			&request.store_id,
+			request.page_token.as_deref().unwrap_or_default(),
+		);
+		let key_versions: Vec<_> = state
+			.objects
+			.range((Bound::Excluded(start), Bound::Unbounded))
+			.take_while(|((token, store_id, _), _)| {
+				*token == user_token && *store_id == request.store_id
+			})
+			.filter(|((_, _, key), _)| key.starts_with(&key_prefix))
+			.take(limit)
+			.filter(|((_, _, key), _)| key != GLOBAL_VERSION_KEY)
+			.map(|((_, _, key), object)| KeyValue {
+				key: key.clone(),
+				value: Bytes::new(),
+				version: object.version,
+			})
+			.collect();
+
+		let next_page_token = match key_versions.last() {
+			Some(kv) => Some(kv.key.clone()),
+			None => Some("".to_string()),
+		};
+		Ok(ListKeyVersionsResponse { key_versions, next_page_token, global_version })
+	}
+
+	async fn get_objects_atomic(
+		&self, user_token: String, request: GetObjectsAtomicRequest,
+	) -> Result<GetObjectsAtomicResponse, VssError> {
+		validate_get_objects_atomic_request(&request)?;
+		// Holding the lock, no write happens while the objects are read.
+		let state = self.lock();
+		let get_object =
+			|key: &str| state.objects.get(&object_key(&user_token, &request.store_id, key));
+		let global_version = get_object(GLOBAL_VERSION_KEY).map_or(0, |object| object.version);
+		let objects = request
+			.keys
+			.iter()
+			.filter_map(|key| {
+				let object = get_object(key)?;
+				Some(KeyValue {
+					key: key.clone(),
+					value: object.value.clone(),
+					version: object.version,
+				})
+			})
+			.collect();
+		Ok(GetObjectsAtomicResponse { objects, global_version })
+	}
+
+	async fn append(
+		&self, user_token: String, request: AppendObjectRequest,
+	) -> Result<AppendObjectResponse, VssError> {
+		validate_append_request(&request)?;
+		let mut state = self.lock();
+		let key = object_key(&user_token, &request.store_id, &request.key);
+		let (value, version) = match state.objects.get(&key) {
+			Some(object) => {
+				if object.value.len() + request.value.len() > MAX_APPENDED_VALUE_SIZE {
+					return Err(append_size_error());
+				}
+				let mut value = object.value.to_vec();
+				value.extend_from_slice(&request.value);
+				(Bytes::from(value), object.version.saturating_add(1))
+			},
+			None => (request.value, INITIAL_RECORD_VERSION as i64),
+		};
+		state.objects.insert(key, Object { value, version, last_updated_at: now() });
+		Ok(AppendObjectResponse { version })
+	}
+
+	async fn delete_store(&self, user_token: String, store_id: String) -> Result<u64, VssError> {
+		let mut state = self.lock();
+		let len = state.objects.len();
+		state.objects.retain(|(token, id, _), _| *token != user_token || *id != store_id);
+		let num_objects = (len - state.objects.len()) as u64;
+		state.devices.remove(&(user_token.clone(), store_id.clone()));
+		state
+			.share_tokens
+			.retain(|_, token| token.user_token != user_token || token.store_id != store_id);
+		state.grants.retain(|(owner, id, _), _| *owner != user_token || *id != store_id);
+		state.pending_deletions.retain(|(token, id, _), _| *token != user_token || *id != store_id);
+		Ok(num_objects)
+	}
+
+	async fn delete_user(&self, user_token: String) -> Result<u64, VssError> {
+		let mut state = self.lock();
+		let len = state.objects.len();
+		state.objects.retain(|(token, _, _), _| *token != user_token);
+		let num_objects = (len - state.objects.len()) as u64;
+		state.devices.retain(|(token, _), _| *token != user_token);
+		state.share_tokens.retain(|_, token| token.user_token != user_token);
+		state
+			.grants
+			.retain(|(owner, _, grantee), _| *owner != user_token && *grantee != user_token);
+		state.request_counts.retain(|(token, _), _| *token != user_token);
+		state.pending_deletions.retain(|(token, _, _), _| *token != user_token);
+		Ok(num_objects)
+	}
+}
+
+/// As there is no database, the backend has no connection pool and reports an empty one.
+impl PoolStatsProvider for InMemoryBackend {
+	fn pool_stats(&self) -> PoolStats {
+		PoolStats::default()
+	}
+}
+
+#[async_trait]
+impl HealthCheck for InMemoryBackend {
+	async fn check_health(&self) -> Result<(), VssError> {
+		Ok(())
+	}
+}
+
+#[async_trait]
+impl RecordStore for InMemoryBackend {
+	async fn scan_records(
+		&self, after: Option<&RecordCursor>, limit: i64,
+	) -> Result<Vec<StoredRecord>, VssError> {
+		let state = self.lock();
+		let start = match after {
+			Some(cursor) => {
+				Bound::Excluded(object_key(&cursor.user_token, &cursor.store_id, &cursor.key))
+			},
+			None => Bound::Unbounded,
+		};
+		Ok(state
+			.objects
+			.range((start, Bound::Unbounded))
+			.take(limit.max(0) as usize)
+			.map(|((user_token, store_id, key), object)| StoredRecord {
+				user_token: user_token.clone(),
+				store_id: store_id.clone(),
+				key: key.clone(),
+				version: object.version,
+				value: object.value.clone(),
+			})
+			.collect())
+	}
+
+	async fn replace_record_value(
+		&self, record: &StoredRecord, new_value: Bytes,
+	) -> Result<bool, VssError> {
+		let mut state = self.lock();
+		let key = object_key(&record.user_token, &record.store_id, &record.key);
+		match state.objects.get_mut(&key) {
+			Some(object) if object.version == record.version && object.value == record.value => {
+				object.value = new_value;
+				Ok(true)
+			},
+			_ => Ok(false),
+		}
+	}
+}
+
+#[async_trait]
+impl DeviceRegistry for InMemoryBackend {
+	async fn record_device(
+		&self, user_token: &str, store_id: &str, device_fingerprint: &[u8],
+	) -> Result<bool, VssError> {
+		let mut state = self.lock();
+		let devices =
+			state.devices.entry((user_token.to_string(), store_id.to_string())).or_default();
+		let known = devices.len();
+		if devices.contains_key(device_fingerprint) {
+			return Ok(false);
+		}
+		devices.insert(device_fingerprint.to_vec(), now());
+		Ok(known > 0)
+	}
+
+	async fn list_devices(&self, user_token: &str) -> Result<Vec<KnownDevice>, VssError> {
+		let state = self.lock();
+		let mut devices: Vec<KnownDevice> = state
+			.devices
+			.iter()
+			.filter(|((token, _), _)| token == user_token)
+			.flat_map(|((_, store_id), devices)| {
+				devices.iter().map(|(device_fingerprint, first_seen_at)| KnownDevice {
+					store_id: store_id.clone(),
+					device_fingerprint: device_fingerprint.clone(),
+					first_seen_at: *first_seen_at,
+				})
+			})
+			.collect();
+		devices.sort_by(|a, b| {
+			(&a.store_id, a.first_seen_at, &a.device_fingerprint).cmp(&(
+				&b.store_id,
+				b.first_seen_at,
+				&b.device_fingerprint,
+			))
+		});
+		Ok(devices)
+	}
+}
+
+#[async_trait]
+impl UsageStore for InMemoryBackend {
+	async fn get_storage_usage(&self, user_token: &str) -> Result<StorageUsage, VssError> {
+		let state = self.lock();
+		let mut usage = StorageUsage::default();
+		for ((token, _, key), object) in &state.objects {
+			if token != user_token || key == GLOBAL_VERSION_KEY {
+				continue;
+			}
+			usage.bytes += object.value.len() as u64;
+			usage.objects += 1;
+		}
+		Ok(usage)
+	}
+
+	async fn list_store_ids(&self, user_token: &str) -> Result<Vec<String>, VssError> {
+		let state = self.lock();
+		let mut store_ids: Vec<String> = state
+			.objects
+			.keys()
+			.filter(|(token, _, _)| token == user_token)
+			.map(|(_, store_id, _)| store_id.clone())
+			.collect();
+		// Objects are ordered by user token and store id, so duplicates are adjacent.
+		store_ids.dedup();
+		Ok(store_ids)
+	}
+
+	async fn list_store_usage(&self, user_token: &str) -> Result<Vec<StoreUsage>, VssError> {
+		let state = self.lock();
+		let mut store_usage: Vec<StoreUsage> = Vec::new();
+		for ((token, store_id, key), object) in &state.objects {
+			if token != user_token {
+				continue;
+			}
+			// Objects are ordered by user token and store id, so those of a store are adjacent.
+			if store_usage.last().is_none_or(|last| &last.store_id != store_id) {
+				store_usage.push(StoreUsage {
+					store_id: store_id.clone(),
+					usage: StorageUsage::default(),
+					last_updated_at: None,
+				});
+			}
+			// unwrap safety: an entry for the store was pushed above.
+			let last = store_usage.last_mut().unwrap();
+			last.last_updated_at = last.last_updated_at.max(Some(object.last_updated_at));
+			if key != GLOBAL_VERSION_KEY {
+				last.usage.bytes += object.value.len() as u64;
+				last.usage.objects += 1;
+			}
+		}
+		Ok(store_usage)
+	}
+
+	async fn record_requests(&self, counts: &[(String, u64)], now: u64) -> Result<(), VssError> {
+		let hour = now - now % SECS_PER_HOUR;
+		let mut state = self.lock();
+		for (user_token, requests) in counts {
+			*state.request_counts.entry((user_token.clone(), hour)).or_default() += requests;
+		}
+		Ok(())
+	}
+
+	async fn purge_request_counts(&self, before: u64) -> Result<u64, VssError> {
+		let mut state = self.lock();
+		let len = state.request_counts.len();
+		state.request_counts.retain(|(_, hour), _| *hour >= before);
+		Ok((len - state.request_counts.len()) as u64)
+	}
+
+	async fn top_users(
+		&self, ranking: UserRanking, since: u64, limit: i64,
+	) -> Result<Vec<RankedUser>, VssError> {
+		let state = self.lock();
+		let mut values: HashMap<&str, u64> = HashMap::new();
+		match ranking {
+			UserRanking::Storage | UserRanking::Growth => {
+				let since = if ranking == UserRanking::Growth { since } else { 0 };
+				for ((user_token, _, key), object) in &state.objects {
+					if key != GLOBAL_VERSION_KEY && object.last_updated_at >= since {
+						*values.entry(user_token).or_default() += object.value.len() as u64;
+					}
+				}
+			},
+			UserRanking::Requests => {
+				let since_hour = since - since % SECS_PER_HOUR;
+				for ((user_token, hour), requests) in &state.request_counts {
+					if *hour >= since_hour {
+						*values.entry(user_token).or_default() += requests;
+					}
+				}
+			},
+		}
+		let mut ranked: Vec<RankedUser> = values
+			.into_iter()
+			.map(|(user_token, value)| RankedUser { user_token: user_token.to_string(), value })
+			.collect();
+		ranked.sort_by(|a, b| b.value.cmp(&a.value).then_with(|| a.user_token.cmp(&b.user_token)));
+		ranked.truncate(limit.max(0) as usize);
+		Ok(ranked)
+	}
+}
+
+#[async_trait]
+impl ShareTokenStore for InMemoryBackend {
+	async fn create_share_token(
+		&self, token: &ShareToken, max_active_tokens: i64,
+	) -> Result<bool, VssError> {
+		let now = now();
+		let mut state = self.lock();
+		state
+			.share_tokens
+			.retain(|_, other| other.user_token != token.user_token || other.expires_at > now);
+		let active = state
+			.share_tokens
+			.values()
+			.filter(|other| other.user_token == token.user_token)
+			.count();
+		if active as i64 >= max_active_tokens {
+			return Ok(false);
+		}
+		state.share_tokens.insert(token.token_sha256, token.clone());
+		Ok(true)
+	}
+
+	async fn get_share_token(
+		&self, token_sha256: &[u8; 32],
+	) -> Result<Option<ShareToken>, VssError> {
+		let now = now();
+		let state = self.lock();
+		Ok(state.share_tokens.get(token_sha256).filter(|token| token.expires_at > now).cloned())
+	}
+
+	async fn revoke_share_token(
+		&self, user_token: &str, token_sha256: &[u8; 32],
+	) -> Result<bool, VssError> {
+		let mut state = self.lock();
+		match state.share_tokens.get(token_sha256) {
+			Some(token) if token.user_token == user_token => {
+				state.share_tokens.remove(token_sha256);
+				Ok(true)
+			},
+			_ => Ok(false),
+		}
+	}
+}
+
+#[async_trait]
+impl StoreAcl for InMemoryBackend {
+	async fn grant_store_access(
+		&self, grant: &StoreGrant, max_grants: i64,
+	) -> Result<bool, VssError> {
+		let mut state = self.lock();
+		let others = state
+			.grants
+			.keys()
+			.filter(|(owner, store_id, grantee)| {
+				*owner == grant.owner_user_token
+					&& *store_id == grant.store_id
+					&& *grantee != grant.grantee_user_token
+			})
+			.count();
+		if others as i64 >= max_grants {
+			return Ok(false);
+		}
+		let key = object_key(&grant.owner_user_token, &grant.store_id, &grant.grantee_user_token);
+		state.grants.insert(key, grant.access);
+		Ok(true)
+	}
+
+	async fn revoke_store_access(
+		&self, owner_user_token: &str, store_id: &str, grantee_user_token: &str,
+	) -> Result<bool, VssError> {
+		let key = object_key(owner_user_token, store_id, grantee_user_token);
+		Ok(self.lock().grants.remove(&key).is_some())
+	}
+
+	async fn list_store_grants(
+		&self, owner_user_token: &str, store_id: &str,
+	) -> Result<Vec<StoreGrant>, VssError> {
+		let state = self.lock();
+		Ok(state
+			.grants
+			.iter()
+			.filter(|((owner, id, _), _)| owner == owner_user_token && id == store_id)
+			.map(|((owner, id, grantee), access)| StoreGrant {
+				owner_user_token: owner.clone(),
+				store_id: id.clone(),
+				grantee_user_token: grantee.clone(),
+				access: *access,
+			})
+			.collect())
+	}
+
+	async fn get_store_access(
+		&self, owner_user_token: &str, store_id: &str, grantee_user_token: &str,
+	) -> Result<Option<StoreAccess>, VssError> {
+		let key = object_key(owner_user_token, store_id, grantee_user_token);
+		Ok(self.lock().grants.get(&key).copied())
+	}
+}
+
+#[async_trait]
+impl PendingDeletionStore for InMemoryBackend {
+	async fn add_pending_deletion(&self, deletion: &PendingDeletion) -> Result<(), VssError> {
+		let key = object_key(&deletion.user_token, &deletion.store_id, &deletion.key);
+		self.lock().pending_deletions.insert(key, deletion.clone());
+		Ok(())
+	}
+
+	async fn cancel_pending_deletion(
+		&self, user_token: &str, store_id: &str, key: &str,
+	) -> Result<bool, VssError> {
+		let key = object_key(user_token, store_id, key);
+		Ok(self.lock().pending_deletions.remove(&key).is_some())
+	}
+
+	async fn list_pending_deletions(
+		&self, user_token: &str, store_id: &str,
+	) -> Result<Vec<PendingDeletion>, VssError> {
+		let state = self.lock();
+		Ok(state
+			.pending_deletions
+			.iter()
+			.filter(|((token, id, _), _)| token == user_token && id == store_id)
+			.map(|(_, deletion)| deletion.clone())
+			.collect())
+	}
+
+	async fn list_due_deletions(
+		&self, now: u64, limit: i64,
+	) -> Result<Vec<PendingDeletion>, VssError> {
+		let state = self.lock();
+		let mut due: Vec<PendingDeletion> = state
+			.pending_deletions
+			.values()
+			.filter(|deletion| deletion.executable_after <= now)
+			.cloned()
+			.collect();
+		due.sort_by_key(|deletion| deletion.executable_after);
+		due.truncate(limit.max(0) as usize);
+		Ok(due)
+	}
+
+	async fn remove_pending_deletion(&self, deletion: &PendingDeletion) -> Result<bool, VssError> {
+		let key = object_key(&deletion.user_token, &deletion.store_id, &deletion.key);
+		let mut state = self.lock();
+		if state.pending_deletions.get(&key) == Some(deletion) {
+			state.pending_deletions.remove(&key);
+			return Ok(true);
+		}
+		Ok(false)
+	}
+}
+
+#[async_trait]
+impl AdminOperationStore for InMemoryBackend {
+	async fn add_pending_operation(
+		&self, operation: &PendingAdminOperation,
+	) -> Result<(), VssError> {
+		let mut state = self.lock();
+		state.admin_operations.retain(|_, pending| pending.expires_at > operation.requested_at);
+		state.admin_operations.insert(operation.approval_token.clone(), operation.clone());
+		Ok(())
+	}
+
+	async fn get_pending_operation(
+		&self, approval_token: &str, now: u64,
+	) -> Result<Option<PendingAdminOperation>, VssError> {
+		let state = self.lock();
+		let operation = state.admin_operations.get(approval_token);
+		Ok(operation.filter(|operation| operation.expires_at > now).cloned())
+	}
+
+	async fn remove_pending_operation(&self, approval_token: &str) -> Result<bool, VssError> {
+		Ok(self.lock().admin_operations.remove(approval_token).is_some())
+	}
+
+	async fn list_pending_operations(
+		&self, now: u64,
+	) -> Result<Vec<PendingAdminOperation>, VssError> {
+		let state = self.lock();
+		let mut operations: Vec<PendingAdminOperation> = state
+			.admin_operations
+			.values()
+			.filter(|operation| operation.expires_at > now)
+			.cloned()
+			.collect();
+		operations.sort_by_key(|operation| operation.requested_at);
+		Ok(operations)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::InMemoryBackend;
+	use crate::usage_store::{StorageUsage, UsageStore};
+	use api::define_kv_store_tests;
+	use api::kv_store::KvStore;
+	use api::kv_store_simulation::{run_simulation, SimulationSettings};
+	use api::types::{GetObjectRequest, KeyValue, PutObjectRequest};
+
+	use bytes::Bytes;
+
+	define_kv_store_tests!(InMemoryKvStoreTest, InMemoryBackend, InMemoryBackend::new());
+
+	#[tokio::test]
+	async fn conflicting_put_leaves_objects_untouched() {
+		let store = InMemoryBackend::new();
+		let kv = |key: &str, version: i64, value: &'static str| KeyValue {
+			key: key.to_string(),
+			version,
+			value: Bytes::from(value),
+		};
+		let put = |transaction_items, delete_items| PutObjectRequest {
+			store_id: "store_id".to_string(),
+			global_version: None,
+			transaction_items,
+			delete_items,
+		};
+		store
+			.put("token".to_string(), put(vec![kv("k1", 0, "a"), kv("k2", 0, "b")], vec![]))
+			.await
+			.unwrap();
+
+		// The put of k1 succeeds on its own, but the deletion of the missing k3 conflicts.
+		let request =
+			put(vec![kv("k1", 1, "c"), kv("k4", 0, "d")], vec![kv("k2", 1, ""), kv("k3", -1, "")]);
+		assert!(store.put("token".to_string(), request).await.is_err());
+
+		let get =
+			|key: &str| GetObjectRequest { store_id: "store_id".to_string(), key: key.to_string() };
+		let k1 = store.get("token".to_string(), get("k1")).await.unwrap().value.unwrap();
+		assert_eq!((k1.value, k1.version), (Bytes::from("a"), 1));
+		assert!(store.get("token".to_string(), get("k2")).await.is_ok());
+		assert!(store.get("token".to_string(), get("k4")).await.is_err());
+		let usage = store.get_storage_usage("token").await.unwrap();
+		assert_eq!(usage, StorageUsage { bytes: 2, objects: 2 });
+	}
+
+	#[tokio::test]
+	async fn simulated_sessions_are_linearizable() {
+		let settings = SimulationSettings::default();
+		for seed in 0..1000 {
+			let store = InMemoryBackend::new();
+			if let Err(history) = run_simulation(&store, "token", "store_id", seed, &settings).await
+			{
+				panic!("{}", history);
+			}
+		}
+	}
+}
@@ -0,0 +1,30 @@
+use api::error::VssError;
+use async_trait::async_trait;
+
+/// The outcome of restoring a store to an earlier point in time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StoreRestore {
+	/// The number of objects written back with the value they had at that time.
+	pub restored_objects: u64,
+	/// The number of objects deleted, as they did not exist at that time.
+	pub deleted_objects: u64,
+	/// The number of keys left as they are, as their version at that time is no longer kept.
+	pub unrestorable_keys: u64,
+}
+
+/// Keeps the previous versions of objects, so that stores can be restored to an earlier point in
+/// time, e.g. after a client wrote a corrupted state.
+#[async_trait]
+pub trait KeyHistory: Send + Sync {
+	/// Restores the store to its state at `at`, in seconds since the Unix epoch, in a single
+	/// transaction. Each key is restored to its newest version written at or before `at`, or
+	/// deleted if it did not exist at that time.
+	///
+	/// Restored objects are assigned versions above their current ones, and the global version of
+	/// the store is incremented, so that clients holding the current state see conflicts rather
+	/// than overwriting the restored one. The replaced versions are kept in turn, so that a restore
+	/// can itself be undone.
+	async fn restore_store(
+		&self, user_token: &str, store_id: &str, at: u64,
+	) -> Result<StoreRestore, VssError>;
+}
@@ -0,0 +1,286 @@
+use api::error::VssError;
+use async_trait::async_trait;
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+
+/// The length in bytes of the symmetric keys handed out by a [`KeyProvider`].
+pub const KEY_LEN: usize = 32;
+
+/// Provides versioned symmetric keys used to encrypt values at rest.
+///
+/// Every encrypted value records the version of the key it was encrypted with, so that keys can
+/// be rotated by introducing a new version while older versions remain available for decryption
+/// until all values have been re-encrypted.
+#[async_trait]
+pub trait KeyProvider: Send + Sync {
+	/// Returns the version of the key that new values are encrypted with.
+	fn current_key_version(&self) -> u32;
+
+	/// Returns the key of the given version, or an error if it is unknown.
+	async fn get_key(&self, version: u32) -> Result<[u8; KEY_LEN], VssError>;
+}
+
+/// A [`KeyProvider`] holding all key versions in memory, e.g. as loaded from the configuration or
+/// a key file.
+pub struct StaticKeyProvider {
+	keys: HashMap<u32, [u8; KEY_LEN]>,
+	current_key_version: u32,
+}
+
+impl StaticKeyProvider {
+	/// Constructs a [`StaticKeyProvider`] from key entries of the form `<version>:<hex key>`.
+	///
+	/// Empty entries and entries starting with `#` are ignored, which allows passing the lines of a
+	/// key file directly. New values are encrypted with `current_key_version` if given, otherwise
+	/// with the highest key version.
+	pub fn from_key_entries<'a>(
+		entries: impl IntoIterator<Item = &'a str>, current_key_version: Option<u32>,
+	) -> Result<Self, String> {
+		let (keys, current_key_version) =
+			parse_key_entries(entries, current_key_version, "<hex key>", |version, key_hex| {
+				parse_key_hex(key_hex)
+					.ok_or(format!("Key version {} must be a hex-encoded 32-byte key", version))
+			})?;
+		Ok(Self { keys, current_key_version })
+	}
+
+	/// The versions of the held keys, in ascending order.
+	pub fn key_versions(&self) -> Vec<u32> {
+		let mut versions: Vec<u32> = self.keys.keys().copied().collect();
+		versions.sort_unstable();
+		versions
+	}
+}
+
+#[async_trait]
+impl KeyProvider for StaticKeyProvider {
+	fn current_key_version(&self) -> u32 {
+		self.current_key_version
+	}
+
+	async fn get_key(&self, version: u32) -> Result<[u8; KEY_LEN], VssError> {
+		self.keys.get(&version).copied().ok_or_else(|| {
+			VssError::InternalServerError(format!("Encryption key version {} is unknown", version))
+		})
+	}
+}
+
+/// A key encrypting the data keys of an [`EnvelopeKeyProvider`], e.g. one held by a KMS.
+///
+/// The master key never encrypts values itself, so it is only used when a data key is first
+/// needed, and data keys can be stored next to the configuration in wrapped form.
+#[async_trait]
+pub trait MasterKey: Send + Sync {
+	/// Decrypts a data key wrapped by this master key.
+	async fn unwrap_key(&self, wrapped_key: &str) -> Result<[u8; KEY_LEN], VssError>;
+}
+
+/// The length in bytes of the nonce prepended to keys wrapped by a [`LocalMasterKey`].
+const WRAPPING_NONCE_LEN: usize = 12;
+
+/// A [`MasterKey`] held in memory, wrapping data keys with ChaCha20-Poly1305.
+///
+/// Wrapped keys are hex-encoded as the random nonce followed by the ciphertext.
+pub struct LocalMasterKey {
+	cipher: ChaCha20Poly1305,
+}
+
+impl LocalMasterKey {
+	/// Constructs a [`LocalMasterKey`] from a hex-encoded 32-byte key.
+	pub fn from_hex(key_hex: &str) -> Result<Self, String> {
+		let key = parse_key_hex(key_hex.trim())
+			.ok_or("The master key must be a hex-encoded 32-byte key".to_string())?;
+		Ok(Self { cipher: ChaCha20Poly1305::new(Key::from_slice(&key)) })
+	}
+
+	/// Wraps `key`, returning it in the form expected by [`MasterKey::unwrap_key`].
+	pub fn wrap_key(&self, key: &[u8; KEY_LEN]) -> String {
+		let nonce: [u8; WRAPPING_NONCE_LEN] = rand::random();
+		// unwrap safety: encryption only fails for messages exceeding the cipher's maximum length.
+		let ciphertext = self.cipher.encrypt(Nonce::from_slice(&nonce), key.as_slice()).unwrap();
+		nonce.iter().chain(ciphertext.iter()).map(|byte| format!("{:02x}", byte)).collect()
+	}
+}
+
+#[async_trait]
+impl MasterKey for LocalMasterKey {
+	async fn unwrap_key(&self, wrapped_key: &str) -> Result<[u8; KEY_LEN], VssError> {
+		let invalid_key = || VssError::InternalServerError("Failed to unwrap data key".to_string());
+		let wrapped_key = parse_hex(wrapped_key).ok_or_else(invalid_key)?;
+		if wrapped_key.len() < WRAPPING_NONCE_LEN {
+			return Err(invalid_key());
+		}
+		let (nonce, ciphertext) = wrapped_key.split_at(WRAPPING_NONCE_LEN);
+		let key =
+			self.cipher.decrypt(Nonce::from_slice(nonce), ciphertext).map_err(|_| invalid_key())?;
+		<[u8; KEY_LEN]>::try_from(key).map_err(|_| invalid_key())
+	}
+}
+
+/// The data keys of an [`EnvelopeKeyProvider`] in wrapped form, by version.
+pub struct WrappedDataKeys {
+	keys: HashMap<u32, String>,
+	current_key_version: u32,
+}
+
+impl WrappedDataKeys {
+	/// Constructs [`WrappedDataKeys`] from key entries of the form `<version>:<wrapped key>`, with
+	/// the same rules as [`StaticKeyProvider::from_key_entries`]. Wrapped keys are opaque until
+	/// unwrapped by the master key.
+	pub fn from_key_entries<'a>(
+		entries: impl IntoIterator<Item = &'a str>, current_key_version: Option<u32>,
+	) -> Result<Self, String> {
+		let (keys, current_key_version) =
+			parse_key_entries(entries, current_key_version, "<wrapped key>", |version, key| {
+				if key.is_empty() {
+					return Err(format!("Key version {} lacks a wrapped key", version));
+				}
+				Ok(key.to_string())
+			})?;
+		Ok(Self { keys, current_key_version })
+	}
+
+	/// The versions of the held keys, in ascending order.
+	pub fn key_versions(&self) -> Vec<u32> {
+		let mut versions: Vec<u32> = self.keys.keys().copied().collect();
+		versions.sort_unstable();
+		versions
+	}
+
+	/// Returns the version of the key that new values are encrypted with.
+	pub fn current_key_version(&self) -> u32 {
+		self.current_key_version
+	}
+}
+
+/// A [`KeyProvider`] implementing envelope encryption: data keys are stored wrapped by a
+/// [`MasterKey`] and unwrapped on first use, after which they are kept in memory.
+///
+/// Rotating the master key only requires re-wrapping the data keys, not re-encrypting values.
+pub struct EnvelopeKeyProvider {
+	wrapped_keys: WrappedDataKeys,
+	master_key: Arc<dyn MasterKey>,
+	unwrapped_keys: Mutex<HashMap<u32, [u8; KEY_LEN]>>,
+}
+
+impl EnvelopeKeyProvider {
+	/// Constructs an [`EnvelopeKeyProvider`] unwrapping `wrapped_keys` with `master_key`.
+	pub fn new(wrapped_keys: WrappedDataKeys, master_key: Arc<dyn MasterKey>) -> Self {
+		Self { wrapped_keys, master_key, unwrapped_keys: Mutex::new(HashMap::new()) }
+	}
+}
+
+#[async_trait]
+impl KeyProvider for EnvelopeKeyProvider {
+	fn current_key_version(&self) -> u32 {
+		self.wrapped_keys.current_key_version
+	}
+
+	async fn get_key(&self, version: u32) -> Result<[u8; KEY_LEN], VssError> {
+		if let Some(key) = self.unwrapped_keys.lock().unwrap().get(&version) {
+			return Ok(*key);
+		}
+		let wrapped_key = self.wrapped_keys.keys.get(&version).ok_or_else(|| {
+			VssError::InternalServerError(format!("Encryption key version {} is unknown", version))
+		})?;
+		// Concurrent first uses may unwrap the same key more than once, which is harmless.
+		let key = self.master_key.unwrap_key(wrapped_key).await.map_err(|e| {
+			VssError::InternalServerError(format!(
+				"Failed to unwrap encryption key version {}: {}",
+				version, e
+			))
+		})?;
+		self.unwrapped_keys.lock().unwrap().insert(version, key);
+		Ok(key)
+	}
+}
+
+/// Decrypts a key file encrypted with [age](https://age-encryption.org/v1), e.g. by
+/// `age --encrypt --recipient age1... --output keys.age keys`, with the identities of an age
+/// identity file as written by `age-keygen`. Returns the key file's contents, whose lines can be
+/// passed to [`StaticKeyProvider::from_key_entries`].
+pub fn decrypt_age_key_file(ciphertext: &[u8], identity_file: &[u8]) -> Result<String, String> {
+	let identities = age::IdentityFile::from_buffer(identity_file)
+		.and_then(|identity_file| identity_file.into_identities().map_err(std::io::Error::other))
+		.map_err(|e| format!("Invalid age identity file: {}", e))?;
+	let decrypt = || -> Result<Vec<u8>, age::DecryptError> {
+		let decryptor = age::Decryptor::new_buffered(ciphertext)?;
+		let mut reader = decryptor.decrypt(identities.iter().map(|identity| identity.as_ref()))?;
+		let mut plaintext = Vec::new();
+		reader.read_to_end(&mut plaintext)?;
+		Ok(plaintext)
+	};
+	let plaintext = decrypt().map_err(|e| format!("Failed to decrypt the key file: {}", e))?;
+	String::from_utf8(plaintext)
+		.map_err(|_| "The decrypted key file is not valid UTF-8".to_string())
+}
+
+/// Parses key entries of the form `<version>:<key>`, skipping empty entries and comments, and
+/// resolves the current key version, which defaults to the highest one.
+fn parse_key_entries<'a, K>(
+	entries: impl IntoIterator<Item = &'a str>, current_key_version: Option<u32>, key_format: &str,
+	parse_key: impl Fn(u32, &str) -> Result<K, String>,
+) -> Result<(HashMap<u32, K>, u32), String> {
+	let mut keys = HashMap::new();
+	for entry in entries.into_iter().map(str::trim) {
+		if entry.is_empty() || entry.starts_with('#') {
+			continue;
+		}
+		let (version, key) = entry
+			.split_once(':')
+			.ok_or(format!("Key entries must be of the form <version>:{}", key_format))?;
+		let version: u32 = version
+			.trim()
+			.parse()
+			.map_err(|e| format!("Invalid key version {}: {}", version, e))?;
+		let key = parse_key(version, key.trim())?;
+		if keys.insert(version, key).is_some() {
+			return Err(format!("Key version {} is defined more than once", version));
+		}
+	}
+
+	let current_key_version = match current_key_version {
+		Some(version) if keys.contains_key(&version) => version,
+		Some(version) => return Err(format!("Current key version {} is not defined", version)),
+		None => *keys.keys().max().ok_or("At least one key must be defined".to_string())?,
+	};
+	Ok((keys, current_key_version))
+}
+
+fn parse_key_hex(hex: &str) -> Option<[u8; KEY_LEN]> {
+	parse_hex(hex)?.try_into().ok()
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+	if hex.len() % 2 != 0 || !hex.is_ascii() {
+		return None;
+	}
+	(0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{decrypt_age_key_file, KeyProvider, StaticKeyProvider};
+	use age::secrecy::ExposeSecret;
+
+	#[tokio::test]
+	async fn reads_keys_from_age_encrypted_key_files() {
+		let identity = age::x25519::Identity::generate();
+		let identity_file = format!("# created: today\n{}\n", identity.to_string().expose_secret());
+		let key_hex = "11".repeat(32);
+		let key_file = format!("# keys\n1:{}\n", key_hex);
+		let ciphertext = age::encrypt(&identity.to_public(), key_file.as_bytes()).unwrap();
+
+		let contents = decrypt_age_key_file(&ciphertext, identity_file.as_bytes()).unwrap();
+		let keys = StaticKeyProvider::from_key_entries(contents.lines(), None).unwrap();
+		assert_eq!(keys.get_key(1).await.unwrap(), [0x11; 32]);
+
+		let other_identity = age::x25519::Identity::generate();
+		let other_identity_file = other_identity.to_string().expose_secret().to_string();
+		assert!(decrypt_age_key_file(&ciphertext, other_identity_file.as_bytes()).is_err());
+		assert!(decrypt_age_key_file(key_file.as_bytes(), identity_file.as_bytes()).is_err());
+	}
+}
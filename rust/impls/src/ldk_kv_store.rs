@@ -0,0 +1,349 @@
+use api::error::VssError;
+use api::kv_store::KvStore;
+use api::types::{
+	DeleteObjectRequest, GetObjectRequest, KeyValue, ListKeyVersionsRequest, PutObjectRequest,
+};
+use bytes::Bytes;
+use std::io;
+use std::sync::Arc;
+#[cfg(feature = "ldk")]
+use std::{future::Future, sync::OnceLock};
+#[cfg(feature = "ldk")]
+use tokio::runtime::{Handle, Runtime, RuntimeFlavor};
+
+/// The characters LDK allows in namespaces and keys.
+const KEY_ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789_-";
+
+/// The maximum length LDK allows for namespaces and keys.
+const KEY_MAX_LEN: usize = 120;
+
+/// Separates the namespaces from the key in stored keys, outside of [`KEY_ALPHABET`].
+const NAMESPACE_SEPARATOR: char = '#';
+
+/// Exposes a single store of a [`KvStore`] with the semantics of LDK's
+/// `lightning::util::persist::KVStore`, so that a process running LDK, such as LDK Node or Alby Hub,
+/// can persist its state in the same backends the server uses, without going over HTTP.
+///
+/// Objects are stored under the keys LDK Node's VSS client uses, `<primary>#<secondary>#<key>`,
+/// or just `<key>` in the empty primary namespace, so that a store written by a remote client can
+/// be served by the adapter and vice versa, as long as the client does not obfuscate its keys.
+/// Values are stored as they are given; wrap the store in an
+/// [`EncryptedKvStore`](crate::encrypted_store::EncryptedKvStore) to encrypt them at rest.
+///
+/// Writes are unconditional, as LDK does not track object versions. Errors are reported as
+/// [`io::Error`]s, with reads of missing keys failing with [`io::ErrorKind::NotFound`], as LDK
+/// expects.
+///
+/// With the `ldk` feature, the adapter implements LDK's synchronous `KVStore` trait by blocking on
+/// its async methods. Called from a multi-threaded Tokio runtime, it blocks in place on that
+/// runtime; called outside of any runtime, it runs the methods on a runtime of its own. LDK calls
+/// from a current-thread runtime fail, as blocking would stall that runtime, so embedders running
+/// one should call the async methods instead.
+pub struct LdkKvStoreAdapter {
+	store: Arc<dyn KvStore>,
+	user_token: String,
+	store_id: String,
+	/// The runtime of calls made outside of any runtime, created on the first such call.
+	#[cfg(feature = "ldk")]
+	runtime: OnceLock<Runtime>,
+}
+
+impl LdkKvStoreAdapter {
+	/// Constructs an adapter persisting to the store `store_id` of `user_token` in `store`.
+	pub fn new(store: Arc<dyn KvStore>, user_token: String, store_id: String) -> Self {
+		Self {
+			store,
+			user_token,
+			store_id,
+			#[cfg(feature = "ldk")]
+			runtime: OnceLock::new(),
+		}
+	}
+
+	/// Returns the value of `key` in the given namespaces.
+	pub async fn read(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str,
+	) -> Result<Vec<u8>, io::Error> {
+		let key = build_key(primary_namespace, secondary_namespace, key)?;
+		let request = GetObjectRequest { store_id: self.store_id.clone(), key };
+		let response =
+			self.store.get(self.user_token.clone(), request).await.map_err(to_io_error)?;
+		match response.value {
+			Some(key_value) => Ok(key_value.value.to_vec()),
+			None => Err(io::Error::new(io::ErrorKind::NotFound, "Requested key not found.")),
+		}
+	}
+
+	/// Writes `value` to `key` in the given namespaces, replacing any previous value.
+	pub async fn write(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str, value: Vec<u8>,
+	) -> Result<(), io::Error> {
+		let key = build_key(primary_namespace, secondary_namespace, key)?;
+		let request = PutObjectRequest {
+			store_id: self.store_id.clone(),
+			global_version: None,
+			transaction_items: vec![KeyValue { key, version: -1, value: Bytes::from(value) }],
+			delete_items: vec![],
+		};
+		self.store.put(self.user_token.clone(), request).await.map_err(to_io_error)?;
+		Ok(())
+	}
+
+	/// Removes `key` in the given namespaces. Removing a missing key succeeds.
+	///
+	/// Removals are always executed right away, so LDK's `lazy` flag needs no counterpart.
+	pub async fn remove(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str,
+	) -> Result<(), io::Error> {
+		let key = build_key(primary_namespace, secondary_namespace, key)?;
+		let request = DeleteObjectRequest {
+			store_id: self.store_id.clone(),
+			key_value: Some(KeyValue { key, version: -1, value: Bytes::new() }),
+		};
+		self.store.delete(self.user_token.clone(), request).await.map_err(to_io_error)?;
+		Ok(())
+	}
+
+	/// Returns the keys stored in the given namespaces, without their namespaces.
+	pub async fn list(
+		&self, primary_namespace: &str, secondary_namespace: &str,
+	) -> Result<Vec<String>, io::Error> {
+		check_namespaces(primary_namespace, secondary_namespace)?;
+		let prefix = if primary_namespace.is_empty() {
+			String::new()
+		} else {
+			format!(
+				"{}{}{}{}",
+				primary_namespace, NAMESPACE_SEPARATOR, secondary_namespace, NAMESPACE_SEPARATOR
+			)
+		};
+		let mut keys = Vec::new();
+		let mut page_token = None;
+		loop {
+			let request = ListKeyVersionsRequest {
+				store_id: self.store_id.clone(),
+				key_prefix: Some(prefix.clone()),
+				page_size: None,
+				page_token,
+			};
+			let response = self
+				.store
+				.list_key_versions(self.user_token.clone(), request)
+				.await
+				.map_err(to_io_error)?;
+			if response.key_versions.is_empty() {
+				break;
+			}
+			keys.extend(
+				response
+					.key_versions
+					.into_iter()
+					.filter_map(|kv| kv.key.strip_prefix(&prefix).map(str::to_string))
+					// Keys of the empty primary namespace contain no separator, unlike the keys of
+					// all other namespaces, which match the empty prefix too.
+					.filter(|key| !key.contains(NAMESPACE_SEPARATOR)),
+			);
+			page_token = match response.next_page_token {
+				Some(token) if !token.is_empty() => Some(token),
+				_ => break,
+			};
+		}
+		Ok(keys)
+	}
+}
+
+#[cfg(feature = "ldk")]
+impl LdkKvStoreAdapter {
+	/// Runs `future` to completion on behalf of a synchronous caller.
+	fn block_on<T>(
+		&self, future: impl Future<Output = Result<T, io::Error>>,
+	) -> Result<T, io::Error> {
+		match Handle::try_current() {
+			Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+				tokio::task::block_in_place(|| handle.block_on(future))
+			},
+			Ok(_) => Err(io::Error::other(
+				"The LDK KVStore cannot block a current-thread runtime; use the async methods",
+			)),
+			Err(_) => self.runtime()?.block_on(future),
+		}
+	}
+
+	fn runtime(&self) -> Result<&Runtime, io::Error> {
+		if let Some(runtime) = self.runtime.get() {
+			return Ok(runtime);
+		}
+		let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+		// Racing callers may both build a runtime; all but the first one are dropped right away.
+		Ok(self.runtime.get_or_init(|| runtime))
+	}
+}
+
+#[cfg(feature = "ldk")]
+impl Drop for LdkKvStoreAdapter {
+	fn drop(&mut self) {
+		// Dropping a runtime blocks, which panics within an async context.
+		if let Some(runtime) = self.runtime.take() {
+			runtime.shutdown_background();
+		}
+	}
+}
+
+#[cfg(feature = "ldk")]
+impl lightning::util::persist::KVStore for LdkKvStoreAdapter {
+	fn read(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str,
+	) -> Result<Vec<u8>, lightning::io::Error> {
+		self.block_on(LdkKvStoreAdapter::read(self, primary_namespace, secondary_namespace, key))
+			.map_err(Into::into)
+	}
+
+	fn write(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str, buf: &[u8],
+	) -> Result<(), lightning::io::Error> {
+		self.block_on(LdkKvStoreAdapter::write(
+			self,
+			primary_namespace,
+			secondary_namespace,
+			key,
+			buf.to_vec(),
+		))
+		.map_err(Into::into)
+	}
+
+	fn remove(
+		&self, primary_namespace: &str, secondary_namespace: &str, key: &str, _lazy: bool,
+	) -> Result<(), lightning::io::Error> {
+		self.block_on(LdkKvStoreAdapter::remove(self, primary_namespace, secondary_namespace, key))
+			.map_err(Into::into)
+	}
+
+	fn list(
+		&self, primary_namespace: &str, secondary_namespace: &str,
+	) -> Result<Vec<String>, lightning::io::Error> {
+		self.block_on(LdkKvStoreAdapter::list(self, primary_namespace, secondary_namespace))
+			.map_err(Into::into)
+	}
+}
+
+fn build_key(
+	primary_namespace: &str, secondary_namespace: &str, key: &str,
+) -> Result<String, io::Error> {
+	check_namespaces(primary_namespace, secondary_namespace)?;
+	if key.is_empty() {
+		return Err(invalid_input("Keys must not be empty"));
+	}
+	check_component(key)?;
+	if primary_namespace.is_empty() {
+		Ok(key.to_string())
+	} else {
+		Ok(format!(
+			"{}{}{}{}{}",
+			primary_namespace, NAMESPACE_SEPARATOR, secondary_namespace, NAMESPACE_SEPARATOR, key
+		))
+	}
+}
+
+/// Checks namespaces the way LDK does: a secondary namespace requires a primary one.
+fn check_namespaces(primary_namespace: &str, secondary_namespace: &str) -> Result<(), io::Error> {
+	if primary_namespace.is_empty() && !secondary_namespace.is_empty() {
+		return Err(invalid_input("A secondary namespace requires a primary namespace"));
+	}
+	check_component(primary_namespace)?;
+	check_component(secondary_namespace)
+}
+
+fn check_component(component: &str) -> Result<(), io::Error> {
+	if component.len() > KEY_MAX_LEN {
+		return Err(invalid_input("Namespaces and keys must not exceed 120 characters"));
+	}
+	if !component.chars().all(|c| KEY_ALPHABET.contains(c)) {
+		return Err(invalid_input("Namespaces and keys must only contain [A-Za-z0-9_-]"));
+	}
+	Ok(())
+}
+
+fn invalid_input(msg: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn to_io_error(e: VssError) -> io::Error {
+	match e {
+		VssError::NoSuchKeyError(msg) => io::Error::new(io::ErrorKind::NotFound, msg),
+		e => io::Error::other(e.to_string()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::LdkKvStoreAdapter;
+	use crate::in_memory_store::InMemoryBackend;
+	use std::io;
+	use std::sync::Arc;
+
+	#[tokio::test]
+	async fn persists_ldk_namespaces() {
+		let adapter = LdkKvStoreAdapter::new(
+			Arc::new(InMemoryBackend::new()),
+			"user".to_string(),
+			"ldk".to_string(),
+		);
+
+		adapter.write("", "", "manager", b"manager".to_vec()).await.unwrap();
+		adapter.write("monitors", "", "chan1", b"monitor 1".to_vec()).await.unwrap();
+		adapter.write("monitors", "", "chan2", b"monitor 2".to_vec()).await.unwrap();
+		adapter.write("monitors", "updates", "chan1", b"update".to_vec()).await.unwrap();
+		// Writes are unconditional.
+		adapter.write("monitors", "", "chan2", b"monitor 2'".to_vec()).await.unwrap();
+
+		assert_eq!(adapter.read("", "", "manager").await.unwrap(), b"manager");
+		assert_eq!(adapter.read("monitors", "", "chan2").await.unwrap(), b"monitor 2'");
+		let error = adapter.read("monitors", "", "chan3").await.unwrap_err();
+		assert_eq!(error.kind(), io::ErrorKind::NotFound);
+
+		assert_eq!(adapter.list("", "").await.unwrap(), vec!["manager"]);
+		assert_eq!(adapter.list("monitors", "").await.unwrap(), vec!["chan1", "chan2"]);
+		assert_eq!(adapter.list("monitors", "updates").await.unwrap(), vec!["chan1"]);
+		assert!(adapter.list("other", "").await.unwrap().is_empty());
+
+		adapter.remove("monitors", "", "chan1").await.unwrap();
+		adapter.remove("monitors", "", "chan1").await.unwrap();
+		assert_eq!(adapter.list("monitors", "").await.unwrap(), vec!["chan2"]);
+		assert_eq!(adapter.list("monitors", "updates").await.unwrap(), vec!["chan1"]);
+
+		// Namespaces and keys are validated as LDK validates them.
+		for (primary, secondary, key) in
+			[("", "updates", "chan1"), ("monitors", "", ""), ("monitors", "", "chan#1")]
+		{
+			let error = adapter.write(primary, secondary, key, vec![]).await.unwrap_err();
+			assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+		}
+	}
+
+	#[cfg(feature = "ldk")]
+	#[tokio::test(flavor = "multi_thread")]
+	async fn implements_the_ldk_kv_store() {
+		use lightning::util::persist::KVStore;
+
+		let adapter = LdkKvStoreAdapter::new(
+			Arc::new(InMemoryBackend::new()),
+			"user".to_string(),
+			"ldk".to_string(),
+		);
+		KVStore::write(&adapter, "monitors", "", "chan1", b"monitor 1").unwrap();
+		assert_eq!(KVStore::read(&adapter, "monitors", "", "chan1").unwrap(), b"monitor 1");
+		assert_eq!(KVStore::list(&adapter, "monitors", "").unwrap(), vec!["chan1"]);
+		let error = KVStore::write(&adapter, "", "updates", "chan1", b"").unwrap_err();
+		assert_eq!(error.kind(), lightning::io::ErrorKind::InvalidInput);
+
+		// Outside of any runtime, the adapter runs the calls on a runtime of its own.
+		let adapter = tokio::task::spawn_blocking(move || {
+			KVStore::remove(&adapter, "monitors", "", "chan1", false).unwrap();
+			assert!(KVStore::list(&adapter, "monitors", "").unwrap().is_empty());
+			adapter
+		})
+		.await
+		.unwrap();
+		drop(adapter);
+	}
+}
@@ -11,8 +11,129 @@
 #![deny(rustdoc::private_intra_doc_links)]
 #![deny(missing_docs)]
 
+/// Contains the [`AdminOperationStore`] trait persisting destructive admin operations until they
+/// are approved.
+///
+/// [`AdminOperationStore`]: admin_operation_store::AdminOperationStore
+pub mod admin_operation_store;
+/// Contains the [`AuditStore`] trait keeping an append-only log of the mutating operations
+/// performed on stores.
+///
+/// [`AuditStore`]: audit_store::AuditStore
+pub mod audit_store;
+/// Contains [`connect_backend`], constructing the storage backend selected by configuration.
+///
+/// [`connect_backend`]: backend_factory::connect_backend
+pub mod backend_factory;
+/// Contains the [`BackupStatusProvider`] trait reporting whether a backend's database is
+/// continuously backed up.
+///
+/// [`BackupStatusProvider`]: backup_status::BackupStatusProvider
+pub mod backup_status;
+/// Contains the [`ChangeFeed`] trait publishing committed changes of stored objects to all
+/// server instances sharing a backend.
+///
+/// [`ChangeFeed`]: change_feed::ChangeFeed
+pub mod change_feed;
+/// Contains a binary delta encoding for storing old object versions compactly against the newest
+/// one.
+pub mod delta;
+/// Contains the [`DeviceRegistry`] trait used to detect store accesses from new devices.
+///
+/// [`DeviceRegistry`]: device_registry::DeviceRegistry
+pub mod device_registry;
+/// Contains [Amazon DynamoDB](https://aws.amazon.com/dynamodb/) based backend implementation for
+/// VSS.
+#[cfg(feature = "dynamodb")]
+pub mod dynamodb_store;
+/// Contains a [`KvStore`] decorator encrypting object values at rest.
+///
+/// [`KvStore`]: api::kv_store::KvStore
+pub mod encrypted_store;
+/// Contains [`ExportRecipient`], encrypting store exports to a public key supplied by the user.
+///
+/// [`ExportRecipient`]: export_encryption::ExportRecipient
+pub mod export_encryption;
+/// Contains a [`KvStore`] decorator retrying puts which conflict only on the global version.
+///
+/// [`KvStore`]: api::kv_store::KvStore
+pub mod global_version_retrying_store;
+/// Contains the [`HealthCheck`] trait telling whether a backend can currently serve requests.
+///
+/// [`HealthCheck`]: health_check::HealthCheck
+pub mod health_check;
+/// Contains an in-memory backend implementation for VSS, meant for tests and demos.
+pub mod in_memory_store;
+/// Contains the [`KeyHistory`] trait restoring stores from the previous versions of their objects.
+///
+/// [`KeyHistory`]: key_history::KeyHistory
+pub mod key_history;
+/// Contains the [`KeyProvider`] trait supplying versioned keys for encryption at rest.
+///
+/// [`KeyProvider`]: key_provider::KeyProvider
+pub mod key_provider;
+/// Contains [`LdkKvStoreAdapter`], exposing a store of a [`KvStore`] with the semantics of LDK's
+/// `KVStore` trait.
+///
+/// [`LdkKvStoreAdapter`]: ldk_kv_store::LdkKvStoreAdapter
+/// [`KvStore`]: api::kv_store::KvStore
+pub mod ldk_kv_store;
+/// Contains the [`MaintenanceStore`] trait cleaning up and correcting the data a backend keeps
+/// about objects.
+///
+/// [`MaintenanceStore`]: maintenance_store::MaintenanceStore
+pub mod maintenance_store;
+/// Contains a [`KvStore`] decorator merging conflicting writes with configurable strategies.
+///
+/// [`KvStore`]: api::kv_store::KvStore
+pub mod merging_store;
 mod migrations;
+/// Contains a [`KvStore`] decorator dispatching changes of stored objects to registered
+/// [`KvStoreObserver`]s.
+///
+/// [`KvStore`]: api::kv_store::KvStore
+/// [`KvStoreObserver`]: api::observer::KvStoreObserver
+pub mod observed_store;
+/// Contains a [`KvStore`] decorator keeping large values in an object store such as S3 or GCS.
+///
+/// [`KvStore`]: api::kv_store::KvStore
+#[cfg(feature = "object-store")]
+pub mod offloading_store;
+/// Contains the [`PendingDeletionStore`] trait persisting deletions held back until they become
+/// executable.
+///
+/// [`PendingDeletionStore`]: pending_deletion_store::PendingDeletionStore
+pub mod pending_deletion_store;
+/// Contains the [`PoolStatsProvider`] trait exposing the state of database connection pools.
+///
+/// [`PoolStatsProvider`]: pool_stats::PoolStatsProvider
+pub mod pool_stats;
 /// Contains [PostgreSQL](https://www.postgresql.org/) based backend implementation for VSS.
 pub mod postgres_store;
+/// Contains the [`RecordStore`] trait giving maintenance jobs raw access to stored records.
+///
+/// [`RecordStore`]: record_store::RecordStore
+pub mod record_store;
+/// Contains [Redis](https://redis.io/) based backend implementation for VSS.
+#[cfg(feature = "redis")]
+pub mod redis_store;
+/// Contains the [`ShareTokenStore`] trait persisting read-only share tokens for single objects.
+///
+/// [`ShareTokenStore`]: share_token_store::ShareTokenStore
+pub mod share_token_store;
+/// Contains the [`StoreAcl`] trait persisting collaborator access to stores of other users.
+///
+/// [`StoreAcl`]: store_acl::StoreAcl
+pub mod store_acl;
+/// Contains the [`TombstoneStore`] trait keeping deleted objects restorable until purged.
+///
+/// [`TombstoneStore`]: tombstone_store::TombstoneStore
+pub mod tombstone_store;
+/// Contains the [`UsageStore`] trait reporting the storage occupied by users.
+///
+/// [`UsageStore`]: usage_store::UsageStore
+pub mod usage_store;
+/// Contains the compression of object values at rest applied by the PostgreSQL backend.
+pub mod value_compression;
 
 extern crate api;
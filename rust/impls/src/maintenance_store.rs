@@ -0,0 +1,43 @@
+use api::error::VssError;
+use async_trait::async_trait;
+
+/// A table whose dead tuples suggest that it should be vacuumed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VacuumHint {
+	/// The name of the table.
+	pub table: String,
+	/// The estimated number of live rows in the table.
+	pub live_tuples: u64,
+	/// The estimated number of dead rows in the table, i.e. rows deleted or replaced but not yet
+	/// reclaimed.
+	pub dead_tuples: u64,
+	/// The time the table was last vacuumed, manually or automatically, in seconds since the Unix
+	/// epoch, if ever.
+	pub last_vacuumed_at: Option<u64>,
+}
+
+/// Housekeeping of the data a backend keeps about objects, run periodically by the server to
+/// clean up after deletions and to correct derived data which drifted.
+#[async_trait]
+pub trait MaintenanceStore: Send + Sync {
+	/// Deletes at most `limit` share tokens which expired before `expired_before`, in seconds since
+	/// the Unix epoch, oldest first. Returns the number of deleted tokens.
+	async fn reap_expired_share_tokens(
+		&self, expired_before: u64, limit: i64,
+	) -> Result<u64, VssError>;
+
+	/// Recomputes the storage usage recorded for each user from their objects, correcting the
+	/// usage of users whose recorded usage drifted. Returns the number of corrected users.
+	async fn recompute_usage(&self) -> Result<u64, VssError>;
+
+	/// Deletes at most `limit` records left behind by objects which no longer exist, i.e. pending
+	/// deletions of objects deleted or written since, which can no longer be executed, and the
+	/// recorded usage of users without objects. Returns the number of deleted records.
+	async fn delete_orphans(&self, limit: i64) -> Result<u64, VssError>;
+
+	/// Returns the tables of the backend with at least `min_dead_tuples` dead rows, making up at
+	/// least `min_dead_ratio` of their rows, by descending number of dead rows.
+	async fn vacuum_hints(
+		&self, min_dead_tuples: u64, min_dead_ratio: f64,
+	) -> Result<Vec<VacuumHint>, VssError>;
+}
@@ -35,6 +35,119 @@ pub(crate) const MIGRATIONS: &[&str] = &[
 	    PRIMARY KEY (user_token, store_id, key)
 	);",
 	"ALTER TABLE vss_db DROP CONSTRAINT IF EXISTS vss_db_store_id_check;",
+	// Fingerprints of the devices which accessed each store, used to detect accesses from new devices
+	"CREATE TABLE vss_known_devices (
+	    user_token character varying(120) NOT NULL,
+	    store_id character varying(120) NOT NULL,
+	    device_fingerprint bytea NOT NULL,
+	    first_seen_at TIMESTAMP WITH TIME ZONE NOT NULL,
+	    PRIMARY KEY (user_token, store_id, device_fingerprint)
+	);",
+	// Read-only share tokens granting access to single objects, identified by the hash of their secret
+	"CREATE TABLE vss_share_tokens (
+	    token_sha256 bytea PRIMARY KEY,
+	    user_token character varying(120) NOT NULL,
+	    store_id character varying(120) NOT NULL,
+	    key character varying(600) NOT NULL,
+	    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+	    expires_at TIMESTAMP WITH TIME ZONE NOT NULL
+	);",
+	"CREATE INDEX vss_share_tokens_user_token_idx ON vss_share_tokens (user_token, store_id);",
+	// Access to stores granted by their owners to other users
+	"CREATE TABLE vss_store_grants (
+	    owner_user_token character varying(120) NOT NULL,
+	    store_id character varying(120) NOT NULL,
+	    grantee_user_token character varying(120) NOT NULL,
+	    access character varying(10) NOT NULL CHECK (access IN ('read', 'write')),
+	    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+	    PRIMARY KEY (owner_user_token, store_id, grantee_user_token)
+	);",
+	"CREATE INDEX vss_store_grants_grantee_idx ON vss_store_grants (grantee_user_token);",
+	// The number of requests made by each user, per hour
+	"CREATE TABLE vss_request_counts (
+	    user_token character varying(120) NOT NULL,
+	    hour TIMESTAMP WITH TIME ZONE NOT NULL,
+	    requests bigint NOT NULL,
+	    PRIMARY KEY (user_token, hour)
+	);",
+	"CREATE INDEX vss_request_counts_hour_idx ON vss_request_counts (hour);",
+	// The total size of values and the number of objects of each user, excluding global versions,
+	// updated along with vss_db so that usage can be looked up without scanning it
+	"CREATE TABLE vss_usage (
+	    user_token character varying(120) PRIMARY KEY,
+	    bytes bigint NOT NULL,
+	    objects bigint NOT NULL
+	);",
+	"INSERT INTO vss_usage (user_token, bytes, objects)
+	    SELECT user_token, coalesce(sum(octet_length(value)), 0), count(*)
+	    FROM vss_db WHERE key <> 'global_version' GROUP BY user_token;",
+	// Deletions of objects under critical key prefixes, held back until they become executable
+	"CREATE TABLE vss_pending_deletions (
+	    user_token character varying(120) NOT NULL,
+	    store_id character varying(120) NOT NULL,
+	    key character varying(600) NOT NULL,
+	    version bigint NOT NULL,
+	    requested_at TIMESTAMP WITH TIME ZONE NOT NULL,
+	    executable_after TIMESTAMP WITH TIME ZONE NOT NULL,
+	    PRIMARY KEY (user_token, store_id, key)
+	);",
+	"CREATE INDEX vss_pending_deletions_executable_after_idx ON vss_pending_deletions (executable_after);",
+	// The last version of deleted objects, kept until purged when soft deletion is enabled
+	"CREATE TABLE vss_tombstones (
+	    user_token character varying(120) NOT NULL,
+	    store_id character varying(120) NOT NULL,
+	    key character varying(600) NOT NULL,
+	    value bytea NULL,
+	    version bigint NOT NULL,
+	    deleted_at TIMESTAMP WITH TIME ZONE NOT NULL,
+	    PRIMARY KEY (user_token, store_id, key)
+	);",
+	"CREATE INDEX vss_tombstones_deleted_at_idx ON vss_tombstones (deleted_at);",
+	// The previous versions of objects, of which the newest are kept when key history is enabled
+	"CREATE TABLE vss_db_history (
+	    id bigserial PRIMARY KEY,
+	    user_token character varying(120) NOT NULL,
+	    store_id character varying(120) NOT NULL,
+	    key character varying(600) NOT NULL,
+	    value bytea NULL,
+	    version bigint NOT NULL,
+	    archived_at TIMESTAMP WITH TIME ZONE NOT NULL
+	);",
+	"CREATE INDEX vss_db_history_key_idx ON vss_db_history (user_token, store_id, key, id);",
+	// When archived versions were created and written, to restore stores to a point in time
+	"ALTER TABLE vss_db_history ADD COLUMN created_at TIMESTAMP WITH TIME ZONE NULL,
+	    ADD COLUMN last_updated_at TIMESTAMP WITH TIME ZONE NULL;",
+	// Expired share tokens are reaped periodically
+	"CREATE INDEX vss_share_tokens_expires_at_idx ON vss_share_tokens (expires_at);",
+	// The mutating operations performed on stores, recorded when the audit log is enabled
+	"CREATE TABLE vss_audit_log (
+	    id bigserial PRIMARY KEY,
+	    recorded_at TIMESTAMP WITH TIME ZONE NOT NULL,
+	    user_token character varying(120) NOT NULL,
+	    caller_user_token character varying(120) NULL,
+	    store_id character varying(120) NOT NULL,
+	    operation character varying(32) NOT NULL,
+	    keys jsonb NOT NULL,
+	    source_ip character varying(45) NULL,
+	    auth_method character varying(32) NULL,
+	    succeeded boolean NOT NULL
+	);",
+	"CREATE INDEX vss_audit_log_recorded_at_idx ON vss_audit_log (recorded_at);",
+	// Audit records are append-only, and only deleted once past their retention
+	"CREATE FUNCTION vss_audit_log_reject_update() RETURNS trigger LANGUAGE plpgsql AS $$
+	    BEGIN RAISE EXCEPTION 'vss_audit_log is append-only'; END; $$;",
+	"CREATE TRIGGER vss_audit_log_append_only BEFORE UPDATE ON vss_audit_log
+	    FOR EACH ROW EXECUTE FUNCTION vss_audit_log_reject_update();",
+	// Destructive operations requested through the admin API, held until they are approved
+	"CREATE TABLE vss_admin_operations (
+	    approval_token character varying(64) PRIMARY KEY,
+	    operation character varying(32) NOT NULL,
+	    parameters text NOT NULL,
+	    requested_by character varying(120) NOT NULL,
+	    requested_at TIMESTAMP WITH TIME ZONE NOT NULL,
+	    executable_after TIMESTAMP WITH TIME ZONE NOT NULL,
+	    expires_at TIMESTAMP WITH TIME ZONE NOT NULL
+	);",
 ];
 #[cfg(test)]
 pub(crate) const DUMMY_MIGRATION: &str = "SELECT 1 WHERE FALSE;";
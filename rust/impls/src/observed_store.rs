@@ -0,0 +1,179 @@
+use api::error::VssError;
+use api::kv_store::KvStore;
+use api::observer::KvStoreObserver;
+use api::types::{
+	AppendObjectRequest, AppendObjectResponse, DeleteObjectRequest, DeleteObjectResponse,
+	GetObjectRequest, GetObjectResponse, GetObjectVersionRequest, GetObjectsAtomicRequest,
+	GetObjectsAtomicResponse, ListKeyVersionsRequest, ListKeyVersionsResponse, ListObjectsRequest,
+	ListObjectsResponse, PutObjectRequest, PutObjectResponse,
+};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// A [`KvStore`] decorator dispatching the outcome of writes to registered [`KvStoreObserver`]s,
+/// in the order they were registered.
+///
+/// Observers are only told about writes that completed, or about puts that conflicted; other
+/// failures are not dispatched.
+pub struct ObservedKvStore {
+	inner: Arc<dyn KvStore>,
+	observers: Vec<Arc<dyn KvStoreObserver>>,
+}
+
+impl ObservedKvStore {
+	/// Constructs an [`ObservedKvStore`] wrapping `inner` and dispatching to `observers`.
+	pub fn new(inner: Arc<dyn KvStore>, observers: Vec<Arc<dyn KvStoreObserver>>) -> Self {
+		Self { inner, observers }
+	}
+}
+
+#[async_trait]
+impl KvStore for ObservedKvStore {
+	async fn get(
+		&self, user_token: String, request: GetObjectRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		self.inner.get(user_token, request).await
+	}
+
+	async fn put(
+		&self, user_token: String, request: PutObjectRequest,
+	) -> Result<PutObjectResponse, VssError> {
+		// Values are reference counted, so keeping the request around does not copy them.
+		let result = self.inner.put(user_token.clone(), request.clone()).await;
+		match &result {
+			Ok(_) => self.observers.iter().for_each(|o| o.on_put(&user_token, &request)),
+			Err(VssError::ConflictError(_)) => {
+				self.observers.iter().for_each(|o| o.on_conflict(&user_token, &request))
+			},
+			Err(_) => {},
+		}
+		result
+	}
+
+	async fn delete(
+		&self, user_token: String, request: DeleteObjectRequest,
+	) -> Result<DeleteObjectResponse, VssError> {
+		let response = self.inner.delete(user_token.clone(), request.clone()).await?;
+		self.observers.iter().for_each(|o| o.on_delete(&user_token, &request));
+		Ok(response)
+	}
+
+	async fn list_key_versions(
+		&self, user_token: String, request: ListKeyVersionsRequest,
+	) -> Result<ListKeyVersionsResponse, VssError> {
+		self.inner.list_key_versions(user_token, request).await
+	}
+
+	async fn list_objects(
+		&self, user_token: String, request: ListObjectsRequest,
+	) -> Result<ListObjectsResponse, VssError> {
+		self.inner.list_objects(user_token, request).await
+	}
+
+	async fn get_objects_atomic(
+		&self, user_token: String, request: GetObjectsAtomicRequest,
+	) -> Result<GetObjectsAtomicResponse, VssError> {
+		self.inner.get_objects_atomic(user_token, request).await
+	}
+
+	async fn get_object_version(
+		&self, user_token: String, request: GetObjectVersionRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		self.inner.get_object_version(user_token, request).await
+	}
+
+	async fn append(
+		&self, user_token: String, request: AppendObjectRequest,
+	) -> Result<AppendObjectResponse, VssError> {
+		let response = self.inner.append(user_token.clone(), request.clone()).await?;
+		self.observers.iter().for_each(|o| o.on_append(&user_token, &request, response.version));
+		Ok(response)
+	}
+
+	async fn delete_store(&self, user_token: String, store_id: String) -> Result<u64, VssError> {
+		self.inner.delete_store(user_token, store_id).await
+	}
+
+	async fn delete_user(&self, user_token: String) -> Result<u64, VssError> {
+		self.inner.delete_user(user_token).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::ObservedKvStore;
+	use crate::in_memory_store::InMemoryBackend;
+	use api::kv_store::KvStore;
+	use api::observer::KvStoreObserver;
+	use api::types::{AppendObjectRequest, DeleteObjectRequest, KeyValue, PutObjectRequest};
+
+	use bytes::Bytes;
+	use std::sync::{Arc, Mutex};
+
+	#[derive(Default)]
+	struct RecordingObserver {
+		events: Mutex<Vec<String>>,
+	}
+
+	impl KvStoreObserver for RecordingObserver {
+		fn on_put(&self, _user_token: &str, request: &PutObjectRequest) {
+			let key = &request.transaction_items[0].key;
+			self.events.lock().unwrap().push(format!("put {}", key));
+		}
+
+		fn on_delete(&self, _user_token: &str, request: &DeleteObjectRequest) {
+			let key = &request.key_value.as_ref().unwrap().key;
+			self.events.lock().unwrap().push(format!("delete {}", key));
+		}
+
+		fn on_append(&self, _user_token: &str, request: &AppendObjectRequest, version: i64) {
+			self.events.lock().unwrap().push(format!("append {} {}", request.key, version));
+		}
+
+		fn on_conflict(&self, _user_token: &str, request: &PutObjectRequest) {
+			let key = &request.transaction_items[0].key;
+			self.events.lock().unwrap().push(format!("conflict {}", key));
+		}
+	}
+
+	fn key_value(key: &str, version: i64) -> KeyValue {
+		KeyValue { key: key.to_string(), version, value: Bytes::from_static(b"value") }
+	}
+
+	fn put_request(key: &str, version: i64) -> PutObjectRequest {
+		PutObjectRequest {
+			store_id: "store".to_string(),
+			global_version: None,
+			transaction_items: vec![key_value(key, version)],
+			delete_items: vec![],
+		}
+	}
+
+	#[tokio::test]
+	async fn dispatches_writes_to_all_observers() {
+		let first = Arc::new(RecordingObserver::default());
+		let second = Arc::new(RecordingObserver::default());
+		let store = ObservedKvStore::new(
+			Arc::new(InMemoryBackend::new()),
+			vec![first.clone(), second.clone()],
+		);
+
+		store.put("token".to_string(), put_request("a", 0)).await.unwrap();
+		assert!(store.put("token".to_string(), put_request("a", 0)).await.is_err());
+		let append = AppendObjectRequest {
+			store_id: "store".to_string(),
+			key: "a".to_string(),
+			value: Bytes::from_static(b"more"),
+		};
+		store.append("token".to_string(), append).await.unwrap();
+		let delete = DeleteObjectRequest {
+			store_id: "store".to_string(),
+			key_value: Some(key_value("a", 2)),
+		};
+		store.delete("token".to_string(), delete).await.unwrap();
+
+		let expected = vec!["put a", "conflict a", "append a 2", "delete a"];
+		assert_eq!(*first.events.lock().unwrap(), expected);
+		assert_eq!(*second.events.lock().unwrap(), expected);
+	}
+}
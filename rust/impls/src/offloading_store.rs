@@ -0,0 +1,372 @@
+use api::error::VssError;
+use api::kv_store::{KvStore, GLOBAL_VERSION_KEY};
+use api::types::{
+	DeleteObjectRequest, DeleteObjectResponse, GetObjectRequest, GetObjectResponse,
+	GetObjectVersionRequest, GetObjectsAtomicRequest, GetObjectsAtomicResponse,
+	ListKeyVersionsRequest, ListKeyVersionsResponse, PutObjectRequest, PutObjectResponse,
+};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::{StreamExt, TryStreamExt};
+use object_store::path::Path;
+use object_store::ObjectStore;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// Marks a value kept by the wrapped store as a reference to an object in the object store.
+const REFERENCE_MAGIC: &[u8] = b"VSSOBJ";
+const REFERENCE_FORMAT_VERSION: u8 = 1;
+
+/// The size from which values are offloaded, unless configured otherwise.
+pub const DEFAULT_MIN_OFFLOADED_VALUE_SIZE: usize = 64 * 1024;
+
+/// A [`KvStore`] decorator which keeps large values in an [`ObjectStore`], such as Amazon S3 or
+/// Google Cloud Storage, and only references to them in the wrapped store.
+///
+/// Every offloaded value is written to a new object before the reference is put into the wrapped
+/// store, which thereby remains the index of all object versions and enforces the versioning
+/// semantics of conditional writes as before. Objects are never overwritten, and are deleted once
+/// the reference to them was replaced or deleted. Objects of failed or racing writes may be left
+/// behind, but are removed along with their store or user.
+///
+/// Values smaller than the configured size are kept in the wrapped store as-is, except for values
+/// which happen to start like a reference.
+pub struct OffloadingKvStore {
+	inner: Arc<dyn KvStore>,
+	objects: Arc<dyn ObjectStore>,
+	min_offloaded_value_size: usize,
+}
+
+impl OffloadingKvStore {
+	/// Constructs an [`OffloadingKvStore`] wrapping `inner`, which offloads values of at least
+	/// `min_offloaded_value_size` bytes to `objects`.
+	pub fn new(
+		inner: Arc<dyn KvStore>, objects: Arc<dyn ObjectStore>, min_offloaded_value_size: usize,
+	) -> Self {
+		Self { inner, objects, min_offloaded_value_size }
+	}
+
+	fn should_offload(&self, value: &[u8]) -> bool {
+		value.len() >= self.min_offloaded_value_size || value.starts_with(REFERENCE_MAGIC)
+	}
+
+	/// Writes the value to a new object, returning its location.
+	async fn offload(
+		&self, user_token: &str, store_id: &str, value: Bytes,
+	) -> Result<Path, VssError> {
+		let name = format!("{:032x}", rand::random::<u128>());
+		let location = store_prefix(user_token, store_id).child(name);
+		self.objects.put(&location, value.into()).await.map_err(object_store_error)?;
+		Ok(location)
+	}
+
+	/// Returns the location of the object the given key refers to, if any.
+	async fn stored_location(
+		&self, user_token: &str, store_id: &str, key: &str,
+	) -> Result<Option<Path>, VssError> {
+		let request = GetObjectRequest { store_id: store_id.to_string(), key: key.to_string() };
+		match self.inner.get(user_token.to_string(), request).await {
+			Ok(response) => Ok(response.value.and_then(|kv| reference_location(&kv.value))),
+			Err(VssError::NoSuchKeyError(_)) => Ok(None),
+			Err(e) => Err(e),
+		}
+	}
+
+	/// Deletes objects no longer referenced. Failures only leave unreferenced objects behind, so
+	/// they are logged rather than returned.
+	async fn delete_objects(&self, locations: Vec<Path>) {
+		if locations.is_empty() {
+			return;
+		}
+		let locations = futures_util::stream::iter(locations.into_iter().map(Ok)).boxed();
+		let mut results = self.objects.delete_stream(locations);
+		while let Some(result) = results.next().await {
+			if let Err(e) = result {
+				tracing::warn!(error = %e, "Failed to delete unreferenced object");
+			}
+		}
+	}
+
+	async fn delete_prefix(&self, prefix: &Path) -> Result<(), VssError> {
+		let locations = self.objects.list(Some(prefix)).map_ok(|meta| meta.location).boxed();
+		self.objects
+			.delete_stream(locations)
+			.try_collect::<Vec<_>>()
+			.await
+			.map_err(object_store_error)?;
+		Ok(())
+	}
+}
+
+/// Returns the location `value` refers to, or `None` if `value` is not a reference.
+fn reference_location(value: &[u8]) -> Option<Path> {
+	let location =
+		value.strip_prefix(REFERENCE_MAGIC)?.strip_prefix(&[REFERENCE_FORMAT_VERSION])?;
+	Path::parse(std::str::from_utf8(location).ok()?).ok()
+}
+
+fn reference(location: &Path) -> Bytes {
+	let mut reference = REFERENCE_MAGIC.to_vec();
+	reference.push(REFERENCE_FORMAT_VERSION);
+	reference.extend_from_slice(location.as_ref().as_bytes());
+	Bytes::from(reference)
+}
+
+/// Objects are grouped by user and store, hashed so that no user token ends up in object paths.
+fn user_prefix(user_token: &str) -> Path {
+	Path::from(format!("{:x}", Sha256::digest(user_token)))
+}
+
+fn store_prefix(user_token: &str, store_id: &str) -> Path {
+	user_prefix(user_token).child(format!("{:x}", Sha256::digest(store_id)))
+}
+
+fn object_store_error(e: object_store::Error) -> VssError {
+	VssError::InternalServerError(format!("Object store request failed: {}", e))
+}
+
+// `append` keeps the default read-modify-write implementation, which offloads the appended value
+// to a new object once it grows large enough.
+#[async_trait]
+impl KvStore for OffloadingKvStore {
+	async fn get(
+		&self, user_token: String, request: GetObjectRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		let mut response = self.inner.get(user_token, request).await?;
+		if let Some(kv) = response.value.as_mut() {
+			if let Some(location) = reference_location(&kv.value) {
+				let object = self.objects.get(&location).await.map_err(object_store_error)?;
+				kv.value = object.bytes().await.map_err(object_store_error)?;
+			}
+		}
+		Ok(response)
+	}
+
+	async fn put(
+		&self, user_token: String, mut request: PutObjectRequest,
+	) -> Result<PutObjectResponse, VssError> {
+		// Objects of all replaced or deleted values become unreferenced once the put succeeds.
+		let mut replaced = Vec::new();
+		let keys = request.transaction_items.iter().chain(request.delete_items.iter());
+		for kv in keys.filter(|kv| kv.key != GLOBAL_VERSION_KEY) {
+			if let Some(location) =
+				self.stored_location(&user_token, &request.store_id, &kv.key).await?
+			{
+				replaced.push(location);
+			}
+		}
+
+		let mut offloaded = Vec::new();
+		for kv in request.transaction_items.iter_mut() {
+			if !self.should_offload(&kv.value) {
+				continue;
+			}
+			let value = std::mem::take(&mut kv.value);
+			match self.offload(&user_token, &request.store_id, value).await {
+				Ok(location) => {
+					kv.value = reference(&location);
+					offloaded.push(location);
+				},
+				Err(e) => {
+					self.delete_objects(offloaded).await;
+					return Err(e);
+				},
+			}
+		}
+
+		match self.inner.put(user_token, request).await {
+			Ok(response) => {
+				self.delete_objects(replaced).await;
+				Ok(response)
+			},
+			Err(e) => {
+				self.delete_objects(offloaded).await;
+				Err(e)
+			},
+		}
+	}
+
+	async fn delete(
+		&self, user_token: String, request: DeleteObjectRequest,
+	) -> Result<DeleteObjectResponse, VssError> {
+		let key = match request.key_value.as_ref() {
+			Some(kv) => kv.key.clone(),
+			None => return self.inner.delete(user_token, request).await,
+		};
+		let store_id = request.store_id.clone();
+		let location = self.stored_location(&user_token, &store_id, &key).await?;
+		let response = self.inner.delete(user_token.clone(), request).await?;
+		// Deleting at a stale version is a no-op, so only delete the object if the reference to
+		// it is gone.
+		if let Some(location) = location {
+			if self.stored_location(&user_token, &store_id, &key).await?.as_ref() != Some(&location)
+			{
+				self.delete_objects(vec![location]).await;
+			}
+		}
+		Ok(response)
+	}
+
+	async fn list_key_versions(
+		&self, user_token: String, request: ListKeyVersionsRequest,
+	) -> Result<ListKeyVersionsResponse, VssError> {
+		self.inner.list_key_versions(user_token, request).await
+	}
+
+	async fn get_objects_atomic(
+		&self, user_token: String, request: GetObjectsAtomicRequest,
+	) -> Result<GetObjectsAtomicResponse, VssError> {
+		// Offloaded objects are never changed, so fetching them after the snapshot is consistent.
+		let mut response = self.inner.get_objects_atomic(user_token, request).await?;
+		for kv in response.objects.iter_mut() {
+			if let Some(location) = reference_location(&kv.value) {
+				let object = self.objects.get(&location).await.map_err(object_store_error)?;
+				kv.value = object.bytes().await.map_err(object_store_error)?;
+			}
+		}
+		Ok(response)
+	}
+
+	async fn get_object_version(
+		&self, user_token: String, request: GetObjectVersionRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		// Objects of replaced values are deleted, so previous versions may no longer be available.
+		let mut response = self.inner.get_object_version(user_token, request).await?;
+		if let Some(kv) = response.value.as_mut() {
+			if let Some(location) = reference_location(&kv.value) {
+				let object = self.objects.get(&location).await.map_err(|e| match e {
+					object_store::Error::NotFound { .. } => VssError::NoSuchKeyError(
+						"The value of the requested version is no longer available.".to_string(),
+					),
+					e => object_store_error(e),
+				})?;
+				kv.value = object.bytes().await.map_err(object_store_error)?;
+			}
+		}
+		Ok(response)
+	}
+
+	async fn delete_store(&self, user_token: String, store_id: String) -> Result<u64, VssError> {
+		let prefix = store_prefix(&user_token, &store_id);
+		let num_items = self.inner.delete_store(user_token, store_id).await?;
+		self.delete_prefix(&prefix).await?;
+		Ok(num_items)
+	}
+
+	async fn delete_user(&self, user_token: String) -> Result<u64, VssError> {
+		let prefix = user_prefix(&user_token);
+		let num_items = self.inner.delete_user(user_token).await?;
+		self.delete_prefix(&prefix).await?;
+		Ok(num_items)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{reference_location, OffloadingKvStore};
+	use crate::in_memory_store::InMemoryBackend;
+	use api::define_kv_store_tests;
+	use api::kv_store::KvStore;
+	use api::types::{DeleteObjectRequest, GetObjectRequest, KeyValue, PutObjectRequest};
+
+	use bytes::Bytes;
+	use futures_util::TryStreamExt;
+	use object_store::memory::InMemory;
+	use object_store::ObjectStore;
+	use std::sync::Arc;
+
+	const MIN_OFFLOADED_VALUE_SIZE: usize = 8;
+
+	define_kv_store_tests!(
+		OffloadingKvStoreTest,
+		OffloadingKvStore,
+		OffloadingKvStore::new(
+			Arc::new(InMemoryBackend::new()),
+			Arc::new(InMemory::new()),
+			MIN_OFFLOADED_VALUE_SIZE
+		)
+	);
+
+	async fn object_count(objects: &InMemory) -> usize {
+		objects.list(None).try_collect::<Vec<_>>().await.unwrap().len()
+	}
+
+	fn put_request(key: &str, version: i64, value: &[u8]) -> PutObjectRequest {
+		PutObjectRequest {
+			store_id: "store".to_string(),
+			global_version: None,
+			transaction_items: vec![KeyValue {
+				key: key.to_string(),
+				version,
+				value: Bytes::copy_from_slice(value),
+			}],
+			delete_items: vec![],
+		}
+	}
+
+	#[tokio::test]
+	async fn offloads_large_values_and_deletes_unreferenced_objects() {
+		let index = Arc::new(InMemoryBackend::new());
+		let objects = Arc::new(InMemory::new());
+		let store = OffloadingKvStore::new(
+			Arc::clone(&index) as Arc<dyn KvStore>,
+			Arc::clone(&objects) as Arc<dyn ObjectStore>,
+			MIN_OFFLOADED_VALUE_SIZE,
+		);
+		let user_token = "user".to_string();
+		let get_request = GetObjectRequest { store_id: "store".to_string(), key: "k".to_string() };
+
+		// Small values stay in the index.
+		store.put(user_token.clone(), put_request("k", 0, b"small")).await.unwrap();
+		assert_eq!(object_count(&objects).await, 0);
+
+		// Large values are offloaded, and only referenced by the index.
+		let large = vec![7u8; 100];
+		store.put(user_token.clone(), put_request("k", 1, &large)).await.unwrap();
+		assert_eq!(object_count(&objects).await, 1);
+		let stored = index.get(user_token.clone(), get_request.clone()).await.unwrap();
+		assert!(reference_location(&stored.value.unwrap().value).is_some());
+		let value = store.get(user_token.clone(), get_request.clone()).await.unwrap();
+		assert_eq!(value.value.unwrap().value, Bytes::from(large.clone()));
+
+		// A conflicting put leaves the stored object in place and cleans up its own.
+		assert!(store.put(user_token.clone(), put_request("k", 1, &large)).await.is_err());
+		assert_eq!(object_count(&objects).await, 1);
+
+		// Replacing the value deletes the replaced object.
+		store.put(user_token.clone(), put_request("k", 2, &[8u8; 100])).await.unwrap();
+		assert_eq!(object_count(&objects).await, 1);
+
+		// Deleting at a stale version keeps the object, deleting at the current one removes it.
+		let delete_request = |version| DeleteObjectRequest {
+			store_id: "store".to_string(),
+			key_value: Some(KeyValue { key: "k".to_string(), version, value: Bytes::new() }),
+		};
+		store.delete(user_token.clone(), delete_request(1)).await.unwrap();
+		assert_eq!(object_count(&objects).await, 1);
+		store.delete(user_token.clone(), delete_request(3)).await.unwrap();
+		assert_eq!(object_count(&objects).await, 0);
+
+		// Deleting the store deletes its objects.
+		store.put(user_token.clone(), put_request("k", 0, &large)).await.unwrap();
+		assert_eq!(object_count(&objects).await, 1);
+		store.delete_store(user_token, "store".to_string()).await.unwrap();
+		assert_eq!(object_count(&objects).await, 0);
+	}
+
+	#[tokio::test]
+	async fn offloads_small_values_resembling_references() {
+		let objects = Arc::new(InMemory::new());
+		let store = OffloadingKvStore::new(
+			Arc::new(InMemoryBackend::new()),
+			Arc::clone(&objects) as Arc<dyn ObjectStore>,
+			MIN_OFFLOADED_VALUE_SIZE,
+		);
+		let value = b"VSSOBJ\x01x";
+		store.put("user".to_string(), put_request("k", 0, value)).await.unwrap();
+		assert_eq!(object_count(&objects).await, 1);
+		let get_request = GetObjectRequest { store_id: "store".to_string(), key: "k".to_string() };
+		let stored = store.get("user".to_string(), get_request).await.unwrap();
+		assert_eq!(stored.value.unwrap().value, Bytes::from_static(value));
+	}
+}
@@ -0,0 +1,48 @@
+use api::error::VssError;
+use async_trait::async_trait;
+
+/// A deletion of an object requested by a client, held back until it becomes executable so that
+/// it can still be cancelled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingDeletion {
+	/// The user token owning the object.
+	pub user_token: String,
+	/// The store the object belongs to.
+	pub store_id: String,
+	/// The key of the object.
+	pub key: String,
+	/// The version of the object when its deletion was requested. The deletion is only executed
+	/// if the object still has this version, so that writes after the request keep the object.
+	pub version: i64,
+	/// The time the deletion was requested, in seconds since the Unix epoch.
+	pub requested_at: u64,
+	/// The time after which the deletion is executed, in seconds since the Unix epoch.
+	pub executable_after: u64,
+}
+
+/// Persists [`PendingDeletion`]s until they are executed or cancelled.
+#[async_trait]
+pub trait PendingDeletionStore: Send + Sync {
+	/// Stores `deletion`, replacing any pending deletion of the same object.
+	async fn add_pending_deletion(&self, deletion: &PendingDeletion) -> Result<(), VssError>;
+
+	/// Cancels the pending deletion of the given object. Returns whether a deletion was pending.
+	async fn cancel_pending_deletion(
+		&self, user_token: &str, store_id: &str, key: &str,
+	) -> Result<bool, VssError>;
+
+	/// Returns the pending deletions of the given store, ordered by key.
+	async fn list_pending_deletions(
+		&self, user_token: &str, store_id: &str,
+	) -> Result<Vec<PendingDeletion>, VssError>;
+
+	/// Returns at most `limit` deletions executable at `now`, in seconds since the Unix epoch,
+	/// ordered by the time they became executable.
+	async fn list_due_deletions(
+		&self, now: u64, limit: i64,
+	) -> Result<Vec<PendingDeletion>, VssError>;
+
+	/// Removes `deletion` once it was executed, unless it was replaced by a newer request for the
+	/// same object in the meantime. Returns whether it was removed.
+	async fn remove_pending_deletion(&self, deletion: &PendingDeletion) -> Result<bool, VssError>;
+}
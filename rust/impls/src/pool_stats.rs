@@ -0,0 +1,24 @@
+use std::time::Duration;
+
+/// A snapshot of the state of a backend's database connection pool.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+	/// The number of connections in the pool.
+	pub size: usize,
+	/// The number of connections currently checked out.
+	pub checked_out: usize,
+	/// The number of callers currently waiting for a connection.
+	pub waiting: usize,
+	/// The number of connections checked out since startup.
+	pub acquisitions: u64,
+	/// The total time callers spent waiting for a connection since startup.
+	pub total_wait_time: Duration,
+	/// The number of broken connections replaced since startup.
+	pub reconnects: u64,
+}
+
+/// Exposes the state of a backend's database connection pool, e.g. for monitoring.
+pub trait PoolStatsProvider: Send + Sync {
+	/// Returns a snapshot of the current state of the connection pool.
+	fn pool_stats(&self) -> PoolStats;
+}
@@ -0,0 +1,64 @@
+use api::error::VssError;
+use async_trait::async_trait;
+use bytes::Bytes;
+
+/// A single stored object, as seen by maintenance jobs operating across all users.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredRecord {
+	/// The user token owning the object.
+	pub user_token: String,
+	/// The store the object belongs to.
+	pub store_id: String,
+	/// The key of the object.
+	pub key: String,
+	/// The version of the object.
+	pub version: i64,
+	/// The value of the object as handed to the backend, i.e. not decoded by decorators such as
+	/// [`EncryptedKvStore`], but decompressed if the backend compressed it.
+	///
+	/// [`EncryptedKvStore`]: crate::encrypted_store::EncryptedKvStore
+	pub value: Bytes,
+}
+
+/// The position of a [`RecordStore::scan_records`] iteration, i.e. the last record returned.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecordCursor {
+	/// The user token of the last returned record.
+	pub user_token: String,
+	/// The store id of the last returned record.
+	pub store_id: String,
+	/// The key of the last returned record.
+	pub key: String,
+}
+
+impl From<&StoredRecord> for RecordCursor {
+	fn from(record: &StoredRecord) -> Self {
+		Self {
+			user_token: record.user_token.clone(),
+			store_id: record.store_id.clone(),
+			key: record.key.clone(),
+		}
+	}
+}
+
+/// Raw access to the records of a backend across all users, used by maintenance jobs which
+/// rewrite stored values in place, e.g. to re-encrypt them with a new key.
+///
+/// Unlike [`KvStore`], values are neither decoded nor are object versions bumped on write, as the
+/// logical content of an object must not change through such rewrites.
+///
+/// [`KvStore`]: api::kv_store::KvStore
+#[async_trait]
+pub trait RecordStore: Send + Sync {
+	/// Returns up to `limit` records ordered by user token, store id and key, starting after
+	/// `after` or at the first record if `None`.
+	async fn scan_records(
+		&self, after: Option<&RecordCursor>, limit: i64,
+	) -> Result<Vec<StoredRecord>, VssError>;
+
+	/// Replaces the stored value of `record` with `new_value`, provided that neither its version nor
+	/// its value changed since it was read. Returns whether the value was replaced.
+	async fn replace_record_value(
+		&self, record: &StoredRecord, new_value: Bytes,
+	) -> Result<bool, VssError>;
+}
@@ -0,0 +1,538 @@
+use api::error::VssError;
+use api::kv_store::{
+	validate_get_objects_atomic_request, KvStore, GLOBAL_VERSION_KEY, INITIAL_RECORD_VERSION,
+};
+use api::types::{
+	DeleteObjectRequest, DeleteObjectResponse, GetObjectRequest, GetObjectResponse,
+	GetObjectsAtomicRequest, GetObjectsAtomicResponse, KeyValue, ListKeyVersionsRequest,
+	ListKeyVersionsResponse, PutObjectRequest, PutObjectResponse,
+};
+use async_trait::async_trait;
+use bytes::Bytes;
+use redis::aio::{ConnectionManager, ConnectionManagerConfig};
+use redis::{AsyncCommands, Client, RedisError, Script};
+use std::cmp::min;
+use std::time::Duration;
+use tracing::instrument;
+
+use crate::health_check::HealthCheck;
+use crate::postgres_store::{LIST_KEY_VERSIONS_MAX_PAGE_SIZE, MAX_PUT_REQUEST_ITEM_COUNT};
+
+/// The time each attempt to connect to Redis has before it fails.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The time Redis has to respond to a command before it fails.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The number of times a failed connection attempt is retried, both on connecting initially and
+/// on reconnecting after the connection was lost.
+const CONNECTION_RETRIES: usize = 3;
+
+/// The factor the delay between connection attempts, starting at one second, grows by.
+const CONNECTION_RETRY_FACTOR: u64 = 2;
+
+/// The maximum delay between connection attempts, in milliseconds.
+const MAX_CONNECTION_RETRY_DELAY_MS: u64 = 4_000;
+
+/// Checks the versions of all objects written or deleted by a put, and only applies the put if
+/// all of them match.
+///
+/// KEYS: the versions, values and key index of the store, and the set of the user's stores.
+/// ARGV: the store id, the global version key and the number of written objects, followed by key,
+/// expected version, new version and value of each written object, and key and expected version
+/// of each deleted object. Returns 1 if the put was applied and 0 on a version mismatch.
+const PUT_SCRIPT: &str = r#"
+local puts_end = 3 + tonumber(ARGV[3]) * 4
+for i = 4, puts_end, 4 do
+	local stored = redis.call('HGET', KEYS[1], ARGV[i])
+	if ARGV[i + 1] == '0' then
+		if stored then return 0 end
+	elseif ARGV[i + 1] ~= '-1' and stored ~= ARGV[i + 1] then
+		return 0
+	end
+end
+for i = puts_end + 1, #ARGV, 2 do
+	local stored = redis.call('HGET', KEYS[1], ARGV[i])
+	if not stored or (ARGV[i + 1] ~= '-1' and stored ~= ARGV[i + 1]) then return 0 end
+end
+for i = 4, puts_end, 4 do
+	redis.call('HSET', KEYS[1], ARGV[i], ARGV[i + 2])
+	redis.call('HSET', KEYS[2], ARGV[i], ARGV[i + 3])
+	if ARGV[i] ~= ARGV[2] then redis.call('ZADD', KEYS[3], 0, ARGV[i]) end
+end
+for i = puts_end + 1, #ARGV, 2 do
+	redis.call('HDEL', KEYS[1], ARGV[i])
+	redis.call('HDEL', KEYS[2], ARGV[i])
+	redis.call('ZREM', KEYS[3], ARGV[i])
+end
+redis.call('SADD', KEYS[4], ARGV[1])
+return 1
+"#;
+
+/// Deletes an object if its version matches, or unconditionally for version -1.
+///
+/// KEYS: the versions, values and key index of the store. ARGV: the key and expected version.
+const DELETE_SCRIPT: &str = r#"
+if ARGV[2] == '-1' or redis.call('HGET', KEYS[1], ARGV[1]) == ARGV[2] then
+	redis.call('HDEL', KEYS[1], ARGV[1])
+	redis.call('HDEL', KEYS[2], ARGV[1])
+	redis.call('ZREM', KEYS[3], ARGV[1])
+end
+return 0
+"#;
+
+/// Lists keys of the store within a lexicographical range along with their versions.
+///
+/// KEYS: the versions and key index of the store. ARGV: the range bounds in `ZRANGEBYLEX` syntax
+/// and the maximum number of keys. Returns the keys alternating with their versions.
+const LIST_SCRIPT: &str = r#"
+local keys = redis.call('ZRANGEBYLEX', KEYS[2], ARGV[1], ARGV[2], 'LIMIT', 0, ARGV[3])
+if #keys == 0 then return {} end
+local versions = redis.call('HMGET', KEYS[1], unpack(keys))
+local result = {}
+for i, key in ipairs(keys) do
+	result[2 * i - 1] = key
+	result[2 * i] = versions[i]
+end
+return result
+"#;
+
+/// Deletes all objects of a store, returning their number including the global version.
+///
+/// KEYS: the versions, values and key index of the store, and the set of the user's stores.
+/// ARGV: the store id.
+const DELETE_STORE_SCRIPT: &str = r#"
+local count = redis.call('HLEN', KEYS[1])
+redis.call('DEL', KEYS[1], KEYS[2], KEYS[3])
+redis.call('SREM', KEYS[4], ARGV[1])
+return count
+"#;
+
+/// A [Redis](https://redis.io/) based [`KvStore`] implementation.
+///
+/// Each store is kept in a hash of object versions, a hash of values and a sorted set indexing its
+/// keys for listing. Puts and deletes run as Lua scripts, which check the stored versions and
+/// apply all changes atomically, preserving the versioning semantics of the PostgreSQL backend.
+///
+/// All keys of a user share a hash tag, so a user's data lives on a single Redis Cluster node.
+/// Objects are only as durable as the Redis persistence configuration, so deployments which must
+/// not lose data should enable AOF persistence with `appendfsync always`.
+pub struct RedisBackend {
+	connection: ConnectionManager,
+	put_script: Script,
+	delete_script: Script,
+	list_script: Script,
+	delete_store_script: Script,
+}
+
+impl RedisBackend {
+	/// Connects to the Redis server at the given URL, e.g. `redis://localhost:6379`, reconnecting
+	/// automatically whenever the connection is lost.
+	///
+	/// Fails if the server cannot be reached within a few attempts, rather than retrying for
+	/// minutes.
+	pub async fn new(url: &str) -> Result<Self, VssError> {
+		let client = Client::open(url).map_err(|e| to_vss_error("connection", e))?;
+		let config = ConnectionManagerConfig::new()
+			.set_connection_timeout(CONNECTION_TIMEOUT)
+			.set_response_timeout(RESPONSE_TIMEOUT)
+			.set_number_of_retries(CONNECTION_RETRIES)
+			.set_factor(CONNECTION_RETRY_FACTOR)
+			.set_max_delay(MAX_CONNECTION_RETRY_DELAY_MS);
+		let connection = ConnectionManager::new_with_config(client, config)
+			.await
+			.map_err(|e| to_vss_error("connection", e))?;
+		Ok(Self {
+			connection,
+			put_script: Script::new(PUT_SCRIPT),
+			delete_script: Script::new(DELETE_SCRIPT),
+			list_script: Script::new(LIST_SCRIPT),
+			delete_store_script: Script::new(DELETE_STORE_SCRIPT),
+		})
+	}
+
+	async fn delete_store_keys(&self, keys: &StoreKeys) -> Result<u64, VssError> {
+		self.delete_store_script
+			.key(&keys.versions)
+			.key(&keys.values)
+			.key(&keys.index)
+			.key(&keys.stores)
+			.arg(&keys.store_id)
+			.invoke_async(&mut self.connection.clone())
+			.await
+			.map_err(|e| to_vss_error("delete_store", e))
+	}
+}
+
+/// The Redis keys holding a store of a user.
+struct StoreKeys {
+	store_id: String,
+	/// A hash mapping object keys to their versions.
+	versions: String,
+	/// A hash mapping object keys to their values.
+	values: String,
+	/// A sorted set of all object keys but the global version, ordered lexicographically.
+	index: String,
+	/// The set of the user's store ids.
+	stores: String,
+}
+
+impl StoreKeys {
+	fn new(user_token: &str, store_id: &str) -> Self {
+		let store_prefix = format!("{}:{}:{}", user_prefix(user_token), store_id.len(), store_id);
+		Self {
+			store_id: store_id.to_string(),
+			versions: format!("{}:versions", store_prefix),
+			values: format!("{}:values", store_prefix),
+			index: format!("{}:keys", store_prefix),
+			stores: stores_key(user_token),
+		}
+	}
+}
+
+/// Identifies a user. The user token is length-prefixed, so that no two pairs of user token and
+/// store id map to the same keys, and serves as hash tag to keep a user's keys in one slot.
+fn user_prefix(user_token: &str) -> String {
+	format!("vss:{{{}:{}}}", user_token.len(), user_token)
+}
+
+fn stores_key(user_token: &str) -> String {
+	format!("{}:stores", user_prefix(user_token))
+}
+
+/// Returns the version an object is stored with after being written at the given version.
+fn new_version(version: i64) -> i64 {
+	// Like in the PostgreSQL backend, a version of -1 writes unconditionally, 0 creates the
+	// object if it does not exist, and any other version must match the stored one.
+	if version <= 0 {
+		INITIAL_RECORD_VERSION as i64
+	} else {
+		version.saturating_add(1)
+	}
+}
+
+#[async_trait]
+impl KvStore for RedisBackend {
+	#[instrument(
+		name = "redis.get",
+		skip(self, user_token, request),
+		fields(
+			db.system = "redis",
+			db.operation = "HGET",
+			span.type = "db",
+			store_id = %request.store_id,
+			key = %request.key
+		)
+	)]
+	async fn get(
+		&self, user_token: String, request: GetObjectRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		let keys = StoreKeys::new(&user_token, &request.store_id);
+		let (version, value): (Option<String>, Option<Vec<u8>>) = redis::pipe()
+			.atomic()
+			.hget(&keys.versions, &request.key)
+			.hget(&keys.values, &request.key)
+			.query_async(&mut self.connection.clone())
+			.await
+			.map_err(|e| to_vss_error("get", e))?;
+
+		let key_value = match version {
+			Some(version) => KeyValue {
+				key: request.key,
+				value: Bytes::from(value.unwrap_or_default()),
+				version: parse_version(&version)?,
+			},
+			None if request.key == GLOBAL_VERSION_KEY => {
+				KeyValue { key: GLOBAL_VERSION_KEY.to_string(), value: Bytes::new(), version: 0 }
+			},
+			None => return Err(VssError::NoSuchKeyError("Requested key not found.".to_string())),
+		};
+		Ok(GetObjectResponse { value: Some(key_value) })
+	}
+
+	#[instrument(
+		name = "redis.put",
+		skip(self, user_token, request),
+		fields(
+			db.system = "redis",
+			db.operation = "EVALSHA",
+			span.type = "db",
+			store_id = %request.store_id,
+			transaction_items_count = %request.transaction_items.len(),
+			delete_items_count = %request.delete_items.len()
+		)
+	)]
+	async fn put(
+		&self, user_token: String, request: PutObjectRequest,
+	) -> Result<PutObjectResponse, VssError> {
+		if request.transaction_items.len() + request.delete_items.len() > MAX_PUT_REQUEST_ITEM_COUNT
+		{
+			return Err(VssError::InvalidRequestError(format!(
+				"Number of write items per request should be less than equal to {}",
+				MAX_PUT_REQUEST_ITEM_COUNT
+			)));
+		}
+
+		let keys = StoreKeys::new(&user_token, &request.store_id);
+		let mut put_items = request.transaction_items;
+		if let Some(global_version) = request.global_version {
+			put_items.push(KeyValue {
+				key: GLOBAL_VERSION_KEY.to_string(),
+				value: Bytes::new(),
+				version: global_version,
+			});
+		}
+		if put_items.is_empty() && request.delete_items.is_empty() {
+			return Ok(PutObjectResponse {});
+		}
+
+		let mut invocation = self.put_script.prepare_invoke();
+		invocation
+			.key(&keys.versions)
+			.key(&keys.values)
+			.key(&keys.index)
+			.key(&keys.stores)
+			.arg(&keys.store_id)
+			.arg(GLOBAL_VERSION_KEY)
+			.arg(put_items.len());
+		for kv in &put_items {
+			invocation
+				.arg(&kv.key)
+				.arg(kv.version)
+				.arg(new_version(kv.version))
+				.arg(kv.value.as_ref());
+		}
+		for kv in &request.delete_items {
+			invocation.arg(&kv.key).arg(kv.version);
+		}
+		let applied: i64 = invocation
+			.invoke_async(&mut self.connection.clone())
+			.await
+			.map_err(|e| to_vss_error("put", e))?;
+		if applied == 0 {
+			tracing::warn!("Put rejected due to version mismatch");
+			return Err(VssError::ConflictError(
+				"Transaction could not be completed due to a possible conflict".to_string(),
+			));
+		}
+		Ok(PutObjectResponse {})
+	}
+
+	#[instrument(
+		name = "redis.delete",
+		skip(self, user_token, request),
+		fields(
+			db.system = "redis",
+			db.operation = "EVALSHA",
+			span.type = "db",
+			store_id = %request.store_id
+		)
+	)]
+	async fn delete(
+		&self, user_token: String, request: DeleteObjectRequest,
+	) -> Result<DeleteObjectResponse, VssError> {
+		let key_value = request.key_value.ok_or_else(|| {
+			VssError::InvalidRequestError("key_value missing in DeleteObjectRequest".to_string())
+		})?;
+		let keys = StoreKeys::new(&user_token, &request.store_id);
+		// Deleting an object at a stale version is a no-op, as in the PostgreSQL backend.
+		let _: i64 = self
+			.delete_script
+			.key(&keys.versions)
+			.key(&keys.values)
+			.key(&keys.index)
+			.arg(&key_value.key)
+			.arg(key_value.version)
+			.invoke_async(&mut self.connection.clone())
+			.await
+			.map_err(|e| to_vss_error("delete", e))?;
+		Ok(DeleteObjectResponse {})
+	}
+
+	#[instrument(
+		name = "redis.list_key_versions",
+		skip(self, user_token, request),
+		fields(
+			db.system = "redis",
+			db.operation = "EVALSHA",
+			span.type = "db",
+			store_id = %request.store_id,
+			key_prefix = ?request.key_prefix,
+			page_size = ?request.page_size
+		)
+	)]
+	async fn list_key_versions(
+		&self, user_token: String, request: ListKeyVersionsRequest,
+	) -> Result<ListKeyVersionsResponse, VssError> {
+		// Only fetch global_version for the first page, before fetching any key versions, to
+		// ensure all listed key versions were stored at global_version or later.
+		let mut global_version = None;
+		if request.page_token.is_none() {
+			let get_global_version_request = GetObjectRequest {
+				store_id: request.store_id.clone(),
+				key: GLOBAL_VERSION_KEY.to_string(),
+			};
+			let get_response = self.get(user_token.clone(), get_global_version_request).await?;
+			// unwrap safety: get request always return a value when global_version is queried.
+			global_version = Some(get_response.value.unwrap().version);
+		}
+
+		let limit =
+			min(request.page_size.unwrap_or(i32::MAX), LIST_KEY_VERSIONS_MAX_PAGE_SIZE).max(0);
+		let key_prefix = request.key_prefix.filter(|prefix| !prefix.is_empty());
+		let start = match (request.page_token.filter(|token| !token.is_empty()), &key_prefix) {
+			(Some(page_token), _) => [b"(", page_token.as_bytes()].concat(),
+			(None, Some(key_prefix)) => [b"[", key_prefix.as_bytes()].concat(),
+			(None, None) => b"-".to_vec(),
+		};
+		// No UTF-8 encoded key contains the byte 0xff, so it sorts after all keys with the prefix.
+		let end = match &key_prefix {
+			Some(key_prefix) => [b"[", key_prefix.as_bytes(), &[0xff]].concat(),
+			None => b"+".to_vec(),
+		};
+
+		let keys = StoreKeys::new(&user_token, &request.store_id);
+		let entries: Vec<String> = self
+			.list_script
+			.key(&keys.versions)
+			.key(&keys.index)
+			.arg(start)
+			.arg(end)
+			.arg(limit)
+			.invoke_async(&mut self.connection.clone())
+			.await
+			.map_err(|e| to_vss_error("list_key_versions", e))?;
+		let key_versions = entries
+			.chunks(2)
+			.map(|entry| match entry {
+				[key, version] => Ok(KeyValue {
+					key: key.clone(),
+					value: Bytes::new(),
+					version: parse_version(version)?,
+				}),
+				_ => Err(malformed_entry_error()),
+			})
+			.collect::<Result<Vec<_>, VssError>>()?;
+
+		let next_page_token = match key_versions.last() {
+			Some(kv) => Some(kv.key.clone()),
+			None => Some("".to_string()),
+		};
+		Ok(ListKeyVersionsResponse { key_versions, next_page_token, global_version })
+	}
+
+	#[instrument(
+		name = "redis.get_objects_atomic",
+		skip(self, user_token, request),
+		fields(
+			db.system = "redis",
+			db.operation = "HMGET",
+			span.type = "db",
+			store_id = %request.store_id,
+			keys_count = request.keys.len()
+		)
+	)]
+	async fn get_objects_atomic(
+		&self, user_token: String, request: GetObjectsAtomicRequest,
+	) -> Result<GetObjectsAtomicResponse, VssError> {
+		validate_get_objects_atomic_request(&request)?;
+		let keys = StoreKeys::new(&user_token, &request.store_id);
+		// The global version is read along with the objects, so neither field list is empty.
+		let mut fields = vec![GLOBAL_VERSION_KEY.to_string()];
+		fields.extend(request.keys.iter().cloned());
+		let (versions, values): (Vec<Option<String>>, Vec<Option<Vec<u8>>>) = redis::pipe()
+			.atomic()
+			.cmd("HMGET")
+			.arg(&keys.versions)
+			.arg(&fields)
+			.cmd("HMGET")
+			.arg(&keys.values)
+			.arg(&fields)
+			.query_async(&mut self.connection.clone())
+			.await
+			.map_err(|e| to_vss_error("get_objects_atomic", e))?;
+
+		let global_version = match versions.first() {
+			Some(Some(version)) => parse_version(version)?,
+			_ => 0,
+		};
+		let mut objects = Vec::with_capacity(request.keys.len());
+		let entries = request.keys.into_iter().zip(versions.into_iter().zip(values).skip(1));
+		for (key, (version, value)) in entries {
+			if let Some(version) = version {
+				let value = Bytes::from(value.unwrap_or_default());
+				objects.push(KeyValue { key, value, version: parse_version(&version)? });
+			}
+		}
+		Ok(GetObjectsAtomicResponse { objects, global_version })
+	}
+
+	#[instrument(
+		name = "redis.delete_store",
+		skip(self, user_token),
+		fields(db.system = "redis", db.operation = "DEL", span.type = "db", store_id = %store_id)
+	)]
+	async fn delete_store(&self, user_token: String, store_id: String) -> Result<u64, VssError> {
+		let num_items = self.delete_store_keys(&StoreKeys::new(&user_token, &store_id)).await?;
+		tracing::debug!(items_deleted = num_items, "Store deleted");
+		Ok(num_items)
+	}
+
+	#[instrument(
+		name = "redis.delete_user",
+		skip(self, user_token),
+		fields(db.system = "redis", db.operation = "DEL", span.type = "db")
+	)]
+	async fn delete_user(&self, user_token: String) -> Result<u64, VssError> {
+		let store_ids: Vec<String> = self
+			.connection
+			.clone()
+			.smembers(stores_key(&user_token))
+			.await
+			.map_err(|e| to_vss_error("delete_user", e))?;
+		let mut num_items = 0;
+		for store_id in store_ids {
+			num_items += self.delete_store_keys(&StoreKeys::new(&user_token, &store_id)).await?;
+		}
+		tracing::debug!(items_deleted = num_items, "User deleted");
+		Ok(num_items)
+	}
+}
+
+#[async_trait]
+impl HealthCheck for RedisBackend {
+	async fn check_health(&self) -> Result<(), VssError> {
+		let _: String = redis::cmd("PING")
+			.query_async(&mut self.connection.clone())
+			.await
+			.map_err(|e| to_vss_error("ping", e))?;
+		Ok(())
+	}
+}
+
+fn parse_version(version: &str) -> Result<i64, VssError> {
+	version.parse().map_err(|_| malformed_entry_error())
+}
+
+fn malformed_entry_error() -> VssError {
+	VssError::InternalServerError("Malformed entry in Redis".to_string())
+}
+
+fn to_vss_error(operation: &str, e: RedisError) -> VssError {
+	VssError::InternalServerError(format!("Redis {} failed: {}", operation, e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::RedisBackend;
+	use api::define_kv_store_tests;
+
+	/// A Redis server to run the tests against.
+	const REDIS_URL: &str = "redis://localhost:6379";
+
+	define_kv_store_tests!(
+		RedisKvStoreTest,
+		RedisBackend,
+		RedisBackend::new(REDIS_URL).await.unwrap()
+	);
+}
@@ -0,0 +1,41 @@
+use api::error::VssError;
+use async_trait::async_trait;
+
+/// A grant of read access to a single object, minted by the object's owner.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShareToken {
+	/// The SHA-256 hash of the secret presented by the holder of the token. The secret itself is
+	/// never stored.
+	pub token_sha256: [u8; 32],
+	/// The user token owning the shared object.
+	pub user_token: String,
+	/// The store the shared object belongs to.
+	pub store_id: String,
+	/// The key of the shared object.
+	pub key: String,
+	/// The time after which the token is no longer valid, in seconds since the Unix epoch.
+	pub expires_at: u64,
+}
+
+/// Persists [`ShareToken`]s, allowing them to be looked up by their holders and revoked by their
+/// owners.
+#[async_trait]
+pub trait ShareTokenStore: Send + Sync {
+	/// Stores `token`, unless its owner already holds `max_active_tokens` unexpired tokens.
+	/// Returns whether the token was stored.
+	async fn create_share_token(
+		&self, token: &ShareToken, max_active_tokens: i64,
+	) -> Result<bool, VssError>;
+
+	/// Returns the token with the given hash, or `None` if it does not exist, expired or was
+	/// revoked.
+	async fn get_share_token(
+		&self, token_sha256: &[u8; 32],
+	) -> Result<Option<ShareToken>, VssError>;
+
+	/// Revokes the token with the given hash if it is owned by `user_token`. Returns whether a
+	/// token was revoked.
+	async fn revoke_share_token(
+		&self, user_token: &str, token_sha256: &[u8; 32],
+	) -> Result<bool, VssError>;
+}
@@ -0,0 +1,69 @@
+use api::error::VssError;
+use async_trait::async_trait;
+
+/// The access a collaborator is granted to another user's store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StoreAccess {
+	/// Allows fetching objects and listing keys.
+	Read,
+	/// Allows everything [`StoreAccess::Read`] does, as well as writing and deleting objects.
+	Write,
+}
+
+impl StoreAccess {
+	/// Returns the name under which the access level is stored and exchanged.
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			StoreAccess::Read => "read",
+			StoreAccess::Write => "write",
+		}
+	}
+
+	/// Parses an access level from its name as returned by [`StoreAccess::as_str`].
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"read" => Some(StoreAccess::Read),
+			"write" => Some(StoreAccess::Write),
+			_ => None,
+		}
+	}
+}
+
+/// Access to a store granted by its owner to another user.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoreGrant {
+	/// The user token owning the store.
+	pub owner_user_token: String,
+	/// The shared store.
+	pub store_id: String,
+	/// The user token of the collaborator.
+	pub grantee_user_token: String,
+	/// The access granted to the collaborator.
+	pub access: StoreAccess,
+}
+
+/// Persists which users may access stores owned by other users.
+#[async_trait]
+pub trait StoreAcl: Send + Sync {
+	/// Grants `grant.access` to the collaborator, replacing any previous grant for the same store,
+	/// unless the store already has `max_grants` other collaborators. Returns whether the grant was
+	/// stored.
+	async fn grant_store_access(
+		&self, grant: &StoreGrant, max_grants: i64,
+	) -> Result<bool, VssError>;
+
+	/// Revokes the collaborator's access to the store. Returns whether access was revoked.
+	async fn revoke_store_access(
+		&self, owner_user_token: &str, store_id: &str, grantee_user_token: &str,
+	) -> Result<bool, VssError>;
+
+	/// Returns all grants of the given store.
+	async fn list_store_grants(
+		&self, owner_user_token: &str, store_id: &str,
+	) -> Result<Vec<StoreGrant>, VssError>;
+
+	/// Returns the access the collaborator was granted to the store, if any.
+	async fn get_store_access(
+		&self, owner_user_token: &str, store_id: &str, grantee_user_token: &str,
+	) -> Result<Option<StoreAccess>, VssError>;
+}
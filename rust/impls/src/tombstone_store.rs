@@ -0,0 +1,20 @@
+use api::error::VssError;
+use async_trait::async_trait;
+
+/// Keeps the last version of deleted objects as tombstones, so that accidentally deleted objects
+/// can be restored until their tombstones are purged.
+#[async_trait]
+pub trait TombstoneStore: Send + Sync {
+	/// Restores the object last deleted under `key`, returning its new version, which is one more
+	/// than its version when it was deleted.
+	///
+	/// Returns [`VssError::NoSuchKeyError`] if there is no tombstone for `key`, and
+	/// [`VssError::ConflictError`] if an object was written under `key` since, which is kept.
+	async fn restore_key(
+		&self, user_token: &str, store_id: &str, key: &str,
+	) -> Result<i64, VssError>;
+
+	/// Purges at most `limit` tombstones of objects deleted before `deleted_before`, in seconds
+	/// since the Unix epoch, oldest first. Returns the number of purged tombstones.
+	async fn purge_tombstones(&self, deleted_before: u64, limit: i64) -> Result<u64, VssError>;
+}
@@ -0,0 +1,73 @@
+use api::error::VssError;
+use async_trait::async_trait;
+
+/// The storage occupied by a user across all of their stores.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StorageUsage {
+	/// The total size of all stored values, in bytes.
+	pub bytes: u64,
+	/// The number of stored objects, not counting global versions.
+	pub objects: u64,
+}
+
+/// The storage occupied by a single store of a user.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoreUsage {
+	/// The id of the store.
+	pub store_id: String,
+	/// The storage occupied by the store's objects.
+	pub usage: StorageUsage,
+	/// The time an object or the global version of the store was last written, in seconds since
+	/// the UNIX epoch, if known.
+	pub last_updated_at: Option<u64>,
+}
+
+/// The measure users are ranked by in [`UsageStore::top_users`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UserRanking {
+	/// The total size of all stored values, in bytes.
+	Storage,
+	/// The number of requests made within the window.
+	Requests,
+	/// The total size of the objects created or updated within the window, in bytes.
+	Growth,
+}
+
+/// A user and their value of the measure they were ranked by.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RankedUser {
+	/// The user token identifying the user.
+	pub user_token: String,
+	/// The user's value of the ranking measure.
+	pub value: u64,
+}
+
+/// Reports how much storage users occupy and how many requests they make, e.g. to enforce quotas
+/// or to plan capacity.
+#[async_trait]
+pub trait UsageStore: Send + Sync {
+	/// Returns the storage currently occupied by the given user token.
+	async fn get_storage_usage(&self, user_token: &str) -> Result<StorageUsage, VssError>;
+
+	/// Returns the ids of the stores the given user token keeps objects or a global version in,
+	/// in ascending order.
+	async fn list_store_ids(&self, user_token: &str) -> Result<Vec<String>, VssError>;
+
+	/// Returns the storage occupied by each store of the given user token, ordered by store id.
+	async fn list_store_usage(&self, user_token: &str) -> Result<Vec<StoreUsage>, VssError>;
+
+	/// Adds the given numbers of requests per user token to the hour containing `now`, given in
+	/// seconds since the UNIX epoch.
+	async fn record_requests(&self, counts: &[(String, u64)], now: u64) -> Result<(), VssError>;
+
+	/// Deletes the request counts of hours starting before `before`, given in seconds since the
+	/// UNIX epoch. Returns the number of deleted hourly counts.
+	async fn purge_request_counts(&self, before: u64) -> Result<u64, VssError>;
+
+	/// Returns up to `limit` users with the highest value of the given measure, in descending
+	/// order. Measures over a window consider the time since `since`, given in seconds since the
+	/// UNIX epoch, at the granularity they are recorded with.
+	async fn top_users(
+		&self, ranking: UserRanking, since: u64, limit: i64,
+	) -> Result<Vec<RankedUser>, VssError>;
+}
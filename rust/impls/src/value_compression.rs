@@ -0,0 +1,140 @@
+use api::error::VssError;
+
+/// Marks a value as encoded by [`ValueCompression::encode`], followed by a byte identifying its
+/// format.
+pub(crate) const COMPRESSED_VALUE_MAGIC: &[u8] = b"VSSCMP";
+const HEADER_LEN: usize = COMPRESSED_VALUE_MAGIC.len() + 1;
+
+/// The value follows the header uncompressed. Only used for values which would otherwise be
+/// mistaken for encoded ones, as they start with [`COMPRESSED_VALUE_MAGIC`].
+const FORMAT_UNCOMPRESSED: u8 = 0;
+const FORMAT_ZSTD: u8 = 1;
+const FORMAT_LZ4: u8 = 2;
+
+/// The zstd compression level used unless configured otherwise.
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/// How object values are compressed before being stored.
+///
+/// Values are only stored compressed if that makes them smaller, so values encrypted by clients
+/// or at rest are stored as they are. Decoding does not depend on the configured compression, so
+/// it can be changed or disabled at any time while values stored before remain readable.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ValueCompression {
+	/// Values are stored uncompressed.
+	#[default]
+	None,
+	/// Values are compressed with [zstd](https://facebook.github.io/zstd/) at the given level.
+	Zstd {
+		/// The compression level, trading speed for size, from 1 to 22 or negative for faster
+		/// levels.
+		level: i32,
+	},
+	/// Values are compressed with [LZ4](https://lz4.org/), which compresses less than zstd but
+	/// faster.
+	Lz4,
+}
+
+impl ValueCompression {
+	/// Encodes `value` for storage, compressing it if that makes it smaller.
+	pub fn encode(&self, value: &[u8]) -> Vec<u8> {
+		let compressed = match self {
+			ValueCompression::None => None,
+			ValueCompression::Zstd { level } => {
+				zstd::bulk::compress(value, *level).ok().map(|data| (FORMAT_ZSTD, data))
+			},
+			ValueCompression::Lz4 => Some((FORMAT_LZ4, lz4_flex::compress_prepend_size(value))),
+		};
+		match compressed {
+			Some((format, data)) if HEADER_LEN + data.len() < value.len() => {
+				with_header(format, &data)
+			},
+			_ if is_encoded(value) => with_header(FORMAT_UNCOMPRESSED, value),
+			_ => value.to_vec(),
+		}
+	}
+}
+
+/// Decodes a value encoded by [`ValueCompression::encode`], returning values stored without a
+/// header unchanged.
+pub fn decode_value(value: Vec<u8>) -> Result<Vec<u8>, VssError> {
+	if !is_encoded(&value) {
+		return Ok(value);
+	}
+	let data = &value[HEADER_LEN..];
+	let decoded = match value[COMPRESSED_VALUE_MAGIC.len()] {
+		FORMAT_UNCOMPRESSED => Ok(data.to_vec()),
+		FORMAT_ZSTD => zstd::stream::decode_all(data).map_err(|e| e.to_string()),
+		FORMAT_LZ4 => lz4_flex::decompress_size_prepended(data).map_err(|e| e.to_string()),
+		format => Err(format!("unknown format {}", format)),
+	};
+	decoded.map_err(|e| VssError::InternalServerError(format!("Failed to decompress value: {}", e)))
+}
+
+/// Whether `value` starts with the header of values encoded by [`ValueCompression::encode`].
+///
+/// Stored values with such a header cannot be appended to in place.
+pub fn is_encoded(value: &[u8]) -> bool {
+	value.len() >= HEADER_LEN && value.starts_with(COMPRESSED_VALUE_MAGIC)
+}
+
+fn with_header(format: u8, data: &[u8]) -> Vec<u8> {
+	let mut encoded = Vec::with_capacity(HEADER_LEN + data.len());
+	encoded.extend_from_slice(COMPRESSED_VALUE_MAGIC);
+	encoded.push(format);
+	encoded.extend_from_slice(data);
+	encoded
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{decode_value, is_encoded, ValueCompression, DEFAULT_ZSTD_LEVEL};
+
+	const COMPRESSIONS: [ValueCompression; 3] = [
+		ValueCompression::None,
+		ValueCompression::Zstd { level: DEFAULT_ZSTD_LEVEL },
+		ValueCompression::Lz4,
+	];
+
+	#[test]
+	fn compresses_repetitive_values() {
+		let value = b"channel monitor ".repeat(1000);
+		for compression in &COMPRESSIONS[1..] {
+			let encoded = compression.encode(&value);
+			assert!(encoded.len() < value.len() / 10, "{:?}", compression);
+			assert_eq!(decode_value(encoded).unwrap(), value);
+		}
+	}
+
+	#[test]
+	fn stores_incompressible_values_unchanged() {
+		let mut state = 1u64;
+		let random: Vec<u8> = (0..10_000)
+			.map(|_| {
+				state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+				(state >> 56) as u8
+			})
+			.collect();
+		for value in [Vec::new(), b"tiny".to_vec(), random] {
+			for compression in &COMPRESSIONS {
+				assert_eq!(compression.encode(&value), value);
+			}
+		}
+	}
+
+	#[test]
+	fn round_trips_values_looking_encoded() {
+		let value = b"VSSCMP\x01not actually compressed".to_vec();
+		for compression in &COMPRESSIONS {
+			let encoded = compression.encode(&value);
+			assert!(is_encoded(&encoded));
+			assert_eq!(decode_value(encoded).unwrap(), value);
+		}
+	}
+
+	#[test]
+	fn rejects_corrupt_values() {
+		assert!(decode_value(b"VSSCMP\x01garbage".to_vec()).is_err());
+		assert!(decode_value(b"VSSCMP\x09".to_vec()).is_err());
+	}
+}
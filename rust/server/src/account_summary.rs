@@ -0,0 +1,118 @@
+//! A summary of a user's own account, served by `/getAccountSummary`.
+//!
+//! Wallets show "backup status" screens from it: which stores the user keeps, how much each
+//! occupies and when it was last written, the devices which accessed them, and the user's storage
+//! quota. The summary covers the stores owned by the caller, not those shared with them.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use api::error::VssError;
+use impls::device_registry::DeviceRegistry;
+use impls::usage_store::UsageStore;
+
+use crate::in_flight::hex_encode;
+use crate::quota::Quotas;
+
+/// The number of leading bytes of a device fingerprint identifying the device in summaries.
+const DEVICE_ID_LEN: usize = 8;
+
+#[derive(Deserialize)]
+pub(crate) struct GetAccountSummaryRequest {}
+
+#[derive(Serialize)]
+pub(crate) struct GetAccountSummaryResponse {
+	stores: Vec<StoreSummary>,
+	total_bytes: u64,
+	total_objects: u64,
+	/// The time any store was last written, in seconds since the UNIX epoch.
+	last_activity_at: Option<u64>,
+	devices: Vec<DeviceSummary>,
+	/// The limits applying to the user, absent if the user's storage is not limited.
+	quota: Option<QuotaSummary>,
+}
+
+#[derive(Serialize)]
+struct StoreSummary {
+	store_id: String,
+	bytes: u64,
+	objects: u64,
+	last_updated_at: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct DeviceSummary {
+	store_id: String,
+	/// An opaque id of the device, stable across requests.
+	device_id: String,
+	first_seen_at: u64,
+}
+
+#[derive(Serialize)]
+struct QuotaSummary {
+	max_bytes: Option<u64>,
+	max_objects: Option<u64>,
+}
+
+/// Assembles account summaries from the backend's metadata.
+pub(crate) struct AccountSummaries {
+	usage: Arc<dyn UsageStore>,
+	devices: Arc<dyn DeviceRegistry>,
+	quotas: Option<Arc<Quotas>>,
+}
+
+impl AccountSummaries {
+	pub(crate) fn new(
+		usage: Arc<dyn UsageStore>, devices: Arc<dyn DeviceRegistry>, quotas: Option<Arc<Quotas>>,
+	) -> Self {
+		Self { usage, devices, quotas }
+	}
+
+	/// Summarizes the account of the given user, whose quota is selected by `profile`.
+	pub(crate) async fn get(
+		&self, user_token: String, profile: Option<&str>, _request: GetAccountSummaryRequest,
+	) -> Result<GetAccountSummaryResponse, VssError> {
+		let (store_usage, devices) = tokio::try_join!(
+			self.usage.list_store_usage(&user_token),
+			self.devices.list_devices(&user_token)
+		)?;
+		let stores: Vec<StoreSummary> = store_usage
+			.into_iter()
+			.map(|store| StoreSummary {
+				store_id: store.store_id,
+				bytes: store.usage.bytes,
+				objects: store.usage.objects,
+				last_updated_at: store.last_updated_at,
+			})
+			.collect();
+		let devices = devices
+			.into_iter()
+			.map(|device| DeviceSummary {
+				store_id: device.store_id,
+				device_id: hex_encode(
+					&device.device_fingerprint
+						[..DEVICE_ID_LEN.min(device.device_fingerprint.len())],
+				),
+				first_seen_at: device.first_seen_at,
+			})
+			.collect();
+		let quota = self
+			.quotas
+			.as_ref()
+			.map(|quotas| quotas.limits(profile))
+			.filter(|limits| !limits.is_unlimited())
+			.map(|limits| QuotaSummary {
+				max_bytes: limits.max_bytes_per_user,
+				max_objects: limits.max_objects_per_user,
+			});
+		Ok(GetAccountSummaryResponse {
+			total_bytes: stores.iter().map(|store| store.bytes).sum(),
+			total_objects: stores.iter().map(|store| store.objects).sum(),
+			last_activity_at: stores.iter().filter_map(|store| store.last_updated_at).max(),
+			stores,
+			devices,
+			quota,
+		})
+	}
+}
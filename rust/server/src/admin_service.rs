@@ -0,0 +1,1106 @@
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::body::{Bytes, Incoming};
+use hyper::service::Service;
+use hyper::{Method, Request, Response, StatusCode};
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use api::error::VssError;
+use api::kv_store::KvStore;
+use api::types::{KeyValue, PutObjectRequest};
+use impls::admin_operation_store::{AdminOperationStore, PendingAdminOperation};
+use impls::audit_store::{AuditRecord, AuditedKey};
+use impls::key_history::{KeyHistory, StoreRestore};
+use impls::usage_store::{RankedUser, UsageStore, UserRanking};
+
+use log::{error, warn};
+
+use crate::in_flight::InFlightRequests;
+use crate::maintenance_mode::MaintenanceMode;
+use crate::metrics::Metrics;
+use crate::mutation_audit::{log_admin_action, AdminAction, MutationAudit};
+use crate::quota::Quotas;
+use crate::reencryption::{
+	ReencryptionJob, ReencryptionSettings, DEFAULT_BATCH_DELAY, DEFAULT_BATCH_SIZE, MAX_BATCH_SIZE,
+};
+use crate::request_capture::{CapturedRequest, RequestCapture};
+use crate::security_notifications::{SecurityEvent, SecurityEventKind, SecurityNotifier};
+use crate::store_namespaces::StoreNamespaces;
+use crate::user_activity::REQUEST_COUNT_RETENTION;
+use crate::util::parse_query;
+
+pub(crate) const ADMIN_BASE_PATH_PREFIX: &str = "/admin";
+
+/// How long a pending destructive operation may still be approved after it became executable.
+const APPROVAL_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+const BEARER_PREFIX: &str = "Bearer ";
+
+const ENCRYPTION_DISABLED_ERROR: &str = "Encryption at rest is not configured.";
+const KEY_HISTORY_DISABLED_ERROR: &str = "Key history is not configured.";
+
+const DEFAULT_TOP_USERS_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+const DEFAULT_TOP_USERS_LIMIT: i64 = 20;
+const MAX_TOP_USERS_LIMIT: i64 = 1000;
+
+/// Requests running for less than this are not listed as in flight, unless requested otherwise.
+const DEFAULT_MIN_IN_FLIGHT_ELAPSED: Duration = Duration::from_secs(1);
+
+const DEFAULT_CAPTURED_REQUESTS_LIMIT: usize = 100;
+
+/// An operator credential accepted by the admin API.
+#[derive(Clone)]
+pub(crate) struct AdminCredential {
+	/// A human readable name identifying the operator, used in the audit log and for the
+	/// two-person rule.
+	pub(crate) name: String,
+	/// The SHA-256 hash of the bearer token presented by the operator.
+	pub(crate) token_sha256: [u8; 32],
+}
+
+#[derive(Clone)]
+pub(crate) struct AdminServiceConfig {
+	pub(crate) admins: Vec<AdminCredential>,
+	/// The delay after which the requesting admin may approve their own destructive operation,
+	/// if no second admin confirmed it earlier. Never zero, which would void the two-person rule.
+	pub(crate) approval_delay: Duration,
+	pub(crate) maximum_request_body_size: usize,
+	/// The effective configuration served by `/admin/config`, with secrets redacted.
+	pub(crate) effective_config: Arc<serde_json::Value>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct WipeStoreRequest {
+	user_token: String,
+	store_id: String,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct EraseUserRequest {
+	user_token: String,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct OverwriteObjectRequest {
+	user_token: String,
+	store_id: String,
+	key: String,
+	/// The base64 encoded value to store, replacing the current value regardless of its version.
+	value: String,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct RestoreStoreRequest {
+	user_token: String,
+	store_id: String,
+	/// The point in time to restore the store to, in seconds since the Unix epoch.
+	at: u64,
+}
+
+/// An operation which irreversibly destroys user data and is therefore subject to the two-person
+/// rule.
+#[derive(Clone)]
+enum DestructiveOperation {
+	WipeStore(WipeStoreRequest),
+	EraseUser(EraseUserRequest),
+	OverwriteObject(OverwriteObjectRequest),
+	RestoreStore(RestoreStoreRequest),
+}
+
+impl DestructiveOperation {
+	fn name(&self) -> &'static str {
+		match self {
+			DestructiveOperation::WipeStore(_) => "wipe_store",
+			DestructiveOperation::EraseUser(_) => "erase_user",
+			DestructiveOperation::OverwriteObject(_) => "overwrite_object",
+			DestructiveOperation::RestoreStore(_) => "restore_store",
+		}
+	}
+
+	/// Reads the operation back from the name and parameters it was persisted with.
+	fn from_parts(name: &str, parameters: &str) -> Result<Self, String> {
+		let operation = match name {
+			"wipe_store" => serde_json::from_str(parameters).map(DestructiveOperation::WipeStore),
+			"erase_user" => serde_json::from_str(parameters).map(DestructiveOperation::EraseUser),
+			"overwrite_object" => {
+				serde_json::from_str(parameters).map(DestructiveOperation::OverwriteObject)
+			},
+			"restore_store" => {
+				serde_json::from_str(parameters).map(DestructiveOperation::RestoreStore)
+			},
+			_ => return Err(format!("unknown operation {}", name)),
+		};
+		operation.map_err(|e| e.to_string())
+	}
+
+	/// The parameters of the operation, as persisted until it is approved.
+	fn parameters(&self) -> String {
+		// unwrap safety: the request types always serialize successfully.
+		match self {
+			DestructiveOperation::WipeStore(req) => serde_json::to_string(req).unwrap(),
+			DestructiveOperation::EraseUser(req) => serde_json::to_string(req).unwrap(),
+			DestructiveOperation::OverwriteObject(req) => serde_json::to_string(req).unwrap(),
+			DestructiveOperation::RestoreStore(req) => serde_json::to_string(req).unwrap(),
+		}
+	}
+
+	/// The security event the affected user is notified about once the operation was executed.
+	fn security_event(&self) -> SecurityEvent {
+		match self {
+			DestructiveOperation::WipeStore(req) => SecurityEvent {
+				kind: SecurityEventKind::StoreWiped,
+				store_id: Some(req.store_id.clone()),
+				detail: "All objects of the store were deleted.".to_string(),
+			},
+			DestructiveOperation::EraseUser(_) => SecurityEvent {
+				kind: SecurityEventKind::UserErased,
+				store_id: None,
+				detail: "All stores of the user were deleted.".to_string(),
+			},
+			DestructiveOperation::OverwriteObject(req) => SecurityEvent {
+				kind: SecurityEventKind::ObjectOverwritten,
+				store_id: Some(req.store_id.clone()),
+				detail: format!("key={}", req.key),
+			},
+			DestructiveOperation::RestoreStore(req) => SecurityEvent {
+				kind: SecurityEventKind::StoreRestored,
+				store_id: Some(req.store_id.clone()),
+				detail: format!("at={}", req.at),
+			},
+		}
+	}
+
+	/// The record of the operation for the audit log, executed with the approval of `admin`.
+	fn audit_record(&self, admin: &AdminCredential, succeeded: bool) -> AuditRecord {
+		let (user_token, store_id) = self.scope();
+		let keys = match self {
+			DestructiveOperation::OverwriteObject(req) => {
+				vec![AuditedKey { key: req.key.clone(), version: -1, deleted: false }]
+			},
+			// The affected keys are only known to the backend.
+			_ => Vec::new(),
+		};
+		AuditRecord {
+			recorded_at: unix_time_secs(),
+			user_token: user_token.to_string(),
+			caller_user_token: Some(format!("admin:{}", admin.name)),
+			store_id: store_id.unwrap_or_default().to_string(),
+			operation: format!("admin.{}", self.name()),
+			keys,
+			source_ip: None,
+			auth_method: Some("admin".to_string()),
+			succeeded,
+		}
+	}
+
+	/// The user token and, unless all of the user's stores are affected, the store id.
+	fn scope(&self) -> (&str, Option<&str>) {
+		match self {
+			DestructiveOperation::WipeStore(req) => (&req.user_token, Some(&req.store_id)),
+			DestructiveOperation::EraseUser(req) => (&req.user_token, None),
+			DestructiveOperation::OverwriteObject(req) => (&req.user_token, Some(&req.store_id)),
+			DestructiveOperation::RestoreStore(req) => (&req.user_token, Some(&req.store_id)),
+		}
+	}
+}
+
+impl Display for DestructiveOperation {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			DestructiveOperation::WipeStore(req) => {
+				write!(f, "user_token={} store_id={}", req.user_token, req.store_id)
+			},
+			DestructiveOperation::EraseUser(req) => write!(f, "user_token={}", req.user_token),
+			DestructiveOperation::OverwriteObject(req) => {
+				write!(f, "user_token={} store_id={} key={}", req.user_token, req.store_id, req.key)
+			},
+			DestructiveOperation::RestoreStore(req) => {
+				write!(f, "user_token={} store_id={} at={}", req.user_token, req.store_id, req.at)
+			},
+		}
+	}
+}
+
+#[derive(Deserialize)]
+struct ApproveRequest {
+	approval_token: String,
+}
+
+#[derive(Default, Deserialize)]
+struct StartReencryptionRequest {
+	batch_size: Option<i64>,
+	batch_delay_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct SetMaintenanceModeRequest {
+	enabled: bool,
+}
+
+#[derive(Deserialize)]
+struct ConfigureRequestCaptureRequest {
+	sample_rate: f64,
+}
+
+#[derive(Serialize)]
+struct CapturedRequestsResponse {
+	sample_rate: f64,
+	captures: Vec<CapturedRequest>,
+}
+
+#[derive(Serialize)]
+struct PendingOperationResponse {
+	approval_token: String,
+	operation: &'static str,
+	description: String,
+	requested_by: String,
+	requested_at: u64,
+	executable_after: u64,
+	expires_at: u64,
+}
+
+/// What an executed destructive operation changed.
+#[derive(Default, Serialize)]
+struct OperationOutcome {
+	deleted_objects: u64,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	restored_objects: Option<u64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	unrestorable_keys: Option<u64>,
+}
+
+impl Display for OperationOutcome {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "deleted_objects={}", self.deleted_objects)?;
+		if let Some(restored_objects) = self.restored_objects {
+			write!(f, " restored_objects={}", restored_objects)?;
+		}
+		if let Some(unrestorable_keys) = self.unrestorable_keys {
+			write!(f, " unrestorable_keys={}", unrestorable_keys)?;
+		}
+		Ok(())
+	}
+}
+
+#[derive(Serialize)]
+struct ExecutedOperationResponse {
+	operation: &'static str,
+	#[serde(flatten)]
+	outcome: OperationOutcome,
+}
+
+#[derive(Serialize)]
+struct TopUserResponse {
+	user_token: String,
+	value: u64,
+}
+
+#[derive(Serialize)]
+struct TopUsersResponse {
+	by: &'static str,
+	since: u64,
+	users: Vec<TopUserResponse>,
+}
+
+#[derive(Serialize)]
+struct AdminErrorResponse<'a> {
+	error: &'a str,
+}
+
+struct AdminState {
+	store: Arc<dyn KvStore>,
+	reencryption: Option<Arc<ReencryptionJob>>,
+	history: Option<Arc<dyn KeyHistory>>,
+	security_notifier: Option<Arc<SecurityNotifier>>,
+	usage: Arc<dyn UsageStore>,
+	operations: Arc<dyn AdminOperationStore>,
+	/// Checks the store ids objects are overwritten in, if store namespaces are configured.
+	store_namespaces: Option<Arc<StoreNamespaces>>,
+	/// Rejects overwrites of users who reached their quota, if quotas are enforced.
+	quotas: Option<Arc<Quotas>>,
+	/// Records executed operations, if the audit log is enabled.
+	mutation_audit: Option<Arc<MutationAudit>>,
+	metrics: Arc<Metrics>,
+	maintenance_mode: Arc<MaintenanceMode>,
+	in_flight: Arc<InFlightRequests>,
+	request_capture: Arc<RequestCapture>,
+	config: AdminServiceConfig,
+}
+
+/// Serves the operator-facing admin API on a separate listener.
+///
+/// Destructive operations are never executed directly. Instead they return an approval token,
+/// which must be redeemed through `/admin/approve` either by a second admin credential, or by the
+/// requesting admin once the configured approval delay elapsed. Pending operations are kept in
+/// the backend, so that they survive restarts and can be approved through any instance.
+#[derive(Clone)]
+pub(crate) struct AdminService {
+	state: Arc<AdminState>,
+}
+
+impl AdminService {
+	#[allow(clippy::too_many_arguments)]
+	pub(crate) fn new(
+		store: Arc<dyn KvStore>, reencryption: Option<Arc<ReencryptionJob>>,
+		history: Option<Arc<dyn KeyHistory>>, security_notifier: Option<Arc<SecurityNotifier>>,
+		usage: Arc<dyn UsageStore>, operations: Arc<dyn AdminOperationStore>,
+		store_namespaces: Option<Arc<StoreNamespaces>>, quotas: Option<Arc<Quotas>>,
+		mutation_audit: Option<Arc<MutationAudit>>, metrics: Arc<Metrics>,
+		maintenance_mode: Arc<MaintenanceMode>, in_flight: Arc<InFlightRequests>,
+		request_capture: Arc<RequestCapture>, config: AdminServiceConfig,
+	) -> Self {
+		let state = AdminState {
+			store,
+			reencryption,
+			history,
+			security_notifier,
+			usage,
+			operations,
+			store_namespaces,
+			quotas,
+			mutation_audit,
+			metrics,
+			maintenance_mode,
+			in_flight,
+			request_capture,
+			config,
+		};
+		Self { state: Arc::new(state) }
+	}
+}
+
+type AdminResponse = Response<Full<Bytes>>;
+
+impl Service<Request<Incoming>> for AdminService {
+	type Response = AdminResponse;
+	type Error = hyper::Error;
+	type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+	fn call(&self, req: Request<Incoming>) -> Self::Future {
+		let state = Arc::clone(&self.state);
+		Box::pin(async move {
+			let admin = match authenticate(&state.config.admins, &req) {
+				Some(admin) => admin,
+				None => {
+					warn!("Rejected admin request with missing or invalid credentials");
+					return Ok(json_error(StatusCode::UNAUTHORIZED, "Invalid admin credentials."));
+				},
+			};
+			let path = req.uri().path().to_owned();
+			let method = req.method().clone();
+			let route = path.strip_prefix(ADMIN_BASE_PATH_PREFIX).unwrap_or_default().to_owned();
+
+			let response = match (method, route.as_str()) {
+				(Method::POST, "/wipeStore")
+				| (Method::POST, "/eraseUser")
+				| (Method::POST, "/overwriteObject")
+				| (Method::POST, "/restoreStore") => {
+					let body = match read_body(req, state.config.maximum_request_body_size).await {
+						Ok(body) => body,
+						Err(response) => return Ok(response),
+					};
+					let operation = match route.as_str() {
+						"/wipeStore" => {
+							serde_json::from_slice(&body).map(DestructiveOperation::WipeStore)
+						},
+						"/eraseUser" => {
+							serde_json::from_slice(&body).map(DestructiveOperation::EraseUser)
+						},
+						"/overwriteObject" => {
+							serde_json::from_slice(&body).map(DestructiveOperation::OverwriteObject)
+						},
+						_ => serde_json::from_slice(&body).map(DestructiveOperation::RestoreStore),
+					};
+					match operation {
+						Ok(operation) => request_operation(&state, &admin, operation).await,
+						Err(e) => json_error(StatusCode::BAD_REQUEST, &e.to_string()),
+					}
+				},
+				(Method::POST, "/approve") => {
+					let body = match read_body(req, state.config.maximum_request_body_size).await {
+						Ok(body) => body,
+						Err(response) => return Ok(response),
+					};
+					match serde_json::from_slice::<ApproveRequest>(&body) {
+						Ok(request) => approve_operation(&state, &admin, request).await,
+						Err(e) => json_error(StatusCode::BAD_REQUEST, &e.to_string()),
+					}
+				},
+				(Method::GET, "/pendingOperations") => list_pending_operations(&state).await,
+				(Method::GET, "/config") => {
+					json_response(StatusCode::OK, &*state.config.effective_config)
+				},
+				(Method::GET, "/topUsers") => list_top_users(&state, req.uri().query()).await,
+				(Method::GET, "/inFlightRequests") => {
+					list_in_flight_requests(&state, req.uri().query())
+				},
+				(Method::GET, "/capturedRequests") => {
+					list_captured_requests(&state, req.uri().query())
+				},
+				(Method::POST, "/requestCapture") => {
+					let body = match read_body(req, state.config.maximum_request_body_size).await {
+						Ok(body) => body,
+						Err(response) => return Ok(response),
+					};
+					match serde_json::from_slice::<ConfigureRequestCaptureRequest>(&body) {
+						Ok(request) => configure_request_capture(&state, &admin, request),
+						Err(e) => json_error(StatusCode::BAD_REQUEST, &e.to_string()),
+					}
+				},
+				(Method::GET, "/maintenance") => {
+					json_response(StatusCode::OK, &state.maintenance_mode.status())
+				},
+				(Method::POST, "/maintenance") => {
+					let body = match read_body(req, state.config.maximum_request_body_size).await {
+						Ok(body) => body,
+						Err(response) => return Ok(response),
+					};
+					match serde_json::from_slice::<SetMaintenanceModeRequest>(&body) {
+						Ok(request) => set_maintenance_mode(&state, &admin, request),
+						Err(e) => json_error(StatusCode::BAD_REQUEST, &e.to_string()),
+					}
+				},
+				(Method::GET, "/metrics") => Response::builder()
+					.status(StatusCode::OK)
+					.header(hyper::header::CONTENT_TYPE, prometheus::TEXT_FORMAT)
+					.body(Full::new(Bytes::from(state.metrics.render())))
+					// unwrap safety: body only errors when previous chained calls failed.
+					.unwrap(),
+				(Method::POST, "/reencrypt") => {
+					let body = match read_body(req, state.config.maximum_request_body_size).await {
+						Ok(body) => body,
+						Err(response) => return Ok(response),
+					};
+					let request = if body.is_empty() {
+						Ok(StartReencryptionRequest::default())
+					} else {
+						serde_json::from_slice::<StartReencryptionRequest>(&body)
+					};
+					match request {
+						Ok(request) => start_reencryption(&state, &admin, request),
+						Err(e) => json_error(StatusCode::BAD_REQUEST, &e.to_string()),
+					}
+				},
+				(Method::GET, "/reencrypt") => match state.reencryption.as_ref() {
+					Some(job) => json_response(StatusCode::OK, &job.progress()),
+					None => json_error(StatusCode::BAD_REQUEST, ENCRYPTION_DISABLED_ERROR),
+				},
+				_ => json_error(StatusCode::NOT_FOUND, "Invalid request path."),
+			};
+			Ok(response)
+		})
+	}
+}
+
+fn authenticate(admins: &[AdminCredential], req: &Request<Incoming>) -> Option<AdminCredential> {
+	let token = req
+		.headers()
+		.get(hyper::header::AUTHORIZATION)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix(BEARER_PREFIX))?;
+	let token_sha256: [u8; 32] = Sha256::digest(token.as_bytes()).into();
+	admins.iter().find(|admin| admin.token_sha256 == token_sha256).cloned()
+}
+
+async fn request_operation(
+	state: &AdminState, admin: &AdminCredential, operation: DestructiveOperation,
+) -> AdminResponse {
+	if let DestructiveOperation::OverwriteObject(req) = &operation {
+		if BASE64.decode(&req.value).is_err() {
+			return json_error(StatusCode::BAD_REQUEST, "value must be base64 encoded.");
+		}
+		// Checked again once executed, as the user's stores may change in the meantime.
+		match check_overwrite(state, req).await {
+			Ok(()) => {},
+			Err(VssError::InternalServerError(e)) => {
+				error!("Failed to check object overwrite: {}", e);
+				return json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to check operation.");
+			},
+			Err(e) => return json_error(StatusCode::BAD_REQUEST, &e.to_string()),
+		}
+	}
+	if let DestructiveOperation::RestoreStore(_) = &operation {
+		if state.history.is_none() {
+			return json_error(StatusCode::BAD_REQUEST, KEY_HISTORY_DISABLED_ERROR);
+		}
+	}
+
+	let now = unix_time_secs();
+	let executable_after = now.saturating_add(state.config.approval_delay.as_secs());
+	let pending = PendingAdminOperation {
+		approval_token: generate_approval_token(),
+		operation: operation.name().to_string(),
+		parameters: operation.parameters(),
+		requested_by: admin.name.clone(),
+		requested_at: now,
+		executable_after,
+		expires_at: executable_after.saturating_add(APPROVAL_WINDOW.as_secs()),
+	};
+	if let Err(e) = state.operations.add_pending_operation(&pending).await {
+		error!("Failed to persist admin operation {}: {}", pending.operation, e);
+		return json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to request operation.");
+	}
+
+	log_admin_action(AdminAction {
+		actor: &admin.name,
+		action: &format!("{}.requested", operation.name()),
+		detail: format!("{} executable_after={}", operation, pending.executable_after),
+	});
+	json_response(StatusCode::ACCEPTED, &pending_operation_response(&pending, &operation))
+}
+
+/// Whether `admin` may approve `pending` at `now`: any admin but the requesting one may, and the
+/// requesting one only once the approval delay elapsed.
+fn may_approve(pending: &PendingAdminOperation, admin: &AdminCredential, now: u64) -> bool {
+	pending.requested_by != admin.name || now >= pending.executable_after
+}
+
+async fn approve_operation(
+	state: &AdminState, admin: &AdminCredential, request: ApproveRequest,
+) -> AdminResponse {
+	let now = unix_time_secs();
+	let pending = match state.operations.get_pending_operation(&request.approval_token, now).await {
+		Ok(Some(pending)) => pending,
+		Ok(None) => return json_error(StatusCode::NOT_FOUND, "Unknown or expired approval token."),
+		Err(e) => {
+			error!("Failed to look up admin operation: {}", e);
+			return json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to look up operation.");
+		},
+	};
+	let operation = match DestructiveOperation::from_parts(&pending.operation, &pending.parameters)
+	{
+		Ok(operation) => operation,
+		Err(e) => {
+			error!("Failed to read admin operation {}: {}", pending.operation, e);
+			return json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read operation.");
+		},
+	};
+	if !may_approve(&pending, admin, now) {
+		log_admin_action(AdminAction {
+			actor: &admin.name,
+			action: &format!("{}.self_approval_rejected", operation.name()),
+			detail: operation.to_string(),
+		});
+		return json_error(
+			StatusCode::FORBIDDEN,
+			"Operation requires approval by a second admin until the approval delay elapsed.",
+		);
+	}
+	// Approval tokens are single-use, removed before executing so that concurrent approvals,
+	// possibly through other instances, execute the operation once.
+	match state.operations.remove_pending_operation(&pending.approval_token).await {
+		Ok(true) => {},
+		Ok(false) => {
+			return json_error(StatusCode::NOT_FOUND, "Unknown or expired approval token.")
+		},
+		Err(e) => {
+			error!("Failed to remove admin operation {}: {}", pending.operation, e);
+			return json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to approve operation.");
+		},
+	}
+
+	log_admin_action(AdminAction {
+		actor: &admin.name,
+		action: &format!("{}.approved", operation.name()),
+		detail: format!("{} requested_by={}", operation, pending.requested_by),
+	});
+
+	let operation_name = operation.name();
+	// Recipients are resolved upfront, as their preferences may be deleted by the operation.
+	let notification_targets = match state.security_notifier.as_ref() {
+		Some(notifier) => {
+			let (user_token, store_id) = operation.scope();
+			notifier.targets(user_token, store_id).await
+		},
+		None => Vec::new(),
+	};
+	let result = execute_operation(state, &operation).await;
+	if let Some(mutation_audit) = state.mutation_audit.as_ref() {
+		mutation_audit.record(operation.audit_record(admin, result.is_ok()));
+	}
+	match result {
+		Ok(outcome) => {
+			if let Some(notifier) = state.security_notifier.as_ref() {
+				notifier.dispatch(&operation.security_event(), &notification_targets);
+			}
+			log_admin_action(AdminAction {
+				actor: &admin.name,
+				action: &format!("{}.executed", operation_name),
+				detail: format!("{} {}", operation, outcome),
+			});
+			json_response(
+				StatusCode::OK,
+				&ExecutedOperationResponse { operation: operation_name, outcome },
+			)
+		},
+		Err(e) => {
+			error!("Failed to execute admin operation {}: {}", operation_name, e);
+			log_admin_action(AdminAction {
+				actor: &admin.name,
+				action: &format!("{}.failed", operation_name),
+				detail: format!("{} error={}", operation, e),
+			});
+			json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to execute operation.")
+		},
+	}
+}
+
+async fn execute_operation(
+	state: &AdminState, operation: &DestructiveOperation,
+) -> Result<OperationOutcome, VssError> {
+	let store = &state.store;
+	let deleted_objects =
+		|deleted_objects| OperationOutcome { deleted_objects, ..Default::default() };
+	match operation.clone() {
+		DestructiveOperation::WipeStore(req) => {
+			store.delete_store(req.user_token, req.store_id).await.map(deleted_objects)
+		},
+		DestructiveOperation::EraseUser(req) => {
+			store.delete_user(req.user_token).await.map(deleted_objects)
+		},
+		DestructiveOperation::OverwriteObject(req) => {
+			check_overwrite(state, &req).await?;
+			// unwrap safety: the value was validated when the operation was requested.
+			let value = BASE64.decode(req.value).unwrap();
+			let request = PutObjectRequest {
+				store_id: req.store_id,
+				global_version: None,
+				transaction_items: vec![KeyValue {
+					key: req.key,
+					version: -1,
+					value: value.into(),
+				}],
+				delete_items: vec![],
+			};
+			store.put(req.user_token, request).await.map(|_| OperationOutcome::default())
+		},
+		DestructiveOperation::RestoreStore(req) => {
+			// unwrap safety: key history was checked to be configured when the operation was requested.
+			let history = state.history.as_ref().unwrap();
+			let StoreRestore { restored_objects, deleted_objects, unrestorable_keys } =
+				history.restore_store(&req.user_token, &req.store_id, req.at).await?;
+			Ok(OperationOutcome {
+				deleted_objects,
+				restored_objects: Some(restored_objects),
+				unrestorable_keys: Some(unrestorable_keys),
+			})
+		},
+	}
+}
+
+/// Checks that an overwrite abides by the store namespace rules, as it may create the object and
+/// with it the store, and that the user has not reached their quota. The profile of the user is
+/// only known from their credentials, so the default limits apply.
+async fn check_overwrite(state: &AdminState, req: &OverwriteObjectRequest) -> Result<(), VssError> {
+	if let Some(namespaces) = state.store_namespaces.as_ref() {
+		namespaces.check_operator_write(&req.user_token, &req.store_id).await?;
+	}
+	match state.quotas.as_ref() {
+		Some(quotas) if quotas.enforces() => quotas.check(&req.user_token, None).await,
+		_ => Ok(()),
+	}
+}
+
+fn start_reencryption(
+	state: &AdminState, admin: &AdminCredential, request: StartReencryptionRequest,
+) -> AdminResponse {
+	let job = match state.reencryption.as_ref() {
+		Some(job) => job,
+		None => return json_error(StatusCode::BAD_REQUEST, ENCRYPTION_DISABLED_ERROR),
+	};
+	let batch_size = request.batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
+	if !(1..=MAX_BATCH_SIZE).contains(&batch_size) {
+		return json_error(
+			StatusCode::BAD_REQUEST,
+			&format!("batch_size must be between 1 and {}.", MAX_BATCH_SIZE),
+		);
+	}
+	let batch_delay =
+		request.batch_delay_ms.map(Duration::from_millis).unwrap_or(DEFAULT_BATCH_DELAY);
+
+	if !job.start(&admin.name, ReencryptionSettings { batch_size, batch_delay }) {
+		return json_error(StatusCode::CONFLICT, "A re-encryption job is already running.");
+	}
+	log_admin_action(AdminAction {
+		actor: &admin.name,
+		action: "reencryption.started",
+		detail: format!("batch_size={} batch_delay_ms={}", batch_size, batch_delay.as_millis()),
+	});
+	json_response(StatusCode::ACCEPTED, &job.progress())
+}
+
+async fn list_pending_operations(state: &AdminState) -> AdminResponse {
+	let pending_operations = match state.operations.list_pending_operations(unix_time_secs()).await
+	{
+		Ok(pending_operations) => pending_operations,
+		Err(e) => {
+			error!("Failed to list admin operations: {}", e);
+			return json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to list operations.");
+		},
+	};
+	let mut response = Vec::with_capacity(pending_operations.len());
+	for pending in &pending_operations {
+		match DestructiveOperation::from_parts(&pending.operation, &pending.parameters) {
+			Ok(operation) => response.push(pending_operation_response(pending, &operation)),
+			Err(e) => error!("Failed to read admin operation {}: {}", pending.operation, e),
+		}
+	}
+	json_response(StatusCode::OK, &response)
+}
+
+/// Ranks users by the measure given in the `by` query parameter, i.e. `storage`, `requests` or
+/// `growth`, over the last `window_secs`.
+async fn list_top_users(state: &AdminState, query: Option<&str>) -> AdminResponse {
+	let params = parse_query(query.unwrap_or_default());
+	let (by, ranking) = match params.get("by").copied().unwrap_or("storage") {
+		"storage" => ("storage", UserRanking::Storage),
+		"requests" => ("requests", UserRanking::Requests),
+		"growth" => ("growth", UserRanking::Growth),
+		_ => {
+			return json_error(
+				StatusCode::BAD_REQUEST,
+				"by must be one of storage, requests or growth.",
+			)
+		},
+	};
+	let window_secs = match params.get("window_secs").map(|value| value.parse::<u64>()) {
+		None => DEFAULT_TOP_USERS_WINDOW.as_secs(),
+		Some(Ok(window_secs)) if window_secs <= REQUEST_COUNT_RETENTION.as_secs() => window_secs,
+		Some(_) => {
+			return json_error(
+				StatusCode::BAD_REQUEST,
+				&format!("window_secs must be at most {}.", REQUEST_COUNT_RETENTION.as_secs()),
+			)
+		},
+	};
+	let limit = match params.get("limit").map(|value| value.parse::<i64>()) {
+		None => DEFAULT_TOP_USERS_LIMIT,
+		Some(Ok(limit)) if (1..=MAX_TOP_USERS_LIMIT).contains(&limit) => limit,
+		Some(_) => {
+			return json_error(
+				StatusCode::BAD_REQUEST,
+				&format!("limit must be between 1 and {}.", MAX_TOP_USERS_LIMIT),
+			)
+		},
+	};
+
+	let since = unix_time_secs().saturating_sub(window_secs);
+	match state.usage.top_users(ranking, since, limit).await {
+		Ok(users) => json_response(
+			StatusCode::OK,
+			&TopUsersResponse {
+				by,
+				since,
+				users: users
+					.into_iter()
+					.map(|RankedUser { user_token, value }| TopUserResponse { user_token, value })
+					.collect(),
+			},
+		),
+		Err(e) => {
+			error!("Failed to list top users: {}", e);
+			json_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to list top users.")
+		},
+	}
+}
+
+/// Lists the requests running for at least `min_elapsed_ms`, longest running first.
+fn list_in_flight_requests(state: &AdminState, query: Option<&str>) -> AdminResponse {
+	let params = parse_query(query.unwrap_or_default());
+	let min_elapsed = match params.get("min_elapsed_ms").map(|value| value.parse::<u64>()) {
+		None => DEFAULT_MIN_IN_FLIGHT_ELAPSED,
+		Some(Ok(min_elapsed_ms)) => Duration::from_millis(min_elapsed_ms),
+		Some(Err(_)) => {
+			return json_error(StatusCode::BAD_REQUEST, "min_elapsed_ms must be a number.")
+		},
+	};
+	json_response(StatusCode::OK, &state.in_flight.list(min_elapsed))
+}
+
+/// Lists up to `limit` captured requests, most recent first.
+fn list_captured_requests(state: &AdminState, query: Option<&str>) -> AdminResponse {
+	let params = parse_query(query.unwrap_or_default());
+	let limit = match params.get("limit").map(|value| value.parse::<usize>()) {
+		None => DEFAULT_CAPTURED_REQUESTS_LIMIT,
+		Some(Ok(limit)) => limit,
+		Some(Err(_)) => return json_error(StatusCode::BAD_REQUEST, "limit must be a number."),
+	};
+	let response = CapturedRequestsResponse {
+		sample_rate: state.request_capture.sample_rate(),
+		captures: state.request_capture.list(limit),
+	};
+	json_response(StatusCode::OK, &response)
+}
+
+fn configure_request_capture(
+	state: &AdminState, admin: &AdminCredential, request: ConfigureRequestCaptureRequest,
+) -> AdminResponse {
+	if !(0.0..=1.0).contains(&request.sample_rate) {
+		return json_error(StatusCode::BAD_REQUEST, "sample_rate must be between 0 and 1.");
+	}
+	state.request_capture.set_sample_rate(request.sample_rate);
+	log_admin_action(AdminAction {
+		actor: &admin.name,
+		action: "request_capture.configured",
+		detail: format!("sample_rate={}", request.sample_rate),
+	});
+	list_captured_requests(state, Some("limit=0"))
+}
+
+fn set_maintenance_mode(
+	state: &AdminState, admin: &AdminCredential, request: SetMaintenanceModeRequest,
+) -> AdminResponse {
+	if state.maintenance_mode.set(request.enabled) {
+		log_admin_action(AdminAction {
+			actor: &admin.name,
+			action: "maintenance_mode.configured",
+			detail: format!("enabled={}", request.enabled),
+		});
+	}
+	json_response(StatusCode::OK, &state.maintenance_mode.status())
+}
+
+fn pending_operation_response(
+	pending: &PendingAdminOperation, operation: &DestructiveOperation,
+) -> PendingOperationResponse {
+	PendingOperationResponse {
+		approval_token: pending.approval_token.clone(),
+		operation: operation.name(),
+		description: operation.to_string(),
+		requested_by: pending.requested_by.clone(),
+		requested_at: pending.requested_at,
+		executable_after: pending.executable_after,
+		expires_at: pending.expires_at,
+	}
+}
+
+async fn read_body(
+	req: Request<Incoming>, maximum_request_body_size: usize,
+) -> Result<Bytes, AdminResponse> {
+	match Limited::new(req.into_body(), maximum_request_body_size).collect().await {
+		Ok(body) => Ok(body.to_bytes()),
+		Err(_) => Err(json_error(StatusCode::PAYLOAD_TOO_LARGE, "Request body too large")),
+	}
+}
+
+fn generate_approval_token() -> String {
+	let bytes: [u8; 32] = rand::random();
+	bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn unix_time_secs() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+fn json_response<T: Serialize>(status: StatusCode, body: &T) -> AdminResponse {
+	// unwrap safety: our response types always serialize successfully.
+	let body = serde_json::to_vec(body).unwrap();
+	Response::builder()
+		.status(status)
+		.header(hyper::header::CONTENT_TYPE, "application/json")
+		.body(Full::new(Bytes::from(body)))
+		// unwrap safety: body only errors when previous chained calls failed.
+		.unwrap()
+}
+
+fn json_error(status: StatusCode, message: &str) -> AdminResponse {
+	json_response(status, &AdminErrorResponse { error: message })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		approve_operation, list_pending_operations, may_approve, request_operation,
+		AdminCredential, AdminServiceConfig, AdminState, ApproveRequest, DestructiveOperation,
+		OverwriteObjectRequest,
+	};
+	use crate::in_flight::InFlightRequests;
+	use crate::maintenance_mode::MaintenanceMode;
+	use crate::metrics::Metrics;
+	use crate::request_capture::RequestCapture;
+	use crate::store_namespaces::{ReservedNamespace, StoreNamespaceConfig, StoreNamespaces};
+
+	use api::kv_store::KvStore;
+	use api::types::{GetObjectRequest, KeyValue, PutObjectRequest};
+	use impls::admin_operation_store::PendingAdminOperation;
+	use impls::in_memory_store::InMemoryBackend;
+
+	use http_body_util::BodyExt;
+	use hyper::StatusCode;
+	use std::sync::Arc;
+	use std::time::Duration;
+
+	fn admin(name: &str) -> AdminCredential {
+		AdminCredential { name: name.to_string(), token_sha256: [0; 32] }
+	}
+
+	fn admin_state(backend: &Arc<InMemoryBackend>) -> AdminState {
+		AdminState {
+			store: backend.clone(),
+			reencryption: None,
+			history: None,
+			security_notifier: None,
+			usage: backend.clone(),
+			operations: backend.clone(),
+			store_namespaces: None,
+			quotas: None,
+			mutation_audit: None,
+			metrics: Metrics::start(backend.clone(), Vec::new(), None, None, None, None).unwrap(),
+			maintenance_mode: Arc::new(MaintenanceMode::new(false)),
+			in_flight: Arc::new(InFlightRequests::default()),
+			request_capture: Arc::new(RequestCapture::new(0.0, 1)),
+			config: AdminServiceConfig {
+				admins: vec![admin("alice"), admin("bob")],
+				approval_delay: Duration::from_secs(60 * 60),
+				maximum_request_body_size: 1024,
+				effective_config: Arc::new(serde_json::Value::Null),
+			},
+		}
+	}
+
+	fn overwrite_object() -> DestructiveOperation {
+		overwrite_object_in("store_id")
+	}
+
+	fn overwrite_object_in(store_id: &str) -> DestructiveOperation {
+		DestructiveOperation::OverwriteObject(OverwriteObjectRequest {
+			user_token: "token".to_string(),
+			store_id: store_id.to_string(),
+			key: "key".to_string(),
+			value: "dmFsdWU=".to_string(),
+		})
+	}
+
+	async fn response_json(response: super::AdminResponse) -> (StatusCode, serde_json::Value) {
+		let status = response.status();
+		let body = response.into_body().collect().await.unwrap().to_bytes();
+		(status, serde_json::from_slice(&body).unwrap())
+	}
+
+	async fn approve(
+		state: &AdminState, approver: &str, approval_token: &str,
+	) -> (StatusCode, serde_json::Value) {
+		let request = ApproveRequest { approval_token: approval_token.to_string() };
+		response_json(approve_operation(state, &admin(approver), request).await).await
+	}
+
+	#[test]
+	fn admins_approve_their_own_operations_only_after_the_delay() {
+		let pending = PendingAdminOperation {
+			approval_token: "token".to_string(),
+			operation: "wipe_store".to_string(),
+			parameters: String::new(),
+			requested_by: "alice".to_string(),
+			requested_at: 1_000,
+			executable_after: 2_000,
+			expires_at: 3_000,
+		};
+		assert!(!may_approve(&pending, &admin("alice"), 1_999));
+		assert!(may_approve(&pending, &admin("alice"), 2_000));
+		assert!(may_approve(&pending, &admin("bob"), 1_000));
+	}
+
+	#[tokio::test]
+	async fn operations_execute_once_approved_by_a_second_admin() {
+		let backend = Arc::new(InMemoryBackend::new());
+		let state = admin_state(&backend);
+		let response = request_operation(&state, &admin("alice"), overwrite_object()).await;
+		let (status, pending) = response_json(response).await;
+		assert_eq!(status, StatusCode::ACCEPTED);
+		let approval_token = pending["approval_token"].as_str().unwrap();
+
+		let (status, _) = approve(&state, "alice", approval_token).await;
+		assert_eq!(status, StatusCode::FORBIDDEN);
+
+		let (status, executed) = approve(&state, "bob", approval_token).await;
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(executed["operation"], "overwrite_object");
+		let request = GetObjectRequest { store_id: "store_id".to_string(), key: "key".to_string() };
+		let object = backend.get("token".to_string(), request).await.unwrap().value.unwrap();
+		assert_eq!(object.value, "value");
+
+		// Approval tokens are single-use.
+		let (status, _) = approve(&state, "bob", approval_token).await;
+		assert_eq!(status, StatusCode::NOT_FOUND);
+	}
+
+	#[tokio::test]
+	async fn pending_operations_are_shared_through_the_backend() {
+		let backend = Arc::new(InMemoryBackend::new());
+		let requesting_state = admin_state(&backend);
+		let response =
+			request_operation(&requesting_state, &admin("alice"), overwrite_object()).await;
+		let (_, pending) = response_json(response).await;
+		let approval_token = pending["approval_token"].as_str().unwrap();
+
+		// Another instance, or the same one after a restart, lists and approves the operation.
+		let approving_state = admin_state(&backend);
+		let (status, listed) = response_json(list_pending_operations(&approving_state).await).await;
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(listed, serde_json::json!([pending]));
+		let (status, _) = approve(&approving_state, "bob", approval_token).await;
+		assert_eq!(status, StatusCode::OK);
+
+		let (_, listed) = response_json(list_pending_operations(&requesting_state).await).await;
+		assert_eq!(listed, serde_json::json!([]));
+	}
+
+	#[tokio::test]
+	async fn overwrites_abide_by_the_store_namespace_rules() {
+		let backend = Arc::new(InMemoryBackend::new());
+		for store_id in ["store_id", "reserved/existing"] {
+			let request = PutObjectRequest {
+				store_id: store_id.to_string(),
+				global_version: None,
+				transaction_items: vec![KeyValue {
+					key: "key".to_string(),
+					version: 0,
+					value: "value".into(),
+				}],
+				delete_items: vec![],
+			};
+			backend.put("token".to_string(), request).await.unwrap();
+		}
+		let mut state = admin_state(&backend);
+		let config = StoreNamespaceConfig {
+			max_store_id_length: 32,
+			restrict_store_id_characters: true,
+			max_stores_per_user: Some(2),
+			reserved_namespaces: vec![ReservedNamespace {
+				prefix: "reserved/".to_string(),
+				claims: [("tier".to_string(), "internal".to_string())].into(),
+			}],
+		};
+		state.store_namespaces = Some(Arc::new(StoreNamespaces::new(config, backend.clone())));
+
+		let alice = admin("alice");
+		for store_id in ["store_id", "reserved/existing"] {
+			let response = request_operation(&state, &alice, overwrite_object_in(store_id)).await;
+			assert_eq!(response.status(), StatusCode::ACCEPTED);
+		}
+		for store_id in ["new_store", "reserved/new", "invalid store"] {
+			let response = request_operation(&state, &alice, overwrite_object_in(store_id)).await;
+			assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+		}
+	}
+
+	#[test]
+	fn executed_operations_are_audited_with_the_approving_admin() {
+		let record = overwrite_object().audit_record(&admin("bob"), true);
+		assert_eq!(record.user_token, "token");
+		assert_eq!(record.caller_user_token.as_deref(), Some("admin:bob"));
+		assert_eq!(record.store_id, "store_id");
+		assert_eq!(record.operation, "admin.overwrite_object");
+		assert_eq!(record.keys.len(), 1);
+		assert_eq!((record.keys[0].key.as_str(), record.keys[0].deleted), ("key", false));
+		assert!(record.succeeded);
+	}
+}
@@ -0,0 +1,359 @@
+//! Opt-in, privacy-preserving usage analytics.
+//!
+//! Only coarse aggregates are collected: the number of distinct active users and stores per
+//! reporting period, and the number of distinct users writing objects within each value size
+//! bucket. Identifiers are never retained. They are hashed with a random salt that is discarded at
+//! the end of each period, and the hashes are only used to set bits in fixed-size bitmaps, from
+//! which the numbers of distinct identifiers are estimated, and to bound each user's contribution
+//! until then.
+//!
+//! Each user contributes at most [`MAX_COUNTED_STORES_PER_USER`] stores and a single value size
+//! bucket, that of the largest value of their first write, to a period's counts. Every published count is perturbed
+//! with Laplace noise scaled to the most a single user can change it by, so that each count is
+//! `epsilon`-differentially private. As the value size buckets are disjoint, a report spends
+//! `epsilon` on active users, on active stores and on the buckets each, i.e. `3 * epsilon` in total,
+//! and the budgets of successive reports add up.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use api::error::VssError;
+use api::kv_store::KvStore;
+use api::types::{
+	AppendObjectRequest, AppendObjectResponse, DeleteObjectRequest, DeleteObjectResponse,
+	GetObjectRequest, GetObjectResponse, GetObjectVersionRequest, GetObjectsAtomicRequest,
+	GetObjectsAtomicResponse, ListKeyVersionsRequest, ListKeyVersionsResponse, ListObjectsRequest,
+	ListObjectsResponse, PutObjectRequest, PutObjectResponse,
+};
+
+use log::info;
+
+use crate::admin_service::unix_time_secs;
+
+/// The log target under which analytics reports are emitted for the metrics pipeline to pick up.
+pub(crate) const ANALYTICS_LOG_TARGET: &str = "vss_analytics";
+
+/// The number of bits of each distinct counter bitmap.
+const SKETCH_BITS: usize = 1 << 16;
+
+/// The number of stores counted for a user per period, bounding how much a single user can change
+/// the number of active stores.
+const MAX_COUNTED_STORES_PER_USER: usize = 4;
+
+/// The number of counts a report spends the privacy budget on: active users, active stores and
+/// the disjoint value size buckets.
+const COUNTS_PER_REPORT: f64 = 3.0;
+
+/// The upper bounds of the value size buckets, in bytes. Larger values fall in a final bucket.
+const VALUE_SIZE_BUCKETS: [u64; 7] =
+	[1 << 10, 1 << 12, 1 << 14, 1 << 16, 1 << 18, 1 << 20, 1 << 22];
+
+#[derive(Clone, Copy)]
+pub(crate) struct AnalyticsConfig {
+	/// The length of a reporting period.
+	pub(crate) report_interval: Duration,
+	/// The differential privacy budget spent on each published count, of which a report spends
+	/// three times as much. Smaller values add more noise.
+	pub(crate) epsilon: f64,
+}
+
+/// Estimates the number of distinct elements inserted using linear counting over a bitmap.
+struct DistinctCounter {
+	bits: Vec<u64>,
+}
+
+impl DistinctCounter {
+	fn new() -> Self {
+		Self { bits: vec![0; SKETCH_BITS / 64] }
+	}
+
+	fn insert(&mut self, hash: u64) {
+		let bit = (hash % SKETCH_BITS as u64) as usize;
+		self.bits[bit / 64] |= 1 << (bit % 64);
+	}
+
+	fn estimate(&self) -> f64 {
+		let set_bits: u32 = self.bits.iter().map(|word| word.count_ones()).sum();
+		let unset_bits = (SKETCH_BITS - set_bits as usize).max(1) as f64;
+		let total_bits = SKETCH_BITS as f64;
+		-total_bits * (unset_bits / total_bits).ln()
+	}
+}
+
+struct Period {
+	salt: [u8; 32],
+	started_at: u64,
+	active_users: DistinctCounter,
+	active_stores: DistinctCounter,
+	writers_by_value_size: Vec<DistinctCounter>,
+	/// The hashes of the stores counted for each user, by the hash of the user.
+	counted_stores: HashMap<u64, Vec<u64>>,
+	/// The hashes of the users already counted as writers in a value size bucket.
+	counted_writers: HashSet<u64>,
+}
+
+impl Period {
+	fn new() -> Self {
+		Self {
+			salt: rand::random(),
+			started_at: unix_time_secs(),
+			active_users: DistinctCounter::new(),
+			active_stores: DistinctCounter::new(),
+			writers_by_value_size: (0..=VALUE_SIZE_BUCKETS.len())
+				.map(|_| DistinctCounter::new())
+				.collect(),
+			counted_stores: HashMap::new(),
+			counted_writers: HashSet::new(),
+		}
+	}
+
+	fn hash(&self, parts: &[&str]) -> u64 {
+		let mut hasher = Sha256::new();
+		hasher.update(self.salt);
+		for part in parts {
+			hasher.update((part.len() as u64).to_be_bytes());
+			hasher.update(part.as_bytes());
+		}
+		let digest = hasher.finalize();
+		let mut hash = [0u8; 8];
+		hash.copy_from_slice(&digest[..8]);
+		u64::from_be_bytes(hash)
+	}
+}
+
+#[derive(Serialize)]
+struct ValueSizeBucketReport {
+	/// The inclusive upper bound of the bucket in bytes, `None` for the last bucket.
+	max_bytes: Option<u64>,
+	users: u64,
+}
+
+#[derive(Serialize)]
+struct AnalyticsReport {
+	period_start: u64,
+	period_end: u64,
+	epsilon: f64,
+	total_epsilon: f64,
+	active_users: u64,
+	active_stores: u64,
+	writers_by_value_size: Vec<ValueSizeBucketReport>,
+}
+
+/// Aggregates usage of the current reporting period.
+pub(crate) struct UsageAnalytics {
+	config: AnalyticsConfig,
+	period: Mutex<Period>,
+}
+
+impl UsageAnalytics {
+	pub(crate) fn new(config: AnalyticsConfig) -> Self {
+		Self { config, period: Mutex::new(Period::new()) }
+	}
+
+	pub(crate) fn report_interval(&self) -> Duration {
+		self.config.report_interval
+	}
+
+	fn record_access(&self, user_token: &str, store_id: &str) {
+		let mut period = self.period.lock().unwrap();
+		let user_hash = period.hash(&[user_token]);
+		let store_hash = period.hash(&[user_token, store_id]);
+		period.active_users.insert(user_hash);
+		let counted_stores = period.counted_stores.entry(user_hash).or_default();
+		if counted_stores.len() < MAX_COUNTED_STORES_PER_USER
+			&& !counted_stores.contains(&store_hash)
+		{
+			counted_stores.push(store_hash);
+			period.active_stores.insert(store_hash);
+		}
+	}
+
+	/// Counts the user as a writer in the bucket of the largest of `value_sizes`, unless they were
+	/// counted in a bucket before in this period.
+	fn record_write(&self, user_token: &str, value_sizes: impl Iterator<Item = usize>) {
+		let mut period = self.period.lock().unwrap();
+		let user_hash = period.hash(&[user_token]);
+		let Some(size) = value_sizes.max() else {
+			return;
+		};
+		if !period.counted_writers.insert(user_hash) {
+			return;
+		}
+		let bucket = VALUE_SIZE_BUCKETS
+			.iter()
+			.position(|max| size as u64 <= *max)
+			.unwrap_or(VALUE_SIZE_BUCKETS.len());
+		period.writers_by_value_size[bucket].insert(user_hash);
+	}
+
+	/// Closes the current reporting period and publishes its noised aggregates.
+	pub(crate) fn publish(&self) {
+		let period = std::mem::replace(&mut *self.period.lock().unwrap(), Period::new());
+		let report = AnalyticsReport {
+			period_start: period.started_at,
+			period_end: unix_time_secs(),
+			epsilon: self.config.epsilon,
+			total_epsilon: COUNTS_PER_REPORT * self.config.epsilon,
+			active_users: self.noisy_count(&period.active_users, 1),
+			active_stores: self.noisy_count(&period.active_stores, MAX_COUNTED_STORES_PER_USER),
+			writers_by_value_size: period
+				.writers_by_value_size
+				.iter()
+				.enumerate()
+				.map(|(idx, counter)| ValueSizeBucketReport {
+					max_bytes: VALUE_SIZE_BUCKETS.get(idx).copied(),
+					users: self.noisy_count(counter, 1),
+				})
+				.collect(),
+		};
+		// unwrap safety: the report always serializes successfully.
+		info!(target: ANALYTICS_LOG_TARGET, "{}", serde_json::to_string(&report).unwrap());
+	}
+
+	/// Estimates the count of `counter` with noise hiding the contribution of a single user, who
+	/// may change it by up to `sensitivity`.
+	fn noisy_count(&self, counter: &DistinctCounter, sensitivity: usize) -> u64 {
+		let noisy = counter.estimate() + laplace_noise(sensitivity as f64 / self.config.epsilon);
+		noisy.round().max(0.0) as u64
+	}
+}
+
+fn laplace_noise(scale: f64) -> f64 {
+	let u = rand::random::<f64>() - 0.5;
+	-scale * u.signum() * (1.0 - 2.0 * u.abs()).max(f64::MIN_POSITIVE).ln()
+}
+
+/// A [`KvStore`] decorator feeding successful requests into [`UsageAnalytics`].
+pub(crate) struct AnalyticsKvStore {
+	inner: Arc<dyn KvStore>,
+	analytics: Arc<UsageAnalytics>,
+}
+
+impl AnalyticsKvStore {
+	pub(crate) fn new(inner: Arc<dyn KvStore>, analytics: Arc<UsageAnalytics>) -> Self {
+		Self { inner, analytics }
+	}
+}
+
+#[async_trait]
+impl KvStore for AnalyticsKvStore {
+	async fn get(
+		&self, user_token: String, request: GetObjectRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		self.analytics.record_access(&user_token, &request.store_id);
+		self.inner.get(user_token, request).await
+	}
+
+	async fn put(
+		&self, user_token: String, request: PutObjectRequest,
+	) -> Result<PutObjectResponse, VssError> {
+		self.analytics.record_access(&user_token, &request.store_id);
+		let value_sizes: Vec<usize> =
+			request.transaction_items.iter().map(|kv| kv.value.len()).collect();
+		let response = self.inner.put(user_token.clone(), request).await?;
+		self.analytics.record_write(&user_token, value_sizes.into_iter());
+		Ok(response)
+	}
+
+	async fn delete(
+		&self, user_token: String, request: DeleteObjectRequest,
+	) -> Result<DeleteObjectResponse, VssError> {
+		self.analytics.record_access(&user_token, &request.store_id);
+		self.inner.delete(user_token, request).await
+	}
+
+	async fn list_key_versions(
+		&self, user_token: String, request: ListKeyVersionsRequest,
+	) -> Result<ListKeyVersionsResponse, VssError> {
+		self.analytics.record_access(&user_token, &request.store_id);
+		self.inner.list_key_versions(user_token, request).await
+	}
+
+	async fn list_objects(
+		&self, user_token: String, request: ListObjectsRequest,
+	) -> Result<ListObjectsResponse, VssError> {
+		self.analytics.record_access(&user_token, &request.store_id);
+		self.inner.list_objects(user_token, request).await
+	}
+
+	async fn get_objects_atomic(
+		&self, user_token: String, request: GetObjectsAtomicRequest,
+	) -> Result<GetObjectsAtomicResponse, VssError> {
+		self.analytics.record_access(&user_token, &request.store_id);
+		self.inner.get_objects_atomic(user_token, request).await
+	}
+
+	async fn get_object_version(
+		&self, user_token: String, request: GetObjectVersionRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		self.analytics.record_access(&user_token, &request.store_id);
+		self.inner.get_object_version(user_token, request).await
+	}
+
+	async fn append(
+		&self, user_token: String, request: AppendObjectRequest,
+	) -> Result<AppendObjectResponse, VssError> {
+		self.analytics.record_access(&user_token, &request.store_id);
+		let value_size = request.value.len();
+		let response = self.inner.append(user_token.clone(), request).await?;
+		self.analytics.record_write(&user_token, std::iter::once(value_size));
+		Ok(response)
+	}
+
+	async fn delete_store(&self, user_token: String, store_id: String) -> Result<u64, VssError> {
+		self.inner.delete_store(user_token, store_id).await
+	}
+
+	async fn delete_user(&self, user_token: String) -> Result<u64, VssError> {
+		self.inner.delete_user(user_token).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{AnalyticsConfig, UsageAnalytics, MAX_COUNTED_STORES_PER_USER};
+
+	use std::time::Duration;
+
+	fn analytics() -> UsageAnalytics {
+		UsageAnalytics::new(AnalyticsConfig {
+			report_interval: Duration::from_secs(60),
+			epsilon: 1.0,
+		})
+	}
+
+	#[test]
+	fn counts_a_bounded_number_of_stores_per_user() {
+		let analytics = analytics();
+		for i in 0..100 {
+			analytics.record_access("user", &format!("store{}", i));
+			analytics.record_access("user", "store0");
+		}
+		analytics.record_access("other", "store0");
+
+		let period = analytics.period.lock().unwrap();
+		assert_eq!(period.active_users.estimate().round(), 2.0);
+		assert_eq!(
+			period.active_stores.estimate().round(),
+			MAX_COUNTED_STORES_PER_USER as f64 + 1.0
+		);
+	}
+
+	#[test]
+	fn counts_each_writer_in_a_single_value_size_bucket() {
+		let analytics = analytics();
+		analytics.record_write("user", [10, 1 << 20].into_iter());
+		analytics.record_write("user", [1 << 23].into_iter());
+		analytics.record_write("other", [10].into_iter());
+
+		let period = analytics.period.lock().unwrap();
+		let writers: Vec<f64> =
+			period.writers_by_value_size.iter().map(|counter| counter.estimate().round()).collect();
+		assert_eq!(writers, vec![1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+	}
+}
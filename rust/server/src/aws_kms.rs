@@ -0,0 +1,194 @@
+//! Unwrapping of data keys with AWS KMS.
+//!
+//! Data keys are generated by KMS, e.g. with `aws kms generate-data-key-without-plaintext
+//! --key-id <key> --key-spec AES_256 --query CiphertextBlob --output text`, and configured as the
+//! returned base64 ciphertexts. The master key never leaves KMS. Requests are signed with
+//! Signature Version 4 using the credentials of the `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`
+//! and, for temporary credentials, `AWS_SESSION_TOKEN` environment variables.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use api::error::VssError;
+use impls::key_provider::{MasterKey, KEY_LEN};
+
+use crate::util::config::AwsKmsConfig;
+
+/// The time KMS has to unwrap a data key before the request fails.
+const KMS_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+const KMS_SERVICE: &str = "kms";
+const KMS_CONTENT_TYPE: &str = "application/x-amz-json-1.1";
+const KMS_DECRYPT_TARGET: &str = "TrentService.Decrypt";
+
+/// A [`MasterKey`] held by AWS KMS.
+pub(crate) struct AwsKmsMasterKey {
+	client: reqwest::Client,
+	endpoint: String,
+	host: String,
+	config: AwsKmsConfig,
+}
+
+impl AwsKmsMasterKey {
+	pub(crate) fn new(config: &AwsKmsConfig) -> Result<Self, String> {
+		let client = reqwest::Client::builder()
+			.timeout(KMS_REQUEST_TIMEOUT)
+			.build()
+			.map_err(|e| format!("Failed to create AWS KMS HTTP client: {}", e))?;
+		let endpoint = config
+			.endpoint
+			.clone()
+			.unwrap_or_else(|| format!("https://kms.{}.amazonaws.com", config.region));
+		let url = reqwest::Url::parse(&endpoint)
+			.map_err(|e| format!("Invalid AWS KMS endpoint {}: {}", endpoint, e))?;
+		let host = match (url.host_str(), url.port()) {
+			(Some(host), Some(port)) => format!("{}:{}", host, port),
+			(Some(host), None) => host.to_string(),
+			(None, _) => return Err(format!("The AWS KMS endpoint {} lacks a host", endpoint)),
+		};
+		Ok(Self { client, endpoint, host, config: config.clone() })
+	}
+}
+
+#[async_trait]
+impl MasterKey for AwsKmsMasterKey {
+	async fn unwrap_key(&self, wrapped_key: &str) -> Result<[u8; KEY_LEN], VssError> {
+		let internal_error = |e: String| {
+			VssError::InternalServerError(format!("Failed to unwrap data key with AWS KMS: {}", e))
+		};
+		let body =
+			json!({ "CiphertextBlob": wrapped_key, "KeyId": self.config.key_id }).to_string();
+		let mut headers = vec![
+			("content-type", KMS_CONTENT_TYPE.to_string()),
+			("host", self.host.clone()),
+			("x-amz-date", amz_date(&Utc::now())),
+			("x-amz-target", KMS_DECRYPT_TARGET.to_string()),
+		];
+		if let Some(session_token) = self.config.session_token.as_ref() {
+			headers.push(("x-amz-security-token", session_token.clone()));
+		}
+		let authorization = sign_request(
+			"POST",
+			"/",
+			&mut headers,
+			body.as_bytes(),
+			&self.config.region,
+			KMS_SERVICE,
+			&self.config.access_key_id,
+			&self.config.secret_access_key,
+		);
+
+		let mut request = self.client.post(&self.endpoint).body(body);
+		for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+			request = request.header(*name, value);
+		}
+		let response = request
+			.header(reqwest::header::AUTHORIZATION, authorization)
+			.send()
+			.await
+			.and_then(|response| response.error_for_status())
+			.map_err(|e| internal_error(e.to_string()))?;
+		let bytes = response.bytes().await.map_err(|e| internal_error(e.to_string()))?;
+		let body: serde_json::Value =
+			serde_json::from_slice(&bytes).map_err(|e| internal_error(e.to_string()))?;
+		// KMS returns plaintexts in base64.
+		body["Plaintext"]
+			.as_str()
+			.and_then(|plaintext| base64::engine::general_purpose::STANDARD.decode(plaintext).ok())
+			.and_then(|key| <[u8; KEY_LEN]>::try_from(key).ok())
+			.ok_or_else(|| internal_error("Response lacks a 32-byte plaintext key".to_string()))
+	}
+}
+
+fn amz_date(time: &DateTime<Utc>) -> String {
+	time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Signs a request without query parameters with Signature Version 4, returning the value of its
+/// `Authorization` header. `headers` must include `host` and `x-amz-date`, and are sorted.
+#[allow(clippy::too_many_arguments)]
+fn sign_request(
+	method: &str, path: &str, headers: &mut [(&str, String)], body: &[u8], region: &str,
+	service: &str, access_key_id: &str, secret_access_key: &str,
+) -> String {
+	headers.sort_by_key(|(name, _)| *name);
+	// unwrap safety: callers always pass the date header.
+	let amz_date = &headers.iter().find(|(name, _)| *name == "x-amz-date").unwrap().1;
+	let date = &amz_date[..8];
+	let canonical_headers: String =
+		headers.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect();
+	let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+	let canonical_request = format!(
+		"{}\n{}\n\n{}\n{}\n{}",
+		method,
+		path,
+		canonical_headers,
+		signed_headers,
+		hex(&Sha256::digest(body))
+	);
+	let credential_scope = format!("{}/{}/{}/aws4_request", date, region, service);
+	let string_to_sign = format!(
+		"AWS4-HMAC-SHA256\n{}\n{}\n{}",
+		amz_date,
+		credential_scope,
+		hex(&Sha256::digest(canonical_request.as_bytes()))
+	);
+	let signing_key = [region, service, "aws4_request"].iter().fold(
+		hmac_sha256(format!("AWS4{}", secret_access_key).as_bytes(), date.as_bytes()),
+		|key, part| hmac_sha256(&key, part.as_bytes()),
+	);
+	format!(
+		"AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+		access_key_id,
+		credential_scope,
+		signed_headers,
+		hex(&hmac_sha256(&signing_key, string_to_sign.as_bytes()))
+	)
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+	// unwrap safety: HMAC accepts keys of any length.
+	let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+	mac.update(message);
+	mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::sign_request;
+
+	#[test]
+	fn signs_requests_like_the_signature_v4_test_suite() {
+		// The get-vanilla case of the AWS Signature Version 4 test suite.
+		let mut headers = vec![
+			("x-amz-date", "20150830T123600Z".to_string()),
+			("host", "example.amazonaws.com".to_string()),
+		];
+		let authorization = sign_request(
+			"GET",
+			"/",
+			&mut headers,
+			b"",
+			"us-east-1",
+			"service",
+			"AKIDEXAMPLE",
+			"wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+		);
+		assert_eq!(
+			authorization,
+			"AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+			 SignedHeaders=host;x-amz-date, \
+			 Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+		);
+	}
+}
@@ -0,0 +1,177 @@
+//! Monitoring of the continuous backups the storage backend depends on for point-in-time
+//! recovery.
+//!
+//! A VSS instance whose database is not continuously archived can only be restored to its last
+//! full backup, losing channel state written since, which may cost users their funds. The backend
+//! is therefore asked periodically whether WAL archiving is enabled, succeeding and recent. The
+//! outcome is exposed as metrics, can fail readiness checks, and changes of it run an operator
+//! command, e.g. to page someone.
+
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use api::error::VssError;
+use impls::backup_status::{BackupStatus, BackupStatusProvider};
+use impls::health_check::HealthCheck;
+
+use log::{error, info, warn};
+
+/// How often the backup status is checked, unless configured otherwise.
+pub(crate) const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// The time without a WAL segment being archived after which backups are considered not running,
+/// unless configured otherwise.
+pub(crate) const DEFAULT_MAX_ARCHIVE_AGE: Duration = Duration::from_secs(60 * 60);
+
+pub(crate) struct BackupMonitorConfig {
+	/// How often the backup status is checked.
+	pub(crate) check_interval: Duration,
+	/// The time without a WAL segment being archived after which backups are considered not
+	/// running.
+	pub(crate) max_archive_age: Duration,
+	/// Whether the server reports itself not ready while backups are not running.
+	pub(crate) fail_readiness: bool,
+	/// A shell command run whenever backups stop or resume running.
+	pub(crate) alert_command: Option<String>,
+}
+
+/// The outcome of a check.
+struct CheckOutcome {
+	/// The status reported by the backend, unless it could not be queried.
+	status: Option<BackupStatus>,
+	/// Why backups are not running, if they are not.
+	problem: Option<String>,
+}
+
+/// Periodically checks that the backend's database is continuously backed up.
+pub(crate) struct BackupMonitor {
+	config: BackupMonitorConfig,
+	provider: Arc<dyn BackupStatusProvider>,
+	/// The outcome of the last check, `None` until the first check completed.
+	last_outcome: Mutex<Option<CheckOutcome>>,
+}
+
+impl BackupMonitor {
+	/// Starts checking the backup status on the current runtime.
+	pub(crate) fn start(
+		config: BackupMonitorConfig, provider: Arc<dyn BackupStatusProvider>,
+	) -> Arc<Self> {
+		let monitor = Arc::new(Self { config, provider, last_outcome: Mutex::new(None) });
+		let checker = Arc::clone(&monitor);
+		tokio::spawn(async move {
+			let mut interval = tokio::time::interval(checker.config.check_interval);
+			loop {
+				interval.tick().await;
+				checker.check().await;
+			}
+		});
+		monitor
+	}
+
+	/// Whether the server reports itself not ready while backups are not running.
+	pub(crate) fn fails_readiness(&self) -> bool {
+		self.config.fail_readiness
+	}
+
+	/// Why backups are not running, or `None` if they are or were not checked yet.
+	pub(crate) fn problem(&self) -> Option<String> {
+		self.last_outcome.lock().unwrap().as_ref().and_then(|outcome| outcome.problem.clone())
+	}
+
+	/// Whether backups are running, and the status last reported by the backend, once checked.
+	pub(crate) fn last_status(&self) -> Option<(bool, Option<BackupStatus>)> {
+		let last_outcome = self.last_outcome.lock().unwrap();
+		last_outcome.as_ref().map(|outcome| (outcome.problem.is_none(), outcome.status))
+	}
+
+	async fn check(&self) {
+		let outcome = match self.provider.backup_status().await {
+			Ok(status) => CheckOutcome {
+				problem: find_problem(&status, self.config.max_archive_age),
+				status: Some(status),
+			},
+			Err(e) => CheckOutcome {
+				status: None,
+				problem: Some(format!("The backup status could not be checked: {}", e)),
+			},
+		};
+		let problem = outcome.problem.clone();
+		let previous = self.last_outcome.lock().unwrap().replace(outcome);
+		// Backups are assumed to have been running before the first check, so that operators are
+		// alerted if they are not running on startup.
+		let was_running = previous.is_none_or(|previous| previous.problem.is_none());
+		match (was_running, problem) {
+			(true, Some(problem)) => {
+				error!("Backups are not running: {}", problem);
+				self.run_alert_command(false, &problem);
+			},
+			(false, None) => {
+				info!("Backups are running again");
+				self.run_alert_command(true, "");
+			},
+			_ => {},
+		}
+	}
+
+	/// Runs the configured alert command, passing the outcome in environment variables.
+	fn run_alert_command(&self, running: bool, problem: &str) {
+		let Some(alert_command) = self.config.alert_command.clone() else {
+			return;
+		};
+		let problem = problem.to_string();
+		tokio::task::spawn_blocking(move || {
+			let result = Command::new("sh")
+				.arg("-c")
+				.arg(&alert_command)
+				.env("VSS_BACKUPS_RUNNING", if running { "1" } else { "0" })
+				.env("VSS_BACKUP_PROBLEM", problem)
+				.status();
+			match result {
+				Ok(status) if status.success() => {},
+				Ok(status) => warn!("The backup alert command failed with {}", status),
+				Err(e) => warn!("Failed to run the backup alert command: {}", e),
+			}
+		});
+	}
+}
+
+/// Describes why backups with the given status are not running, if they are not.
+fn find_problem(status: &BackupStatus, max_archive_age: Duration) -> Option<String> {
+	if !status.archiving_enabled {
+		return Some("WAL archiving is disabled, set archive_mode".to_string());
+	}
+	let last_archived_at = status.last_archived_at.unwrap_or_default();
+	if status.last_failed_at.is_some_and(|failed_at| failed_at > last_archived_at) {
+		return Some(format!("Archiving WAL segments failed {} time(s)", status.failed_count));
+	}
+	match status.last_archived_at {
+		None => Some("No WAL segment was archived yet".to_string()),
+		Some(archived_at)
+			if status.checked_at.saturating_sub(archived_at) > max_archive_age.as_secs() =>
+		{
+			Some(format!("No WAL segment was archived for {}s", status.checked_at - archived_at))
+		},
+		Some(_) => None,
+	}
+}
+
+/// Reports the server ready only while the backend is healthy and backups are running.
+pub(crate) struct BackupAwareHealthCheck {
+	pub(crate) backend: Arc<dyn HealthCheck>,
+	pub(crate) backups: Arc<BackupMonitor>,
+}
+
+#[async_trait]
+impl HealthCheck for BackupAwareHealthCheck {
+	async fn check_health(&self) -> Result<(), VssError> {
+		self.backend.check_health().await?;
+		match self.backups.problem() {
+			Some(problem) => {
+				Err(VssError::InternalServerError(format!("Backups are not running: {}", problem)))
+			},
+			None => Ok(()),
+		}
+	}
+}
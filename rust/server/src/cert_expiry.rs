@@ -0,0 +1,231 @@
+//! Monitoring of the expiry of the certificates the server depends on.
+//!
+//! Expired certificates fail all at once: clients refuse the server's TLS certificate, connections
+//! to the database fail once its CA certificate expired, and the issuer of JWTs rotates keys whose
+//! certificates expire, making every token signed with the new key fail authentication against a
+//! pinned key. The certificates of the TLS listener, of the PostgreSQL CA and of the configured JWT
+//! keys, including those published in a JWKS, are therefore checked periodically. Their expiry is
+//! exposed as metrics, and certificates entering the warning period or expiring are logged and
+//! reported to Sentry.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::CertificateDer;
+
+use crate::admin_service::unix_time_secs;
+use crate::util::config::Configuration;
+use crate::util::tls::load_certificates;
+use impls::backend_factory::BackendConfig;
+
+use log::{error, warn};
+
+/// How often certificates are checked, unless configured otherwise.
+pub(crate) const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+/// The time before their expiry from which certificates are warned about, unless configured
+/// otherwise.
+pub(crate) const DEFAULT_WARNING_PERIOD: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
+#[derive(Clone, Copy)]
+pub(crate) struct CertificateExpiryConfig {
+	/// How often certificates are checked.
+	pub(crate) check_interval: Duration,
+	/// The time before their expiry from which certificates are warned about.
+	pub(crate) warning_period: Duration,
+}
+
+/// A DER encoded certificate the server depends on, along with what it is used for.
+#[derive(Clone)]
+pub(crate) struct MonitoredCertificate {
+	pub(crate) source: String,
+	pub(crate) der: Vec<u8>,
+}
+
+/// Returns the certificates currently in use, which may change over time, as those of a JWKS.
+pub(crate) type CertificateSource = Box<dyn Fn() -> Vec<MonitoredCertificate> + Send + Sync>;
+
+/// The expiry of a certificate, as of the last check.
+#[derive(Clone)]
+pub(crate) struct CertificateExpiry {
+	pub(crate) source: String,
+	pub(crate) subject: String,
+	/// The time the certificate expires at, in seconds since the Unix epoch.
+	pub(crate) not_after: i64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ExpiryState {
+	Valid,
+	Expiring,
+	Expired,
+}
+
+/// Periodically checks the expiry of the certificates the server depends on.
+pub(crate) struct CertificateExpiryMonitor {
+	config: CertificateExpiryConfig,
+	sources: Vec<CertificateSource>,
+	/// The expiries found by the last check.
+	last_expiries: Mutex<Vec<CertificateExpiry>>,
+	/// The state certificates were last alerted about, by source, subject and expiry.
+	alerted: Mutex<HashMap<(String, String, i64), ExpiryState>>,
+}
+
+impl CertificateExpiryMonitor {
+	/// Starts checking the certificates of `sources` on the current runtime.
+	pub(crate) fn start(
+		config: CertificateExpiryConfig, sources: Vec<CertificateSource>,
+	) -> Arc<Self> {
+		let monitor = Arc::new(Self {
+			config,
+			sources,
+			last_expiries: Mutex::new(Vec::new()),
+			alerted: Mutex::new(HashMap::new()),
+		});
+		let checker = Arc::clone(&monitor);
+		tokio::spawn(async move {
+			let mut interval = tokio::time::interval(checker.config.check_interval);
+			loop {
+				interval.tick().await;
+				checker.check();
+			}
+		});
+		monitor
+	}
+
+	/// The time before their expiry from which certificates are warned about.
+	pub(crate) fn warning_period(&self) -> Duration {
+		self.config.warning_period
+	}
+
+	/// The expiries found by the last check.
+	pub(crate) fn last_expiries(&self) -> Vec<CertificateExpiry> {
+		self.last_expiries.lock().unwrap().clone()
+	}
+
+	fn check(&self) {
+		let now = unix_time_secs() as i64;
+		let warning_period = self.config.warning_period.as_secs() as i64;
+		let mut expiries = Vec::new();
+		for certificate in self.sources.iter().flat_map(|source| source()) {
+			match x509_parser::parse_x509_certificate(&certificate.der) {
+				Ok((_, parsed)) => expiries.push(CertificateExpiry {
+					source: certificate.source,
+					subject: parsed.subject().to_string(),
+					not_after: parsed.validity().not_after.timestamp(),
+				}),
+				Err(e) => warn!("Failed to parse the {}: {}", certificate.source, e),
+			}
+		}
+
+		let mut alerted = self.alerted.lock().unwrap();
+		let mut current = HashMap::new();
+		for expiry in &expiries {
+			let state = if expiry.not_after <= now {
+				ExpiryState::Expired
+			} else if expiry.not_after - now <= warning_period {
+				ExpiryState::Expiring
+			} else {
+				ExpiryState::Valid
+			};
+			let key = (expiry.source.clone(), expiry.subject.clone(), expiry.not_after);
+			if state != ExpiryState::Valid && alerted.get(&key) != Some(&state) {
+				alert(expiry, state, now);
+			}
+			current.insert(key, state);
+		}
+		// Certificates no longer in use, e.g. rotated out of a JWKS, are forgotten.
+		*alerted = current;
+		*self.last_expiries.lock().unwrap() = expiries;
+	}
+}
+
+/// Logs and reports to Sentry that a certificate is about to expire or expired.
+fn alert(expiry: &CertificateExpiry, state: ExpiryState, now: i64) {
+	if state == ExpiryState::Expired {
+		let message = format!(
+			"The {} of {} expired {}s ago",
+			expiry.source,
+			expiry.subject,
+			now - expiry.not_after
+		);
+		error!("{}", message);
+		sentry::capture_message(&message, sentry::Level::Error);
+	} else {
+		let days = (expiry.not_after - now) / (24 * 60 * 60);
+		let message = format!(
+			"The {} of {} expires in {} day(s), at {}",
+			expiry.source,
+			expiry.subject,
+			days,
+			chrono::DateTime::from_timestamp(expiry.not_after, 0)
+				.map(|at| at.to_rfc3339())
+				.unwrap_or_default()
+		);
+		warn!("{}", message);
+		sentry::capture_message(&message, sentry::Level::Warning);
+	}
+}
+
+/// Returns a source of certificates which do not change while the server runs.
+pub(crate) fn fixed_source(certificates: Vec<MonitoredCertificate>) -> CertificateSource {
+	Box::new(move || certificates.clone())
+}
+
+/// Collects the certificates of the TLS listener, the PostgreSQL CA and the JWT issuer key, where
+/// configured.
+pub(crate) fn configured_certificates(
+	config: &Configuration,
+) -> Result<Vec<MonitoredCertificate>, String> {
+	let mut certificates = Vec::new();
+	if let Some(tls_config) = config.tls_config.as_ref() {
+		let chain = load_certificates(&tls_config.cert_path)?;
+		certificates.extend(monitored("server TLS certificate", chain));
+		if let Some(client_ca_path) = tls_config.client_ca_path.as_ref() {
+			let cas = load_certificates(client_ca_path)?;
+			certificates.extend(monitored("TLS client CA certificate", cas));
+		}
+	}
+	if let BackendConfig::Postgres(postgres) = &config.storage {
+		if let Some(Some(crt_pem)) = postgres.tls_config.as_ref() {
+			let cas = pem_certificates(crt_pem)
+				.map_err(|e| format!("Invalid PostgreSQL CA certificate: {}", e))?;
+			certificates.extend(monitored("PostgreSQL CA certificate", cas));
+		}
+	}
+	// The issuer key may be configured as a certificate, rather than a bare public key.
+	if let Some(rsa_pem) = config.rsa_pem.as_deref() {
+		let keys =
+			pem_certificates(rsa_pem).map_err(|e| format!("Invalid JWT issuer key: {}", e))?;
+		certificates.extend(monitored("JWT issuer certificate", keys));
+	}
+	Ok(certificates)
+}
+
+/// Returns the certificates published with the keys of a JWKS.
+#[cfg(feature = "jwks")]
+pub(crate) fn key_certificates(
+	issuer: &str, keys: Vec<auth_impls::jwt::KeyCertificate>,
+) -> Vec<MonitoredCertificate> {
+	keys.into_iter()
+		.map(|key| MonitoredCertificate {
+			source: format!("certificate of {} key {}", issuer, key.kid.as_deref().unwrap_or("-")),
+			der: key.der,
+		})
+		.collect()
+}
+
+fn pem_certificates(pem: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+	CertificateDer::pem_slice_iter(pem.as_bytes())
+		.collect::<Result<Vec<_>, _>>()
+		.map_err(|e| e.to_string())
+}
+
+fn monitored(
+	source: &'static str, certificates: Vec<CertificateDer<'static>>,
+) -> impl Iterator<Item = MonitoredCertificate> {
+	certificates
+		.into_iter()
+		.map(move |der| MonitoredCertificate { source: source.to_string(), der: der.to_vec() })
+}
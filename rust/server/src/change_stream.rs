@@ -0,0 +1,293 @@
+//! Pushes changes of stores to subscribed clients as they happen.
+//!
+//! Multi-device wallets need to learn that another device updated their state, which they would
+//! otherwise have to poll `listKeyVersions` for. A client subscribes to a store by opening a
+//! WebSocket to `/vss/subscribe?store_id=...`, authenticated as any other request, and then
+//! receives a JSON text message naming the written and deleted keys whenever objects of the store
+//! change. Messages carry keys only, never values, so subscribing needs `list` access to the whole
+//! store. A subscriber falling behind by more than the buffered events is told to resynchronize by
+//! listing the store instead of receiving the events it missed.
+//!
+//! Changes are broadcast by an observer of the store, so only writes served by this instance are
+//! pushed to subscribers connected to it, unless the backend publishes the changes committed by
+//! all instances sharing it. The broadcaster then follows those instead, pushing each changed key
+//! as an event of its own, and tells all subscribers to resynchronize if it lost track of them.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use hyper::upgrade::OnUpgrade;
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use api::auth::AccessVerb;
+use api::error::VssError;
+use api::observer::KvStoreObserver;
+use api::types::{AppendObjectRequest, DeleteObjectRequest, PutObjectRequest};
+use impls::change_feed::ChangeFeed;
+
+use crate::vss_service::StoreScopedRequest;
+
+use log::{debug, info, warn};
+
+pub(crate) const DEFAULT_MAX_SUBSCRIPTIONS_PER_USER: usize = 16;
+pub(crate) const DEFAULT_BUFFERED_EVENTS: usize = 64;
+
+/// How often subscribers are pinged, keeping idle connections open through proxies.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long to wait before subscribing to the backend's changes again after losing them.
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+
+pub(crate) struct ChangeStreamConfig {
+	/// The number of subscriptions a user may hold open at the same time, across their stores.
+	pub(crate) max_subscriptions_per_user: usize,
+	/// The number of events buffered for a subscriber before it has to resynchronize.
+	pub(crate) buffered_events: usize,
+}
+
+/// A change of a store, as pushed to its subscribers.
+#[derive(Serialize)]
+pub(crate) struct ChangeEvent {
+	store_id: String,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	updated_keys: Vec<String>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	deleted_keys: Vec<String>,
+	/// Whether events were missed, after which the subscriber has to list the store.
+	#[serde(skip_serializing_if = "std::ops::Not::not")]
+	resync: bool,
+}
+
+/// The channels of the subscribed stores, keyed by user token and store id.
+type Channels = HashMap<String, HashMap<String, broadcast::Sender<Arc<ChangeEvent>>>>;
+
+/// Broadcasts the changes of the stores to their subscribers.
+pub(crate) struct ChangeBroadcaster {
+	config: ChangeStreamConfig,
+	channels: Mutex<Channels>,
+}
+
+impl ChangeBroadcaster {
+	pub(crate) fn new(config: ChangeStreamConfig) -> Self {
+		Self { config, channels: Mutex::new(HashMap::new()) }
+	}
+
+	/// Subscribes to the changes of a store of `user_token`.
+	pub(crate) fn subscribe(
+		&self, user_token: &str, store_id: &str,
+	) -> Result<broadcast::Receiver<Arc<ChangeEvent>>, VssError> {
+		let mut channels = self.channels.lock().unwrap();
+		let stores = channels.entry(user_token.to_string()).or_default();
+		stores.retain(|_, sender| sender.receiver_count() > 0);
+		let subscriptions: usize = stores.values().map(|sender| sender.receiver_count()).sum();
+		if subscriptions >= self.config.max_subscriptions_per_user {
+			return Err(VssError::InvalidRequestError(format!(
+				"At most {} subscriptions may be open at the same time.",
+				self.config.max_subscriptions_per_user
+			)));
+		}
+		let buffered_events = self.config.buffered_events;
+		let sender = stores
+			.entry(store_id.to_string())
+			.or_insert_with(|| broadcast::channel(buffered_events).0);
+		Ok(sender.subscribe())
+	}
+
+	/// Publishes the changes committed by all instances sharing the backend instead of observing
+	/// the writes of this instance, resubscribing whenever the subscription to them is lost.
+	pub(crate) fn follow(self: Arc<Self>, changes: Arc<dyn ChangeFeed>) {
+		tokio::spawn(async move {
+			let mut resubscribing = false;
+			loop {
+				match changes.subscribe().await {
+					Ok(mut receiver) => {
+						// Changes committed while no subscription was open were missed.
+						if resubscribing {
+							info!("Resubscribed to the changes of the backend");
+							self.resync_all();
+						}
+						while let Some(change) = receiver.recv().await {
+							let key = std::iter::once(change.key.as_str());
+							match change.version {
+								Some(_) => self.publish(
+									&change.user_token,
+									&change.store_id,
+									key,
+									std::iter::empty(),
+								),
+								None => self.publish(
+									&change.user_token,
+									&change.store_id,
+									std::iter::empty(),
+									key,
+								),
+							}
+						}
+						warn!("Lost the subscription to the changes of the backend");
+					},
+					Err(e) => warn!("Failed to subscribe to the changes of the backend: {}", e),
+				}
+				resubscribing = true;
+				tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+			}
+		});
+	}
+
+	/// Tells all subscribers to resynchronize.
+	fn resync_all(&self) {
+		let channels = self.channels.lock().unwrap();
+		for (store_id, sender) in channels.values().flat_map(|stores| stores.iter()) {
+			let event = ChangeEvent {
+				store_id: store_id.clone(),
+				updated_keys: vec![],
+				deleted_keys: vec![],
+				resync: true,
+			};
+			let _ = sender.send(Arc::new(event));
+		}
+	}
+
+	fn publish<'a>(
+		&self, user_token: &str, store_id: &str, updated_keys: impl Iterator<Item = &'a str>,
+		deleted_keys: impl Iterator<Item = &'a str>,
+	) {
+		let mut channels = self.channels.lock().unwrap();
+		let Some(stores) = channels.get_mut(user_token) else {
+			return;
+		};
+		let Some(sender) = stores.get(store_id) else {
+			return;
+		};
+		let event = ChangeEvent {
+			store_id: store_id.to_string(),
+			updated_keys: updated_keys.map(str::to_string).collect(),
+			deleted_keys: deleted_keys.map(str::to_string).collect(),
+			resync: false,
+		};
+		// Sending only fails once all subscribers are gone.
+		if sender.send(Arc::new(event)).is_err() {
+			stores.remove(store_id);
+			if stores.is_empty() {
+				channels.remove(user_token);
+			}
+		}
+	}
+}
+
+impl KvStoreObserver for ChangeBroadcaster {
+	fn on_put(&self, user_token: &str, request: &PutObjectRequest) {
+		let updated_keys = request.transaction_items.iter().map(|kv| kv.key.as_str());
+		let deleted_keys = request.delete_items.iter().map(|kv| kv.key.as_str());
+		self.publish(user_token, &request.store_id, updated_keys, deleted_keys);
+	}
+
+	fn on_delete(&self, user_token: &str, request: &DeleteObjectRequest) {
+		let deleted_keys = request.key_value.iter().map(|kv| kv.key.as_str());
+		self.publish(user_token, &request.store_id, std::iter::empty(), deleted_keys);
+	}
+
+	fn on_append(&self, user_token: &str, request: &AppendObjectRequest, _version: i64) {
+		let updated_keys = std::iter::once(request.key.as_str());
+		self.publish(user_token, &request.store_id, updated_keys, std::iter::empty());
+	}
+}
+
+/// A request to subscribe to the changes of a store, read from the query of the handshake.
+pub(crate) struct SubscribeRequest {
+	pub(crate) store_id: String,
+}
+
+// Events name any key of the store.
+impl StoreScopedRequest for SubscribeRequest {
+	const VERB: AccessVerb = AccessVerb::List;
+
+	fn store_id(&self) -> &str {
+		&self.store_id
+	}
+
+	fn keys(&self) -> Vec<&str> {
+		vec![""]
+	}
+
+	fn keys_are_prefixes(&self) -> bool {
+		true
+	}
+}
+
+/// Checks that a request opens a WebSocket, returning the `Sec-WebSocket-Accept` header of the
+/// response accepting it.
+pub(crate) fn websocket_accept_key(headers: &HashMap<String, String>) -> Result<String, VssError> {
+	let is_websocket =
+		headers.get("upgrade").is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+	if !is_websocket || headers.get("sec-websocket-version").map(String::as_str) != Some("13") {
+		return Err(VssError::InvalidRequestError(
+			"Subscriptions must be opened as a WebSocket.".to_string(),
+		));
+	}
+	match headers.get("sec-websocket-key") {
+		Some(key) => Ok(derive_accept_key(key.as_bytes())),
+		None => Err(VssError::InvalidRequestError("Missing Sec-WebSocket-Key header.".to_string())),
+	}
+}
+
+/// Pushes the changes of a store to a subscriber over the upgraded connection, until either side
+/// closes it.
+pub(crate) async fn serve_subscription(
+	on_upgrade: OnUpgrade, store_id: String, mut receiver: broadcast::Receiver<Arc<ChangeEvent>>,
+) {
+	let upgraded = match on_upgrade.await {
+		Ok(upgraded) => upgraded,
+		Err(e) => {
+			debug!("Failed to upgrade the subscription to store {}: {}", store_id, e);
+			return;
+		},
+	};
+	let mut socket =
+		WebSocketStream::from_raw_socket(TokioIo::new(upgraded), Role::Server, None).await;
+	let mut ping = tokio::time::interval(PING_INTERVAL);
+	// The first tick completes immediately.
+	ping.tick().await;
+	loop {
+		let message = tokio::select! {
+			event = receiver.recv() => match event {
+				// unwrap safety: events always serialize successfully.
+				Ok(event) => serde_json::to_string(&*event).unwrap(),
+				Err(RecvError::Lagged(_)) => {
+					let event = ChangeEvent {
+						store_id: store_id.clone(),
+						updated_keys: vec![],
+						deleted_keys: vec![],
+						resync: true,
+					};
+					// unwrap safety: events always serialize successfully.
+					serde_json::to_string(&event).unwrap()
+				},
+				Err(RecvError::Closed) => break,
+			},
+			_ = ping.tick() => {
+				if socket.send(Message::Ping(Vec::new())).await.is_err() {
+					break;
+				}
+				continue;
+			},
+			// Pings of the client are answered while reading, other messages are ignored.
+			incoming = socket.next() => match incoming {
+				Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+				Some(Ok(_)) => continue,
+			},
+		};
+		if socket.send(Message::Text(message)).await.is_err() {
+			break;
+		}
+	}
+	debug!("Subscription to store {} closed", store_id);
+}
@@ -0,0 +1,123 @@
+//! The command line of the server binary.
+//!
+//! Besides serving the API, the binary runs the operational tasks which need the same
+//! configuration, so that operators can run them from the artifact they deploy. Running it with
+//! only a configuration file, or none, serves the API as `serve` does.
+
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::failover_drill::DrillSettings;
+use crate::fixtures::FixtureSettings;
+use crate::vss_service::BodyFormat;
+
+#[derive(Parser)]
+#[command(name = "vss-server", version, about = "Versioned Storage Service")]
+#[command(args_conflicts_with_subcommands = true)]
+pub(crate) struct Cli {
+	#[command(subcommand)]
+	pub(crate) command: Option<Command>,
+	/// The configuration file to serve the API with, when no command is given.
+	pub(crate) config_file: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub(crate) enum Command {
+	/// Serves the VSS API.
+	Serve {
+		/// The configuration file, settings may also be given as environment variables.
+		config_file: Option<String>,
+	},
+	/// Applies pending schema migrations to the configured backend and exits.
+	Migrate {
+		/// The configuration file, settings may also be given as environment variables.
+		config_file: Option<String>,
+		/// Lists the pending migrations without applying them.
+		#[arg(long)]
+		dry_run: bool,
+	},
+	/// Validates the configuration and prints the effective configuration, with secrets redacted.
+	CheckConfig {
+		/// The configuration file, settings may also be given as environment variables.
+		config_file: Option<String>,
+		/// Skips connecting to the backend and binding the configured ports, e.g. when checking
+		/// a configuration on another host than the server's.
+		#[arg(long)]
+		offline: bool,
+	},
+	/// Exports a store of a user in the format of `/vss/exportStore`, which `/vss/importStore`
+	/// accepts.
+	Export {
+		/// The configuration file, settings may also be given as environment variables.
+		config_file: Option<String>,
+		/// The user token of the store owner.
+		#[arg(long)]
+		user: String,
+		/// The ID of the store to export.
+		#[arg(long)]
+		store: String,
+		/// The encoding of the exported objects.
+		#[arg(long, value_enum, default_value_t = ExportFormat::Protobuf)]
+		format: ExportFormat,
+		/// The file to write the export to, instead of stdout.
+		#[arg(long)]
+		output: Option<PathBuf>,
+	},
+	/// Rehearses database failovers against the configured PostgreSQL database under load.
+	FailoverDrill {
+		/// The configuration file, settings may also be given as environment variables.
+		config_file: Option<String>,
+		#[command(flatten)]
+		settings: DrillSettings,
+	},
+	/// Populates the configured backend with synthetic LDK data.
+	GenerateFixtures {
+		/// The configuration file, settings may also be given as environment variables.
+		config_file: Option<String>,
+		#[command(flatten)]
+		settings: FixtureSettings,
+	},
+	/// Reads a configuration value from stdin and prints its encrypted `enc:` form.
+	EncryptConfigValue,
+	/// Prints a new data key, wrapped by the master key in `VSS_ENCRYPTION_MASTER_KEY`.
+	GenerateDataKey,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub(crate) enum ExportFormat {
+	/// Length-delimited protobuf `KeyValue` records.
+	Protobuf,
+	/// Newline-delimited JSON records.
+	Json,
+}
+
+impl From<ExportFormat> for BodyFormat {
+	fn from(format: ExportFormat) -> Self {
+		match format {
+			ExportFormat::Protobuf => BodyFormat::Protobuf,
+			ExportFormat::Json => BodyFormat::Json,
+		}
+	}
+}
+
+/// Parses an integer greater than zero.
+pub(crate) fn parse_positive<T: FromStr + Default + PartialOrd>(value: &str) -> Result<T, String> {
+	value
+		.parse::<T>()
+		.ok()
+		.filter(|value| *value > T::default())
+		.ok_or_else(|| "must be a positive integer".to_string())
+}
+
+/// Parses a positive number of seconds.
+pub(crate) fn parse_secs(value: &str) -> Result<Duration, String> {
+	parse_positive(value).map(Duration::from_secs)
+}
+
+/// Parses a positive number of milliseconds.
+pub(crate) fn parse_millis(value: &str) -> Result<Duration, String> {
+	parse_positive(value).map(Duration::from_millis)
+}
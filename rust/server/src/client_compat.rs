@@ -0,0 +1,138 @@
+//! Shims for known quirks of older VSS clients, so that upgrading the server does not break wallet
+//! versions which are already deployed.
+//!
+//! Each shim is enabled separately by the operator, as it relaxes validation which current clients
+//! rely on.
+
+use std::collections::HashMap;
+
+use api::types::{
+	AppendObjectRequest, DeleteObjectRequest, ExportStoreRequest, GetObjectRequest,
+	GetObjectVersionRequest, GetObjectsAtomicRequest, ImportStoreRequest, ListKeyVersionsRequest,
+	ListObjectsRequest, PutObjectRequest,
+};
+
+use log::debug;
+
+/// The enabled client compatibility shims.
+pub(crate) struct ClientCompatibility {
+	/// Header names sent by older clients, mapped to the lowercase names the server expects. A
+	/// legacy header is only used if the request lacks the expected one.
+	pub(crate) header_aliases: HashMap<String, String>,
+	/// The store addressed by requests with an empty store id, which older clients sent when the
+	/// application did not configure one.
+	pub(crate) default_store_id: Option<String>,
+	/// Whether a listing page size of 0 asks for the default page size. Clients predating the
+	/// optional `page_size` field always sent it, set to 0 if unspecified.
+	pub(crate) zero_page_size_as_unset: bool,
+}
+
+impl ClientCompatibility {
+	/// Renames the legacy headers of a request to the names the server expects.
+	pub(crate) fn normalize_headers(&self, headers_map: &mut HashMap<String, String>) {
+		for (legacy_name, name) in &self.header_aliases {
+			if headers_map.contains_key(name) {
+				continue;
+			}
+			if let Some(value) = headers_map.remove(legacy_name) {
+				debug!("Treating legacy header {} as {}", legacy_name, name);
+				headers_map.insert(name.clone(), value);
+			}
+		}
+	}
+
+	/// Rewrites a decoded request the way the enabled shims prescribe.
+	pub(crate) fn apply<T: ShimmedRequest>(&self, request: &mut T) {
+		if let Some(default_store_id) = self.default_store_id.as_ref() {
+			let store_id = request.store_id_mut();
+			if store_id.is_empty() {
+				debug!(
+					"Addressing default store {} for request without store id",
+					default_store_id
+				);
+				*store_id = default_store_id.clone();
+			}
+		}
+		request.apply_request_shims(self);
+	}
+}
+
+/// A request the [`ClientCompatibility`] shims apply to.
+pub(crate) trait ShimmedRequest {
+	fn store_id_mut(&mut self) -> &mut String;
+
+	/// Applies the shims specific to the type of request.
+	fn apply_request_shims(&mut self, _compat: &ClientCompatibility) {}
+}
+
+impl ShimmedRequest for GetObjectRequest {
+	fn store_id_mut(&mut self) -> &mut String {
+		&mut self.store_id
+	}
+}
+
+impl ShimmedRequest for GetObjectVersionRequest {
+	fn store_id_mut(&mut self) -> &mut String {
+		&mut self.store_id
+	}
+}
+
+impl ShimmedRequest for GetObjectsAtomicRequest {
+	fn store_id_mut(&mut self) -> &mut String {
+		&mut self.store_id
+	}
+}
+
+impl ShimmedRequest for PutObjectRequest {
+	fn store_id_mut(&mut self) -> &mut String {
+		&mut self.store_id
+	}
+}
+
+impl ShimmedRequest for DeleteObjectRequest {
+	fn store_id_mut(&mut self) -> &mut String {
+		&mut self.store_id
+	}
+}
+
+impl ShimmedRequest for AppendObjectRequest {
+	fn store_id_mut(&mut self) -> &mut String {
+		&mut self.store_id
+	}
+}
+
+impl ShimmedRequest for ListKeyVersionsRequest {
+	fn store_id_mut(&mut self) -> &mut String {
+		&mut self.store_id
+	}
+
+	fn apply_request_shims(&mut self, compat: &ClientCompatibility) {
+		if compat.zero_page_size_as_unset && self.page_size == Some(0) {
+			self.page_size = None;
+		}
+	}
+}
+
+impl ShimmedRequest for ExportStoreRequest {
+	fn store_id_mut(&mut self) -> &mut String {
+		&mut self.store_id
+	}
+}
+
+impl ShimmedRequest for ImportStoreRequest {
+	fn store_id_mut(&mut self) -> &mut String {
+		&mut self.store_id
+	}
+}
+
+impl ShimmedRequest for ListObjectsRequest {
+	fn store_id_mut(&mut self) -> &mut String {
+		&mut self.store_id
+	}
+
+	fn apply_request_shims(&mut self, compat: &ClientCompatibility) {
+		if compat.zero_page_size_as_unset && self.page_size == Some(0) {
+			self.page_size = None;
+		}
+	}
+}
@@ -0,0 +1,120 @@
+//! Enforcement of client-side encryption, for providers who must never store plaintext state.
+//!
+//! VSS clients encrypt values before sending them and wrap the ciphertext in a [`Storable`],
+//! whose [`EncryptionMetadata`] names the cipher and holds the nonce it was used with. With
+//! `require_client_encryption` set, objects written through the API are rejected unless their
+//! values decode as such a `Storable`. The server cannot tell ciphertext from plaintext, so this
+//! only catches clients which do not encrypt at all, not ones which encrypt badly.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use prost::Message;
+
+use api::error::VssError;
+use api::kv_store::KvStore;
+use api::types::{
+	AppendObjectRequest, AppendObjectResponse, DeleteObjectRequest, DeleteObjectResponse,
+	EncryptionMetadata, GetObjectRequest, GetObjectResponse, GetObjectVersionRequest,
+	GetObjectsAtomicRequest, GetObjectsAtomicResponse, ListKeyVersionsRequest,
+	ListKeyVersionsResponse, ListObjectsRequest, ListObjectsResponse, PutObjectRequest,
+	PutObjectResponse, Storable,
+};
+
+/// Whether `value` is a [`Storable`] carrying the metadata of client-side encryption.
+pub(crate) fn is_client_encrypted(value: &[u8]) -> bool {
+	match Storable::decode(value) {
+		Ok(Storable {
+			encryption_metadata: Some(EncryptionMetadata { cipher_format, nonce, .. }),
+			..
+		}) => !cipher_format.is_empty() && !nonce.is_empty(),
+		_ => false,
+	}
+}
+
+fn unencrypted_value_error(key: &str) -> VssError {
+	VssError::InvalidRequestError(format!(
+		"The value of {} is not encrypted client-side, which this server requires.",
+		key
+	))
+}
+
+/// Rejects writes of values which are not encrypted client-side.
+pub(crate) struct ClientEncryptionEnforcingKvStore {
+	inner: Arc<dyn KvStore>,
+}
+
+impl ClientEncryptionEnforcingKvStore {
+	pub(crate) fn new(inner: Arc<dyn KvStore>) -> Self {
+		Self { inner }
+	}
+}
+
+#[async_trait]
+impl KvStore for ClientEncryptionEnforcingKvStore {
+	async fn get(
+		&self, user_token: String, request: GetObjectRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		self.inner.get(user_token, request).await
+	}
+
+	async fn put(
+		&self, user_token: String, request: PutObjectRequest,
+	) -> Result<PutObjectResponse, VssError> {
+		if let Some(item) =
+			request.transaction_items.iter().find(|kv| !is_client_encrypted(&kv.value))
+		{
+			return Err(unencrypted_value_error(&item.key));
+		}
+		self.inner.put(user_token, request).await
+	}
+
+	async fn delete(
+		&self, user_token: String, request: DeleteObjectRequest,
+	) -> Result<DeleteObjectResponse, VssError> {
+		self.inner.delete(user_token, request).await
+	}
+
+	async fn list_key_versions(
+		&self, user_token: String, request: ListKeyVersionsRequest,
+	) -> Result<ListKeyVersionsResponse, VssError> {
+		self.inner.list_key_versions(user_token, request).await
+	}
+
+	async fn list_objects(
+		&self, user_token: String, request: ListObjectsRequest,
+	) -> Result<ListObjectsResponse, VssError> {
+		self.inner.list_objects(user_token, request).await
+	}
+
+	async fn get_objects_atomic(
+		&self, user_token: String, request: GetObjectsAtomicRequest,
+	) -> Result<GetObjectsAtomicResponse, VssError> {
+		self.inner.get_objects_atomic(user_token, request).await
+	}
+
+	async fn get_object_version(
+		&self, user_token: String, request: GetObjectVersionRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		self.inner.get_object_version(user_token, request).await
+	}
+
+	/// Requires each appended chunk to be encrypted on its own, as the server cannot tell whether
+	/// the value it is appended to still decrypts.
+	async fn append(
+		&self, user_token: String, request: AppendObjectRequest,
+	) -> Result<AppendObjectResponse, VssError> {
+		if !is_client_encrypted(&request.value) {
+			return Err(unencrypted_value_error(&request.key));
+		}
+		self.inner.append(user_token, request).await
+	}
+
+	async fn delete_store(&self, user_token: String, store_id: String) -> Result<u64, VssError> {
+		self.inner.delete_store(user_token, store_id).await
+	}
+
+	async fn delete_user(&self, user_token: String) -> Result<u64, VssError> {
+		self.inner.delete_user(user_token).await
+	}
+}
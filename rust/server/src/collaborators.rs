@@ -0,0 +1,285 @@
+//! Access to a user's stores granted to other authenticated users.
+//!
+//! A store owner grants a collaborator read or write access to one of their stores. The
+//! collaborator then sends regular requests for that store with the owner's user token in the
+//! `X-VSS-Store-Owner` header. Once the collaborator is authenticated as usual, the request is
+//! executed on behalf of the owner if a sufficient grant exists, and rejected otherwise, as
+//! decided by the [`AccessPolicy`] implemented by [`Collaborators`].
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use api::auth::{AccessPolicy, AccessRequest};
+use api::error::VssError;
+use impls::store_acl::{StoreAccess, StoreAcl, StoreGrant};
+
+use log::debug;
+
+/// The header naming the owner of the store a collaborator wants to access.
+pub(crate) const STORE_OWNER_HEADER: &str = "x-vss-store-owner";
+
+/// The number of collaborators a single store may have.
+const MAX_COLLABORATORS_PER_STORE: i64 = 100;
+/// The maximum length of user tokens and store ids, as imposed by the database schema.
+const MAX_IDENTIFIER_LENGTH: usize = 120;
+
+#[derive(Deserialize)]
+pub(crate) struct GrantStoreAccessRequest {
+	store_id: String,
+	grantee_user_token: String,
+	/// Either `read` or `write`.
+	access: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct RevokeStoreAccessRequest {
+	store_id: String,
+	grantee_user_token: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ListStoreAccessRequest {
+	store_id: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct StoreAccessGrant {
+	grantee_user_token: String,
+	access: &'static str,
+}
+
+#[derive(Serialize)]
+pub(crate) struct StoreAccessResponse {
+	grants: Vec<StoreAccessGrant>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct RevokeStoreAccessResponse {
+	revoked: bool,
+}
+
+/// Manages and enforces collaborator access to stores.
+pub(crate) struct Collaborators {
+	acl: Arc<dyn StoreAcl>,
+}
+
+impl Collaborators {
+	pub(crate) fn new(acl: Arc<dyn StoreAcl>) -> Self {
+		Self { acl }
+	}
+
+	pub(crate) async fn grant(
+		&self, user_token: String, request: GrantStoreAccessRequest,
+	) -> Result<StoreAccessResponse, VssError> {
+		let access = StoreAccess::from_name(&request.access).ok_or_else(|| {
+			VssError::InvalidRequestError("access must be either read or write.".to_string())
+		})?;
+		validate_identifiers(&request.store_id, &request.grantee_user_token)?;
+		if request.grantee_user_token == user_token {
+			return Err(VssError::InvalidRequestError(
+				"Access cannot be granted to the store owner.".to_string(),
+			));
+		}
+		let grant = StoreGrant {
+			owner_user_token: user_token.clone(),
+			store_id: request.store_id.clone(),
+			grantee_user_token: request.grantee_user_token,
+			access,
+		};
+		if !self.acl.grant_store_access(&grant, MAX_COLLABORATORS_PER_STORE).await? {
+			return Err(VssError::InvalidRequestError(format!(
+				"A store may have at most {} collaborators.",
+				MAX_COLLABORATORS_PER_STORE
+			)));
+		}
+		self.list(user_token, ListStoreAccessRequest { store_id: request.store_id }).await
+	}
+
+	pub(crate) async fn revoke(
+		&self, user_token: String, request: RevokeStoreAccessRequest,
+	) -> Result<RevokeStoreAccessResponse, VssError> {
+		let revoked = self
+			.acl
+			.revoke_store_access(&user_token, &request.store_id, &request.grantee_user_token)
+			.await?;
+		Ok(RevokeStoreAccessResponse { revoked })
+	}
+
+	pub(crate) async fn list(
+		&self, user_token: String, request: ListStoreAccessRequest,
+	) -> Result<StoreAccessResponse, VssError> {
+		let grants = self.acl.list_store_grants(&user_token, &request.store_id).await?;
+		Ok(StoreAccessResponse {
+			grants: grants
+				.into_iter()
+				.map(|grant| StoreAccessGrant {
+					grantee_user_token: grant.grantee_user_token,
+					access: grant.access.as_str(),
+				})
+				.collect(),
+		})
+	}
+}
+
+/// Allows callers to access their own stores, and the stores of other users who granted them
+/// sufficient access.
+#[async_trait]
+impl AccessPolicy for Collaborators {
+	async fn check(&self, request: &AccessRequest<'_>) -> Result<(), VssError> {
+		let caller_token = &request.caller.user_token;
+		if request.owner_user_token == caller_token {
+			return Ok(());
+		}
+		let required_access =
+			if request.verb.is_mutation() { StoreAccess::Write } else { StoreAccess::Read };
+		let granted = self
+			.acl
+			.get_store_access(request.owner_user_token, request.store_id, caller_token)
+			.await?;
+		match granted {
+			Some(access) if access >= required_access => Ok(()),
+			_ => {
+				debug!("Rejected collaborator access to store {}", request.store_id);
+				Err(VssError::AuthError(format!(
+					"No {} access granted to the requested store.",
+					required_access.as_str()
+				)))
+			},
+		}
+	}
+}
+
+fn validate_identifiers(store_id: &str, grantee_user_token: &str) -> Result<(), VssError> {
+	if store_id.is_empty() || grantee_user_token.is_empty() {
+		return Err(VssError::InvalidRequestError(
+			"store_id and grantee_user_token must not be empty.".to_string(),
+		));
+	}
+	if store_id.len() > MAX_IDENTIFIER_LENGTH || grantee_user_token.len() > MAX_IDENTIFIER_LENGTH {
+		return Err(VssError::InvalidRequestError(format!(
+			"store_id and grantee_user_token must not exceed {} characters.",
+			MAX_IDENTIFIER_LENGTH
+		)));
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		Collaborators, GrantStoreAccessRequest, ListStoreAccessRequest, RevokeStoreAccessRequest,
+		MAX_COLLABORATORS_PER_STORE,
+	};
+
+	use api::auth::{AccessPolicy, AccessRequest, AccessVerb, AuthResponse};
+	use api::error::VssError;
+	use impls::in_memory_store::InMemoryBackend;
+	use std::collections::HashMap;
+	use std::sync::Arc;
+
+	fn collaborators() -> Collaborators {
+		Collaborators::new(Arc::new(InMemoryBackend::new()))
+	}
+
+	fn grant_request(grantee: &str, access: &str) -> GrantStoreAccessRequest {
+		GrantStoreAccessRequest {
+			store_id: "store".to_string(),
+			grantee_user_token: grantee.to_string(),
+			access: access.to_string(),
+		}
+	}
+
+	async fn check(
+		collaborators: &Collaborators, caller: &str, owner: &str, verb: AccessVerb,
+	) -> Result<(), VssError> {
+		let caller = AuthResponse {
+			user_token: caller.to_string(),
+			limit_profile: None,
+			claims: HashMap::new(),
+			scopes: None,
+			store_ids: None,
+			auth_method: None,
+		};
+		let request = AccessRequest {
+			caller: &caller,
+			owner_user_token: owner,
+			store_id: "store",
+			keys: vec!["key"],
+			keys_are_prefixes: false,
+			verb,
+		};
+		collaborators.check(&request).await
+	}
+
+	#[tokio::test]
+	async fn enforces_the_granted_access() {
+		let collaborators = collaborators();
+		assert!(check(&collaborators, "owner", "owner", AccessVerb::Write).await.is_ok());
+		assert!(check(&collaborators, "reader", "owner", AccessVerb::Read).await.is_err());
+
+		collaborators.grant("owner".to_string(), grant_request("reader", "read")).await.unwrap();
+		collaborators.grant("owner".to_string(), grant_request("writer", "write")).await.unwrap();
+		for verb in [AccessVerb::Read, AccessVerb::List] {
+			assert!(check(&collaborators, "reader", "owner", verb).await.is_ok());
+		}
+		for verb in [AccessVerb::Write, AccessVerb::Delete] {
+			let result = check(&collaborators, "reader", "owner", verb).await;
+			assert!(matches!(result, Err(VssError::AuthError(_))));
+			assert!(check(&collaborators, "writer", "owner", verb).await.is_ok());
+		}
+		// Grants only apply to the owner's store, not to the grantee's.
+		assert!(check(&collaborators, "owner", "reader", AccessVerb::Read).await.is_err());
+	}
+
+	#[tokio::test]
+	async fn rejects_invalid_grants() {
+		let collaborators = collaborators();
+		for request in [
+			grant_request("owner", "read"),
+			grant_request("reader", "admin"),
+			grant_request("", "read"),
+			grant_request(&"a".repeat(121), "read"),
+		] {
+			let result = collaborators.grant("owner".to_string(), request).await;
+			assert!(matches!(result, Err(VssError::InvalidRequestError(_))));
+		}
+	}
+
+	#[tokio::test]
+	async fn limits_the_collaborators_per_store() {
+		let collaborators = collaborators();
+		for i in 0..MAX_COLLABORATORS_PER_STORE {
+			let request = grant_request(&format!("user{}", i), "read");
+			collaborators.grant("owner".to_string(), request).await.unwrap();
+		}
+		let result = collaborators.grant("owner".to_string(), grant_request("late", "read")).await;
+		assert!(matches!(result, Err(VssError::InvalidRequestError(_))));
+		// Changing the access of an existing collaborator is no further grant.
+		collaborators.grant("owner".to_string(), grant_request("user0", "write")).await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn only_owners_manage_their_grants() {
+		let collaborators = collaborators();
+		collaborators.grant("owner".to_string(), grant_request("writer", "write")).await.unwrap();
+
+		// Collaborators can neither revoke nor list grants of stores they do not own.
+		let revoke = || RevokeStoreAccessRequest {
+			store_id: "store".to_string(),
+			grantee_user_token: "writer".to_string(),
+		};
+		let response = collaborators.revoke("writer".to_string(), revoke()).await.unwrap();
+		assert!(!response.revoked);
+		let list = || ListStoreAccessRequest { store_id: "store".to_string() };
+		let response = collaborators.list("writer".to_string(), list()).await.unwrap();
+		assert!(response.grants.is_empty());
+		assert!(check(&collaborators, "writer", "owner", AccessVerb::Write).await.is_ok());
+
+		let response = collaborators.revoke("owner".to_string(), revoke()).await.unwrap();
+		assert!(response.revoked);
+		assert!(collaborators.list("owner".to_string(), list()).await.unwrap().grants.is_empty());
+		assert!(check(&collaborators, "writer", "owner", AccessVerb::Read).await.is_err());
+	}
+}
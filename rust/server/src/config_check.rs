@@ -0,0 +1,108 @@
+//! Sanity checks of a loaded configuration, beyond what parsing it verifies.
+//!
+//! `check-config` runs all of them and reports each, so that a deployment can be validated before
+//! it is rolled out. On startup, the server checks that its ports are available before it
+//! connects to its backend, failing fast with a message naming the setting at fault rather than
+//! after migrating the database.
+
+use std::net::TcpListener;
+
+use api::error::VssError;
+use impls::backend_factory::{connect_backend, pending_backend_migrations, BackendConfig};
+
+use crate::util::config::{AuthMethod, Configuration};
+
+/// The outcome of a check, describing what was found or what is wrong.
+pub(crate) type CheckResult = Result<String, String>;
+
+/// Checks that the addresses the server listens on are available.
+pub(crate) fn check_ports(config: &Configuration) -> CheckResult {
+	let mut addresses = vec![("bind_address", config.bind_address.as_str())];
+	if let Some(admin_config) = config.admin_config.as_ref() {
+		addresses.push(("[admin_config] bind_address", admin_config.bind_address.as_str()));
+	}
+	#[cfg(feature = "grpc")]
+	if let Some(grpc_bind_address) = config.grpc_bind_address.as_deref() {
+		addresses.push(("[grpc_config] bind_address", grpc_bind_address));
+	}
+	for (setting, address) in &addresses {
+		// The listener is closed right away, for the server to bind the address again.
+		TcpListener::bind(address)
+			.map_err(|e| format!("Cannot listen on {}, set as the {}: {}", address, setting, e))?;
+	}
+	let addresses: Vec<&str> = addresses.iter().map(|(_, address)| *address).collect();
+	Ok(format!("Able to listen on {}", addresses.join(", ")))
+}
+
+/// Checks that clients can be authenticated, and that the JWT public key, if any, parses.
+pub(crate) async fn check_authentication(config: &Configuration) -> CheckResult {
+	#[cfg(feature = "jwt")]
+	if let Some(rsa_pem) = config.rsa_pem.as_deref() {
+		crate::config_reload::build_jwt_authorizer(rsa_pem, config)
+			.await
+			.map_err(|e| format!("The JWT rsa_pem is not a valid public key: {}", e))?;
+	}
+	let mut methods = Vec::new();
+	if config.rsa_pem.is_some() || config.jwks_config.is_some() {
+		methods.push(AuthMethod::Jwt);
+	}
+	if config.api_key_auth_config.is_some() {
+		methods.push(AuthMethod::ApiKey);
+	}
+	if config.nostr_auth_config.is_some() {
+		methods.push(AuthMethod::Nostr);
+	}
+	if config.l402_config.is_some() {
+		methods.push(AuthMethod::L402);
+	}
+	if config.oidc_auth_config.is_some() {
+		methods.push(AuthMethod::Oidc);
+	}
+	if methods.is_empty() {
+		// Mirrors the fallback of the server to the signatures of clients' own keys.
+		if cfg!(feature = "sigs") {
+			methods.push(AuthMethod::Signature);
+		} else if !cfg!(noop_authorizer) {
+			return Err("No authentication method is configured, configure one of \
+				[jwt_auth_config], [api_key_auth_config], [nostr_auth_config], [l402_config] \
+				or [oidc_auth_config]"
+				.to_string());
+		}
+	}
+	let names: Vec<&str> = match config.auth_chain.as_ref() {
+		Some(chain) => chain.iter().map(AuthMethod::name).collect(),
+		None => methods.iter().map(AuthMethod::name).collect(),
+	};
+	match names.is_empty() {
+		true => {
+			Ok("No authentication, all storage with the same store id is commingled".to_string())
+		},
+		false => Ok(format!("Authenticating with {}", names.join(", "))),
+	}
+}
+
+/// Checks that the backend can be connected to and, for PostgreSQL, that its database exists and
+/// the server would start with its schema, without creating or migrating the database.
+pub(crate) async fn check_backend(storage: &BackendConfig) -> CheckResult {
+	let error = |e: VssError| format!("Failed to connect to the {} backend: {}", storage.name(), e);
+	let BackendConfig::Postgres(postgres) = storage else {
+		connect_backend(storage).await.map_err(error)?;
+		return Ok(format!("Connected to the {} backend", storage.name()));
+	};
+	// Connects to the existing database only, so that checking leaves no database behind. The
+	// server creates a missing database on startup.
+	let pending = pending_backend_migrations(storage).await.map_err(error)?.unwrap_or_default();
+	match (pending.first(), pending.last()) {
+		(Some(first), Some(last)) if !postgres.migrate_on_startup => Err(format!(
+			"The database schema lacks migration(s) {} through {} and migrate_on_startup is \
+			 disabled, apply them with `vss-server migrate`",
+			first.version, last.version
+		)),
+		(Some(_), Some(_)) => Ok(format!(
+			"Connected to the {} backend, {} migration(s) are applied on startup",
+			storage.name(),
+			pending.len()
+		)),
+		_ => Ok(format!("Connected to the {} backend, its schema is up to date", storage.name())),
+	}
+}
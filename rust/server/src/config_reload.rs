@@ -0,0 +1,199 @@
+//! Reloading of selected configuration while the server runs.
+//!
+//! When the server is sent `SIGHUP`, the configuration is read anew and the settings which can
+//! change without a restart are applied: the log level, the rate limits, the storage quotas and
+//! the public key verifying JWTs. The [`ConfigRegistry`] publishes each of them through a watch
+//! channel, which the modules using it read on every request, so that no connection is dropped.
+//! Other settings keep the values the server started with until it is restarted, as does whether
+//! rate limits, quotas and JWT authentication are enabled at all. A configuration which fails to
+//! load is rejected as a whole, leaving the running one in place.
+
+#[cfg(feature = "jwt")]
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+#[cfg(feature = "jwt")]
+use api::auth::{AuthResponse, Authorizer};
+#[cfg(feature = "jwt")]
+use api::error::VssError;
+#[cfg(feature = "jwt")]
+use auth_impls::jwt::JWTAuthorizer;
+
+#[cfg(feature = "jwt")]
+use async_trait::async_trait;
+use log::{info, warn};
+
+use crate::quota::QuotaConfig;
+use crate::rate_limit::RateLimitConfig;
+use crate::util::config::load_configuration;
+#[cfg(feature = "jwt")]
+use crate::util::config::Configuration;
+use crate::util::logger::ServerLogger;
+
+/// Holds the senders of the settings which are reloaded, for the features enabled at startup.
+pub(crate) struct ConfigRegistry {
+	config_file_path: Option<String>,
+	logger: Arc<ServerLogger>,
+	rate_limit: Option<watch::Sender<RateLimitConfig>>,
+	quota: Option<watch::Sender<QuotaConfig>>,
+	/// The authorizer verifying JWTs with a public key, along with the key.
+	#[cfg(feature = "jwt")]
+	jwt_authorizer: Option<(watch::Sender<Arc<dyn Authorizer>>, String)>,
+}
+
+impl ConfigRegistry {
+	pub(crate) fn new(config_file_path: Option<&str>, logger: Arc<ServerLogger>) -> Self {
+		Self {
+			config_file_path: config_file_path.map(str::to_string),
+			logger,
+			rate_limit: None,
+			quota: None,
+			#[cfg(feature = "jwt")]
+			jwt_authorizer: None,
+		}
+	}
+
+	/// Returns the receiver of the rate limits, starting out as `config`.
+	pub(crate) fn register_rate_limit(
+		&mut self, config: RateLimitConfig,
+	) -> watch::Receiver<RateLimitConfig> {
+		let (sender, receiver) = watch::channel(config);
+		self.rate_limit = Some(sender);
+		receiver
+	}
+
+	/// Returns the receiver of the storage quotas, starting out as `config`.
+	pub(crate) fn register_quota(&mut self, config: QuotaConfig) -> watch::Receiver<QuotaConfig> {
+		let (sender, receiver) = watch::channel(config);
+		self.quota = Some(sender);
+		receiver
+	}
+
+	/// Returns an authorizer delegating to `authorizer`, built from the public key `rsa_pem`, until
+	/// it is replaced by one built from the reloaded key.
+	#[cfg(feature = "jwt")]
+	pub(crate) fn register_jwt_authorizer(
+		&mut self, authorizer: Arc<dyn Authorizer>, rsa_pem: &str,
+	) -> ReloadableAuthorizer {
+		let (sender, receiver) = watch::channel(authorizer);
+		self.jwt_authorizer = Some((sender, rsa_pem.to_string()));
+		ReloadableAuthorizer(receiver)
+	}
+
+	/// Reads the configuration anew and applies the settings which changed.
+	pub(crate) async fn reload(&mut self) -> Result<(), String> {
+		// Loading may fetch secrets from Vault, which blocks.
+		let config_file_path = self.config_file_path.clone();
+		let mut config =
+			tokio::task::spawn_blocking(move || load_configuration(config_file_path.as_deref()))
+				.await
+				.map_err(|e| format!("Failed to load configuration: {}", e))??;
+		// The authorizer is built first, so that an invalid key leaves all settings in place.
+		#[cfg(feature = "jwt")]
+		let jwt_authorizer = match (&self.jwt_authorizer, config.rsa_pem.as_deref()) {
+			(Some((_, rsa_pem)), Some(new_rsa_pem)) if rsa_pem != new_rsa_pem => {
+				Some((build_jwt_authorizer(new_rsa_pem, &config).await?, new_rsa_pem.to_string()))
+			},
+			_ => None,
+		};
+
+		if self.logger.level() != config.log_level {
+			info!("Reloaded the log level as {}", config.log_level);
+			self.logger.set_level(config.log_level);
+		}
+		match (&self.rate_limit, config.rate_limit_config.take()) {
+			(Some(sender), Some(rate_limit)) => {
+				if sender.send_if_modified(|current| replace(current, rate_limit)) {
+					info!(
+						"Reloaded the rate limits as {} requests per second with a burst of {}",
+						rate_limit.requests_per_second, rate_limit.burst
+					);
+				}
+			},
+			(Some(_), None) => warn!("Rate limiting is only disabled on restart"),
+			(None, Some(_)) => warn!("Rate limiting is only enabled on restart"),
+			(None, None) => {},
+		}
+		match (&self.quota, config.quota_config.take()) {
+			(Some(sender), Some(quota)) => {
+				if sender.send_if_modified(|current| replace(current, quota)) {
+					info!("Reloaded the storage quotas");
+				}
+			},
+			(Some(_), None) => warn!("Storage quotas are only disabled on restart"),
+			(None, Some(_)) => warn!("Storage quotas are only enabled on restart"),
+			(None, None) => {},
+		}
+		#[cfg(feature = "jwt")]
+		self.apply_jwt_authorizer(jwt_authorizer, config.rsa_pem.is_some());
+		Ok(())
+	}
+
+	/// Publishes the authorizer built from the reloaded public key, if it changed.
+	#[cfg(feature = "jwt")]
+	fn apply_jwt_authorizer(
+		&mut self, reloaded: Option<(Arc<dyn Authorizer>, String)>, configured: bool,
+	) {
+		match (&mut self.jwt_authorizer, reloaded) {
+			(Some((sender, rsa_pem)), Some((authorizer, new_rsa_pem))) => {
+				sender.send_replace(authorizer);
+				*rsa_pem = new_rsa_pem;
+				info!("Reloaded the JWT public key");
+			},
+			(Some(_), None) if !configured => {
+				warn!("JWT authentication with a public key is only disabled on restart")
+			},
+			(None, _) if configured => {
+				warn!("JWT authentication with a public key is only enabled on restart")
+			},
+			_ => {},
+		}
+	}
+}
+
+/// Replaces `current` by `new`, returning whether it changed.
+fn replace<T: PartialEq>(current: &mut T, new: T) -> bool {
+	if *current == new {
+		return false;
+	}
+	*current = new;
+	true
+}
+
+/// Builds the authorizer verifying JWTs with the public key `rsa_pem`, set up as configured.
+#[cfg(feature = "jwt")]
+pub(crate) async fn build_jwt_authorizer(
+	rsa_pem: &str, config: &Configuration,
+) -> Result<Arc<dyn Authorizer>, String> {
+	let mut auth = JWTAuthorizer::new(rsa_pem).await?;
+	if let Some(algorithm) = config.jwt_algorithm.as_deref() {
+		auth = auth.with_algorithm(algorithm)?;
+	}
+	if let Some(claim) = config.jwt_profile_claim.clone() {
+		auth = auth.with_profile_claim(claim);
+	}
+	if let Some(claim) = config.jwt_scope_claim.clone() {
+		auth = auth.with_scope_claim(claim);
+	}
+	if let Some(claim) = config.jwt_store_claim.clone() {
+		auth = auth.with_store_claim(claim);
+	}
+	Ok(Arc::new(auth))
+}
+
+/// An [`Authorizer`] delegating to the one last published by the [`ConfigRegistry`].
+#[cfg(feature = "jwt")]
+pub(crate) struct ReloadableAuthorizer(watch::Receiver<Arc<dyn Authorizer>>);
+
+#[cfg(feature = "jwt")]
+#[async_trait]
+impl Authorizer for ReloadableAuthorizer {
+	async fn verify(
+		&self, headers_map: &HashMap<String, String>,
+	) -> Result<AuthResponse, VssError> {
+		let authorizer = Arc::clone(&self.0.borrow());
+		authorizer.verify(headers_map).await
+	}
+}
@@ -0,0 +1,89 @@
+//! Describes the conflicts of failed conditional writes to the clients sending them.
+//!
+//! A `CONFLICT_EXCEPTION` alone only tells a client that some version in its request is stale, so
+//! it has to list the whole store to find out which. After a conditional write fails, the server
+//! therefore reads the current versions of the keys and of the global version the request was
+//! conditional on, and returns those differing in the `ErrorResponse`, for the client to fetch and
+//! merge right away. The description is read after the conflict, so further writes may race it;
+//! it is a hint to narrow down what to fetch, not a snapshot to write against.
+
+use api::error::VssError;
+use api::kv_store::{KvStore, GLOBAL_VERSION_KEY};
+use api::types::{ConflictingKey, GetObjectRequest};
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+
+use log::debug;
+
+/// The versions a request is conditional on, which it may conflict with.
+pub(crate) struct VersionChecks {
+	pub(crate) store_id: String,
+	/// The keys along with the versions the request expects them at.
+	pub(crate) keys: Vec<(String, i64)>,
+	/// The global version the request expects the store at, if any.
+	pub(crate) global_version: Option<i64>,
+}
+
+/// The current versions differing from those a conflicting request expected.
+#[derive(Default)]
+pub(crate) struct ConflictDetails {
+	pub(crate) conflicting_keys: Vec<ConflictingKey>,
+	pub(crate) global_version: Option<i64>,
+}
+
+impl VersionChecks {
+	/// Reads the current versions of the checked keys and global version of the store, returning
+	/// those differing from the expected ones. The hashes of the current values are included only
+	/// if `reveal_values` is set, i.e. the caller may read them.
+	pub(crate) async fn describe_conflict(
+		&self, store: &dyn KvStore, user_token: &str, reveal_values: bool,
+	) -> ConflictDetails {
+		let store_id = &self.store_id;
+		let mut details = ConflictDetails::default();
+		if let Some(expected) = self.global_version {
+			match current(store, user_token, store_id, GLOBAL_VERSION_KEY).await {
+				Ok(Some((version, _))) if version != expected => {
+					details.global_version = Some(version);
+				},
+				Ok(_) => {},
+				Err(e) => debug!("Failed to read the global version of a conflict: {}", e),
+			}
+		}
+		for (key, expected) in &self.keys {
+			let (version, value_sha256) = match current(store, user_token, store_id, key).await {
+				Ok(Some((version, value))) => {
+					if reveal_values {
+						(version, Bytes::from(Sha256::digest(&value).to_vec()))
+					} else {
+						(version, Bytes::new())
+					}
+				},
+				Ok(None) => (0, Bytes::new()),
+				Err(e) => {
+					debug!("Failed to read the version of a conflicting key: {}", e);
+					continue;
+				},
+			};
+			if version != *expected {
+				details.conflicting_keys.push(ConflictingKey {
+					key: key.clone(),
+					version,
+					value_sha256,
+				});
+			}
+		}
+		details
+	}
+}
+
+/// Reads the current version and value of a key, or `None` if it does not exist.
+async fn current(
+	store: &dyn KvStore, user_token: &str, store_id: &str, key: &str,
+) -> Result<Option<(i64, Bytes)>, VssError> {
+	let request = GetObjectRequest { store_id: store_id.to_string(), key: key.to_string() };
+	match store.get(user_token.to_string(), request).await {
+		Ok(response) => Ok(response.value.map(|kv| (kv.version, kv.value))),
+		Err(VssError::NoSuchKeyError(_)) => Ok(None),
+		Err(e) => Err(e),
+	}
+}
@@ -0,0 +1,327 @@
+//! Delayed deletion of objects under critical key prefixes, such as channel monitors.
+//!
+//! Deleting a channel monitor too early may cost its owner the channel's funds, and a buggy or
+//! compromised client could do so in a single call. Deletions of objects under the configured
+//! prefixes are therefore only recorded as pending when requested, and executed once the
+//! configured delay passed, unless the owner cancelled them in the meantime. Until then, the
+//! object can still be read, and writing it keeps it, as a deletion is only executed if the object
+//! still has the version it had when the deletion was requested.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use api::auth::AccessVerb;
+use api::error::VssError;
+use api::kv_store::KvStore;
+use api::types::{
+	AppendObjectRequest, AppendObjectResponse, DeleteObjectRequest, DeleteObjectResponse,
+	GetObjectRequest, GetObjectResponse, GetObjectVersionRequest, GetObjectsAtomicRequest,
+	GetObjectsAtomicResponse, KeyValue, ListKeyVersionsRequest, ListKeyVersionsResponse,
+	ListObjectsRequest, ListObjectsResponse, PutObjectRequest, PutObjectResponse,
+};
+use impls::audit_store::AuditedKey;
+use impls::pending_deletion_store::{PendingDeletion, PendingDeletionStore};
+
+use log::{debug, info, warn};
+
+use crate::admin_service::unix_time_secs;
+use crate::vss_service::StoreScopedRequest;
+
+/// How long deletions are held back, unless configured otherwise.
+pub(crate) const DEFAULT_DELETION_DELAY: Duration = Duration::from_secs(24 * 60 * 60);
+/// How often due deletions are executed, unless configured otherwise.
+pub(crate) const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The number of due deletions executed per query.
+const DUE_DELETIONS_BATCH_SIZE: i64 = 100;
+
+pub(crate) struct DelayedDeletionConfig {
+	/// The key prefixes under which deletions are delayed.
+	pub(crate) key_prefixes: Vec<String>,
+	/// How long deletions are held back before they are executed.
+	pub(crate) delay: Duration,
+	/// How often due deletions are executed.
+	pub(crate) check_interval: Duration,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ListPendingDeletionsRequest {
+	store_id: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CancelDeletionRequest {
+	store_id: String,
+	key: String,
+}
+
+// Cancelling a deletion keeps the object, reverting a write.
+impl StoreScopedRequest for CancelDeletionRequest {
+	const VERB: AccessVerb = AccessVerb::Write;
+
+	fn store_id(&self) -> &str {
+		&self.store_id
+	}
+
+	fn keys(&self) -> Vec<&str> {
+		vec![&self.key]
+	}
+
+	fn audited_keys(&self) -> Option<Vec<AuditedKey>> {
+		Some(vec![AuditedKey { key: self.key.clone(), version: -1, deleted: false }])
+	}
+}
+
+#[derive(Serialize)]
+pub(crate) struct PendingDeletionEntry {
+	key: String,
+	version: i64,
+	/// The time the deletion was requested, in seconds since the Unix epoch.
+	requested_at: u64,
+	/// The time after which the deletion is executed, in seconds since the Unix epoch.
+	executable_after: u64,
+}
+
+#[derive(Serialize)]
+pub(crate) struct PendingDeletionsResponse {
+	deletions: Vec<PendingDeletionEntry>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct CancelDeletionResponse {
+	cancelled: bool,
+}
+
+/// Holds back deletions under critical key prefixes and executes them once due.
+pub(crate) struct DelayedDeletions {
+	config: DelayedDeletionConfig,
+	pending: Arc<dyn PendingDeletionStore>,
+	/// The store due deletions are executed on.
+	store: Arc<dyn KvStore>,
+}
+
+impl DelayedDeletions {
+	/// Spawns the task executing due deletions on `store` on the current runtime.
+	pub(crate) fn start(
+		config: DelayedDeletionConfig, pending: Arc<dyn PendingDeletionStore>,
+		store: Arc<dyn KvStore>,
+	) -> Arc<Self> {
+		let deletions = Arc::new(Self { config, pending, store });
+		let executor = Arc::clone(&deletions);
+		tokio::spawn(async move {
+			let mut interval = tokio::time::interval(executor.config.check_interval);
+			loop {
+				interval.tick().await;
+				executor.execute_due_deletions().await;
+			}
+		});
+		deletions
+	}
+
+	fn is_critical(&self, key: &str) -> bool {
+		self.config.key_prefixes.iter().any(|prefix| key.starts_with(prefix.as_str()))
+	}
+
+	/// Records the deletion of an object under a critical prefix as pending, following the
+	/// semantics of immediate deletions: deleting a missing object, or an object of another
+	/// version than the conditionally deleted one, succeeds without effect.
+	async fn request_deletion(
+		&self, user_token: String, store_id: String, key_value: KeyValue,
+	) -> Result<DeleteObjectResponse, VssError> {
+		let get_request = GetObjectRequest { store_id: store_id.clone(), key: key_value.key };
+		let object = match self.store.get(user_token.clone(), get_request).await {
+			Ok(response) => response.value,
+			Err(VssError::NoSuchKeyError(_)) => None,
+			Err(e) => return Err(e),
+		};
+		let Some(object) = object else {
+			return Ok(DeleteObjectResponse {});
+		};
+		if key_value.version != -1 && key_value.version != object.version {
+			return Ok(DeleteObjectResponse {});
+		}
+		let requested_at = unix_time_secs();
+		let deletion = PendingDeletion {
+			user_token,
+			store_id,
+			key: object.key,
+			version: object.version,
+			requested_at,
+			executable_after: requested_at + self.config.delay.as_secs(),
+		};
+		self.pending.add_pending_deletion(&deletion).await?;
+		debug!(
+			"Delayed deletion of an object in store {} until {}",
+			deletion.store_id, deletion.executable_after
+		);
+		Ok(DeleteObjectResponse {})
+	}
+
+	pub(crate) async fn list(
+		&self, user_token: String, request: ListPendingDeletionsRequest,
+	) -> Result<PendingDeletionsResponse, VssError> {
+		let deletions = self.pending.list_pending_deletions(&user_token, &request.store_id).await?;
+		Ok(PendingDeletionsResponse {
+			deletions: deletions
+				.into_iter()
+				.map(|deletion| PendingDeletionEntry {
+					key: deletion.key,
+					version: deletion.version,
+					requested_at: deletion.requested_at,
+					executable_after: deletion.executable_after,
+				})
+				.collect(),
+		})
+	}
+
+	pub(crate) async fn cancel(
+		&self, user_token: String, request: CancelDeletionRequest,
+	) -> Result<CancelDeletionResponse, VssError> {
+		let cancelled = self
+			.pending
+			.cancel_pending_deletion(&user_token, &request.store_id, &request.key)
+			.await?;
+		if cancelled {
+			info!("Cancelled pending deletion of an object in store {}", request.store_id);
+		}
+		Ok(CancelDeletionResponse { cancelled })
+	}
+
+	async fn execute_due_deletions(&self) {
+		loop {
+			let due = match self
+				.pending
+				.list_due_deletions(unix_time_secs(), DUE_DELETIONS_BATCH_SIZE)
+				.await
+			{
+				Ok(due) => due,
+				Err(e) => {
+					warn!("Failed to list due deletions: {}", e);
+					return;
+				},
+			};
+			let mut executed = 0;
+			for deletion in &due {
+				match self.execute(deletion).await {
+					Ok(()) => executed += 1,
+					Err(e) => warn!(
+						"Failed to execute pending deletion in store {}: {}",
+						deletion.store_id, e
+					),
+				}
+			}
+			if executed > 0 {
+				info!("Executed {} pending deletion(s)", executed);
+			}
+			// Deletions failing to execute are retried on the next check.
+			if (due.len() as i64) < DUE_DELETIONS_BATCH_SIZE || executed == 0 {
+				return;
+			}
+		}
+	}
+
+	async fn execute(&self, deletion: &PendingDeletion) -> Result<(), VssError> {
+		let request = DeleteObjectRequest {
+			store_id: deletion.store_id.clone(),
+			key_value: Some(KeyValue {
+				key: deletion.key.clone(),
+				version: deletion.version,
+				value: Bytes::new(),
+			}),
+		};
+		self.store.delete(deletion.user_token.clone(), request).await?;
+		self.pending.remove_pending_deletion(deletion).await?;
+		Ok(())
+	}
+}
+
+/// A [`KvStore`] decorator turning deletions under critical prefixes into pending deletions of
+/// [`DelayedDeletions`].
+pub(crate) struct DelayedDeletionKvStore {
+	inner: Arc<dyn KvStore>,
+	deletions: Arc<DelayedDeletions>,
+}
+
+impl DelayedDeletionKvStore {
+	pub(crate) fn new(inner: Arc<dyn KvStore>, deletions: Arc<DelayedDeletions>) -> Self {
+		Self { inner, deletions }
+	}
+}
+
+#[async_trait]
+impl KvStore for DelayedDeletionKvStore {
+	async fn get(
+		&self, user_token: String, request: GetObjectRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		self.inner.get(user_token, request).await
+	}
+
+	/// Rejects puts deleting objects under critical prefixes, as their deletion cannot be delayed
+	/// without breaking the atomicity of the put.
+	async fn put(
+		&self, user_token: String, request: PutObjectRequest,
+	) -> Result<PutObjectResponse, VssError> {
+		if let Some(item) =
+			request.delete_items.iter().find(|kv| self.deletions.is_critical(&kv.key))
+		{
+			return Err(VssError::InvalidRequestError(format!(
+				"Deleting {} is delayed and must be requested with deleteObject.",
+				item.key
+			)));
+		}
+		self.inner.put(user_token, request).await
+	}
+
+	async fn delete(
+		&self, user_token: String, request: DeleteObjectRequest,
+	) -> Result<DeleteObjectResponse, VssError> {
+		match request.key_value {
+			Some(key_value) if self.deletions.is_critical(&key_value.key) => {
+				self.deletions.request_deletion(user_token, request.store_id, key_value).await
+			},
+			_ => self.inner.delete(user_token, request).await,
+		}
+	}
+
+	async fn list_key_versions(
+		&self, user_token: String, request: ListKeyVersionsRequest,
+	) -> Result<ListKeyVersionsResponse, VssError> {
+		self.inner.list_key_versions(user_token, request).await
+	}
+
+	async fn list_objects(
+		&self, user_token: String, request: ListObjectsRequest,
+	) -> Result<ListObjectsResponse, VssError> {
+		self.inner.list_objects(user_token, request).await
+	}
+
+	async fn get_objects_atomic(
+		&self, user_token: String, request: GetObjectsAtomicRequest,
+	) -> Result<GetObjectsAtomicResponse, VssError> {
+		self.inner.get_objects_atomic(user_token, request).await
+	}
+
+	async fn get_object_version(
+		&self, user_token: String, request: GetObjectVersionRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		self.inner.get_object_version(user_token, request).await
+	}
+
+	async fn append(
+		&self, user_token: String, request: AppendObjectRequest,
+	) -> Result<AppendObjectResponse, VssError> {
+		self.inner.append(user_token, request).await
+	}
+
+	async fn delete_store(&self, user_token: String, store_id: String) -> Result<u64, VssError> {
+		self.inner.delete_store(user_token, store_id).await
+	}
+
+	async fn delete_user(&self, user_token: String) -> Result<u64, VssError> {
+		self.inner.delete_user(user_token).await
+	}
+}
@@ -0,0 +1,145 @@
+//! Drill rehearsing a database failover: puts load on the backend, terminates its database
+//! connections mid-load and checks that requests succeed again within the configured time.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+use api::kv_store::KvStore;
+use api::types::{GetObjectRequest, KeyValue, PutObjectRequest};
+
+use crate::cli::{parse_millis, parse_positive, parse_secs};
+
+/// The user token the drill's objects are stored under.
+const DRILL_USER_TOKEN: &str = "vss-failover-drill";
+/// The time the load runs before the first failover.
+const WARMUP: Duration = Duration::from_secs(2);
+
+#[derive(clap::Args)]
+pub(crate) struct DrillSettings {
+	/// The number of concurrent clients putting load on the backend.
+	#[arg(long, default_value = "4", value_parser = parse_positive::<usize>)]
+	pub(crate) workers: usize,
+	/// The number of failovers to rehearse.
+	#[arg(long, default_value = "3", value_parser = parse_positive::<u32>)]
+	pub(crate) rounds: u32,
+	/// The time between two failovers.
+	#[arg(long = "interval-secs", value_name = "SECS", default_value = "5", value_parser = parse_secs)]
+	pub(crate) interval: Duration,
+	/// The time requests must succeed again within after each failover.
+	#[arg(long = "slo-ms", value_name = "MS", default_value = "5000", value_parser = parse_millis)]
+	pub(crate) slo: Duration,
+}
+
+/// A single request made by a drill worker, timed relative to the start of the drill.
+struct Outcome {
+	started_at: Duration,
+	finished_at: Duration,
+	succeeded: bool,
+}
+
+pub(crate) struct DrillReport {
+	pub(crate) requests: usize,
+	pub(crate) failed_requests: usize,
+	/// The number of connections terminated in each round.
+	pub(crate) terminated_connections: Vec<usize>,
+	/// The time from each failover until a request started after it succeeded, if any did.
+	pub(crate) recovery_times: Vec<Option<Duration>>,
+}
+
+impl DrillReport {
+	/// Whether requests succeeded again within `slo` after every failover.
+	pub(crate) fn met_slo(&self, slo: Duration) -> bool {
+		self.recovery_times.iter().all(|recovery_time| recovery_time.is_some_and(|t| t <= slo))
+	}
+}
+
+/// Runs the drill, calling `terminate_connections` to simulate each failover.
+pub(crate) async fn run<F, Fut>(
+	store: Arc<dyn KvStore>, terminate_connections: F, settings: &DrillSettings,
+) -> DrillReport
+where
+	F: Fn() -> Fut,
+	Fut: Future<Output = usize>,
+{
+	let store_id = format!("drill-{:016x}", rand::random::<u64>());
+	let start = Instant::now();
+	let stop = Arc::new(AtomicBool::new(false));
+	let workers: Vec<_> = (0..settings.workers)
+		.map(|worker| {
+			let store = Arc::clone(&store);
+			let store_id = store_id.clone();
+			let stop = Arc::clone(&stop);
+			tokio::spawn(async move {
+				let mut outcomes = Vec::new();
+				while !stop.load(Ordering::Relaxed) {
+					let started_at = start.elapsed();
+					let succeeded = exercise(&*store, &store_id, worker).await;
+					outcomes.push(Outcome { started_at, finished_at: start.elapsed(), succeeded });
+					if !succeeded {
+						// Avoid spinning while the backend is unavailable.
+						tokio::time::sleep(Duration::from_millis(10)).await;
+					}
+				}
+				outcomes
+			})
+		})
+		.collect();
+
+	tokio::time::sleep(WARMUP).await;
+	let mut failovers = Vec::new();
+	let mut terminated_connections = Vec::new();
+	for _ in 0..settings.rounds {
+		failovers.push(start.elapsed());
+		terminated_connections.push(terminate_connections().await);
+		tokio::time::sleep(settings.interval).await;
+	}
+	stop.store(true, Ordering::Relaxed);
+
+	let mut outcomes = Vec::new();
+	for worker in workers {
+		// Workers only panic if the store does.
+		outcomes.extend(worker.await.unwrap_or_default());
+	}
+	let _ = store.delete_store(DRILL_USER_TOKEN.to_string(), store_id).await;
+
+	let recovery_times = failovers
+		.iter()
+		.map(|failover| {
+			outcomes
+				.iter()
+				.filter(|outcome| outcome.succeeded && outcome.started_at >= *failover)
+				.map(|outcome| outcome.finished_at - *failover)
+				.min()
+		})
+		.collect();
+	DrillReport {
+		requests: outcomes.len(),
+		failed_requests: outcomes.iter().filter(|outcome| !outcome.succeeded).count(),
+		terminated_connections,
+		recovery_times,
+	}
+}
+
+/// Writes and reads back an object, returning whether both requests succeeded.
+async fn exercise(store: &dyn KvStore, store_id: &str, worker: usize) -> bool {
+	let key = format!("worker_{}", worker);
+	let put_request = PutObjectRequest {
+		store_id: store_id.to_string(),
+		global_version: None,
+		transaction_items: vec![KeyValue {
+			key: key.clone(),
+			version: -1,
+			value: Bytes::from(rand::random::<[u8; 32]>().to_vec()),
+		}],
+		delete_items: vec![],
+	};
+	if store.put(DRILL_USER_TOKEN.to_string(), put_request).await.is_err() {
+		return false;
+	}
+	let get_request = GetObjectRequest { store_id: store_id.to_string(), key };
+	store.get(DRILL_USER_TOKEN.to_string(), get_request).await.is_ok()
+}
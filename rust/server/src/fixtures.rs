@@ -0,0 +1,201 @@
+//! Generator of synthetic LDK data, populating a VSS instance for load testing, migration
+//! rehearsals and reproducible benchmarks.
+//!
+//! Each generated store is shaped like the state of an LDK node: a channel manager, a network
+//! graph, a scorer and a few small records, a channel monitor per channel along with its pending
+//! monitor updates, and payment records. The number of channels, updates and payments varies
+//! between stores around the configured means. Values are random bytes, as are the encrypted
+//! values real clients store. The same settings and seed always generate the same data.
+
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use clap::builder::NonEmptyStringValueParser;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+
+use api::error::VssError;
+use api::kv_store::KvStore;
+use api::types::{KeyValue, PutObjectRequest};
+use impls::postgres_store::MAX_PUT_REQUEST_ITEM_COUNT;
+
+use crate::cli::parse_positive;
+
+/// The total size of the values written by a single put, bounding the size of requests.
+pub(crate) const MAX_PUT_BYTES: usize = 8 * 1024 * 1024;
+
+/// The sizes of the objects of an LDK node, in bytes, before scaling.
+const MANAGER_SIZE: RangeInclusive<usize> = 20_000..=80_000;
+const NETWORK_GRAPH_SIZE: RangeInclusive<usize> = 500_000..=2_000_000;
+const SCORER_SIZE: RangeInclusive<usize> = 50_000..=200_000;
+const SMALL_RECORD_SIZE: RangeInclusive<usize> = 100..=1_000;
+const MONITOR_SIZE: RangeInclusive<usize> = 5_000..=50_000;
+const MONITOR_UPDATE_SIZE: RangeInclusive<usize> = 200..=2_000;
+const PAYMENT_SIZE: RangeInclusive<usize> = 150..=600;
+
+#[derive(clap::Args)]
+pub(crate) struct FixtureSettings {
+	/// The number of users to generate stores for.
+	#[arg(long, default_value_t = 10)]
+	pub(crate) users: usize,
+	/// The number of stores of each user.
+	#[arg(long, default_value_t = 1)]
+	pub(crate) stores_per_user: usize,
+	/// The mean number of channels of a store.
+	#[arg(long, default_value_t = 4)]
+	pub(crate) channels: usize,
+	/// The mean number of pending monitor updates of a channel.
+	#[arg(long, default_value_t = 10)]
+	pub(crate) updates_per_channel: usize,
+	/// The mean number of payments of a store.
+	#[arg(long, default_value_t = 100)]
+	pub(crate) payments: usize,
+	/// The percentage all value sizes are scaled by.
+	#[arg(long, default_value_t = 100)]
+	pub(crate) value_scale_percent: usize,
+	/// The prefix of the generated user tokens, which are numbered from 0.
+	#[arg(long, default_value = "vss-fixture-user-", value_parser = NonEmptyStringValueParser::new())]
+	pub(crate) user_prefix: String,
+	/// The seed of the generated data.
+	#[arg(long, default_value_t = 0)]
+	pub(crate) seed: u64,
+	/// The number of stores written concurrently.
+	#[arg(long, default_value = "8", value_parser = parse_positive::<usize>)]
+	pub(crate) concurrency: usize,
+}
+
+pub(crate) struct FixtureReport {
+	pub(crate) stores: usize,
+	pub(crate) objects: u64,
+	pub(crate) bytes: u64,
+	pub(crate) elapsed: Duration,
+}
+
+/// Writes the stores described by `settings` to `store`, overwriting objects of earlier runs.
+pub(crate) async fn generate(
+	store: Arc<dyn KvStore>, settings: Arc<FixtureSettings>,
+) -> Result<FixtureReport, VssError> {
+	let start = Instant::now();
+	let stores = settings.users * settings.stores_per_user;
+	let next_store = Arc::new(AtomicUsize::new(0));
+	let objects = Arc::new(AtomicU64::new(0));
+	let bytes = Arc::new(AtomicU64::new(0));
+	let workers: Vec<_> = (0..settings.concurrency.min(stores))
+		.map(|_| {
+			let store = Arc::clone(&store);
+			let settings = Arc::clone(&settings);
+			let next_store = Arc::clone(&next_store);
+			let objects = Arc::clone(&objects);
+			let bytes = Arc::clone(&bytes);
+			tokio::spawn(async move {
+				loop {
+					let index = next_store.fetch_add(1, Ordering::Relaxed);
+					if index >= stores {
+						return Ok::<(), VssError>(());
+					}
+					let user_token =
+						format!("{}{}", settings.user_prefix, index / settings.stores_per_user);
+					let store_id = format!("store-{}", index % settings.stores_per_user);
+					let items = generate_store(&settings, index);
+					objects.fetch_add(items.len() as u64, Ordering::Relaxed);
+					let size: usize = items.iter().map(|kv| kv.value.len()).sum();
+					bytes.fetch_add(size as u64, Ordering::Relaxed);
+					write_store(&*store, &user_token, &store_id, items).await?;
+				}
+			})
+		})
+		.collect();
+	for worker in workers {
+		worker.await.map_err(|e| {
+			VssError::InternalServerError(format!("Fixture generation failed: {}", e))
+		})??;
+	}
+	Ok(FixtureReport {
+		stores,
+		objects: objects.load(Ordering::Relaxed),
+		bytes: bytes.load(Ordering::Relaxed),
+		elapsed: start.elapsed(),
+	})
+}
+
+/// Generates the objects of the store with the given index, deterministically for the seed.
+fn generate_store(settings: &FixtureSettings, index: usize) -> Vec<KeyValue> {
+	// Each store gets its own stream of random numbers, so its contents do not depend on the
+	// order stores are generated in.
+	let mut rng =
+		StdRng::seed_from_u64(settings.seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ index as u64);
+	let mut items = Vec::new();
+	let mut push = |rng: &mut StdRng, key: String, size: &RangeInclusive<usize>| {
+		let size = rng.random_range(size.clone()) * settings.value_scale_percent / 100;
+		let mut value = vec![0u8; size];
+		rng.fill_bytes(&mut value);
+		items.push(KeyValue { key, version: -1, value: Bytes::from(value) });
+	};
+
+	push(&mut rng, "manager".to_string(), &MANAGER_SIZE);
+	push(&mut rng, "network_graph".to_string(), &NETWORK_GRAPH_SIZE);
+	push(&mut rng, "scorer".to_string(), &SCORER_SIZE);
+	push(&mut rng, "node_metrics".to_string(), &SMALL_RECORD_SIZE);
+	push(&mut rng, "peers".to_string(), &SMALL_RECORD_SIZE);
+	for _ in 0..around(&mut rng, settings.channels) {
+		let funding_outpoint = format!("{}_{}", random_hex(&mut rng), rng.random_range(0..4));
+		push(&mut rng, format!("monitors#{}", funding_outpoint), &MONITOR_SIZE);
+		for update_id in 1..=around(&mut rng, settings.updates_per_channel) {
+			let key = format!("monitor_updates#{}#{}", funding_outpoint, update_id);
+			push(&mut rng, key, &MONITOR_UPDATE_SIZE);
+		}
+	}
+	for _ in 0..around(&mut rng, settings.payments) {
+		let key = format!("payments#{}", random_hex(&mut rng));
+		push(&mut rng, key, &PAYMENT_SIZE);
+	}
+	items
+}
+
+/// Draws a count uniformly distributed around `mean`.
+fn around(rng: &mut StdRng, mean: usize) -> usize {
+	rng.random_range(0..=2 * mean)
+}
+
+/// Returns 32 random bytes, hex encoded, like a transaction or payment id.
+fn random_hex(rng: &mut StdRng) -> String {
+	rng.random::<[u8; 32]>().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Writes the objects in batches bounded in item count and size.
+async fn write_store(
+	store: &dyn KvStore, user_token: &str, store_id: &str, items: Vec<KeyValue>,
+) -> Result<(), VssError> {
+	let mut batch = Vec::new();
+	let mut batch_size = 0;
+	for item in items {
+		if !batch.is_empty()
+			&& (batch.len() >= MAX_PUT_REQUEST_ITEM_COUNT
+				|| batch_size + item.value.len() > MAX_PUT_BYTES)
+		{
+			put(store, user_token, store_id, std::mem::take(&mut batch)).await?;
+			batch_size = 0;
+		}
+		batch_size += item.value.len();
+		batch.push(item);
+	}
+	if !batch.is_empty() {
+		put(store, user_token, store_id, batch).await?;
+	}
+	Ok(())
+}
+
+async fn put(
+	store: &dyn KvStore, user_token: &str, store_id: &str, transaction_items: Vec<KeyValue>,
+) -> Result<(), VssError> {
+	let request = PutObjectRequest {
+		store_id: store_id.to_string(),
+		global_version: None,
+		transaction_items,
+		delete_items: vec![],
+	};
+	store.put(user_token.to_string(), request).await.map(|_| ())
+}
@@ -0,0 +1,104 @@
+//! Unwrapping of data keys with Google Cloud KMS.
+//!
+//! Data keys are encrypted by Cloud KMS, e.g. with `head -c 32 /dev/urandom | gcloud kms encrypt
+//! --key <key> --plaintext-file - --ciphertext-file - | base64 -w0`, and configured as the base64
+//! ciphertexts. The master key never leaves Cloud KMS. Requests are authorized with the configured
+//! access token or, if none is, with one of the service account of the instance, fetched from the
+//! metadata server.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use base64::Engine;
+use serde_json::json;
+
+use api::error::VssError;
+use impls::key_provider::{MasterKey, KEY_LEN};
+
+use crate::util::config::GcpKmsConfig;
+
+/// The time Cloud KMS and the metadata server have to answer before the request fails.
+const KMS_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+const CLOUD_KMS_ADDRESS: &str = "https://cloudkms.googleapis.com";
+const METADATA_TOKEN_URL: &str =
+	"http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// A [`MasterKey`] held by Google Cloud KMS.
+pub(crate) struct GcpKmsMasterKey {
+	client: reqwest::Client,
+	decrypt_url: String,
+	access_token: Option<String>,
+}
+
+impl GcpKmsMasterKey {
+	pub(crate) fn new(config: &GcpKmsConfig) -> Result<Self, String> {
+		let client = reqwest::Client::builder()
+			.timeout(KMS_REQUEST_TIMEOUT)
+			.build()
+			.map_err(|e| format!("Failed to create Cloud KMS HTTP client: {}", e))?;
+		Ok(Self {
+			client,
+			decrypt_url: format!(
+				"{}/v1/{}:decrypt",
+				CLOUD_KMS_ADDRESS,
+				config.key_name.trim_matches('/')
+			),
+			access_token: config.access_token.clone(),
+		})
+	}
+
+	async fn access_token(&self) -> Result<String, String> {
+		if let Some(access_token) = self.access_token.as_ref() {
+			return Ok(access_token.clone());
+		}
+		let response = self
+			.client
+			.get(METADATA_TOKEN_URL)
+			.header("Metadata-Flavor", "Google")
+			.send()
+			.await
+			.and_then(|response| response.error_for_status())
+			.map_err(|e| {
+				format!("failed to fetch an access token from the metadata server: {}", e)
+			})?;
+		let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+		let body: serde_json::Value = serde_json::from_slice(&bytes).map_err(|e| e.to_string())?;
+		body["access_token"]
+			.as_str()
+			.map(str::to_string)
+			.ok_or_else(|| "the metadata server response lacks an access token".to_string())
+	}
+}
+
+#[async_trait]
+impl MasterKey for GcpKmsMasterKey {
+	async fn unwrap_key(&self, wrapped_key: &str) -> Result<[u8; KEY_LEN], VssError> {
+		let internal_error = |e: String| {
+			VssError::InternalServerError(format!(
+				"Failed to unwrap data key with Cloud KMS: {}",
+				e
+			))
+		};
+		let access_token = self.access_token().await.map_err(internal_error)?;
+		let response = self
+			.client
+			.post(&self.decrypt_url)
+			.bearer_auth(access_token)
+			.header(reqwest::header::CONTENT_TYPE, "application/json")
+			.body(json!({ "ciphertext": wrapped_key }).to_string())
+			.send()
+			.await
+			.and_then(|response| response.error_for_status())
+			.map_err(|e| internal_error(e.to_string()))?;
+		let bytes = response.bytes().await.map_err(|e| internal_error(e.to_string()))?;
+		let body: serde_json::Value =
+			serde_json::from_slice(&bytes).map_err(|e| internal_error(e.to_string()))?;
+		// Cloud KMS returns plaintexts in base64.
+		body["plaintext"]
+			.as_str()
+			.and_then(|plaintext| base64::engine::general_purpose::STANDARD.decode(plaintext).ok())
+			.and_then(|key| <[u8; KEY_LEN]>::try_from(key).ok())
+			.ok_or_else(|| internal_error("Response lacks a 32-byte plaintext key".to_string()))
+	}
+}
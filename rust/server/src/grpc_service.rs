@@ -0,0 +1,253 @@
+//! Serves the [`KvStore`] operations over gRPC, as the `vss.Vss` service defined in
+//! `proto/vss_grpc.proto`, for clients which already speak gRPC.
+//!
+//! Requests are authenticated and executed by the same handlers as their HTTP counterparts, with
+//! gRPC metadata taking the place of HTTP headers.
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use prost::Message;
+use tonic::body::BoxBody;
+use tonic::codec::ProstCodec;
+use tonic::codegen::{empty_body, http, BoxFuture, Service};
+use tonic::metadata::MetadataValue;
+use tonic::server::{Grpc, NamedService, UnaryService};
+use tonic::{Code, Status};
+use tracing::Instrument;
+
+use api::error::VssError;
+use api::kv_store::KvStore;
+
+use crate::conflict_feedback::ConflictDetails;
+use crate::quota::QUOTA_WARNING_HEADER;
+use crate::vss_service::{
+	get_error_status_code, handle_delete_object_request, handle_get_object_request,
+	handle_list_object_request, handle_put_object_request, retry_after_secs, to_error_response,
+	StoreScopedRequest, VssService, REQUEST_TIMED_OUT,
+};
+
+/// The fully qualified name of the gRPC service.
+const SERVICE_NAME: &str = "vss.Vss";
+
+/// The `vss.Vss` gRPC service, routing each method to its [`KvStore`] operation.
+#[derive(Clone)]
+pub(crate) struct VssGrpcService {
+	service: VssService,
+	max_message_size: usize,
+}
+
+impl VssGrpcService {
+	/// Constructs the gRPC service, limiting request messages to `max_message_size` bytes.
+	pub(crate) fn new(service: VssService, max_message_size: usize) -> Self {
+		Self { service, max_message_size }
+	}
+
+	fn serve<T, R, F, Fut>(
+		&self, request: http::Request<tonic::transport::Body>, operation_name: &'static str,
+		handler: F,
+	) -> BoxFuture<http::Response<BoxBody>, Infallible>
+	where
+		T: Message + Default + StoreScopedRequest + Send + 'static,
+		R: Message + Send + 'static,
+		F: FnOnce(Arc<dyn KvStore>, String, T) -> Fut + Copy + Send + 'static,
+		Fut: Future<Output = Result<R, VssError>> + Send + 'static,
+	{
+		let method = KvStoreMethod { service: self.service.clone(), operation_name, handler };
+		let mut grpc = Grpc::new(ProstCodec::<R, T>::default())
+			.max_decoding_message_size(self.max_message_size)
+			.max_encoding_message_size(usize::MAX);
+		Box::pin(async move { Ok(grpc.unary(method, request).await) })
+	}
+}
+
+impl Service<http::Request<tonic::transport::Body>> for VssGrpcService {
+	type Response = http::Response<BoxBody>;
+	type Error = Infallible;
+	type Future = BoxFuture<Self::Response, Self::Error>;
+
+	fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn call(&mut self, request: http::Request<tonic::transport::Body>) -> Self::Future {
+		let path = request.uri().path().to_owned();
+		match path.strip_prefix("/vss.Vss/").unwrap_or_default() {
+			"GetObject" => self.serve(request, "getObject", handle_get_object_request),
+			"PutObjects" => self.serve(request, "putObjects", handle_put_object_request),
+			"DeleteObject" => self.serve(request, "deleteObject", handle_delete_object_request),
+			"ListKeyVersions" => self.serve(request, "listKeyVersions", handle_list_object_request),
+			_ => Box::pin(async move {
+				Ok(http::Response::builder()
+					.header("grpc-status", (Code::Unimplemented as i32).to_string())
+					.header(http::header::CONTENT_TYPE, "application/grpc")
+					.body(empty_body())
+					// unwrap safety: body only errors when previous chained calls failed.
+					.unwrap())
+			}),
+		}
+	}
+}
+
+impl NamedService for VssGrpcService {
+	const NAME: &'static str = SERVICE_NAME;
+}
+
+/// A unary gRPC method executing a [`KvStore`] operation.
+#[derive(Clone)]
+struct KvStoreMethod<F> {
+	service: VssService,
+	operation_name: &'static str,
+	handler: F,
+}
+
+impl<T, R, F, Fut> UnaryService<T> for KvStoreMethod<F>
+where
+	T: Message + StoreScopedRequest + Send + 'static,
+	R: Message + Send + 'static,
+	F: FnOnce(Arc<dyn KvStore>, String, T) -> Fut + Copy + Send + 'static,
+	Fut: Future<Output = Result<R, VssError>> + Send + 'static,
+{
+	type Response = R;
+	type Future = BoxFuture<tonic::Response<R>, Status>;
+
+	fn call(&mut self, request: tonic::Request<T>) -> Self::Future {
+		let KvStoreMethod { service, operation_name, handler } = self.clone();
+		let span = tracing::info_span!(
+			"grpc.request",
+			rpc.system = "grpc",
+			rpc.service = SERVICE_NAME,
+			rpc.method = operation_name,
+			span.type = "web",
+			// Recorded while the request is handled.
+			operation = tracing::field::Empty,
+			user.hash = tracing::field::Empty,
+		);
+		Box::pin(
+			async move {
+				let peer_ip = request
+					.remote_addr()
+					.map(|addr| addr.ip())
+					.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+				let service = service.for_peer(peer_ip);
+				let started_at = Instant::now();
+				if let Err(retry_after) = service.check_load(operation_name) {
+					service.observe_shed_response(operation_name, started_at);
+					return Err(shed_status(retry_after));
+				}
+				if let Err(e) = service.check_maintenance(operation_name) {
+					service.observe_shed_response(operation_name, started_at);
+					return Err(to_status(e, None));
+				}
+				let in_flight = service.start_request(operation_name);
+				let headers_map = request
+					.metadata()
+					.clone()
+					.into_headers()
+					.iter()
+					// HeaderName converted to a string is in lowercase.
+					.map(|(k, v)| (k.to_string(), v.to_str().unwrap_or_default().to_string()))
+					.collect();
+
+				service.observe_request_body(operation_name, request.get_ref().encoded_len());
+				let authenticated = service.authenticate(&headers_map, &in_flight).await;
+				let user_token =
+					authenticated.as_ref().ok().map(|caller| caller.user_token.as_str());
+				if let Err(retry_after) = service.check_rate_limit(user_token) {
+					service.observe_response(operation_name, 429, started_at, 0);
+					return Err(rate_limited_status(retry_after));
+				}
+				let mut conflict_details = None;
+				let result = match authenticated {
+					Ok(caller) => {
+						let request = request.into_inner();
+						service
+							.execute_request(
+								&headers_map,
+								&in_flight,
+								caller,
+								request,
+								handler,
+								&mut conflict_details,
+							)
+							.await
+					},
+					Err(e) => Err(e),
+				};
+				// Statuses are recorded as the status codes of the equivalent HTTP responses.
+				let (status, body_size) = match &result {
+					Ok((response, _)) => (200, response.encoded_len()),
+					Err(e) => (get_error_status_code(e), 0),
+				};
+				service.observe_response(operation_name, status, started_at, body_size);
+				let (response, quota_warning) =
+					result.map_err(|e| to_status(e, conflict_details))?;
+				tracing::info!(operation = operation_name, "Request completed successfully");
+
+				let mut response = tonic::Response::new(response);
+				if let Some(value) =
+					quota_warning.and_then(|warning| MetadataValue::try_from(warning).ok())
+				{
+					response.metadata_mut().insert(QUOTA_WARNING_HEADER, value);
+				}
+				Ok(response)
+			}
+			.instrument(span),
+		)
+	}
+}
+
+/// Rejects a request exceeding the client's rate limit, telling it when to retry in the same
+/// `retry-after` header as HTTP responses.
+fn rate_limited_status(retry_after: Duration) -> Status {
+	let mut status = Status::resource_exhausted("Too many requests");
+	let retry_after_secs = (retry_after.as_secs_f64().ceil() as u64).max(1);
+	status.metadata_mut().insert("retry-after", MetadataValue::from(retry_after_secs));
+	status
+}
+
+/// Rejects a request shed while the backend is degraded, telling the client when to retry.
+fn shed_status(retry_after: Duration) -> Status {
+	let mut status = Status::unavailable("Service temporarily unavailable");
+	let retry_after_secs = (retry_after.as_secs_f64().ceil() as u64).max(1);
+	status.metadata_mut().insert("retry-after", MetadataValue::from(retry_after_secs));
+	status
+}
+
+/// Maps an error to the gRPC status clients are told, hiding the details of internal errors.
+/// Errors of the service being unavailable tell clients when to retry, like HTTP responses.
+///
+/// Conflicts carry the encoded [`ErrorResponse`] describing them as the status details, if these
+/// were read.
+///
+/// [`ErrorResponse`]: api::types::ErrorResponse
+fn to_status(e: VssError, conflict_details: Option<ConflictDetails>) -> Status {
+	let retry_after = retry_after_secs(&e);
+	let mut status = match e {
+		VssError::NoSuchKeyError(msg) => Status::not_found(msg),
+		VssError::ConflictError(msg) => match conflict_details {
+			Some(details) => {
+				let error_response =
+					to_error_response(VssError::ConflictError(msg.clone()), Some(details));
+				Status::with_details(Code::Aborted, msg, error_response.encode_to_vec().into())
+			},
+			None => Status::aborted(msg),
+		},
+		VssError::InvalidRequestError(msg) => Status::invalid_argument(msg),
+		VssError::AuthError(msg) => Status::unauthenticated(msg),
+		VssError::InternalServerError(msg) if msg == REQUEST_TIMED_OUT => {
+			Status::deadline_exceeded(msg)
+		},
+		VssError::InternalServerError(_) => Status::internal("Unknown Server Error occurred."),
+		VssError::QuotaExceededError(msg) => Status::resource_exhausted(msg),
+		VssError::ServiceUnavailableError(msg) => Status::unavailable(msg),
+	};
+	if let Some(retry_after_secs) = retry_after {
+		status.metadata_mut().insert("retry-after", MetadataValue::from(retry_after_secs));
+	}
+	status
+}
@@ -0,0 +1,118 @@
+//! Tracking of in-flight requests, to diagnose requests which hang.
+//!
+//! A stuck request otherwise only shows up as slowly rising latency. Each request registers itself
+//! for as long as it is being handled, together with the stage it is currently awaiting, so that
+//! `/admin/inFlightRequests` can list the requests running for longer than expected and where
+//! they are stuck.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// The number of bytes of the hashed user token identifying a user in diagnostics.
+const USER_HASH_LEN: usize = 8;
+
+struct InFlightRequest {
+	operation: &'static str,
+	user_hash: Option<String>,
+	stage: &'static str,
+	started_at: Instant,
+}
+
+#[derive(Serialize)]
+pub(crate) struct InFlightRequestInfo {
+	operation: &'static str,
+	/// A prefix of the SHA-256 hash of the user token, absent until the request is authorized.
+	user_hash: Option<String>,
+	stage: &'static str,
+	elapsed_ms: u128,
+}
+
+/// The requests currently being handled.
+#[derive(Default)]
+pub(crate) struct InFlightRequests {
+	next_id: AtomicU64,
+	requests: Mutex<HashMap<u64, InFlightRequest>>,
+}
+
+impl InFlightRequests {
+	/// Registers a request, which stays registered until the returned guard is dropped.
+	pub(crate) fn start(self: &Arc<Self>, operation: &'static str) -> InFlightGuard {
+		let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+		let request =
+			InFlightRequest { operation, user_hash: None, stage: "", started_at: Instant::now() };
+		self.requests.lock().unwrap().insert(id, request);
+		InFlightGuard { requests: Arc::clone(self), id, operation }
+	}
+
+	/// Returns the requests running for at least `min_elapsed`, longest running first.
+	pub(crate) fn list(&self, min_elapsed: Duration) -> Vec<InFlightRequestInfo> {
+		let mut requests: Vec<_> = self
+			.requests
+			.lock()
+			.unwrap()
+			.values()
+			.map(|request| (request.started_at.elapsed(), request))
+			.filter(|(elapsed, _)| *elapsed >= min_elapsed)
+			.map(|(elapsed, request)| InFlightRequestInfo {
+				operation: request.operation,
+				user_hash: request.user_hash.clone(),
+				stage: request.stage,
+				elapsed_ms: elapsed.as_millis(),
+			})
+			.collect();
+		requests.sort_by_key(|request| Reverse(request.elapsed_ms));
+		requests
+	}
+
+	fn update(&self, id: u64, f: impl FnOnce(&mut InFlightRequest)) {
+		if let Some(request) = self.requests.lock().unwrap().get_mut(&id) {
+			f(request);
+		}
+	}
+}
+
+/// Keeps a request registered in [`InFlightRequests`] while it is being handled.
+pub(crate) struct InFlightGuard {
+	requests: Arc<InFlightRequests>,
+	id: u64,
+	operation: &'static str,
+}
+
+impl InFlightGuard {
+	/// The name of the operation the request performs.
+	pub(crate) fn operation(&self) -> &'static str {
+		self.operation
+	}
+
+	/// Records what the request is about to await.
+	pub(crate) fn set_stage(&self, stage: &'static str) {
+		self.requests.update(self.id, |request| request.stage = stage);
+	}
+
+	/// Records the user the request was authorized for.
+	pub(crate) fn set_user(&self, user_token: &str) {
+		let user_hash = user_hash(user_token);
+		self.requests.update(self.id, |request| request.user_hash = Some(user_hash));
+	}
+}
+
+impl Drop for InFlightGuard {
+	fn drop(&mut self) {
+		self.requests.requests.lock().unwrap().remove(&self.id);
+	}
+}
+
+/// Identifies a user in diagnostics by a prefix of the SHA-256 hash of their user token.
+pub(crate) fn user_hash(user_token: &str) -> String {
+	hex_encode(&Sha256::digest(user_token.as_bytes())[..USER_HASH_LEN])
+}
+
+pub(crate) fn hex_encode(bytes: &[u8]) -> String {
+	bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
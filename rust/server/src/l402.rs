@@ -0,0 +1,91 @@
+//! Payment of access through L402 tokens, with invoices created on an LND node.
+//!
+//! Clients failing authentication are challenged with `402 Payment Required`, carrying a macaroon
+//! and an invoice in the `WWW-Authenticate` header. Each challenge creates an invoice on the node,
+//! so unauthenticated requests should be rate limited.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use base64::Engine;
+use serde_json::json;
+
+use api::error::VssError;
+use auth_impls::l402::{Invoice, InvoiceIssuer, L402Authorizer};
+
+use crate::util::config::L402Config;
+use crate::vss_service::AuthChallenger;
+
+/// The time LND has to create an invoice before the challenge fails.
+const LND_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The time invoices can be paid for.
+const INVOICE_EXPIRY_SECS: u64 = 60 * 60;
+
+/// Creates invoices through the REST API of an LND node.
+pub(crate) struct LndInvoiceIssuer {
+	client: reqwest::Client,
+	rest_url: String,
+	macaroon: String,
+}
+
+impl LndInvoiceIssuer {
+	pub(crate) fn new(config: &L402Config) -> Result<Self, String> {
+		let mut builder = reqwest::Client::builder().timeout(LND_REQUEST_TIMEOUT);
+		if let Some(cert_pem) = config.lnd_cert_pem.as_deref() {
+			let cert = reqwest::Certificate::from_pem(cert_pem)
+				.map_err(|e| format!("Invalid LND TLS certificate: {}", e))?;
+			builder = builder.add_root_certificate(cert);
+		}
+		let client =
+			builder.build().map_err(|e| format!("Failed to create LND HTTP client: {}", e))?;
+		Ok(Self {
+			client,
+			rest_url: config.lnd_rest_url.trim_end_matches('/').to_string(),
+			macaroon: config.lnd_macaroon.clone(),
+		})
+	}
+}
+
+#[async_trait]
+impl InvoiceIssuer for LndInvoiceIssuer {
+	async fn create_invoice(&self, amount_sats: u64, memo: &str) -> Result<Invoice, VssError> {
+		let internal_error =
+			|e: String| VssError::InternalServerError(format!("Failed to create invoice: {}", e));
+		let body = json!({
+			"value": amount_sats.to_string(),
+			"memo": memo,
+			"expiry": INVOICE_EXPIRY_SECS.to_string(),
+		});
+		let response = self
+			.client
+			.post(format!("{}/v1/invoices", self.rest_url))
+			.header("Grpc-Metadata-macaroon", &self.macaroon)
+			.header(reqwest::header::CONTENT_TYPE, "application/json")
+			.body(body.to_string())
+			.send()
+			.await
+			.and_then(|response| response.error_for_status())
+			.map_err(|e| internal_error(e.to_string()))?;
+		let bytes = response.bytes().await.map_err(|e| internal_error(e.to_string()))?;
+		let invoice: serde_json::Value =
+			serde_json::from_slice(&bytes).map_err(|e| internal_error(e.to_string()))?;
+		let payment_request = invoice["payment_request"]
+			.as_str()
+			.ok_or_else(|| internal_error("Response lacks the payment request".to_string()))?;
+		// LND encodes bytes in base64 in its REST API.
+		let payment_hash = invoice["r_hash"]
+			.as_str()
+			.and_then(|r_hash| base64::engine::general_purpose::STANDARD.decode(r_hash).ok())
+			.and_then(|r_hash| <[u8; 32]>::try_from(r_hash).ok())
+			.ok_or_else(|| internal_error("Response lacks a valid payment hash".to_string()))?;
+		Ok(Invoice { payment_request: payment_request.to_string(), payment_hash })
+	}
+}
+
+#[async_trait]
+impl AuthChallenger for L402Authorizer {
+	async fn challenge(&self) -> Result<String, VssError> {
+		Ok(self.issue_challenge().await?.www_authenticate())
+	}
+}
@@ -0,0 +1,177 @@
+//! Shedding of low-priority endpoints while the backend is degraded.
+//!
+//! The health of the backend is judged like a circuit breaker would: from the share of requests
+//! failing with internal errors or as the backend is unavailable over a sliding window. As that
+//! share grows, endpoints are shed by their priority, listings and summaries first and everything
+//! but reads and writes of single objects next, so that wallets can still persist their state
+//! during partial outages while the backend is relieved from the most expensive requests. Shed
+//! requests are rejected with `503 Service Unavailable`, telling the client when to retry, and do
+//! not count towards the failure share, so that the backend recovering is noticed once the window
+//! passed.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+/// The length of the window failures are counted over, unless configured otherwise.
+pub(crate) const DEFAULT_WINDOW: Duration = Duration::from_secs(10);
+/// The number of requests in the window needed to judge the backend's health, unless configured
+/// otherwise.
+pub(crate) const DEFAULT_MIN_REQUESTS: u64 = 20;
+/// The share of failing requests at which low-priority endpoints are shed, unless configured
+/// otherwise.
+pub(crate) const DEFAULT_DEGRADED_FAILURE_RATIO: f64 = 0.1;
+/// The share of failing requests at which all but critical endpoints are shed, unless configured
+/// otherwise.
+pub(crate) const DEFAULT_UNHEALTHY_FAILURE_RATIO: f64 = 0.5;
+
+/// The number of buckets the window is divided into.
+const WINDOW_BUCKETS: u32 = 10;
+
+pub(crate) struct LoadSheddingConfig {
+	/// The length of the window failures are counted over.
+	pub(crate) window: Duration,
+	/// The number of requests in the window needed to judge the backend's health.
+	pub(crate) min_requests: u64,
+	/// The share of failing requests at which low-priority endpoints are shed.
+	pub(crate) degraded_failure_ratio: f64,
+	/// The share of failing requests at which all but critical endpoints are shed.
+	pub(crate) unhealthy_failure_ratio: f64,
+}
+
+/// The health of the backend, as judged from recent requests.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum BackendHealth {
+	Healthy,
+	Degraded,
+	Unhealthy,
+}
+
+/// The priority of an endpoint, deciding from which [`BackendHealth`] on it is shed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Priority {
+	/// Reads and writes of objects, never shed.
+	Critical,
+	/// Shed once the backend is unhealthy.
+	Normal,
+	/// Listings, summaries and whole-store exports and imports, shed once the backend is degraded.
+	Low,
+}
+
+impl Priority {
+	fn of(operation_name: &str) -> Self {
+		match operation_name {
+			"getObject" | "getObjectsAtomic" | "putObjects" | "deleteObject" | "appendObject" => {
+				Priority::Critical
+			},
+			"listObjects" | "exportStore" | "importStore" | "getAccountSummary" => Priority::Low,
+			_ => Priority::Normal,
+		}
+	}
+
+	fn is_shed(&self, health: BackendHealth) -> bool {
+		match self {
+			Priority::Critical => false,
+			Priority::Normal => health == BackendHealth::Unhealthy,
+			Priority::Low => health >= BackendHealth::Degraded,
+		}
+	}
+}
+
+/// The requests and failures counted in a slice of the window.
+#[derive(Clone, Copy, Default)]
+struct Bucket {
+	/// The index of the slice since the shedder was started.
+	slice: u64,
+	requests: u64,
+	failures: u64,
+}
+
+struct State {
+	buckets: Vec<Bucket>,
+	/// The health last reported, to log changes only.
+	health: BackendHealth,
+}
+
+/// Tracks the health of the backend and decides which requests to shed.
+pub(crate) struct LoadShedder {
+	config: LoadSheddingConfig,
+	started_at: Instant,
+	state: Mutex<State>,
+}
+
+impl LoadShedder {
+	pub(crate) fn new(config: LoadSheddingConfig) -> Self {
+		let state = State {
+			buckets: vec![Bucket::default(); WINDOW_BUCKETS as usize],
+			health: BackendHealth::Healthy,
+		};
+		Self { config, started_at: Instant::now(), state: Mutex::new(state) }
+	}
+
+	fn slice_length(&self) -> Duration {
+		self.config.window / WINDOW_BUCKETS
+	}
+
+	fn current_slice(&self) -> u64 {
+		let slice_nanos = self.slice_length().as_nanos().max(1);
+		(self.started_at.elapsed().as_nanos() / slice_nanos) as u64
+	}
+
+	/// Records the outcome of a request which was not shed.
+	pub(crate) fn record(&self, failed: bool) {
+		let slice = self.current_slice();
+		let mut state = self.state.lock().unwrap();
+		let bucket = &mut state.buckets[(slice % WINDOW_BUCKETS as u64) as usize];
+		if bucket.slice != slice {
+			*bucket = Bucket { slice, requests: 0, failures: 0 };
+		}
+		bucket.requests += 1;
+		if failed {
+			bucket.failures += 1;
+		}
+	}
+
+	/// Judges the health of the backend from the requests of the window.
+	fn health(&self, state: &State) -> BackendHealth {
+		let slice = self.current_slice();
+		let (requests, failures) = state
+			.buckets
+			.iter()
+			.filter(|bucket| slice.saturating_sub(bucket.slice) < WINDOW_BUCKETS as u64)
+			.fold((0, 0), |(requests, failures), bucket| {
+				(requests + bucket.requests, failures + bucket.failures)
+			});
+		if requests == 0 || requests < self.config.min_requests {
+			return BackendHealth::Healthy;
+		}
+		let failure_ratio = failures as f64 / requests as f64;
+		if failure_ratio >= self.config.unhealthy_failure_ratio {
+			BackendHealth::Unhealthy
+		} else if failure_ratio >= self.config.degraded_failure_ratio {
+			BackendHealth::Degraded
+		} else {
+			BackendHealth::Healthy
+		}
+	}
+
+	/// Checks whether a request for `operation_name` is served under the current health of the
+	/// backend. Returns the time after which the client may retry if it is shed.
+	pub(crate) fn check(&self, operation_name: &str) -> Result<(), Duration> {
+		let mut state = self.state.lock().unwrap();
+		let health = self.health(&state);
+		if health != state.health {
+			match health {
+				BackendHealth::Healthy => info!("Backend recovered, serving all endpoints"),
+				_ => warn!("Backend is {:?}, shedding low-priority endpoints", health),
+			}
+			state.health = health;
+		}
+		if Priority::of(operation_name).is_shed(health) {
+			// The health is judged anew once the oldest requests left the window.
+			return Err(self.slice_length().max(Duration::from_secs(1)));
+		}
+		Ok(())
+	}
+}
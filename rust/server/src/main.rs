@@ -8,51 +8,199 @@
 #![deny(rustdoc::broken_intra_doc_links)]
 #![deny(rustdoc::private_intra_doc_links)]
 #![deny(missing_docs)]
+// The `json!` describing the effective configuration nests deeper than the default limit.
+#![recursion_limit = "256"]
 
+use std::io::Write;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
-use tokio::net::TcpListener;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
 use tokio::signal::unix::SignalKind;
 
+use http_body_util::BodyExt;
 use hyper::server::conn::http1;
-use hyper_util::rt::TokioIo;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
 
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
 
-use api::auth::Authorizer;
+use account_summary::AccountSummaries;
+use admin_service::{AdminService, AdminServiceConfig};
+use analytics::{AnalyticsKvStore, UsageAnalytics};
 #[cfg(noop_authorizer)]
 use api::auth::NoopAuthorizer;
+use api::auth::{AccessPolicy, AllOfPolicies, Authorizer, CompositeAuthorizer};
 use api::kv_store::KvStore;
+use api::observer::KvStoreObserver;
+use api::types::ExportStoreRequest;
+#[cfg(feature = "apikey")]
+use auth_impls::api_key::ApiKeyAuthorizer;
 #[cfg(feature = "jwt")]
 use auth_impls::jwt::JWTAuthorizer;
+#[cfg(feature = "l402")]
+use auth_impls::l402::L402Authorizer;
+#[cfg(feature = "nostr")]
+use auth_impls::nostr::NostrAuthorizer;
+#[cfg(feature = "oidc")]
+use auth_impls::oidc::OidcAuthorizer;
 #[cfg(feature = "sigs")]
 use auth_impls::signature::SignatureValidatingAuthorizer;
+use aws_kms::AwsKmsMasterKey;
+use backup_monitor::{BackupAwareHealthCheck, BackupMonitor};
+use cert_expiry::{CertificateExpiryMonitor, CertificateSource};
+use change_stream::ChangeBroadcaster;
+use clap::Parser;
+use cli::{Cli, Command, ExportFormat};
+use client_encryption::ClientEncryptionEnforcingKvStore;
+use collaborators::Collaborators;
+use config_check::{check_authentication, check_backend, check_ports};
+use config_reload::ConfigRegistry;
+use delayed_deletions::{DelayedDeletionKvStore, DelayedDeletions};
+use failover_drill::DrillSettings;
+use fixtures::FixtureSettings;
+use gcp_kms::GcpKmsMasterKey;
+#[cfg(feature = "object-store")]
+use impls::backend_factory::offload_values;
+use impls::backend_factory::{
+	connect_backend, migrate_backend, Backend, BackendConfig, MetadataStores,
+};
+use impls::encrypted_store::EncryptedKvStore;
+use impls::global_version_retrying_store::GlobalVersionRetryingKvStore;
+use impls::health_check::HealthCheck;
+use impls::in_memory_store::InMemoryBackend;
+use impls::key_provider::{EnvelopeKeyProvider, KeyProvider, LocalMasterKey, MasterKey};
+use impls::merging_store::MergingKvStore;
+use impls::observed_store::ObservedKvStore;
 use impls::postgres_store::{PostgresPlaintextBackend, PostgresTlsBackend};
-use util::logger::ServerLogger;
-use vss_service::{VssService, VssServiceConfig};
+use in_flight::InFlightRequests;
+use load_shedding::LoadShedder;
+use maintenance::Maintenance;
+use maintenance_mode::MaintenanceMode;
+use metrics::Metrics;
+use mutation_audit::MutationAudit;
+use nostr_notifications::{NostrNotifier, NostrNotifyingObserver};
+use quota::Quotas;
+use rate_limit::RateLimiter;
+use reencryption::ReencryptionJob;
+use request_capture::RequestCapture;
+use security_notifications::SecurityNotifier;
+use sessions::SessionLimiter;
+use share_tokens::ShareTokens;
+use soft_deletion::SoftDeletion;
+use store_export::handle_export_store_request;
+use store_namespaces::StoreNamespaces;
+use store_templates::StoreTemplates;
+use synthetic_probe::SyntheticProbe;
+use telemetry::{BudgetFilter, ExportBudget};
+use user_activity::{RequestCounter, RequestCountingKvStore};
+use util::config::{AuthMethod, EncryptionKeys, MasterKeyConfig};
+use util::logger::{JsonEventLayer, ServerLogger};
+use util::tls::load_tls_acceptor;
+use util::write_timeout::WriteTimeoutStream;
+use vault_transit::VaultTransitMasterKey;
+use vss_service::{AuthChallenger, ResponseBody, VssService, VssServiceConfig};
 
-use tracing_subscriber::layer::SubscriberExt;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{WithExportConfig, WithHttpConfig};
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use tracing_subscriber::layer::{Layer as _, SubscriberExt};
 
+mod account_summary;
+mod admin_service;
+mod analytics;
+mod aws_kms;
+mod backup_monitor;
+mod cert_expiry;
+mod change_stream;
+mod cli;
+mod client_compat;
+mod client_encryption;
+mod collaborators;
+mod config_check;
+mod config_reload;
+mod conflict_feedback;
+mod delayed_deletions;
+mod failover_drill;
+mod fixtures;
+mod gcp_kms;
+#[cfg(feature = "grpc")]
+mod grpc_service;
+mod in_flight;
+#[cfg(feature = "l402")]
+mod l402;
+mod load_shedding;
+mod maintenance;
+mod maintenance_mode;
+mod metrics;
+mod mutation_audit;
+mod nostr_notifications;
+mod quota;
+mod rate_limit;
+mod reencryption;
+mod request_capture;
+mod security_notifications;
+mod sessions;
+mod share_tokens;
+mod soft_deletion;
+mod store_export;
+mod store_import;
+mod store_namespaces;
+mod store_templates;
+mod synthetic_probe;
+mod telemetry;
+mod user_activity;
 mod util;
+mod vault_transit;
 mod vss_service;
 
+/// The time a client is given to complete the TLS handshake after connecting.
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// The time allowed to connect to Sentry, and to send it an event, before the event is dropped.
+const SENTRY_CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const SENTRY_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
 fn main() {
-	let args: Vec<String> = std::env::args().collect();
-	let config =
-		util::config::load_configuration(args.get(1).map(|s| s.as_str())).unwrap_or_else(|e| {
-			eprintln!("Failed to load configuration: {}", e);
-			std::process::exit(-1);
-		});
-	let vss_service_config = match config.max_request_body_size {
-		Some(size) => match VssServiceConfig::new(size) {
-			Ok(config) => config,
-			Err(e) => {
-				eprintln!("Configuration validation error: {}", e);
-				std::process::exit(-1);
-			},
+	let cli = Cli::parse();
+	match cli.command {
+		None => serve(cli.config_file.as_deref()),
+		Some(Command::Serve { config_file }) => serve(config_file.as_deref()),
+		Some(Command::Migrate { config_file, dry_run }) => migrate(config_file.as_deref(), dry_run),
+		Some(Command::CheckConfig { config_file, offline }) => {
+			check_config(config_file.as_deref(), offline)
 		},
-		None => VssServiceConfig::default(),
-	};
+		Some(Command::Export { config_file, user, store, format, output }) => {
+			export_store(config_file.as_deref(), user, store, format, output)
+		},
+		Some(Command::FailoverDrill { config_file, settings }) => {
+			run_failover_drill(config_file.as_deref(), settings)
+		},
+		Some(Command::GenerateFixtures { config_file, settings }) => {
+			generate_fixtures(config_file.as_deref(), settings)
+		},
+		Some(Command::EncryptConfigValue) => encrypt_config_value(),
+		Some(Command::GenerateDataKey) => generate_data_key(),
+	}
+}
+
+/// Serves the VSS API until the server is told to shut down.
+fn serve(config_file_path: Option<&str>) {
+	let mut config = util::config::load_configuration(config_file_path).unwrap_or_else(|e| {
+		eprintln!("Failed to load configuration: {}", e);
+		std::process::exit(-1);
+	});
+	let vss_service_config = VssServiceConfig::new(
+		config.max_request_body_size,
+		config.body_read_timeout,
+		config.request_timeout,
+	)
+	.unwrap_or_else(|e| {
+		eprintln!("Configuration validation error: {}", e);
+		std::process::exit(-1);
+	});
 
 	// Initialize Sentry before the tokio runtime to ensure proper Hub inheritance
 	// for spawned threads. The guard must be kept alive for the duration of the program.
@@ -60,20 +208,39 @@ fn main() {
 		eprintln!("Failed to load Sentry configuration from environment: {}", e);
 		std::process::exit(-1);
 	});
-	let _sentry_guard = initialize_sentry(&sentry_config);
+	let mut telemetry_budgets = Vec::new();
+	let _sentry_guard = initialize_sentry(&sentry_config, &mut telemetry_budgets);
 
 	let datadog_config = util::config::load_datadog_configuration().unwrap_or_else(|e| {
 		eprintln!("Failed to load Datadog configuration from environment: {}", e);
 		std::process::exit(-1);
 	});
 
-	// Initialize Datadog APM tracing
-	initialize_datadog(&datadog_config);
+	let effective_config =
+		Arc::new(util::config_dump::effective_config(&config, &sentry_config, &datadog_config));
 
-	let logger = ServerLogger::init(config.log_level, &config.log_file).unwrap_or_else(|e| {
-		eprintln!("Failed to initialize logger: {e}");
+	let logger = ServerLogger::init(config.log_level, config.log_format, &config.log_file)
+		.unwrap_or_else(|e| {
+			eprintln!("Failed to initialize logger: {e}");
+			std::process::exit(-1);
+		});
+
+	// Initialize Datadog APM tracing and OpenTelemetry trace export
+	let otel_provider = initialize_tracing(
+		&datadog_config,
+		config.otel_config.as_ref(),
+		logger.json_event_layer(),
+		&mut telemetry_budgets,
+	);
+	for line in util::config_dump::startup_banner(&effective_config) {
+		info!("{}", line);
+	}
+	info!("Run `vss-server check-config` to print the effective configuration");
+	// A port in use fails the server before it connects to its backend.
+	if let Err(e) = check_ports(&config) {
+		error!("{}", e);
 		std::process::exit(-1);
-	});
+	}
 
 	let runtime = match tokio::runtime::Builder::new_multi_thread().enable_all().build() {
 		Ok(runtime) => Arc::new(runtime),
@@ -84,7 +251,7 @@ fn main() {
 	};
 
 	runtime.block_on(async {
-		// Register SIGHUP handler for log rotation
+		// Register SIGHUP handler for log rotation and configuration reloads
 		let mut sighup_stream = match tokio::signal::unix::signal(SignalKind::hangup()) {
 			Ok(stream) => stream,
 			Err(e) => {
@@ -93,6 +260,15 @@ fn main() {
 			}
 		};
 
+		// Register SIGUSR1 handler for toggling maintenance mode
+		let mut sigusr1_stream = match tokio::signal::unix::signal(SignalKind::user_defined1()) {
+			Ok(stream) => stream,
+			Err(e) => {
+				error!("Failed to register SIGUSR1 handler: {e}");
+				std::process::exit(-1);
+			}
+		};
+
 		let mut sigterm_stream = match tokio::signal::unix::signal(SignalKind::terminate()) {
 			Ok(stream) => stream,
 			Err(e) => {
@@ -100,30 +276,200 @@ fn main() {
 				std::process::exit(-1);
 			},
 		};
+		let mut config_registry = ConfigRegistry::new(config_file_path, Arc::clone(&logger));
 
-		let mut authorizer: Option<Arc<dyn Authorizer>> = None;
+		// The configured authorizers, along with their authentication method.
+		#[allow(unused_mut)]
+		let mut authorizers: Vec<(AuthMethod, Arc<dyn Authorizer>)> = Vec::new();
+		// The certificates of keys fetched by authorizers, whose expiry is monitored.
+		#[allow(unused_mut)]
+		let mut certificate_sources: Vec<CertificateSource> = Vec::new();
+		#[allow(unused_mut)]
+		let mut auth_challenger: Option<Arc<dyn AuthChallenger>> = None;
 		#[cfg(feature = "jwt")]
 		{
 			if let Some(rsa_pem) = config.rsa_pem.as_deref() {
-				authorizer = match JWTAuthorizer::new(rsa_pem).await {
+				let auth = match config_reload::build_jwt_authorizer(rsa_pem, &config).await {
 					Ok(auth) => {
-						info!("Configured JWT authorizer with RSA public key");
-						Some(Arc::new(auth))
+						info!("Configured JWT authorizer with public key");
+						auth
 					},
 					Err(e) => {
 						error!("Failed to configure JWT authorizer: {}", e);
 						std::process::exit(-1);
 					},
 				};
+				let auth = config_registry.register_jwt_authorizer(auth, rsa_pem);
+				authorizers.push((AuthMethod::Jwt, Arc::new(auth)));
+			}
+		}
+		#[cfg(feature = "jwks")]
+		{
+			if let Some(jwks_config) = config.jwks_config.take() {
+				let auth = match JWTAuthorizer::from_jwks_url(
+					jwks_config.url.clone(),
+					jwks_config.refresh_interval,
+				)
+				.await
+				{
+					Ok(auth) => {
+						info!("Configured JWT authorizer with the JWKS at {}", jwks_config.url);
+						let auth = match config.jwt_profile_claim.clone() {
+							Some(claim) => auth.with_profile_claim(claim),
+							None => auth,
+						};
+						let auth = match config.jwt_scope_claim.clone() {
+							Some(claim) => auth.with_scope_claim(claim),
+							None => auth,
+						};
+						let auth = Arc::new(match config.jwt_store_claim.clone() {
+							Some(claim) => auth.with_store_claim(claim),
+							None => auth,
+						});
+						let jwks_auth = Arc::clone(&auth);
+						certificate_sources.push(Box::new(move || {
+							cert_expiry::key_certificates("JWKS", jwks_auth.key_certificates())
+						}));
+						auth
+					},
+					Err(e) => {
+						error!("Failed to configure JWT authorizer: {}", e);
+						std::process::exit(-1);
+					},
+				};
+				authorizers.push((AuthMethod::Jwt, auth));
+			}
+		}
+		#[cfg(feature = "apikey")]
+		{
+			if let Some(api_key_config) = config.api_key_auth_config.take() {
+				let auth = match ApiKeyAuthorizer::new(api_key_config.keys) {
+					Ok(auth) => {
+						info!("Configured API key authorizer with {} key(s)", auth.key_count());
+						let auth = match api_key_config.header {
+							Some(header) => auth.with_header(header),
+							None => auth,
+						};
+						let auth = api_key_config
+							.key_scopes
+							.into_iter()
+							.fold(auth, |auth, (key, scopes)| auth.with_key_scopes(key, scopes));
+						api_key_config.key_store_ids.into_iter().fold(auth, |auth, (key, store_ids)| {
+							auth.with_key_store_ids(key, store_ids)
+						})
+					},
+					Err(e) => {
+						error!("Failed to configure API key authorizer: {}", e);
+						std::process::exit(-1);
+					},
+				};
+				authorizers.push((AuthMethod::ApiKey, Arc::new(auth)));
+			}
+		}
+		#[cfg(feature = "nostr")]
+		{
+			if let Some(nostr_config) = config.nostr_auth_config.take() {
+				let auth = NostrAuthorizer::new();
+				let auth = match nostr_config.url_prefix {
+					Some(url_prefix) => {
+						info!("Configured NIP-98 Nostr authorizer for URLs under {}", url_prefix);
+						auth.with_url_prefix(url_prefix)
+					},
+					None => {
+						info!("Configured NIP-98 Nostr authorizer");
+						auth
+					},
+				};
+				authorizers.push((AuthMethod::Nostr, Arc::new(auth)));
+			}
+		}
+		#[cfg(feature = "l402")]
+		{
+			if let Some(l402_config) = config.l402_config.take() {
+				let issuer = match l402::LndInvoiceIssuer::new(&l402_config) {
+					Ok(issuer) => issuer,
+					Err(e) => {
+						error!("Failed to configure L402 authorizer: {}", e);
+						std::process::exit(-1);
+					},
+				};
+				let auth =
+					L402Authorizer::new(l402_config.root_key, Arc::new(issuer), l402_config.price_sats);
+				let auth = match l402_config.validity {
+					Some(validity) => auth.with_validity(validity),
+					None => auth,
+				};
+				info!(
+					"Configured L402 authorizer charging {} sats per token, invoiced by LND at {}",
+					l402_config.price_sats, l402_config.lnd_rest_url
+				);
+				let auth = Arc::new(auth);
+				auth_challenger = Some(Arc::clone(&auth) as Arc<dyn AuthChallenger>);
+				authorizers.push((AuthMethod::L402, auth));
+			}
+		}
+		#[cfg(feature = "oidc")]
+		{
+			if let Some(oidc_config) = config.oidc_auth_config.take() {
+				let auth = match OidcAuthorizer::new(
+					&oidc_config.issuer_url,
+					oidc_config.audience,
+					oidc_config.key_refresh_interval,
+				)
+				.await
+				{
+					Ok(auth) => {
+						info!("Configured OIDC authorizer for issuer {}", oidc_config.issuer_url);
+						let auth = match oidc_config.profile_claim {
+							Some(claim) => auth.with_profile_claim(claim),
+							None => auth,
+						};
+						let auth = match oidc_config.scope_claim {
+							Some(claim) => auth.with_scope_claim(claim),
+							None => auth,
+						};
+						let auth = Arc::new(match oidc_config.store_claim {
+							Some(claim) => auth.with_store_claim(claim),
+							None => auth,
+						});
+						let oidc_auth = Arc::clone(&auth);
+						certificate_sources.push(Box::new(move || {
+							cert_expiry::key_certificates("OIDC", oidc_auth.key_certificates())
+						}));
+						auth
+					},
+					Err(e) => {
+						error!("Failed to configure OIDC authorizer: {}", e);
+						std::process::exit(-1);
+					},
+				};
+				authorizers.push((AuthMethod::Oidc, auth));
 			}
 		}
 		#[cfg(feature = "sigs")]
 		{
-			if authorizer.is_none() {
+			let chains_signatures = config
+				.auth_chain
+				.as_ref()
+				.is_some_and(|chain| chain.contains(&AuthMethod::Signature));
+			if authorizers.is_empty() || chains_signatures {
 				info!("Configured signature-validating authorizer");
-				authorizer = Some(Arc::new(SignatureValidatingAuthorizer));
+				authorizers.push((AuthMethod::Signature, Arc::new(SignatureValidatingAuthorizer)));
 			}
 		}
+		let authorizer: Option<Arc<dyn Authorizer>> = match config.auth_chain.as_ref() {
+			Some(chain) => {
+				// Authorizers are tried in the configured order.
+				authorizers.sort_by_key(|(method, _)| chain.iter().position(|m| m == method));
+				info!(
+					"Chaining authorizers in the order {}",
+					chain.iter().map(|method| method.name()).collect::<Vec<_>>().join(", ")
+				);
+				let chained = authorizers.into_iter().map(|(_, auth)| auth).collect();
+				Some(Arc::new(CompositeAuthorizer::new(chained)))
+			},
+			None => authorizers.pop().map(|(_, auth)| auth),
+		};
 
 		#[cfg(noop_authorizer)]
 		let authorizer = if let Some(auth) = authorizer {
@@ -135,68 +481,510 @@ fn main() {
 
 		#[cfg(not(noop_authorizer))]
 		let authorizer = authorizer.unwrap_or_else(||  {
-			error!("No authentication method configured, please configure either `JWTAuthorizer` or `SignatureValidatingAuthorizer`");
+			error!("No authentication method configured, please configure either `JWTAuthorizer`, `ApiKeyAuthorizer`, `NostrAuthorizer`, `L402Authorizer`, `OidcAuthorizer` or `SignatureValidatingAuthorizer`");
 			std::process::exit(-1);
 		});
 
-		let store: Arc<dyn KvStore> = if let Some(crt_pem) = config.tls_config.as_ref() {
-			let postgres_tls_backend = PostgresTlsBackend::new(
-				&config.postgresql_prefix,
-				&config.default_db,
-				&config.vss_db,
-				crt_pem.as_deref(),
-			)
-			.await
-			.unwrap_or_else(|e| {
-				error!("Failed to start postgres TLS backend: {}", e);
+		if let BackendConfig::InMemory = config.storage {
+			warn!("Storing objects in memory, all data will be lost on shutdown.");
+		}
+		let backend = connect_backend(&config.storage).await.unwrap_or_else(|e| {
+			error!("Failed to start {} backend: {}", config.storage.name(), e);
+			std::process::exit(-1);
+		});
+		info!("Connected to {} backend", config.storage.name());
+		#[cfg(feature = "object-store")]
+		let backend = match config.offloading_config.as_ref() {
+			Some(offloading_config) => {
+				let backend = offload_values(backend, offloading_config).unwrap_or_else(|e| {
+					error!("Failed to start offloading values: {}", e);
+					std::process::exit(-1);
+				});
+				info!(
+					"Offloading values of at least {} bytes to {}",
+					offloading_config.min_offloaded_value_size, offloading_config.url
+				);
+				backend
+			},
+			None => backend,
+		};
+		let Backend {
+			kv_store: store,
+			health,
+			metadata,
+			backups,
+			changes,
+			tombstones,
+			history,
+			maintenance,
+			audit,
+		} = backend;
+		// The configuration only enables backup monitoring for backends reporting their backups.
+		let backup_monitor = config.backup_monitor_config.take().zip(backups).map(
+			|(backup_monitor_config, backups)| {
+				info!(
+					"Checking WAL archiving every {}s",
+					backup_monitor_config.check_interval.as_secs()
+				);
+				BackupMonitor::start(backup_monitor_config, backups)
+			},
+		);
+		let configured_certificates =
+			cert_expiry::configured_certificates(&config).unwrap_or_else(|e| {
+				error!("Failed to load certificates to monitor: {}", e);
 				std::process::exit(-1);
 			});
+		if !configured_certificates.is_empty() {
+			certificate_sources.push(cert_expiry::fixed_source(configured_certificates));
+		}
+		let certificate_monitor = (!certificate_sources.is_empty()).then(|| {
+			let certificate_expiry_config = config.certificate_expiry_config;
 			info!(
-				"Connected to PostgreSQL TLS backend with DSN: {}/{}",
-				config.postgresql_prefix, config.vss_db
+				"Checking the expiry of certificates every {}s",
+				certificate_expiry_config.check_interval.as_secs()
 			);
-			Arc::new(postgres_tls_backend)
+			CertificateExpiryMonitor::start(certificate_expiry_config, certificate_sources)
+		});
+		let health: Arc<dyn HealthCheck> = match backup_monitor.as_ref() {
+			Some(backups) if backups.fails_readiness() => {
+				Arc::new(BackupAwareHealthCheck { backend: health, backups: Arc::clone(backups) })
+			},
+			_ => health,
+		};
+		// Re-encryption and account summaries need the backend's records, which metadata kept in
+		// memory lacks.
+		let provides_metadata = metadata.is_some();
+		let MetadataStores {
+			records,
+			devices,
+			share_tokens: share_token_store,
+			store_acl,
+			pending_deletions,
+			admin_operations,
+			usage,
+			pool_stats,
+		} = metadata.unwrap_or_else(|| {
+			warn!(
+				"The {} backend only stores objects, share tokens, collaborator grants, pending deletions, pending admin operations and known devices are kept in memory and lost on shutdown.",
+				config.storage.name()
+			);
+			MetadataStores::new(Arc::new(InMemoryBackend::new()))
+		});
+		let (store, reencryption): (Arc<dyn KvStore>, _) = match config.encryption_keys.take() {
+			Some(encryption_keys) => {
+				let key_provider =
+					connect_key_provider(encryption_keys).await.unwrap_or_else(|e| {
+						error!("Failed to load encryption keys: {}", e);
+						std::process::exit(-1);
+					});
+				info!(
+					"Encrypting values at rest with key version {}",
+					key_provider.current_key_version()
+				);
+				let store = Arc::new(EncryptedKvStore::new(store, key_provider));
+				let reencryption = provides_metadata
+					.then(|| Arc::new(ReencryptionJob::new(records, Arc::clone(&store))));
+				(store, reencryption)
+			},
+			None => (store, None),
+		};
+		let store: Arc<dyn KvStore> = if config.max_global_version_retries == 0 {
+			store
 		} else {
-			let postgres_plaintext_backend = PostgresPlaintextBackend::new(
-				&config.postgresql_prefix,
-				&config.default_db,
-				&config.vss_db,
-			)
-			.await
-			.unwrap_or_else(|e| {
-				error!("Failed to start postgres plaintext backend: {}", e);
-				std::process::exit(-1);
-			});
 			info!(
-				"Connected to PostgreSQL plaintext backend with DSN: {}/{}",
-				config.postgresql_prefix, config.vss_db
+				"Retrying puts conflicting only on the global version up to {} time(s)",
+				config.max_global_version_retries
+			);
+			Arc::new(GlobalVersionRetryingKvStore::new(store, config.max_global_version_retries))
+		};
+		let store: Arc<dyn KvStore> = if config.merge_strategies.is_empty() {
+			store
+		} else {
+			info!("Merging conflicting writes under {} key prefix(es)", config.merge_strategies.len());
+			Arc::new(MergingKvStore::new(store, std::mem::take(&mut config.merge_strategies)))
+		};
+		let security_notifier = match config.security_notification_config.take() {
+			Some(security_notification_config) => {
+				info!("Security notifications enabled");
+				Some(SecurityNotifier::start(
+					security_notification_config,
+					Arc::clone(&store),
+					Arc::clone(&devices),
+				))
+			},
+			None => None,
+		};
+		let store: Arc<dyn KvStore> = match config.analytics_config {
+			Some(analytics_config) => {
+				let analytics = Arc::new(UsageAnalytics::new(analytics_config));
+				let publisher = Arc::clone(&analytics);
+				runtime.spawn(async move {
+					let mut interval = tokio::time::interval(publisher.report_interval());
+					// The first tick completes immediately.
+					interval.tick().await;
+					loop {
+						interval.tick().await;
+						publisher.publish();
+					}
+				});
+				info!("Usage analytics enabled");
+				Arc::new(AnalyticsKvStore::new(store, analytics))
+			},
+			None => store,
+		};
+		// Request counts are only queried through the admin API.
+		let store: Arc<dyn KvStore> = if config.admin_config.is_some() {
+			let counter = RequestCounter::start(Arc::clone(&usage));
+			Arc::new(RequestCountingKvStore::new(store, counter))
+		} else {
+			store
+		};
+		// Subsystems reacting to changes of stored objects register here, observing all writes
+		// that passed through the layers below.
+		let mut observers: Vec<Arc<dyn KvStoreObserver>> = Vec::new();
+		let change_broadcaster = config.change_stream_config.take().map(|change_stream_config| {
+			info!(
+				"Pushing changes to subscribers, at most {} per user",
+				change_stream_config.max_subscriptions_per_user
+			);
+			let broadcaster = Arc::new(ChangeBroadcaster::new(change_stream_config));
+			match changes {
+				Some(changes) => {
+					info!("Following the changes committed by all instances");
+					Arc::clone(&broadcaster).follow(changes);
+				},
+				None => observers.push(broadcaster.clone()),
+			}
+			broadcaster
+		});
+		if let Some(nostr_notification_config) = config.nostr_notification_config.take() {
+			info!(
+				"Sending Nostr notifications via {} relay(s)",
+				nostr_notification_config.relays.len()
 			);
-			Arc::new(postgres_plaintext_backend)
+			let notifier = NostrNotifier::start(nostr_notification_config);
+			observers.push(Arc::new(NostrNotifyingObserver::new(Arc::clone(&store), notifier)));
+		}
+		let store: Arc<dyn KvStore> = if observers.is_empty() {
+			store
+		} else {
+			Arc::new(ObservedKvStore::new(store, observers))
 		};
+		let delayed_deletions =
+			config.delayed_deletion_config.take().map(|delayed_deletion_config| {
+				info!(
+					"Delaying deletions under {} key prefix(es) by {}s",
+					delayed_deletion_config.key_prefixes.len(),
+					delayed_deletion_config.delay.as_secs()
+				);
+				DelayedDeletions::start(
+					delayed_deletion_config,
+					pending_deletions,
+					Arc::clone(&store),
+				)
+			});
+		let store: Arc<dyn KvStore> = match delayed_deletions.as_ref() {
+			Some(deletions) => Arc::new(DelayedDeletionKvStore::new(store, Arc::clone(deletions))),
+			None => store,
+		};
+		// The configuration only enables soft deletion for backends keeping tombstones.
+		let soft_deletion = config.soft_deletion_config.take().zip(tombstones).map(
+			|(soft_deletion_config, tombstones)| {
+				info!(
+					"Keeping deleted objects restorable for {}s",
+					soft_deletion_config.retention.as_secs()
+				);
+				SoftDeletion::start(soft_deletion_config, tombstones)
+			},
+		);
+
+		// Only writes through the API are checked, not those made by the server itself.
+		let api_store: Arc<dyn KvStore> = if config.require_client_encryption {
+			info!("Rejecting values not encrypted client-side");
+			Arc::new(ClientEncryptionEnforcingKvStore::new(Arc::clone(&store)))
+		} else {
+			Arc::clone(&store)
+		};
+
+		let share_tokens = Arc::new(ShareTokens::new(Arc::clone(&store), share_token_store));
+		let collaborators = Arc::new(Collaborators::new(store_acl));
+		// Every policy deciding which operations callers may perform registers here. Callers may
+		// always access their own stores and those shared with them.
+		let mut access_policies: Vec<Arc<dyn AccessPolicy>> = vec![collaborators.clone()];
+		if let Some(access_rule_policy) = config.access_rule_policy.take() {
+			info!("Evaluating operator-defined access rules");
+			access_policies.push(Arc::new(access_rule_policy));
+		}
+		let store_namespaces = config.store_namespace_config.take().map(|store_namespace_config| {
+			info!("Validating store ids against the configured namespace rules");
+			Arc::new(StoreNamespaces::new(store_namespace_config, Arc::clone(&usage)))
+		});
+		if let Some(store_namespaces) = store_namespaces.as_ref() {
+			access_policies.push(store_namespaces.clone());
+		}
+		let access_policy: Arc<dyn AccessPolicy> = Arc::new(AllOfPolicies::new(access_policies));
+		let store_templates = Arc::new(StoreTemplates::new(
+			Arc::clone(&store),
+			std::mem::take(&mut config.store_templates),
+		));
+		let quotas = config.quota_config.take().map(|quota_config| {
+			info!(
+				"Warning users above {}% of their storage quota",
+				(quota_config.soft_limit_ratio * 100.0).round()
+			);
+			if quota_config.enforce {
+				info!("Rejecting writes of users who reached their storage quota");
+			}
+			Arc::new(Quotas::new(config_registry.register_quota(quota_config), Arc::clone(&usage)))
+		});
+		let account_summaries = provides_metadata.then(|| {
+			Arc::new(AccountSummaries::new(Arc::clone(&usage), devices, quotas.clone()))
+		});
 
+		let sessions = config.session_limit_config.take().map(|session_limit_config| {
+			info!(
+				"Limiting users to {} concurrent sessions",
+				session_limit_config.max_sessions_per_user
+			);
+			Arc::new(SessionLimiter::new(session_limit_config))
+		});
+		let rate_limiter = config.rate_limit_config.take().map(|rate_limit_config| {
+			info!(
+				"Rate limiting clients to {} requests per second with bursts of {}",
+				rate_limit_config.requests_per_second, rate_limit_config.burst
+			);
+			Arc::new(RateLimiter::new(config_registry.register_rate_limit(rate_limit_config)))
+		});
+		let load_shedder = config.load_shedding_config.take().map(|load_shedding_config| {
+			info!(
+				"Shedding low-priority endpoints once {}% and all but object reads and writes once \
+				 {}% of requests fail",
+				load_shedding_config.degraded_failure_ratio * 100.0,
+				load_shedding_config.unhealthy_failure_ratio * 100.0
+			);
+			Arc::new(LoadShedder::new(load_shedding_config))
+		});
+		let maintenance_mode = Arc::new(MaintenanceMode::new(config.maintenance_mode));
+
+		let in_flight = Arc::new(InFlightRequests::default());
+		let probe = config.probe_config.take().map(|probe_config| {
+			info!(
+				"Probing the store {} at {} every {}s",
+				probe_config.store_id,
+				probe_config.url,
+				probe_config.interval.as_secs()
+			);
+			SyntheticProbe::start(probe_config).unwrap_or_else(|e| {
+				error!("Failed to set up the synthetic probe: {}", e);
+				std::process::exit(-1);
+			})
+		});
+		// The configuration only enables maintenance for backends needing it.
+		let maintenance = config.maintenance_config.take().zip(maintenance).map(
+			|(maintenance_config, maintenance)| {
+				info!("Running maintenance jobs in the background");
+				Maintenance::start(maintenance_config, maintenance)
+			},
+		);
+		let mutation_audit = config.audit_config.take().map(|audit_config| {
+			info!("Recording mutating operations in the {} audit log", audit_config.sink.name());
+			MutationAudit::start(audit_config, audit).unwrap_or_else(|e| {
+				error!("Failed to set up the audit log: {}", e);
+				std::process::exit(-1);
+			})
+		});
+		// Metrics are only served through the admin API.
+		let metrics = config.admin_config.as_ref().map(|_| {
+			Metrics::start(pool_stats, telemetry_budgets, backup_monitor.clone(), certificate_monitor.clone(), probe.clone(), maintenance.clone()).unwrap_or_else(|e| {
+				error!("Failed to set up metrics: {}", e);
+				std::process::exit(-1);
+			})
+		});
+		let client_compatibility = config.client_compatibility.take().map(Arc::new);
+		// Captured requests are only retrievable through the admin API.
+		let request_capture = config.admin_config.as_ref().map(|admin_config| {
+			Arc::new(RequestCapture::new(
+				admin_config.request_capture_sample_rate,
+				admin_config.request_capture_capacity,
+			))
+		});
+
+		let tls_acceptor = config.tls_config.as_ref().map(|tls_config| {
+			load_tls_acceptor(tls_config).unwrap_or_else(|e| {
+				error!("Failed to set up TLS: {}", e);
+				std::process::exit(-1);
+			})
+		});
 		let rest_svc_listener = TcpListener::bind(&config.bind_address).await.unwrap_or_else(|e| {
 			error!("Failed to bind listening port: {}", e);
 			std::process::exit(-1);
 		});
-		info!("Listening for incoming connections on {}{}", config.bind_address, crate::vss_service::BASE_PATH_PREFIX);
+		info!(
+			"Listening for incoming {} connections on {}{}",
+			if tls_acceptor.is_some() { "TLS" } else { "plaintext" },
+			config.bind_address,
+			crate::vss_service::BASE_PATH_PREFIX
+		);
+
+		#[cfg(feature = "grpc")]
+		if let Some(grpc_bind_address) = config.grpc_bind_address.as_ref() {
+			let incoming = grpc_bind_address
+				.parse()
+				.map_err(|e: std::net::AddrParseError| e.to_string())
+				.and_then(|addr| {
+					tonic::transport::server::TcpIncoming::new(addr, true, None)
+						.map_err(|e| e.to_string())
+				})
+				.unwrap_or_else(|e| {
+					error!("Failed to bind gRPC listening port: {}", e);
+					std::process::exit(-1);
+				});
+			// Each request is served for the peer it came from, see `VssGrpcService`.
+			let vss_service = VssService::new(
+				Arc::clone(&api_store),
+				Arc::clone(&health),
+				Arc::clone(&authorizer),
+				auth_challenger.clone(),
+				Arc::clone(&access_policy),
+				security_notifier.clone(),
+				Arc::clone(&share_tokens),
+				Arc::clone(&collaborators),
+				Arc::clone(&store_templates),
+				delayed_deletions.clone(),
+				soft_deletion.clone(),
+				account_summaries.clone(),
+				// Subscriptions are served over WebSockets only.
+				None,
+				quotas.clone(),
+				sessions.clone(),
+				rate_limiter.clone(),
+				load_shedder.clone(),
+				Arc::clone(&maintenance_mode),
+				Arc::clone(&in_flight),
+				request_capture.clone(),
+				// Clients with known quirks predate the gRPC transport.
+				None,
+				metrics.clone(),
+				mutation_audit.clone(),
+				vss_service_config,
+				std::net::Ipv4Addr::UNSPECIFIED.into(),
+			);
+			let grpc_service = grpc_service::VssGrpcService::new(
+				vss_service,
+				vss_service_config.maximum_request_body_size(),
+			);
+			info!("Listening for gRPC connections on {}", grpc_bind_address);
+			runtime.spawn(async move {
+				let server = tonic::transport::Server::builder().add_service(grpc_service);
+				if let Err(e) = server.serve_with_incoming(incoming).await {
+					error!("gRPC server failed: {}", e);
+				}
+			});
+		}
+
+		let admin_svc = match config.admin_config.as_ref() {
+			Some(admin_config) => {
+				let listener = TcpListener::bind(&admin_config.bind_address).await.unwrap_or_else(|e| {
+					error!("Failed to bind admin listening port: {}", e);
+					std::process::exit(-1);
+				});
+				info!(
+					"Listening for admin connections on {}{}",
+					admin_config.bind_address,
+					admin_service::ADMIN_BASE_PATH_PREFIX
+				);
+				let admin_service_config = AdminServiceConfig {
+					admins: admin_config.admins.clone(),
+					approval_delay: admin_config.approval_delay,
+					maximum_request_body_size: vss_service_config.maximum_request_body_size(),
+					effective_config: Arc::clone(&effective_config),
+				};
+				Some((listener, AdminService::new(
+					Arc::clone(&store),
+					reencryption.clone(),
+					history.clone(),
+					security_notifier.clone(),
+					Arc::clone(&usage),
+					Arc::clone(&admin_operations),
+					store_namespaces.clone(),
+					quotas.clone(),
+					mutation_audit.clone(),
+					// unwrap safety: metrics are set up whenever the admin API is.
+					metrics.clone().unwrap(),
+					Arc::clone(&maintenance_mode),
+					Arc::clone(&in_flight),
+					// unwrap safety: request capture is set up whenever the admin API is.
+					request_capture.clone().unwrap(),
+					admin_service_config,
+				)))
+			},
+			None => None,
+		};
 
 		loop {
 			tokio::select! {
 				res = rest_svc_listener.accept() => {
 					match res {
-						Ok((stream, _)) => {
-							let io_stream = TokioIo::new(stream);
-							let vss_service =
-								VssService::new(Arc::clone(&store), Arc::clone(&authorizer), vss_service_config);
+						Ok((stream, peer_addr)) => {
+							let stream = WriteTimeoutStream::new(stream, config.response_write_timeout);
+							let vss_service = VssService::new(
+								Arc::clone(&api_store),
+								Arc::clone(&health),
+								Arc::clone(&authorizer),
+								auth_challenger.clone(),
+								Arc::clone(&access_policy),
+								security_notifier.clone(),
+								Arc::clone(&share_tokens),
+								Arc::clone(&collaborators),
+								Arc::clone(&store_templates),
+								delayed_deletions.clone(),
+								soft_deletion.clone(),
+								account_summaries.clone(),
+								change_broadcaster.clone(),
+								quotas.clone(),
+								sessions.clone(),
+								rate_limiter.clone(),
+								load_shedder.clone(),
+								Arc::clone(&maintenance_mode),
+								Arc::clone(&in_flight),
+								request_capture.clone(),
+								client_compatibility.clone(),
+								metrics.clone(),
+								mutation_audit.clone(),
+								vss_service_config,
+								peer_addr.ip(),
+							);
+							let tls_acceptor = tls_acceptor.clone();
+							// The handshake runs on the connection's task to not hold up accepting others.
 							runtime.spawn(async move {
-								if let Err(err) = http1::Builder::new().serve_connection(io_stream, vss_service).await {
-									warn!("Failed to serve connection: {}", err);
+								match tls_acceptor {
+									Some(tls_acceptor) => {
+										match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, tls_acceptor.accept(stream)).await {
+											Ok(Ok(stream)) => serve_vss_connection(stream, vss_service).await,
+											Ok(Err(e)) => debug!("TLS handshake with {} failed: {}", peer_addr, e),
+											Err(_) => debug!("TLS handshake with {} timed out", peer_addr),
+										}
+									},
+									None => serve_vss_connection(stream, vss_service).await,
 								}
 							});
 						},
 						Err(e) => warn!("Failed to accept connection: {}", e),
 					}
 				}
+				res = accept_admin_connection(&admin_svc) => {
+					match res {
+						Ok((stream, admin_service)) => {
+							let io_stream = TokioIo::new(stream);
+							runtime.spawn(async move {
+								if let Err(err) = http1::Builder::new().serve_connection(io_stream, admin_service).await {
+									warn!("Failed to serve admin connection: {}", err);
+								}
+							});
+						},
+						Err(e) => warn!("Failed to accept admin connection: {}", e),
+					}
+				}
 				_ = tokio::signal::ctrl_c() => {
 					info!("Received CTRL-C, shutting down..");
 					break;
@@ -205,6 +993,14 @@ fn main() {
 					if let Err(e) = logger.reopen() {
 						error!("Failed to reopen log file on SIGHUP: {e}");
 					}
+					info!("Received SIGHUP, reloading configuration..");
+					if let Err(e) = config_registry.reload().await {
+						error!("Failed to reload configuration on SIGHUP, keeping the current one: {}", e);
+					}
+				}
+				_ = sigusr1_stream.recv() => {
+					info!("Received SIGUSR1, toggling maintenance mode..");
+					maintenance_mode.toggle();
 				}
 				_ = sigterm_stream.recv() => {
 					info!("Received SIGTERM, shutting down..");
@@ -212,7 +1008,407 @@ fn main() {
 				}
 			}
 		}
+		// Write the audit records of the last requests.
+		if let Some(mutation_audit) = mutation_audit {
+			mutation_audit.flush().await;
+		}
+	});
+
+	// Flush the spans not yet exported.
+	if let Some(otel_provider) = otel_provider {
+		if let Err(e) = otel_provider.shutdown() {
+			error!("Failed to shut down OpenTelemetry trace export: {}", e);
+		}
+	}
+}
+
+/// Reads a plaintext configuration value from stdin and prints its encrypted `enc:` form.
+fn encrypt_config_value() {
+	let mut plaintext = String::new();
+	if let Err(e) = std::io::Read::read_to_string(&mut std::io::stdin(), &mut plaintext) {
+		eprintln!("Failed to read the value to encrypt from stdin: {}", e);
+		std::process::exit(-1);
+	}
+	let plaintext = plaintext.trim_end_matches(['\r', '\n']);
+	match util::secrets::encrypt_config_value(plaintext) {
+		Ok(value) => println!("{}", value),
+		Err(e) => {
+			eprintln!("Failed to encrypt configuration value: {}", e);
+			std::process::exit(-1);
+		},
+	}
+}
+
+/// Prints a new random data key, wrapped by the master key in `VSS_ENCRYPTION_MASTER_KEY`.
+fn generate_data_key() {
+	let var_name = util::config::ENCRYPTION_MASTER_KEY_VAR;
+	let master_key = std::env::var(var_name)
+		.map_err(|e| format!("{} must be set: {}", var_name, e))
+		.and_then(|master_key| util::secrets::resolve(master_key, var_name))
+		.and_then(|master_key| LocalMasterKey::from_hex(&master_key));
+	match master_key {
+		Ok(master_key) => println!("{}", master_key.wrap_key(&rand::random())),
+		Err(e) => {
+			eprintln!("Failed to generate data key: {}", e);
+			std::process::exit(-1);
+		},
+	}
+}
+
+/// Prints the effective configuration as JSON, with secrets redacted, exiting with a non-zero
+/// status if it is invalid.
+fn check_config(config_file_path: Option<&str>, offline: bool) {
+	let config = util::config::load_configuration(config_file_path).unwrap_or_else(|e| {
+		eprintln!("Failed to load configuration: {}", e);
+		std::process::exit(-1);
+	});
+	let sentry_config = util::config::load_sentry_configuration().unwrap_or_else(|e| {
+		eprintln!("Failed to load Sentry configuration from environment: {}", e);
+		std::process::exit(-1);
+	});
+	let datadog_config = util::config::load_datadog_configuration().unwrap_or_else(|e| {
+		eprintln!("Failed to load Datadog configuration from environment: {}", e);
+		std::process::exit(-1);
+	});
+	let effective_config =
+		util::config_dump::effective_config(&config, &sentry_config, &datadog_config);
+	// unwrap safety: values built by `json!` always serialize successfully.
+	println!("{}", serde_json::to_string_pretty(&effective_config).unwrap());
+
+	// The checks are reported on stderr, keeping stdout parseable.
+	for line in util::config_dump::startup_banner(&effective_config) {
+		eprintln!("{}", line);
+	}
+	let runtime =
+		tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap_or_else(|e| {
+			eprintln!("Failed to setup tokio runtime: {}", e);
+			std::process::exit(-1);
+		});
+	let mut checks = vec![("authentication", runtime.block_on(check_authentication(&config)))];
+	if !offline {
+		checks.push(("ports", check_ports(&config)));
+		checks.push(("backend", runtime.block_on(check_backend(&config.storage))));
+	}
+	let mut failed = false;
+	for (name, result) in checks {
+		match result {
+			Ok(summary) => eprintln!("ok     {}: {}", name, summary),
+			Err(e) => {
+				eprintln!("FAILED {}: {}", name, e);
+				failed = true;
+			},
+		}
+	}
+	if failed {
+		std::process::exit(-1);
+	}
+}
+
+/// Applies the pending schema migrations of the configured backend, or only lists them in a
+/// `dry_run`.
+fn migrate(config_file_path: Option<&str>, dry_run: bool) {
+	let config = util::config::load_configuration(config_file_path).unwrap_or_else(|e| {
+		eprintln!("Failed to load configuration: {}", e);
+		std::process::exit(-1);
+	});
+	let runtime =
+		tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap_or_else(|e| {
+			eprintln!("Failed to setup tokio runtime: {}", e);
+			std::process::exit(-1);
+		});
+	let pending = runtime.block_on(migrate_backend(&config.storage, dry_run)).unwrap_or_else(|e| {
+		eprintln!("Failed to migrate {} backend: {}", config.storage.name(), e);
+		std::process::exit(-1);
+	});
+	let pending = match pending {
+		Some(pending) if !pending.is_empty() => pending,
+		Some(_) => return println!("The database schema is up to date"),
+		None => return println!("The {} backend has no schema to migrate", config.storage.name()),
+	};
+	for migration in &pending {
+		// Statements span several lines, which are joined to list one migration per line.
+		let statement = migration.statement.split_whitespace().collect::<Vec<_>>().join(" ");
+		println!("{:>4}  {}", migration.version, statement);
+	}
+	println!(
+		"{} {} migration(s), upgrading the schema from version {} to {}",
+		if dry_run { "Would apply" } else { "Applied" },
+		pending.len(),
+		pending[0].version,
+		pending[pending.len() - 1].version + 1
+	);
+}
+
+/// Writes an export of a store, as served by `/vss/exportStore`, to `output` or stdout.
+fn export_store(
+	config_file_path: Option<&str>, user_token: String, store_id: String, format: ExportFormat,
+	output: Option<PathBuf>,
+) {
+	let mut config = util::config::load_configuration(config_file_path).unwrap_or_else(|e| {
+		eprintln!("Failed to load configuration: {}", e);
+		std::process::exit(-1);
+	});
+	let runtime =
+		tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap_or_else(|e| {
+			eprintln!("Failed to setup tokio runtime: {}", e);
+			std::process::exit(-1);
+		});
+
+	let written = runtime.block_on(async {
+		let Backend { kv_store: store, .. } =
+			connect_backend(&config.storage).await.unwrap_or_else(|e| {
+				eprintln!("Failed to start {} backend: {}", config.storage.name(), e);
+				std::process::exit(-1);
+			});
+		// Values are exported as clients read them, not as they are encrypted at rest.
+		let store: Arc<dyn KvStore> = match config.encryption_keys.take() {
+			Some(encryption_keys) => {
+				let key_provider =
+					connect_key_provider(encryption_keys).await.unwrap_or_else(|e| {
+						eprintln!("Failed to load encryption keys: {}", e);
+						std::process::exit(-1);
+					});
+				Arc::new(EncryptedKvStore::new(store, key_provider))
+			},
+			None => store,
+		};
+		let request = ExportStoreRequest { store_id: store_id.clone() };
+		let export =
+			handle_export_store_request(store, user_token, request).await.unwrap_or_else(|e| {
+				eprintln!("Failed to export store {}: {}", store_id, e);
+				std::process::exit(-1);
+			});
+		let mut writer: Box<dyn Write> = match &output {
+			Some(path) => Box::new(std::fs::File::create(path).unwrap_or_else(|e| {
+				eprintln!("Failed to create {}: {}", path.display(), e);
+				std::process::exit(-1);
+			})),
+			None => Box::new(std::io::stdout().lock()),
+		};
+		// The export is written as it is read, page by page.
+		let mut body = export.into_body(format.into());
+		let mut written = 0;
+		while let Some(frame) = body.frame().await {
+			let chunk = match frame {
+				Ok(frame) => frame.into_data().unwrap_or_default(),
+				Err(e) => {
+					eprintln!("Failed to export store {}: {}", store_id, e);
+					std::process::exit(-1);
+				},
+			};
+			if let Err(e) = writer.write_all(&chunk) {
+				eprintln!("Failed to write the export: {}", e);
+				std::process::exit(-1);
+			}
+			written += chunk.len();
+		}
+		if let Err(e) = writer.flush() {
+			eprintln!("Failed to write the export: {}", e);
+			std::process::exit(-1);
+		}
+		written
 	});
+
+	if let Some(path) = output {
+		eprintln!("Exported {} bytes to {}", written, path.display());
+	}
+}
+
+/// Rehearses a database failover against the configured PostgreSQL database, exiting with a non-zero
+/// status if the backend did not recover in time.
+fn run_failover_drill(config_file_path: Option<&str>, settings: DrillSettings) {
+	let config = util::config::load_configuration(config_file_path).unwrap_or_else(|e| {
+		eprintln!("Failed to load configuration: {}", e);
+		std::process::exit(-1);
+	});
+	let postgres_config = match config.storage {
+		BackendConfig::Postgres(postgres_config) => postgres_config,
+		_ => {
+			eprintln!("The failover drill requires the PostgreSQL storage backend");
+			std::process::exit(-1);
+		},
+	};
+	let runtime =
+		tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap_or_else(|e| {
+			eprintln!("Failed to setup tokio runtime: {}", e);
+			std::process::exit(-1);
+		});
+
+	println!(
+		"Rehearsing {} failover(s) every {}s under the load of {} worker(s)",
+		settings.rounds,
+		settings.interval.as_secs(),
+		settings.workers
+	);
+	let report = runtime.block_on(async {
+		let exit_on_error = |e: std::io::Error| -> ! {
+			eprintln!("Failed to connect to PostgreSQL: {}", e);
+			std::process::exit(-1);
+		};
+		if let Some(crt_pem) = postgres_config.tls_config.as_ref() {
+			let backend = Arc::new(
+				PostgresTlsBackend::new_with_pool_config(
+					&postgres_config.prefix,
+					&postgres_config.default_db,
+					&postgres_config.vss_db,
+					crt_pem.as_deref(),
+					postgres_config.pool_config,
+				)
+				.await
+				.unwrap_or_else(|e| exit_on_error(e))
+				.with_transient_retries(postgres_config.transient_retries),
+			);
+			backend.prepare_schema(postgres_config.migrate_on_startup).await.unwrap_or_else(|e| {
+				eprintln!("Failed to prepare the database schema: {}", e);
+				std::process::exit(-1);
+			});
+			let terminating = Arc::clone(&backend);
+			failover_drill::run(backend, || terminating.terminate_connections(), &settings).await
+		} else {
+			let backend = Arc::new(
+				PostgresPlaintextBackend::new_with_pool_config(
+					&postgres_config.prefix,
+					&postgres_config.default_db,
+					&postgres_config.vss_db,
+					postgres_config.pool_config,
+				)
+				.await
+				.unwrap_or_else(|e| exit_on_error(e))
+				.with_transient_retries(postgres_config.transient_retries),
+			);
+			backend.prepare_schema(postgres_config.migrate_on_startup).await.unwrap_or_else(|e| {
+				eprintln!("Failed to prepare the database schema: {}", e);
+				std::process::exit(-1);
+			});
+			let terminating = Arc::clone(&backend);
+			failover_drill::run(backend, || terminating.terminate_connections(), &settings).await
+		}
+	});
+
+	println!("Requests: {} ({} failed)", report.requests, report.failed_requests);
+	for (round, (recovery_time, terminated)) in
+		report.recovery_times.iter().zip(&report.terminated_connections).enumerate()
+	{
+		match recovery_time {
+			Some(recovery_time) => println!(
+				"Failover {}: terminated {} connection(s), recovered after {}ms",
+				round + 1,
+				terminated,
+				recovery_time.as_millis()
+			),
+			None => println!(
+				"Failover {}: terminated {} connection(s), did not recover",
+				round + 1,
+				terminated
+			),
+		}
+	}
+	if !report.met_slo(settings.slo) {
+		eprintln!("Failover drill failed: recovery exceeded {}ms", settings.slo.as_millis());
+		std::process::exit(1);
+	}
+	println!("Failover drill passed");
+}
+
+/// Populates the configured backend with synthetic LDK data, see [`fixtures`].
+fn generate_fixtures(config_file_path: Option<&str>, settings: FixtureSettings) {
+	let mut config = util::config::load_configuration(config_file_path).unwrap_or_else(|e| {
+		eprintln!("Failed to load configuration: {}", e);
+		std::process::exit(-1);
+	});
+	let runtime =
+		tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap_or_else(|e| {
+			eprintln!("Failed to setup tokio runtime: {}", e);
+			std::process::exit(-1);
+		});
+
+	println!(
+		"Generating {} store(s) for {} user(s) with seed {}",
+		settings.users * settings.stores_per_user,
+		settings.users,
+		settings.seed
+	);
+	let report = runtime.block_on(async {
+		let Backend { kv_store: store, .. } =
+			connect_backend(&config.storage).await.unwrap_or_else(|e| {
+				eprintln!("Failed to start {} backend: {}", config.storage.name(), e);
+				std::process::exit(-1);
+			});
+		// Values are encrypted like those written through the API, so the server can read them.
+		let store: Arc<dyn KvStore> = match config.encryption_keys.take() {
+			Some(encryption_keys) => {
+				let key_provider =
+					connect_key_provider(encryption_keys).await.unwrap_or_else(|e| {
+						eprintln!("Failed to load encryption keys: {}", e);
+						std::process::exit(-1);
+					});
+				Arc::new(EncryptedKvStore::new(store, key_provider))
+			},
+			None => store,
+		};
+		fixtures::generate(store, Arc::new(settings)).await.unwrap_or_else(|e| {
+			eprintln!("Failed to generate fixtures: {}", e);
+			std::process::exit(-1);
+		})
+	});
+
+	println!(
+		"Wrote {} object(s) totalling {} bytes to {} store(s) in {}ms",
+		report.objects,
+		report.bytes,
+		report.stores,
+		report.elapsed.as_millis()
+	);
+}
+
+/// Accepts the next connection on the admin listener, or never resolves if the admin API is
+/// disabled.
+/// Serves the VSS API on an accepted connection, speaking HTTP/1.1 or, for clients multiplexing
+/// requests over one connection, HTTP/2 as told by the connection preface. HTTP/1.1 connections
+/// may be upgraded to WebSockets by subscriptions to changes.
+/// Builds the provider of the keys values are encrypted at rest with, unwrapping all data keys up
+/// front so that an unreachable or wrong master key fails startup rather than requests.
+async fn connect_key_provider(keys: EncryptionKeys) -> Result<Arc<dyn KeyProvider>, String> {
+	let (wrapped_keys, master_key) = match keys {
+		EncryptionKeys::Static(keys) => return Ok(Arc::new(keys)),
+		EncryptionKeys::Envelope { wrapped_keys, master_key } => (wrapped_keys, master_key),
+	};
+	let key_versions = wrapped_keys.key_versions();
+	let master_key: Arc<dyn MasterKey> = match master_key {
+		MasterKeyConfig::Local(master_key) => Arc::new(master_key),
+		MasterKeyConfig::VaultTransit(config) => Arc::new(VaultTransitMasterKey::new(&config)?),
+		MasterKeyConfig::AwsKms(config) => Arc::new(AwsKmsMasterKey::new(&config)?),
+		MasterKeyConfig::GcpKms(config) => Arc::new(GcpKmsMasterKey::new(&config)?),
+	};
+	let key_provider = EnvelopeKeyProvider::new(wrapped_keys, master_key);
+	for version in key_versions {
+		key_provider.get_key(version).await.map_err(|e| e.to_string())?;
+	}
+	Ok(Arc::new(key_provider))
+}
+
+async fn serve_vss_connection<S>(stream: S, vss_service: VssService)
+where
+	S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+	let builder = auto::Builder::new(TokioExecutor::new());
+	if let Err(err) =
+		builder.serve_connection_with_upgrades(TokioIo::new(stream), vss_service).await
+	{
+		warn!("Failed to serve connection: {}", err);
+	}
+}
+
+async fn accept_admin_connection(
+	admin_svc: &Option<(TcpListener, AdminService)>,
+) -> std::io::Result<(TcpStream, AdminService)> {
+	match admin_svc {
+		Some((listener, admin_service)) => {
+			let (stream, _) = listener.accept().await?;
+			Ok((stream, admin_service.clone()))
+		},
+		None => std::future::pending().await,
+	}
 }
 
 /// Initializes Sentry error tracking if configured.
@@ -220,7 +1416,12 @@ fn main() {
 /// Sentry must be initialized before the tokio runtime starts to ensure proper
 /// Hub inheritance for spawned threads. Returns a guard that must be kept alive
 /// for the duration of the program to ensure events are flushed on shutdown.
-fn initialize_sentry(sentry_config: &util::config::SentryConfig) -> Option<sentry::ClientInitGuard> {
+///
+/// Events beyond the configured rate are dropped, and requests to Sentry time out, so that an
+/// unreachable Sentry only ever costs dropped events.
+fn initialize_sentry(
+	sentry_config: &util::config::SentryConfig, telemetry_budgets: &mut Vec<Arc<ExportBudget>>,
+) -> Option<sentry::ClientInitGuard> {
 	let dsn = match sentry_config.get_dsn() {
 		Some(dsn) if !dsn.is_empty() => dsn,
 		_ => return None,
@@ -229,12 +1430,33 @@ fn initialize_sentry(sentry_config: &util::config::SentryConfig) -> Option<sentr
 	let environment = sentry_config.get_environment();
 	let sample_rate = sentry_config.get_sample_rate();
 
+	let client = match reqwest::Client::builder()
+		.connect_timeout(SENTRY_CONNECT_TIMEOUT)
+		.timeout(SENTRY_REQUEST_TIMEOUT)
+		.build()
+	{
+		Ok(client) => client,
+		Err(e) => {
+			eprintln!("Failed to initialize Sentry: {}", e);
+			return None;
+		},
+	};
+	let budget = ExportBudget::new("sentry", sentry_config.get_max_events_per_second());
+	telemetry_budgets.push(Arc::clone(&budget));
+
 	let guard = sentry::init((
 		dsn,
 		sentry::ClientOptions {
 			release: sentry::release_name!(),
 			environment: environment.map(std::borrow::Cow::Owned),
 			sample_rate,
+			before_send: Some(Arc::new(move |event| budget.admit().then_some(event))),
+			transport: Some(Arc::new(move |options: &sentry::ClientOptions| {
+				Arc::new(sentry::transports::ReqwestHttpTransport::with_client(
+					options,
+					client.clone(),
+				)) as Arc<dyn sentry::Transport>
+			})),
 			..Default::default()
 		},
 	));
@@ -250,18 +1472,53 @@ fn initialize_sentry(sentry_config: &util::config::SentryConfig) -> Option<sentr
 	Some(guard)
 }
 
-/// Initializes Datadog APM tracing if configured.
+/// Initializes the tracing subscriber, exporting spans to Datadog and over OTLP if configured, and
+/// logging events through `json_event_layer` in JSON log mode.
 ///
-/// This sets up the tracing subscriber with Datadog's tracing layer to send
-/// traces to the Datadog Agent. The layer is responsible for collecting spans
-/// and sending them to the Datadog Agent.
-fn initialize_datadog(config: &util::config::DatadogConfig) {
+/// Each exporter is only handed spans within its budget, which is added to `telemetry_budgets`.
+/// Returns the OpenTelemetry tracer provider if enabled, which must be shut down on exit to flush
+/// the spans not yet exported.
+fn initialize_tracing(
+	datadog_config: &util::config::DatadogConfig, otel_config: Option<&util::config::OtelConfig>,
+	json_event_layer: Option<JsonEventLayer>, telemetry_budgets: &mut Vec<Arc<ExportBudget>>,
+) -> Option<SdkTracerProvider> {
+	let datadog_layer = datadog_layer(datadog_config).map(|layer| {
+		let budget = ExportBudget::new("datadog", datadog_config.get_max_spans_per_second());
+		// The exporter buffers spans without bound while the agent is unreachable.
+		budget.probe_reachability(format!(
+			"{}:{}",
+			datadog_config.get_agent_host(),
+			datadog_config.get_agent_port()
+		));
+		telemetry_budgets.push(Arc::clone(&budget));
+		layer.with_filter(BudgetFilter(budget))
+	});
+	let otel_provider = otel_config.and_then(otel_tracer_provider);
+	let otel_layer = otel_provider.as_ref().zip(otel_config).map(|(provider, config)| {
+		let budget = ExportBudget::new("otel", config.max_spans_per_second);
+		telemetry_budgets.push(Arc::clone(&budget));
+		tracing_opentelemetry::layer()
+			.with_tracer(provider.tracer("vss-server"))
+			.with_filter(BudgetFilter(budget))
+	});
+
+	let subscriber =
+		tracing_subscriber::registry().with(datadog_layer).with(otel_layer).with(json_event_layer);
+	if let Err(e) = tracing::subscriber::set_global_default(subscriber) {
+		eprintln!("Failed to initialize tracing subscriber: {}", e);
+	}
+	otel_provider
+}
+
+/// Builds the Datadog APM tracing layer if configured.
+///
+/// The layer is responsible for collecting spans and sending them to the Datadog Agent.
+fn datadog_layer(
+	config: &util::config::DatadogConfig,
+) -> Option<tracing_datadog::DatadogTraceLayer<tracing_subscriber::Registry>> {
 	if !config.is_enabled() {
 		println!("Datadog tracing is disabled");
-		if let Err(e) = tracing::subscriber::set_global_default(tracing_subscriber::registry()) {
-			eprintln!("Failed to initialize tracing subscriber: {}", e);
-		}
-		return;
+		return None;
 	}
 
 	let service = config.get_service();
@@ -288,18 +1545,10 @@ fn initialize_datadog(config: &util::config::DatadogConfig) {
 		Ok(layer) => layer,
 		Err(e) => {
 			eprintln!("Failed to initialize Datadog tracing: {}", e);
-			if let Err(e) = tracing::subscriber::set_global_default(tracing_subscriber::registry()) {
-				eprintln!("Failed to initialize tracing subscriber: {}", e);
-			}
-			return;
+			return None;
 		},
 	};
 
-	// Initialize the tracing subscriber with the Datadog layer
-	if let Err(e) = tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer)) {
-		eprintln!("Failed to initialize tracing subscriber with Datadog layer: {}", e);
-	}
-
 	println!(
 		"Datadog APM tracing initialized (service: {}, agent: {}, env: {}, version: {})",
 		service,
@@ -307,4 +1556,41 @@ fn initialize_datadog(config: &util::config::DatadogConfig) {
 		env.unwrap_or_else(|| "not set".to_string()),
 		version.unwrap_or_else(|| "not set".to_string())
 	);
+	Some(layer)
+}
+
+/// Sets up batched export of spans over OTLP/HTTP.
+///
+/// Spans of requests carrying a sampled trace context are always exported, so that traces started
+/// by clients stay complete; other traces are sampled at the configured ratio.
+fn otel_tracer_provider(config: &util::config::OtelConfig) -> Option<SdkTracerProvider> {
+	let mut builder = opentelemetry_otlp::SpanExporter::builder()
+		.with_http()
+		.with_headers(config.headers.clone());
+	if let Some(endpoint) = config.endpoint.as_ref() {
+		builder = builder.with_endpoint(endpoint);
+	}
+	let exporter = match builder.build() {
+		Ok(exporter) => exporter,
+		Err(e) => {
+			eprintln!("Failed to initialize OpenTelemetry trace export: {}", e);
+			return None;
+		},
+	};
+
+	let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sample_ratio)));
+	let resource = Resource::builder().with_service_name(config.service_name.clone()).build();
+	let provider = SdkTracerProvider::builder()
+		.with_batch_exporter(exporter)
+		.with_sampler(sampler)
+		.with_resource(resource)
+		.build();
+
+	println!(
+		"OpenTelemetry trace export initialized (service: {}, endpoint: {}, sample_ratio: {})",
+		config.service_name,
+		config.endpoint.as_deref().unwrap_or("default"),
+		config.sample_ratio
+	);
+	Some(provider)
 }
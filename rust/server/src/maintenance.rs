@@ -0,0 +1,218 @@
+//! Periodic maintenance of the data the backend keeps about objects.
+//!
+//! Some data outlives its use unless it is cleaned up: share tokens are only deleted on expiry
+//! when their owner creates another one, and pending deletions of objects written or deleted in
+//! the meantime can never be executed. The storage usage recorded for each user is derived from
+//! their objects and drifts if objects are written around the server, e.g. by hand or by a
+//! restored backup. Each job runs on its own interval, and its runs, failures and durations are
+//! exposed as metrics.
+//!
+//! Tables with many dead rows are reported in the log as candidates for `VACUUM`, which the server
+//! does not run itself, as it may contend with autovacuum and the operator's own schedule.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::time::MissedTickBehavior;
+
+use api::error::VssError;
+use impls::maintenance_store::MaintenanceStore;
+
+use log::{info, warn};
+
+use crate::admin_service::unix_time_secs;
+
+/// How often expired share tokens are reaped, unless configured otherwise.
+pub(crate) const DEFAULT_SHARE_TOKEN_REAPING_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How often the storage usage of users is recomputed, unless configured otherwise.
+pub(crate) const DEFAULT_USAGE_RECOMPUTATION_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How often orphaned records are deleted, unless configured otherwise.
+pub(crate) const DEFAULT_ORPHAN_CLEANUP_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+/// How often tables are checked for dead rows, unless configured otherwise.
+pub(crate) const DEFAULT_VACUUM_HINT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The number of records deleted per query.
+const DELETION_BATCH_SIZE: i64 = 1000;
+/// The dead rows a table needs to be reported as a candidate for `VACUUM`, both in number and as
+/// a share of its rows.
+const VACUUM_HINT_MIN_DEAD_TUPLES: u64 = 10_000;
+const VACUUM_HINT_MIN_DEAD_RATIO: f64 = 0.2;
+
+/// A periodic maintenance job.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MaintenanceJob {
+	/// Deletes expired share tokens.
+	ShareTokenReaping,
+	/// Corrects the storage usage recorded for users from their objects.
+	UsageRecomputation,
+	/// Deletes records left behind by objects which no longer exist.
+	OrphanCleanup,
+	/// Logs the tables which should be vacuumed.
+	VacuumHints,
+}
+
+impl MaintenanceJob {
+	pub(crate) const ALL: [MaintenanceJob; 4] = [
+		MaintenanceJob::ShareTokenReaping,
+		MaintenanceJob::UsageRecomputation,
+		MaintenanceJob::OrphanCleanup,
+		MaintenanceJob::VacuumHints,
+	];
+
+	pub(crate) fn name(&self) -> &'static str {
+		match self {
+			MaintenanceJob::ShareTokenReaping => "share_token_reaping",
+			MaintenanceJob::UsageRecomputation => "usage_recomputation",
+			MaintenanceJob::OrphanCleanup => "orphan_cleanup",
+			MaintenanceJob::VacuumHints => "vacuum_hints",
+		}
+	}
+}
+
+pub(crate) struct MaintenanceConfig {
+	/// How often expired share tokens are reaped.
+	pub(crate) share_token_reaping_interval: Duration,
+	/// How often the storage usage of users is recomputed.
+	pub(crate) usage_recomputation_interval: Duration,
+	/// How often orphaned records are deleted.
+	pub(crate) orphan_cleanup_interval: Duration,
+	/// How often tables are checked for dead rows.
+	pub(crate) vacuum_hint_interval: Duration,
+}
+
+impl MaintenanceConfig {
+	fn interval(&self, job: MaintenanceJob) -> Duration {
+		match job {
+			MaintenanceJob::ShareTokenReaping => self.share_token_reaping_interval,
+			MaintenanceJob::UsageRecomputation => self.usage_recomputation_interval,
+			MaintenanceJob::OrphanCleanup => self.orphan_cleanup_interval,
+			MaintenanceJob::VacuumHints => self.vacuum_hint_interval,
+		}
+	}
+}
+
+/// The runs of a job since startup.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct JobStats {
+	/// The number of runs, including failed ones.
+	pub(crate) runs: u64,
+	/// The number of failed runs.
+	pub(crate) failures: u64,
+	/// The time the last run started at, in seconds since the Unix epoch, if the job ran yet.
+	pub(crate) last_run_at: Option<u64>,
+	/// The time taken by the last run.
+	pub(crate) last_duration: Duration,
+}
+
+/// Runs the maintenance jobs, each on its own interval.
+pub(crate) struct Maintenance {
+	store: Arc<dyn MaintenanceStore>,
+	/// The stats of each job, in the order of [`MaintenanceJob::ALL`].
+	stats: Mutex<[JobStats; MaintenanceJob::ALL.len()]>,
+}
+
+impl Maintenance {
+	/// Spawns a task per job on the current runtime.
+	pub(crate) fn start(config: MaintenanceConfig, store: Arc<dyn MaintenanceStore>) -> Arc<Self> {
+		let maintenance = Arc::new(Self { store, stats: Mutex::new(Default::default()) });
+		for job in MaintenanceJob::ALL {
+			let runner = Arc::clone(&maintenance);
+			let period = config.interval(job);
+			tokio::spawn(async move {
+				let mut interval = tokio::time::interval(period);
+				// Runs outlasting the interval are not followed by a burst of catch-up runs.
+				interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+				loop {
+					interval.tick().await;
+					runner.run(job).await;
+				}
+			});
+		}
+		maintenance
+	}
+
+	/// Returns the stats of each job since startup.
+	pub(crate) fn job_stats(&self) -> Vec<(MaintenanceJob, JobStats)> {
+		let stats = self.stats.lock().unwrap();
+		MaintenanceJob::ALL.into_iter().zip(stats.iter().copied()).collect()
+	}
+
+	async fn run(&self, job: MaintenanceJob) {
+		let run_at = unix_time_secs();
+		let started_at = Instant::now();
+		let result = match job {
+			MaintenanceJob::ShareTokenReaping => self.reap_expired_share_tokens(run_at).await,
+			MaintenanceJob::UsageRecomputation => self.recompute_usage().await,
+			MaintenanceJob::OrphanCleanup => self.delete_orphans().await,
+			MaintenanceJob::VacuumHints => self.log_vacuum_hints().await,
+		};
+		let duration = started_at.elapsed();
+		if let Err(e) = &result {
+			warn!("Maintenance job {} failed: {}", job.name(), e);
+		}
+
+		let mut stats = self.stats.lock().unwrap();
+		let stats = &mut stats[job as usize];
+		stats.runs += 1;
+		stats.failures += result.is_err() as u64;
+		stats.last_run_at = Some(run_at);
+		stats.last_duration = duration;
+	}
+
+	async fn reap_expired_share_tokens(&self, now: u64) -> Result<(), VssError> {
+		let mut reaped = 0;
+		loop {
+			let count = self.store.reap_expired_share_tokens(now, DELETION_BATCH_SIZE).await?;
+			reaped += count;
+			if (count as i64) < DELETION_BATCH_SIZE {
+				break;
+			}
+		}
+		if reaped > 0 {
+			info!("Reaped {} expired share token(s)", reaped);
+		}
+		Ok(())
+	}
+
+	async fn recompute_usage(&self) -> Result<(), VssError> {
+		let corrected = self.store.recompute_usage().await?;
+		if corrected > 0 {
+			warn!("Corrected the drifted storage usage of {} user(s)", corrected);
+		}
+		Ok(())
+	}
+
+	async fn delete_orphans(&self) -> Result<(), VssError> {
+		let mut deleted = 0;
+		loop {
+			let count = self.store.delete_orphans(DELETION_BATCH_SIZE).await?;
+			deleted += count;
+			if (count as i64) < DELETION_BATCH_SIZE {
+				break;
+			}
+		}
+		if deleted > 0 {
+			info!("Deleted {} orphaned record(s)", deleted);
+		}
+		Ok(())
+	}
+
+	async fn log_vacuum_hints(&self) -> Result<(), VssError> {
+		let hints = self
+			.store
+			.vacuum_hints(VACUUM_HINT_MIN_DEAD_TUPLES, VACUUM_HINT_MIN_DEAD_RATIO)
+			.await?;
+		for hint in hints {
+			let last_vacuumed = match hint.last_vacuumed_at {
+				Some(at) => format!("{}s ago", unix_time_secs().saturating_sub(at)),
+				None => "never".to_string(),
+			};
+			warn!(
+				"Table {} has {} dead row(s) besides {} live one(s) and was last vacuumed {}, \
+				 consider running VACUUM on it",
+				hint.table, hint.dead_tuples, hint.live_tuples, last_vacuumed
+			);
+		}
+		Ok(())
+	}
+}
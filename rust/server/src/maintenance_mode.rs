@@ -0,0 +1,111 @@
+//! A maintenance mode in which writes are rejected while reads are still served.
+//!
+//! Migrating the database or failing it over may fail writes halfway or lose those committed
+//! just before, leaving wallets unsure whether their latest state was persisted. In maintenance
+//! mode, requests changing data are instead rejected up front with `503 Service Unavailable` and
+//! `SERVICE_UNAVAILABLE_EXCEPTION`, telling clients when to retry, so that they keep their state
+//! until the maintenance is over. Reads keep being served throughout.
+//!
+//! The server starts in maintenance mode if configured to, and enters or leaves it through the
+//! admin API or when sent `SIGUSR1`. Like shed requests, rejected requests never reach the
+//! backend, so they do not count towards its health.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use api::error::VssError;
+
+use log::{info, warn};
+
+use crate::admin_service::unix_time_secs;
+
+/// The time clients are told to wait before retrying a write rejected in maintenance mode.
+pub(crate) const MAINTENANCE_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// The message of writes rejected in maintenance mode.
+pub(crate) const MAINTENANCE_ERROR: &str = "The server is in maintenance, writes are rejected.";
+
+/// The state of maintenance mode, as reported through the admin API.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct MaintenanceStatus {
+	pub(crate) enabled: bool,
+	/// The UNIX time at which maintenance mode was entered, if it is enabled.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) since: Option<u64>,
+}
+
+/// Tracks whether the server is in maintenance mode.
+pub(crate) struct MaintenanceMode {
+	/// The UNIX time at which maintenance mode was entered, if it is enabled.
+	since: Mutex<Option<u64>>,
+}
+
+impl MaintenanceMode {
+	pub(crate) fn new(enabled: bool) -> Self {
+		if enabled {
+			warn!("Starting in maintenance mode, writes are rejected");
+		}
+		Self { since: Mutex::new(enabled.then(unix_time_secs)) }
+	}
+
+	pub(crate) fn status(&self) -> MaintenanceStatus {
+		let since = *self.since.lock().unwrap();
+		MaintenanceStatus { enabled: since.is_some(), since }
+	}
+
+	/// Enters or leaves maintenance mode, returning whether it changed.
+	pub(crate) fn set(&self, enabled: bool) -> bool {
+		let mut since = self.since.lock().unwrap();
+		if since.is_some() == enabled {
+			return false;
+		}
+		switch(&mut since, enabled);
+		true
+	}
+
+	/// Enters maintenance mode if it is disabled and leaves it otherwise, returning whether it is
+	/// now enabled.
+	pub(crate) fn toggle(&self) -> bool {
+		let mut since = self.since.lock().unwrap();
+		let enabled = since.is_none();
+		switch(&mut since, enabled);
+		enabled
+	}
+
+	/// Checks whether a request for `operation_name` is served, failing it if it writes while in
+	/// maintenance mode.
+	pub(crate) fn check(&self, operation_name: &str) -> Result<(), VssError> {
+		if self.since.lock().unwrap().is_some() && !is_read(operation_name) {
+			return Err(VssError::ServiceUnavailableError(MAINTENANCE_ERROR.to_string()));
+		}
+		Ok(())
+	}
+}
+
+fn switch(since: &mut Option<u64>, enabled: bool) {
+	*since = enabled.then(unix_time_secs);
+	match enabled {
+		true => warn!("Entered maintenance mode, writes are rejected"),
+		false => info!("Left maintenance mode, serving writes again"),
+	}
+}
+
+/// Whether the operation only reads data, and so is served in maintenance mode. Operations not
+/// known to be reads are rejected, so that new endpoints are safe by default.
+fn is_read(operation_name: &str) -> bool {
+	matches!(
+		operation_name,
+		"getObject"
+			| "getObjectsAtomic"
+			| "getObjectVersion"
+			| "listKeyVersions"
+			| "listObjects"
+			| "exportStore"
+			| "subscribe"
+			| "listStoreAccess"
+			| "listPendingDeletions"
+			| "getAccountSummary"
+	)
+}
@@ -0,0 +1,614 @@
+//! Prometheus metrics served by the admin API at `/admin/metrics`.
+//!
+//! Besides the state of the database connection pool, the metrics cover the tokio runtime: its
+//! task count, queue depth and worker utilization, as well as the scheduler latency, i.e. how long
+//! a freshly spawned task waits before it is first polled. Together they tell apart slow requests
+//! caused by the database from those caused by an overloaded runtime.
+//!
+//! Requests to the [`KvStore`] API are counted by operation and status code, along with their
+//! latencies and the sizes of their bodies. Spans and events dropped by telemetry exporters are
+//! counted by exporter. If backups are monitored, their last checked state is exposed as well, as
+//! is the expiry of the certificates the server depends on, the outcome of the synthetic probe and
+//! the runs of the maintenance jobs.
+//!
+//! [`KvStore`]: api::kv_store::KvStore
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use prometheus::{
+	exponential_buckets, Counter, Encoder, GaugeVec, Histogram, HistogramOpts, HistogramVec,
+	IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder, DEFAULT_BUCKETS,
+};
+use tokio::runtime::Handle;
+
+use impls::pool_stats::PoolStatsProvider;
+
+use crate::admin_service::unix_time_secs;
+use crate::backup_monitor::BackupMonitor;
+use crate::cert_expiry::CertificateExpiryMonitor;
+use crate::maintenance::Maintenance;
+use crate::synthetic_probe::SyntheticProbe;
+use crate::telemetry::ExportBudget;
+
+use log::warn;
+
+/// How often the scheduler latency is sampled.
+const SCHEDULER_PROBE_INTERVAL: Duration = Duration::from_secs(1);
+/// Doubling from 50 microseconds up to about 1.6 seconds.
+const SCHEDULER_LATENCY_BUCKETS: [f64; 16] = [
+	0.00005, 0.0001, 0.0002, 0.0004, 0.0008, 0.0016, 0.0032, 0.0064, 0.0128, 0.0256, 0.0512,
+	0.1024, 0.2048, 0.4096, 0.8192, 1.6384,
+];
+/// Quadrupling from 64 bytes up to 16 MiB.
+const BODY_SIZE_BUCKET_START: f64 = 64.0;
+const BODY_SIZE_BUCKET_COUNT: usize = 10;
+
+struct PoolMetrics {
+	connections: IntGauge,
+	checked_out: IntGauge,
+	idle: IntGauge,
+	waiting: IntGauge,
+	acquisitions: IntCounter,
+	wait_seconds: Counter,
+	reconnects: IntCounter,
+}
+
+struct RuntimeMetrics {
+	workers: IntGauge,
+	alive_tasks: IntGauge,
+	global_queue_depth: IntGauge,
+	worker_busy_seconds: Counter,
+	worker_parks: IntCounter,
+	scheduler_latency: Histogram,
+}
+
+struct RequestMetrics {
+	requests: IntCounterVec,
+	duration: HistogramVec,
+	request_body_size: HistogramVec,
+	response_body_size: HistogramVec,
+}
+
+struct BackupMetrics {
+	monitor: Arc<BackupMonitor>,
+	running: IntGauge,
+	archiving_enabled: IntGauge,
+	last_archive_age_seconds: IntGauge,
+	failed_archivals: IntGauge,
+}
+
+struct CertificateMetrics {
+	monitor: Arc<CertificateExpiryMonitor>,
+	expiry_timestamp_seconds: IntGaugeVec,
+	expiring: IntGauge,
+}
+
+struct ProbeMetrics {
+	probe: Arc<SyntheticProbe>,
+	success: IntGauge,
+	last_run_timestamp_seconds: IntGauge,
+	step_duration_seconds: GaugeVec,
+	runs: IntCounter,
+	failures: IntCounter,
+}
+
+struct MaintenanceMetrics {
+	maintenance: Arc<Maintenance>,
+	runs: IntCounterVec,
+	failures: IntCounterVec,
+	last_run_timestamp_seconds: IntGaugeVec,
+	last_run_duration_seconds: GaugeVec,
+}
+
+struct TelemetryMetrics {
+	budgets: Vec<Arc<ExportBudget>>,
+	dropped: IntCounterVec,
+	reachable: IntGaugeVec,
+}
+
+/// Collects the metrics and renders them in the Prometheus text format.
+pub(crate) struct Metrics {
+	registry: Registry,
+	pool_stats: Arc<dyn PoolStatsProvider>,
+	runtime: Handle,
+	pool: PoolMetrics,
+	runtime_metrics: RuntimeMetrics,
+	requests: RequestMetrics,
+	telemetry: TelemetryMetrics,
+	backups: Option<BackupMetrics>,
+	certificates: Option<CertificateMetrics>,
+	probe: Option<ProbeMetrics>,
+	maintenance: Option<MaintenanceMetrics>,
+	/// Serializes renders, as counters are brought up to date with cumulative snapshots.
+	render_lock: Mutex<()>,
+}
+
+impl Metrics {
+	/// Registers all metrics and spawns the task sampling the scheduler latency on the current
+	/// runtime.
+	pub(crate) fn start(
+		pool_stats: Arc<dyn PoolStatsProvider>, telemetry_budgets: Vec<Arc<ExportBudget>>,
+		backup_monitor: Option<Arc<BackupMonitor>>,
+		certificate_monitor: Option<Arc<CertificateExpiryMonitor>>,
+		probe: Option<Arc<SyntheticProbe>>, maintenance: Option<Arc<Maintenance>>,
+	) -> Result<Arc<Self>, String> {
+		let registry = Registry::new();
+		let pool = PoolMetrics {
+			connections: register(
+				&registry,
+				IntGauge::new("vss_db_pool_connections", "Connections in the database pool."),
+			)?,
+			checked_out: register(
+				&registry,
+				IntGauge::new(
+					"vss_db_pool_connections_checked_out",
+					"Database connections currently in use.",
+				),
+			)?,
+			idle: register(
+				&registry,
+				IntGauge::new(
+					"vss_db_pool_connections_idle",
+					"Database connections currently idle.",
+				),
+			)?,
+			waiting: register(
+				&registry,
+				IntGauge::new("vss_db_pool_waiting", "Callers waiting for a database connection."),
+			)?,
+			acquisitions: register(
+				&registry,
+				IntCounter::new(
+					"vss_db_pool_acquisitions_total",
+					"Database connections checked out of the pool.",
+				),
+			)?,
+			wait_seconds: register(
+				&registry,
+				Counter::new(
+					"vss_db_pool_wait_seconds_total",
+					"Time spent waiting for a database connection.",
+				),
+			)?,
+			reconnects: register(
+				&registry,
+				IntCounter::new(
+					"vss_db_pool_reconnects_total",
+					"Broken database connections replaced.",
+				),
+			)?,
+		};
+		let runtime_metrics = RuntimeMetrics {
+			workers: register(
+				&registry,
+				IntGauge::new("vss_runtime_workers", "Worker threads of the tokio runtime."),
+			)?,
+			alive_tasks: register(
+				&registry,
+				IntGauge::new("vss_runtime_alive_tasks", "Tasks alive in the tokio runtime."),
+			)?,
+			global_queue_depth: register(
+				&registry,
+				IntGauge::new(
+					"vss_runtime_global_queue_depth",
+					"Tasks waiting in the global queue of the tokio runtime.",
+				),
+			)?,
+			worker_busy_seconds: register(
+				&registry,
+				Counter::new(
+					"vss_runtime_worker_busy_seconds_total",
+					"Time worker threads spent polling tasks, summed over all workers.",
+				),
+			)?,
+			worker_parks: register(
+				&registry,
+				IntCounter::new(
+					"vss_runtime_worker_parks_total",
+					"Times worker threads parked for lack of work, summed over all workers.",
+				),
+			)?,
+			scheduler_latency: register(
+				&registry,
+				Histogram::with_opts(
+					HistogramOpts::new(
+						"vss_runtime_scheduler_latency_seconds",
+						"Time a newly spawned task waits before it is first polled.",
+					)
+					.buckets(SCHEDULER_LATENCY_BUCKETS.to_vec()),
+				),
+			)?,
+		};
+
+		let size_buckets = exponential_buckets(BODY_SIZE_BUCKET_START, 4.0, BODY_SIZE_BUCKET_COUNT)
+			.map_err(|e| format!("Failed to create metric: {}", e))?;
+		let requests = RequestMetrics {
+			requests: register(
+				&registry,
+				IntCounterVec::new(
+					Opts::new(
+						"vss_requests_total",
+						"Requests served, by operation and status code.",
+					),
+					&["operation", "status"],
+				),
+			)?,
+			duration: register(
+				&registry,
+				HistogramVec::new(
+					HistogramOpts::new(
+						"vss_request_duration_seconds",
+						"Time taken to serve a request, by operation.",
+					)
+					.buckets(DEFAULT_BUCKETS.to_vec()),
+					&["operation"],
+				),
+			)?,
+			request_body_size: register(
+				&registry,
+				HistogramVec::new(
+					HistogramOpts::new(
+						"vss_request_body_size_bytes",
+						"Size of decompressed request bodies, by operation.",
+					)
+					.buckets(size_buckets.clone()),
+					&["operation"],
+				),
+			)?,
+			response_body_size: register(
+				&registry,
+				HistogramVec::new(
+					HistogramOpts::new(
+						"vss_response_body_size_bytes",
+						"Size of response bodies, by operation.",
+					)
+					.buckets(size_buckets),
+					&["operation"],
+				),
+			)?,
+		};
+
+		let telemetry = TelemetryMetrics {
+			budgets: telemetry_budgets,
+			dropped: register(
+				&registry,
+				IntCounterVec::new(
+					Opts::new(
+						"vss_telemetry_dropped_total",
+						"Spans and events dropped instead of being exported, by exporter.",
+					),
+					&["exporter"],
+				),
+			)?,
+			reachable: register(
+				&registry,
+				IntGaugeVec::new(
+					Opts::new(
+						"vss_telemetry_exporter_reachable",
+						"Whether the endpoint of a probed exporter accepts connections, by exporter.",
+					),
+					&["exporter"],
+				),
+			)?,
+		};
+
+		let backups = match backup_monitor {
+			Some(monitor) => Some(BackupMetrics {
+				monitor,
+				running: register(
+					&registry,
+					IntGauge::new(
+						"vss_backups_running",
+						"Whether the database was continuously backed up when last checked.",
+					),
+				)?,
+				archiving_enabled: register(
+					&registry,
+					IntGauge::new(
+						"vss_backup_archiving_enabled",
+						"Whether WAL archiving is enabled in the database.",
+					),
+				)?,
+				last_archive_age_seconds: register(
+					&registry,
+					IntGauge::new(
+						"vss_backup_last_archive_age_seconds",
+						"Time since the last WAL segment was archived.",
+					),
+				)?,
+				failed_archivals: register(
+					&registry,
+					IntGauge::new(
+						"vss_backup_failed_archivals",
+						"Failed attempts to archive a WAL segment, as counted by the database.",
+					),
+				)?,
+			}),
+			None => None,
+		};
+
+		let certificates = match certificate_monitor {
+			Some(monitor) => Some(CertificateMetrics {
+				monitor,
+				expiry_timestamp_seconds: register(
+					&registry,
+					IntGaugeVec::new(
+						Opts::new(
+							"vss_certificate_expiry_timestamp_seconds",
+							"Time a certificate the server depends on expires at, by use and subject.",
+						),
+						&["source", "subject"],
+					),
+				)?,
+				expiring: register(
+					&registry,
+					IntGauge::new(
+						"vss_certificates_expiring",
+						"Certificates expired or within their warning period when last checked.",
+					),
+				)?,
+			}),
+			None => None,
+		};
+
+		let probe = match probe {
+			Some(probe) => Some(ProbeMetrics {
+				probe,
+				success: register(
+					&registry,
+					IntGauge::new(
+						"vss_probe_success",
+						"Whether the last synthetic probe wrote, read and deleted its object.",
+					),
+				)?,
+				last_run_timestamp_seconds: register(
+					&registry,
+					IntGauge::new(
+						"vss_probe_last_run_timestamp_seconds",
+						"Time the last synthetic probe started at.",
+					),
+				)?,
+				step_duration_seconds: register(
+					&registry,
+					GaugeVec::new(
+						Opts::new(
+							"vss_probe_step_duration_seconds",
+							"Time taken by each step of the last synthetic probe, by step.",
+						),
+						&["step"],
+					),
+				)?,
+				runs: register(
+					&registry,
+					IntCounter::new("vss_probe_runs_total", "Synthetic probes run."),
+				)?,
+				failures: register(
+					&registry,
+					IntCounter::new("vss_probe_failures_total", "Synthetic probes failed."),
+				)?,
+			}),
+			None => None,
+		};
+
+		let maintenance = match maintenance {
+			Some(maintenance) => Some(MaintenanceMetrics {
+				maintenance,
+				runs: register(
+					&registry,
+					IntCounterVec::new(
+						Opts::new("vss_maintenance_runs_total", "Maintenance jobs run, by job."),
+						&["job"],
+					),
+				)?,
+				failures: register(
+					&registry,
+					IntCounterVec::new(
+						Opts::new(
+							"vss_maintenance_failures_total",
+							"Maintenance jobs failed, by job.",
+						),
+						&["job"],
+					),
+				)?,
+				last_run_timestamp_seconds: register(
+					&registry,
+					IntGaugeVec::new(
+						Opts::new(
+							"vss_maintenance_last_run_timestamp_seconds",
+							"Time the last run of a maintenance job started at, by job.",
+						),
+						&["job"],
+					),
+				)?,
+				last_run_duration_seconds: register(
+					&registry,
+					GaugeVec::new(
+						Opts::new(
+							"vss_maintenance_last_run_duration_seconds",
+							"Time taken by the last run of a maintenance job, by job.",
+						),
+						&["job"],
+					),
+				)?,
+			}),
+			None => None,
+		};
+
+		let metrics = Arc::new(Self {
+			registry,
+			pool_stats,
+			runtime: Handle::current(),
+			pool,
+			runtime_metrics,
+			requests,
+			telemetry,
+			backups,
+			certificates,
+			probe,
+			maintenance,
+			render_lock: Mutex::new(()),
+		});
+		let scheduler_latency = metrics.runtime_metrics.scheduler_latency.clone();
+		tokio::spawn(async move {
+			let mut interval = tokio::time::interval(SCHEDULER_PROBE_INTERVAL);
+			loop {
+				interval.tick().await;
+				let spawned_at = Instant::now();
+				let latency = tokio::spawn(async move { spawned_at.elapsed() }).await;
+				if let Ok(latency) = latency {
+					scheduler_latency.observe(latency.as_secs_f64());
+				}
+			}
+		});
+		Ok(metrics)
+	}
+
+	/// Records the size of the body of a request for `operation`.
+	pub(crate) fn observe_request_body(&self, operation: &str, size: usize) {
+		self.requests.request_body_size.with_label_values(&[operation]).observe(size as f64);
+	}
+
+	/// Records a request for `operation` served with `status`, along with the time taken and the
+	/// size of the response body.
+	pub(crate) fn observe_response(
+		&self, operation: &str, status: u16, duration: Duration, body_size: usize,
+	) {
+		let status = status.to_string();
+		self.requests.requests.with_label_values(&[operation, status.as_str()]).inc();
+		self.requests.duration.with_label_values(&[operation]).observe(duration.as_secs_f64());
+		self.requests.response_body_size.with_label_values(&[operation]).observe(body_size as f64);
+	}
+
+	/// Renders the current values of all metrics.
+	pub(crate) fn render(&self) -> String {
+		let _render_lock = self.render_lock.lock().unwrap();
+
+		let stats = self.pool_stats.pool_stats();
+		self.pool.connections.set(stats.size as i64);
+		self.pool.checked_out.set(stats.checked_out as i64);
+		self.pool.idle.set(stats.size.saturating_sub(stats.checked_out) as i64);
+		self.pool.waiting.set(stats.waiting as i64);
+		advance_int_counter(&self.pool.acquisitions, stats.acquisitions);
+		advance_counter(&self.pool.wait_seconds, stats.total_wait_time.as_secs_f64());
+		advance_int_counter(&self.pool.reconnects, stats.reconnects);
+
+		let runtime = self.runtime.metrics();
+		let workers = runtime.num_workers();
+		self.runtime_metrics.workers.set(workers as i64);
+		self.runtime_metrics.alive_tasks.set(runtime.num_alive_tasks() as i64);
+		self.runtime_metrics.global_queue_depth.set(runtime.global_queue_depth() as i64);
+		let busy: Duration = (0..workers).map(|w| runtime.worker_total_busy_duration(w)).sum();
+		advance_counter(&self.runtime_metrics.worker_busy_seconds, busy.as_secs_f64());
+		let parks: u64 = (0..workers).map(|w| runtime.worker_park_count(w)).sum();
+		advance_int_counter(&self.runtime_metrics.worker_parks, parks);
+
+		for budget in &self.telemetry.budgets {
+			let exporter = [budget.exporter()];
+			advance_int_counter(
+				&self.telemetry.dropped.with_label_values(&exporter),
+				budget.dropped(),
+			);
+			if let Some(reachable) = budget.reachability() {
+				self.telemetry.reachable.with_label_values(&exporter).set(reachable as i64);
+			}
+		}
+
+		if let Some(backups) = self.backups.as_ref() {
+			if let Some((running, status)) = backups.monitor.last_status() {
+				backups.running.set(running as i64);
+				if let Some(status) = status {
+					backups.archiving_enabled.set(status.archiving_enabled as i64);
+					if let Some(archived_at) = status.last_archived_at {
+						let age = status.checked_at.saturating_sub(archived_at);
+						backups.last_archive_age_seconds.set(age as i64);
+					}
+					backups.failed_archivals.set(status.failed_count as i64);
+				}
+			}
+		}
+
+		if let Some(certificates) = self.certificates.as_ref() {
+			// Certificates rotated out since the last render are no longer reported.
+			certificates.expiry_timestamp_seconds.reset();
+			let expiring_from = unix_time_secs() + certificates.monitor.warning_period().as_secs();
+			let mut expiring = 0;
+			for expiry in certificates.monitor.last_expiries() {
+				let labels = [expiry.source.as_str(), expiry.subject.as_str()];
+				certificates
+					.expiry_timestamp_seconds
+					.with_label_values(&labels)
+					.set(expiry.not_after);
+				if expiry.not_after <= expiring_from as i64 {
+					expiring += 1;
+				}
+			}
+			certificates.expiring.set(expiring);
+		}
+
+		if let Some(probe) = self.probe.as_ref() {
+			if let Some(result) = probe.probe.last_result() {
+				probe.success.set(result.success as i64);
+				probe.last_run_timestamp_seconds.set(result.ran_at as i64);
+				// Steps not reached by the last run are no longer reported.
+				probe.step_duration_seconds.reset();
+				for (step, duration) in result.step_durations {
+					probe
+						.step_duration_seconds
+						.with_label_values(&[step.name()])
+						.set(duration.as_secs_f64());
+				}
+				advance_int_counter(&probe.runs, result.runs);
+				advance_int_counter(&probe.failures, result.failures);
+			}
+		}
+
+		if let Some(maintenance) = self.maintenance.as_ref() {
+			for (job, stats) in maintenance.maintenance.job_stats() {
+				let Some(last_run_at) = stats.last_run_at else {
+					continue;
+				};
+				let job = [job.name()];
+				advance_int_counter(&maintenance.runs.with_label_values(&job), stats.runs);
+				advance_int_counter(&maintenance.failures.with_label_values(&job), stats.failures);
+				maintenance
+					.last_run_timestamp_seconds
+					.with_label_values(&job)
+					.set(last_run_at as i64);
+				maintenance
+					.last_run_duration_seconds
+					.with_label_values(&job)
+					.set(stats.last_duration.as_secs_f64());
+			}
+		}
+
+		let mut buffer = Vec::new();
+		if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+			warn!("Failed to encode metrics: {}", e);
+		}
+		String::from_utf8(buffer).unwrap_or_default()
+	}
+}
+
+fn register<M: prometheus::core::Collector + Clone + 'static>(
+	registry: &Registry, metric: prometheus::Result<M>,
+) -> Result<M, String> {
+	let metric = metric.map_err(|e| format!("Failed to create metric: {}", e))?;
+	registry
+		.register(Box::new(metric.clone()))
+		.map_err(|e| format!("Failed to register metric: {}", e))?;
+	Ok(metric)
+}
+
+/// Advances `counter` to the cumulative `total` observed by its source.
+fn advance_int_counter(counter: &IntCounter, total: u64) {
+	counter.inc_by(total.saturating_sub(counter.get()));
+}
+
+/// Advances `counter` to the cumulative `total` observed by its source.
+fn advance_counter(counter: &Counter, total: f64) {
+	let delta = total - counter.get();
+	if delta > 0.0 {
+		counter.inc_by(delta);
+	}
+}
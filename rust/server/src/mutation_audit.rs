@@ -0,0 +1,258 @@
+//! An audit log of the mutating operations performed on stores, for operators subject to
+//! compliance audits.
+//!
+//! Every put, delete, append, import, store initialization, restoration and cancelled deletion made
+//! through the API is recorded, whether it succeeded or not, with the store owner and the caller,
+//! the keys and versions it addressed, the IP address and authentication method of the client, and
+//! the time it completed at. Destructive operations executed through the admin API are recorded
+//! too, with the approving admin as the caller. Records are buffered and written once a second,
+//! either to the append-only `vss_audit_log` table of the database, where they are purged once
+//! past their retention, or as JSON lines to a file.
+//!
+//! Independently of the records, every action taken through the admin API, such as requesting or
+//! approving an operation, is logged under the [`AUDIT_LOG_TARGET`].
+
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use api::error::VssError;
+use impls::audit_store::{AuditRecord, AuditStore};
+
+use log::{info, warn};
+
+use crate::admin_service::unix_time_secs;
+
+/// The log target under which admin actions are logged, allowing them to be filtered or routed
+/// separately from regular server logs.
+pub(crate) const AUDIT_LOG_TARGET: &str = "vss_audit";
+
+/// How often buffered records are written.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// How often records past their retention are purged.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// The number of records deleted per query.
+const PURGE_BATCH_SIZE: i64 = 1000;
+/// The number of records buffered while they cannot be written, beyond which the oldest ones are
+/// dropped so that an unavailable sink does not exhaust memory.
+const MAX_BUFFERED_RECORDS: usize = 100_000;
+
+/// An action taken through the admin API.
+pub(crate) struct AdminAction<'a> {
+	/// The name of the admin credential the action was taken with.
+	pub(crate) actor: &'a str,
+	/// A short machine-friendly name of the action, e.g. `wipe_store.requested`.
+	pub(crate) action: &'a str,
+	/// Free-form details about the subject of the action.
+	pub(crate) detail: String,
+}
+
+/// Logs `action` under the [`AUDIT_LOG_TARGET`].
+pub(crate) fn log_admin_action(action: AdminAction<'_>) {
+	info!(
+		target: AUDIT_LOG_TARGET,
+		"actor={} action={} {}", action.actor, action.action, action.detail
+	);
+}
+
+/// Where audit records are written to.
+pub(crate) enum AuditSink {
+	/// The `vss_audit_log` table of the PostgreSQL backend.
+	Database {
+		/// The time records are kept for, if they are purged.
+		retention: Option<Duration>,
+	},
+	/// A file records are appended to as JSON lines.
+	File(PathBuf),
+}
+
+impl AuditSink {
+	pub(crate) fn name(&self) -> &'static str {
+		match self {
+			AuditSink::Database { .. } => "database",
+			AuditSink::File(_) => "file",
+		}
+	}
+}
+
+pub(crate) struct AuditConfig {
+	pub(crate) sink: AuditSink,
+}
+
+/// Buffers audit records and writes them to the configured sink in the background.
+pub(crate) struct MutationAudit {
+	store: Arc<dyn AuditStore>,
+	buffered: Mutex<Buffer>,
+}
+
+#[derive(Default)]
+struct Buffer {
+	records: VecDeque<AuditRecord>,
+	/// The number of records dropped since the last flush.
+	dropped: u64,
+}
+
+impl Buffer {
+	fn push(&mut self, record: AuditRecord) {
+		if self.records.len() >= MAX_BUFFERED_RECORDS {
+			self.records.pop_front();
+			self.dropped += 1;
+		}
+		self.records.push_back(record);
+	}
+}
+
+impl MutationAudit {
+	/// Spawns the tasks writing and purging records on the current runtime. `database` is the
+	/// backend's audit store, required by the database sink.
+	pub(crate) fn start(
+		config: AuditConfig, database: Option<Arc<dyn AuditStore>>,
+	) -> Result<Arc<Self>, String> {
+		let (store, retention): (Arc<dyn AuditStore>, _) = match config.sink {
+			AuditSink::Database { retention } => {
+				let store = database.ok_or(
+					"The storage backend cannot keep the audit log, use the file sink instead",
+				)?;
+				(store, retention)
+			},
+			AuditSink::File(path) => (Arc::new(FileAuditStore { path }), None),
+		};
+		let audit = Arc::new(Self { store, buffered: Mutex::new(Buffer::default()) });
+
+		let flusher = Arc::clone(&audit);
+		tokio::spawn(async move {
+			let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+			loop {
+				interval.tick().await;
+				flusher.flush().await;
+			}
+		});
+		if let Some(retention) = retention {
+			let purger = Arc::clone(&audit);
+			tokio::spawn(async move {
+				let mut interval = tokio::time::interval(PURGE_INTERVAL);
+				loop {
+					interval.tick().await;
+					purger.purge(retention).await;
+				}
+			});
+		}
+		Ok(audit)
+	}
+
+	/// Buffers `record` until the next flush.
+	pub(crate) fn record(&self, record: AuditRecord) {
+		self.buffered.lock().unwrap().push(record);
+	}
+
+	/// Writes the buffered records. Records which cannot be written are kept for the next flush.
+	pub(crate) async fn flush(&self) {
+		let (records, dropped) = {
+			let mut buffered = self.buffered.lock().unwrap();
+			(
+				Vec::from(std::mem::take(&mut buffered.records)),
+				std::mem::take(&mut buffered.dropped),
+			)
+		};
+		if dropped > 0 {
+			warn!("Dropped {} audit record(s) which could not be written in time", dropped);
+		}
+		if records.is_empty() {
+			return;
+		}
+		if let Err(e) = self.store.append_audit_records(&records).await {
+			warn!("Failed to write {} audit record(s), retrying: {}", records.len(), e);
+			// Records made in the meantime are newer, so the failed ones go first.
+			let mut buffered = self.buffered.lock().unwrap();
+			let newer = std::mem::take(&mut buffered.records);
+			for record in records.into_iter().chain(newer) {
+				buffered.push(record);
+			}
+		}
+	}
+
+	async fn purge(&self, retention: Duration) {
+		let recorded_before = unix_time_secs().saturating_sub(retention.as_secs());
+		let mut purged = 0;
+		loop {
+			match self.store.purge_audit_records(recorded_before, PURGE_BATCH_SIZE).await {
+				Ok(count) => {
+					purged += count;
+					if (count as i64) < PURGE_BATCH_SIZE {
+						break;
+					}
+				},
+				Err(e) => {
+					warn!("Failed to purge expired audit records: {}", e);
+					break;
+				},
+			}
+		}
+		if purged > 0 {
+			info!("Purged {} audit record(s) past their retention", purged);
+		}
+	}
+}
+
+/// Appends audit records to a file as JSON lines.
+///
+/// The file is reopened for every write, so it can be rotated by moving it away.
+struct FileAuditStore {
+	path: PathBuf,
+}
+
+#[async_trait]
+impl AuditStore for FileAuditStore {
+	async fn append_audit_records(&self, records: &[AuditRecord]) -> Result<(), VssError> {
+		let mut lines = String::new();
+		for record in records {
+			let keys: Vec<_> = record
+				.keys
+				.iter()
+				.map(
+					|key| json!({ "key": key.key, "version": key.version, "deleted": key.deleted }),
+				)
+				.collect();
+			let line = json!({
+				"recorded_at": record.recorded_at,
+				"user_token": record.user_token,
+				"caller_user_token": record.caller_user_token,
+				"store_id": record.store_id,
+				"operation": record.operation,
+				"keys": keys,
+				"source_ip": record.source_ip,
+				"auth_method": record.auth_method,
+				"succeeded": record.succeeded,
+			});
+			lines.push_str(&line.to_string());
+			lines.push('\n');
+		}
+		let path = self.path.clone();
+		let write = move || {
+			let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+			file.write_all(lines.as_bytes())?;
+			file.sync_data()
+		};
+		tokio::task::spawn_blocking(write)
+			.await
+			.map_err(|e| VssError::InternalServerError(e.to_string()))?
+			.map_err(|e| {
+				VssError::InternalServerError(format!(
+					"Failed to append to {}: {}",
+					self.path.display(),
+					e
+				))
+			})
+	}
+
+	/// Records in files are not purged, they are rotated by the operator instead.
+	async fn purge_audit_records(&self, _: u64, _: i64) -> Result<u64, VssError> {
+		Ok(0)
+	}
+}
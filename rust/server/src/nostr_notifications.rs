@@ -0,0 +1,487 @@
+//! Optional Nostr notifications about changes to a user's stores.
+//!
+//! A user opts in by storing their Nostr public key (`npub` or hex) under the configured
+//! registration key in a store. Whenever objects under one of the configured key prefixes are
+//! written or deleted in that store, a NIP-44 encrypted event in the style of an NWC notification is
+//! sent to the registered key through the configured relays, e.g. to alert wallets that their
+//! backup was updated from another device.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
+use futures_util::{SinkExt, StreamExt};
+use hmac::{Hmac, Mac};
+use secp256k1::{ecdh, Keypair, Parity, PublicKey, SecretKey, XOnlyPublicKey, SECP256K1};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+use api::error::VssError;
+use api::kv_store::KvStore;
+use api::observer::KvStoreObserver;
+use api::types::{AppendObjectRequest, DeleteObjectRequest, GetObjectRequest, PutObjectRequest};
+
+use log::{debug, warn};
+
+use crate::admin_service::unix_time_secs;
+
+/// The event kind used by NWC for NIP-44 encrypted notifications.
+pub(crate) const DEFAULT_EVENT_KIND: u16 = 23197;
+pub(crate) const DEFAULT_REGISTRATION_KEY: &str = "nostr_notification_pubkey";
+pub(crate) const DEFAULT_MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+const NOTIFICATION_TYPE: &str = "vss_store_updated";
+const RELAY_TIMEOUT: Duration = Duration::from_secs(10);
+const QUEUE_SIZE: usize = 1024;
+
+pub(crate) struct NostrNotificationConfig {
+	/// The keypair the server signs and encrypts notifications with.
+	pub(crate) keypair: Keypair,
+	pub(crate) relays: Vec<String>,
+	/// Only changes to keys starting with one of these prefixes trigger a notification.
+	pub(crate) key_prefixes: Vec<String>,
+	/// The key under which users register the public key to notify, per store.
+	pub(crate) registration_key: String,
+	/// The minimum time between two notifications about the same store.
+	pub(crate) min_interval: Duration,
+	pub(crate) event_kind: u16,
+}
+
+/// Parses a Nostr secret key given as `nsec` or hex.
+pub(crate) fn parse_secret_key(secret_key: &str) -> Result<Keypair, String> {
+	let bytes = decode_key(secret_key, "nsec").ok_or("Invalid Nostr secret key".to_string())?;
+	let secret_key =
+		SecretKey::from_byte_array(bytes).map_err(|_| "Invalid Nostr secret key".to_string())?;
+	Ok(Keypair::from_secret_key(SECP256K1, &secret_key))
+}
+
+/// Parses a Nostr public key given as `npub` or hex.
+fn parse_public_key(public_key: &str) -> Option<XOnlyPublicKey> {
+	XOnlyPublicKey::from_byte_array(decode_key(public_key, "npub")?).ok()
+}
+
+fn decode_key(key: &str, hrp: &str) -> Option<[u8; 32]> {
+	let key = key.trim();
+	let bytes = if key.starts_with(hrp) {
+		let (decoded_hrp, bytes) = bech32::decode(key).ok()?;
+		if decoded_hrp.as_str() != hrp {
+			return None;
+		}
+		bytes
+	} else {
+		hex_decode(key)?
+	};
+	bytes.try_into().ok()
+}
+
+fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+	if hex.len() % 2 != 0 || !hex.is_ascii() {
+		return None;
+	}
+	(0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok()).collect()
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+	bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+	// unwrap safety: HMAC accepts keys of any length.
+	let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+	for part in parts {
+		mac.update(part);
+	}
+	mac.finalize().into_bytes().into()
+}
+
+fn nip44_padded_len(unpadded_len: usize) -> usize {
+	if unpadded_len <= 32 {
+		return 32;
+	}
+	let next_power = 1usize << (usize::BITS - (unpadded_len - 1).leading_zeros());
+	let chunk = if next_power <= 256 { 32 } else { next_power / 8 };
+	chunk * ((unpadded_len - 1) / chunk + 1)
+}
+
+/// Derives the NIP-44 version 2 conversation key shared by `secret_key` and `public_key`.
+fn nip44_conversation_key(secret_key: &SecretKey, public_key: &XOnlyPublicKey) -> [u8; 32] {
+	let public_key = PublicKey::from_x_only_public_key(*public_key, Parity::Even);
+	let shared_point = ecdh::shared_secret_point(&public_key, secret_key);
+	hmac_sha256(b"nip44-v2", &[&shared_point[..32]])
+}
+
+/// Encrypts `plaintext` from `keypair` to `recipient` as specified by NIP-44 version 2.
+fn nip44_encrypt(
+	keypair: &Keypair, recipient: &XOnlyPublicKey, plaintext: &str, nonce: [u8; 32],
+) -> Result<String, String> {
+	let plaintext = plaintext.as_bytes();
+	if plaintext.is_empty() || plaintext.len() > u16::MAX as usize {
+		return Err("Notification plaintext has an invalid length".to_string());
+	}
+	let conversation_key = nip44_conversation_key(&keypair.secret_key(), recipient);
+
+	// HKDF-expand the conversation key into the ChaCha20 key, ChaCha20 nonce and HMAC key.
+	let t1 = hmac_sha256(&conversation_key, &[&nonce, &[1]]);
+	let t2 = hmac_sha256(&conversation_key, &[&t1, &nonce, &[2]]);
+	let t3 = hmac_sha256(&conversation_key, &[&t2, &nonce, &[3]]);
+	let mut message_keys = [0u8; 96];
+	message_keys[..32].copy_from_slice(&t1);
+	message_keys[32..64].copy_from_slice(&t2);
+	message_keys[64..].copy_from_slice(&t3);
+	let (chacha_key, rest) = message_keys.split_at(32);
+	let (chacha_nonce, rest) = rest.split_at(12);
+	let hmac_key = &rest[..32];
+
+	let mut padded = vec![0u8; 2 + nip44_padded_len(plaintext.len())];
+	padded[..2].copy_from_slice(&(plaintext.len() as u16).to_be_bytes());
+	padded[2..2 + plaintext.len()].copy_from_slice(plaintext);
+	ChaCha20::new(chacha_key.into(), chacha_nonce.into()).apply_keystream(&mut padded);
+	let mac = hmac_sha256(hmac_key, &[&nonce, &padded]);
+
+	let mut payload = Vec::with_capacity(1 + 32 + padded.len() + 32);
+	payload.push(2);
+	payload.extend_from_slice(&nonce);
+	payload.extend_from_slice(&padded);
+	payload.extend_from_slice(&mac);
+	Ok(BASE64.encode(payload))
+}
+
+#[derive(Serialize)]
+struct StoreUpdatedNotification<'a> {
+	notification_type: &'a str,
+	notification: StoreUpdate<'a>,
+}
+
+#[derive(Serialize)]
+struct StoreUpdate<'a> {
+	store_id: &'a str,
+	keys: &'a [String],
+	updated_at: u64,
+}
+
+#[derive(Serialize)]
+struct NostrEvent {
+	id: String,
+	pubkey: String,
+	created_at: u64,
+	kind: u16,
+	tags: Vec<Vec<String>>,
+	content: String,
+	sig: String,
+}
+
+impl NostrEvent {
+	fn new_signed(
+		keypair: &Keypair, kind: u16, tags: Vec<Vec<String>>, content: String,
+	) -> NostrEvent {
+		let pubkey = hex_encode(&keypair.x_only_public_key().0.serialize());
+		let created_at = unix_time_secs();
+		let id = event_id(&pubkey, created_at, kind, &tags, &content);
+		let sig = SECP256K1.sign_schnorr_with_aux_rand(&id, keypair, &rand::random());
+		NostrEvent {
+			id: hex_encode(&id),
+			pubkey,
+			created_at,
+			kind,
+			tags,
+			content,
+			sig: hex_encode(&sig.to_byte_array()),
+		}
+	}
+}
+
+/// Computes the id of an event as specified by NIP-01, i.e. the SHA-256 hash of its serialization.
+fn event_id(
+	pubkey: &str, created_at: u64, kind: u16, tags: &[Vec<String>], content: &str,
+) -> [u8; 32] {
+	let serialized = serde_json::json!([0, pubkey, created_at, kind, tags, content]).to_string();
+	Sha256::digest(serialized.as_bytes()).into()
+}
+
+/// Builds, rate-limits and publishes store update notifications.
+pub(crate) struct NostrNotifier {
+	config: NostrNotificationConfig,
+	last_notified: Mutex<HashMap<[u8; 32], Instant>>,
+	queue: mpsc::Sender<NostrEvent>,
+}
+
+impl NostrNotifier {
+	/// Creates the notifier and spawns the task publishing its events to the relays.
+	pub(crate) fn start(config: NostrNotificationConfig) -> Arc<Self> {
+		let (queue, events) = mpsc::channel(QUEUE_SIZE);
+		tokio::spawn(publish_events(config.relays.clone(), events));
+		Arc::new(Self { config, last_notified: Mutex::new(HashMap::new()), queue })
+	}
+
+	fn watched_keys<'a>(&self, keys: impl Iterator<Item = &'a str>) -> Vec<String> {
+		keys.filter(|key| *key != self.config.registration_key)
+			.filter(|key| self.config.key_prefixes.iter().any(|prefix| key.starts_with(prefix)))
+			.map(str::to_string)
+			.collect()
+	}
+
+	/// Returns whether a notification about the given store may be sent now, recording it if so.
+	fn try_acquire_slot(&self, user_token: &str, store_id: &str) -> bool {
+		let mut hasher = Sha256::new();
+		hasher.update((user_token.len() as u64).to_be_bytes());
+		hasher.update(user_token.as_bytes());
+		hasher.update(store_id.as_bytes());
+		let slot: [u8; 32] = hasher.finalize().into();
+
+		let now = Instant::now();
+		let mut last_notified = self.last_notified.lock().unwrap();
+		last_notified.retain(|_, at| now.duration_since(*at) < self.config.min_interval);
+		if last_notified.contains_key(&slot) {
+			return false;
+		}
+		last_notified.insert(slot, now);
+		true
+	}
+
+	async fn notify(
+		&self, store: &Arc<dyn KvStore>, user_token: String, store_id: String, keys: Vec<String>,
+	) {
+		let request = GetObjectRequest {
+			store_id: store_id.clone(),
+			key: self.config.registration_key.clone(),
+		};
+		let registration = match store.get(user_token.clone(), request).await {
+			Ok(response) => response.value.map(|kv| kv.value).unwrap_or_default(),
+			Err(VssError::NoSuchKeyError(_)) => return,
+			Err(e) => {
+				warn!("Failed to look up Nostr notification registration: {}", e);
+				return;
+			},
+		};
+		let recipient = match std::str::from_utf8(&registration).ok().and_then(parse_public_key) {
+			Some(recipient) => recipient,
+			None => {
+				debug!("Ignoring invalid Nostr notification registration in store {}", store_id);
+				return;
+			},
+		};
+		if !self.try_acquire_slot(&user_token, &store_id) {
+			return;
+		}
+
+		let notification = StoreUpdatedNotification {
+			notification_type: NOTIFICATION_TYPE,
+			notification: StoreUpdate {
+				store_id: &store_id,
+				keys: &keys,
+				updated_at: unix_time_secs(),
+			},
+		};
+		// unwrap safety: the notification always serializes successfully.
+		let plaintext = serde_json::to_string(&notification).unwrap();
+		let content =
+			match nip44_encrypt(&self.config.keypair, &recipient, &plaintext, rand::random()) {
+				Ok(content) => content,
+				Err(e) => {
+					warn!("Failed to encrypt Nostr notification: {}", e);
+					return;
+				},
+			};
+		let tags = vec![vec!["p".to_string(), hex_encode(&recipient.serialize())]];
+		let event =
+			NostrEvent::new_signed(&self.config.keypair, self.config.event_kind, tags, content);
+		if self.queue.try_send(event).is_err() {
+			warn!("Dropping Nostr notification as the relay queue is full");
+		}
+	}
+}
+
+async fn publish_events(relays: Vec<String>, mut events: mpsc::Receiver<NostrEvent>) {
+	while let Some(event) = events.recv().await {
+		let message = serde_json::json!(["EVENT", event]).to_string();
+		for relay in &relays {
+			match tokio::time::timeout(RELAY_TIMEOUT, publish_to_relay(relay, &message)).await {
+				Ok(Ok(())) => debug!("Published Nostr notification {} to {}", event.id, relay),
+				Ok(Err(e)) => warn!("Failed to publish Nostr notification to {}: {}", relay, e),
+				Err(_) => warn!("Timed out publishing Nostr notification to {}", relay),
+			}
+		}
+	}
+}
+
+async fn publish_to_relay(relay: &str, message: &str) -> Result<(), String> {
+	let (mut socket, _) =
+		tokio_tungstenite::connect_async(relay).await.map_err(|e| e.to_string())?;
+	socket.send(Message::text(message)).await.map_err(|e| e.to_string())?;
+	// Wait for the relay to acknowledge the event with an OK message.
+	let result = loop {
+		match socket.next().await {
+			Some(Ok(Message::Text(reply))) if reply.starts_with("[\"OK\"") => {
+				break if reply.contains("true") {
+					Ok(())
+				} else {
+					Err(format!("Relay rejected event: {}", reply))
+				};
+			},
+			Some(Ok(_)) => continue,
+			Some(Err(e)) => break Err(e.to_string()),
+			None => break Err("Connection closed before acknowledgement".to_string()),
+		}
+	};
+	let _ = socket.close(None).await;
+	result
+}
+
+/// A [`KvStoreObserver`] triggering [`NostrNotifier`] notifications on successful writes.
+pub(crate) struct NostrNotifyingObserver {
+	/// The store registrations are looked up in.
+	store: Arc<dyn KvStore>,
+	notifier: Arc<NostrNotifier>,
+}
+
+impl NostrNotifyingObserver {
+	pub(crate) fn new(store: Arc<dyn KvStore>, notifier: Arc<NostrNotifier>) -> Self {
+		Self { store, notifier }
+	}
+
+	fn notify<'a>(&self, user_token: &str, store_id: &str, keys: impl Iterator<Item = &'a str>) {
+		let keys = self.notifier.watched_keys(keys);
+		if keys.is_empty() {
+			return;
+		}
+		let store = Arc::clone(&self.store);
+		let notifier = Arc::clone(&self.notifier);
+		let (user_token, store_id) = (user_token.to_string(), store_id.to_string());
+		tokio::spawn(async move { notifier.notify(&store, user_token, store_id, keys).await });
+	}
+}
+
+impl KvStoreObserver for NostrNotifyingObserver {
+	fn on_put(&self, user_token: &str, request: &PutObjectRequest) {
+		let keys = request.transaction_items.iter().chain(request.delete_items.iter());
+		self.notify(user_token, &request.store_id, keys.map(|kv| kv.key.as_str()));
+	}
+
+	fn on_delete(&self, user_token: &str, request: &DeleteObjectRequest) {
+		let keys = request.key_value.iter().map(|kv| kv.key.as_str());
+		self.notify(user_token, &request.store_id, keys);
+	}
+
+	fn on_append(&self, user_token: &str, request: &AppendObjectRequest, _version: i64) {
+		self.notify(user_token, &request.store_id, std::iter::once(request.key.as_str()));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		event_id, hex_decode, hex_encode, nip44_conversation_key, nip44_encrypt, nip44_padded_len,
+		NostrEvent,
+	};
+	use secp256k1::{schnorr, Keypair, SecretKey, XOnlyPublicKey, SECP256K1};
+	use sha2::{Digest, Sha256};
+
+	fn keypair(secret_key_hex: &str) -> Keypair {
+		let secret_key: [u8; 32] = hex_decode(secret_key_hex).unwrap().try_into().unwrap();
+		Keypair::from_secret_key(SECP256K1, &SecretKey::from_byte_array(secret_key).unwrap())
+	}
+
+	fn public_key(public_key_hex: &str) -> XOnlyPublicKey {
+		let public_key: [u8; 32] = hex_decode(public_key_hex).unwrap().try_into().unwrap();
+		XOnlyPublicKey::from_byte_array(public_key).unwrap()
+	}
+
+	// The vectors below are those of NIP-44 version 2, see
+	// https://github.com/paulmillr/nip44/blob/main/nip44.vectors.json.
+
+	#[test]
+	fn derives_nip44_conversation_keys() {
+		let secret_key =
+			keypair("315e59ff51cb9209768cf7da80791ddcaae56ac9775eb25b6dee1234bc5d2268");
+		let public_key =
+			public_key("c2f9d9948dc8c7c38321e4b85c8558872eafa0641cd269db76848a6073e69133");
+		assert_eq!(
+			hex_encode(&nip44_conversation_key(&secret_key.secret_key(), &public_key)),
+			"3dfef0ce2a4d80a25e7a328accf73448ef67096f65f79588e358d9a0eb9013f1"
+		);
+	}
+
+	#[test]
+	fn pads_nip44_plaintexts() {
+		let vectors = [
+			(16, 32),
+			(32, 32),
+			(33, 64),
+			(37, 64),
+			(45, 64),
+			(49, 64),
+			(64, 64),
+			(65, 96),
+			(100, 128),
+			(111, 128),
+			(200, 224),
+			(250, 256),
+			(320, 320),
+			(383, 384),
+			(384, 384),
+			(400, 448),
+			(500, 512),
+			(512, 512),
+			(515, 640),
+			(700, 768),
+			(800, 896),
+			(900, 1024),
+			(1020, 1024),
+			(65536, 65536),
+		];
+		for (unpadded_len, padded_len) in vectors {
+			assert_eq!(nip44_padded_len(unpadded_len), padded_len, "length {}", unpadded_len);
+		}
+	}
+
+	#[test]
+	fn encrypts_nip44_payloads() {
+		let sender = keypair("0000000000000000000000000000000000000000000000000000000000000001");
+		let recipient = keypair("0000000000000000000000000000000000000000000000000000000000000002");
+		let recipient = recipient.x_only_public_key().0;
+		assert_eq!(
+			hex_encode(&nip44_conversation_key(&sender.secret_key(), &recipient)),
+			"c41c775356fd92eadc63ff5a0dc1da211b268cbea22316767095b2871ea1412d"
+		);
+		let nonce = hex_decode("0000000000000000000000000000000000000000000000000000000000000001");
+		let payload =
+			nip44_encrypt(&sender, &recipient, "a", nonce.unwrap().try_into().unwrap()).unwrap();
+		assert_eq!(
+			payload,
+			"AgAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABee0G5VSK0/9YypIObAtDKfYEAjD35uVkHyB0F4DwrcNaCXlCWZKaArsGrY6M9wnuTMxWfp1RTN9Xga8no+kF5Vsb"
+		);
+	}
+
+	#[test]
+	fn computes_nip01_event_ids() {
+		let pubkey = "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+		let tags = vec![vec!["p".to_string(), pubkey.to_string()]];
+		let content = "line\nbreak \"quoted\" back\\slash\ttab \u{2603}";
+		// NIP-01 serializes events without whitespace, escaping only line breaks, double quotes,
+		// backslashes, carriage returns, tabs, backspaces and form feeds in strings.
+		let serialized = format!(
+			"[0,\"{pubkey}\",1700000000,1,[[\"p\",\"{pubkey}\"]],\"{}\"]",
+			"line\\nbreak \\\"quoted\\\" back\\\\slash\\ttab \u{2603}"
+		);
+		let expected: [u8; 32] = Sha256::digest(serialized.as_bytes()).into();
+		assert_eq!(event_id(pubkey, 1_700_000_000, 1, &tags, content), expected);
+	}
+
+	#[test]
+	fn signs_events_with_their_ids() {
+		let keypair = keypair("0000000000000000000000000000000000000000000000000000000000000003");
+		let event = NostrEvent::new_signed(&keypair, 23197, Vec::new(), "content".to_string());
+		let id = event_id(&event.pubkey, event.created_at, event.kind, &event.tags, &event.content);
+		assert_eq!(event.id, hex_encode(&id));
+		let sig = schnorr::Signature::from_byte_array(
+			hex_decode(&event.sig).unwrap().try_into().unwrap(),
+		);
+		SECP256K1.verify_schnorr(&sig, &id, &keypair.x_only_public_key().0).unwrap();
+	}
+}
@@ -0,0 +1,170 @@
+//! Per-user storage quotas and the warnings sent to users approaching them.
+//!
+//! Once a user's storage usage crosses the configured share of their quota, successful writes
+//! carry an `X-VSS-Quota-Warning` header, e.g. `bytes=870000/1000000`, listing each limit nearly
+//! or fully used up. Clients can surface this as "backup storage almost full" well before writes
+//! start failing.
+//!
+//! Unless only warnings are configured, writes adding values are rejected with a
+//! `QuotaExceededException` once a user's usage reached any of their limits. Deleting objects is
+//! always allowed, so users can free up space. Usage is checked before each write, so the write
+//! crossing a limit still succeeds.
+//!
+//! Limits can differ between users through named profiles, e.g. for free and paid plans, which
+//! the authorizer selects from the user's credentials.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::watch;
+
+use api::error::VssError;
+use impls::usage_store::{StorageUsage, UsageStore};
+
+use log::{debug, warn};
+
+/// The header carrying quota warnings on responses.
+pub(crate) const QUOTA_WARNING_HEADER: &str = "x-vss-quota-warning";
+
+/// The share of a quota after which warnings are sent, unless configured otherwise.
+pub(crate) const DEFAULT_SOFT_LIMIT_RATIO: f64 = 0.8;
+
+/// How long a user's measured usage is reused before it is queried again.
+const USAGE_CACHE_TTL: Duration = Duration::from_secs(60);
+/// The number of users whose usage is cached before the cache is reset, bounding its memory
+/// usage.
+const MAX_CACHED_USERS: usize = 100_000;
+
+/// The quotas applying to a user.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct QuotaLimits {
+	/// The total size of values a user may store, in bytes.
+	pub(crate) max_bytes_per_user: Option<u64>,
+	/// The number of objects a user may store.
+	pub(crate) max_objects_per_user: Option<u64>,
+}
+
+impl QuotaLimits {
+	pub(crate) fn is_unlimited(&self) -> bool {
+		self.max_bytes_per_user.is_none() && self.max_objects_per_user.is_none()
+	}
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct QuotaConfig {
+	/// The limits of users without a profile, or with a profile not configured below.
+	pub(crate) default_limits: QuotaLimits,
+	/// The limits of users by the name of their profile. A profile's unset limits do not apply.
+	pub(crate) profiles: HashMap<String, QuotaLimits>,
+	/// The share of a quota, between 0 and 1, after which writes carry a warning.
+	pub(crate) soft_limit_ratio: f64,
+	/// Whether writes are rejected once a limit is reached, rather than only warned about.
+	pub(crate) enforce: bool,
+}
+
+/// Tracks users' storage usage against the configured quota, which may be reloaded.
+pub(crate) struct Quotas {
+	config: watch::Receiver<QuotaConfig>,
+	usage: Arc<dyn UsageStore>,
+	cache: Mutex<HashMap<String, (Instant, StorageUsage)>>,
+}
+
+impl Quotas {
+	pub(crate) fn new(config: watch::Receiver<QuotaConfig>, usage: Arc<dyn UsageStore>) -> Self {
+		Self { config, usage, cache: Mutex::new(HashMap::new()) }
+	}
+
+	/// Returns the limits of the given profile, falling back to the default limits.
+	pub(crate) fn limits(&self, profile: Option<&str>) -> QuotaLimits {
+		let config = self.config.borrow();
+		profile
+			.and_then(|profile| config.profiles.get(profile))
+			.copied()
+			.unwrap_or(config.default_limits)
+	}
+
+	/// Whether writes are rejected once a limit is reached.
+	pub(crate) fn enforces(&self) -> bool {
+		self.config.borrow().enforce
+	}
+
+	/// Fails if the given user reached any quota of their profile, measuring their usage afresh.
+	pub(crate) async fn check(
+		&self, user_token: &str, profile: Option<&str>,
+	) -> Result<(), VssError> {
+		let limits = self.limits(profile);
+		if limits.is_unlimited() {
+			return Ok(());
+		}
+		let usage = self.usage.get_storage_usage(user_token).await?;
+		self.cache_usage(user_token, usage);
+		let limits = [
+			("bytes", usage.bytes, limits.max_bytes_per_user),
+			("objects", usage.objects, limits.max_objects_per_user),
+		];
+		for (name, used, limit) in limits {
+			if let Some(limit) = limit.filter(|limit| used >= *limit) {
+				debug!("User reached their storage quota of {} {}", limit, name);
+				return Err(VssError::QuotaExceededError(format!(
+					"Quota of {} {} reached, delete objects to free up space.",
+					limit, name
+				)));
+			}
+		}
+		Ok(())
+	}
+
+	/// Returns the warning to attach to responses for the given user, if their usage crossed the
+	/// soft limit of any quota of their profile.
+	pub(crate) async fn warning(&self, user_token: &str, profile: Option<&str>) -> Option<String> {
+		let limits = self.limits(profile);
+		if limits.is_unlimited() {
+			return None;
+		}
+		let usage = self.storage_usage(user_token).await?;
+		let soft_limit_ratio = self.config.borrow().soft_limit_ratio;
+		let limits = [
+			("bytes", usage.bytes, limits.max_bytes_per_user),
+			("objects", usage.objects, limits.max_objects_per_user),
+		];
+		let warnings: Vec<String> = limits
+			.iter()
+			.filter_map(|(name, used, limit)| {
+				let limit = (*limit)?;
+				let soft_limit = (limit as f64 * soft_limit_ratio) as u64;
+				(*used >= soft_limit).then(|| format!("{}={}/{}", name, used, limit))
+			})
+			.collect();
+		if warnings.is_empty() {
+			return None;
+		}
+		debug!("User is approaching their storage quota: {}", warnings.join(", "));
+		Some(warnings.join(", "))
+	}
+
+	async fn storage_usage(&self, user_token: &str) -> Option<StorageUsage> {
+		if let Some((measured_at, usage)) = self.cache.lock().unwrap().get(user_token) {
+			if measured_at.elapsed() < USAGE_CACHE_TTL {
+				return Some(*usage);
+			}
+		}
+		let usage = match self.usage.get_storage_usage(user_token).await {
+			Ok(usage) => usage,
+			Err(e) => {
+				warn!("Failed to look up storage usage: {}", e);
+				return None;
+			},
+		};
+		self.cache_usage(user_token, usage);
+		Some(usage)
+	}
+
+	fn cache_usage(&self, user_token: &str, usage: StorageUsage) {
+		let mut cache = self.cache.lock().unwrap();
+		if cache.len() >= MAX_CACHED_USERS {
+			cache.clear();
+		}
+		cache.insert(user_token.to_string(), (Instant::now(), usage));
+	}
+}
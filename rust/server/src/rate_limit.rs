@@ -0,0 +1,76 @@
+//! Limits on the rate of requests a single client may make.
+//!
+//! Each client is given a token bucket, refilled at the configured rate up to the configured burst,
+//! and every request takes a token from it. Authenticated requests are accounted to the user
+//! token, while requests failing authentication and requests to unauthenticated endpoints are
+//! accounted to the client's IP address. Requests finding their bucket empty are rejected with
+//! `429 Too Many Requests`, telling the client when to retry.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tokio::sync::watch;
+
+use log::debug;
+
+/// The number of clients whose buckets are tracked before the tracked buckets are reset, bounding
+/// their memory usage.
+const MAX_TRACKED_CLIENTS: usize = 100_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct RateLimitConfig {
+	/// The rate at which a client's bucket is refilled, in requests per second.
+	pub(crate) requests_per_second: f64,
+	/// The number of requests a client may make at once after being idle.
+	pub(crate) burst: u32,
+}
+
+/// The client a request is accounted to.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(crate) enum RateLimitKey {
+	User(String),
+	Ip(IpAddr),
+}
+
+/// A client's bucket, holding the tokens left as of the time it was last updated.
+struct Bucket {
+	tokens: f64,
+	updated_at: Instant,
+}
+
+/// Tracks the token buckets of clients against the configured rate, which may be reloaded.
+pub(crate) struct RateLimiter {
+	config: watch::Receiver<RateLimitConfig>,
+	buckets: Mutex<HashMap<RateLimitKey, Bucket>>,
+}
+
+impl RateLimiter {
+	pub(crate) fn new(config: watch::Receiver<RateLimitConfig>) -> Self {
+		Self { config, buckets: Mutex::new(HashMap::new()) }
+	}
+
+	/// Takes a token from the client's bucket, or returns the time after which one is available.
+	pub(crate) fn acquire(&self, key: RateLimitKey) -> Result<(), Duration> {
+		let now = Instant::now();
+		let config = *self.config.borrow();
+		let burst = config.burst as f64;
+		let mut buckets = self.buckets.lock().unwrap();
+		if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&key) {
+			buckets.clear();
+		}
+		let bucket = buckets.entry(key).or_insert(Bucket { tokens: burst, updated_at: now });
+		let refilled =
+			now.duration_since(bucket.updated_at).as_secs_f64() * config.requests_per_second;
+		bucket.tokens = (bucket.tokens + refilled).min(burst);
+		bucket.updated_at = now;
+		if bucket.tokens >= 1.0 {
+			bucket.tokens -= 1.0;
+			return Ok(());
+		}
+		let retry_after = (1.0 - bucket.tokens) / config.requests_per_second;
+		debug!("Rejected request exceeding rate limit, retry after {:.3}s", retry_after);
+		Err(Duration::from_secs_f64(retry_after))
+	}
+}
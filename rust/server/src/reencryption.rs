@@ -0,0 +1,172 @@
+//! Background job re-encrypting all stored values with the current encryption key.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use api::error::VssError;
+use impls::encrypted_store::EncryptedKvStore;
+use impls::record_store::{RecordCursor, RecordStore};
+
+use log::{error, info};
+
+use crate::admin_service::unix_time_secs;
+use crate::mutation_audit::{log_admin_action, AdminAction};
+
+/// The number of records re-encrypted per batch unless requested otherwise.
+pub(crate) const DEFAULT_BATCH_SIZE: i64 = 100;
+/// The upper bound on the number of records re-encrypted per batch.
+pub(crate) const MAX_BATCH_SIZE: i64 = 1000;
+/// The pause between two batches unless requested otherwise, throttling the load put on the
+/// database.
+pub(crate) const DEFAULT_BATCH_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum JobState {
+	#[default]
+	Idle,
+	Running,
+	Completed,
+	Failed,
+}
+
+/// The progress of the most recent re-encryption run, as reported through the admin API.
+#[derive(Clone, Default, Serialize)]
+pub(crate) struct ReencryptionProgress {
+	pub(crate) state: JobState,
+	pub(crate) started_by: Option<String>,
+	pub(crate) started_at: Option<u64>,
+	pub(crate) finished_at: Option<u64>,
+	/// The number of records inspected so far.
+	pub(crate) scanned_records: u64,
+	/// The number of records rewritten with the current key.
+	pub(crate) reencrypted_records: u64,
+	/// The number of records left untouched as they were modified or deleted concurrently.
+	pub(crate) skipped_records: u64,
+	pub(crate) error: Option<String>,
+}
+
+pub(crate) struct ReencryptionSettings {
+	pub(crate) batch_size: i64,
+	pub(crate) batch_delay: Duration,
+}
+
+/// Walks all stored records in throttled batches and rewrites those not encrypted with the
+/// current key, without changing their object versions.
+pub(crate) struct ReencryptionJob {
+	records: Arc<dyn RecordStore>,
+	store: Arc<EncryptedKvStore>,
+	progress: Mutex<ReencryptionProgress>,
+}
+
+impl ReencryptionJob {
+	pub(crate) fn new(records: Arc<dyn RecordStore>, store: Arc<EncryptedKvStore>) -> Self {
+		Self { records, store, progress: Mutex::new(ReencryptionProgress::default()) }
+	}
+
+	pub(crate) fn progress(&self) -> ReencryptionProgress {
+		self.progress.lock().unwrap().clone()
+	}
+
+	/// Starts a new run in the background on behalf of the admin `started_by`. Returns `false`
+	/// without doing anything if a run is already in progress.
+	pub(crate) fn start(
+		self: &Arc<Self>, started_by: &str, settings: ReencryptionSettings,
+	) -> bool {
+		{
+			let mut progress = self.progress.lock().unwrap();
+			if progress.state == JobState::Running {
+				return false;
+			}
+			*progress = ReencryptionProgress {
+				state: JobState::Running,
+				started_by: Some(started_by.to_string()),
+				started_at: Some(unix_time_secs()),
+				..Default::default()
+			};
+		}
+
+		let job = Arc::clone(self);
+		let started_by = started_by.to_string();
+		tokio::spawn(async move {
+			let result = job.run(&settings).await;
+			let progress = {
+				let mut progress = job.progress.lock().unwrap();
+				progress.finished_at = Some(unix_time_secs());
+				match &result {
+					Ok(()) => progress.state = JobState::Completed,
+					Err(e) => {
+						progress.state = JobState::Failed;
+						progress.error = Some(e.to_string());
+					},
+				}
+				progress.clone()
+			};
+			let detail = format!(
+				"scanned_records={} reencrypted_records={} skipped_records={}",
+				progress.scanned_records, progress.reencrypted_records, progress.skipped_records
+			);
+			match result {
+				Ok(()) => {
+					info!("Re-encryption completed: {}", detail);
+					log_admin_action(AdminAction {
+						actor: &started_by,
+						action: "reencryption.completed",
+						detail,
+					});
+				},
+				Err(e) => {
+					error!("Re-encryption failed: {} {}", e, detail);
+					log_admin_action(AdminAction {
+						actor: &started_by,
+						action: "reencryption.failed",
+						detail: format!("{} error={}", detail, e),
+					});
+				},
+			}
+		});
+		true
+	}
+
+	async fn run(&self, settings: &ReencryptionSettings) -> Result<(), VssError> {
+		let mut cursor: Option<RecordCursor> = None;
+		loop {
+			let records = self.records.scan_records(cursor.as_ref(), settings.batch_size).await?;
+			let last = match records.last() {
+				Some(last) => RecordCursor::from(last),
+				None => return Ok(()),
+			};
+
+			for record in records {
+				let new_value = self
+					.store
+					.reencrypt_value(
+						&record.user_token,
+						&record.store_id,
+						&record.key,
+						record.value.clone(),
+					)
+					.await?;
+				let replaced = match new_value {
+					Some(new_value) => {
+						Some(self.records.replace_record_value(&record, new_value).await?)
+					},
+					None => None,
+				};
+
+				let mut progress = self.progress.lock().unwrap();
+				progress.scanned_records += 1;
+				match replaced {
+					Some(true) => progress.reencrypted_records += 1,
+					Some(false) => progress.skipped_records += 1,
+					None => {},
+				}
+			}
+
+			cursor = Some(last);
+			tokio::time::sleep(settings.batch_delay).await;
+		}
+	}
+}
@@ -0,0 +1,276 @@
+//! Sampled capture of request envelopes, to reconstruct intermittent client bugs without packet
+//! captures.
+//!
+//! A configurable fraction of [`KvStore`] requests is recorded into a ring buffer, which admins
+//! read through `/admin/capturedRequests`. A captured envelope describes the request and its
+//! outcome: the operation, the headers, sizes, the addressed store, keys and versions, the status
+//! and the error, if any. Values and credentials are never captured; authentication headers are
+//! dropped and user tokens are replaced by a hash prefix.
+//!
+//! [`KvStore`]: api::kv_store::KvStore
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use serde::Serialize;
+
+use api::error::VssError;
+use api::types::{
+	AppendObjectRequest, DeleteObjectRequest, ExportStoreRequest, GetObjectRequest,
+	GetObjectVersionRequest, GetObjectsAtomicRequest, ImportStoreRequest, ListKeyVersionsRequest,
+	ListObjectsRequest, PutObjectRequest,
+};
+
+use crate::admin_service::unix_time_secs;
+use crate::collaborators::STORE_OWNER_HEADER;
+use crate::in_flight::user_hash;
+
+pub(crate) const DEFAULT_CAPTURE_CAPACITY: usize = 1000;
+
+/// Headers which carry credentials and are dropped from captures.
+const DROPPED_HEADERS: [&str; 3] = ["authorization", "proxy-authorization", "cookie"];
+
+/// A request as captured, without values or credentials.
+#[derive(Serialize, Clone, Default)]
+pub(crate) struct CapturedRequest {
+	id: u64,
+	captured_at: u64,
+	operation: &'static str,
+	headers: BTreeMap<String, String>,
+	/// A prefix of the SHA-256 hash of the user token, absent if the request was not authorized.
+	user_hash: Option<String>,
+	store_id: Option<String>,
+	global_version: Option<i64>,
+	objects: Vec<CapturedObject>,
+	request_body_size: Option<usize>,
+	response_body_size: Option<usize>,
+	status_code: Option<u16>,
+	error: Option<String>,
+	duration_ms: u128,
+}
+
+/// An object addressed by a captured request.
+#[derive(Serialize, Clone)]
+pub(crate) struct CapturedObject {
+	key: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	version: Option<i64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	value_size: Option<usize>,
+	#[serde(skip_serializing_if = "std::ops::Not::not")]
+	deleted: bool,
+}
+
+impl CapturedObject {
+	fn new(key: &str) -> Self {
+		Self { key: key.to_string(), version: None, value_size: None, deleted: false }
+	}
+}
+
+/// A request whose addressed objects can be captured without their values.
+pub(crate) trait CapturableRequest {
+	/// The global version the request expects, if any.
+	fn global_version(&self) -> Option<i64> {
+		None
+	}
+
+	/// The objects the request addresses, or for listings the listed key prefix.
+	fn captured_objects(&self) -> Vec<CapturedObject>;
+}
+
+impl CapturableRequest for GetObjectRequest {
+	fn captured_objects(&self) -> Vec<CapturedObject> {
+		vec![CapturedObject::new(&self.key)]
+	}
+}
+
+impl CapturableRequest for GetObjectVersionRequest {
+	fn captured_objects(&self) -> Vec<CapturedObject> {
+		vec![CapturedObject { version: Some(self.version), ..CapturedObject::new(&self.key) }]
+	}
+}
+
+impl CapturableRequest for GetObjectsAtomicRequest {
+	fn captured_objects(&self) -> Vec<CapturedObject> {
+		self.keys.iter().map(|key| CapturedObject::new(key)).collect()
+	}
+}
+
+impl CapturableRequest for PutObjectRequest {
+	fn global_version(&self) -> Option<i64> {
+		self.global_version
+	}
+
+	fn captured_objects(&self) -> Vec<CapturedObject> {
+		let written = self.transaction_items.iter().map(|kv| CapturedObject {
+			version: Some(kv.version),
+			value_size: Some(kv.value.len()),
+			..CapturedObject::new(&kv.key)
+		});
+		let deleted = self.delete_items.iter().map(|kv| CapturedObject {
+			version: Some(kv.version),
+			deleted: true,
+			..CapturedObject::new(&kv.key)
+		});
+		written.chain(deleted).collect()
+	}
+}
+
+impl CapturableRequest for DeleteObjectRequest {
+	fn captured_objects(&self) -> Vec<CapturedObject> {
+		self.key_value
+			.iter()
+			.map(|kv| CapturedObject {
+				version: Some(kv.version),
+				deleted: true,
+				..CapturedObject::new(&kv.key)
+			})
+			.collect()
+	}
+}
+
+impl CapturableRequest for AppendObjectRequest {
+	fn captured_objects(&self) -> Vec<CapturedObject> {
+		vec![CapturedObject {
+			value_size: Some(self.value.len()),
+			..CapturedObject::new(&self.key)
+		}]
+	}
+}
+
+impl CapturableRequest for ListKeyVersionsRequest {
+	fn captured_objects(&self) -> Vec<CapturedObject> {
+		vec![CapturedObject::new(self.key_prefix.as_deref().unwrap_or_default())]
+	}
+}
+
+impl CapturableRequest for ListObjectsRequest {
+	fn captured_objects(&self) -> Vec<CapturedObject> {
+		vec![CapturedObject::new(self.key_prefix.as_deref().unwrap_or_default())]
+	}
+}
+
+// Exports list all keys, as listings of the empty prefix do.
+impl CapturableRequest for ExportStoreRequest {
+	fn captured_objects(&self) -> Vec<CapturedObject> {
+		vec![CapturedObject::new("")]
+	}
+}
+
+// Imports may write any key, so they are captured as the empty prefix along with the archive size.
+impl CapturableRequest for ImportStoreRequest {
+	fn captured_objects(&self) -> Vec<CapturedObject> {
+		vec![CapturedObject { value_size: Some(self.archive.len()), ..CapturedObject::new("") }]
+	}
+}
+
+/// Samples requests and keeps the most recent captures.
+pub(crate) struct RequestCapture {
+	/// The fraction of requests captured, as the bits of an `f64`.
+	sample_rate: AtomicU64,
+	capacity: usize,
+	next_id: AtomicU64,
+	captures: Mutex<VecDeque<CapturedRequest>>,
+}
+
+impl RequestCapture {
+	pub(crate) fn new(sample_rate: f64, capacity: usize) -> Self {
+		Self {
+			sample_rate: AtomicU64::new(sample_rate.to_bits()),
+			capacity,
+			next_id: AtomicU64::new(0),
+			captures: Mutex::new(VecDeque::with_capacity(capacity)),
+		}
+	}
+
+	pub(crate) fn sample_rate(&self) -> f64 {
+		f64::from_bits(self.sample_rate.load(Ordering::Relaxed))
+	}
+
+	/// Changes the fraction of requests captured from now on.
+	pub(crate) fn set_sample_rate(&self, sample_rate: f64) {
+		self.sample_rate.store(sample_rate.to_bits(), Ordering::Relaxed);
+	}
+
+	/// Decides whether to capture a request with the given headers, returning the capture to
+	/// fill in if so. The capture is recorded once it is dropped.
+	pub(crate) fn sample(
+		self: &Arc<Self>, operation: &'static str, headers_map: &HashMap<String, String>,
+	) -> Option<RequestCaptureGuard> {
+		let sample_rate = self.sample_rate();
+		if sample_rate <= 0.0 || rand::random::<f64>() >= sample_rate {
+			return None;
+		}
+		let headers = headers_map
+			.iter()
+			.filter(|(name, _)| !DROPPED_HEADERS.contains(&name.as_str()))
+			.map(|(name, value)| {
+				let value =
+					if name == STORE_OWNER_HEADER { user_hash(value) } else { value.clone() };
+				(name.clone(), value)
+			})
+			.collect();
+		let request = CapturedRequest {
+			id: self.next_id.fetch_add(1, Ordering::Relaxed),
+			captured_at: unix_time_secs(),
+			operation,
+			headers,
+			..Default::default()
+		};
+		Some(RequestCaptureGuard { capture: Arc::clone(self), request, started_at: Instant::now() })
+	}
+
+	/// Returns up to `limit` captures, most recent first.
+	pub(crate) fn list(&self, limit: usize) -> Vec<CapturedRequest> {
+		self.captures.lock().unwrap().iter().rev().take(limit).cloned().collect()
+	}
+
+	fn record(&self, request: CapturedRequest) {
+		let mut captures = self.captures.lock().unwrap();
+		if captures.len() >= self.capacity {
+			captures.pop_front();
+		}
+		captures.push_back(request);
+	}
+}
+
+/// A request being captured, recorded when dropped.
+pub(crate) struct RequestCaptureGuard {
+	capture: Arc<RequestCapture>,
+	request: CapturedRequest,
+	started_at: Instant,
+}
+
+impl RequestCaptureGuard {
+	pub(crate) fn set_user(&mut self, user_token: &str) {
+		self.request.user_hash = Some(user_hash(user_token));
+	}
+
+	pub(crate) fn set_request_body_size(&mut self, size: usize) {
+		self.request.request_body_size = Some(size);
+	}
+
+	pub(crate) fn set_request<T: CapturableRequest>(&mut self, store_id: &str, request: &T) {
+		self.request.store_id = Some(store_id.to_string());
+		self.request.global_version = request.global_version();
+		self.request.objects = request.captured_objects();
+	}
+
+	pub(crate) fn set_error(&mut self, error: &VssError) {
+		self.request.error = Some(error.to_string());
+	}
+
+	pub(crate) fn set_response(&mut self, status_code: u16, body_size: usize) {
+		self.request.status_code = Some(status_code);
+		self.request.response_body_size = Some(body_size);
+	}
+}
+
+impl Drop for RequestCaptureGuard {
+	fn drop(&mut self) {
+		self.request.duration_ms = self.started_at.elapsed().as_millis();
+		self.capture.record(std::mem::take(&mut self.request));
+	}
+}
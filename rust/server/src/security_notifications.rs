@@ -0,0 +1,525 @@
+//! Webhook and email notifications about security relevant events concerning a user's stores.
+//!
+//! Recipients are either registered by the user, by storing a JSON document such as
+//! `{"webhook_url": "https://...", "email": "...", "events": ["new_device"]}` under the configured
+//! preferences key in a store, or by the operator through subscriptions in the server
+//! configuration. Events are raised when a store is accessed from a device not seen before, and
+//! when an admin wipes a store, erases a user or overwrites an object.
+
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+
+use api::error::VssError;
+use api::kv_store::KvStore;
+use api::types::GetObjectRequest;
+use impls::device_registry::DeviceRegistry;
+
+use log::{debug, warn};
+
+use crate::admin_service::unix_time_secs;
+
+pub(crate) const DEFAULT_PREFERENCES_KEY: &str = "security_notification_preferences";
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+const QUEUE_SIZE: usize = 1024;
+/// The number of device fingerprints remembered in memory before the cache is reset, bounding
+/// its memory usage.
+const MAX_CACHED_DEVICES: usize = 100_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SecurityEventKind {
+	NewDevice,
+	StoreWiped,
+	UserErased,
+	ObjectOverwritten,
+	StoreRestored,
+}
+
+impl SecurityEventKind {
+	fn description(&self) -> &'static str {
+		match self {
+			SecurityEventKind::NewDevice => "A store was accessed from a new device",
+			SecurityEventKind::StoreWiped => "A store was wiped by an operator",
+			SecurityEventKind::UserErased => "All data of a user was erased by an operator",
+			SecurityEventKind::ObjectOverwritten => "An object was overwritten by an operator",
+			SecurityEventKind::StoreRestored => {
+				"A store was restored to an earlier state by an operator"
+			},
+		}
+	}
+}
+
+/// Where to deliver notifications about which events.
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct NotificationTarget {
+	/// Receives each event as a JSON `POST` request.
+	pub(crate) webhook_url: Option<String>,
+	/// Receives each event by email, through the configured email webhook.
+	pub(crate) email: Option<String>,
+	/// The events to notify about, or all events if unset.
+	pub(crate) events: Option<Vec<SecurityEventKind>>,
+	/// Whether the target was registered by the user, whose webhook may then only be delivered
+	/// to public addresses.
+	#[serde(skip)]
+	registered_by_user: bool,
+}
+
+impl NotificationTarget {
+	fn wants(&self, kind: SecurityEventKind) -> bool {
+		self.events.as_ref().is_none_or(|events| events.contains(&kind))
+	}
+}
+
+/// A notification target configured by the operator for all stores of a user.
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct SecuritySubscription {
+	pub(crate) user_token: String,
+	#[serde(flatten)]
+	pub(crate) target: NotificationTarget,
+}
+
+pub(crate) struct SecurityNotificationConfig {
+	/// The key under which users store their notification preferences, per store.
+	pub(crate) preferences_key: String,
+	/// Emails are delivered by posting `{"to", "subject", "text"}` to this URL, e.g. a mail
+	/// relay. Email recipients are ignored if unset.
+	pub(crate) email_webhook_url: Option<String>,
+	/// If set, webhooks registered by users must point to one of these hosts. They must point to
+	/// public addresses either way.
+	pub(crate) allowed_webhook_hosts: Option<Vec<String>>,
+	pub(crate) subscriptions: Vec<SecuritySubscription>,
+}
+
+/// A security relevant event concerning a user's store or all of their stores.
+pub(crate) struct SecurityEvent {
+	pub(crate) kind: SecurityEventKind,
+	pub(crate) store_id: Option<String>,
+	pub(crate) detail: String,
+}
+
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+	event: SecurityEventKind,
+	description: &'static str,
+	store_id: Option<&'a str>,
+	detail: &'a str,
+	occurred_at: u64,
+}
+
+#[derive(Serialize)]
+struct EmailPayload<'a> {
+	to: &'a str,
+	subject: &'static str,
+	text: String,
+}
+
+struct Delivery {
+	url: String,
+	body: String,
+	/// Whether the URL was registered by a user, and so may only resolve to public addresses.
+	registered_by_user: bool,
+}
+
+/// Resolves the recipients of security events and delivers notifications in the background.
+pub(crate) struct SecurityNotifier {
+	config: SecurityNotificationConfig,
+	store: Arc<dyn KvStore>,
+	devices: Arc<dyn DeviceRegistry>,
+	/// Hashes of the (user, store, device) combinations recorded since startup, sparing a
+	/// database round trip on every request.
+	known_devices: Mutex<HashSet<[u8; 32]>>,
+	queue: mpsc::Sender<Delivery>,
+}
+
+impl SecurityNotifier {
+	/// Creates the notifier and spawns the task delivering its notifications.
+	///
+	/// User preferences are read through `store`, which should decode values the same way client
+	/// requests are served.
+	pub(crate) fn start(
+		config: SecurityNotificationConfig, store: Arc<dyn KvStore>,
+		devices: Arc<dyn DeviceRegistry>,
+	) -> Arc<Self> {
+		let (queue, deliveries) = mpsc::channel(QUEUE_SIZE);
+		tokio::spawn(deliver_notifications(deliveries));
+		Arc::new(Self { config, store, devices, known_devices: Mutex::new(HashSet::new()), queue })
+	}
+
+	/// Records an access to the store from the device identified by `user_agent`, notifying the
+	/// user if the device was not seen before.
+	pub(crate) async fn observe_access(
+		&self, user_token: &str, store_id: &str, user_agent: Option<&str>,
+	) {
+		let fingerprint: [u8; 32] = Sha256::digest(user_agent.unwrap_or_default()).into();
+		let mut hasher = Sha256::new();
+		hasher.update((user_token.len() as u64).to_be_bytes());
+		hasher.update(user_token.as_bytes());
+		hasher.update((store_id.len() as u64).to_be_bytes());
+		hasher.update(store_id.as_bytes());
+		hasher.update(fingerprint);
+		let cache_key: [u8; 32] = hasher.finalize().into();
+		if self.known_devices.lock().unwrap().contains(&cache_key) {
+			return;
+		}
+
+		let is_new_device =
+			match self.devices.record_device(user_token, store_id, &fingerprint).await {
+				Ok(is_new_device) => is_new_device,
+				Err(e) => {
+					warn!("Failed to record device accessing store: {}", e);
+					return;
+				},
+			};
+		{
+			let mut known_devices = self.known_devices.lock().unwrap();
+			if known_devices.len() >= MAX_CACHED_DEVICES {
+				known_devices.clear();
+			}
+			known_devices.insert(cache_key);
+		}
+
+		if is_new_device {
+			let targets = self.targets(user_token, Some(store_id)).await;
+			let event = SecurityEvent {
+				kind: SecurityEventKind::NewDevice,
+				store_id: Some(store_id.to_string()),
+				detail: format!("user_agent={}", user_agent.unwrap_or("unknown")),
+			};
+			self.dispatch(&event, &targets);
+		}
+	}
+
+	/// Returns who to notify about events concerning the given store, or all stores of the user if
+	/// `store_id` is `None`. Must be called before the store's data is deleted, as user
+	/// preferences are read from the store itself.
+	pub(crate) async fn targets(
+		&self, user_token: &str, store_id: Option<&str>,
+	) -> Vec<NotificationTarget> {
+		let mut targets: Vec<NotificationTarget> = self
+			.config
+			.subscriptions
+			.iter()
+			.filter(|subscription| subscription.user_token == user_token)
+			.map(|subscription| subscription.target.clone())
+			.collect();
+
+		if let Some(store_id) = store_id {
+			let request = GetObjectRequest {
+				store_id: store_id.to_string(),
+				key: self.config.preferences_key.clone(),
+			};
+			match self.store.get(user_token.to_string(), request).await {
+				Ok(response) => {
+					let preferences = response.value.map(|kv| kv.value).unwrap_or_default();
+					match serde_json::from_slice::<NotificationTarget>(&preferences) {
+						Ok(target) => targets.push(self.sanitize_user_target(target)),
+						Err(_) => debug!(
+							"Ignoring invalid security notification preferences in store {}",
+							store_id
+						),
+					}
+				},
+				Err(VssError::NoSuchKeyError(_)) => {},
+				Err(e) => warn!("Failed to look up security notification preferences: {}", e),
+			}
+		}
+		targets
+	}
+
+	/// Drops user-registered webhooks which do not use https, point to a host not allowed or to
+	/// an address which is not public. Host names are only resolved on delivery, when addresses
+	/// which are not public are rejected too.
+	fn sanitize_user_target(&self, mut target: NotificationTarget) -> NotificationTarget {
+		target.registered_by_user = true;
+		if let Some(webhook_url) = target.webhook_url.as_deref() {
+			let allowed = Url::parse(webhook_url).ok().is_some_and(|url| {
+				// IPv6 hosts are enclosed in brackets.
+				let is_public = url.host_str().is_some_and(|host| {
+					let host = host.trim_start_matches('[').trim_end_matches(']');
+					host.parse().map_or(true, is_public_ip)
+				});
+				let is_allowed_host =
+					self.config.allowed_webhook_hosts.as_ref().is_none_or(|hosts| {
+						url.host_str().is_some_and(|host| hosts.iter().any(|h| h == host))
+					});
+				url.scheme() == "https" && is_public && is_allowed_host
+			});
+			if !allowed {
+				debug!("Ignoring disallowed security notification webhook {}", webhook_url);
+				target.webhook_url = None;
+			}
+		}
+		target
+	}
+
+	/// Queues notifications about `event` for all targets interested in it.
+	pub(crate) fn dispatch(&self, event: &SecurityEvent, targets: &[NotificationTarget]) {
+		let payload = WebhookPayload {
+			event: event.kind,
+			description: event.kind.description(),
+			store_id: event.store_id.as_deref(),
+			detail: &event.detail,
+			occurred_at: unix_time_secs(),
+		};
+		// unwrap safety: the payload always serializes successfully.
+		let webhook_body = serde_json::to_string(&payload).unwrap();
+
+		for target in targets.iter().filter(|target| target.wants(event.kind)) {
+			if let Some(url) = target.webhook_url.as_ref() {
+				self.enqueue(Delivery {
+					url: url.clone(),
+					body: webhook_body.clone(),
+					registered_by_user: target.registered_by_user,
+				});
+			}
+			if let (Some(to), Some(url)) =
+				(target.email.as_deref(), self.config.email_webhook_url.as_ref())
+			{
+				let email = EmailPayload {
+					to,
+					subject: "VSS security notification",
+					text: format!(
+						"{}.\n\nStore: {}\nDetails: {}\n",
+						payload.description,
+						payload.store_id.unwrap_or("all stores"),
+						payload.detail
+					),
+				};
+				// unwrap safety: the payload always serializes successfully.
+				let body = serde_json::to_string(&email).unwrap();
+				self.enqueue(Delivery { url: url.clone(), body, registered_by_user: false });
+			}
+		}
+	}
+
+	fn enqueue(&self, delivery: Delivery) {
+		if self.queue.try_send(delivery).is_err() {
+			warn!("Dropping security notification as the delivery queue is full");
+		}
+	}
+}
+
+/// Whether `ip` is reachable on the internet, rather than e.g. a loopback, private or link-local
+/// address of the server's network.
+fn is_public_ip(ip: IpAddr) -> bool {
+	match ip {
+		IpAddr::V4(ip) => {
+			let is_shared = ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64;
+			!(ip.is_loopback()
+				|| ip.is_private()
+				|| ip.is_link_local()
+				|| ip.is_unspecified()
+				|| ip.is_broadcast()
+				|| ip.is_documentation()
+				|| is_shared)
+		},
+		IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+			Some(ip) => is_public_ip(IpAddr::V4(ip)),
+			None => {
+				!(ip.is_loopback()
+					|| ip.is_unspecified()
+					|| ip.is_unique_local()
+					|| ip.is_unicast_link_local())
+			},
+		},
+	}
+}
+
+/// Resolves host names to their public addresses only, failing if they have none, so the webhooks
+/// of users cannot reach the server's network however their host names resolve.
+struct PublicAddressResolver;
+
+impl Resolve for PublicAddressResolver {
+	fn resolve(&self, name: Name) -> Resolving {
+		Box::pin(async move {
+			let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+				.await?
+				.filter(|addr| is_public_ip(addr.ip()))
+				.collect();
+			if addrs.is_empty() {
+				return Err(format!("{} has no public address", name.as_str()).into());
+			}
+			Ok(Box::new(addrs.into_iter()) as Addrs)
+		})
+	}
+}
+
+async fn deliver_notifications(mut deliveries: mpsc::Receiver<Delivery>) {
+	// Redirects are not followed, as they could lead anywhere regardless of the checks above.
+	let builder = || reqwest::Client::builder().timeout(DELIVERY_TIMEOUT).redirect(Policy::none());
+	// Proxies would resolve host names themselves.
+	let user_client = builder().dns_resolver(Arc::new(PublicAddressResolver)).no_proxy().build();
+	let (client, user_client) = match (builder().build(), user_client) {
+		(Ok(client), Ok(user_client)) => (client, user_client),
+		(Err(e), _) | (_, Err(e)) => {
+			warn!("Failed to create security notification HTTP client: {}", e);
+			return;
+		},
+	};
+	while let Some(delivery) = deliveries.recv().await {
+		let client = if delivery.registered_by_user { &user_client } else { &client };
+		let result = client
+			.post(&delivery.url)
+			.header(reqwest::header::CONTENT_TYPE, "application/json")
+			.body(delivery.body)
+			.send()
+			.await
+			.and_then(|response| response.error_for_status());
+		match result {
+			Ok(_) => debug!("Delivered security notification to {}", delivery.url),
+			Err(e) => warn!("Failed to deliver security notification to {}: {}", delivery.url, e),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		NotificationTarget, PublicAddressResolver, SecurityEventKind, SecurityNotificationConfig,
+		SecurityNotifier, SecuritySubscription, DEFAULT_PREFERENCES_KEY,
+	};
+
+	use api::kv_store::KvStore;
+	use api::types::{KeyValue, PutObjectRequest};
+	use bytes::Bytes;
+	use impls::device_registry::DeviceRegistry;
+	use impls::in_memory_store::InMemoryBackend;
+	use reqwest::dns::{Name, Resolve};
+	use sha2::{Digest, Sha256};
+	use std::str::FromStr;
+	use std::sync::Arc;
+
+	fn notifier_over(
+		backend: &Arc<InMemoryBackend>, allowed_webhook_hosts: Option<Vec<String>>,
+		subscriptions: Vec<SecuritySubscription>,
+	) -> Arc<SecurityNotifier> {
+		let config = SecurityNotificationConfig {
+			preferences_key: DEFAULT_PREFERENCES_KEY.to_string(),
+			email_webhook_url: None,
+			allowed_webhook_hosts,
+			subscriptions,
+		};
+		SecurityNotifier::start(config, backend.clone(), backend.clone())
+	}
+
+	fn notifier(allowed_webhook_hosts: Option<Vec<String>>) -> Arc<SecurityNotifier> {
+		notifier_over(&Arc::new(InMemoryBackend::new()), allowed_webhook_hosts, Vec::new())
+	}
+
+	fn sanitized_webhook(notifier: &SecurityNotifier, webhook_url: &str) -> Option<String> {
+		let target = NotificationTarget {
+			webhook_url: Some(webhook_url.to_string()),
+			email: None,
+			events: None,
+			registered_by_user: false,
+		};
+		let target = notifier.sanitize_user_target(target);
+		assert!(target.registered_by_user);
+		target.webhook_url
+	}
+
+	#[tokio::test]
+	async fn drops_user_webhooks_to_non_public_addresses() {
+		let notifier = notifier(None);
+
+		let url = "https://hooks.example.com/vss";
+		assert_eq!(sanitized_webhook(&notifier, url).as_deref(), Some(url));
+		assert_eq!(
+			sanitized_webhook(&notifier, "https://1.1.1.1/").as_deref(),
+			Some("https://1.1.1.1/")
+		);
+		for url in [
+			"http://hooks.example.com/vss",
+			"https://127.0.0.1/",
+			"https://10.0.0.5/",
+			"https://172.16.0.1/",
+			"https://192.168.1.1/",
+			"https://169.254.169.254/latest/meta-data/",
+			"https://100.64.0.1/",
+			"https://0.0.0.0/",
+			"https://[::1]/",
+			"https://[fd00::1]/",
+			"https://[fe80::1]/",
+			"https://[::ffff:10.0.0.5]/",
+			"not a url",
+		] {
+			assert_eq!(sanitized_webhook(&notifier, url), None, "{}", url);
+		}
+	}
+
+	#[tokio::test]
+	async fn drops_user_webhooks_to_hosts_not_allowed() {
+		let notifier = notifier(Some(vec!["hooks.example.com".to_string()]));
+
+		let url = "https://hooks.example.com/vss";
+		assert_eq!(sanitized_webhook(&notifier, url).as_deref(), Some(url));
+		assert_eq!(sanitized_webhook(&notifier, "https://evil.example.com/vss"), None);
+	}
+
+	#[tokio::test]
+	async fn resolves_host_names_to_public_addresses_only() {
+		let resolver = PublicAddressResolver;
+		let localhost = Name::from_str("localhost").unwrap();
+		assert!(resolver.resolve(localhost).await.is_err());
+	}
+
+	#[tokio::test]
+	async fn combines_subscriptions_with_user_preferences() {
+		let backend = Arc::new(InMemoryBackend::new());
+		let subscription = SecuritySubscription {
+			user_token: "user".to_string(),
+			target: NotificationTarget {
+				webhook_url: Some("https://10.0.0.5/operator".to_string()),
+				email: None,
+				events: Some(vec![SecurityEventKind::StoreWiped]),
+				registered_by_user: false,
+			},
+		};
+		let notifier = notifier_over(&backend, None, vec![subscription]);
+		let preferences =
+			r#"{"webhook_url": "https://10.0.0.5/user", "email": "user@example.com"}"#;
+		let request = PutObjectRequest {
+			store_id: "store".to_string(),
+			global_version: None,
+			transaction_items: vec![KeyValue {
+				key: DEFAULT_PREFERENCES_KEY.to_string(),
+				version: 0,
+				value: Bytes::from(preferences),
+			}],
+			delete_items: vec![],
+		};
+		backend.put("user".to_string(), request).await.unwrap();
+
+		let targets = notifier.targets("user", Some("store")).await;
+		assert_eq!(targets.len(), 2);
+		// Operators may notify internal hosts, users may not.
+		assert_eq!(targets[0].webhook_url.as_deref(), Some("https://10.0.0.5/operator"));
+		assert!(!targets[0].wants(SecurityEventKind::NewDevice));
+		assert_eq!(targets[1].webhook_url, None);
+		assert_eq!(targets[1].email.as_deref(), Some("user@example.com"));
+		assert!(targets[1].wants(SecurityEventKind::NewDevice));
+		assert!(notifier.targets("other", Some("store")).await.is_empty());
+	}
+
+	#[tokio::test]
+	async fn records_devices_accessing_stores() {
+		let backend = Arc::new(InMemoryBackend::new());
+		let notifier = notifier_over(&backend, None, Vec::new());
+		notifier.observe_access("user", "store", Some("wallet/1.0")).await;
+
+		let fingerprint = Sha256::digest("wallet/1.0");
+		assert!(!backend.record_device("user", "store", &fingerprint).await.unwrap());
+		let fingerprint = Sha256::digest("wallet/2.0");
+		assert!(backend.record_device("user", "store", &fingerprint).await.unwrap());
+	}
+}
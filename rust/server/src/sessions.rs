@@ -0,0 +1,79 @@
+//! Limits on the number of devices a user may be active from at the same time.
+//!
+//! A session is identified by the client's IP address and user agent, and stays active as long as
+//! it made a request within the configured window. Requests from a new session are rejected while
+//! the user already has as many active sessions as allowed, which defends against credentials
+//! being shared and against several wallet instances writing to the same stores.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+
+use api::error::VssError;
+
+use log::debug;
+
+/// The time after its last request a session is considered inactive, unless configured otherwise.
+pub(crate) const DEFAULT_SESSION_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+/// The number of users whose sessions are tracked before the tracked sessions are reset, bounding
+/// their memory usage.
+const MAX_TRACKED_USERS: usize = 100_000;
+
+/// A session's fingerprint along with the time of its last request.
+type Session = ([u8; 32], Instant);
+
+pub(crate) struct SessionLimitConfig {
+	/// The number of sessions a user may have active at the same time.
+	pub(crate) max_sessions_per_user: usize,
+	/// The time after its last request a session is considered inactive.
+	pub(crate) window: Duration,
+}
+
+/// Tracks the active sessions of users against the configured limit.
+pub(crate) struct SessionLimiter {
+	config: SessionLimitConfig,
+	/// The active sessions of each user.
+	sessions: Mutex<HashMap<String, Vec<Session>>>,
+}
+
+impl SessionLimiter {
+	pub(crate) fn new(config: SessionLimitConfig) -> Self {
+		Self { config, sessions: Mutex::new(HashMap::new()) }
+	}
+
+	/// Records a request of the given user, failing if it starts a session beyond the limit.
+	pub(crate) fn observe_request(
+		&self, user_token: &str, ip: IpAddr, user_agent: Option<&str>,
+	) -> Result<(), VssError> {
+		let mut hasher = Sha256::new();
+		hasher.update(ip.to_string());
+		hasher.update([0]);
+		hasher.update(user_agent.unwrap_or_default());
+		let fingerprint: [u8; 32] = hasher.finalize().into();
+
+		let now = Instant::now();
+		let mut sessions = self.sessions.lock().unwrap();
+		if sessions.len() >= MAX_TRACKED_USERS && !sessions.contains_key(user_token) {
+			sessions.clear();
+		}
+		let user_sessions = sessions.entry(user_token.to_string()).or_default();
+		user_sessions.retain(|(_, last_seen)| now.duration_since(*last_seen) < self.config.window);
+		if let Some(session) = user_sessions.iter_mut().find(|(known, _)| *known == fingerprint) {
+			session.1 = now;
+			return Ok(());
+		}
+		if user_sessions.len() >= self.config.max_sessions_per_user {
+			debug!("Rejected request starting session beyond limit of {}", user_sessions.len());
+			return Err(VssError::AuthError(format!(
+				"Too many concurrent sessions, at most {} are allowed.",
+				self.config.max_sessions_per_user
+			)));
+		}
+		user_sessions.push((fingerprint, now));
+		Ok(())
+	}
+}
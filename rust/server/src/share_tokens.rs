@@ -0,0 +1,278 @@
+//! Read-only share tokens granting access to a single object.
+//!
+//! An authenticated user mints a time-limited token for one of their objects, e.g. to hand a
+//! watch-only descriptor or a static channel backup to a recovery service. The holder presents the
+//! token as bearer credential to `/getSharedObject`, which serves exactly that object and nothing
+//! else. Tokens may be revoked by their owner at any time.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use api::error::VssError;
+use api::kv_store::KvStore;
+use api::types::{GetObjectRequest, GetObjectResponse};
+use impls::share_token_store::{ShareToken, ShareTokenStore};
+
+use log::debug;
+
+use crate::admin_service::unix_time_secs;
+
+/// The validity of a share token unless requested otherwise.
+const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// The upper bound on the validity of a share token.
+const MAX_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// The number of unexpired share tokens a single user may hold at once.
+const MAX_ACTIVE_TOKENS_PER_USER: i64 = 100;
+
+const SHARE_TOKEN_PREFIX: &str = "vss_share_";
+const BEARER_PREFIX: &str = "Bearer ";
+
+#[derive(Deserialize)]
+pub(crate) struct CreateShareTokenRequest {
+	store_id: String,
+	key: String,
+	ttl_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct CreateShareTokenResponse {
+	share_token: String,
+	expires_at: u64,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct RevokeShareTokenRequest {
+	share_token: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct RevokeShareTokenResponse {
+	revoked: bool,
+}
+
+/// Mints, revokes and redeems share tokens.
+pub(crate) struct ShareTokens {
+	store: Arc<dyn KvStore>,
+	tokens: Arc<dyn ShareTokenStore>,
+}
+
+impl ShareTokens {
+	pub(crate) fn new(store: Arc<dyn KvStore>, tokens: Arc<dyn ShareTokenStore>) -> Self {
+		Self { store, tokens }
+	}
+
+	pub(crate) async fn create(
+		&self, user_token: String, request: CreateShareTokenRequest,
+	) -> Result<CreateShareTokenResponse, VssError> {
+		let ttl = request.ttl_secs.map(Duration::from_secs).unwrap_or(DEFAULT_TTL);
+		if ttl.is_zero() || ttl > MAX_TTL {
+			return Err(VssError::InvalidRequestError(format!(
+				"ttl_secs must be between 1 and {}.",
+				MAX_TTL.as_secs()
+			)));
+		}
+		if request.store_id.is_empty() || request.key.is_empty() {
+			return Err(VssError::InvalidRequestError(
+				"store_id and key must not be empty.".to_string(),
+			));
+		}
+
+		let secret: [u8; 32] = rand::random();
+		let share_token = format!("{}{}", SHARE_TOKEN_PREFIX, hex_encode(&secret));
+		let expires_at = unix_time_secs().saturating_add(ttl.as_secs());
+		let token = ShareToken {
+			token_sha256: Sha256::digest(share_token.as_bytes()).into(),
+			user_token,
+			store_id: request.store_id,
+			key: request.key,
+			expires_at,
+		};
+		if !self.tokens.create_share_token(&token, MAX_ACTIVE_TOKENS_PER_USER).await? {
+			return Err(VssError::InvalidRequestError(format!(
+				"At most {} share tokens may be active at once.",
+				MAX_ACTIVE_TOKENS_PER_USER
+			)));
+		}
+		debug!("Created share token for key {} in store {}", token.key, token.store_id);
+		Ok(CreateShareTokenResponse { share_token, expires_at })
+	}
+
+	pub(crate) async fn revoke(
+		&self, user_token: String, request: RevokeShareTokenRequest,
+	) -> Result<RevokeShareTokenResponse, VssError> {
+		let token_sha256: [u8; 32] = Sha256::digest(request.share_token.as_bytes()).into();
+		let revoked = self.tokens.revoke_share_token(&user_token, &token_sha256).await?;
+		Ok(RevokeShareTokenResponse { revoked })
+	}
+
+	/// Serves `request` on behalf of the owner of the share token in the `authorization` header,
+	/// provided the token grants access to exactly the requested object.
+	pub(crate) async fn get_shared_object(
+		&self, authorization: Option<&str>, request: GetObjectRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		let share_token = authorization
+			.and_then(|value| value.strip_prefix(BEARER_PREFIX))
+			.filter(|token| token.starts_with(SHARE_TOKEN_PREFIX))
+			.ok_or_else(|| VssError::AuthError("Missing share token.".to_string()))?;
+		let token_sha256: [u8; 32] = Sha256::digest(share_token.as_bytes()).into();
+		let token =
+			self.tokens.get_share_token(&token_sha256).await?.ok_or_else(|| {
+				VssError::AuthError("Invalid or expired share token.".to_string())
+			})?;
+		if token.store_id != request.store_id || token.key != request.key {
+			return Err(VssError::AuthError(
+				"Share token does not grant access to the requested object.".to_string(),
+			));
+		}
+		self.store.get(token.user_token, request).await
+	}
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+	bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		CreateShareTokenRequest, RevokeShareTokenRequest, ShareTokens, MAX_ACTIVE_TOKENS_PER_USER,
+		SHARE_TOKEN_PREFIX,
+	};
+	use crate::admin_service::unix_time_secs;
+
+	use api::error::VssError;
+	use api::kv_store::KvStore;
+	use api::types::{GetObjectRequest, KeyValue, PutObjectRequest};
+	use bytes::Bytes;
+	use impls::in_memory_store::InMemoryBackend;
+	use impls::share_token_store::{ShareToken, ShareTokenStore};
+	use sha2::{Digest, Sha256};
+	use std::sync::Arc;
+
+	async fn share_tokens() -> (Arc<InMemoryBackend>, ShareTokens) {
+		let backend = Arc::new(InMemoryBackend::new());
+		for key in ["shared", "private"] {
+			let request = PutObjectRequest {
+				store_id: "store".to_string(),
+				global_version: None,
+				transaction_items: vec![KeyValue {
+					key: key.to_string(),
+					version: 0,
+					value: Bytes::from(key),
+				}],
+				delete_items: vec![],
+			};
+			backend.put("owner".to_string(), request).await.unwrap();
+		}
+		let share_tokens = ShareTokens::new(backend.clone(), backend.clone());
+		(backend, share_tokens)
+	}
+
+	fn create_request(key: &str, ttl_secs: Option<u64>) -> CreateShareTokenRequest {
+		CreateShareTokenRequest { store_id: "store".to_string(), key: key.to_string(), ttl_secs }
+	}
+
+	fn get_request(store_id: &str, key: &str) -> GetObjectRequest {
+		GetObjectRequest { store_id: store_id.to_string(), key: key.to_string() }
+	}
+
+	async fn get(
+		share_tokens: &ShareTokens, share_token: &str, request: GetObjectRequest,
+	) -> Result<Bytes, VssError> {
+		let authorization = format!("Bearer {}", share_token);
+		let response = share_tokens.get_shared_object(Some(&authorization), request).await?;
+		Ok(response.value.unwrap().value)
+	}
+
+	#[tokio::test]
+	async fn serves_only_the_shared_object() {
+		let (_, share_tokens) = share_tokens().await;
+		let created =
+			share_tokens.create("owner".to_string(), create_request("shared", None)).await.unwrap();
+		let share_token = created.share_token.as_str();
+
+		let value = get(&share_tokens, share_token, get_request("store", "shared")).await.unwrap();
+		assert_eq!(value, Bytes::from("shared"));
+		for request in [get_request("store", "private"), get_request("other", "shared")] {
+			let result = get(&share_tokens, share_token, request).await;
+			assert!(matches!(result, Err(VssError::AuthError(_))));
+		}
+		let unknown = format!("{}{}", SHARE_TOKEN_PREFIX, "00".repeat(32));
+		let result = get(&share_tokens, &unknown, get_request("store", "shared")).await;
+		assert!(matches!(result, Err(VssError::AuthError(_))));
+		// Other bearer credentials are not share tokens.
+		let result = share_tokens
+			.get_shared_object(Some("Bearer eyJhbGciOi"), get_request("store", "shared"))
+			.await;
+		assert!(matches!(result, Err(VssError::AuthError(_))));
+		let result = share_tokens.get_shared_object(None, get_request("store", "shared")).await;
+		assert!(matches!(result, Err(VssError::AuthError(_))));
+	}
+
+	#[tokio::test]
+	async fn rejects_expired_share_tokens() {
+		let (backend, share_tokens) = share_tokens().await;
+		let share_token = format!("{}{}", SHARE_TOKEN_PREFIX, "11".repeat(32));
+		let token = ShareToken {
+			token_sha256: Sha256::digest(share_token.as_bytes()).into(),
+			user_token: "owner".to_string(),
+			store_id: "store".to_string(),
+			key: "shared".to_string(),
+			expires_at: unix_time_secs() - 1,
+		};
+		assert!(backend.create_share_token(&token, MAX_ACTIVE_TOKENS_PER_USER).await.unwrap());
+
+		let result = get(&share_tokens, &share_token, get_request("store", "shared")).await;
+		assert!(matches!(result, Err(VssError::AuthError(_))));
+	}
+
+	#[tokio::test]
+	async fn rejects_invalid_validities() {
+		let (_, share_tokens) = share_tokens().await;
+		for ttl_secs in [0, 31 * 24 * 60 * 60] {
+			let request = create_request("shared", Some(ttl_secs));
+			let result = share_tokens.create("owner".to_string(), request).await;
+			assert!(matches!(result, Err(VssError::InvalidRequestError(_))));
+		}
+		let created_at = unix_time_secs();
+		let created = share_tokens
+			.create("owner".to_string(), create_request("shared", Some(60)))
+			.await
+			.unwrap();
+		assert!((created_at + 60..=unix_time_secs() + 60).contains(&created.expires_at));
+	}
+
+	#[tokio::test]
+	async fn limits_the_active_share_tokens_per_user() {
+		let (_, share_tokens) = share_tokens().await;
+		for _ in 0..MAX_ACTIVE_TOKENS_PER_USER {
+			share_tokens.create("owner".to_string(), create_request("shared", None)).await.unwrap();
+		}
+		let result = share_tokens.create("owner".to_string(), create_request("shared", None)).await;
+		assert!(matches!(result, Err(VssError::InvalidRequestError(_))));
+		// The limit applies per user.
+		share_tokens.create("other".to_string(), create_request("shared", None)).await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn only_owners_revoke_their_share_tokens() {
+		let (_, share_tokens) = share_tokens().await;
+		let created =
+			share_tokens.create("owner".to_string(), create_request("shared", None)).await.unwrap();
+		let revoke = || RevokeShareTokenRequest { share_token: created.share_token.clone() };
+
+		let response = share_tokens.revoke("other".to_string(), revoke()).await.unwrap();
+		assert!(!response.revoked);
+		assert!(get(&share_tokens, &created.share_token, get_request("store", "shared"))
+			.await
+			.is_ok());
+
+		let response = share_tokens.revoke("owner".to_string(), revoke()).await.unwrap();
+		assert!(response.revoked);
+		let result = get(&share_tokens, &created.share_token, get_request("store", "shared")).await;
+		assert!(matches!(result, Err(VssError::AuthError(_))));
+	}
+}
@@ -0,0 +1,124 @@
+//! Soft deletion, keeping deleted objects restorable for a while.
+//!
+//! Wallet users occasionally delete their state by accident, e.g. by resetting an app, and would
+//! otherwise have no recourse. With soft deletion enabled, the backend moves deleted objects into
+//! tombstones holding their last version, from which `/vss/restoreKey` restores them one key at a
+//! time. Tombstones are purged once they are older than the configured retention.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use api::auth::AccessVerb;
+use api::error::VssError;
+use impls::audit_store::AuditedKey;
+use impls::tombstone_store::TombstoneStore;
+
+use log::{info, warn};
+
+use crate::admin_service::unix_time_secs;
+use crate::vss_service::StoreScopedRequest;
+
+/// How long tombstones are kept, unless configured otherwise.
+pub(crate) const DEFAULT_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// How often expired tombstones are purged, unless configured otherwise.
+pub(crate) const DEFAULT_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The number of tombstones purged per query.
+const PURGE_BATCH_SIZE: i64 = 1000;
+
+pub(crate) struct SoftDeletionConfig {
+	/// How long tombstones are kept before they are purged.
+	pub(crate) retention: Duration,
+	/// How often expired tombstones are purged.
+	pub(crate) purge_interval: Duration,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct RestoreKeyRequest {
+	store_id: String,
+	key: String,
+}
+
+// Restoring writes the object back.
+impl StoreScopedRequest for RestoreKeyRequest {
+	const VERB: AccessVerb = AccessVerb::Write;
+
+	fn store_id(&self) -> &str {
+		&self.store_id
+	}
+
+	fn keys(&self) -> Vec<&str> {
+		vec![&self.key]
+	}
+
+	fn adds_values(&self) -> bool {
+		true
+	}
+
+	fn audited_keys(&self) -> Option<Vec<AuditedKey>> {
+		Some(vec![AuditedKey { key: self.key.clone(), version: -1, deleted: false }])
+	}
+}
+
+#[derive(Serialize)]
+pub(crate) struct RestoreKeyResponse {
+	/// The version of the restored object.
+	version: i64,
+}
+
+/// Restores soft-deleted objects and purges expired tombstones.
+pub(crate) struct SoftDeletion {
+	config: SoftDeletionConfig,
+	tombstones: Arc<dyn TombstoneStore>,
+}
+
+impl SoftDeletion {
+	/// Spawns the task purging expired tombstones on the current runtime.
+	pub(crate) fn start(
+		config: SoftDeletionConfig, tombstones: Arc<dyn TombstoneStore>,
+	) -> Arc<Self> {
+		let soft_deletion = Arc::new(Self { config, tombstones });
+		let purger = Arc::clone(&soft_deletion);
+		tokio::spawn(async move {
+			let mut interval = tokio::time::interval(purger.config.purge_interval);
+			loop {
+				interval.tick().await;
+				purger.purge_expired_tombstones().await;
+			}
+		});
+		soft_deletion
+	}
+
+	pub(crate) async fn restore(
+		&self, user_token: String, request: RestoreKeyRequest,
+	) -> Result<RestoreKeyResponse, VssError> {
+		let version =
+			self.tombstones.restore_key(&user_token, &request.store_id, &request.key).await?;
+		info!("Restored a deleted object in store {}", request.store_id);
+		Ok(RestoreKeyResponse { version })
+	}
+
+	async fn purge_expired_tombstones(&self) {
+		let deleted_before = unix_time_secs().saturating_sub(self.config.retention.as_secs());
+		let mut purged = 0;
+		loop {
+			match self.tombstones.purge_tombstones(deleted_before, PURGE_BATCH_SIZE).await {
+				Ok(count) => {
+					purged += count;
+					if (count as i64) < PURGE_BATCH_SIZE {
+						break;
+					}
+				},
+				Err(e) => {
+					warn!("Failed to purge expired tombstones: {}", e);
+					break;
+				},
+			}
+		}
+		if purged > 0 {
+			info!("Purged {} expired tombstone(s)", purged);
+		}
+	}
+}
@@ -0,0 +1,203 @@
+//! Exports whole stores, for users moving their data to another VSS provider.
+//!
+//! `/vss/exportStore` reads all objects of a store, page by page, along with its global version,
+//! and streams them as length-delimited `KeyValue` records, or as newline-delimited JSON for JSON
+//! clients, so exports need no more memory than a page of objects. The first record holds the
+//! global version as the version of the key `global_version`, so that the export can be imported
+//! as is. The global version is read again once all objects are streamed, and if a write changed it
+//! meanwhile the response is aborted, so exports of stores written with global versioning either
+//! reflect a single snapshot or are incomplete.
+
+use std::sync::Arc;
+
+use api::error::VssError;
+use api::kv_store::{KvStore, GLOBAL_VERSION_KEY};
+use api::types::{ExportStoreRequest, GetObjectRequest, KeyValue, ListObjectsRequest};
+use bytes::Bytes;
+use futures_util::stream;
+use http_body_util::{BodyExt, StreamBody};
+use hyper::body::Frame;
+use prost::Message;
+
+use crate::util::json_messages::ToJson;
+use crate::vss_service::{BodyFormat, ResponseBody, VssBody};
+
+/// The `Content-Type` of protobuf exports.
+const PROTOBUF_EXPORT_CONTENT_TYPE: &str = "application/octet-stream";
+/// The `Content-Type` of JSON exports.
+const JSON_EXPORT_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// An export of a store, whose objects are read as its body is streamed.
+pub(crate) struct StoreExport {
+	store: Arc<dyn KvStore>,
+	user_token: String,
+	store_id: String,
+	global_version: i64,
+}
+
+/// How far an export has been streamed.
+enum ExportProgress {
+	/// The global version is to be streamed next.
+	Started,
+	/// The page of objects with the given token is to be streamed next.
+	Listing(Option<String>),
+	/// All objects have been streamed.
+	Done,
+}
+
+impl StoreExport {
+	async fn next_chunk(
+		&self, progress: ExportProgress, format: BodyFormat,
+	) -> Result<Option<(Bytes, ExportProgress)>, VssError> {
+		match progress {
+			ExportProgress::Started => {
+				let global_version = KeyValue {
+					key: GLOBAL_VERSION_KEY.to_string(),
+					version: self.global_version,
+					value: Bytes::new(),
+				};
+				let chunk = encode_records(std::iter::once(global_version), format);
+				Ok(Some((chunk, ExportProgress::Listing(None))))
+			},
+			ExportProgress::Listing(page_token) => {
+				let list_request = ListObjectsRequest {
+					store_id: self.store_id.clone(),
+					key_prefix: None,
+					page_size: None,
+					page_token,
+				};
+				let page = self.store.list_objects(self.user_token.clone(), list_request).await?;
+				let next = match page.next_page_token.filter(|token| !token.is_empty()) {
+					Some(page_token) => ExportProgress::Listing(Some(page_token)),
+					None => {
+						if global_version(&*self.store, &self.user_token, &self.store_id).await?
+							!= self.global_version
+						{
+							return Err(VssError::ConflictError(
+								"The store was written to while exporting it, please retry."
+									.to_string(),
+							));
+						}
+						ExportProgress::Done
+					},
+				};
+				Ok(Some((encode_records(page.objects.into_iter(), format), next)))
+			},
+			ExportProgress::Done => Ok(None),
+		}
+	}
+}
+
+fn encode_records(records: impl Iterator<Item = KeyValue>, format: BodyFormat) -> Bytes {
+	let mut chunk = Vec::new();
+	for record in records {
+		match format {
+			// unwrap safety: encoding into a vector always has enough capacity.
+			BodyFormat::Protobuf => record.encode_length_delimited(&mut chunk).unwrap(),
+			BodyFormat::Json => {
+				chunk.extend(record.to_json());
+				chunk.push(b'\n');
+			},
+		}
+	}
+	chunk.into()
+}
+
+impl ResponseBody for StoreExport {
+	fn into_body(self, format: BodyFormat) -> VssBody {
+		let export = Arc::new(self);
+		let chunks = stream::try_unfold(ExportProgress::Started, move |progress| {
+			let export = Arc::clone(&export);
+			async move { export.next_chunk(progress, format).await }
+		});
+		StreamBody::new(stream::TryStreamExt::map_ok(chunks, Frame::data)).boxed_unsync()
+	}
+
+	fn content_type(format: BodyFormat) -> Option<&'static str> {
+		match format {
+			BodyFormat::Protobuf => Some(PROTOBUF_EXPORT_CONTENT_TYPE),
+			BodyFormat::Json => Some(JSON_EXPORT_CONTENT_TYPE),
+		}
+	}
+}
+
+async fn global_version(
+	store: &dyn KvStore, user_token: &str, store_id: &str,
+) -> Result<i64, VssError> {
+	let get_request =
+		GetObjectRequest { store_id: store_id.to_string(), key: GLOBAL_VERSION_KEY.into() };
+	let response = store.get(user_token.to_string(), get_request).await?;
+	// unwrap safety: get request always return a value when global_version is queried.
+	Ok(response.value.unwrap().version)
+}
+
+/// Reads the global version of a store, returning its export.
+pub(crate) async fn handle_export_store_request(
+	store: Arc<dyn KvStore>, user_token: String, request: ExportStoreRequest,
+) -> Result<StoreExport, VssError> {
+	let global_version = global_version(&*store, &user_token, &request.store_id).await?;
+	Ok(StoreExport { store, user_token, store_id: request.store_id, global_version })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::handle_export_store_request;
+	use crate::vss_service::{BodyFormat, ResponseBody};
+
+	use api::kv_store::{KvStore, GLOBAL_VERSION_KEY};
+	use api::types::{ExportStoreRequest, KeyValue, PutObjectRequest};
+	use bytes::{Buf, Bytes};
+	use http_body_util::BodyExt;
+	use impls::in_memory_store::InMemoryBackend;
+	use prost::Message;
+	use std::sync::Arc;
+
+	async fn put(store: &InMemoryBackend, key: &str, global_version: i64) {
+		let request = PutObjectRequest {
+			store_id: "store".to_string(),
+			global_version: Some(global_version),
+			transaction_items: vec![KeyValue {
+				key: key.to_string(),
+				version: 0,
+				value: Bytes::from_static(b"value"),
+			}],
+			delete_items: vec![],
+		};
+		store.put("user".to_string(), request).await.unwrap();
+	}
+
+	fn export_request() -> ExportStoreRequest {
+		ExportStoreRequest { store_id: "store".to_string() }
+	}
+
+	#[tokio::test]
+	async fn streams_the_global_version_and_all_objects() {
+		let store = Arc::new(InMemoryBackend::new());
+		put(&store, "a", 0).await;
+		put(&store, "b", 1).await;
+
+		let export =
+			handle_export_store_request(store, "user".to_string(), export_request()).await.unwrap();
+		let mut body = export.into_body(BodyFormat::Protobuf).collect().await.unwrap().to_bytes();
+		let mut records = Vec::new();
+		while body.has_remaining() {
+			records.push(KeyValue::decode_length_delimited(&mut body).unwrap());
+		}
+		let keys: Vec<(&str, i64)> =
+			records.iter().map(|kv| (kv.key.as_str(), kv.version)).collect();
+		assert_eq!(keys, vec![(GLOBAL_VERSION_KEY, 2), ("a", 1), ("b", 1)]);
+	}
+
+	#[tokio::test]
+	async fn aborts_exports_of_stores_written_meanwhile() {
+		let store = Arc::new(InMemoryBackend::new());
+		put(&store, "a", 0).await;
+
+		let export =
+			handle_export_store_request(store.clone(), "user".to_string(), export_request())
+				.await
+				.unwrap();
+		put(&store, "b", 1).await;
+		assert!(export.into_body(BodyFormat::Json).collect().await.is_err());
+	}
+}
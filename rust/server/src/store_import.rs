@@ -0,0 +1,136 @@
+//! Imports whole stores exported by `/vss/exportStore`, for users moving their data from another
+//! VSS provider and for disaster recovery drills.
+//!
+//! `/vss/importStore` decodes the archive up front, so malformed archives are rejected before
+//! anything is written, and then writes its objects in transactions bounded in item count and
+//! size. Each transaction is conditional on the global version of the store, so a concurrent write
+//! stops the import with a conflict rather than being interleaved with it. Unless existing objects
+//! are to be overwritten, the import fails if any archived key already exists, and each object is
+//! only written if it still does not.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use api::error::VssError;
+use api::kv_store::{KvStore, GLOBAL_VERSION_KEY};
+use api::types::{
+	GetObjectRequest, ImportStoreRequest, ImportStoreResponse, KeyValue, ListKeyVersionsRequest,
+	PutObjectRequest,
+};
+use impls::postgres_store::MAX_PUT_REQUEST_ITEM_COUNT;
+use prost::Message;
+
+use crate::fixtures::MAX_PUT_BYTES;
+
+use log::debug;
+
+/// Writes the objects of an archive into the store.
+pub(crate) async fn handle_import_store_request(
+	store: Arc<dyn KvStore>, user_token: String, request: ImportStoreRequest,
+) -> Result<ImportStoreResponse, VssError> {
+	let objects = decode_archive(&request.archive)?;
+	if !request.overwrite {
+		let existing =
+			count_existing_keys(&*store, &user_token, &request.store_id, &objects).await?;
+		if existing > 0 {
+			return Err(VssError::ConflictError(format!(
+				"{} of the imported keys already exist in store {}.",
+				existing, request.store_id
+			)));
+		}
+	}
+	// Existing objects are overwritten regardless of their version, others only written if they
+	// still do not exist.
+	let version = if request.overwrite { -1 } else { 0 };
+
+	let get_request =
+		GetObjectRequest { store_id: request.store_id.clone(), key: GLOBAL_VERSION_KEY.into() };
+	// unwrap safety: get request always return a value when global_version is queried.
+	let mut global_version =
+		store.get(user_token.clone(), get_request).await?.value.unwrap().version;
+
+	let mut imported_objects = 0;
+	let mut batch = Vec::new();
+	let mut batch_size = 0;
+	let mut objects = objects.into_iter().peekable();
+	while let Some(object) = objects.next() {
+		batch_size += object.value.len();
+		batch.push(KeyValue { version, ..object });
+		let is_full = objects.peek().is_none_or(|next| {
+			batch.len() >= MAX_PUT_REQUEST_ITEM_COUNT
+				|| batch_size + next.value.len() > MAX_PUT_BYTES
+		});
+		if is_full {
+			let put_request = PutObjectRequest {
+				store_id: request.store_id.clone(),
+				global_version: Some(global_version),
+				transaction_items: std::mem::take(&mut batch),
+				delete_items: vec![],
+			};
+			let count = put_request.transaction_items.len() as i64;
+			store.put(user_token.clone(), put_request).await.inspect_err(|_| {
+				debug!(
+					"Import into store {} stopped after {} objects",
+					request.store_id, imported_objects
+				)
+			})?;
+			imported_objects += count;
+			global_version += 1;
+			batch_size = 0;
+		}
+	}
+	Ok(ImportStoreResponse { imported_objects })
+}
+
+/// Decodes the objects of an archive, which must start with the global version record.
+fn decode_archive(mut archive: &[u8]) -> Result<Vec<KeyValue>, VssError> {
+	let malformed = |e: prost::DecodeError| {
+		VssError::InvalidRequestError(format!("The archive is malformed: {}", e))
+	};
+	if archive.is_empty() {
+		return Err(VssError::InvalidRequestError("The archive is empty.".to_string()));
+	}
+	let header = KeyValue::decode_length_delimited(&mut archive).map_err(malformed)?;
+	if header.key != GLOBAL_VERSION_KEY {
+		return Err(VssError::InvalidRequestError(
+			"The archive does not start with the global version.".to_string(),
+		));
+	}
+	let mut objects = Vec::new();
+	let mut keys = HashSet::new();
+	while !archive.is_empty() {
+		let object = KeyValue::decode_length_delimited(&mut archive).map_err(malformed)?;
+		if object.key == GLOBAL_VERSION_KEY || !keys.insert(object.key.clone()) {
+			return Err(VssError::InvalidRequestError(format!(
+				"The archive holds the key {} more than once.",
+				object.key
+			)));
+		}
+		objects.push(object);
+	}
+	Ok(objects)
+}
+
+/// Counts the archived objects whose keys already exist in the store.
+async fn count_existing_keys(
+	store: &dyn KvStore, user_token: &str, store_id: &str, objects: &[KeyValue],
+) -> Result<usize, VssError> {
+	let imported: HashSet<&str> = objects.iter().map(|kv| kv.key.as_str()).collect();
+	let mut existing = 0;
+	let mut page_token = None;
+	loop {
+		let list_request = ListKeyVersionsRequest {
+			store_id: store_id.to_string(),
+			key_prefix: None,
+			page_size: None,
+			page_token,
+		};
+		let page = store.list_key_versions(user_token.to_string(), list_request).await?;
+		existing +=
+			page.key_versions.iter().filter(|kv| imported.contains(kv.key.as_str())).count();
+		page_token = page.next_page_token.filter(|token| !token.is_empty());
+		if page_token.is_none() {
+			return Ok(existing);
+		}
+	}
+}
@@ -0,0 +1,174 @@
+//! Rules on the store ids clients may use, keeping the stores of each user under control.
+//!
+//! Store ids can be required to follow a format, the number of stores a user may create can be
+//! limited, and operators can reserve namespaces, i.e. store id prefixes such as `alby-hub/`, for
+//! callers whose credentials make given claims.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use api::auth::{AccessPolicy, AccessRequest, AccessVerb};
+use api::error::VssError;
+use async_trait::async_trait;
+use impls::usage_store::UsageStore;
+
+/// The length store ids are limited to, unless configured otherwise.
+pub(crate) const DEFAULT_MAX_STORE_ID_LENGTH: usize = 256;
+
+/// The number of users whose stores are cached before the cache is reset, bounding its memory
+/// usage.
+const MAX_CACHED_USERS: usize = 100_000;
+
+/// A store id prefix reserved for callers making the given claims.
+pub(crate) struct ReservedNamespace {
+	pub(crate) prefix: String,
+	/// The claims, with their exact values, the caller's credentials must make to access stores
+	/// in the namespace.
+	pub(crate) claims: HashMap<String, String>,
+}
+
+pub(crate) struct StoreNamespaceConfig {
+	/// The maximum length of store ids, in bytes.
+	pub(crate) max_store_id_length: usize,
+	/// Whether store ids are limited to ASCII letters, digits and `-`, `_`, `.`, `:` and `/`.
+	pub(crate) restrict_store_id_characters: bool,
+	/// The number of stores a user may keep objects in.
+	pub(crate) max_stores_per_user: Option<usize>,
+	pub(crate) reserved_namespaces: Vec<ReservedNamespace>,
+}
+
+/// An [`AccessPolicy`] enforcing the [`StoreNamespaceConfig`].
+pub(crate) struct StoreNamespaces {
+	config: StoreNamespaceConfig,
+	/// Lists users' stores, if the number of stores per user is limited.
+	usage: Arc<dyn UsageStore>,
+	/// The stores users are known to keep objects in, so writes to known stores need no lookup.
+	/// Deleted stores stay known until the cache is reset, so a user may exceed their limit by the
+	/// stores they deleted in the meantime.
+	known_stores: Mutex<HashMap<String, HashSet<String>>>,
+}
+
+impl StoreNamespaces {
+	/// Creates the policy, looking up users' stores in `usage` if their number is limited.
+	pub(crate) fn new(config: StoreNamespaceConfig, usage: Arc<dyn UsageStore>) -> Self {
+		Self { config, usage, known_stores: Mutex::new(HashMap::new()) }
+	}
+
+	fn check_format(&self, store_id: &str) -> Result<(), VssError> {
+		if store_id.is_empty() {
+			return Err(VssError::InvalidRequestError("Store id must not be empty.".to_string()));
+		}
+		if store_id.len() > self.config.max_store_id_length {
+			return Err(VssError::InvalidRequestError(format!(
+				"Store id must be at most {} bytes long.",
+				self.config.max_store_id_length
+			)));
+		}
+		let is_allowed =
+			|c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':' | '/');
+		if self.config.restrict_store_id_characters && !store_id.chars().all(is_allowed) {
+			return Err(VssError::InvalidRequestError(
+				"Store id may only contain ASCII letters, digits and any of -_.:/".to_string(),
+			));
+		}
+		Ok(())
+	}
+
+	fn check_reserved(&self, request: &AccessRequest<'_>) -> Result<(), VssError> {
+		let caller_claims = &request.caller.claims;
+		for namespace in &self.config.reserved_namespaces {
+			if !request.store_id.starts_with(namespace.prefix.as_str()) {
+				continue;
+			}
+			let is_permitted =
+				namespace.claims.iter().all(|(name, value)| caller_claims.get(name) == Some(value));
+			if !is_permitted {
+				return Err(VssError::AuthError(format!(
+					"Store namespace {} is reserved.",
+					namespace.prefix
+				)));
+			}
+		}
+		Ok(())
+	}
+
+	/// Returns the number of stores `owner` keeps objects in if `store_id` is not one of them, or
+	/// `None` if it is.
+	async fn new_store_count(
+		&self, owner: &str, store_id: &str,
+	) -> Result<Option<usize>, VssError> {
+		let is_known = |known_stores: &HashMap<String, HashSet<String>>| {
+			known_stores.get(owner).is_some_and(|stores| stores.contains(store_id))
+		};
+		if is_known(&self.known_stores.lock().unwrap()) {
+			return Ok(None);
+		}
+
+		let store_ids = self.usage.list_store_ids(owner).await?;
+		let is_new = !store_ids.iter().any(|id| id == store_id);
+		let store_count = store_ids.len();
+		let mut known_stores = self.known_stores.lock().unwrap();
+		if known_stores.len() >= MAX_CACHED_USERS && !known_stores.contains_key(owner) {
+			known_stores.clear();
+		}
+		known_stores.entry(owner.to_string()).or_default().extend(store_ids);
+		Ok(is_new.then_some(store_count))
+	}
+
+	/// Fails if the write would create a store beyond the owner's limit.
+	async fn check_store_count(&self, owner: &str, store_id: &str) -> Result<(), VssError> {
+		let max_stores = match self.config.max_stores_per_user {
+			Some(max_stores) => max_stores,
+			None => return Ok(()),
+		};
+		match self.new_store_count(owner, store_id).await? {
+			Some(store_count) if store_count >= max_stores => Err(VssError::InvalidRequestError(
+				format!("Store limit of {} stores reached.", max_stores),
+			)),
+			_ => Ok(()),
+		}
+	}
+
+	/// Checks a write the operator makes to a store of `owner`, e.g. through the admin API. The
+	/// operator makes no claims, so it may write to existing stores in reserved namespaces but not
+	/// create new ones there.
+	pub(crate) async fn check_operator_write(
+		&self, owner: &str, store_id: &str,
+	) -> Result<(), VssError> {
+		self.check_format(store_id)?;
+		let reserved = (self.config.reserved_namespaces.iter())
+			.find(|namespace| store_id.starts_with(namespace.prefix.as_str()));
+		if reserved.is_none() && self.config.max_stores_per_user.is_none() {
+			return Ok(());
+		}
+		let store_count = match self.new_store_count(owner, store_id).await? {
+			Some(store_count) => store_count,
+			None => return Ok(()),
+		};
+		if let Some(namespace) = reserved {
+			return Err(VssError::AuthError(format!(
+				"Store namespace {} is reserved.",
+				namespace.prefix
+			)));
+		}
+		match self.config.max_stores_per_user {
+			Some(max_stores) if store_count >= max_stores => Err(VssError::InvalidRequestError(
+				format!("Store limit of {} stores reached.", max_stores),
+			)),
+			_ => Ok(()),
+		}
+	}
+}
+
+#[async_trait]
+impl AccessPolicy for StoreNamespaces {
+	async fn check(&self, request: &AccessRequest<'_>) -> Result<(), VssError> {
+		self.check_format(request.store_id)?;
+		self.check_reserved(request)?;
+		// Only writes create stores.
+		if request.verb == AccessVerb::Write {
+			self.check_store_count(request.owner_user_token, request.store_id).await?;
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,171 @@
+//! Initialization of new stores from operator-defined templates.
+//!
+//! Wallet onboarding flows typically write a number of boilerplate objects to a fresh store. An
+//! operator may instead define these as a named template, which a client applies in a single
+//! request to `/initializeStore`. All objects of the template are created atomically, and only if
+//! none of them exists yet, so initializing a store twice fails with a conflict.
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use api::auth::AccessVerb;
+use api::error::VssError;
+use api::kv_store::{KvStore, GLOBAL_VERSION_KEY, INITIAL_RECORD_VERSION};
+use api::types::{KeyValue, PutObjectRequest};
+use impls::audit_store::AuditedKey;
+use impls::postgres_store::MAX_PUT_REQUEST_ITEM_COUNT;
+
+use log::debug;
+
+use crate::vss_service::StoreScopedRequest;
+
+/// A named set of objects to create in a new store.
+pub(crate) struct StoreTemplate {
+	pub(crate) name: String,
+	pub(crate) objects: Vec<(String, Bytes)>,
+}
+
+impl StoreTemplate {
+	/// Checks that the template can be applied in a single put.
+	pub(crate) fn validate(&self) -> Result<(), String> {
+		if self.name.is_empty() {
+			return Err("Store template names must not be empty".to_string());
+		}
+		if self.objects.is_empty() || self.objects.len() > MAX_PUT_REQUEST_ITEM_COUNT {
+			return Err(format!(
+				"Store template {} must contain between 1 and {} objects",
+				self.name, MAX_PUT_REQUEST_ITEM_COUNT
+			));
+		}
+		for (i, (key, _)) in self.objects.iter().enumerate() {
+			if key.is_empty() || key == GLOBAL_VERSION_KEY {
+				return Err(format!("Store template {} contains invalid key {:?}", self.name, key));
+			}
+			if self.objects[..i].iter().any(|(other, _)| other == key) {
+				return Err(format!("Store template {} contains duplicate key {}", self.name, key));
+			}
+		}
+		Ok(())
+	}
+}
+
+#[derive(Deserialize)]
+pub(crate) struct InitializeStoreRequest {
+	store_id: String,
+	template: String,
+}
+
+/// An [`InitializeStoreRequest`] with its template looked up, so that the objects it creates are
+/// checked like those of any other write.
+pub(crate) struct TemplateApplication {
+	store_id: String,
+	template: String,
+	objects: Vec<(String, Bytes)>,
+}
+
+impl StoreScopedRequest for TemplateApplication {
+	const VERB: AccessVerb = AccessVerb::Write;
+
+	fn store_id(&self) -> &str {
+		&self.store_id
+	}
+
+	fn keys(&self) -> Vec<&str> {
+		self.objects.iter().map(|(key, _)| key.as_str()).collect()
+	}
+
+	fn adds_values(&self) -> bool {
+		true
+	}
+
+	fn audited_keys(&self) -> Option<Vec<AuditedKey>> {
+		Some(
+			(self.objects.iter())
+				.map(|(key, _)| AuditedKey { key: key.clone(), version: 0, deleted: false })
+				.collect(),
+		)
+	}
+}
+
+#[derive(Serialize)]
+pub(crate) struct InitializedObject {
+	key: String,
+	version: i64,
+}
+
+#[derive(Serialize)]
+pub(crate) struct InitializeStoreResponse {
+	objects: Vec<InitializedObject>,
+}
+
+/// Applies the configured store templates.
+pub(crate) struct StoreTemplates {
+	store: Arc<dyn KvStore>,
+	templates: Vec<StoreTemplate>,
+}
+
+impl StoreTemplates {
+	pub(crate) fn new(store: Arc<dyn KvStore>, templates: Vec<StoreTemplate>) -> Self {
+		Self { store, templates }
+	}
+
+	/// Looks up the template of `request`.
+	pub(crate) fn resolve(
+		&self, request: InitializeStoreRequest,
+	) -> Result<TemplateApplication, VssError> {
+		let template = match self.templates.iter().find(|t| t.name == request.template) {
+			Some(template) => template,
+			None => {
+				return Err(VssError::InvalidRequestError(format!(
+					"Unknown store template: {}",
+					request.template
+				)))
+			},
+		};
+		Ok(TemplateApplication {
+			store_id: request.store_id,
+			template: template.name.clone(),
+			objects: template.objects.clone(),
+		})
+	}
+
+	/// Creates the objects of a template in the store of `user_token`. Templates are defined by the
+	/// operator, so their values are written without the checks applied to clients' values.
+	pub(crate) async fn initialize(
+		&self, user_token: String, application: TemplateApplication,
+	) -> Result<InitializeStoreResponse, VssError> {
+		// A version of 0 creates each object only if it does not exist yet.
+		let transaction_items = application
+			.objects
+			.iter()
+			.map(|(key, value)| KeyValue { key: key.clone(), version: 0, value: value.clone() })
+			.collect();
+		let put_request = PutObjectRequest {
+			store_id: application.store_id.clone(),
+			global_version: None,
+			transaction_items,
+			delete_items: vec![],
+		};
+		self.store.put(user_token, put_request).await.map_err(|e| match e {
+			VssError::ConflictError(_) => VssError::ConflictError(format!(
+				"Store {} already contains objects of template {}.",
+				application.store_id, application.template
+			)),
+			e => e,
+		})?;
+		debug!("Initialized store {} from template {}", application.store_id, application.template);
+
+		Ok(InitializeStoreResponse {
+			objects: application
+				.objects
+				.iter()
+				.map(|(key, _)| InitializedObject {
+					key: key.clone(),
+					version: INITIAL_RECORD_VERSION as i64,
+				})
+				.collect(),
+		})
+	}
+}
@@ -0,0 +1,254 @@
+//! Synthetic probe traffic exercising the server end to end.
+//!
+//! Health checks only tell that the backend answers, and the metrics of user traffic go quiet
+//! rather than red when clients cannot reach the server at all. The probe therefore periodically
+//! writes, reads back and deletes an object in a dedicated store, over the API listener and with
+//! credentials accepted by the configured authorizer, just as a client would. The outcome and
+//! latency of each step are exposed as metrics, and failures are logged.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use prost::Message;
+use rand::RngCore;
+
+use api::types::{
+	DeleteObjectRequest, EncryptionMetadata, GetObjectRequest, GetObjectResponse, KeyValue,
+	PutObjectRequest, Storable,
+};
+
+use crate::admin_service::unix_time_secs;
+
+use log::{info, warn};
+
+/// How often the probe runs, unless configured otherwise.
+pub(crate) const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+/// The time each request of the probe may take, unless configured otherwise.
+pub(crate) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+/// The store probed, unless configured otherwise.
+pub(crate) const DEFAULT_STORE_ID: &str = "vss-probe";
+/// The key written, read and deleted by the probe.
+const PROBE_KEY: &str = "probe";
+/// The cipher format the probe's value claims, which is random data rather than ciphertext.
+const PROBE_CIPHER_FORMAT: &str = "vss-probe";
+
+/// How the probe authenticates its requests.
+#[derive(Clone)]
+pub(crate) enum ProbeCredentials {
+	/// A fixed `Authorization` header, e.g. a long-lived JWT or API key.
+	Authorization(String),
+	/// A secp256k1 secret key signing each request as `SignatureValidatingAuthorizer` expects.
+	#[cfg(feature = "sigs")]
+	SigningKey(secp256k1::SecretKey),
+	/// No credentials, for servers which do not authenticate requests.
+	None,
+}
+
+pub(crate) struct ProbeConfig {
+	/// How often the probe runs.
+	pub(crate) interval: Duration,
+	/// The base URL of the API the probe sends requests to, e.g. `http://127.0.0.1:8080/vss`.
+	pub(crate) url: String,
+	/// The store the probe writes to.
+	pub(crate) store_id: String,
+	/// The time each request may take.
+	pub(crate) timeout: Duration,
+	pub(crate) credentials: ProbeCredentials,
+}
+
+/// A step of the probe.
+#[derive(Clone, Copy)]
+pub(crate) enum ProbeStep {
+	Put,
+	Get,
+	Delete,
+}
+
+impl ProbeStep {
+	pub(crate) fn name(&self) -> &'static str {
+		match self {
+			ProbeStep::Put => "put",
+			ProbeStep::Get => "get",
+			ProbeStep::Delete => "delete",
+		}
+	}
+}
+
+/// The outcome of the last run of the probe, along with the totals since startup.
+#[derive(Clone, Default)]
+pub(crate) struct ProbeResult {
+	/// Whether the last run completed all steps.
+	pub(crate) success: bool,
+	/// The time the last run started at, in seconds since the Unix epoch.
+	pub(crate) ran_at: u64,
+	/// The time taken by each step of the last run, up to the one failing, if any.
+	pub(crate) step_durations: Vec<(ProbeStep, Duration)>,
+	/// The number of runs since startup.
+	pub(crate) runs: u64,
+	/// The number of failed runs since startup.
+	pub(crate) failures: u64,
+}
+
+/// Periodically exercises the API with a write, read and delete of a probe object.
+pub(crate) struct SyntheticProbe {
+	config: ProbeConfig,
+	client: reqwest::Client,
+	/// The result of the last run, `None` until the first run completed.
+	last_result: Mutex<Option<ProbeResult>>,
+}
+
+impl SyntheticProbe {
+	/// Starts running the probe on the current runtime.
+	pub(crate) fn start(config: ProbeConfig) -> Result<Arc<Self>, String> {
+		let client = reqwest::Client::builder()
+			.timeout(config.timeout)
+			.build()
+			.map_err(|e| format!("Failed to create probe HTTP client: {}", e))?;
+		let probe = Arc::new(Self { config, client, last_result: Mutex::new(None) });
+		let runner = Arc::clone(&probe);
+		tokio::spawn(async move {
+			// The first run waits for an interval, by which the listener is serving requests.
+			let first_run = tokio::time::Instant::now() + runner.config.interval;
+			let mut interval = tokio::time::interval_at(first_run, runner.config.interval);
+			loop {
+				interval.tick().await;
+				runner.run().await;
+			}
+		});
+		Ok(probe)
+	}
+
+	/// The result of the last run, once the probe ran.
+	pub(crate) fn last_result(&self) -> Option<ProbeResult> {
+		self.last_result.lock().unwrap().clone()
+	}
+
+	async fn run(&self) {
+		let ran_at = unix_time_secs();
+		let mut step_durations = Vec::new();
+		let outcome = self.run_steps(&mut step_durations).await;
+
+		let mut last_result = self.last_result.lock().unwrap();
+		let previous = last_result.take().unwrap_or_default();
+		// The probe is assumed to have succeeded before its first run, so that failures on startup
+		// are logged.
+		let succeeded_before = previous.runs == 0 || previous.success;
+		match (&outcome, succeeded_before) {
+			(Err(e), true) => warn!("Synthetic probe failed: {}", e),
+			(Ok(()), false) => info!("Synthetic probe succeeded again"),
+			_ => {},
+		}
+		*last_result = Some(ProbeResult {
+			success: outcome.is_ok(),
+			ran_at,
+			step_durations,
+			runs: previous.runs + 1,
+			failures: previous.failures + outcome.is_err() as u64,
+		});
+	}
+
+	/// Runs each step in turn, recording the time taken by each, up to the first failing.
+	async fn run_steps(
+		&self, step_durations: &mut Vec<(ProbeStep, Duration)>,
+	) -> Result<(), String> {
+		let mut data = vec![0u8; 32];
+		rand::rng().fill_bytes(&mut data);
+		// The random data is framed like a value encrypted client-side, as some servers require.
+		let value = Storable {
+			data: Bytes::from(data),
+			encryption_metadata: Some(EncryptionMetadata {
+				cipher_format: PROBE_CIPHER_FORMAT.to_string(),
+				nonce: Bytes::from_static(&[0; 12]),
+				tag: Bytes::new(),
+			}),
+		};
+		let value = Bytes::from(value.encode_to_vec());
+
+		let put = PutObjectRequest {
+			store_id: self.config.store_id.clone(),
+			global_version: None,
+			transaction_items: vec![KeyValue {
+				key: PROBE_KEY.to_string(),
+				version: -1,
+				value: value.clone(),
+			}],
+			delete_items: Vec::new(),
+		};
+		self.timed(ProbeStep::Put, "/putObjects", put.encode_to_vec(), step_durations).await?;
+
+		let get =
+			GetObjectRequest { store_id: self.config.store_id.clone(), key: PROBE_KEY.to_string() };
+		let response =
+			self.timed(ProbeStep::Get, "/getObject", get.encode_to_vec(), step_durations).await?;
+		let response = GetObjectResponse::decode(response)
+			.map_err(|e| format!("Failed to decode the get response: {}", e))?;
+		if response.value.map(|kv| kv.value) != Some(value) {
+			return Err("The value read back differs from the one written".to_string());
+		}
+
+		let delete = DeleteObjectRequest {
+			store_id: self.config.store_id.clone(),
+			key_value: Some(KeyValue {
+				key: PROBE_KEY.to_string(),
+				version: -1,
+				value: Bytes::new(),
+			}),
+		};
+		self.timed(ProbeStep::Delete, "/deleteObject", delete.encode_to_vec(), step_durations)
+			.await?;
+		Ok(())
+	}
+
+	/// Sends a request for `step`, recording the time taken.
+	async fn timed(
+		&self, step: ProbeStep, path: &str, body: Vec<u8>,
+		step_durations: &mut Vec<(ProbeStep, Duration)>,
+	) -> Result<Bytes, String> {
+		let started_at = Instant::now();
+		let result = self.send(path, body).await;
+		step_durations.push((step, started_at.elapsed()));
+		result.map_err(|e| format!("The {} step failed: {}", step.name(), e))
+	}
+
+	async fn send(&self, path: &str, body: Vec<u8>) -> Result<Bytes, String> {
+		let mut request = self.client.post(format!("{}{}", self.config.url, path)).body(body);
+		if let Some(authorization) = self.authorization() {
+			request = request.header(reqwest::header::AUTHORIZATION, authorization);
+		}
+		let response = request.send().await.map_err(|e| e.to_string())?;
+		let status = response.status();
+		if !status.is_success() {
+			return Err(format!("HTTP status {}", status));
+		}
+		response.bytes().await.map_err(|e| e.to_string())
+	}
+
+	fn authorization(&self) -> Option<String> {
+		match &self.config.credentials {
+			ProbeCredentials::Authorization(authorization) => Some(authorization.clone()),
+			#[cfg(feature = "sigs")]
+			ProbeCredentials::SigningKey(secret_key) => Some(sign(secret_key, unix_time_secs())),
+			ProbeCredentials::None => None,
+		}
+	}
+}
+
+/// Creates the `Authorization` header proving knowledge of `secret_key` at `time`, as verified by
+/// `SignatureValidatingAuthorizer`.
+#[cfg(feature = "sigs")]
+fn sign(secret_key: &secp256k1::SecretKey, time: u64) -> String {
+	use crate::in_flight::hex_encode;
+	use auth_impls::signature::SIGNING_CONSTANT;
+	use sha2::{Digest, Sha256};
+
+	let public_key = secret_key.public_key(secp256k1::SECP256K1).serialize();
+	let time = time.to_string();
+	let mut hash = Sha256::new();
+	hash.update(SIGNING_CONSTANT);
+	hash.update(public_key);
+	hash.update(time.as_bytes());
+	let message = secp256k1::Message::from_digest(hash.finalize().into());
+	let signature = secp256k1::SECP256K1.sign_ecdsa(message, secret_key).serialize_compact();
+	format!("{}{}{}", hex_encode(&public_key), hex_encode(&signature), time)
+}
@@ -0,0 +1,129 @@
+//! Safeguards keeping telemetry exporters from affecting the request path.
+//!
+//! Spans and error events are handed to exporters which send them from background threads, but
+//! buffer them until they are sent. Were Sentry or the Datadog agent unreachable or slow, those
+//! buffers would grow for as long as the outage lasts. Each exporter is therefore given an
+//! [`ExportBudget`] of spans or events per second, beyond which they are dropped, and the Datadog
+//! agent is probed so that no spans are buffered for it while it refuses connections. Dropped
+//! spans and events are counted and exposed as metrics.
+
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tracing::subscriber::Interest;
+use tracing::Metadata;
+use tracing_subscriber::layer::{Context, Filter};
+
+use log::{info, warn};
+
+/// How often the reachability of an exporter's endpoint is probed.
+const PROBE_INTERVAL: Duration = Duration::from_secs(5);
+/// The time a probe waits for a connection to the endpoint.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The number of spans or events an exporter accepts per second, counting those it drops.
+pub(crate) struct ExportBudget {
+	exporter: &'static str,
+	max_per_second: u64,
+	started_at: Instant,
+	/// The second since `started_at` the admitted items are counted for.
+	window: AtomicU64,
+	admitted: AtomicU64,
+	dropped: AtomicU64,
+	/// Whether the exporter's endpoint is probed, and whether it accepted the last connection.
+	probed: AtomicBool,
+	reachable: AtomicBool,
+}
+
+impl ExportBudget {
+	pub(crate) fn new(exporter: &'static str, max_per_second: u64) -> Arc<Self> {
+		Arc::new(Self {
+			exporter,
+			max_per_second,
+			started_at: Instant::now(),
+			window: AtomicU64::new(0),
+			admitted: AtomicU64::new(0),
+			dropped: AtomicU64::new(0),
+			probed: AtomicBool::new(false),
+			reachable: AtomicBool::new(true),
+		})
+	}
+
+	/// Whether another item may be exported, counting it as dropped if not.
+	pub(crate) fn admit(&self) -> bool {
+		if self.reachable.load(Ordering::Relaxed) {
+			let second = self.started_at.elapsed().as_secs();
+			let window = self.window.load(Ordering::Relaxed);
+			if window != second
+				&& self
+					.window
+					.compare_exchange(window, second, Ordering::Relaxed, Ordering::Relaxed)
+					.is_ok()
+			{
+				self.admitted.store(0, Ordering::Relaxed);
+			}
+			if self.admitted.fetch_add(1, Ordering::Relaxed) < self.max_per_second {
+				return true;
+			}
+		}
+		self.dropped.fetch_add(1, Ordering::Relaxed);
+		false
+	}
+
+	pub(crate) fn exporter(&self) -> &'static str {
+		self.exporter
+	}
+
+	/// The number of items dropped since startup.
+	pub(crate) fn dropped(&self) -> u64 {
+		self.dropped.load(Ordering::Relaxed)
+	}
+
+	/// Whether the exporter's endpoint accepts connections, if it is probed.
+	pub(crate) fn reachability(&self) -> Option<bool> {
+		self.probed.load(Ordering::Relaxed).then(|| self.reachable.load(Ordering::Relaxed))
+	}
+
+	/// Probes `address` from a background thread, dropping all items while it refuses
+	/// connections.
+	pub(crate) fn probe_reachability(self: &Arc<Self>, address: String) {
+		self.probed.store(true, Ordering::Relaxed);
+		let budget = Arc::clone(self);
+		let probe = move || loop {
+			std::thread::sleep(PROBE_INTERVAL);
+			let reachable = address
+				.to_socket_addrs()
+				.ok()
+				.and_then(|mut addrs| addrs.next())
+				.is_some_and(|addr| TcpStream::connect_timeout(&addr, PROBE_TIMEOUT).is_ok());
+			if budget.reachable.swap(reachable, Ordering::Relaxed) != reachable {
+				if reachable {
+					info!("{} is reachable at {} again", budget.exporter, address);
+				} else {
+					warn!("{} is unreachable at {}, dropping telemetry", budget.exporter, address);
+				}
+			}
+		};
+		if let Err(e) = std::thread::Builder::new().name("telemetry-probe".into()).spawn(probe) {
+			warn!("Failed to start probing {}: {}", self.exporter, e);
+		}
+	}
+}
+
+/// A per-layer filter passing spans to a tracing layer within an [`ExportBudget`].
+///
+/// Events are always passed on, as layers only attach them to spans they already hold.
+pub(crate) struct BudgetFilter(pub(crate) Arc<ExportBudget>);
+
+impl<S> Filter<S> for BudgetFilter {
+	fn enabled(&self, meta: &Metadata<'_>, _cx: &Context<'_, S>) -> bool {
+		!meta.is_span() || self.0.admit()
+	}
+
+	fn callsite_enabled(&self, _meta: &'static Metadata<'static>) -> Interest {
+		// The budget changes over time, so it must be asked for every span.
+		Interest::sometimes()
+	}
+}
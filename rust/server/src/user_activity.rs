@@ -0,0 +1,166 @@
+//! Per-user request counts backing the admin API's capacity planning queries.
+//!
+//! Requests are counted in memory and added to hourly counts in the database once a minute, so
+//! that counting costs a single query per minute regardless of traffic. Hourly counts are deleted
+//! once they are older than [`REQUEST_COUNT_RETENTION`].
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use api::error::VssError;
+use api::kv_store::KvStore;
+use api::types::{
+	AppendObjectRequest, AppendObjectResponse, DeleteObjectRequest, DeleteObjectResponse,
+	GetObjectRequest, GetObjectResponse, GetObjectVersionRequest, GetObjectsAtomicRequest,
+	GetObjectsAtomicResponse, ListKeyVersionsRequest, ListKeyVersionsResponse, ListObjectsRequest,
+	ListObjectsResponse, PutObjectRequest, PutObjectResponse,
+};
+use impls::usage_store::UsageStore;
+
+use log::{debug, warn};
+
+use crate::admin_service::unix_time_secs;
+
+/// How long hourly request counts are kept.
+pub(crate) const REQUEST_COUNT_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+/// Expired request counts are purged every this many flushes.
+const FLUSHES_PER_PURGE: u64 = 60;
+
+/// Counts requests per user and periodically persists the counts.
+pub(crate) struct RequestCounter {
+	counts: Mutex<HashMap<String, u64>>,
+	usage: Arc<dyn UsageStore>,
+}
+
+impl RequestCounter {
+	/// Creates the counter and spawns the task persisting its counts.
+	pub(crate) fn start(usage: Arc<dyn UsageStore>) -> Arc<Self> {
+		let counter = Arc::new(Self { counts: Mutex::new(HashMap::new()), usage });
+		let flusher = Arc::clone(&counter);
+		tokio::spawn(async move {
+			let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+			// The first tick completes immediately.
+			interval.tick().await;
+			let mut flushes = 0u64;
+			loop {
+				interval.tick().await;
+				flusher.flush().await;
+				flushes += 1;
+				if flushes % FLUSHES_PER_PURGE == 0 {
+					flusher.purge().await;
+				}
+			}
+		});
+		counter
+	}
+
+	fn record(&self, user_token: &str) {
+		let mut counts = self.counts.lock().unwrap();
+		match counts.get_mut(user_token) {
+			Some(count) => *count += 1,
+			None => {
+				counts.insert(user_token.to_string(), 1);
+			},
+		}
+	}
+
+	async fn flush(&self) {
+		let counts: Vec<(String, u64)> =
+			std::mem::take(&mut *self.counts.lock().unwrap()).into_iter().collect();
+		if let Err(e) = self.usage.record_requests(&counts, unix_time_secs()).await {
+			warn!("Failed to record request counts of {} users: {}", counts.len(), e);
+		}
+	}
+
+	async fn purge(&self) {
+		let before = unix_time_secs().saturating_sub(REQUEST_COUNT_RETENTION.as_secs());
+		match self.usage.purge_request_counts(before).await {
+			Ok(purged) => debug!("Purged {} expired hourly request counts", purged),
+			Err(e) => warn!("Failed to purge expired request counts: {}", e),
+		}
+	}
+}
+
+/// A [`KvStore`] decorator counting requests per user with a [`RequestCounter`].
+pub(crate) struct RequestCountingKvStore {
+	inner: Arc<dyn KvStore>,
+	counter: Arc<RequestCounter>,
+}
+
+impl RequestCountingKvStore {
+	pub(crate) fn new(inner: Arc<dyn KvStore>, counter: Arc<RequestCounter>) -> Self {
+		Self { inner, counter }
+	}
+}
+
+#[async_trait]
+impl KvStore for RequestCountingKvStore {
+	async fn get(
+		&self, user_token: String, request: GetObjectRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		self.counter.record(&user_token);
+		self.inner.get(user_token, request).await
+	}
+
+	async fn put(
+		&self, user_token: String, request: PutObjectRequest,
+	) -> Result<PutObjectResponse, VssError> {
+		self.counter.record(&user_token);
+		self.inner.put(user_token, request).await
+	}
+
+	async fn delete(
+		&self, user_token: String, request: DeleteObjectRequest,
+	) -> Result<DeleteObjectResponse, VssError> {
+		self.counter.record(&user_token);
+		self.inner.delete(user_token, request).await
+	}
+
+	async fn list_key_versions(
+		&self, user_token: String, request: ListKeyVersionsRequest,
+	) -> Result<ListKeyVersionsResponse, VssError> {
+		self.counter.record(&user_token);
+		self.inner.list_key_versions(user_token, request).await
+	}
+
+	async fn list_objects(
+		&self, user_token: String, request: ListObjectsRequest,
+	) -> Result<ListObjectsResponse, VssError> {
+		self.counter.record(&user_token);
+		self.inner.list_objects(user_token, request).await
+	}
+
+	async fn get_objects_atomic(
+		&self, user_token: String, request: GetObjectsAtomicRequest,
+	) -> Result<GetObjectsAtomicResponse, VssError> {
+		self.counter.record(&user_token);
+		self.inner.get_objects_atomic(user_token, request).await
+	}
+
+	async fn get_object_version(
+		&self, user_token: String, request: GetObjectVersionRequest,
+	) -> Result<GetObjectResponse, VssError> {
+		self.counter.record(&user_token);
+		self.inner.get_object_version(user_token, request).await
+	}
+
+	async fn append(
+		&self, user_token: String, request: AppendObjectRequest,
+	) -> Result<AppendObjectResponse, VssError> {
+		self.counter.record(&user_token);
+		self.inner.append(user_token, request).await
+	}
+
+	async fn delete_store(&self, user_token: String, store_id: String) -> Result<u64, VssError> {
+		self.inner.delete_store(user_token, store_id).await
+	}
+
+	async fn delete_user(&self, user_token: String) -> Result<u64, VssError> {
+		self.inner.delete_user(user_token).await
+	}
+}
@@ -1,12 +1,85 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bytes::Bytes;
 use log::LevelFilter;
 use serde::Deserialize;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use api::auth::AccessVerb;
+use auth_impls::rules::{AccessRule, RuleBasedPolicy, RuleEffect};
+#[cfg(feature = "dynamodb")]
+use impls::backend_factory::DynamoDbBackendConfig;
+#[cfg(feature = "object-store")]
+use impls::backend_factory::OffloadingConfig;
+#[cfg(feature = "redis")]
+use impls::backend_factory::RedisBackendConfig;
+use impls::backend_factory::{BackendConfig, PostgresBackendConfig};
+use impls::key_provider::{
+	decrypt_age_key_file, KeyProvider, LocalMasterKey, StaticKeyProvider, WrappedDataKeys,
+};
+use impls::merging_store::{builtin_merge_strategy, MergeStrategy};
+use impls::postgres_store::{validate_endpoint, PoolConfig, DEFAULT_TRANSIENT_RETRIES};
+use impls::value_compression::{ValueCompression, DEFAULT_ZSTD_LEVEL};
+
+use crate::admin_service::AdminCredential;
+use crate::analytics::AnalyticsConfig;
+use crate::backup_monitor::{BackupMonitorConfig, DEFAULT_CHECK_INTERVAL, DEFAULT_MAX_ARCHIVE_AGE};
+use crate::cert_expiry::{self, CertificateExpiryConfig};
+use crate::change_stream::{
+	ChangeStreamConfig, DEFAULT_BUFFERED_EVENTS, DEFAULT_MAX_SUBSCRIPTIONS_PER_USER,
+};
+use crate::client_compat::ClientCompatibility;
+use crate::delayed_deletions::{
+	DelayedDeletionConfig, DEFAULT_CHECK_INTERVAL as DEFAULT_DELETION_CHECK_INTERVAL,
+	DEFAULT_DELETION_DELAY,
+};
+use crate::load_shedding::{
+	LoadSheddingConfig, DEFAULT_DEGRADED_FAILURE_RATIO, DEFAULT_MIN_REQUESTS,
+	DEFAULT_UNHEALTHY_FAILURE_RATIO, DEFAULT_WINDOW,
+};
+use crate::maintenance::{
+	MaintenanceConfig, DEFAULT_ORPHAN_CLEANUP_INTERVAL, DEFAULT_SHARE_TOKEN_REAPING_INTERVAL,
+	DEFAULT_USAGE_RECOMPUTATION_INTERVAL, DEFAULT_VACUUM_HINT_INTERVAL,
+};
+use crate::mutation_audit::{AuditConfig, AuditSink};
+use crate::nostr_notifications::{
+	self, NostrNotificationConfig, DEFAULT_EVENT_KIND, DEFAULT_MIN_INTERVAL,
+	DEFAULT_REGISTRATION_KEY,
+};
+use crate::quota::{QuotaConfig, QuotaLimits, DEFAULT_SOFT_LIMIT_RATIO};
+use crate::rate_limit::RateLimitConfig;
+use crate::request_capture::DEFAULT_CAPTURE_CAPACITY;
+use crate::security_notifications::{
+	SecurityNotificationConfig, SecuritySubscription, DEFAULT_PREFERENCES_KEY,
+};
+use crate::sessions::{SessionLimitConfig, DEFAULT_SESSION_WINDOW};
+use crate::soft_deletion::{
+	SoftDeletionConfig, DEFAULT_PURGE_INTERVAL as DEFAULT_TOMBSTONE_PURGE_INTERVAL,
+	DEFAULT_RETENTION as DEFAULT_TOMBSTONE_RETENTION,
+};
+use crate::store_namespaces::{
+	ReservedNamespace, StoreNamespaceConfig, DEFAULT_MAX_STORE_ID_LENGTH,
+};
+use crate::store_templates::StoreTemplate;
+use crate::synthetic_probe::{self, ProbeConfig, ProbeCredentials};
+use crate::util::logger::LogFormat;
+use crate::util::tls::ServerTlsConfig;
+use crate::util::{env_config, percent_encode, secrets};
+use crate::vss_service::{BASE_PATH_PREFIX, DEFAULT_BODY_READ_TIMEOUT};
 
 const BIND_ADDR_VAR: &str = "VSS_BIND_ADDRESS";
+const HOST_VAR: &str = "VSS_HOST";
+const PORT_VAR: &str = "VSS_PORT";
 const MAX_REQUEST_BODY_SIZE_VAR: &str = "VSS_MAX_REQUEST_BODY_SIZE";
 const LOG_FILE_VAR: &str = "VSS_LOG_FILE";
 const LOG_LEVEL_VAR: &str = "VSS_LOG_LEVEL";
+const LOG_FORMAT_VAR: &str = "VSS_LOG_FORMAT";
 const JWT_RSA_PEM_VAR: &str = "VSS_JWT_RSA_PEM";
+const STORAGE_BACKEND_VAR: &str = "VSS_STORAGE_BACKEND";
+const PSQL_DSN_VAR: &str = "VSS_PG_DSN";
 const PSQL_USER_VAR: &str = "VSS_PSQL_USERNAME";
 const PSQL_PASS_VAR: &str = "VSS_PSQL_PASSWORD";
 const PSQL_ADDR_VAR: &str = "VSS_PSQL_ADDRESS";
@@ -14,37 +87,157 @@ const PSQL_DB_VAR: &str = "VSS_PSQL_DEFAULT_DB";
 const PSQL_VSS_DB_VAR: &str = "VSS_PSQL_VSS_DB";
 const PSQL_TLS_VAR: &str = "VSS_PSQL_TLS";
 const PSQL_CERT_PEM_VAR: &str = "VSS_PSQL_CRT_PEM";
+const PSQL_VALUE_COMPRESSION_VAR: &str = "VSS_PSQL_VALUE_COMPRESSION";
+const PSQL_MAX_VALUE_SIZE_VAR: &str = "VSS_PSQL_MAX_VALUE_SIZE";
+const PSQL_NOTIFY_CHANGES_VAR: &str = "VSS_PSQL_NOTIFY_CHANGES";
+const PSQL_KEY_HISTORY_VERSIONS_VAR: &str = "VSS_PSQL_KEY_HISTORY_VERSIONS";
+const PSQL_MIGRATE_ON_STARTUP_VAR: &str = "VSS_PSQL_MIGRATE_ON_STARTUP";
+const PSQL_REPLICA_ADDR_VAR: &str = "VSS_PSQL_REPLICA_ADDRESS";
+const PSQL_REQUIRE_PRIMARY_READS_VAR: &str = "VSS_PSQL_REQUIRE_PRIMARY_READS";
+const PSQL_TRANSIENT_RETRIES_VAR: &str = "VSS_PSQL_TRANSIENT_RETRIES";
+const PSQL_READ_USER_VAR: &str = "VSS_PSQL_READ_USERNAME";
+const PSQL_READ_PASS_VAR: &str = "VSS_PSQL_READ_PASSWORD";
+const PSQL_ADMIN_USER_VAR: &str = "VSS_PSQL_ADMIN_USERNAME";
+const PSQL_ADMIN_PASS_VAR: &str = "VSS_PSQL_ADMIN_PASSWORD";
+#[cfg(feature = "redis")]
+const REDIS_URL_VAR: &str = "VSS_REDIS_URL";
+#[cfg(feature = "dynamodb")]
+const DYNAMODB_TABLE_VAR: &str = "VSS_DYNAMODB_TABLE";
+const OBJECT_STORE_URL_VAR: &str = "VSS_OBJECT_STORE_URL";
 const SENTRY_DSN_VAR: &str = "SENTRY_DSN";
 const SENTRY_ENVIRONMENT_VAR: &str = "SENTRY_ENVIRONMENT";
 const SENTRY_SAMPLE_RATE_VAR: &str = "SENTRY_SAMPLE_RATE";
+const SENTRY_MAX_EVENTS_PER_SECOND_VAR: &str = "SENTRY_MAX_EVENTS_PER_SECOND";
 const DD_TRACE_ENABLED_VAR: &str = "DD_TRACE_ENABLED";
 const DD_SERVICE_VAR: &str = "DD_SERVICE";
 const DD_ENV_VAR: &str = "DD_ENV";
 const DD_VERSION_VAR: &str = "DD_VERSION";
 const DD_AGENT_HOST_VAR: &str = "DD_AGENT_HOST";
 const DD_TRACE_AGENT_PORT_VAR: &str = "DD_TRACE_AGENT_PORT";
+const DD_TRACE_MAX_SPANS_PER_SECOND_VAR: &str = "DD_TRACE_MAX_SPANS_PER_SECOND";
+const ADMIN_BIND_ADDR_VAR: &str = "VSS_ADMIN_BIND_ADDRESS";
+const GRPC_BIND_ADDR_VAR: &str = "VSS_GRPC_BIND_ADDRESS";
+const TLS_CERT_PATH_VAR: &str = "VSS_TLS_CERT_PATH";
+const TLS_KEY_PATH_VAR: &str = "VSS_TLS_KEY_PATH";
+const TLS_CLIENT_CA_PATH_VAR: &str = "VSS_TLS_CLIENT_CA_PATH";
+const ANALYTICS_ENABLED_VAR: &str = "VSS_ANALYTICS_ENABLED";
+const NOSTR_SECRET_KEY_VAR: &str = "VSS_NOSTR_SECRET_KEY";
+const L402_ROOT_KEY_VAR: &str = "VSS_L402_ROOT_KEY";
+const L402_LND_MACAROON_VAR: &str = "VSS_L402_LND_MACAROON";
+const ENCRYPTION_KEYS_VAR: &str = "VSS_ENCRYPTION_KEYS";
+const ENCRYPTION_KEY_FILE_VAR: &str = "VSS_ENCRYPTION_KEY_FILE";
+const ENCRYPTION_CURRENT_KEY_VERSION_VAR: &str = "VSS_ENCRYPTION_CURRENT_KEY_VERSION";
+pub(crate) const ENCRYPTION_MASTER_KEY_VAR: &str = "VSS_ENCRYPTION_MASTER_KEY";
+const ENCRYPTION_VAULT_TOKEN_VAR: &str = "VSS_ENCRYPTION_VAULT_TOKEN";
+const ENCRYPTION_GCP_ACCESS_TOKEN_VAR: &str = "VSS_ENCRYPTION_GCP_ACCESS_TOKEN";
+const ENCRYPTION_AGE_IDENTITY_FILE_VAR: &str = "VSS_ENCRYPTION_AGE_IDENTITY_FILE";
+const AWS_ACCESS_KEY_ID_VAR: &str = "AWS_ACCESS_KEY_ID";
+const AWS_SECRET_ACCESS_KEY_VAR: &str = "AWS_SECRET_ACCESS_KEY";
+const AWS_SESSION_TOKEN_VAR: &str = "AWS_SESSION_TOKEN";
+const AWS_REGION_VAR: &str = "AWS_REGION";
+const PROBE_AUTHORIZATION_VAR: &str = "VSS_PROBE_AUTHORIZATION";
+const PROBE_SIGNING_KEY_VAR: &str = "VSS_PROBE_SIGNING_KEY";
+
+const DEFAULT_ADMIN_APPROVAL_DELAY_SECS: u64 = 24 * 60 * 60;
+const DEFAULT_ANALYTICS_REPORT_INTERVAL_SECS: u64 = 24 * 60 * 60;
+const DEFAULT_JWKS_REFRESH_INTERVAL_SECS: u64 = 60 * 60;
+const DEFAULT_ANALYTICS_EPSILON: f64 = 1.0;
+const DEFAULT_RESPONSE_WRITE_TIMEOUT: Duration = Duration::from_secs(60);
+const DEFAULT_SENTRY_MAX_EVENTS_PER_SECOND: u64 = 10;
+const DEFAULT_MAX_SPANS_PER_SECOND: u64 = 2000;
+const DEFAULT_VAULT_TRANSIT_MOUNT: &str = "transit";
 
 // The structure of the toml config file. Any settings specified therein can be overriden by the corresponding
 // environment variable.
 #[derive(Deserialize, Default)]
 struct TomlConfig {
 	server_config: Option<ServerConfig>,
+	tls: Option<ServerTlsTomlConfig>,
 	log_config: Option<LogConfig>,
 	jwt_auth_config: Option<JwtAuthConfig>,
+	api_key_auth_config: Option<ApiKeyAuthTomlConfig>,
+	nostr_auth_config: Option<NostrAuthTomlConfig>,
+	l402_config: Option<L402TomlConfig>,
+	oidc_auth_config: Option<OidcAuthTomlConfig>,
+	auth_chain_config: Option<AuthChainTomlConfig>,
+	storage_config: Option<StorageConfig>,
 	postgresql_config: Option<PostgreSQLConfig>,
+	#[cfg(feature = "redis")]
+	redis_config: Option<RedisTomlConfig>,
+	#[cfg(feature = "dynamodb")]
+	dynamodb_config: Option<DynamoDbTomlConfig>,
+	object_store_config: Option<ObjectStoreTomlConfig>,
+	admin_config: Option<AdminConfig>,
+	encryption_config: Option<EncryptionConfig>,
+	analytics_config: Option<AnalyticsTomlConfig>,
+	nostr_notification_config: Option<NostrNotificationTomlConfig>,
+	security_notification_config: Option<SecurityNotificationTomlConfig>,
+	merge_config: Option<MergeConfig>,
+	store_template_config: Option<StoreTemplateConfig>,
+	quota_config: Option<QuotaTomlConfig>,
+	session_limit_config: Option<SessionLimitTomlConfig>,
+	rate_limit_config: Option<RateLimitTomlConfig>,
+	load_shedding_config: Option<LoadSheddingTomlConfig>,
+	backup_monitor_config: Option<BackupMonitorTomlConfig>,
+	certificate_expiry_config: Option<CertificateExpiryTomlConfig>,
+	probe_config: Option<ProbeTomlConfig>,
+	delayed_deletion_config: Option<DelayedDeletionTomlConfig>,
+	soft_deletion_config: Option<SoftDeletionTomlConfig>,
+	change_stream_config: Option<ChangeStreamTomlConfig>,
+	maintenance_config: Option<MaintenanceTomlConfig>,
+	audit_config: Option<AuditTomlConfig>,
+	grpc_config: Option<GrpcConfig>,
+	access_policy_config: Option<AccessPolicyTomlConfig>,
+	client_compatibility_config: Option<ClientCompatibilityTomlConfig>,
+	store_namespace_config: Option<StoreNamespaceTomlConfig>,
+	otel: Option<OtelTomlConfig>,
+}
+
+#[derive(Deserialize)]
+struct OtelTomlConfig {
+	enabled: Option<bool>,
+	endpoint: Option<String>,
+	service_name: Option<String>,
+	sample_ratio: Option<f64>,
+	headers: Option<HashMap<String, String>>,
+	max_spans_per_second: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct StoreNamespaceTomlConfig {
+	max_store_id_length: Option<usize>,
+	restrict_store_id_characters: Option<bool>,
+	max_stores_per_user: Option<usize>,
+	reserved: Option<Vec<ReservedNamespaceTomlConfig>>,
+}
+
+#[derive(Deserialize)]
+struct ReservedNamespaceTomlConfig {
+	prefix: String,
+	claims: Option<HashMap<String, String>>,
+}
+
+#[derive(Deserialize)]
+struct ClientCompatibilityTomlConfig {
+	header_aliases: Option<HashMap<String, String>>,
+	default_store_id: Option<String>,
+	zero_page_size_as_unset: Option<bool>,
 }
 
 #[derive(Deserialize)]
-pub(crate) struct Config {
-	pub(crate) server_config: ServerConfig,
-	pub(crate) jwt_auth_config: Option<JwtAuthConfig>,
-	pub(crate) postgresql_config: Option<PostgreSQLConfig>,
+struct GrpcConfig {
+	bind_address: Option<String>,
 }
 
 #[derive(Deserialize)]
 struct ServerConfig {
 	bind_address: Option<String>,
 	max_request_body_size: Option<usize>,
+	body_read_timeout_secs: Option<u64>,
+	response_write_timeout_secs: Option<u64>,
+	request_timeout_secs: Option<u64>,
+	require_client_encryption: Option<bool>,
+	maintenance_mode: Option<bool>,
 }
 
 #[derive(Clone)]
@@ -52,6 +245,8 @@ pub(crate) struct SentryConfig {
 	pub(crate) dsn: Option<String>,
 	pub(crate) environment: Option<String>,
 	pub(crate) sample_rate: f32,
+	/// The number of events sent to Sentry per second, beyond which events are dropped.
+	pub(crate) max_events_per_second: u64,
 }
 
 impl SentryConfig {
@@ -66,8 +261,19 @@ impl SentryConfig {
 			})
 			.transpose()?
 			.unwrap_or(1.0);
+		let max_events_per_second = read_env(SENTRY_MAX_EVENTS_PER_SECOND_VAR)?
+			.map(|s| {
+				s.parse::<u64>().map_err(|e| {
+					format!(
+						"Unable to parse the SENTRY_MAX_EVENTS_PER_SECOND environment variable: {}",
+						e
+					)
+				})
+			})
+			.transpose()?
+			.unwrap_or(DEFAULT_SENTRY_MAX_EVENTS_PER_SECOND);
 
-		Ok(Self { dsn, environment, sample_rate })
+		Ok(Self { dsn, environment, sample_rate, max_events_per_second })
 	}
 
 	pub(crate) fn get_dsn(&self) -> Option<String> {
@@ -81,6 +287,10 @@ impl SentryConfig {
 	pub(crate) fn get_sample_rate(&self) -> f32 {
 		self.sample_rate
 	}
+
+	pub(crate) fn get_max_events_per_second(&self) -> u64 {
+		self.max_events_per_second
+	}
 }
 
 /// Configuration for Datadog APM tracing.
@@ -98,6 +308,8 @@ pub(crate) struct DatadogConfig {
 	pub(crate) agent_host: String,
 	/// The Datadog Agent trace port. Defaults to 8126.
 	pub(crate) agent_port: u16,
+	/// The number of spans exported per second, beyond which spans are dropped.
+	pub(crate) max_spans_per_second: u64,
 }
 
 impl DatadogConfig {
@@ -123,8 +335,19 @@ impl DatadogConfig {
 			})
 			.transpose()?
 			.unwrap_or(8126);
+		let max_spans_per_second = read_env(DD_TRACE_MAX_SPANS_PER_SECOND_VAR)?
+			.map(|s| {
+				s.parse::<u64>().map_err(|e| {
+					format!(
+						"Unable to parse the DD_TRACE_MAX_SPANS_PER_SECOND environment variable: {}",
+						e
+					)
+				})
+			})
+			.transpose()?
+			.unwrap_or(DEFAULT_MAX_SPANS_PER_SECOND);
 
-		Ok(Self { enabled, service, env, version, agent_host, agent_port })
+		Ok(Self { enabled, service, env, version, agent_host, agent_port, max_spans_per_second })
 	}
 
 	pub(crate) fn is_enabled(&self) -> bool {
@@ -150,21 +373,154 @@ impl DatadogConfig {
 	pub(crate) fn get_agent_port(&self) -> u16 {
 		self.agent_port
 	}
+
+	pub(crate) fn get_max_spans_per_second(&self) -> u64 {
+		self.max_spans_per_second
+	}
+}
+
+/// Configuration for exporting traces over OTLP, e.g. to Tempo, Jaeger or Honeycomb.
+pub(crate) struct OtelConfig {
+	/// The OTLP/HTTP traces endpoint, e.g. `http://localhost:4318/v1/traces`. Defaults to the
+	/// standard `OTEL_EXPORTER_OTLP_*` environment variables, or the local collector.
+	pub(crate) endpoint: Option<String>,
+	/// The service name traces are reported under. Defaults to "vss-server".
+	pub(crate) service_name: String,
+	/// The fraction of traces sampled, unless the caller's trace context decided already.
+	pub(crate) sample_ratio: f64,
+	/// The headers sent with each export, e.g. an API key of the tracing vendor.
+	pub(crate) headers: HashMap<String, String>,
+	/// The number of spans exported per second, beyond which spans are dropped.
+	pub(crate) max_spans_per_second: u64,
 }
 
 #[derive(Deserialize)]
 struct JwtAuthConfig {
 	rsa_pem: Option<String>,
+	rsa_pem_file: Option<PathBuf>,
+	algorithm: Option<String>,
+	jwks_url: Option<String>,
+	jwks_refresh_interval_secs: Option<u64>,
+	profile_claim: Option<String>,
+	scope_claim: Option<String>,
+	store_claim: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ApiKeyAuthTomlConfig {
+	keys: Option<Vec<ApiKeyTomlConfig>>,
+	key_file: Option<PathBuf>,
+	header: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ApiKeyTomlConfig {
+	user_token: String,
+	key_sha256: String,
+	scopes: Option<Vec<String>>,
+	store_ids: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct NostrAuthTomlConfig {
+	url_prefix: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct L402TomlConfig {
+	root_key: Option<String>,
+	price_sats: u64,
+	validity_secs: Option<u64>,
+	lnd_rest_url: String,
+	lnd_macaroon: Option<String>,
+	lnd_cert_path: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct OidcAuthTomlConfig {
+	issuer_url: String,
+	audience: String,
+	key_refresh_interval_secs: Option<u64>,
+	profile_claim: Option<String>,
+	scope_claim: Option<String>,
+	store_claim: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AuthChainTomlConfig {
+	authorizers: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct StorageConfig {
+	backend: Option<String>,
+}
+
+#[cfg(feature = "redis")]
+#[derive(Deserialize)]
+struct RedisTomlConfig {
+	url: Option<String>,
+}
+
+#[cfg(feature = "dynamodb")]
+#[derive(Deserialize)]
+struct DynamoDbTomlConfig {
+	table_name: Option<String>,
+	create_table: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct ObjectStoreTomlConfig {
+	url: Option<String>,
+	#[cfg_attr(not(feature = "object-store"), allow(dead_code))]
+	min_offloaded_value_size: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct ServerTlsTomlConfig {
+	cert_path: Option<PathBuf>,
+	key_path: Option<PathBuf>,
+	client_ca_path: Option<PathBuf>,
 }
 
 #[derive(Deserialize)]
 struct PostgreSQLConfig {
 	username: Option<String>,
 	password: Option<String>,
+	password_file: Option<PathBuf>,
 	address: Option<String>,
 	default_database: Option<String>,
 	vss_database: Option<String>,
 	tls: Option<TlsConfig>,
+	value_compression: Option<String>,
+	value_compression_level: Option<i32>,
+	max_value_size: Option<usize>,
+	notify_changes: Option<bool>,
+	key_history_versions: Option<usize>,
+	migrate_on_startup: Option<bool>,
+	replica_address: Option<String>,
+	require_primary_reads: Option<bool>,
+	transient_retries: Option<usize>,
+	pool: Option<PostgreSQLPoolConfig>,
+	read_role: Option<PostgreSQLRoleConfig>,
+	admin_role: Option<PostgreSQLRoleConfig>,
+}
+
+#[derive(Deserialize)]
+struct PostgreSQLPoolConfig {
+	max_size: Option<usize>,
+	min_idle: Option<usize>,
+	connection_timeout_secs: Option<u64>,
+	idle_timeout_secs: Option<u64>,
+	max_lifetime_secs: Option<u64>,
+	statement_timeout_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct PostgreSQLRoleConfig {
+	username: Option<String>,
+	password: Option<String>,
+	password_file: Option<PathBuf>,
 }
 
 #[derive(Deserialize)]
@@ -172,23 +528,507 @@ struct TlsConfig {
 	crt_pem: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct AdminConfig {
+	bind_address: Option<String>,
+	approval_delay_secs: Option<u64>,
+	admins: Option<Vec<AdminCredentialConfig>>,
+	request_capture_sample_rate: Option<f64>,
+	request_capture_capacity: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct AdminCredentialConfig {
+	name: String,
+	token_sha256: String,
+}
+
+#[derive(Deserialize)]
+struct EncryptionConfig {
+	keys: Option<Vec<String>>,
+	key_file: Option<PathBuf>,
+	current_key_version: Option<u32>,
+	wrapped_keys: Option<Vec<String>>,
+	master_key: Option<String>,
+	vault_transit: Option<VaultTransitTomlConfig>,
+	aws_kms: Option<AwsKmsTomlConfig>,
+	gcp_kms: Option<GcpKmsTomlConfig>,
+	age_identity_file: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct VaultTransitTomlConfig {
+	address: String,
+	token: Option<String>,
+	key_name: String,
+	mount: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AwsKmsTomlConfig {
+	key_id: String,
+	region: Option<String>,
+	endpoint: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GcpKmsTomlConfig {
+	key_name: String,
+	access_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AnalyticsTomlConfig {
+	enabled: Option<bool>,
+	report_interval_secs: Option<u64>,
+	epsilon: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct NostrNotificationTomlConfig {
+	secret_key: Option<String>,
+	relays: Option<Vec<String>>,
+	key_prefixes: Option<Vec<String>>,
+	registration_key: Option<String>,
+	min_interval_secs: Option<u64>,
+	event_kind: Option<u16>,
+}
+
+#[derive(Deserialize)]
+struct SecurityNotificationTomlConfig {
+	preferences_key: Option<String>,
+	email_webhook_url: Option<String>,
+	allowed_webhook_hosts: Option<Vec<String>>,
+	subscriptions: Option<Vec<SecuritySubscription>>,
+}
+
+#[derive(Deserialize)]
+struct MergeConfig {
+	rules: Option<Vec<MergeRuleConfig>>,
+	max_global_version_retries: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct MergeRuleConfig {
+	key_prefix: String,
+	strategy: String,
+}
+
+#[derive(Deserialize)]
+struct AccessPolicyTomlConfig {
+	default_effect: Option<String>,
+	rules: Option<Vec<AccessRuleTomlConfig>>,
+}
+
+#[derive(Deserialize)]
+struct AccessRuleTomlConfig {
+	name: Option<String>,
+	effect: String,
+	verbs: Option<Vec<String>>,
+	store_ids: Option<Vec<String>>,
+	key_prefixes: Option<Vec<String>>,
+	claims: Option<HashMap<String, String>>,
+	missing_claims: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct StoreTemplateConfig {
+	templates: Option<Vec<StoreTemplateTomlConfig>>,
+}
+
+#[derive(Deserialize)]
+struct StoreTemplateTomlConfig {
+	name: String,
+	objects: Vec<StoreTemplateObjectConfig>,
+}
+
+// An object's value is given either as UTF-8 text or base64 encoded.
+#[derive(Deserialize)]
+struct StoreTemplateObjectConfig {
+	key: String,
+	value: Option<String>,
+	value_base64: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct QuotaTomlConfig {
+	max_bytes_per_user: Option<u64>,
+	max_objects_per_user: Option<u64>,
+	soft_limit_ratio: Option<f64>,
+	enforce: Option<bool>,
+	profiles: Option<HashMap<String, QuotaProfileTomlConfig>>,
+}
+
+#[derive(Deserialize)]
+struct BackupMonitorTomlConfig {
+	check_interval_secs: Option<u64>,
+	max_archive_age_secs: Option<u64>,
+	fail_readiness: Option<bool>,
+	alert_command: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CertificateExpiryTomlConfig {
+	check_interval_secs: Option<u64>,
+	warning_period_days: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ProbeTomlConfig {
+	interval_secs: Option<u64>,
+	url: Option<String>,
+	store_id: Option<String>,
+	timeout_secs: Option<u64>,
+	authorization: Option<String>,
+	signing_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DelayedDeletionTomlConfig {
+	key_prefixes: Vec<String>,
+	delay_secs: Option<u64>,
+	check_interval_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct SoftDeletionTomlConfig {
+	retention_secs: Option<u64>,
+	purge_interval_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ChangeStreamTomlConfig {
+	max_subscriptions_per_user: Option<usize>,
+	buffered_events: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct MaintenanceTomlConfig {
+	share_token_reaping_interval_secs: Option<u64>,
+	usage_recomputation_interval_secs: Option<u64>,
+	orphan_cleanup_interval_secs: Option<u64>,
+	vacuum_hint_interval_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct AuditTomlConfig {
+	enabled: Option<bool>,
+	sink: Option<String>,
+	file: Option<PathBuf>,
+	retention_days: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct SessionLimitTomlConfig {
+	max_sessions_per_user: usize,
+	window_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct RateLimitTomlConfig {
+	requests_per_second: f64,
+	burst: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct LoadSheddingTomlConfig {
+	window_secs: Option<u64>,
+	min_requests: Option<u64>,
+	degraded_failure_ratio: Option<f64>,
+	unhealthy_failure_ratio: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct QuotaProfileTomlConfig {
+	max_bytes_per_user: Option<u64>,
+	max_objects_per_user: Option<u64>,
+}
+
 #[derive(Deserialize)]
 struct LogConfig {
 	level: Option<String>,
 	file: Option<PathBuf>,
+	format: Option<String>,
 }
 
 // Encapsulates the result of reading both the environment variables and the config file.
 pub(crate) struct Configuration {
 	pub(crate) bind_address: String,
 	pub(crate) max_request_body_size: Option<usize>,
+	/// The time allowed to receive a request body.
+	pub(crate) body_read_timeout: Duration,
+	/// The time writing a response may make no progress before the connection is closed.
+	pub(crate) response_write_timeout: Duration,
+	/// The time a request may take to execute once its body was read, if limited.
+	pub(crate) request_timeout: Option<Duration>,
+	/// Whether values written through the API must be encrypted client-side.
+	pub(crate) require_client_encryption: bool,
+	/// Whether the server starts in maintenance mode, rejecting writes until it is left.
+	pub(crate) maintenance_mode: bool,
+	/// The TLS settings of the API listener, which serves plaintext HTTP if unset.
+	pub(crate) tls_config: Option<ServerTlsConfig>,
 	pub(crate) rsa_pem: Option<String>,
-	pub(crate) postgresql_prefix: String,
-	pub(crate) default_db: String,
-	pub(crate) vss_db: String,
-	pub(crate) tls_config: Option<Option<String>>,
+	/// The JWKS whose keys verify JWTs instead of a fixed public key, if set.
+	pub(crate) jwks_config: Option<JwksConfig>,
+	/// The algorithm JWTs must be signed with, if not the one detected from `rsa_pem`.
+	pub(crate) jwt_algorithm: Option<String>,
+	/// The JWT claim selecting the user's limit profile.
+	pub(crate) jwt_profile_claim: Option<String>,
+	/// The JWT claim limiting the user's credentials to some kinds of access.
+	pub(crate) jwt_scope_claim: Option<String>,
+	/// The JWT claim limiting the user's credentials to some stores.
+	pub(crate) jwt_store_claim: Option<String>,
+	/// The static API keys users authenticate with, if enabled.
+	pub(crate) api_key_auth_config: Option<ApiKeyAuthConfig>,
+	pub(crate) nostr_auth_config: Option<NostrAuthConfig>,
+	pub(crate) l402_config: Option<L402Config>,
+	pub(crate) oidc_auth_config: Option<OidcAuthConfig>,
+	/// The configured authentication methods in the order they are tried, if several are chained.
+	pub(crate) auth_chain: Option<Vec<AuthMethod>>,
+	pub(crate) storage: BackendConfig,
+	/// The offloading of large values to an object store, if enabled.
+	#[cfg(feature = "object-store")]
+	pub(crate) offloading_config: Option<OffloadingConfig>,
 	pub(crate) log_file: PathBuf,
 	pub(crate) log_level: LevelFilter,
+	pub(crate) log_format: LogFormat,
+	pub(crate) admin_config: Option<AdminConfiguration>,
+	pub(crate) encryption_keys: Option<EncryptionKeys>,
+	pub(crate) analytics_config: Option<AnalyticsConfig>,
+	pub(crate) nostr_notification_config: Option<NostrNotificationConfig>,
+	pub(crate) security_notification_config: Option<SecurityNotificationConfig>,
+	/// The merge strategies to resolve write conflicts with, by key prefix.
+	pub(crate) merge_strategies: Vec<(String, Arc<dyn MergeStrategy>)>,
+	/// The number of times puts conflicting only on the global version are retried.
+	pub(crate) max_global_version_retries: usize,
+	/// The templates clients may initialize new stores from.
+	pub(crate) store_templates: Vec<StoreTemplate>,
+	pub(crate) quota_config: Option<QuotaConfig>,
+	pub(crate) session_limit_config: Option<SessionLimitConfig>,
+	/// The rate limit of requests per client, if enabled.
+	pub(crate) rate_limit_config: Option<RateLimitConfig>,
+	pub(crate) load_shedding_config: Option<LoadSheddingConfig>,
+	/// The monitoring of the database's continuous backups, if enabled.
+	pub(crate) backup_monitor_config: Option<BackupMonitorConfig>,
+	/// The monitoring of the expiry of the certificates the server depends on.
+	pub(crate) certificate_expiry_config: CertificateExpiryConfig,
+	/// The synthetic probe exercising the API end to end, if enabled.
+	pub(crate) probe_config: Option<ProbeConfig>,
+	/// The delaying of deletions under critical key prefixes, if enabled.
+	pub(crate) delayed_deletion_config: Option<DelayedDeletionConfig>,
+	/// The keeping of deleted objects as restorable tombstones, if enabled.
+	pub(crate) soft_deletion_config: Option<SoftDeletionConfig>,
+	/// The pushing of changes to subscribed clients, if enabled.
+	pub(crate) change_stream_config: Option<ChangeStreamConfig>,
+	/// The periodic maintenance of the backend's data, if enabled.
+	pub(crate) maintenance_config: Option<MaintenanceConfig>,
+	/// The audit log of mutating operations, if enabled.
+	pub(crate) audit_config: Option<AuditConfig>,
+	/// The address to serve the gRPC transport on, if enabled.
+	#[cfg(feature = "grpc")]
+	pub(crate) grpc_bind_address: Option<String>,
+	/// The operator-defined rules deciding which operations callers may perform, if any.
+	pub(crate) access_rule_policy: Option<RuleBasedPolicy>,
+	/// The shims for quirks of older clients, if any are enabled.
+	pub(crate) client_compatibility: Option<ClientCompatibility>,
+	/// The rules on the store ids clients may use, if any.
+	pub(crate) store_namespace_config: Option<StoreNamespaceConfig>,
+	/// The OTLP trace export settings, if enabled.
+	pub(crate) otel_config: Option<OtelConfig>,
+}
+
+/// An authentication method which may be chained with others.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum AuthMethod {
+	/// JWTs verified with `rsa_pem` or the keys of a JWKS.
+	Jwt,
+	ApiKey,
+	Nostr,
+	L402,
+	Oidc,
+	/// The signatures of clients' own keys, used when no other method is configured.
+	Signature,
+}
+
+impl AuthMethod {
+	fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"jwt" => Some(AuthMethod::Jwt),
+			"apikey" => Some(AuthMethod::ApiKey),
+			"nostr" => Some(AuthMethod::Nostr),
+			"l402" => Some(AuthMethod::L402),
+			"oidc" => Some(AuthMethod::Oidc),
+			"signature" => Some(AuthMethod::Signature),
+			_ => None,
+		}
+	}
+
+	pub(crate) fn name(&self) -> &'static str {
+		match self {
+			AuthMethod::Jwt => "jwt",
+			AuthMethod::ApiKey => "apikey",
+			AuthMethod::Nostr => "nostr",
+			AuthMethod::L402 => "l402",
+			AuthMethod::Oidc => "oidc",
+			AuthMethod::Signature => "signature",
+		}
+	}
+}
+
+// The resolved configuration of the JWKS verifying JWTs.
+#[cfg_attr(not(feature = "jwks"), allow(dead_code))]
+pub(crate) struct JwksConfig {
+	/// The URL the issuer publishes its keys at.
+	pub(crate) url: String,
+	/// The interval at which the keys are refetched.
+	pub(crate) refresh_interval: Duration,
+}
+
+// The resolved configuration of the API key authorizer.
+pub(crate) struct ApiKeyAuthConfig {
+	/// The SHA-256 hash of each key, along with the user token it grants access to.
+	pub(crate) keys: Vec<([u8; 32], String)>,
+	/// The header carrying the key instead of the `Authorization` header, if any.
+	pub(crate) header: Option<String>,
+	/// The kinds of access keys are limited to, by the SHA-256 hash of the key.
+	pub(crate) key_scopes: Vec<([u8; 32], Vec<AccessVerb>)>,
+	/// The stores keys are limited to, by the SHA-256 hash of the key.
+	pub(crate) key_store_ids: Vec<([u8; 32], Vec<String>)>,
+}
+
+// The resolved configuration of the NIP-98 Nostr authorizer.
+pub(crate) struct NostrAuthConfig {
+	/// The prefix the URL signed in auth events must start with, if any.
+	pub(crate) url_prefix: Option<String>,
+}
+
+// The resolved configuration of the L402 authorizer.
+#[cfg_attr(not(feature = "l402"), allow(dead_code))]
+pub(crate) struct L402Config {
+	/// The key macaroons are signed with.
+	pub(crate) root_key: [u8; 32],
+	/// The price of a token.
+	pub(crate) price_sats: u64,
+	/// The time tokens are valid for after they were issued, if limited.
+	pub(crate) validity: Option<Duration>,
+	/// The base URL of LND's REST API, e.g. `https://localhost:8080`.
+	pub(crate) lnd_rest_url: String,
+	/// The hex-encoded macaroon authorizing the creation of invoices on LND.
+	pub(crate) lnd_macaroon: String,
+	/// The PEM-encoded TLS certificate of LND, if it is self-signed.
+	pub(crate) lnd_cert_pem: Option<Vec<u8>>,
+}
+
+/// The keys object values are encrypted at rest with.
+pub(crate) enum EncryptionKeys {
+	/// Data keys given in plaintext.
+	Static(StaticKeyProvider),
+	/// Data keys given wrapped by a master key, which unwraps them at startup.
+	Envelope { wrapped_keys: WrappedDataKeys, master_key: MasterKeyConfig },
+}
+
+impl EncryptionKeys {
+	/// The versions of the configured keys, in ascending order.
+	pub(crate) fn key_versions(&self) -> Vec<u32> {
+		match self {
+			EncryptionKeys::Static(keys) => keys.key_versions(),
+			EncryptionKeys::Envelope { wrapped_keys, .. } => wrapped_keys.key_versions(),
+		}
+	}
+
+	/// The version of the key new values are encrypted with.
+	pub(crate) fn current_key_version(&self) -> u32 {
+		match self {
+			EncryptionKeys::Static(keys) => keys.current_key_version(),
+			EncryptionKeys::Envelope { wrapped_keys, .. } => wrapped_keys.current_key_version(),
+		}
+	}
+}
+
+/// The master key data keys are wrapped with.
+pub(crate) enum MasterKeyConfig {
+	/// A master key given in the configuration.
+	Local(LocalMasterKey),
+	/// A key of Vault's transit secrets engine.
+	VaultTransit(VaultTransitConfig),
+	/// A key of AWS KMS.
+	AwsKms(AwsKmsConfig),
+	/// A key of Google Cloud KMS.
+	GcpKms(GcpKmsConfig),
+}
+
+impl MasterKeyConfig {
+	pub(crate) fn name(&self) -> &'static str {
+		match self {
+			MasterKeyConfig::Local(_) => "local",
+			MasterKeyConfig::VaultTransit(_) => "vault_transit",
+			MasterKeyConfig::AwsKms(_) => "aws_kms",
+			MasterKeyConfig::GcpKms(_) => "gcp_kms",
+		}
+	}
+}
+
+pub(crate) struct VaultTransitConfig {
+	/// The base URL of Vault, e.g. `https://vault.example.com:8200`.
+	pub(crate) address: String,
+	/// The token authorizing decryption with the key.
+	pub(crate) token: String,
+	/// The name of the transit key data keys are wrapped with.
+	pub(crate) key_name: String,
+	/// The path the transit secrets engine is mounted at.
+	pub(crate) mount: String,
+}
+
+#[derive(Clone)]
+pub(crate) struct AwsKmsConfig {
+	/// The id or ARN of the KMS key data keys are wrapped with.
+	pub(crate) key_id: String,
+	pub(crate) region: String,
+	/// The KMS endpoint, if not the public one of the region.
+	pub(crate) endpoint: Option<String>,
+	pub(crate) access_key_id: String,
+	pub(crate) secret_access_key: String,
+	/// The session token of temporary credentials.
+	pub(crate) session_token: Option<String>,
+}
+
+pub(crate) struct GcpKmsConfig {
+	/// The resource name of the key data keys are wrapped with, i.e.
+	/// `projects/<project>/locations/<location>/keyRings/<key ring>/cryptoKeys/<key>`.
+	pub(crate) key_name: String,
+	/// The OAuth access token authorizing decryption with the key, or `None` to fetch one for the
+	/// instance's service account from the metadata server.
+	pub(crate) access_token: Option<String>,
+}
+
+// The resolved configuration of the OIDC authorizer.
+#[cfg_attr(not(feature = "oidc"), allow(dead_code))]
+pub(crate) struct OidcAuthConfig {
+	/// The URL the provider is identified by, and serves its discovery document under.
+	pub(crate) issuer_url: String,
+	/// The audience tokens must be issued for.
+	pub(crate) audience: String,
+	/// The interval at which the provider's signing keys are refetched.
+	pub(crate) key_refresh_interval: Duration,
+	/// The claim selecting the user's limit profile.
+	pub(crate) profile_claim: Option<String>,
+	/// The claim limiting the user's credentials to some kinds of access.
+	pub(crate) scope_claim: Option<String>,
+	/// The claim limiting the user's credentials to some stores.
+	pub(crate) store_claim: Option<String>,
+}
+
+// The resolved configuration of the admin API, only present if an admin bind address is set.
+pub(crate) struct AdminConfiguration {
+	pub(crate) bind_address: String,
+	pub(crate) approval_delay: Duration,
+	pub(crate) admins: Vec<AdminCredential>,
+	/// The fraction of requests captured initially, see [`RequestCapture`].
+	///
+	/// [`RequestCapture`]: crate::request_capture::RequestCapture
+	pub(crate) request_capture_sample_rate: f64,
+	/// The number of most recent captured requests kept.
+	pub(crate) request_capture_capacity: usize,
 }
 
 pub(crate) fn load_datadog_configuration() -> Result<DatadogConfig, String> {
@@ -199,17 +1039,39 @@ pub(crate) fn load_sentry_configuration() -> Result<SentryConfig, String> {
 	SentryConfig::from_env()
 }
 
+/// Reads the environment variable `env_var`, or if it is not set, the file named by
+/// `<env_var>_FILE`, as secrets are mounted by Docker and Kubernetes.
 #[inline]
 fn read_env(env_var: &str) -> Result<Option<String>, String> {
 	match std::env::var(env_var) {
-		Ok(env) => Ok(Some(env)),
-		Err(std::env::VarError::NotPresent) => Ok(None),
+		Ok(env) => secrets::resolve(env, env_var).map(Some),
+		Err(std::env::VarError::NotPresent) => {
+			let file_var = format!("{}_FILE", env_var);
+			match std::env::var(&file_var) {
+				Ok(path) => secrets::read_secret_file(Path::new(&path), &file_var).map(Some),
+				Err(std::env::VarError::NotPresent) => Ok(None),
+				Err(e) => {
+					Err(format!("Failed to load the {} environment variable: {}", file_var, e))
+				},
+			}
+		},
 		Err(e) => Err(format!("Failed to load the {} environment variable: {}", env_var, e)),
 	}
 }
 
+/// Returns the secret given in the configuration file either as `value` or in the file at `path`.
+fn read_secret_config(
+	value: Option<String>, path: Option<PathBuf>, name: &str,
+) -> Result<Option<String>, String> {
+	match (value, path) {
+		(Some(_), Some(_)) => Err(format!("Only one of {0} and {0}_file may be set", name)),
+		(None, Some(path)) => secrets::read_secret_file(&path, &format!("{}_file", name)).map(Some),
+		(value, None) => Ok(value),
+	}
+}
+
 #[inline]
-fn read_config<'a, T: std::fmt::Display>(
+fn read_config<T: std::fmt::Display>(
 	env: Option<T>, config: Option<T>, item: &str, var_name: &str,
 ) -> Result<T, String> {
 	env.or(config).ok_or(format!(
@@ -219,23 +1081,90 @@ fn read_config<'a, T: std::fmt::Display>(
 }
 
 pub(crate) fn load_configuration(config_file_path: Option<&str>) -> Result<Configuration, String> {
-	let TomlConfig { server_config, log_config, jwt_auth_config, postgresql_config } =
-		match config_file_path {
-			Some(path) => {
-				let config_file = std::fs::read_to_string(path)
-					.map_err(|e| format!("Failed to read configuration file: {}", e))?;
-				toml::from_str(&config_file)
-					.map_err(|e| format!("Failed to parse configuration file: {}", e))?
-			},
-			None => TomlConfig::default(), // All fields are set to `None`
-		};
+	let TomlConfig {
+		server_config,
+		tls,
+		log_config,
+		jwt_auth_config,
+		api_key_auth_config,
+		nostr_auth_config,
+		l402_config,
+		oidc_auth_config,
+		auth_chain_config,
+		storage_config,
+		postgresql_config,
+		#[cfg(feature = "redis")]
+		redis_config,
+		#[cfg(feature = "dynamodb")]
+		dynamodb_config,
+		object_store_config,
+		admin_config,
+		encryption_config,
+		analytics_config,
+		nostr_notification_config,
+		security_notification_config,
+		merge_config,
+		store_template_config,
+		quota_config,
+		session_limit_config,
+		rate_limit_config,
+		load_shedding_config,
+		backup_monitor_config,
+		certificate_expiry_config,
+		probe_config,
+		delayed_deletion_config,
+		soft_deletion_config,
+		change_stream_config,
+		maintenance_config,
+		audit_config,
+		grpc_config,
+		access_policy_config,
+		client_compatibility_config,
+		store_namespace_config,
+		otel,
+	} = match config_file_path {
+		Some(path) => {
+			let config_file = std::fs::read_to_string(path)
+				.map_err(|e| format!("Failed to read configuration file: {}", e))?;
+			let mut table: toml::Table = toml::from_str(&config_file)
+				.map_err(|e| format!("Failed to parse configuration file: {}", e))?;
+			secrets::resolve_toml_table(&mut table)?;
+			table.try_into().map_err(|e| format!("Failed to parse configuration file: {}", e))?
+		},
+		None => {
+			let mut table = env_config::table_from_env()?;
+			secrets::resolve_toml_table(&mut table)?;
+			table
+				.try_into()
+				.map_err(|e| format!("Failed to parse configuration from the environment: {}", e))?
+		},
+	};
 
-	let (bind_address_config, max_request_body_size_config) = match server_config {
-		Some(c) => (c.bind_address, c.max_request_body_size),
-		None => (None, None),
+	let (
+		bind_address_config,
+		max_request_body_size_config,
+		body_read_timeout_secs,
+		response_write_timeout_secs,
+		request_timeout_secs,
+		require_client_encryption,
+		maintenance_mode,
+	) = match server_config {
+		Some(c) => (
+			c.bind_address,
+			c.max_request_body_size,
+			c.body_read_timeout_secs,
+			c.response_write_timeout_secs,
+			c.request_timeout_secs,
+			c.require_client_encryption,
+			c.maintenance_mode,
+		),
+		None => (None, None, None, None, None, None, None),
 	};
 
-	let bind_address_env = read_env(BIND_ADDR_VAR)?;
+	let bind_address_env = match read_env(BIND_ADDR_VAR)? {
+		Some(bind_address) => Some(bind_address),
+		None => env_config::bind_address(read_env(HOST_VAR)?, read_env(PORT_VAR)?)?,
+	};
 	let bind_address = read_config(
 		bind_address_env,
 		bind_address_config,
@@ -252,6 +1181,22 @@ pub(crate) fn load_configuration(config_file_path: Option<&str>) -> Result<Confi
 		.transpose()?;
 	let max_request_body_size = max_request_body_size_env.or(max_request_body_size_config);
 
+	let body_read_timeout =
+		Duration::from_secs(body_read_timeout_secs.unwrap_or(DEFAULT_BODY_READ_TIMEOUT.as_secs()));
+	let response_write_timeout = Duration::from_secs(
+		response_write_timeout_secs.unwrap_or(DEFAULT_RESPONSE_WRITE_TIMEOUT.as_secs()),
+	);
+	if body_read_timeout.is_zero() || response_write_timeout.is_zero() {
+		return Err(
+			"body_read_timeout_secs and response_write_timeout_secs must be greater than 0"
+				.to_string(),
+		);
+	}
+	if request_timeout_secs == Some(0) {
+		return Err("request_timeout_secs must be greater than 0".to_string());
+	}
+	let request_timeout = request_timeout_secs.map(Duration::from_secs);
+
 	let log_level_env: Option<LevelFilter> = read_env(LOG_LEVEL_VAR)?
 		.map(|level_str| {
 			level_str
@@ -277,19 +1222,1192 @@ pub(crate) fn load_configuration(config_file_path: Option<&str>) -> Result<Confi
 				.map_err(|e| format!("Unable to parse the log file environment variable: {}", e))
 		})
 		.transpose()?;
+	let log_format_env = read_env(LOG_FORMAT_VAR)?;
+	let log_format_config = log_config.as_ref().and_then(|config| config.format.clone());
+	let log_format = log_format_env
+		.or(log_format_config)
+		.map(|format| {
+			LogFormat::from_name(&format)
+				.ok_or(format!("Unknown log format {}, expected text or json", format))
+		})
+		.transpose()?
+		.unwrap_or(LogFormat::Text);
+
 	let log_file_config: Option<PathBuf> = log_config.and_then(|config| config.file);
 	let log_file = log_file_env.or(log_file_config).unwrap_or(PathBuf::from("vss.log"));
 
 	let rsa_pem_env = read_env(JWT_RSA_PEM_VAR)?;
-	let rsa_pem = rsa_pem_env.or(jwt_auth_config.and_then(|config| config.rsa_pem));
+	let (
+		rsa_pem_config,
+		jwt_algorithm,
+		jwks_url,
+		jwks_refresh_interval_secs,
+		jwt_profile_claim,
+		jwt_scope_claim,
+		jwt_store_claim,
+	) = match jwt_auth_config {
+		Some(c) => (
+			read_secret_config(c.rsa_pem, c.rsa_pem_file, "rsa_pem")?,
+			c.algorithm,
+			c.jwks_url,
+			c.jwks_refresh_interval_secs,
+			c.profile_claim,
+			c.scope_claim,
+			c.store_claim,
+		),
+		None => (None, None, None, None, None, None, None),
+	};
+	let rsa_pem = rsa_pem_env.or(rsa_pem_config);
+	if rsa_pem.is_some() && !cfg!(feature = "jwt") {
+		return Err("JWT authentication requires building with the `jwt` feature".to_string());
+	}
+	if jwt_algorithm.is_some() && rsa_pem.is_none() {
+		return Err("The JWT algorithm may only be set along with rsa_pem".to_string());
+	}
+	let jwks_config = jwks_url
+		.map(|url| {
+			if rsa_pem.is_some() {
+				return Err("Only one of the JWT rsa_pem and jwks_url may be set".to_string());
+			}
+			if !cfg!(feature = "jwks") {
+				return Err(
+					"JWKS key rotation requires building with the `jwks` feature".to_string()
+				);
+			}
+			if !url.starts_with("https://") && !url.starts_with("http://") {
+				return Err("The JWT jwks_url must be an http(s) URL".to_string());
+			}
+			let refresh_interval = Duration::from_secs(
+				jwks_refresh_interval_secs.unwrap_or(DEFAULT_JWKS_REFRESH_INTERVAL_SECS),
+			);
+			if refresh_interval.is_zero() {
+				return Err("The JWT jwks_refresh_interval_secs must be positive".to_string());
+			}
+			Ok(JwksConfig { url, refresh_interval })
+		})
+		.transpose()?;
+	let jwt_configured = rsa_pem.is_some() || jwks_config.is_some();
+	// Several authentication methods may only be configured when chained in a given order.
+	let chained = auth_chain_config.is_some();
 
-	let username_env = read_env(PSQL_USER_VAR)?;
-	let password_env = read_env(PSQL_PASS_VAR)?;
-	let address_env: Option<String> = read_env(PSQL_ADDR_VAR)?;
+	let api_key_auth_config = api_key_auth_config
+		.map(|config| {
+			if !chained && jwt_configured {
+				return Err(
+					"Only one of jwt_auth_config and api_key_auth_config may be set".to_string()
+				);
+			}
+			if !cfg!(feature = "apikey") {
+				return Err("API key authentication requires building with the `apikey` feature"
+					.to_string());
+			}
+			let mut keys = Vec::new();
+			let mut key_scopes = Vec::new();
+			let mut key_store_ids = Vec::new();
+			for key in config.keys.unwrap_or_default() {
+				let key_sha256 = parse_sha256_hex(&key.key_sha256).ok_or(format!(
+					"The key_sha256 of the API key of user {} must be a hex-encoded SHA-256 hash",
+					key.user_token
+				))?;
+				if let Some(scopes) = key.scopes {
+					let scopes = parse_scopes(&scopes).map_err(|e| {
+						format!("Invalid scopes of the API key of user {}: {}", key.user_token, e)
+					})?;
+					key_scopes.push((key_sha256, scopes));
+				}
+				if let Some(store_ids) = key.store_ids {
+					validate_store_ids(&store_ids).map_err(|e| {
+						format!(
+							"Invalid store_ids of the API key of user {}: {}",
+							key.user_token, e
+						)
+					})?;
+					key_store_ids.push((key_sha256, store_ids));
+				}
+				keys.push((key_sha256, key.user_token));
+			}
+			if let Some(path) = config.key_file {
+				keys.extend(read_api_key_file(&path)?);
+			}
+			if keys.is_empty() {
+				return Err("At least one API key must be configured to enable API key \
+					authentication"
+					.to_string());
+			}
+			if config.header.as_deref().is_some_and(|header| header.trim().is_empty()) {
+				return Err("The API key header must not be empty".to_string());
+			}
+			Ok(ApiKeyAuthConfig { keys, header: config.header, key_scopes, key_store_ids })
+		})
+		.transpose()?;
+
+	let nostr_auth_config = nostr_auth_config
+		.map(|config| {
+			if !chained && (jwt_configured || api_key_auth_config.is_some()) {
+				return Err("Only one of jwt_auth_config, api_key_auth_config and \
+					nostr_auth_config may be set"
+					.to_string());
+			}
+			if !cfg!(feature = "nostr") {
+				return Err(
+					"Nostr authentication requires building with the `nostr` feature".to_string()
+				);
+			}
+			if let Some(url_prefix) = config.url_prefix.as_deref() {
+				if !url_prefix.starts_with("https://") && !url_prefix.starts_with("http://") {
+					return Err("The Nostr auth url_prefix must be an http(s) URL".to_string());
+				}
+			}
+			Ok(NostrAuthConfig { url_prefix: config.url_prefix })
+		})
+		.transpose()?;
+
+	let l402_root_key_env = read_env(L402_ROOT_KEY_VAR)?;
+	let l402_lnd_macaroon_env = read_env(L402_LND_MACAROON_VAR)?;
+	let l402_config = l402_config
+		.map(|config| {
+			if !chained
+				&& (jwt_configured || api_key_auth_config.is_some() || nostr_auth_config.is_some())
+			{
+				return Err("Only one of jwt_auth_config, api_key_auth_config, \
+					nostr_auth_config and l402_config may be set"
+					.to_string());
+			}
+			if !cfg!(feature = "l402") {
+				return Err(
+					"L402 authentication requires building with the `l402` feature".to_string()
+				);
+			}
+			let root_key = read_config(
+				l402_root_key_env,
+				config.root_key,
+				"The L402 root_key",
+				L402_ROOT_KEY_VAR,
+			)?;
+			let root_key = parse_sha256_hex(&root_key)
+				.ok_or("The L402 root_key must be 32 hex-encoded bytes".to_string())?;
+			if config.price_sats == 0 {
+				return Err("The L402 price_sats must be positive".to_string());
+			}
+			let validity = config.validity_secs.map(Duration::from_secs);
+			if validity.is_some_and(|validity| validity.is_zero()) {
+				return Err("The L402 validity_secs must be positive".to_string());
+			}
+			if !config.lnd_rest_url.starts_with("https://")
+				&& !config.lnd_rest_url.starts_with("http://")
+			{
+				return Err("The L402 lnd_rest_url must be an http(s) URL".to_string());
+			}
+			let lnd_macaroon = read_config(
+				l402_lnd_macaroon_env,
+				config.lnd_macaroon,
+				"The L402 lnd_macaroon",
+				L402_LND_MACAROON_VAR,
+			)?;
+			let lnd_cert_pem = config
+				.lnd_cert_path
+				.map(|path| {
+					std::fs::read(&path).map_err(|e| {
+						format!("Failed to read LND certificate {}: {}", path.display(), e)
+					})
+				})
+				.transpose()?;
+			Ok(L402Config {
+				root_key,
+				price_sats: config.price_sats,
+				validity,
+				lnd_rest_url: config.lnd_rest_url,
+				lnd_macaroon,
+				lnd_cert_pem,
+			})
+		})
+		.transpose()?;
+
+	let oidc_auth_config = oidc_auth_config
+		.map(|config| {
+			if !chained
+				&& (jwt_configured
+					|| api_key_auth_config.is_some()
+					|| nostr_auth_config.is_some()
+					|| l402_config.is_some())
+			{
+				return Err("Only one of jwt_auth_config, api_key_auth_config, \
+					nostr_auth_config, l402_config and oidc_auth_config may be set"
+					.to_string());
+			}
+			if !cfg!(feature = "oidc") {
+				return Err(
+					"OIDC authentication requires building with the `oidc` feature".to_string()
+				);
+			}
+			if !config.issuer_url.starts_with("https://")
+				&& !config.issuer_url.starts_with("http://")
+			{
+				return Err("The OIDC issuer_url must be an http(s) URL".to_string());
+			}
+			if config.audience.is_empty() {
+				return Err("The OIDC audience must not be empty".to_string());
+			}
+			let key_refresh_interval = Duration::from_secs(
+				config.key_refresh_interval_secs.unwrap_or(DEFAULT_JWKS_REFRESH_INTERVAL_SECS),
+			);
+			if key_refresh_interval.is_zero() {
+				return Err("The OIDC key_refresh_interval_secs must be positive".to_string());
+			}
+			Ok(OidcAuthConfig {
+				issuer_url: config.issuer_url,
+				audience: config.audience,
+				key_refresh_interval,
+				profile_claim: config.profile_claim,
+				scope_claim: config.scope_claim,
+				store_claim: config.store_claim,
+			})
+		})
+		.transpose()?;
+
+	let auth_chain = auth_chain_config
+		.map(|config| {
+			let configured = [
+				(AuthMethod::Jwt, jwt_configured),
+				(AuthMethod::ApiKey, api_key_auth_config.is_some()),
+				(AuthMethod::Nostr, nostr_auth_config.is_some()),
+				(AuthMethod::L402, l402_config.is_some()),
+				(AuthMethod::Oidc, oidc_auth_config.is_some()),
+				(AuthMethod::Signature, cfg!(feature = "sigs")),
+			];
+			let mut methods = Vec::new();
+			for name in &config.authorizers {
+				let method = AuthMethod::from_name(name)
+					.ok_or(format!("Unknown authorizer {} in auth_chain_config", name))?;
+				if methods.contains(&method) {
+					return Err(format!(
+						"The authorizer {} is listed twice in auth_chain_config",
+						name
+					));
+				}
+				if !configured.contains(&(method, true)) {
+					return Err(format!(
+						"The authorizer {} in auth_chain_config is not configured",
+						name
+					));
+				}
+				methods.push(method);
+			}
+			if let Some((method, _)) = configured.iter().find(|(method, is_configured)| {
+				*is_configured && *method != AuthMethod::Signature && !methods.contains(method)
+			}) {
+				return Err(format!(
+					"The configured authorizer {} must be listed in auth_chain_config",
+					method.name()
+				));
+			}
+			if methods.is_empty() {
+				return Err(
+					"At least one authorizer must be listed in auth_chain_config".to_string()
+				);
+			}
+			Ok(methods)
+		})
+		.transpose()?;
+
+	let storage_backend_env = read_env(STORAGE_BACKEND_VAR)?;
+	let storage_backend_config = storage_config.and_then(|config| config.backend);
+	let mut storage = match storage_backend_env.or(storage_backend_config).as_deref() {
+		None | Some("postgres") => {
+			BackendConfig::Postgres(Box::new(load_postgres_configuration(postgresql_config)?))
+		},
+		Some("memory") => BackendConfig::InMemory,
+		#[cfg(feature = "redis")]
+		Some("redis") => {
+			let url_config = redis_config.and_then(|c| c.url);
+			let url =
+				read_config(read_env(REDIS_URL_VAR)?, url_config, "Redis URL", REDIS_URL_VAR)?;
+			BackendConfig::Redis(RedisBackendConfig { url })
+		},
+		#[cfg(feature = "dynamodb")]
+		Some("dynamodb") => {
+			let (table_name_config, create_table) = match dynamodb_config {
+				Some(c) => (c.table_name, c.create_table.unwrap_or(false)),
+				None => (None, false),
+			};
+			let table_name = read_config(
+				read_env(DYNAMODB_TABLE_VAR)?,
+				table_name_config,
+				"DynamoDB table name",
+				DYNAMODB_TABLE_VAR,
+			)?;
+			BackendConfig::DynamoDb(DynamoDbBackendConfig { table_name, create_table })
+		},
+		#[cfg(not(feature = "redis"))]
+		Some("redis") => {
+			return Err(
+				"The redis storage backend requires building with the `redis` feature".to_string()
+			)
+		},
+		#[cfg(not(feature = "dynamodb"))]
+		Some("dynamodb") => {
+			return Err(
+				"The dynamodb storage backend requires building with the `dynamodb` feature"
+					.to_string(),
+			)
+		},
+		Some(backend) => return Err(format!("Unknown storage backend: {}", backend)),
+	};
+
+	let admin_bind_address_env = read_env(ADMIN_BIND_ADDR_VAR)?;
+	let admin_config = match (admin_bind_address_env, admin_config) {
+		(None, None) | (None, Some(AdminConfig { bind_address: None, .. })) => None,
+		(bind_address_env, config) => {
+			let (bind_address_config, approval_delay_secs, admins_config, capture_rate, capacity) =
+				match config {
+					Some(c) => (
+						c.bind_address,
+						c.approval_delay_secs,
+						c.admins.unwrap_or_default(),
+						c.request_capture_sample_rate,
+						c.request_capture_capacity,
+					),
+					None => (None, None, Vec::new(), None, None),
+				};
+			// unwrap safety: at least one of the two bind addresses is set per the match above.
+			let bind_address = bind_address_env.or(bind_address_config).unwrap();
+			let admins = admins_config
+				.into_iter()
+				.map(|admin| {
+					let token_sha256 = parse_sha256_hex(&admin.token_sha256).ok_or(format!(
+						"The token_sha256 of admin {} must be a hex-encoded SHA-256 hash",
+						admin.name
+					))?;
+					Ok(AdminCredential { name: admin.name, token_sha256 })
+				})
+				.collect::<Result<Vec<_>, String>>()?;
+			if admins.is_empty() {
+				return Err(
+					"At least one admin must be configured to enable the admin API".to_string()
+				);
+			}
+			let mut names: Vec<&str> = admins.iter().map(|admin| admin.name.as_str()).collect();
+			names.sort_unstable();
+			names.dedup();
+			if names.len() != admins.len() {
+				return Err("Admin names must be unique".to_string());
+			}
+			let approval_delay_secs =
+				approval_delay_secs.unwrap_or(DEFAULT_ADMIN_APPROVAL_DELAY_SECS);
+			if approval_delay_secs == 0 {
+				return Err("The admin approval_delay_secs must be positive, a zero delay lets \
+					admins approve their own destructive operations"
+					.to_string());
+			}
+			let approval_delay = Duration::from_secs(approval_delay_secs);
+			let request_capture_sample_rate = capture_rate.unwrap_or(0.0);
+			if !(0.0..=1.0).contains(&request_capture_sample_rate) {
+				return Err("The request capture sample rate must be between 0 and 1".to_string());
+			}
+			let request_capture_capacity = capacity.unwrap_or(DEFAULT_CAPTURE_CAPACITY);
+			if request_capture_capacity == 0 {
+				return Err("The request capture capacity must be positive".to_string());
+			}
+			Some(AdminConfiguration {
+				bind_address,
+				approval_delay,
+				admins,
+				request_capture_sample_rate,
+				request_capture_capacity,
+			})
+		},
+	};
+
+	let encryption_keys_env = read_env(ENCRYPTION_KEYS_VAR)?;
+	let encryption_key_file_env: Option<PathBuf> =
+		read_env(ENCRYPTION_KEY_FILE_VAR)?.map(PathBuf::from);
+	let current_key_version_env =
+		read_env(ENCRYPTION_CURRENT_KEY_VERSION_VAR)?
+			.map(|version| {
+				version.parse::<u32>().map_err(|e| {
+					format!("Unable to parse the current encryption key version environment variable: {}", e)
+				})
+			})
+			.transpose()?;
+	let master_key_env = read_env(ENCRYPTION_MASTER_KEY_VAR)?;
+	let vault_token_env = read_env(ENCRYPTION_VAULT_TOKEN_VAR)?;
+	let gcp_access_token_env = read_env(ENCRYPTION_GCP_ACCESS_TOKEN_VAR)?;
+	let age_identity_file_env: Option<PathBuf> =
+		read_env(ENCRYPTION_AGE_IDENTITY_FILE_VAR)?.map(PathBuf::from);
+	let (
+		keys_config,
+		key_file_config,
+		current_key_version_config,
+		wrapped_keys_config,
+		master_key_config,
+		vault_transit_config,
+		aws_kms_config,
+		gcp_kms_config,
+		age_identity_file_config,
+	) = match encryption_config {
+		Some(c) => (
+			c.keys,
+			c.key_file,
+			c.current_key_version,
+			c.wrapped_keys,
+			c.master_key,
+			c.vault_transit,
+			c.aws_kms,
+			c.gcp_kms,
+			c.age_identity_file,
+		),
+		None => (None, None, None, None, None, None, None, None, None),
+	};
+	let current_key_version = current_key_version_env.or(current_key_version_config);
+	let age_identity_file = age_identity_file_env.or(age_identity_file_config);
+	// Keys from the environment replace those from the configuration file, and a key file
+	// replaces inline keys of the same source.
+	let encryption_keys = match (encryption_key_file_env, encryption_keys_env) {
+		(Some(path), _) => Some(read_key_file(&path, age_identity_file.as_ref())?),
+		(None, Some(keys)) => Some(keys.split(',').map(str::to_string).collect()),
+		(None, None) => match key_file_config {
+			Some(path) => Some(read_key_file(&path, age_identity_file.as_ref())?),
+			None => keys_config,
+		},
+	};
+	if age_identity_file.is_some() && encryption_keys.is_none() {
+		return Err(
+			"An age identity file requires an encrypted key_file in encryption_config".to_string()
+		);
+	}
+	let master_key = master_key_env.or(master_key_config);
+	let master_key_count = [
+		master_key.is_some(),
+		vault_transit_config.is_some(),
+		aws_kms_config.is_some(),
+		gcp_kms_config.is_some(),
+	]
+	.iter()
+	.filter(|configured| **configured)
+	.count();
+	if master_key_count > 1 {
+		return Err("Only one of master_key, vault_transit, aws_kms and gcp_kms may be set in \
+			encryption_config"
+			.to_string());
+	}
+	let master_key = if let Some(master_key) = master_key {
+		Some(MasterKeyConfig::Local(
+			LocalMasterKey::from_hex(&master_key)
+				.map_err(|e| format!("Invalid encryption key configuration: {}", e))?,
+		))
+	} else if let Some(config) = vault_transit_config {
+		Some(MasterKeyConfig::VaultTransit(VaultTransitConfig {
+			address: config.address,
+			token: read_config(
+				vault_token_env,
+				config.token,
+				"The Vault token",
+				ENCRYPTION_VAULT_TOKEN_VAR,
+			)?,
+			key_name: config.key_name,
+			mount: config.mount.unwrap_or(DEFAULT_VAULT_TRANSIT_MOUNT.to_string()),
+		}))
+	} else if let Some(config) = aws_kms_config {
+		let region = read_env(AWS_REGION_VAR)?.or(config.region).ok_or(format!(
+			"The AWS KMS region must be set in [encryption_config.aws_kms] or by {}",
+			AWS_REGION_VAR
+		))?;
+		let credential = |var_name: &str| {
+			read_env(var_name)?.ok_or(format!("AWS KMS requires the {} variable", var_name))
+		};
+		Some(MasterKeyConfig::AwsKms(AwsKmsConfig {
+			key_id: config.key_id,
+			region,
+			endpoint: config.endpoint,
+			access_key_id: credential(AWS_ACCESS_KEY_ID_VAR)?,
+			secret_access_key: credential(AWS_SECRET_ACCESS_KEY_VAR)?,
+			session_token: read_env(AWS_SESSION_TOKEN_VAR)?,
+		}))
+	} else {
+		gcp_kms_config.map(|config| {
+			MasterKeyConfig::GcpKms(GcpKmsConfig {
+				key_name: config.key_name,
+				access_token: gcp_access_token_env.or(config.access_token),
+			})
+		})
+	};
+	let encryption_keys = match (encryption_keys, wrapped_keys_config, master_key) {
+		(Some(_), Some(_), _) => {
+			return Err("Either plaintext or wrapped encryption keys may be configured, not both"
+				.to_string())
+		},
+		(Some(keys), None, None) => Some(EncryptionKeys::Static(
+			StaticKeyProvider::from_key_entries(
+				keys.iter().map(String::as_str),
+				current_key_version,
+			)
+			.map_err(|e| format!("Invalid encryption key configuration: {}", e))?,
+		)),
+		(_, None, Some(_)) => {
+			return Err("A master key requires wrapped_keys in encryption_config".to_string())
+		},
+		(None, Some(wrapped_keys), Some(master_key)) => Some(EncryptionKeys::Envelope {
+			wrapped_keys: WrappedDataKeys::from_key_entries(
+				wrapped_keys.iter().map(String::as_str),
+				current_key_version,
+			)
+			.map_err(|e| format!("Invalid encryption key configuration: {}", e))?,
+			master_key,
+		}),
+		(None, Some(_), None) => {
+			return Err(
+				"Wrapped encryption keys require one of master_key, vault_transit, aws_kms \
+				or gcp_kms"
+					.to_string(),
+			)
+		},
+		(None, None, None) => None,
+	};
+
+	let analytics_enabled_env = read_env(ANALYTICS_ENABLED_VAR)?
+		.map(|enabled| {
+			enabled.parse::<bool>().map_err(|e| {
+				format!("Unable to parse the analytics enabled environment variable: {}", e)
+			})
+		})
+		.transpose()?;
+	let (analytics_enabled_config, report_interval_secs, epsilon) = match analytics_config {
+		Some(c) => (c.enabled, c.report_interval_secs, c.epsilon),
+		None => (None, None, None),
+	};
+	let analytics_config = if analytics_enabled_env.or(analytics_enabled_config).unwrap_or(false) {
+		let report_interval_secs =
+			report_interval_secs.unwrap_or(DEFAULT_ANALYTICS_REPORT_INTERVAL_SECS);
+		let epsilon = epsilon.unwrap_or(DEFAULT_ANALYTICS_EPSILON);
+		if report_interval_secs == 0 {
+			return Err("The analytics report interval must be positive".to_string());
+		}
+		if !(epsilon.is_finite() && epsilon > 0.0) {
+			return Err("The analytics epsilon must be a positive number".to_string());
+		}
+		Some(AnalyticsConfig {
+			report_interval: Duration::from_secs(report_interval_secs),
+			epsilon,
+		})
+	} else {
+		None
+	};
+
+	let nostr_secret_key_env = read_env(NOSTR_SECRET_KEY_VAR)?;
+	let nostr_notification_config = match (nostr_secret_key_env, nostr_notification_config) {
+		(None, None) | (None, Some(NostrNotificationTomlConfig { secret_key: None, .. })) => None,
+		(secret_key_env, config) => {
+			let config = config.ok_or(
+				"Nostr notifications require relays to be set in the configuration file"
+					.to_string(),
+			)?;
+			// unwrap safety: at least one of the two secret keys is set per the match above.
+			let secret_key = secret_key_env.or(config.secret_key).unwrap();
+			let keypair = nostr_notifications::parse_secret_key(&secret_key)?;
+			let relays = config.relays.unwrap_or_default();
+			if relays.is_empty() {
+				return Err("At least one Nostr relay must be configured".to_string());
+			}
+			let key_prefixes = config.key_prefixes.unwrap_or_default();
+			if key_prefixes.is_empty() {
+				return Err("At least one key prefix must be configured for Nostr notifications"
+					.to_string());
+			}
+			Some(NostrNotificationConfig {
+				keypair,
+				relays,
+				key_prefixes,
+				registration_key: config
+					.registration_key
+					.unwrap_or_else(|| DEFAULT_REGISTRATION_KEY.to_string()),
+				min_interval: config
+					.min_interval_secs
+					.map(Duration::from_secs)
+					.unwrap_or(DEFAULT_MIN_INTERVAL),
+				event_kind: config.event_kind.unwrap_or(DEFAULT_EVENT_KIND),
+			})
+		},
+	};
+
+	let security_notification_config =
+		security_notification_config.map(|config| SecurityNotificationConfig {
+			preferences_key: config
+				.preferences_key
+				.unwrap_or_else(|| DEFAULT_PREFERENCES_KEY.to_string()),
+			email_webhook_url: config.email_webhook_url,
+			allowed_webhook_hosts: config.allowed_webhook_hosts,
+			subscriptions: config.subscriptions.unwrap_or_default(),
+		});
+
+	let (merge_rules, max_global_version_retries) = match merge_config {
+		Some(c) => (c.rules, c.max_global_version_retries.unwrap_or(0)),
+		None => (None, 0),
+	};
+	let merge_strategies = merge_rules
+		.unwrap_or_default()
+		.into_iter()
+		.map(|rule| {
+			let strategy = builtin_merge_strategy(&rule.strategy)
+				.ok_or(format!("Unknown merge strategy: {}", rule.strategy))?;
+			Ok((rule.key_prefix, strategy))
+		})
+		.collect::<Result<Vec<_>, String>>()?;
+
+	let store_templates = store_template_config
+		.and_then(|config| config.templates)
+		.unwrap_or_default()
+		.into_iter()
+		.map(parse_store_template)
+		.collect::<Result<Vec<_>, String>>()?;
+	for (i, template) in store_templates.iter().enumerate() {
+		if store_templates[..i].iter().any(|other| other.name == template.name) {
+			return Err(format!("Duplicate store template: {}", template.name));
+		}
+	}
+
+	let quota_config = quota_config
+		.map(|config| {
+			let default_limits = QuotaLimits {
+				max_bytes_per_user: config.max_bytes_per_user,
+				max_objects_per_user: config.max_objects_per_user,
+			};
+			let profiles: HashMap<String, QuotaLimits> = config
+				.profiles
+				.unwrap_or_default()
+				.into_iter()
+				.map(|(name, profile)| {
+					let limits = QuotaLimits {
+						max_bytes_per_user: profile.max_bytes_per_user,
+						max_objects_per_user: profile.max_objects_per_user,
+					};
+					(name, limits)
+				})
+				.collect();
+			if default_limits.is_unlimited() && profiles.values().all(QuotaLimits::is_unlimited) {
+				return Err(
+					"At least one of max_bytes_per_user and max_objects_per_user must be set"
+						.to_string(),
+				);
+			}
+			let soft_limit_ratio = config.soft_limit_ratio.unwrap_or(DEFAULT_SOFT_LIMIT_RATIO);
+			if !(soft_limit_ratio > 0.0 && soft_limit_ratio <= 1.0) {
+				return Err("The quota soft_limit_ratio must be between 0 and 1".to_string());
+			}
+			let enforce = config.enforce.unwrap_or(true);
+			Ok(QuotaConfig { default_limits, profiles, soft_limit_ratio, enforce })
+		})
+		.transpose()?;
+	if let Some(quota_config) = quota_config.as_ref() {
+		let oidc_profile_claim = oidc_auth_config.as_ref().and_then(|c| c.profile_claim.as_ref());
+		if !quota_config.profiles.is_empty()
+			&& jwt_profile_claim.is_none()
+			&& oidc_profile_claim.is_none()
+		{
+			return Err("Quota profiles require the profile_claim under jwt_auth_config or \
+				oidc_auth_config to be set"
+				.to_string());
+		}
+		if !storage.provides_metadata() {
+			return Err(format!(
+				"Storage quotas are not supported with the {} storage backend",
+				storage.name()
+			));
+		}
+	}
+
+	let session_limit_config = session_limit_config
+		.map(|config| {
+			if config.max_sessions_per_user == 0 {
+				return Err("The max_sessions_per_user must be at least 1".to_string());
+			}
+			let window = match config.window_secs {
+				Some(0) => return Err("The session window_secs must be positive".to_string()),
+				Some(window_secs) => Duration::from_secs(window_secs),
+				None => DEFAULT_SESSION_WINDOW,
+			};
+			Ok(SessionLimitConfig { max_sessions_per_user: config.max_sessions_per_user, window })
+		})
+		.transpose()?;
+
+	let rate_limit_config = rate_limit_config
+		.map(|config| {
+			if !(config.requests_per_second.is_finite() && config.requests_per_second > 0.0) {
+				return Err("The rate limit's requests_per_second must be positive".to_string());
+			}
+			// Unless configured otherwise, clients may make a second's worth of requests at once.
+			let burst = config.burst.unwrap_or(config.requests_per_second.ceil() as u32);
+			if burst == 0 {
+				return Err("The rate limit's burst must be at least 1".to_string());
+			}
+			Ok(RateLimitConfig { requests_per_second: config.requests_per_second, burst })
+		})
+		.transpose()?;
+
+	let load_shedding_config = load_shedding_config
+		.map(|config| {
+			let window =
+				Duration::from_secs(config.window_secs.unwrap_or(DEFAULT_WINDOW.as_secs()));
+			if window.is_zero() {
+				return Err("The load shedding window_secs must be positive".to_string());
+			}
+			let degraded_failure_ratio =
+				config.degraded_failure_ratio.unwrap_or(DEFAULT_DEGRADED_FAILURE_RATIO);
+			let unhealthy_failure_ratio =
+				config.unhealthy_failure_ratio.unwrap_or(DEFAULT_UNHEALTHY_FAILURE_RATIO);
+			let is_ratio = |ratio: f64| ratio > 0.0 && ratio <= 1.0;
+			if !is_ratio(degraded_failure_ratio) || !is_ratio(unhealthy_failure_ratio) {
+				return Err("Load shedding failure ratios must be in (0, 1]".to_string());
+			}
+			if degraded_failure_ratio > unhealthy_failure_ratio {
+				return Err("The load shedding degraded_failure_ratio must not exceed the \
+					 unhealthy_failure_ratio"
+					.to_string());
+			}
+			Ok(LoadSheddingConfig {
+				window,
+				min_requests: config.min_requests.unwrap_or(DEFAULT_MIN_REQUESTS),
+				degraded_failure_ratio,
+				unhealthy_failure_ratio,
+			})
+		})
+		.transpose()?;
+
+	let backup_monitor_config = backup_monitor_config
+		.map(|config| {
+			if !matches!(storage, BackendConfig::Postgres(_)) {
+				return Err(format!(
+					"Backup monitoring is not supported with the {} storage backend",
+					storage.name()
+				));
+			}
+			let check_interval = match config.check_interval_secs {
+				Some(0) => {
+					return Err("The backup check_interval_secs must be positive".to_string())
+				},
+				Some(secs) => Duration::from_secs(secs),
+				None => DEFAULT_CHECK_INTERVAL,
+			};
+			Ok(BackupMonitorConfig {
+				check_interval,
+				max_archive_age: config
+					.max_archive_age_secs
+					.map_or(DEFAULT_MAX_ARCHIVE_AGE, Duration::from_secs),
+				fail_readiness: config.fail_readiness.unwrap_or(false),
+				alert_command: config.alert_command,
+			})
+		})
+		.transpose()?;
+
+	let certificate_expiry_config = match certificate_expiry_config {
+		Some(config) => {
+			let check_interval = match config.check_interval_secs {
+				Some(0) => {
+					return Err("The certificate check_interval_secs must be positive".to_string())
+				},
+				Some(secs) => Duration::from_secs(secs),
+				None => cert_expiry::DEFAULT_CHECK_INTERVAL,
+			};
+			let warning_period =
+				config.warning_period_days.map_or(cert_expiry::DEFAULT_WARNING_PERIOD, |days| {
+					Duration::from_secs(days * 24 * 60 * 60)
+				});
+			CertificateExpiryConfig { check_interval, warning_period }
+		},
+		None => CertificateExpiryConfig {
+			check_interval: cert_expiry::DEFAULT_CHECK_INTERVAL,
+			warning_period: cert_expiry::DEFAULT_WARNING_PERIOD,
+		},
+	};
+
+	let delayed_deletion_config = delayed_deletion_config
+		.map(|config| {
+			if !storage.provides_metadata() {
+				return Err(format!(
+					"Delayed deletion is not supported with the {} storage backend",
+					storage.name()
+				));
+			}
+			if config.key_prefixes.is_empty()
+				|| config.key_prefixes.iter().any(|prefix| prefix.is_empty())
+			{
+				return Err("Delayed deletion requires non-empty key_prefixes".to_string());
+			}
+			let delay = match config.delay_secs {
+				Some(0) => return Err("The deletion delay_secs must be positive".to_string()),
+				Some(secs) => Duration::from_secs(secs),
+				None => DEFAULT_DELETION_DELAY,
+			};
+			let check_interval = match config.check_interval_secs {
+				Some(0) => {
+					return Err("The deletion check_interval_secs must be positive".to_string())
+				},
+				Some(secs) => Duration::from_secs(secs),
+				None => DEFAULT_DELETION_CHECK_INTERVAL,
+			};
+			Ok(DelayedDeletionConfig { key_prefixes: config.key_prefixes, delay, check_interval })
+		})
+		.transpose()?;
+
+	// Statements of timed out requests would otherwise keep running, holding their connections.
+	if let (BackendConfig::Postgres(postgres), Some(request_timeout)) =
+		(&mut storage, request_timeout)
+	{
+		postgres.pool_config.statement_timeout.get_or_insert(request_timeout);
+	}
+
+	// Keeping usage up to date serializes the writes of each user, which only quotas need.
+	if let BackendConfig::Postgres(postgres) = &mut storage {
+		postgres.track_usage = quota_config.is_some();
+	}
+
+	let soft_deletion_config = soft_deletion_config
+		.map(|config| {
+			let BackendConfig::Postgres(postgres) = &mut storage else {
+				return Err(format!(
+					"Soft deletion is not supported with the {} storage backend",
+					storage.name()
+				));
+			};
+			postgres.soft_deletion = true;
+			let retention = match config.retention_secs {
+				Some(0) => {
+					return Err("The soft deletion retention_secs must be positive".to_string())
+				},
+				Some(secs) => Duration::from_secs(secs),
+				None => DEFAULT_TOMBSTONE_RETENTION,
+			};
+			let purge_interval = match config.purge_interval_secs {
+				Some(0) => {
+					return Err("The soft deletion purge_interval_secs must be positive".to_string())
+				},
+				Some(secs) => Duration::from_secs(secs),
+				None => DEFAULT_TOMBSTONE_PURGE_INTERVAL,
+			};
+			Ok(SoftDeletionConfig { retention, purge_interval })
+		})
+		.transpose()?;
+
+	let change_stream_config = change_stream_config
+		.map(|config| {
+			let max_subscriptions_per_user =
+				config.max_subscriptions_per_user.unwrap_or(DEFAULT_MAX_SUBSCRIPTIONS_PER_USER);
+			let buffered_events = config.buffered_events.unwrap_or(DEFAULT_BUFFERED_EVENTS);
+			if max_subscriptions_per_user == 0 || buffered_events == 0 {
+				return Err(
+					"max_subscriptions_per_user and buffered_events must be greater than 0"
+						.to_string(),
+				);
+			}
+			Ok(ChangeStreamConfig { max_subscriptions_per_user, buffered_events })
+		})
+		.transpose()?;
+
+	let maintenance_config = maintenance_config
+		.map(|config| {
+			if !matches!(storage, BackendConfig::Postgres(_)) {
+				return Err(format!(
+					"Maintenance jobs are not supported with the {} storage backend",
+					storage.name()
+				));
+			}
+			let interval = |name: &str, secs: Option<u64>, default| match secs {
+				Some(0) => Err(format!("The maintenance {} must be positive", name)),
+				Some(secs) => Ok(Duration::from_secs(secs)),
+				None => Ok(default),
+			};
+			Ok(MaintenanceConfig {
+				share_token_reaping_interval: interval(
+					"share_token_reaping_interval_secs",
+					config.share_token_reaping_interval_secs,
+					DEFAULT_SHARE_TOKEN_REAPING_INTERVAL,
+				)?,
+				usage_recomputation_interval: interval(
+					"usage_recomputation_interval_secs",
+					config.usage_recomputation_interval_secs,
+					DEFAULT_USAGE_RECOMPUTATION_INTERVAL,
+				)?,
+				orphan_cleanup_interval: interval(
+					"orphan_cleanup_interval_secs",
+					config.orphan_cleanup_interval_secs,
+					DEFAULT_ORPHAN_CLEANUP_INTERVAL,
+				)?,
+				vacuum_hint_interval: interval(
+					"vacuum_hint_interval_secs",
+					config.vacuum_hint_interval_secs,
+					DEFAULT_VACUUM_HINT_INTERVAL,
+				)?,
+			})
+		})
+		.transpose()?;
+
+	let audit_config = audit_config
+		.filter(|config| config.enabled.unwrap_or(true))
+		.map(|config| {
+			let sink = match config.sink.as_deref().unwrap_or("database") {
+				"database" => {
+					if !matches!(storage, BackendConfig::Postgres(_)) {
+						return Err(format!(
+							"The database audit sink is not supported with the {} storage backend",
+							storage.name()
+						));
+					}
+					if config.file.is_some() {
+						return Err("The audit file is only used by the file sink".to_string());
+					}
+					let retention = match config.retention_days {
+						Some(0) => {
+							return Err("The audit retention_days must be positive".to_string())
+						},
+						Some(days) => Some(Duration::from_secs(days * 24 * 60 * 60)),
+						None => None,
+					};
+					AuditSink::Database { retention }
+				},
+				"file" => {
+					if config.retention_days.is_some() {
+						return Err(
+							"The audit retention_days is only supported by the database sink"
+								.to_string(),
+						);
+					}
+					AuditSink::File(config.file.ok_or("The file audit sink requires a file")?)
+				},
+				other => {
+					return Err(format!("Unknown audit sink {}, expected database or file", other))
+				},
+			};
+			Ok(AuditConfig { sink })
+		})
+		.transpose()?;
+
+	let tls_config = load_tls_configuration(tls)?;
+
+	let probe_authorization_env = read_env(PROBE_AUTHORIZATION_VAR)?;
+	let probe_signing_key_env = read_env(PROBE_SIGNING_KEY_VAR)?;
+	let probe_config = probe_config
+		.map(|config| {
+			let interval = match config.interval_secs {
+				Some(0) => return Err("The probe interval_secs must be positive".to_string()),
+				Some(secs) => Duration::from_secs(secs),
+				None => synthetic_probe::DEFAULT_INTERVAL,
+			};
+			let timeout = match config.timeout_secs {
+				Some(0) => return Err("The probe timeout_secs must be positive".to_string()),
+				Some(secs) => Duration::from_secs(secs),
+				None => synthetic_probe::DEFAULT_TIMEOUT,
+			};
+			let url = match config.url {
+				Some(url) if url.starts_with("https://") || url.starts_with("http://") => {
+					url.trim_end_matches('/').to_string()
+				},
+				Some(_) => return Err("The probe url must be an http(s) URL".to_string()),
+				// The certificate of the listener is unlikely to be issued for its bind address.
+				None if tls_config.is_some() => {
+					return Err("The probe url must be set when serving TLS".to_string())
+				},
+				None => format!("http://{}{}", probe_address(&bind_address), BASE_PATH_PREFIX),
+			};
+			let store_id =
+				config.store_id.unwrap_or_else(|| synthetic_probe::DEFAULT_STORE_ID.to_string());
+			if store_id.is_empty() {
+				return Err("The probe store_id must not be empty".to_string());
+			}
+			let authorization = probe_authorization_env.or(config.authorization);
+			let signing_key = probe_signing_key_env.or(config.signing_key);
+			let credentials = match (authorization, signing_key) {
+				(Some(_), Some(_)) => {
+					return Err("Only one of the probe authorization and signing_key may be set"
+						.to_string())
+				},
+				(Some(authorization), None) => ProbeCredentials::Authorization(authorization),
+				#[cfg(feature = "sigs")]
+				(None, Some(signing_key)) => {
+					let secret_key = parse_sha256_hex(&signing_key)
+						.and_then(|bytes| secp256k1::SecretKey::from_byte_array(bytes).ok())
+						.ok_or(
+							"The probe signing_key must be a hex-encoded secp256k1 secret key",
+						)?;
+					ProbeCredentials::SigningKey(secret_key)
+				},
+				#[cfg(not(feature = "sigs"))]
+				(None, Some(_)) => {
+					return Err("Probing with a signing_key requires building with the `sigs` \
+						feature"
+						.to_string())
+				},
+				(None, None) => ProbeCredentials::None,
+			};
+			Ok(ProbeConfig { interval, url, store_id, timeout, credentials })
+		})
+		.transpose()?;
+
+	let access_rule_policy = access_policy_config.map(parse_access_policy).transpose()?;
+
+	let client_compatibility =
+		client_compatibility_config.map(parse_client_compatibility).transpose()?;
+
+	let store_namespace_config =
+		store_namespace_config.map(parse_store_namespace_config).transpose()?;
+	if let Some(store_namespace_config) = store_namespace_config.as_ref() {
+		if store_namespace_config.max_stores_per_user.is_some() && !storage.provides_metadata() {
+			return Err(format!(
+				"Store limits are not supported with the {} storage backend",
+				storage.name()
+			));
+		}
+	}
+
+	let otel_config = otel.map(parse_otel_config).transpose()?.flatten();
+
+	let object_store_url_config =
+		object_store_config.as_ref().and_then(|config| config.url.clone());
+	let object_store_url = read_env(OBJECT_STORE_URL_VAR)?.or(object_store_url_config);
+	#[cfg(not(feature = "object-store"))]
+	if object_store_url.is_some() {
+		return Err(
+			"Offloading values to an object store requires building with the `object-store` feature"
+				.to_string(),
+		);
+	}
+	#[cfg(feature = "object-store")]
+	let offloading_config = object_store_url.map(|url| OffloadingConfig {
+		url,
+		min_offloaded_value_size: object_store_config
+			.and_then(|config| config.min_offloaded_value_size)
+			.unwrap_or(impls::offloading_store::DEFAULT_MIN_OFFLOADED_VALUE_SIZE),
+	});
+
+	let grpc_bind_address_env = read_env(GRPC_BIND_ADDR_VAR)?;
+	let grpc_bind_address =
+		grpc_bind_address_env.or(grpc_config.and_then(|config| config.bind_address));
+	#[cfg(not(feature = "grpc"))]
+	if grpc_bind_address.is_some() {
+		return Err("The gRPC transport requires building with the `grpc` feature".to_string());
+	}
+
+	Ok(Configuration {
+		bind_address,
+		max_request_body_size,
+		body_read_timeout,
+		response_write_timeout,
+		request_timeout,
+		require_client_encryption: require_client_encryption.unwrap_or(false),
+		maintenance_mode: maintenance_mode.unwrap_or(false),
+		tls_config,
+		log_file,
+		log_level,
+		log_format,
+		rsa_pem,
+		jwks_config,
+		jwt_algorithm,
+		jwt_profile_claim,
+		jwt_scope_claim,
+		jwt_store_claim,
+		api_key_auth_config,
+		nostr_auth_config,
+		l402_config,
+		oidc_auth_config,
+		auth_chain,
+		storage,
+		#[cfg(feature = "object-store")]
+		offloading_config,
+		admin_config,
+		encryption_keys,
+		analytics_config,
+		nostr_notification_config,
+		security_notification_config,
+		merge_strategies,
+		max_global_version_retries,
+		store_templates,
+		quota_config,
+		session_limit_config,
+		rate_limit_config,
+		load_shedding_config,
+		backup_monitor_config,
+		certificate_expiry_config,
+		probe_config,
+		delayed_deletion_config,
+		soft_deletion_config,
+		change_stream_config,
+		maintenance_config,
+		audit_config,
+		#[cfg(feature = "grpc")]
+		grpc_bind_address,
+		access_rule_policy,
+		client_compatibility,
+		store_namespace_config,
+		otel_config,
+	})
+}
+
+fn load_postgres_configuration(
+	postgresql_config: Option<PostgreSQLConfig>,
+) -> Result<PostgresBackendConfig, String> {
+	// The settings of the DSN are overridden by those given separately.
+	let dsn =
+		read_env(PSQL_DSN_VAR)?.map(|dsn| env_config::parse_postgres_dsn(&dsn)).transpose()?;
+	let (dsn_username, dsn_password, dsn_address, dsn_default_db, dsn_vss_db, dsn_tls) = match dsn {
+		Some(dsn) => (
+			Some(dsn.username),
+			Some(dsn.password),
+			Some(dsn.address),
+			Some(dsn.default_database),
+			Some(dsn.vss_database),
+			dsn.tls.then_some(None),
+		),
+		None => (None, None, None, None, None, None),
+	};
+	let username_env = read_env(PSQL_USER_VAR)?.or(dsn_username);
+	let password_env = read_env(PSQL_PASS_VAR)?.or(dsn_password);
+	let address_env: Option<String> = read_env(PSQL_ADDR_VAR)?.or(dsn_address);
 	let default_db_env = read_env(PSQL_DB_VAR)?;
-	let vss_db_env = read_env(PSQL_VSS_DB_VAR)?;
+	let vss_db_env = read_env(PSQL_VSS_DB_VAR)?.or(dsn_vss_db);
 	let tls_config_env = read_env(PSQL_TLS_VAR)?;
 	let crt_pem_env = read_env(PSQL_CERT_PEM_VAR)?;
+	let value_compression_env = read_env(PSQL_VALUE_COMPRESSION_VAR)?;
+	let max_value_size_env = read_env(PSQL_MAX_VALUE_SIZE_VAR)?
+		.map(|size| {
+			size.parse::<usize>().map_err(|e| {
+				format!("Unable to parse the maximum value size environment variable: {}", e)
+			})
+		})
+		.transpose()?;
+	let notify_changes_env = read_env(PSQL_NOTIFY_CHANGES_VAR)?
+		.map(|enabled| {
+			enabled.parse::<bool>().map_err(|e| {
+				format!("Unable to parse the change notifications environment variable: {}", e)
+			})
+		})
+		.transpose()?;
+	let key_history_versions_env = read_env(PSQL_KEY_HISTORY_VERSIONS_VAR)?
+		.map(|versions| {
+			versions.parse::<usize>().map_err(|e| {
+				format!("Unable to parse the key history versions environment variable: {}", e)
+			})
+		})
+		.transpose()?;
+	let migrate_on_startup_env = read_env(PSQL_MIGRATE_ON_STARTUP_VAR)?
+		.map(|enabled| {
+			enabled.parse::<bool>().map_err(|e| {
+				format!("Unable to parse the migrate on startup environment variable: {}", e)
+			})
+		})
+		.transpose()?;
+	let replica_address_env = read_env(PSQL_REPLICA_ADDR_VAR)?;
+	let require_primary_reads_env = read_env(PSQL_REQUIRE_PRIMARY_READS_VAR)?
+		.map(|enabled| {
+			enabled.parse::<bool>().map_err(|e| {
+				format!("Unable to parse the require primary reads environment variable: {}", e)
+			})
+		})
+		.transpose()?;
+	let transient_retries_env = read_env(PSQL_TRANSIENT_RETRIES_VAR)?
+		.map(|retries| {
+			retries.parse::<usize>().map_err(|e| {
+				format!("Unable to parse the transient retries environment variable: {}", e)
+			})
+		})
+		.transpose()?;
 
 	let (
 		username_config,
@@ -298,16 +2416,43 @@ pub(crate) fn load_configuration(config_file_path: Option<&str>) -> Result<Confi
 		default_db_config,
 		vss_db_config,
 		tls_config,
+		value_compression_config,
+		value_compression_level,
+		max_value_size_config,
+		notify_changes_config,
+		key_history_versions_config,
+		migrate_on_startup_config,
+		replica_address_config,
+		require_primary_reads_config,
+		transient_retries_config,
+		pool_config,
+		read_role_config,
+		admin_role_config,
 	) = match postgresql_config {
 		Some(c) => (
 			c.username,
-			c.password,
+			read_secret_config(c.password, c.password_file, "password")?,
 			c.address,
 			c.default_database,
 			c.vss_database,
 			c.tls.map(|tls| tls.crt_pem),
+			c.value_compression,
+			c.value_compression_level,
+			c.max_value_size,
+			c.notify_changes,
+			c.key_history_versions,
+			c.migrate_on_startup,
+			c.replica_address,
+			c.require_primary_reads,
+			c.transient_retries,
+			c.pool,
+			c.read_role,
+			c.admin_role,
+		),
+		None => (
+			None, None, None, None, None, None, None, None, None, None, None, None, None, None,
+			None, None, None, None,
 		),
-		None => (None, None, None, None, None, None),
 	};
 
 	let username =
@@ -316,9 +2461,10 @@ pub(crate) fn load_configuration(config_file_path: Option<&str>) -> Result<Confi
 		read_config(password_env, password_config, "PostgreSQL database password", PSQL_PASS_VAR)?;
 	let address =
 		read_config(address_env, address_config, "PostgreSQL service address", PSQL_ADDR_VAR)?;
+	// A DSN names only the VSS database, the default one is assumed unless given separately.
 	let default_db = read_config(
 		default_db_env,
-		default_db_config,
+		default_db_config.or(dsn_default_db),
 		"PostgreSQL default database name",
 		PSQL_DB_VAR,
 	)?;
@@ -326,19 +2472,415 @@ pub(crate) fn load_configuration(config_file_path: Option<&str>) -> Result<Confi
 		read_config(vss_db_env, vss_db_config, "PostgreSQL vss database name", PSQL_VSS_DB_VAR)?;
 
 	let tls_config =
-		crt_pem_env.map(|pem| Some(pem)).or(tls_config_env.map(|_| None)).or(tls_config);
+		crt_pem_env.map(Some).or(tls_config_env.map(|_| None)).or(dsn_tls).or(tls_config);
 
-	let postgresql_prefix = format!("postgresql://{}:{}@{}", username, password, address);
+	let value_compression = parse_value_compression(
+		value_compression_env.or(value_compression_config).as_deref().unwrap_or("none"),
+		value_compression_level,
+	)?;
 
-	Ok(Configuration {
-		bind_address,
-		max_request_body_size,
-		log_file,
-		log_level,
-		rsa_pem,
-		postgresql_prefix,
+	let max_value_size = max_value_size_env.or(max_value_size_config);
+	if max_value_size == Some(0) {
+		return Err("The PostgreSQL max_value_size must be positive".to_string());
+	}
+
+	let key_history_versions = key_history_versions_env.or(key_history_versions_config);
+	if key_history_versions == Some(0) {
+		return Err("The PostgreSQL key_history_versions must be positive".to_string());
+	}
+
+	let pool_config = pool_config.map(load_postgres_pool).transpose()?.unwrap_or_default();
+
+	// The DSN is URL-decoded when connecting, so credentials are encoded in case they contain
+	// characters such as `@`, `/` or `%`.
+	let dsn_prefix = |(username, password): &(String, String), address: &str| {
+		format!(
+			"postgresql://{}:{}@{}",
+			percent_encode(username),
+			percent_encode(password),
+			address
+		)
+	};
+	let credentials = (username, password);
+	let prefix = dsn_prefix(&credentials, &address);
+	let read_credentials =
+		load_postgres_role("read", read_role_config, (PSQL_READ_USER_VAR, PSQL_READ_PASS_VAR))?;
+	let admin_credentials =
+		load_postgres_role("admin", admin_role_config, (PSQL_ADMIN_USER_VAR, PSQL_ADMIN_PASS_VAR))?;
+	let read_prefix = read_credentials.as_ref().map(|role| dsn_prefix(role, &address));
+	let admin_prefix = admin_credentials.as_ref().map(|role| dsn_prefix(role, &address));
+	// The replica is read as the read role, if one is set up.
+	let replica_prefix = replica_address_env.or(replica_address_config).map(|replica_address| {
+		dsn_prefix(read_credentials.as_ref().unwrap_or(&credentials), &replica_address)
+	});
+	// Malformed addresses would otherwise only fail the first connection.
+	let prefixes = [Some(&prefix), read_prefix.as_ref(), admin_prefix.as_ref()];
+	for prefix in prefixes.into_iter().chain([replica_prefix.as_ref()]).flatten() {
+		validate_endpoint(prefix, &vss_db)
+			.map_err(|e| format!("{}, check the PostgreSQL addresses and credentials", e))?;
+	}
+	Ok(PostgresBackendConfig {
+		prefix,
 		default_db,
 		vss_db,
 		tls_config,
+		value_compression,
+		max_value_size,
+		read_prefix,
+		admin_prefix,
+		notify_changes: notify_changes_env.or(notify_changes_config).unwrap_or(false),
+		soft_deletion: false,
+		key_history_versions,
+		migrate_on_startup: migrate_on_startup_env.or(migrate_on_startup_config).unwrap_or(true),
+		replica_prefix,
+		require_primary_reads: require_primary_reads_env
+			.or(require_primary_reads_config)
+			.unwrap_or(false),
+		pool_config,
+		transient_retries: transient_retries_env
+			.or(transient_retries_config)
+			.unwrap_or(DEFAULT_TRANSIENT_RETRIES),
+		track_usage: true,
+	})
+}
+
+/// Returns the settings of the connection pools given in the `[postgresql_config.pool]` table.
+fn load_postgres_pool(config: PostgreSQLPoolConfig) -> Result<PoolConfig, String> {
+	let defaults = PoolConfig::default();
+	let max_size = config.max_size.unwrap_or(defaults.max_size);
+	if max_size == 0 {
+		return Err("The PostgreSQL pool max_size must be positive".to_string());
+	}
+	if config.min_idle.is_some_and(|min_idle| min_idle > max_size) {
+		return Err("The PostgreSQL pool min_idle must not exceed its max_size".to_string());
+	}
+	let timeout = |name: &str, secs: Option<u64>| match secs {
+		Some(0) => Err(format!("The PostgreSQL pool {} must be positive", name)),
+		secs => Ok(secs.map(Duration::from_secs)),
+	};
+	Ok(PoolConfig {
+		max_size,
+		min_idle: config.min_idle,
+		connection_timeout: timeout("connection_timeout_secs", config.connection_timeout_secs)?,
+		idle_timeout: timeout("idle_timeout_secs", config.idle_timeout_secs)?,
+		max_lifetime: timeout("max_lifetime_secs", config.max_lifetime_secs)?,
+		statement_timeout: timeout("statement_timeout_secs", config.statement_timeout_secs)?,
 	})
 }
+
+/// Returns the username and password of the role `name` operations connect as, if a separate
+/// role is set up through the `[postgresql_config.<name>_role]` table or the environment.
+fn load_postgres_role(
+	name: &str, role_config: Option<PostgreSQLRoleConfig>, (user_var, pass_var): (&str, &str),
+) -> Result<Option<(String, String)>, String> {
+	let username_env = read_env(user_var)?;
+	let password_env = read_env(pass_var)?;
+	let (username_config, password_config) = match role_config {
+		Some(c) => (c.username, read_secret_config(c.password, c.password_file, "password")?),
+		None => (None, None),
+	};
+	let Some(username) = username_env.or(username_config) else {
+		if password_env.is_some() || password_config.is_some() {
+			return Err(format!("The PostgreSQL {} role password requires a username", name));
+		}
+		return Ok(None);
+	};
+	let item = format!("PostgreSQL {} role password", name);
+	let password = read_config(password_env, password_config, &item, pass_var)?;
+	Ok(Some((username, password)))
+}
+
+fn parse_value_compression(name: &str, level: Option<i32>) -> Result<ValueCompression, String> {
+	match (name, level) {
+		("none", None) => Ok(ValueCompression::None),
+		("lz4", None) => Ok(ValueCompression::Lz4),
+		("zstd", level) => {
+			let level = level.unwrap_or(DEFAULT_ZSTD_LEVEL);
+			let levels = zstd::compression_level_range();
+			if !levels.contains(&level) {
+				return Err(format!(
+					"value_compression_level must be between {} and {}",
+					levels.start(),
+					levels.end()
+				));
+			}
+			Ok(ValueCompression::Zstd { level })
+		},
+		("none" | "lz4", Some(_)) => {
+			Err("value_compression_level only applies to zstd compression".to_string())
+		},
+		(name, _) => {
+			Err(format!("Invalid value compression {}, expected one of none, zstd or lz4", name))
+		},
+	}
+}
+
+fn load_tls_configuration(
+	tls_config: Option<ServerTlsTomlConfig>,
+) -> Result<Option<ServerTlsConfig>, String> {
+	let cert_path_env = read_env(TLS_CERT_PATH_VAR)?.map(PathBuf::from);
+	let key_path_env = read_env(TLS_KEY_PATH_VAR)?.map(PathBuf::from);
+	let client_ca_path_env = read_env(TLS_CLIENT_CA_PATH_VAR)?.map(PathBuf::from);
+	let (cert_path_config, key_path_config, client_ca_path_config) = match tls_config {
+		Some(c) => (c.cert_path, c.key_path, c.client_ca_path),
+		None => (None, None, None),
+	};
+
+	let cert_path = cert_path_env.or(cert_path_config);
+	let key_path = key_path_env.or(key_path_config);
+	let client_ca_path = client_ca_path_env.or(client_ca_path_config);
+	match (cert_path, key_path) {
+		(Some(cert_path), Some(key_path)) => {
+			Ok(Some(ServerTlsConfig { cert_path, key_path, client_ca_path }))
+		},
+		(None, None) if client_ca_path.is_none() => Ok(None),
+		_ => Err(format!(
+			"Both the TLS cert_path and key_path must be set to enable TLS, or the environment variables {} and {}",
+			TLS_CERT_PATH_VAR, TLS_KEY_PATH_VAR
+		)),
+	}
+}
+
+fn parse_access_policy(config: AccessPolicyTomlConfig) -> Result<RuleBasedPolicy, String> {
+	let default_effect = match config.default_effect {
+		Some(effect) => RuleEffect::from_name(&effect)
+			.ok_or(format!("Unknown access rule default_effect: {}", effect))?,
+		None => RuleEffect::Allow,
+	};
+	let rules = config
+		.rules
+		.unwrap_or_default()
+		.into_iter()
+		.map(|rule| {
+			let effect = RuleEffect::from_name(&rule.effect)
+				.ok_or(format!("Unknown access rule effect: {}", rule.effect))?;
+			let verbs = rule
+				.verbs
+				.unwrap_or_default()
+				.iter()
+				.map(|verb| {
+					AccessVerb::from_name(verb).ok_or(format!("Unknown access rule verb: {}", verb))
+				})
+				.collect::<Result<Vec<_>, String>>()?;
+			Ok(AccessRule {
+				name: rule.name,
+				effect,
+				verbs,
+				store_ids: rule.store_ids.unwrap_or_default(),
+				key_prefixes: rule.key_prefixes.unwrap_or_default(),
+				claims: rule.claims.unwrap_or_default(),
+				missing_claims: rule.missing_claims.unwrap_or_default(),
+			})
+		})
+		.collect::<Result<Vec<_>, String>>()?;
+	Ok(RuleBasedPolicy::new(rules, default_effect))
+}
+
+fn parse_store_namespace_config(
+	config: StoreNamespaceTomlConfig,
+) -> Result<StoreNamespaceConfig, String> {
+	let max_store_id_length = config.max_store_id_length.unwrap_or(DEFAULT_MAX_STORE_ID_LENGTH);
+	if max_store_id_length == 0 {
+		return Err("The max_store_id_length must be positive".to_string());
+	}
+	if config.max_stores_per_user == Some(0) {
+		return Err("The max_stores_per_user must be at least 1".to_string());
+	}
+	let reserved_namespaces = config
+		.reserved
+		.unwrap_or_default()
+		.into_iter()
+		.map(|namespace| {
+			let claims = namespace.claims.unwrap_or_default();
+			if namespace.prefix.is_empty() {
+				return Err("Reserved store namespaces must have a non-empty prefix".to_string());
+			}
+			if claims.is_empty() {
+				return Err(format!(
+					"The reserved store namespace {} must require at least one claim",
+					namespace.prefix
+				));
+			}
+			Ok(ReservedNamespace { prefix: namespace.prefix, claims })
+		})
+		.collect::<Result<Vec<_>, String>>()?;
+	Ok(StoreNamespaceConfig {
+		max_store_id_length,
+		restrict_store_id_characters: config.restrict_store_id_characters.unwrap_or(false),
+		max_stores_per_user: config.max_stores_per_user,
+		reserved_namespaces,
+	})
+}
+
+fn parse_client_compatibility(
+	config: ClientCompatibilityTomlConfig,
+) -> Result<ClientCompatibility, String> {
+	let header_aliases = config
+		.header_aliases
+		.unwrap_or_default()
+		.into_iter()
+		.map(|(legacy_name, name)| {
+			let (legacy_name, name) = (legacy_name.to_lowercase(), name.to_lowercase());
+			if legacy_name.is_empty() || name.is_empty() || legacy_name == name {
+				return Err(format!("Invalid header alias {} = {}", legacy_name, name));
+			}
+			Ok((legacy_name, name))
+		})
+		.collect::<Result<HashMap<_, _>, String>>()?;
+	if config.default_store_id.as_deref() == Some("") {
+		return Err("The default_store_id must not be empty".to_string());
+	}
+	Ok(ClientCompatibility {
+		header_aliases,
+		default_store_id: config.default_store_id,
+		zero_page_size_as_unset: config.zero_page_size_as_unset.unwrap_or(false),
+	})
+}
+
+fn parse_otel_config(config: OtelTomlConfig) -> Result<Option<OtelConfig>, String> {
+	if !config.enabled.unwrap_or(true) {
+		return Ok(None);
+	}
+	let sample_ratio = config.sample_ratio.unwrap_or(1.0);
+	if !(0.0..=1.0).contains(&sample_ratio) {
+		return Err("The OpenTelemetry sample_ratio must be between 0 and 1".to_string());
+	}
+	if config.service_name.as_deref() == Some("") {
+		return Err("The OpenTelemetry service_name must not be empty".to_string());
+	}
+	Ok(Some(OtelConfig {
+		endpoint: config.endpoint,
+		service_name: config.service_name.unwrap_or_else(|| "vss-server".to_string()),
+		sample_ratio,
+		headers: config.headers.unwrap_or_default(),
+		max_spans_per_second: config.max_spans_per_second.unwrap_or(DEFAULT_MAX_SPANS_PER_SECOND),
+	}))
+}
+
+fn parse_store_template(config: StoreTemplateTomlConfig) -> Result<StoreTemplate, String> {
+	let objects = config
+		.objects
+		.into_iter()
+		.map(|object| {
+			let value = match (object.value, object.value_base64) {
+				(Some(value), None) => Bytes::from(value),
+				(None, Some(value_base64)) => {
+					BASE64.decode(value_base64).map(Bytes::from).map_err(|e| {
+						format!(
+							"Invalid base64 value for key {} in store template {}: {}",
+							object.key, config.name, e
+						)
+					})?
+				},
+				_ => {
+					return Err(format!(
+						"Either value or value_base64 must be set for key {} in store template {}",
+						object.key, config.name
+					))
+				},
+			};
+			Ok((object.key, value))
+		})
+		.collect::<Result<Vec<_>, String>>()?;
+	let template = StoreTemplate { name: config.name, objects };
+	template.validate()?;
+	Ok(template)
+}
+
+/// Reads the entries of a key file, decrypting it with the identities of `age_identity_file` if
+/// given.
+fn read_key_file(
+	path: &PathBuf, age_identity_file: Option<&PathBuf>,
+) -> Result<Vec<String>, String> {
+	let contents = std::fs::read(path)
+		.map_err(|e| format!("Failed to read encryption key file {}: {}", path.display(), e))?;
+	let contents = match age_identity_file {
+		Some(identity_path) => {
+			let identity_file = std::fs::read(identity_path).map_err(|e| {
+				format!("Failed to read age identity file {}: {}", identity_path.display(), e)
+			})?;
+			decrypt_age_key_file(&contents, &identity_file)
+				.map_err(|e| format!("Invalid encryption key file {}: {}", path.display(), e))?
+		},
+		None => String::from_utf8(contents)
+			.map_err(|_| format!("Encryption key file {} is not valid UTF-8", path.display()))?,
+	};
+	Ok(contents.lines().map(str::to_string).collect())
+}
+
+/// Parses the names of the kinds of access credentials are limited to, e.g. `read`.
+fn parse_scopes(scopes: &[String]) -> Result<Vec<AccessVerb>, String> {
+	if scopes.is_empty() {
+		return Err("At least one scope must be listed".to_string());
+	}
+	scopes
+		.iter()
+		.map(|scope| AccessVerb::from_name(scope).ok_or(format!("Unknown scope: {}", scope)))
+		.collect()
+}
+
+/// Checks the stores credentials are limited to.
+fn validate_store_ids(store_ids: &[String]) -> Result<(), String> {
+	if store_ids.is_empty() {
+		return Err("At least one store must be listed".to_string());
+	}
+	if store_ids.iter().any(|store_id| store_id.is_empty()) {
+		return Err("Store ids must not be empty".to_string());
+	}
+	Ok(())
+}
+
+/// Reads the API keys from a file holding the hex-encoded SHA-256 hash of a key followed by the
+/// user token it grants access to on each line. Empty lines and lines starting with `#` are
+/// skipped.
+fn read_api_key_file(path: &PathBuf) -> Result<Vec<([u8; 32], String)>, String> {
+	let contents = std::fs::read_to_string(path)
+		.map_err(|e| format!("Failed to read API key file {}: {}", path.display(), e))?;
+	contents
+		.lines()
+		.enumerate()
+		.map(|(index, line)| (index + 1, line.trim()))
+		.filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+		.map(|(line_number, line)| {
+			let invalid_line = || {
+				format!(
+					"Line {} of API key file {} must hold a hex-encoded SHA-256 hash followed by \
+					a user token",
+					line_number,
+					path.display()
+				)
+			};
+			let (key_sha256, user_token) =
+				line.split_once(char::is_whitespace).ok_or_else(invalid_line)?;
+			let key_sha256 = parse_sha256_hex(key_sha256).ok_or_else(invalid_line)?;
+			Ok((key_sha256, user_token.trim().to_string()))
+		})
+		.collect()
+}
+
+/// The address the probe reaches a listener bound to `bind_address` at, which is the loopback
+/// address for listeners bound to all interfaces.
+fn probe_address(bind_address: &str) -> String {
+	match bind_address.parse::<std::net::SocketAddr>() {
+		Ok(addr) if addr.ip().is_unspecified() => {
+			let loopback: std::net::IpAddr = if addr.is_ipv4() {
+				std::net::Ipv4Addr::LOCALHOST.into()
+			} else {
+				std::net::Ipv6Addr::LOCALHOST.into()
+			};
+			std::net::SocketAddr::new(loopback, addr.port()).to_string()
+		},
+		_ => bind_address.to_string(),
+	}
+}
+
+fn parse_sha256_hex(hex: &str) -> Option<[u8; 32]> {
+	if hex.len() != 64 || !hex.is_ascii() {
+		return None;
+	}
+	let mut bytes = [0u8; 32];
+	for (i, byte) in bytes.iter_mut().enumerate() {
+		*byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+	}
+	Some(bytes)
+}
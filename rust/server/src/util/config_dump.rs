@@ -0,0 +1,474 @@
+//! The effective configuration of a running server, as resolved from the configuration file, the
+//! environment and defaults, for operators to verify which settings are actually in effect.
+//!
+//! The dump mirrors the tables of the configuration file. Secrets, such as passwords in DSNs,
+//! encryption keys and admin token hashes, are redacted, so the dump can be shared in support
+//! requests. Optional tables which are not enabled are `null`.
+
+use std::collections::HashMap;
+
+use serde_json::{json, Map, Value};
+
+use impls::backend_factory::BackendConfig;
+use impls::value_compression::ValueCompression;
+
+use crate::mutation_audit::AuditSink;
+use crate::quota::QuotaLimits;
+use crate::synthetic_probe::ProbeCredentials;
+use crate::util::config::{
+	Configuration, DatadogConfig, EncryptionKeys, MasterKeyConfig, SentryConfig,
+};
+
+/// Replaces secrets in the dump.
+const REDACTED: &str = "<redacted>";
+
+/// The optional tables, listed on startup if enabled.
+const OPTIONAL_TABLES: &[&str] = &[
+	"tls",
+	"jwt_auth_config",
+	"api_key_auth_config",
+	"nostr_auth_config",
+	"l402_config",
+	"oidc_auth_config",
+	"auth_chain_config",
+	"object_store_config",
+	"admin_config",
+	"encryption_config",
+	"analytics_config",
+	"nostr_notification_config",
+	"security_notification_config",
+	"quota_config",
+	"session_limit_config",
+	"rate_limit_config",
+	"load_shedding_config",
+	"backup_monitor_config",
+	"probe_config",
+	"delayed_deletion_config",
+	"soft_deletion_config",
+	"change_stream_config",
+	"maintenance_config",
+	"audit_config",
+	"grpc_config",
+	"access_policy_config",
+	"client_compatibility_config",
+	"store_namespace_config",
+	"otel",
+];
+
+/// The optional features the server was built with.
+const BUILD_FEATURES: &[(&str, bool)] = &[
+	("jwt", cfg!(feature = "jwt")),
+	("jwks", cfg!(feature = "jwks")),
+	("sigs", cfg!(feature = "sigs")),
+	("apikey", cfg!(feature = "apikey")),
+	("nostr", cfg!(feature = "nostr")),
+	("l402", cfg!(feature = "l402")),
+	("oidc", cfg!(feature = "oidc")),
+	("grpc", cfg!(feature = "grpc")),
+	("redis", cfg!(feature = "redis")),
+	("dynamodb", cfg!(feature = "dynamodb")),
+	("object-store", cfg!(feature = "object-store")),
+];
+
+/// Describes the effective configuration, with secrets redacted.
+pub(crate) fn effective_config(
+	config: &Configuration, sentry: &SentryConfig, datadog: &DatadogConfig,
+) -> Value {
+	#[cfg(feature = "grpc")]
+	let grpc_config = config.grpc_bind_address.as_ref().map(|addr| json!({ "bind_address": addr }));
+	#[cfg(not(feature = "grpc"))]
+	let grpc_config = Value::Null;
+	#[cfg(feature = "object-store")]
+	let object_store_config = config.offloading_config.as_ref().map(|offloading| {
+		json!({
+			"url": offloading.url,
+			"min_offloaded_value_size": offloading.min_offloaded_value_size,
+		})
+	});
+	#[cfg(not(feature = "object-store"))]
+	let object_store_config = Value::Null;
+	let soft_deletion_config = config.soft_deletion_config.as_ref().map(|soft_deletion| {
+		json!({
+			"retention_secs": soft_deletion.retention.as_secs(),
+			"purge_interval_secs": soft_deletion.purge_interval.as_secs(),
+		})
+	});
+	let change_stream_config = config.change_stream_config.as_ref().map(|change_stream| {
+		json!({
+			"max_subscriptions_per_user": change_stream.max_subscriptions_per_user,
+			"buffered_events": change_stream.buffered_events,
+		})
+	});
+	let maintenance_config = config.maintenance_config.as_ref().map(|maintenance| {
+		json!({
+			"share_token_reaping_interval_secs": maintenance.share_token_reaping_interval.as_secs(),
+			"usage_recomputation_interval_secs": maintenance.usage_recomputation_interval.as_secs(),
+			"orphan_cleanup_interval_secs": maintenance.orphan_cleanup_interval.as_secs(),
+			"vacuum_hint_interval_secs": maintenance.vacuum_hint_interval.as_secs(),
+		})
+	});
+	let audit_config = config.audit_config.as_ref().map(|audit| match &audit.sink {
+		AuditSink::Database { retention } => json!({
+			"sink": audit.sink.name(),
+			"retention_days": retention.map(|retention| retention.as_secs() / (24 * 60 * 60)),
+		}),
+		AuditSink::File(path) => json!({ "sink": audit.sink.name(), "file": path }),
+	});
+	let security_notifications = config.security_notification_config.as_ref();
+	let merge_key_prefixes: Vec<&String> =
+		config.merge_strategies.iter().map(|(prefix, _)| prefix).collect();
+	let templates: Vec<&String> =
+		config.store_templates.iter().map(|template| &template.name).collect();
+	let quota_profiles = |profiles: &HashMap<String, QuotaLimits>| -> Map<String, Value> {
+		profiles.iter().map(|(name, limits)| (name.clone(), quota_limits(limits))).collect()
+	};
+	let redacted_headers = |headers: &HashMap<String, String>| -> Map<String, Value> {
+		headers.keys().map(|name| (name.clone(), Value::from(REDACTED))).collect()
+	};
+	json!({
+		"version": env!("CARGO_PKG_VERSION"),
+		"build_features": build_features(),
+		"server_config": {
+			"bind_address": config.bind_address,
+			"max_request_body_size": config.max_request_body_size,
+			"body_read_timeout_secs": config.body_read_timeout.as_secs(),
+			"response_write_timeout_secs": config.response_write_timeout.as_secs(),
+			"request_timeout_secs": config.request_timeout.map(|timeout| timeout.as_secs()),
+			"require_client_encryption": config.require_client_encryption,
+			"maintenance_mode": config.maintenance_mode,
+		},
+		"tls": config.tls_config.as_ref().map(|tls| json!({
+			"cert_path": tls.cert_path,
+			"key_path": tls.key_path,
+			"client_ca_path": tls.client_ca_path,
+		})),
+		"log_config": {
+			"level": config.log_level.to_string(),
+			"file": config.log_file,
+			"format": config.log_format.name(),
+		},
+		"jwt_auth_config": (config.rsa_pem.is_some() || config.jwks_config.is_some()).then(|| json!({
+			"rsa_pem": config.rsa_pem.as_ref().map(|_| "<configured>"),
+			"algorithm": config.jwt_algorithm,
+			"jwks_url": config.jwks_config.as_ref().map(|jwks| &jwks.url),
+			"jwks_refresh_interval_secs":
+				config.jwks_config.as_ref().map(|jwks| jwks.refresh_interval.as_secs()),
+			"profile_claim": config.jwt_profile_claim,
+			"scope_claim": config.jwt_scope_claim,
+			"store_claim": config.jwt_store_claim,
+		})),
+		"api_key_auth_config": config.api_key_auth_config.as_ref().map(|api_keys| json!({
+			"keys": api_keys.keys.iter().map(|(key_sha256, user_token)| json!({
+				"user_token": user_token,
+				"key_sha256": REDACTED,
+				"scopes": api_keys
+					.key_scopes
+					.iter()
+					.find(|(scoped_key, _)| scoped_key == key_sha256)
+					.map(|(_, scopes)| scopes.iter().map(|verb| verb.name()).collect::<Vec<_>>()),
+				"store_ids": api_keys
+					.key_store_ids
+					.iter()
+					.find(|(limited_key, _)| limited_key == key_sha256)
+					.map(|(_, store_ids)| store_ids),
+			})).collect::<Vec<_>>(),
+			"header": api_keys.header,
+		})),
+		"nostr_auth_config": config.nostr_auth_config.as_ref().map(|nostr| json!({
+			"url_prefix": nostr.url_prefix,
+		})),
+		"l402_config": config.l402_config.as_ref().map(|l402| json!({
+			"root_key": REDACTED,
+			"price_sats": l402.price_sats,
+			"validity_secs": l402.validity.map(|validity| validity.as_secs()),
+			"lnd_rest_url": l402.lnd_rest_url,
+			"lnd_macaroon": REDACTED,
+			"lnd_cert": l402.lnd_cert_pem.as_ref().map(|_| "<configured>"),
+		})),
+		"oidc_auth_config": config.oidc_auth_config.as_ref().map(|oidc| json!({
+			"issuer_url": oidc.issuer_url,
+			"audience": oidc.audience,
+			"key_refresh_interval_secs": oidc.key_refresh_interval.as_secs(),
+			"profile_claim": oidc.profile_claim,
+			"scope_claim": oidc.scope_claim,
+			"store_claim": oidc.store_claim,
+		})),
+		"auth_chain_config": config.auth_chain.as_ref().map(|chain| json!({
+			"authorizers": chain.iter().map(|method| method.name()).collect::<Vec<_>>(),
+		})),
+		"storage_config": storage_config(&config.storage),
+		"object_store_config": object_store_config,
+		"admin_config": config.admin_config.as_ref().map(|admin| json!({
+			"bind_address": admin.bind_address,
+			"approval_delay_secs": admin.approval_delay.as_secs(),
+			"admins": admin.admins.iter().map(|admin| json!({
+				"name": admin.name,
+				"token_sha256": REDACTED,
+			})).collect::<Vec<_>>(),
+			"request_capture_sample_rate": admin.request_capture_sample_rate,
+			"request_capture_capacity": admin.request_capture_capacity,
+		})),
+		"encryption_config": config.encryption_keys.as_ref().map(|keys| json!({
+			"key_versions": keys.key_versions(),
+			"current_key_version": keys.current_key_version(),
+			"master_key": match keys {
+				EncryptionKeys::Static(_) => None,
+				EncryptionKeys::Envelope { master_key, .. } => Some(master_key.name()),
+			},
+			"vault_transit": match keys {
+				EncryptionKeys::Envelope { master_key: MasterKeyConfig::VaultTransit(vault), .. } => {
+					Some(json!({
+						"address": vault.address,
+						"token": REDACTED,
+						"key_name": vault.key_name,
+						"mount": vault.mount,
+					}))
+				},
+				_ => None,
+			},
+			"aws_kms": match keys {
+				EncryptionKeys::Envelope { master_key: MasterKeyConfig::AwsKms(aws), .. } => {
+					Some(json!({
+						"key_id": aws.key_id,
+						"region": aws.region,
+						"endpoint": aws.endpoint,
+						"access_key_id": aws.access_key_id,
+						"secret_access_key": REDACTED,
+					}))
+				},
+				_ => None,
+			},
+			"gcp_kms": match keys {
+				EncryptionKeys::Envelope { master_key: MasterKeyConfig::GcpKms(gcp), .. } => {
+					Some(json!({
+						"key_name": gcp.key_name,
+						"access_token": gcp.access_token.as_ref().map(|_| REDACTED),
+					}))
+				},
+				_ => None,
+			},
+		})),
+		"analytics_config": config.analytics_config.as_ref().map(|analytics| json!({
+			"report_interval_secs": analytics.report_interval.as_secs(),
+			"epsilon": analytics.epsilon,
+		})),
+		"nostr_notification_config": config.nostr_notification_config.as_ref().map(|nostr| json!({
+			"secret_key": REDACTED,
+			"relays": nostr.relays,
+			"key_prefixes": nostr.key_prefixes,
+			"registration_key": nostr.registration_key,
+			"min_interval_secs": nostr.min_interval.as_secs(),
+			"event_kind": nostr.event_kind,
+		})),
+		"security_notification_config": security_notifications.map(|security| {
+			json!({
+				"preferences_key": security.preferences_key,
+				"email_webhook_url": security.email_webhook_url.as_deref().map(redact_url),
+				"allowed_webhook_hosts": security.allowed_webhook_hosts,
+				"subscriptions": security.subscriptions.len(),
+			})
+		}),
+		"merge_config": {
+			"key_prefixes": merge_key_prefixes,
+			"max_global_version_retries": config.max_global_version_retries,
+		},
+		"store_template_config": {
+			"templates": templates,
+		},
+		"quota_config": config.quota_config.as_ref().map(|quota| json!({
+			"default": quota_limits(&quota.default_limits),
+			"profiles": quota_profiles(&quota.profiles),
+			"soft_limit_ratio": quota.soft_limit_ratio,
+			"enforce": quota.enforce,
+		})),
+		"session_limit_config": config.session_limit_config.as_ref().map(|sessions| json!({
+			"max_sessions_per_user": sessions.max_sessions_per_user,
+			"window_secs": sessions.window.as_secs(),
+		})),
+		"rate_limit_config": config.rate_limit_config.as_ref().map(|rate_limit| json!({
+			"requests_per_second": rate_limit.requests_per_second,
+			"burst": rate_limit.burst,
+		})),
+		"load_shedding_config": config.load_shedding_config.as_ref().map(|shedding| json!({
+			"window_secs": shedding.window.as_secs(),
+			"min_requests": shedding.min_requests,
+			"degraded_failure_ratio": shedding.degraded_failure_ratio,
+			"unhealthy_failure_ratio": shedding.unhealthy_failure_ratio,
+		})),
+		"backup_monitor_config": config.backup_monitor_config.as_ref().map(|backups| json!({
+			"check_interval_secs": backups.check_interval.as_secs(),
+			"max_archive_age_secs": backups.max_archive_age.as_secs(),
+			"fail_readiness": backups.fail_readiness,
+			"alert_command": backups.alert_command.as_ref().map(|_| "<configured>"),
+		})),
+		"certificate_expiry_config": {
+			"check_interval_secs": config.certificate_expiry_config.check_interval.as_secs(),
+			"warning_period_days": config.certificate_expiry_config.warning_period.as_secs() / (24 * 60 * 60),
+		},
+		"probe_config": config.probe_config.as_ref().map(|probe| json!({
+			"interval_secs": probe.interval.as_secs(),
+			"url": probe.url,
+			"store_id": probe.store_id,
+			"timeout_secs": probe.timeout.as_secs(),
+			"credentials": match probe.credentials {
+				ProbeCredentials::Authorization(_) => "authorization",
+				#[cfg(feature = "sigs")]
+				ProbeCredentials::SigningKey(_) => "signing_key",
+				ProbeCredentials::None => "none",
+			},
+		})),
+		"delayed_deletion_config": config.delayed_deletion_config.as_ref().map(|deletions| json!({
+			"key_prefixes": deletions.key_prefixes,
+			"delay_secs": deletions.delay.as_secs(),
+			"check_interval_secs": deletions.check_interval.as_secs(),
+		})),
+		"soft_deletion_config": soft_deletion_config,
+		"change_stream_config": change_stream_config,
+		"maintenance_config": maintenance_config,
+		"audit_config": audit_config,
+		"grpc_config": grpc_config,
+		"access_policy_config": config.access_rule_policy.as_ref().map(|_| {
+			json!({ "rules": "<configured>" })
+		}),
+		"client_compatibility_config": config.client_compatibility.as_ref().map(|compat| json!({
+			"header_aliases": compat.header_aliases,
+			"default_store_id": compat.default_store_id,
+			"zero_page_size_as_unset": compat.zero_page_size_as_unset,
+		})),
+		"store_namespace_config": config.store_namespace_config.as_ref().map(|namespaces| json!({
+			"max_store_id_length": namespaces.max_store_id_length,
+			"restrict_store_id_characters": namespaces.restrict_store_id_characters,
+			"max_stores_per_user": namespaces.max_stores_per_user,
+			"reserved": namespaces.reserved_namespaces.iter().map(|namespace| json!({
+				"prefix": namespace.prefix,
+				"claims": namespace.claims.keys().collect::<Vec<_>>(),
+			})).collect::<Vec<_>>(),
+		})),
+		"otel": config.otel_config.as_ref().map(|otel| json!({
+			"endpoint": otel.endpoint,
+			"service_name": otel.service_name,
+			"sample_ratio": otel.sample_ratio,
+			"headers": redacted_headers(&otel.headers),
+			"max_spans_per_second": otel.max_spans_per_second,
+		})),
+		"sentry": {
+			"dsn": sentry.get_dsn().map(|_| REDACTED),
+			"environment": sentry.get_environment(),
+			"sample_rate": sentry.get_sample_rate(),
+			"max_events_per_second": sentry.get_max_events_per_second(),
+		},
+		"datadog": {
+			"enabled": datadog.enabled,
+			"service": datadog.service,
+			"env": datadog.env,
+			"version": datadog.version,
+			"agent_host": datadog.agent_host,
+			"agent_port": datadog.agent_port,
+			"max_spans_per_second": datadog.max_spans_per_second,
+		},
+	})
+}
+
+/// Summarizes the effective configuration in a few lines, logged on startup.
+pub(crate) fn startup_banner(effective_config: &Value) -> Vec<String> {
+	let enabled: Vec<&str> = OPTIONAL_TABLES
+		.iter()
+		.copied()
+		.filter(|table| !effective_config[*table].is_null())
+		.collect();
+	vec![
+		format!(
+			"VSS server {} built with features [{}]",
+			env!("CARGO_PKG_VERSION"),
+			build_features().join(", ")
+		),
+		format!(
+			"Storing objects in the {} backend, enabled tables: [{}]",
+			effective_config["storage_config"]["backend"].as_str().unwrap_or_default(),
+			enabled.join(", ")
+		),
+	]
+}
+
+fn build_features() -> Vec<&'static str> {
+	BUILD_FEATURES.iter().filter(|(_, enabled)| *enabled).map(|(name, _)| *name).collect()
+}
+
+fn storage_config(storage: &BackendConfig) -> Value {
+	let mut config = match storage {
+		BackendConfig::Postgres(postgres) => json!({
+			"postgresql_config": {
+				"endpoint": redact_url(&postgres.prefix),
+				"default_database": postgres.default_db,
+				"vss_database": postgres.vss_db,
+				"tls": postgres.tls_config.as_ref().map(|crt_pem| json!({
+					"crt_pem": crt_pem.as_ref().map(|_| "<configured>"),
+				})),
+				"value_compression": value_compression(&postgres.value_compression),
+				"max_value_size": postgres.max_value_size,
+				"notify_changes": postgres.notify_changes,
+				"key_history_versions": postgres.key_history_versions,
+				"migrate_on_startup": postgres.migrate_on_startup,
+				"replica": postgres.replica_prefix.as_deref().map(redact_url),
+				"require_primary_reads": postgres.require_primary_reads,
+				"transient_retries": postgres.transient_retries,
+				"pool": {
+					"max_size": postgres.pool_config.max_size,
+					"min_idle": postgres.pool_config.min_idle,
+					"connection_timeout_secs": postgres.pool_config.connection_timeout.map(|t| t.as_secs()),
+					"idle_timeout_secs": postgres.pool_config.idle_timeout.map(|t| t.as_secs()),
+					"max_lifetime_secs": postgres.pool_config.max_lifetime.map(|t| t.as_secs()),
+					"statement_timeout_secs": postgres.pool_config.statement_timeout.map(|t| t.as_secs()),
+				},
+				"read_role": postgres.read_prefix.as_deref().map(redact_url),
+				"admin_role": postgres.admin_prefix.as_deref().map(redact_url),
+			},
+		}),
+		BackendConfig::InMemory => json!({}),
+		#[cfg(feature = "redis")]
+		BackendConfig::Redis(redis) => json!({
+			"redis_config": { "url": redact_url(&redis.url) },
+		}),
+		#[cfg(feature = "dynamodb")]
+		BackendConfig::DynamoDb(dynamodb) => json!({
+			"dynamodb_config": {
+				"table_name": dynamodb.table_name,
+				"create_table": dynamodb.create_table,
+			},
+		}),
+	};
+	config["backend"] = Value::from(storage.name());
+	config
+}
+
+fn value_compression(compression: &ValueCompression) -> Value {
+	match compression {
+		ValueCompression::None => json!({ "algorithm": "none" }),
+		ValueCompression::Zstd { level } => json!({ "algorithm": "zstd", "level": level }),
+		ValueCompression::Lz4 => json!({ "algorithm": "lz4" }),
+	}
+}
+
+fn quota_limits(limits: &QuotaLimits) -> Value {
+	json!({
+		"max_bytes_per_user": limits.max_bytes_per_user,
+		"max_objects_per_user": limits.max_objects_per_user,
+	})
+}
+
+/// Redacts the password in the user info of `url`, e.g. of a PostgreSQL DSN.
+fn redact_url(url: &str) -> String {
+	let Some((scheme, rest)) = url.split_once("://") else {
+		return url.to_string();
+	};
+	let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+	match rest[..authority_end].rsplit_once('@') {
+		Some((user_info, _)) => match user_info.split_once(':') {
+			Some((user, _)) => {
+				format!("{}://{}:{}{}", scheme, user, REDACTED, &rest[user_info.len()..])
+			},
+			None => url.to_string(),
+		},
+		None => url.to_string(),
+	}
+}
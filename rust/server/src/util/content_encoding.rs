@@ -0,0 +1,55 @@
+//! Decoding of request bodies compressed as announced by their `Content-Encoding` header.
+
+use std::io::Read;
+
+use bytes::Bytes;
+use flate2::read::GzDecoder;
+use hyper::StatusCode;
+
+/// Why a request body could not be decoded, along with the status to respond with.
+pub(crate) struct DecodeError {
+	pub(crate) status: StatusCode,
+	pub(crate) message: &'static str,
+}
+
+/// Decodes a body sent with the given `Content-Encoding`, failing if the decoded body would
+/// exceed `max_size` bytes. Supports `gzip` and `zstd`, as well as uncompressed bodies.
+pub(crate) fn decode_body(
+	body: Bytes, content_encoding: Option<&str>, max_size: usize,
+) -> Result<Bytes, DecodeError> {
+	let encoding = content_encoding.map(str::trim).unwrap_or_default().to_ascii_lowercase();
+	let decoded = match encoding.as_str() {
+		"" | "identity" => return Ok(body),
+		"gzip" | "x-gzip" => read_limited(GzDecoder::new(&body[..]), max_size)?,
+		"zstd" => {
+			let decoder =
+				zstd::stream::read::Decoder::new(&body[..]).map_err(|_| corrupt_body())?;
+			read_limited(decoder, max_size)?
+		},
+		_ => {
+			return Err(DecodeError {
+				status: StatusCode::UNSUPPORTED_MEDIA_TYPE,
+				message: "Unsupported Content-Encoding, expected gzip or zstd",
+			})
+		},
+	};
+	Ok(Bytes::from(decoded))
+}
+
+/// Reads the decoder to the end, reading at most one byte beyond `max_size` to detect bodies
+/// expanding beyond the limit without decompressing them in full.
+fn read_limited(decoder: impl Read, max_size: usize) -> Result<Vec<u8>, DecodeError> {
+	let mut decoded = Vec::new();
+	decoder.take(max_size as u64 + 1).read_to_end(&mut decoded).map_err(|_| corrupt_body())?;
+	if decoded.len() > max_size {
+		return Err(DecodeError {
+			status: StatusCode::PAYLOAD_TOO_LARGE,
+			message: "Decompressed request body too large",
+		});
+	}
+	Ok(decoded)
+}
+
+fn corrupt_body() -> DecodeError {
+	DecodeError { status: StatusCode::BAD_REQUEST, message: "Failed to decompress request body" }
+}
@@ -0,0 +1,376 @@
+//! JSON representations of the protobuf messages of the [`KvStore`] API, for clients sending
+//! `application/json` requests.
+//!
+//! Fields are named as in `vss.proto`, with values encoded as base64 strings and error codes as
+//! their names, e.g. `CONFLICT_EXCEPTION`.
+//!
+//! [`KvStore`]: api::kv_store::KvStore
+
+use api::types::{
+	AppendObjectRequest, AppendObjectResponse, DeleteObjectRequest, DeleteObjectResponse,
+	ErrorCode, ErrorResponse, ExportStoreRequest, GetObjectRequest, GetObjectResponse,
+	GetObjectVersionRequest, GetObjectsAtomicRequest, GetObjectsAtomicResponse, ImportStoreRequest,
+	ImportStoreResponse, KeyValue, ListKeyVersionsRequest, ListKeyVersionsResponse,
+	ListObjectsRequest, ListObjectsResponse, PutObjectRequest, PutObjectResponse,
+};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bytes::Bytes;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+
+/// A request which can be parsed from its JSON representation.
+pub(crate) trait FromJson: Sized {
+	fn from_json(json: &[u8]) -> Result<Self, String>;
+}
+
+/// A response which can be serialized to its JSON representation.
+pub(crate) trait ToJson {
+	fn to_json(&self) -> Vec<u8>;
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonKeyValue {
+	key: String,
+	#[serde(default)]
+	version: i64,
+	/// The base64 encoded value.
+	#[serde(default)]
+	value: String,
+}
+
+impl JsonKeyValue {
+	fn from_key_value(kv: &KeyValue) -> Self {
+		Self { key: kv.key.clone(), version: kv.version, value: BASE64.encode(&kv.value) }
+	}
+
+	fn into_key_value(self) -> Result<KeyValue, String> {
+		let value = decode_value(&self.value)?;
+		Ok(KeyValue { key: self.key, version: self.version, value })
+	}
+}
+
+fn decode_value(value: &str) -> Result<Bytes, String> {
+	BASE64.decode(value).map(Bytes::from).map_err(|_| "value must be base64 encoded".to_string())
+}
+
+fn parse<'a, T: Deserialize<'a>>(json: &'a [u8]) -> Result<T, String> {
+	serde_json::from_slice(json).map_err(|e| e.to_string())
+}
+
+fn to_vec<T: Serialize>(value: &T) -> Vec<u8> {
+	// unwrap safety: our JSON representations always serialize successfully.
+	serde_json::to_vec(value).unwrap()
+}
+
+#[derive(Deserialize)]
+struct JsonGetObjectRequest {
+	store_id: String,
+	key: String,
+}
+
+impl FromJson for GetObjectRequest {
+	fn from_json(json: &[u8]) -> Result<Self, String> {
+		let JsonGetObjectRequest { store_id, key } = parse(json)?;
+		Ok(GetObjectRequest { store_id, key })
+	}
+}
+
+#[derive(Serialize)]
+struct JsonGetObjectResponse {
+	value: Option<JsonKeyValue>,
+}
+
+impl ToJson for GetObjectResponse {
+	fn to_json(&self) -> Vec<u8> {
+		to_vec(&JsonGetObjectResponse {
+			value: self.value.as_ref().map(JsonKeyValue::from_key_value),
+		})
+	}
+}
+
+#[derive(Deserialize)]
+struct JsonPutObjectRequest {
+	store_id: String,
+	global_version: Option<i64>,
+	#[serde(default)]
+	transaction_items: Vec<JsonKeyValue>,
+	#[serde(default)]
+	delete_items: Vec<JsonKeyValue>,
+}
+
+impl FromJson for PutObjectRequest {
+	fn from_json(json: &[u8]) -> Result<Self, String> {
+		let request: JsonPutObjectRequest = parse(json)?;
+		Ok(PutObjectRequest {
+			store_id: request.store_id,
+			global_version: request.global_version,
+			transaction_items: request
+				.transaction_items
+				.into_iter()
+				.map(JsonKeyValue::into_key_value)
+				.collect::<Result<_, _>>()?,
+			delete_items: request
+				.delete_items
+				.into_iter()
+				.map(JsonKeyValue::into_key_value)
+				.collect::<Result<_, _>>()?,
+		})
+	}
+}
+
+impl ToJson for PutObjectResponse {
+	fn to_json(&self) -> Vec<u8> {
+		b"{}".to_vec()
+	}
+}
+
+#[derive(Deserialize)]
+struct JsonDeleteObjectRequest {
+	store_id: String,
+	key_value: Option<JsonKeyValue>,
+}
+
+impl FromJson for DeleteObjectRequest {
+	fn from_json(json: &[u8]) -> Result<Self, String> {
+		let request: JsonDeleteObjectRequest = parse(json)?;
+		Ok(DeleteObjectRequest {
+			store_id: request.store_id,
+			key_value: request.key_value.map(JsonKeyValue::into_key_value).transpose()?,
+		})
+	}
+}
+
+impl ToJson for DeleteObjectResponse {
+	fn to_json(&self) -> Vec<u8> {
+		b"{}".to_vec()
+	}
+}
+
+#[derive(Deserialize)]
+struct JsonAppendObjectRequest {
+	store_id: String,
+	key: String,
+	/// The base64 encoded bytes to append.
+	value: String,
+}
+
+impl FromJson for AppendObjectRequest {
+	fn from_json(json: &[u8]) -> Result<Self, String> {
+		let request: JsonAppendObjectRequest = parse(json)?;
+		let value = decode_value(&request.value)?;
+		Ok(AppendObjectRequest { store_id: request.store_id, key: request.key, value })
+	}
+}
+
+#[derive(Serialize)]
+struct JsonAppendObjectResponse {
+	version: i64,
+}
+
+impl ToJson for AppendObjectResponse {
+	fn to_json(&self) -> Vec<u8> {
+		to_vec(&JsonAppendObjectResponse { version: self.version })
+	}
+}
+
+#[derive(Deserialize)]
+struct JsonListKeyVersionsRequest {
+	store_id: String,
+	key_prefix: Option<String>,
+	page_size: Option<i32>,
+	page_token: Option<String>,
+}
+
+impl FromJson for ListKeyVersionsRequest {
+	fn from_json(json: &[u8]) -> Result<Self, String> {
+		let JsonListKeyVersionsRequest { store_id, key_prefix, page_size, page_token } =
+			parse(json)?;
+		Ok(ListKeyVersionsRequest { store_id, key_prefix, page_size, page_token })
+	}
+}
+
+#[derive(Serialize)]
+struct JsonListKeyVersionsResponse {
+	key_versions: Vec<JsonKeyValue>,
+	next_page_token: Option<String>,
+	global_version: Option<i64>,
+}
+
+impl ToJson for ListKeyVersionsResponse {
+	fn to_json(&self) -> Vec<u8> {
+		to_vec(&JsonListKeyVersionsResponse {
+			key_versions: self.key_versions.iter().map(JsonKeyValue::from_key_value).collect(),
+			next_page_token: self.next_page_token.clone(),
+			global_version: self.global_version,
+		})
+	}
+}
+
+#[derive(Deserialize)]
+struct JsonListObjectsRequest {
+	store_id: String,
+	key_prefix: Option<String>,
+	page_size: Option<i32>,
+	page_token: Option<String>,
+}
+
+impl FromJson for ListObjectsRequest {
+	fn from_json(json: &[u8]) -> Result<Self, String> {
+		let JsonListObjectsRequest { store_id, key_prefix, page_size, page_token } = parse(json)?;
+		Ok(ListObjectsRequest { store_id, key_prefix, page_size, page_token })
+	}
+}
+
+#[derive(Serialize)]
+struct JsonListObjectsResponse {
+	objects: Vec<JsonKeyValue>,
+	next_page_token: Option<String>,
+	global_version: Option<i64>,
+}
+
+impl ToJson for ListObjectsResponse {
+	fn to_json(&self) -> Vec<u8> {
+		to_vec(&JsonListObjectsResponse {
+			objects: self.objects.iter().map(JsonKeyValue::from_key_value).collect(),
+			next_page_token: self.next_page_token.clone(),
+			global_version: self.global_version,
+		})
+	}
+}
+
+#[derive(Deserialize)]
+struct JsonGetObjectVersionRequest {
+	store_id: String,
+	key: String,
+	version: i64,
+}
+
+impl FromJson for GetObjectVersionRequest {
+	fn from_json(json: &[u8]) -> Result<Self, String> {
+		let JsonGetObjectVersionRequest { store_id, key, version } = parse(json)?;
+		Ok(GetObjectVersionRequest { store_id, key, version })
+	}
+}
+
+#[derive(Deserialize)]
+struct JsonGetObjectsAtomicRequest {
+	store_id: String,
+	keys: Vec<String>,
+}
+
+impl FromJson for GetObjectsAtomicRequest {
+	fn from_json(json: &[u8]) -> Result<Self, String> {
+		let JsonGetObjectsAtomicRequest { store_id, keys } = parse(json)?;
+		Ok(GetObjectsAtomicRequest { store_id, keys })
+	}
+}
+
+#[derive(Serialize)]
+struct JsonGetObjectsAtomicResponse {
+	objects: Vec<JsonKeyValue>,
+	global_version: i64,
+}
+
+impl ToJson for GetObjectsAtomicResponse {
+	fn to_json(&self) -> Vec<u8> {
+		to_vec(&JsonGetObjectsAtomicResponse {
+			objects: self.objects.iter().map(JsonKeyValue::from_key_value).collect(),
+			global_version: self.global_version,
+		})
+	}
+}
+
+#[derive(Deserialize)]
+struct JsonExportStoreRequest {
+	store_id: String,
+}
+
+impl FromJson for ExportStoreRequest {
+	fn from_json(json: &[u8]) -> Result<Self, String> {
+		let JsonExportStoreRequest { store_id } = parse(json)?;
+		Ok(ExportStoreRequest { store_id })
+	}
+}
+
+impl ToJson for KeyValue {
+	fn to_json(&self) -> Vec<u8> {
+		to_vec(&JsonKeyValue::from_key_value(self))
+	}
+}
+
+#[derive(Deserialize)]
+struct JsonImportStoreRequest {
+	store_id: String,
+	/// The lines of a JSON export, starting with the global version.
+	records: Vec<JsonKeyValue>,
+	#[serde(default)]
+	overwrite: bool,
+}
+
+impl FromJson for ImportStoreRequest {
+	fn from_json(json: &[u8]) -> Result<Self, String> {
+		let request: JsonImportStoreRequest = parse(json)?;
+		let mut archive = Vec::new();
+		for record in request.records {
+			// unwrap safety: encoding into a vector always has enough capacity.
+			record.into_key_value()?.encode_length_delimited(&mut archive).unwrap();
+		}
+		Ok(ImportStoreRequest {
+			store_id: request.store_id,
+			archive: Bytes::from(archive),
+			overwrite: request.overwrite,
+		})
+	}
+}
+
+#[derive(Serialize)]
+struct JsonImportStoreResponse {
+	imported_objects: i64,
+}
+
+impl ToJson for ImportStoreResponse {
+	fn to_json(&self) -> Vec<u8> {
+		to_vec(&JsonImportStoreResponse { imported_objects: self.imported_objects })
+	}
+}
+
+#[derive(Serialize)]
+struct JsonConflictingKey {
+	key: String,
+	version: i64,
+	/// The base64 encoded SHA-256 hash of the value, if revealed.
+	#[serde(skip_serializing_if = "String::is_empty")]
+	value_sha256: String,
+}
+
+#[derive(Serialize)]
+struct JsonErrorResponse {
+	error_code: &'static str,
+	message: String,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	conflicting_keys: Vec<JsonConflictingKey>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	global_version: Option<i64>,
+}
+
+impl ToJson for ErrorResponse {
+	fn to_json(&self) -> Vec<u8> {
+		let error_code = ErrorCode::from_i32(self.error_code).unwrap_or(ErrorCode::Unknown);
+		let conflicting_keys = self
+			.conflicting_keys
+			.iter()
+			.map(|conflicting_key| JsonConflictingKey {
+				key: conflicting_key.key.clone(),
+				version: conflicting_key.version,
+				value_sha256: BASE64.encode(&conflicting_key.value_sha256),
+			})
+			.collect();
+		to_vec(&JsonErrorResponse {
+			error_code: error_code.as_str_name(),
+			message: self.message.clone(),
+			conflicting_keys,
+			global_version: self.global_version,
+		})
+	}
+}
@@ -10,9 +10,47 @@
 use std::fs::{self, File, OpenOptions};
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
 use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde_json::{json, Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record as SpanRecord};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// The targets of the tracing events logged in JSON mode, i.e. those of the server's own crates.
+/// Events of dependencies, e.g. of HTTP/2 frames, are left to the exporters.
+const JSON_EVENT_TARGETS: &[&str] = &["vss_server", "impls", "auth_impls", "api"];
+
+/// The format log lines are written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+	/// Human-readable lines, as described on [`ServerLogger`].
+	Text,
+	/// One JSON object per line, including the tracing events of requests with the fields of
+	/// their spans, for ingestion by log aggregators such as Loki or Elasticsearch.
+	Json,
+}
+
+impl LogFormat {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"text" => Some(LogFormat::Text),
+			"json" => Some(LogFormat::Json),
+			_ => None,
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			LogFormat::Text => "text",
+			LogFormat::Json => "json",
+		}
+	}
+}
 
 /// A logger implementation that writes logs to both stderr and a file.
 ///
@@ -25,10 +63,16 @@ use log::{Level, LevelFilter, Log, Metadata, Record};
 ///
 /// Example: `[2025-12-04T10:30:45Z INFO ldk_server:42] Starting up...`
 ///
-/// The logger handles SIGHUP for log rotation by reopening the file handle when signaled.
+/// In [`LogFormat::Json`], each line is instead a JSON object with `timestamp`, `level`, `target`,
+/// `line` and `message` fields, and tracing events are logged too, see [`JsonEventLayer`].
+///
+/// The logger handles SIGHUP for log rotation by reopening the file handle when signaled, and its
+/// level can be changed while it is in use.
 pub struct ServerLogger {
-	/// The maximum log level to display
-	level: LevelFilter,
+	/// The maximum log level to display, as the discriminant of its [`LevelFilter`]
+	level: AtomicUsize,
+	/// The format of log lines
+	format: LogFormat,
 	/// The file to write logs to, protected by a mutex for thread-safe access
 	file: Mutex<File>,
 	/// Path to the log file for reopening on SIGHUP
@@ -44,7 +88,9 @@ impl ServerLogger {
 	/// This should be called once at application startup. Subsequent calls will fail.
 	///
 	/// Returns an Arc to the logger for signal handling purposes.
-	pub fn init(level: LevelFilter, log_file_path: &Path) -> Result<Arc<Self>, io::Error> {
+	pub fn init(
+		level: LevelFilter, format: LogFormat, log_file_path: &Path,
+	) -> Result<Arc<Self>, io::Error> {
 		// Create parent directories if they don't exist
 		if let Some(parent) = log_file_path.parent() {
 			fs::create_dir_all(parent)?;
@@ -53,7 +99,8 @@ impl ServerLogger {
 		let file = open_log_file(log_file_path)?;
 
 		let logger = Arc::new(ServerLogger {
-			level,
+			level: AtomicUsize::new(level as usize),
+			format,
 			file: Mutex::new(file),
 			log_file_path: log_file_path.to_path_buf(),
 		});
@@ -64,6 +111,18 @@ impl ServerLogger {
 		Ok(logger)
 	}
 
+	/// The maximum log level to display.
+	pub fn level(&self) -> LevelFilter {
+		let level = self.level.load(Ordering::Relaxed);
+		LevelFilter::iter().find(|filter| *filter as usize == level).unwrap_or(LevelFilter::Trace)
+	}
+
+	/// Changes the maximum log level to display, e.g. when the configuration is reloaded.
+	pub fn set_level(&self, level: LevelFilter) {
+		self.level.store(level as usize, Ordering::Relaxed);
+		log::set_max_level(level);
+	}
+
 	/// Reopens the log file. Called on SIGHUP for log rotation.
 	pub fn reopen(&self) -> Result<(), io::Error> {
 		let new_file = open_log_file(&self.log_file_path)?;
@@ -81,52 +140,30 @@ impl ServerLogger {
 
 impl Log for ServerLogger {
 	fn enabled(&self, metadata: &Metadata) -> bool {
-		metadata.level() <= self.level
+		metadata.level() <= self.level()
 	}
 
 	fn log(&self, record: &Record) {
 		if self.enabled(record.metadata()) {
-			let level_str = format_level(record.level());
-			let line = record.line().unwrap_or(0);
-
-			// Log to console
-			let _ = match record.level() {
-				Level::Error => {
-					writeln!(
-						io::stderr(),
-						"[{} {} {}:{}] {}",
-						format_timestamp(),
-						level_str,
-						record.target(),
-						line,
-						record.args()
-					)
-				},
-				_ => {
-					writeln!(
-						io::stdout(),
-						"[{} {} {}:{}] {}",
-						format_timestamp(),
-						level_str,
-						record.target(),
-						line,
-						record.args()
-					)
-				},
-			};
-
-			// Log to file
-			if let Ok(mut file) = self.file.lock() {
-				let _ = writeln!(
-					file,
+			let line = match self.format {
+				LogFormat::Text => format!(
 					"[{} {} {}:{}] {}",
 					format_timestamp(),
-					level_str,
+					format_level(record.level()),
 					record.target(),
-					line,
+					record.line().unwrap_or(0),
 					record.args()
-				);
-			}
+				),
+				LogFormat::Json => json!({
+					"timestamp": format_timestamp(),
+					"level": record.level().as_str(),
+					"target": record.target(),
+					"line": record.line().unwrap_or(0),
+					"message": record.args().to_string(),
+				})
+				.to_string(),
+			};
+			self.write_line(record.level(), &line);
 		}
 	}
 
@@ -139,6 +176,132 @@ impl Log for ServerLogger {
 	}
 }
 
+impl ServerLogger {
+	/// Returns a layer logging tracing events in JSON mode, or `None` in text mode.
+	pub fn json_event_layer(self: &Arc<Self>) -> Option<JsonEventLayer> {
+		(self.format == LogFormat::Json).then(|| JsonEventLayer { logger: Arc::clone(self) })
+	}
+
+	/// Writes a line to the console, errors to stderr and anything else to stdout, and to the file.
+	fn write_line(&self, level: Level, line: &str) {
+		let _ = match level {
+			Level::Error => writeln!(io::stderr(), "{}", line),
+			_ => writeln!(io::stdout(), "{}", line),
+		};
+		if let Ok(mut file) = self.file.lock() {
+			let _ = writeln!(file, "{}", line);
+		}
+	}
+}
+
+/// A [`Layer`] writing the tracing events of the server's crates as JSON lines to the
+/// [`ServerLogger`], so request events such as `Request completed successfully` carry the fields
+/// of their spans, e.g. `http.route`, `user.hash` and `http.status_code`.
+///
+/// Every line logged within a span carries a `trace_id` shared by all spans of its root span and
+/// the `span_id` of the innermost span, so the lines of a request can be correlated.
+pub struct JsonEventLayer {
+	logger: Arc<ServerLogger>,
+}
+
+/// The fields recorded on a span, and its identifiers, kept in the span's extensions.
+struct SpanFields {
+	trace_id: u128,
+	span_id: u64,
+	fields: Map<String, Value>,
+}
+
+impl<S> Layer<S> for JsonEventLayer
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+{
+	fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+		let Some(span) = ctx.span(id) else { return };
+		let trace_id = span
+			.parent()
+			.and_then(|parent| parent.extensions().get::<SpanFields>().map(|f| f.trace_id))
+			.unwrap_or_else(rand::random);
+		let mut fields = Map::new();
+		attrs.record(&mut JsonVisitor(&mut fields));
+		span.extensions_mut().insert(SpanFields { trace_id, span_id: rand::random(), fields });
+	}
+
+	fn on_record(&self, id: &Id, values: &SpanRecord<'_>, ctx: Context<'_, S>) {
+		let Some(span) = ctx.span(id) else { return };
+		let mut extensions = span.extensions_mut();
+		if let Some(span_fields) = extensions.get_mut::<SpanFields>() {
+			values.record(&mut JsonVisitor(&mut span_fields.fields));
+		}
+	}
+
+	fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+		let metadata = event.metadata();
+		let level = tracing_level(metadata.level());
+		let crate_name = metadata.target().split("::").next().unwrap_or_default();
+		if level > self.logger.level() || !JSON_EVENT_TARGETS.contains(&crate_name) {
+			return;
+		}
+
+		let mut line = Map::new();
+		line.insert("timestamp".to_string(), format_timestamp().into());
+		line.insert("level".to_string(), level.as_str().into());
+		line.insert("target".to_string(), metadata.target().into());
+		line.insert("line".to_string(), metadata.line().unwrap_or(0).into());
+		// Fields of inner spans take precedence over those of outer spans, and the event's own
+		// fields over those of any span.
+		if let Some(scope) = ctx.event_scope(event) {
+			let mut ids = None;
+			for span in scope.from_root() {
+				if let Some(span_fields) = span.extensions().get::<SpanFields>() {
+					line.extend(span_fields.fields.clone());
+					ids = Some((span_fields.trace_id, span_fields.span_id));
+				}
+			}
+			if let Some((trace_id, span_id)) = ids {
+				line.insert("trace_id".to_string(), format!("{:032x}", trace_id).into());
+				line.insert("span_id".to_string(), format!("{:016x}", span_id).into());
+			}
+		}
+		event.record(&mut JsonVisitor(&mut line));
+		self.logger.write_line(level, &Value::Object(line).to_string());
+	}
+}
+
+/// Records fields into a JSON object, keeping numbers and booleans as such.
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+	fn record_i64(&mut self, field: &Field, value: i64) {
+		self.0.insert(field.name().to_string(), value.into());
+	}
+
+	fn record_u64(&mut self, field: &Field, value: u64) {
+		self.0.insert(field.name().to_string(), value.into());
+	}
+
+	fn record_bool(&mut self, field: &Field, value: bool) {
+		self.0.insert(field.name().to_string(), value.into());
+	}
+
+	fn record_str(&mut self, field: &Field, value: &str) {
+		self.0.insert(field.name().to_string(), value.into());
+	}
+
+	fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+		self.0.insert(field.name().to_string(), format!("{:?}", value).into());
+	}
+}
+
+fn tracing_level(level: &tracing::Level) -> Level {
+	match *level {
+		tracing::Level::ERROR => Level::Error,
+		tracing::Level::WARN => Level::Warn,
+		tracing::Level::INFO => Level::Info,
+		tracing::Level::DEBUG => Level::Debug,
+		tracing::Level::TRACE => Level::Trace,
+	}
+}
+
 fn format_timestamp() -> String {
 	let now = chrono::Utc::now();
 	now.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
@@ -1,8 +1,55 @@
 pub(crate) mod config;
+pub(crate) mod config_dump;
+pub(crate) mod content_encoding;
+pub(crate) mod env_config;
+pub(crate) mod json_messages;
 pub(crate) mod logger;
+pub(crate) mod secrets;
+pub(crate) mod tls;
+pub(crate) mod vault_secrets;
+pub(crate) mod write_timeout;
+
+use std::collections::HashMap;
 
 use api::types::KeyValue;
 
+/// Parses the parameters of a URL query, without percent-decoding them.
+pub(crate) fn parse_query(query: &str) -> HashMap<&str, &str> {
+	query.split('&').filter_map(|param| param.split_once('=')).collect()
+}
+
+/// Decodes the `%XX` escapes of a URL component, returning `None` for malformed escapes and for
+/// decoded bytes which are not UTF-8.
+pub(crate) fn percent_decode(component: &str) -> Option<String> {
+	let mut bytes = Vec::with_capacity(component.len());
+	let mut rest = component.as_bytes();
+	while let Some((&byte, tail)) = rest.split_first() {
+		if byte != b'%' {
+			bytes.push(byte);
+			rest = tail;
+			continue;
+		}
+		let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+		bytes.push(u8::from_str_radix(hex, 16).ok()?);
+		rest = &tail[2..];
+	}
+	String::from_utf8(bytes).ok()
+}
+
+/// Escapes all characters of `component` but unreserved ones with `%XX`, so that it can be placed
+/// in any part of a URL.
+pub(crate) fn percent_encode(component: &str) -> String {
+	let mut encoded = String::with_capacity(component.len());
+	for byte in component.bytes() {
+		if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+			encoded.push(char::from(byte));
+		} else {
+			encoded.push_str(&format!("%{:02X}", byte));
+		}
+	}
+	encoded
+}
+
 /// A copy of the same printer used in vss-client to keep logs consistent.
 pub(crate) struct KeyValueVecKeyPrinter<'a>(pub(crate) &'a Vec<KeyValue>);
 
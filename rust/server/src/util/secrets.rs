@@ -0,0 +1,135 @@
+//! Support for secret configuration values.
+//!
+//! Any configuration value, whether read from the TOML file or from an environment variable, may be
+//! given as `enc:<base64>` where the payload is a 12-byte nonce followed by the ChaCha20-Poly1305
+//! ciphertext of the plaintext value. Such values are decrypted at startup with the 32-byte key
+//! provided hex-encoded in the `VSS_CONFIG_KEY` environment variable. Values may also reference a
+//! secret stored in Vault, see [`vault_secrets`], or be read from files, such as the ones
+//! Kubernetes mounts secrets as.
+
+use std::path::Path;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+
+use crate::util::vault_secrets::{self, SECRET_REFERENCE_PREFIX};
+
+pub(crate) const CONFIG_KEY_VAR: &str = "VSS_CONFIG_KEY";
+
+const ENCRYPTED_VALUE_PREFIX: &str = "enc:";
+const NONCE_LEN: usize = 12;
+
+fn load_config_key() -> Result<ChaCha20Poly1305, String> {
+	let key_hex = match std::env::var(CONFIG_KEY_VAR) {
+		Ok(key_hex) => key_hex,
+		Err(std::env::VarError::NotPresent) => {
+			return Err(format!("The {} environment variable is not set", CONFIG_KEY_VAR))
+		},
+		Err(e) => {
+			return Err(format!(
+				"Failed to load the {} environment variable: {}",
+				CONFIG_KEY_VAR, e
+			))
+		},
+	};
+	let key_hex = key_hex.trim();
+	if key_hex.len() != 64 || !key_hex.is_ascii() {
+		return Err(format!("{} must be a hex-encoded 32-byte key", CONFIG_KEY_VAR));
+	}
+	let mut key = [0u8; 32];
+	for (i, byte) in key.iter_mut().enumerate() {
+		*byte = u8::from_str_radix(&key_hex[i * 2..i * 2 + 2], 16)
+			.map_err(|_| format!("{} must be a hex-encoded 32-byte key", CONFIG_KEY_VAR))?;
+	}
+	Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+/// Returns `value` unchanged unless it carries the `enc:` prefix, in which case it is decrypted,
+/// or the `vault-kv:` prefix, in which case the secret it references is fetched from Vault.
+///
+/// `source` names where the value came from and is only used in error messages.
+pub(crate) fn resolve(value: String, source: &str) -> Result<String, String> {
+	match value.strip_prefix(SECRET_REFERENCE_PREFIX) {
+		Some(reference) => vault_secrets::fetch_secret(reference, source),
+		None => decrypt_if_encrypted(value, source),
+	}
+}
+
+/// Reads a secret value from the file at `path`, ignoring a trailing line break, and resolves it
+/// like any other value.
+pub(crate) fn read_secret_file(path: &Path, source: &str) -> Result<String, String> {
+	let value = std::fs::read_to_string(path).map_err(|e| {
+		format!("Failed to read the file {} given by {}: {}", path.display(), source, e)
+	})?;
+	resolve(value.trim_end_matches(['\n', '\r']).to_string(), source)
+}
+
+/// Returns `value` unchanged unless it carries the `enc:` prefix, in which case it is decrypted.
+///
+/// `source` names where the value came from and is only used in error messages.
+pub(crate) fn decrypt_if_encrypted(value: String, source: &str) -> Result<String, String> {
+	let payload = match value.strip_prefix(ENCRYPTED_VALUE_PREFIX) {
+		Some(payload) => payload,
+		None => return Ok(value),
+	};
+	let cipher = load_config_key()
+		.map_err(|e| format!("Unable to decrypt the encrypted value of {}: {}", source, e))?;
+	let bytes = BASE64
+		.decode(payload.trim())
+		.map_err(|_| format!("The encrypted value of {} is not valid base64", source))?;
+	if bytes.len() < NONCE_LEN {
+		return Err(format!("The encrypted value of {} is too short", source));
+	}
+	let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+	let plaintext = cipher.decrypt(Nonce::from_slice(nonce), ciphertext).map_err(|_| {
+		format!("Failed to decrypt the value of {}, is {} correct?", source, CONFIG_KEY_VAR)
+	})?;
+	String::from_utf8(plaintext)
+		.map_err(|_| format!("The decrypted value of {} is not valid UTF-8", source))
+}
+
+/// Resolves all `enc:` and `vault-kv:` prefixed strings found anywhere in the given TOML table in
+/// place.
+pub(crate) fn resolve_toml_table(table: &mut toml::Table) -> Result<(), String> {
+	for (key, value) in table.iter_mut() {
+		resolve_toml_value(value, key)?;
+	}
+	Ok(())
+}
+
+fn resolve_toml_value(value: &mut toml::Value, path: &str) -> Result<(), String> {
+	match value {
+		toml::Value::String(s)
+			if s.starts_with(ENCRYPTED_VALUE_PREFIX) || s.starts_with(SECRET_REFERENCE_PREFIX) =>
+		{
+			*s = resolve(std::mem::take(s), path)?;
+		},
+		toml::Value::Array(values) => {
+			for (idx, value) in values.iter_mut().enumerate() {
+				resolve_toml_value(value, &format!("{}[{}]", path, idx))?;
+			}
+		},
+		toml::Value::Table(table) => {
+			for (key, value) in table.iter_mut() {
+				resolve_toml_value(value, &format!("{}.{}", path, key))?;
+			}
+		},
+		_ => {},
+	}
+	Ok(())
+}
+
+/// Encrypts `plaintext` with the key in `VSS_CONFIG_KEY`, returning an `enc:` prefixed value
+/// suitable for use in the configuration file or environment.
+pub(crate) fn encrypt_config_value(plaintext: &str) -> Result<String, String> {
+	let cipher = load_config_key()?;
+	let nonce: [u8; NONCE_LEN] = rand::random();
+	let ciphertext = cipher
+		.encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+		.map_err(|_| "Failed to encrypt the configuration value".to_string())?;
+	let mut payload = nonce.to_vec();
+	payload.extend_from_slice(&ciphertext);
+	Ok(format!("{}{}", ENCRYPTED_VALUE_PREFIX, BASE64.encode(payload)))
+}
@@ -0,0 +1,68 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rustls::crypto::ring::default_provider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+/// The TLS settings of the API listener.
+pub(crate) struct ServerTlsConfig {
+	/// The PEM file holding the server certificate, followed by its intermediates.
+	pub(crate) cert_path: PathBuf,
+	/// The PEM file holding the private key of the server certificate.
+	pub(crate) key_path: PathBuf,
+	/// The PEM file holding the CAs client certificates must be issued by. If set, clients must
+	/// present a certificate (mutual TLS).
+	pub(crate) client_ca_path: Option<PathBuf>,
+}
+
+/// Loads the configured certificates and key into an acceptor terminating TLS on accepted
+/// connections, offering both HTTP/2 and HTTP/1.1 via ALPN.
+pub(crate) fn load_tls_acceptor(config: &ServerTlsConfig) -> Result<TlsAcceptor, String> {
+	let provider = Arc::new(default_provider());
+	let cert_chain = load_certificates(&config.cert_path)?;
+	let key = PrivateKeyDer::from_pem_file(&config.key_path).map_err(|e| {
+		format!("Failed to load TLS private key from {}: {}", config.key_path.display(), e)
+	})?;
+
+	let builder = ServerConfig::builder_with_provider(Arc::clone(&provider))
+		.with_safe_default_protocol_versions()
+		.map_err(|e| format!("Failed to configure TLS: {}", e))?;
+	let builder = match config.client_ca_path.as_ref() {
+		Some(client_ca_path) => {
+			let mut roots = RootCertStore::empty();
+			for ca in load_certificates(client_ca_path)? {
+				roots.add(ca).map_err(|e| {
+					format!("Invalid client CA certificate in {}: {}", client_ca_path.display(), e)
+				})?;
+			}
+			let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+				.build()
+				.map_err(|e| format!("Failed to configure TLS client verification: {}", e))?;
+			builder.with_client_cert_verifier(verifier)
+		},
+		None => builder.with_no_client_auth(),
+	};
+	let mut server_config = builder
+		.with_single_cert(cert_chain, key)
+		.map_err(|e| format!("Invalid TLS certificate or private key: {}", e))?;
+	server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+	Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
+
+pub(crate) fn load_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+	let to_error = |e: rustls::pki_types::pem::Error| {
+		format!("Failed to load certificates from {}: {}", path.display(), e)
+	};
+	let certificates = CertificateDer::pem_file_iter(path)
+		.map_err(to_error)?
+		.collect::<Result<Vec<_>, _>>()
+		.map_err(to_error)?;
+	if certificates.is_empty() {
+		return Err(format!("No certificates found in {}", path.display()));
+	}
+	Ok(certificates)
+}
@@ -0,0 +1,104 @@
+//! Fetching of configuration values from the KV secrets engine of HashiCorp Vault.
+//!
+//! Any configuration value may be given as `vault-kv:<mount>/<path>#<field>`, e.g.
+//! `vault-kv:secret/vss/postgres#password`, in which case the field is read from the latest
+//! version of the secret in the KV version 2 engine mounted at `<mount>`, on the Vault server at
+//! `VAULT_ADDR`. Vault is authenticated with the token in `VAULT_TOKEN`, or if none is set and
+//! `VAULT_K8S_ROLE` is, by logging in as that role with the service account token of the pod to
+//! the Kubernetes auth method, mounted at `VAULT_K8S_AUTH_MOUNT` or `kubernetes`.
+
+use std::time::Duration;
+
+use serde_json::json;
+
+pub(crate) const SECRET_REFERENCE_PREFIX: &str = "vault-kv:";
+
+const VAULT_ADDR_VAR: &str = "VAULT_ADDR";
+const VAULT_TOKEN_VAR: &str = "VAULT_TOKEN";
+const VAULT_K8S_ROLE_VAR: &str = "VAULT_K8S_ROLE";
+const VAULT_K8S_AUTH_MOUNT_VAR: &str = "VAULT_K8S_AUTH_MOUNT";
+
+const DEFAULT_K8S_AUTH_MOUNT: &str = "kubernetes";
+/// Where Kubernetes mounts the token of the pod's service account.
+const K8S_SERVICE_ACCOUNT_TOKEN_PATH: &str = "/var/run/secrets/kubernetes.io/serviceaccount/token";
+
+/// The time Vault has to answer a request before it fails.
+const VAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Returns the value of the field `reference` names, after the `vault-kv:` prefix.
+///
+/// `source` names where the reference came from and is only used in error messages.
+pub(crate) fn fetch_secret(reference: &str, source: &str) -> Result<String, String> {
+	let error = |e: String| format!("Failed to fetch the value of {} from Vault: {}", source, e);
+	let (path, field) = reference
+		.split_once('#')
+		.filter(|(path, field)| !path.is_empty() && !field.is_empty())
+		.ok_or_else(|| {
+			error(format!("expected {}<mount>/<path>#<field>", SECRET_REFERENCE_PREFIX))
+		})?;
+	let (mount, path) = path
+		.trim_matches('/')
+		.split_once('/')
+		.ok_or_else(|| error("the reference lacks a secret path after the mount".to_string()))?;
+
+	let address =
+		read_var(VAULT_ADDR_VAR)?.ok_or_else(|| error(format!("{} is not set", VAULT_ADDR_VAR)))?;
+	let address = address.trim_end_matches('/');
+	let client = reqwest::blocking::Client::builder()
+		.timeout(VAULT_REQUEST_TIMEOUT)
+		.build()
+		.map_err(|e| error(format!("failed to create HTTP client: {}", e)))?;
+	let token = match read_var(VAULT_TOKEN_VAR)? {
+		Some(token) => token,
+		None => login_with_service_account(&client, address).map_err(error)?,
+	};
+
+	let request = client
+		.get(format!("{}/v1/{}/data/{}", address, mount, path))
+		.header("X-Vault-Token", token);
+	let body = send(request).map_err(error)?;
+	match &body["data"]["data"][field] {
+		serde_json::Value::String(value) => Ok(value.clone()),
+		serde_json::Value::Null => Err(error(format!("the secret has no field {}", field))),
+		value => Ok(value.to_string()),
+	}
+}
+
+/// Logs in to the Kubernetes auth method with the service account token of the pod, returning
+/// the Vault token.
+fn login_with_service_account(
+	client: &reqwest::blocking::Client, address: &str,
+) -> Result<String, String> {
+	let role = read_var(VAULT_K8S_ROLE_VAR)?
+		.ok_or_else(|| format!("neither {} nor {} is set", VAULT_TOKEN_VAR, VAULT_K8S_ROLE_VAR))?;
+	let mount =
+		read_var(VAULT_K8S_AUTH_MOUNT_VAR)?.unwrap_or_else(|| DEFAULT_K8S_AUTH_MOUNT.to_string());
+	let jwt = std::fs::read_to_string(K8S_SERVICE_ACCOUNT_TOKEN_PATH)
+		.map_err(|e| format!("failed to read the service account token: {}", e))?;
+	let request = client
+		.post(format!("{}/v1/auth/{}/login", address, mount.trim_matches('/')))
+		.header(reqwest::header::CONTENT_TYPE, "application/json")
+		.body(json!({ "role": role, "jwt": jwt.trim() }).to_string());
+	let body = send(request)?;
+	body["auth"]["client_token"]
+		.as_str()
+		.map(str::to_string)
+		.ok_or_else(|| "the Kubernetes login response lacks a client token".to_string())
+}
+
+fn send(request: reqwest::blocking::RequestBuilder) -> Result<serde_json::Value, String> {
+	let response = request
+		.send()
+		.and_then(|response| response.error_for_status())
+		.map_err(|e| e.to_string())?;
+	let bytes = response.bytes().map_err(|e| e.to_string())?;
+	serde_json::from_slice(&bytes).map_err(|e| e.to_string())
+}
+
+fn read_var(var_name: &str) -> Result<Option<String>, String> {
+	match std::env::var(var_name) {
+		Ok(value) => Ok(Some(value)),
+		Err(std::env::VarError::NotPresent) => Ok(None),
+		Err(e) => Err(format!("Failed to load the {} environment variable: {}", var_name, e)),
+	}
+}
@@ -0,0 +1,88 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Sleep;
+
+/// Wraps a connection, failing writes which make no progress for longer than the configured
+/// timeout.
+///
+/// A client which stops reading its response otherwise keeps the connection, and the task serving
+/// it, alive indefinitely. Reads are not bounded, so idle keep-alive connections are unaffected.
+pub(crate) struct WriteTimeoutStream<S> {
+	inner: S,
+	timeout: Duration,
+	/// The deadline of the currently stalled write, if any.
+	deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> WriteTimeoutStream<S> {
+	pub(crate) fn new(inner: S, timeout: Duration) -> Self {
+		Self { inner, timeout, deadline: None }
+	}
+
+	/// Maps a pending write to a timeout error once its deadline passed.
+	fn poll_stalled<T>(
+		&mut self, cx: &mut Context<'_>, poll: Poll<io::Result<T>>,
+	) -> Poll<io::Result<T>> {
+		if poll.is_ready() {
+			self.deadline = None;
+			return poll;
+		}
+		let timeout = self.timeout;
+		let deadline = self.deadline.get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+		match deadline.as_mut().poll(cx) {
+			Poll::Ready(()) => {
+				self.deadline = None;
+				Poll::Ready(Err(io::Error::new(
+					io::ErrorKind::TimedOut,
+					"Timed out writing response",
+				)))
+			},
+			Poll::Pending => Poll::Pending,
+		}
+	}
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for WriteTimeoutStream<S> {
+	fn poll_read(
+		self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>,
+	) -> Poll<io::Result<()>> {
+		Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
+	}
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for WriteTimeoutStream<S> {
+	fn poll_write(
+		self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8],
+	) -> Poll<io::Result<usize>> {
+		let this = self.get_mut();
+		let poll = Pin::new(&mut this.inner).poll_write(cx, buf);
+		this.poll_stalled(cx, poll)
+	}
+
+	fn poll_write_vectored(
+		self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[io::IoSlice<'_>],
+	) -> Poll<io::Result<usize>> {
+		let this = self.get_mut();
+		let poll = Pin::new(&mut this.inner).poll_write_vectored(cx, bufs);
+		this.poll_stalled(cx, poll)
+	}
+
+	fn is_write_vectored(&self) -> bool {
+		self.inner.is_write_vectored()
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		let this = self.get_mut();
+		let poll = Pin::new(&mut this.inner).poll_flush(cx);
+		this.poll_stalled(cx, poll)
+	}
+
+	fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+	}
+}
@@ -0,0 +1,72 @@
+//! Unwrapping of data keys with the transit secrets engine of HashiCorp Vault.
+//!
+//! Data keys are wrapped by Vault, e.g. with `vault write -f transit/datakey/wrapped/<key>`, and
+//! configured as the returned `vault:v1:...` ciphertexts. The master key never leaves Vault.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use base64::Engine;
+use serde_json::json;
+
+use api::error::VssError;
+use impls::key_provider::{MasterKey, KEY_LEN};
+
+use crate::util::config::VaultTransitConfig;
+
+/// The time Vault has to unwrap a data key before the request fails.
+const VAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A [`MasterKey`] held by the transit secrets engine of a Vault server.
+pub(crate) struct VaultTransitMasterKey {
+	client: reqwest::Client,
+	decrypt_url: String,
+	token: String,
+}
+
+impl VaultTransitMasterKey {
+	pub(crate) fn new(config: &VaultTransitConfig) -> Result<Self, String> {
+		let client = reqwest::Client::builder()
+			.timeout(VAULT_REQUEST_TIMEOUT)
+			.build()
+			.map_err(|e| format!("Failed to create Vault HTTP client: {}", e))?;
+		Ok(Self {
+			client,
+			decrypt_url: format!(
+				"{}/v1/{}/decrypt/{}",
+				config.address.trim_end_matches('/'),
+				config.mount.trim_matches('/'),
+				config.key_name
+			),
+			token: config.token.clone(),
+		})
+	}
+}
+
+#[async_trait]
+impl MasterKey for VaultTransitMasterKey {
+	async fn unwrap_key(&self, wrapped_key: &str) -> Result<[u8; KEY_LEN], VssError> {
+		let internal_error = |e: String| {
+			VssError::InternalServerError(format!("Failed to unwrap data key with Vault: {}", e))
+		};
+		let response = self
+			.client
+			.post(&self.decrypt_url)
+			.header("X-Vault-Token", &self.token)
+			.header(reqwest::header::CONTENT_TYPE, "application/json")
+			.body(json!({ "ciphertext": wrapped_key }).to_string())
+			.send()
+			.await
+			.and_then(|response| response.error_for_status())
+			.map_err(|e| internal_error(e.to_string()))?;
+		let bytes = response.bytes().await.map_err(|e| internal_error(e.to_string()))?;
+		let body: serde_json::Value =
+			serde_json::from_slice(&bytes).map_err(|e| internal_error(e.to_string()))?;
+		// Vault returns plaintexts in base64.
+		body["data"]["plaintext"]
+			.as_str()
+			.and_then(|plaintext| base64::engine::general_purpose::STANDARD.decode(plaintext).ok())
+			.and_then(|key| <[u8; KEY_LEN]>::try_from(key).ok())
+			.ok_or_else(|| internal_error("Response lacks a 32-byte plaintext key".to_string()))
+	}
+}
@@ -1,37 +1,99 @@
+use http_body_util::combinators::UnsyncBoxBody;
 use http_body_util::{BodyExt, Full, Limited};
-use hyper::body::{Bytes, Incoming};
+use hyper::body::{Body, Bytes, Incoming};
 use hyper::service::Service;
 use hyper::{Request, Response, StatusCode};
 use std::collections::HashMap;
 
 use prost::Message;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use tracing::{instrument, Instrument, Span};
 
-use api::auth::Authorizer;
+use api::auth::{AccessPolicy, AccessRequest, AccessVerb, AuthResponse, Authorizer};
 use api::error::VssError;
 use api::kv_store::KvStore;
+use impls::audit_store::{AuditRecord, AuditedKey};
+use impls::health_check::HealthCheck;
+
 use api::types::{
-	DeleteObjectRequest, DeleteObjectResponse, ErrorCode, ErrorResponse, GetObjectRequest,
-	GetObjectResponse, ListKeyVersionsRequest, ListKeyVersionsResponse, PutObjectRequest,
-	PutObjectResponse,
+	AppendObjectRequest, AppendObjectResponse, DeleteObjectRequest, DeleteObjectResponse,
+	ErrorCode, ErrorResponse, ExportStoreRequest, GetObjectRequest, GetObjectResponse,
+	GetObjectVersionRequest, GetObjectsAtomicRequest, GetObjectsAtomicResponse, ImportStoreRequest,
+	ListKeyVersionsRequest, ListKeyVersionsResponse, ListObjectsRequest, ListObjectsResponse,
+	PutObjectRequest, PutObjectResponse,
 };
 use std::future::Future;
+use std::net::IpAddr;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use log::{debug, trace};
+use async_trait::async_trait;
+use log::{debug, trace, warn};
 
+use crate::account_summary::AccountSummaries;
+use crate::admin_service::unix_time_secs;
+use crate::change_stream::{
+	serve_subscription, websocket_accept_key, ChangeBroadcaster, SubscribeRequest,
+};
+use crate::client_compat::{ClientCompatibility, ShimmedRequest};
+use crate::collaborators::{Collaborators, STORE_OWNER_HEADER};
+use crate::conflict_feedback::{ConflictDetails, VersionChecks};
+use crate::delayed_deletions::DelayedDeletions;
+use crate::in_flight::{user_hash, InFlightGuard, InFlightRequests};
+use crate::load_shedding::LoadShedder;
+use crate::maintenance_mode::{MaintenanceMode, MAINTENANCE_ERROR, MAINTENANCE_RETRY_AFTER};
+use crate::metrics::Metrics;
+use crate::mutation_audit::MutationAudit;
+use crate::quota::{Quotas, QUOTA_WARNING_HEADER};
+use crate::rate_limit::{RateLimitKey, RateLimiter};
+use crate::request_capture::{CapturableRequest, RequestCapture, RequestCaptureGuard};
+use crate::security_notifications::SecurityNotifier;
+use crate::sessions::SessionLimiter;
+use crate::share_tokens::ShareTokens;
+use crate::soft_deletion::SoftDeletion;
+use crate::store_export::handle_export_store_request;
+use crate::store_import::handle_import_store_request;
+use crate::store_templates::StoreTemplates;
+use crate::util::content_encoding::{decode_body, DecodeError};
+use crate::util::json_messages::{FromJson, ToJson};
+use crate::util::parse_query;
 use crate::util::KeyValueVecKeyPrinter;
 
 const MAXIMUM_REQUEST_BODY_SIZE: usize = 1024 * 1024 * 1024;
 
+pub(crate) const DEFAULT_BODY_READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The time the backend has to answer a readiness probe before the server is reported not ready.
+const READINESS_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The message of requests failing for exceeding the request timeout, which unlike those of other
+/// internal errors is told to clients.
+pub(crate) const REQUEST_TIMED_OUT: &str = "Request timed out.";
+
+/// The time clients are told to wait before retrying requests failing while the service is
+/// temporarily unavailable, which covers the failover of a managed database.
+const UNAVAILABLE_RETRY_AFTER: Duration = Duration::from_secs(5);
+
 #[derive(Clone, Copy)]
 pub(crate) struct VssServiceConfig {
 	maximum_request_body_size: usize,
+	/// The time allowed to receive a request body, after which the request is aborted.
+	body_read_timeout: Duration,
+	/// The time allowed to execute a request, after which it fails with [`REQUEST_TIMED_OUT`].
+	request_timeout: Option<Duration>,
 }
 
 impl VssServiceConfig {
-	pub fn new(maximum_request_body_size: usize) -> Result<Self, String> {
+	/// Constructs the config, limiting request bodies to `maximum_request_body_size` if given, or
+	/// to the maximum of 1 GB otherwise.
+	pub fn new(
+		maximum_request_body_size: Option<usize>, body_read_timeout: Duration,
+		request_timeout: Option<Duration>,
+	) -> Result<Self, String> {
+		let maximum_request_body_size =
+			maximum_request_body_size.unwrap_or(MAXIMUM_REQUEST_BODY_SIZE);
 		if maximum_request_body_size > MAXIMUM_REQUEST_BODY_SIZE {
 			return Err(format!(
 				"Maximum request body size {} exceeds maximum {}",
@@ -39,46 +101,367 @@ impl VssServiceConfig {
 			));
 		}
 
-		Ok(Self { maximum_request_body_size })
+		Ok(Self { maximum_request_body_size, body_read_timeout, request_timeout })
 	}
-}
 
-impl Default for VssServiceConfig {
-	fn default() -> Self {
-		Self { maximum_request_body_size: MAXIMUM_REQUEST_BODY_SIZE }
+	pub(crate) fn maximum_request_body_size(&self) -> usize {
+		self.maximum_request_body_size
 	}
 }
 
+/// Challenges clients failing authentication to acquire credentials, e.g. by paying for them.
+#[async_trait]
+pub(crate) trait AuthChallenger: Send + Sync {
+	/// Issues a challenge, returning the value of the `WWW-Authenticate` header of the
+	/// `402 Payment Required` response carrying it.
+	async fn challenge(&self) -> Result<String, VssError>;
+}
+
 #[derive(Clone)]
 pub struct VssService {
 	store: Arc<dyn KvStore>,
+	health: Arc<dyn HealthCheck>,
 	authorizer: Arc<dyn Authorizer>,
+	/// Challenges clients failing authentication, if the authorizer issues credentials itself.
+	auth_challenger: Option<Arc<dyn AuthChallenger>>,
+	access_policy: Arc<dyn AccessPolicy>,
+	security_notifier: Option<Arc<SecurityNotifier>>,
+	share_tokens: Arc<ShareTokens>,
+	collaborators: Arc<Collaborators>,
+	store_templates: Arc<StoreTemplates>,
+	/// Holds back deletions under critical key prefixes, if enabled.
+	delayed_deletions: Option<Arc<DelayedDeletions>>,
+	/// Restores soft-deleted objects, if enabled.
+	soft_deletion: Option<Arc<SoftDeletion>>,
+	/// Summarizes users' accounts, if the backend keeps the metadata needed.
+	account_summaries: Option<Arc<AccountSummaries>>,
+	/// Pushes changes of stores to their subscribers, if enabled.
+	change_broadcaster: Option<Arc<ChangeBroadcaster>>,
+	quotas: Option<Arc<Quotas>>,
+	sessions: Option<Arc<SessionLimiter>>,
+	rate_limiter: Option<Arc<RateLimiter>>,
+	/// Sheds low-priority endpoints while the backend is degraded, if enabled.
+	load_shedder: Option<Arc<LoadShedder>>,
+	maintenance_mode: Arc<MaintenanceMode>,
+	in_flight: Arc<InFlightRequests>,
+	request_capture: Option<Arc<RequestCapture>>,
+	client_compatibility: Option<Arc<ClientCompatibility>>,
+	metrics: Option<Arc<Metrics>>,
+	mutation_audit: Option<Arc<MutationAudit>>,
 	config: VssServiceConfig,
+	/// The IP address of the client the connection is served for.
+	peer_ip: IpAddr,
 }
 
 impl VssService {
+	#[allow(clippy::too_many_arguments)]
 	pub(crate) fn new(
-		store: Arc<dyn KvStore>, authorizer: Arc<dyn Authorizer>, config: VssServiceConfig,
+		store: Arc<dyn KvStore>, health: Arc<dyn HealthCheck>, authorizer: Arc<dyn Authorizer>,
+		auth_challenger: Option<Arc<dyn AuthChallenger>>, access_policy: Arc<dyn AccessPolicy>,
+		security_notifier: Option<Arc<SecurityNotifier>>, share_tokens: Arc<ShareTokens>,
+		collaborators: Arc<Collaborators>, store_templates: Arc<StoreTemplates>,
+		delayed_deletions: Option<Arc<DelayedDeletions>>, soft_deletion: Option<Arc<SoftDeletion>>,
+		account_summaries: Option<Arc<AccountSummaries>>,
+		change_broadcaster: Option<Arc<ChangeBroadcaster>>, quotas: Option<Arc<Quotas>>,
+		sessions: Option<Arc<SessionLimiter>>, rate_limiter: Option<Arc<RateLimiter>>,
+		load_shedder: Option<Arc<LoadShedder>>, maintenance_mode: Arc<MaintenanceMode>,
+		in_flight: Arc<InFlightRequests>, request_capture: Option<Arc<RequestCapture>>,
+		client_compatibility: Option<Arc<ClientCompatibility>>, metrics: Option<Arc<Metrics>>,
+		mutation_audit: Option<Arc<MutationAudit>>, config: VssServiceConfig, peer_ip: IpAddr,
 	) -> Self {
-		Self { store, authorizer, config }
+		Self {
+			store,
+			health,
+			authorizer,
+			auth_challenger,
+			access_policy,
+			security_notifier,
+			share_tokens,
+			collaborators,
+			store_templates,
+			delayed_deletions,
+			soft_deletion,
+			account_summaries,
+			change_broadcaster,
+			quotas,
+			sessions,
+			rate_limiter,
+			load_shedder,
+			maintenance_mode,
+			in_flight,
+			request_capture,
+			client_compatibility,
+			metrics,
+			mutation_audit,
+			config,
+			peer_ip,
+		}
+	}
+}
+
+/// A request operating on a single store.
+pub(crate) trait StoreScopedRequest {
+	/// The kind of access the request needs, as presented to the [`AccessPolicy`].
+	const VERB: AccessVerb;
+
+	fn store_id(&self) -> &str;
+
+	/// The keys the request addresses, or for listings the listed key prefix.
+	fn keys(&self) -> Vec<&str>;
+
+	/// Whether [`Self::keys`] are prefixes of the keys the request addresses, e.g. for listings.
+	fn keys_are_prefixes(&self) -> bool {
+		false
+	}
+
+	/// Whether the request writes values, and so is rejected once the store owner's quota is
+	/// reached.
+	fn adds_values(&self) -> bool {
+		false
+	}
+
+	/// The versions the request is conditional on, if any, which are described to the client when
+	/// it fails with a conflict.
+	fn version_checks(&self) -> Option<VersionChecks> {
+		None
+	}
+
+	/// The keys the request writes or deletes, recorded in the audit log if it is enabled, or
+	/// `None` if the request does not mutate objects.
+	fn audited_keys(&self) -> Option<Vec<AuditedKey>> {
+		None
+	}
+}
+
+impl StoreScopedRequest for GetObjectRequest {
+	const VERB: AccessVerb = AccessVerb::Read;
+
+	fn store_id(&self) -> &str {
+		&self.store_id
+	}
+
+	fn keys(&self) -> Vec<&str> {
+		vec![&self.key]
+	}
+}
+
+impl StoreScopedRequest for GetObjectVersionRequest {
+	const VERB: AccessVerb = AccessVerb::Read;
+
+	fn store_id(&self) -> &str {
+		&self.store_id
+	}
+
+	fn keys(&self) -> Vec<&str> {
+		vec![&self.key]
+	}
+}
+
+impl StoreScopedRequest for GetObjectsAtomicRequest {
+	const VERB: AccessVerb = AccessVerb::Read;
+
+	fn store_id(&self) -> &str {
+		&self.store_id
+	}
+
+	fn keys(&self) -> Vec<&str> {
+		self.keys.iter().map(String::as_str).collect()
+	}
+}
+
+impl StoreScopedRequest for PutObjectRequest {
+	const VERB: AccessVerb = AccessVerb::Write;
+
+	fn store_id(&self) -> &str {
+		&self.store_id
+	}
+
+	fn keys(&self) -> Vec<&str> {
+		self.transaction_items.iter().chain(&self.delete_items).map(|kv| kv.key.as_str()).collect()
+	}
+
+	fn adds_values(&self) -> bool {
+		!self.transaction_items.is_empty()
+	}
+
+	fn version_checks(&self) -> Option<VersionChecks> {
+		let keys: Vec<(String, i64)> = (self.transaction_items.iter().chain(&self.delete_items))
+			.filter(|kv| kv.version != -1)
+			.map(|kv| (kv.key.clone(), kv.version))
+			.collect();
+		if keys.is_empty() && self.global_version.is_none() {
+			return None;
+		}
+		Some(VersionChecks {
+			store_id: self.store_id.clone(),
+			keys,
+			global_version: self.global_version,
+		})
+	}
+
+	fn audited_keys(&self) -> Option<Vec<AuditedKey>> {
+		let written = self.transaction_items.iter().map(|kv| (kv, false));
+		let deleted = self.delete_items.iter().map(|kv| (kv, true));
+		Some(
+			written
+				.chain(deleted)
+				.map(|(kv, deleted)| AuditedKey {
+					key: kv.key.clone(),
+					version: kv.version,
+					deleted,
+				})
+				.collect(),
+		)
+	}
+}
+
+impl StoreScopedRequest for DeleteObjectRequest {
+	const VERB: AccessVerb = AccessVerb::Delete;
+
+	fn store_id(&self) -> &str {
+		&self.store_id
+	}
+
+	fn keys(&self) -> Vec<&str> {
+		self.key_value.iter().map(|kv| kv.key.as_str()).collect()
+	}
+
+	fn audited_keys(&self) -> Option<Vec<AuditedKey>> {
+		Some(
+			(self.key_value.iter())
+				.map(|kv| AuditedKey { key: kv.key.clone(), version: kv.version, deleted: true })
+				.collect(),
+		)
+	}
+}
+
+impl StoreScopedRequest for AppendObjectRequest {
+	const VERB: AccessVerb = AccessVerb::Write;
+
+	fn store_id(&self) -> &str {
+		&self.store_id
+	}
+
+	fn keys(&self) -> Vec<&str> {
+		vec![&self.key]
+	}
+
+	fn adds_values(&self) -> bool {
+		true
+	}
+
+	/// Appends are not conditional on a version.
+	fn audited_keys(&self) -> Option<Vec<AuditedKey>> {
+		Some(vec![AuditedKey { key: self.key.clone(), version: -1, deleted: false }])
+	}
+}
+
+impl StoreScopedRequest for ListKeyVersionsRequest {
+	const VERB: AccessVerb = AccessVerb::List;
+
+	fn store_id(&self) -> &str {
+		&self.store_id
+	}
+
+	fn keys(&self) -> Vec<&str> {
+		vec![self.key_prefix.as_deref().unwrap_or_default()]
+	}
+
+	fn keys_are_prefixes(&self) -> bool {
+		true
+	}
+}
+
+// Listing objects reveals their values, so it needs the access reads of the listed prefix need.
+impl StoreScopedRequest for ListObjectsRequest {
+	const VERB: AccessVerb = AccessVerb::Read;
+
+	fn store_id(&self) -> &str {
+		&self.store_id
+	}
+
+	fn keys(&self) -> Vec<&str> {
+		vec![self.key_prefix.as_deref().unwrap_or_default()]
+	}
+
+	fn keys_are_prefixes(&self) -> bool {
+		true
+	}
+}
+
+// Exports reveal all values of the store, so they need the access reads of any key need.
+impl StoreScopedRequest for ExportStoreRequest {
+	const VERB: AccessVerb = AccessVerb::Read;
+
+	fn store_id(&self) -> &str {
+		&self.store_id
+	}
+
+	fn keys(&self) -> Vec<&str> {
+		vec![""]
+	}
+
+	fn keys_are_prefixes(&self) -> bool {
+		true
+	}
+}
+
+// Imports write arbitrary keys, so they need access to the whole store.
+impl StoreScopedRequest for ImportStoreRequest {
+	const VERB: AccessVerb = AccessVerb::Write;
+
+	fn store_id(&self) -> &str {
+		&self.store_id
+	}
+
+	fn keys(&self) -> Vec<&str> {
+		vec![""]
+	}
+
+	fn keys_are_prefixes(&self) -> bool {
+		true
+	}
+
+	fn adds_values(&self) -> bool {
+		true
+	}
+
+	/// The imported keys are only known once the archive is unpacked, so imports are recorded
+	/// without them.
+	fn audited_keys(&self) -> Option<Vec<AuditedKey>> {
+		Some(Vec::new())
 	}
 }
 
 pub(crate) const BASE_PATH_PREFIX: &str = "/vss";
 
+/// The body of a response, which is streamed as it is produced for responses too large to buffer,
+/// such as exports. Streamed bodies fail if producing them fails midway, aborting the response.
+pub(crate) type VssBody = UnsyncBoxBody<Bytes, VssError>;
+
+/// A body holding all of `bytes`.
+pub(crate) fn full_body(bytes: impl Into<Bytes>) -> VssBody {
+	Full::new(bytes.into()).map_err(|never| match never {}).boxed_unsync()
+}
+
 impl Service<Request<Incoming>> for VssService {
-	type Response = Response<Full<Bytes>>;
+	type Response = Response<VssBody>;
 	type Error = hyper::Error;
 	type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
 
 	fn call(&self, req: Request<Incoming>) -> Self::Future {
-		let store = Arc::clone(&self.store);
-		let authorizer = Arc::clone(&self.authorizer);
+		let service = self.clone();
+		let share_tokens = Arc::clone(&self.share_tokens);
+		let collaborators = Arc::clone(&self.collaborators);
+		let store_templates = Arc::clone(&self.store_templates);
+		let delayed_deletions = self.delayed_deletions.clone();
+		let soft_deletion = self.soft_deletion.clone();
+		let account_summaries = self.account_summaries.clone();
 		let path = req.uri().path().to_owned();
 		let method = req.method().to_string();
-		let maximum_request_body_size = self.config.maximum_request_body_size;
 
-		let prefix_stripped_path = path.strip_prefix(BASE_PATH_PREFIX).unwrap_or_default().to_owned();
+		let prefix_stripped_path =
+			path.strip_prefix(BASE_PATH_PREFIX).unwrap_or_default().to_owned();
 
 		// Create a root span for the HTTP request
 		let span = tracing::info_span!(
@@ -88,6 +471,11 @@ impl Service<Request<Incoming>> for VssService {
 			http.route = %prefix_stripped_path,
 			span.type = "web",
 			resource.name = %format!("{} {}", method, prefix_stripped_path),
+			// Recorded while the request is handled.
+			operation = tracing::field::Empty,
+			http.status_code = tracing::field::Empty,
+			user.hash = tracing::field::Empty,
+			error = tracing::field::Empty,
 		);
 
 		// Use .instrument(span) instead of span.enter() for async code.
@@ -95,52 +483,197 @@ impl Service<Request<Incoming>> for VssService {
 		// resume on a different thread, causing span lifecycle issues.
 		Box::pin(
 			async move {
+				// Probes are served outside of the API's path prefix, where orchestrators such as
+				// Kubernetes expect them.
+				match path.as_str() {
+					"/healthz" => return Ok(plain_response(StatusCode::OK, "ok")),
+					"/readyz" => return Ok(handle_readiness_request(service.health).await),
+					_ => {},
+				}
 				match prefix_stripped_path.as_str() {
 					"/health" => Ok(Response::builder()
 						.status(StatusCode::OK)
-						.body(Full::new(Bytes::new()))
+						.body(full_body(Bytes::new()))
 						.unwrap()),
 					"/getObject" => {
+						handle_request(service, req, "getObject", handle_get_object_request).await
+					},
+					"/getObjectsAtomic" => {
 						handle_request(
-							store,
-							authorizer,
+							service,
 							req,
-							maximum_request_body_size,
-							"getObject",
-							handle_get_object_request,
+							"getObjectsAtomic",
+							handle_get_objects_atomic_request,
 						)
-							.await
+						.await
 					},
-					"/putObjects" => {
+					"/getObjectVersion" => {
 						handle_request(
-							store,
-							authorizer,
+							service,
 							req,
-							maximum_request_body_size,
-							"putObjects",
-							handle_put_object_request,
+							"getObjectVersion",
+							handle_get_object_version_request,
 						)
-							.await
+						.await
+					},
+					"/putObjects" => {
+						handle_request(service, req, "putObjects", handle_put_object_request).await
 					},
 					"/deleteObject" => {
-						handle_request(
-							store,
-							authorizer,
+						handle_request(service, req, "deleteObject", handle_delete_object_request)
+							.await
+					},
+					"/appendObject" => {
+						handle_request(service, req, "appendObject", handle_append_object_request)
+							.await
+					},
+					"/listKeyVersions" => {
+						handle_request(service, req, "listKeyVersions", handle_list_object_request)
+							.await
+					},
+					"/listObjects" => {
+						handle_request(service, req, "listObjects", handle_list_objects_request)
+							.await
+					},
+					"/exportStore" => {
+						handle_request(service, req, "exportStore", handle_export_store_request)
+							.await
+					},
+					"/importStore" => {
+						handle_request(service, req, "importStore", handle_import_store_request)
+							.await
+					},
+					"/createShareToken" => {
+						handle_json_request(
+							service,
 							req,
-							maximum_request_body_size,
-							"deleteObject",
-							handle_delete_object_request,
+							"createShareToken",
+							|auth, request| async move {
+								share_tokens.create(auth.user_token, request).await
+							},
 						)
 						.await
 					},
-					"/listKeyVersions" => {
-						handle_request(
-							store,
-							authorizer,
+					"/revokeShareToken" => {
+						handle_json_request(
+							service,
+							req,
+							"revokeShareToken",
+							|auth, request| async move {
+								share_tokens.revoke(auth.user_token, request).await
+							},
+						)
+						.await
+					},
+					"/getSharedObject" => {
+						handle_get_shared_object_request(service, share_tokens, req).await
+					},
+					"/subscribe" => handle_subscribe_request(service, req).await,
+					"/grantStoreAccess" => {
+						handle_json_request(
+							service,
+							req,
+							"grantStoreAccess",
+							|auth, request| async move {
+								collaborators.grant(auth.user_token, request).await
+							},
+						)
+						.await
+					},
+					"/revokeStoreAccess" => {
+						handle_json_request(
+							service,
+							req,
+							"revokeStoreAccess",
+							|auth, request| async move {
+								collaborators.revoke(auth.user_token, request).await
+							},
+						)
+						.await
+					},
+					"/listStoreAccess" => {
+						handle_json_request(
+							service,
+							req,
+							"listStoreAccess",
+							|auth, request| async move {
+								collaborators.list(auth.user_token, request).await
+							},
+						)
+						.await
+					},
+					"/initializeStore" => {
+						let resolver = Arc::clone(&store_templates);
+						handle_json_store_request(
+							service,
+							req,
+							"initializeStore",
+							move |request| resolver.resolve(request),
+							|_, user_token, application| async move {
+								store_templates.initialize(user_token, application).await
+							},
+						)
+						.await
+					},
+					"/listPendingDeletions" => {
+						handle_json_request(
+							service,
+							req,
+							"listPendingDeletions",
+							|auth, request| async move {
+								delayed_deletions_enabled(delayed_deletions)?
+									.list(auth.user_token, request)
+									.await
+							},
+						)
+						.await
+					},
+					"/cancelDeletion" => {
+						handle_json_store_request(
+							service,
+							req,
+							"cancelDeletion",
+							Ok,
+							|_, user_token, request| async move {
+								delayed_deletions_enabled(delayed_deletions)?
+									.cancel(user_token, request)
+									.await
+							},
+						)
+						.await
+					},
+					"/restoreKey" => {
+						handle_json_store_request(
+							service,
+							req,
+							"restoreKey",
+							Ok,
+							|_, user_token, request| async move {
+								let soft_deletion = soft_deletion.ok_or_else(|| {
+									VssError::InvalidRequestError(
+										"Soft deletion is not enabled.".to_string(),
+									)
+								})?;
+								soft_deletion.restore(user_token, request).await
+							},
+						)
+						.await
+					},
+					"/getAccountSummary" => {
+						handle_json_request(
+							service,
 							req,
-							maximum_request_body_size,
-							"listKeyVersions",
-							handle_list_object_request,
+							"getAccountSummary",
+							|auth, request| async move {
+								let account_summaries = account_summaries.ok_or_else(|| {
+									VssError::InvalidRequestError(
+										"Account summaries are not supported by the storage backend."
+											.to_string(),
+									)
+								})?;
+								let profile = auth.limit_profile.as_deref();
+								account_summaries.get(auth.user_token, profile, request).await
+							},
 						)
 						.await
 					},
@@ -153,11 +686,15 @@ impl Service<Request<Incoming>> for VssService {
 							&format!("Invalid request path: {}", prefix_stripped_path),
 							sentry::Level::Warning,
 						);
-						tracing::warn!(http.status_code = 400, "Invalid request path: {}", prefix_stripped_path);
+						tracing::warn!(
+							http.status_code = 400,
+							"Invalid request path: {}",
+							prefix_stripped_path
+						);
 						let error_msg = "Invalid request path.".as_bytes();
 						Ok(Response::builder()
 							.status(StatusCode::BAD_REQUEST)
-							.body(Full::new(Bytes::from(error_msg)))
+							.body(full_body(Bytes::from(error_msg)))
 							.unwrap())
 					},
 				}
@@ -176,7 +713,7 @@ impl Service<Request<Incoming>> for VssService {
 		span.type = "vss"
 	)
 )]
-async fn handle_get_object_request(
+pub(crate) async fn handle_get_object_request(
 	store: Arc<dyn KvStore>, user_token: String, request: GetObjectRequest,
 ) -> Result<GetObjectResponse, VssError> {
 	let request_id: u64 = rand::random();
@@ -188,6 +725,54 @@ async fn handle_get_object_request(
 	result
 }
 
+#[instrument(
+	name = "vss.get_objects_atomic",
+	skip(store, user_token, request),
+	fields(
+		store_id = %request.store_id,
+		keys_count = %request.keys.len(),
+		span.type = "vss"
+	)
+)]
+async fn handle_get_objects_atomic_request(
+	store: Arc<dyn KvStore>, user_token: String, request: GetObjectsAtomicRequest,
+) -> Result<GetObjectsAtomicResponse, VssError> {
+	let request_id: u64 = rand::random();
+	trace!("Handling GetObjectsAtomicRequest {} for {} keys.", request_id, request.keys.len());
+	let result = store.get_objects_atomic(user_token, request).await;
+	if let Err(ref e) = result {
+		debug!("GetObjectsAtomicRequest {} failed: {}", request_id, e);
+	}
+	result
+}
+
+#[instrument(
+	name = "vss.get_object_version",
+	skip(store, user_token, request),
+	fields(
+		store_id = %request.store_id,
+		key = %request.key,
+		version = %request.version,
+		span.type = "vss"
+	)
+)]
+async fn handle_get_object_version_request(
+	store: Arc<dyn KvStore>, user_token: String, request: GetObjectVersionRequest,
+) -> Result<GetObjectResponse, VssError> {
+	let request_id: u64 = rand::random();
+	trace!(
+		"Handling GetObjectVersionRequest {} for version {} of key {}.",
+		request_id,
+		request.version,
+		request.key
+	);
+	let result = store.get_object_version(user_token, request).await;
+	if let Err(ref e) = result {
+		debug!("GetObjectVersionRequest {} failed: {}", request_id, e);
+	}
+	result
+}
+
 #[instrument(
 	name = "vss.put_objects",
 	skip(store, user_token, request),
@@ -198,7 +783,7 @@ async fn handle_get_object_request(
 		span.type = "vss"
 	)
 )]
-async fn handle_put_object_request(
+pub(crate) async fn handle_put_object_request(
 	store: Arc<dyn KvStore>, user_token: String, request: PutObjectRequest,
 ) -> Result<PutObjectResponse, VssError> {
 	let request_id: u64 = rand::random();
@@ -223,7 +808,7 @@ async fn handle_put_object_request(
 		span.type = "vss"
 	)
 )]
-async fn handle_delete_object_request(
+pub(crate) async fn handle_delete_object_request(
 	store: Arc<dyn KvStore>, user_token: String, request: DeleteObjectRequest,
 ) -> Result<DeleteObjectResponse, VssError> {
 	let request_id: u64 = rand::random();
@@ -239,6 +824,28 @@ async fn handle_delete_object_request(
 	result
 }
 
+#[instrument(
+	name = "vss.append_object",
+	skip(store, user_token, request),
+	fields(
+		store_id = %request.store_id,
+		key = %request.key,
+		value_size = request.value.len(),
+		span.type = "vss"
+	)
+)]
+async fn handle_append_object_request(
+	store: Arc<dyn KvStore>, user_token: String, request: AppendObjectRequest,
+) -> Result<AppendObjectResponse, VssError> {
+	let request_id: u64 = rand::random();
+	trace!("Handling AppendObjectRequest {} for key {}.", request_id, request.key);
+	let result = store.append(user_token, request).await;
+	if let Err(ref e) = result {
+		debug!("AppendObjectRequest {} failed: {}", request_id, e);
+	}
+	result
+}
+
 #[instrument(
 	name = "vss.list_key_versions",
 	skip(store, user_token, request),
@@ -248,7 +855,7 @@ async fn handle_delete_object_request(
 		span.type = "vss"
 	)
 )]
-async fn handle_list_object_request(
+pub(crate) async fn handle_list_object_request(
 	store: Arc<dyn KvStore>, user_token: String, request: ListKeyVersionsRequest,
 ) -> Result<ListKeyVersionsResponse, VssError> {
 	let request_id: u64 = rand::random();
@@ -266,13 +873,63 @@ async fn handle_list_object_request(
 	result
 }
 
+#[instrument(
+	name = "vss.list_objects",
+	skip(store, user_token, request),
+	fields(
+		store_id = %request.store_id,
+		key_prefix = ?request.key_prefix,
+		span.type = "vss"
+	)
+)]
+async fn handle_list_objects_request(
+	store: Arc<dyn KvStore>, user_token: String, request: ListObjectsRequest,
+) -> Result<ListObjectsResponse, VssError> {
+	let request_id: u64 = rand::random();
+	trace!(
+		"Handling ListObjectsRequest {} for key_prefix {:?}, page_size {:?}, page_token {:?}",
+		request_id,
+		request.key_prefix,
+		request.page_size,
+		request.page_token
+	);
+	let result = store.list_objects(user_token, request).await;
+	if let Err(ref e) = result {
+		debug!("ListObjectsRequest {} failed: {}", request_id, e);
+	}
+	result
+}
+
+/// Reports the server ready if the backend completes a trivial round trip to its database in
+/// time, so load balancers stop routing requests to instances which cannot serve them.
+async fn handle_readiness_request(health: Arc<dyn HealthCheck>) -> Response<VssBody> {
+	match tokio::time::timeout(READINESS_CHECK_TIMEOUT, health.check_health()).await {
+		Ok(Ok(())) => plain_response(StatusCode::OK, "ready"),
+		Ok(Err(e)) => {
+			warn!("Readiness check failed: {}", e);
+			plain_response(StatusCode::SERVICE_UNAVAILABLE, "Backend unavailable.")
+		},
+		Err(_) => {
+			warn!("Readiness check timed out after {:?}", READINESS_CHECK_TIMEOUT);
+			plain_response(StatusCode::SERVICE_UNAVAILABLE, "Backend unavailable.")
+		},
+	}
+}
+
+fn plain_response(status: StatusCode, body: &'static str) -> Response<VssBody> {
+	Response::builder()
+		.status(status)
+		.body(full_body(Bytes::from(body)))
+		// unwrap safety: body only errors when previous chained calls failed.
+		.unwrap()
+}
+
 /// Test endpoint to verify Sentry integration is working.
 /// Sends a test error event to Sentry and returns a confirmation message.
 async fn handle_test_sentry_request(
 ) -> Result<<VssService as Service<Request<Incoming>>>::Response, hyper::Error> {
 	// Create a test error and capture it
-	let test_error =
-		std::io::Error::new(std::io::ErrorKind::Other, "Test error from /vss/testSentry endpoint");
+	let test_error = std::io::Error::other("Test error from /vss/testSentry endpoint");
 	sentry::capture_error(&test_error);
 
 	// Also send a test message
@@ -281,81 +938,263 @@ async fn handle_test_sentry_request(
 	let response_body = b"Sentry test events sent. Check your Sentry dashboard.";
 	Ok(Response::builder()
 		.status(StatusCode::OK)
-		.body(Full::new(Bytes::from(response_body.to_vec())))
+		.body(full_body(Bytes::from(response_body.to_vec())))
 		.unwrap())
 }
-async fn handle_request<
-	T: Message + Default,
-	R: Message,
-	F: FnOnce(Arc<dyn KvStore>, String, T) -> Fut + Send + 'static,
-	Fut: Future<Output = Result<R, VssError>> + Send,
->(
-	store: Arc<dyn KvStore>, authorizer: Arc<dyn Authorizer>, request: Request<Incoming>,
-	maximum_request_body_size: usize, operation_name: &str, handler: F,
-) -> Result<<VssService as Service<Request<Incoming>>>::Response, hyper::Error> {
-	let (parts, body) = request.into_parts();
-	let headers_map = parts
-		.headers
-		.iter()
-		// HeaderName converted to a string is in lowercase.
-		.map(|(k, v)| (k.to_string(), v.to_str().unwrap_or_default().to_string()))
-		.collect::<HashMap<String, String>>();
-	debug_assert!(headers_map.keys().all(|key| key.chars().all(|c| !c.is_uppercase())));
+impl VssService {
+	/// Starts tracking a request in the in-flight request registry until the guard is dropped.
+	pub(crate) fn start_request(&self, operation_name: &'static str) -> InFlightGuard {
+		Span::current().record("operation", operation_name);
+		self.in_flight.start(operation_name)
+	}
 
-	// Create a span for authentication and use .instrument() for async-safety
-	let auth_span = tracing::info_span!("auth.verify", span.type = "auth");
-	let user_token = match authorizer
-		.verify(&headers_map)
-		.instrument(auth_span)
-		.await
-	{
-		Ok(auth_response) => {
-			tracing::info!("Authentication successful");
-			auth_response.user_token
-		},
-		Err(e) => {
-			sentry::capture_message(
-				&format!("Authentication failure: {}", e),
-				sentry::Level::Warning,
-			);
-			tracing::warn!(error = %e, "Authentication failure");
-			return Ok(build_error_response(e));
-		},
-	};
+	/// Records the size of the body of a request for `operation`, if metrics are collected.
+	pub(crate) fn observe_request_body(&self, operation_name: &str, size: usize) {
+		if let Some(metrics) = self.metrics.as_ref() {
+			metrics.observe_request_body(operation_name, size);
+		}
+	}
 
-	let limited_body = Limited::new(body, maximum_request_body_size);
-	let bytes = match limited_body.collect().await {
-		Ok(body) => body.to_bytes(),
-		Err(_) => {
-			Span::current().record("http.status_code", 413);
-			Span::current().record("error", true);
-			tracing::warn!(http.status_code = 413, "Request body too large");
-			return Ok(Response::builder()
-				.status(StatusCode::PAYLOAD_TOO_LARGE)
-				.body(Full::new(Bytes::from("Request body too large")))
-				// unwrap safety: body only errors when previous chained calls failed.
-				.unwrap());
-		},
-	};
+	/// Records the outcome of a request for `operation` started at `started_at`, if metrics are
+	/// collected, and towards the backend's health, if endpoints are shed.
+	pub(crate) fn observe_response(
+		&self, operation_name: &str, status: u16, started_at: Instant, body_size: usize,
+	) {
+		if let Some(metrics) = self.metrics.as_ref() {
+			metrics.observe_response(operation_name, status, started_at.elapsed(), body_size);
+		}
+		if let Some(load_shedder) = self.load_shedder.as_ref() {
+			load_shedder.record(status == 500 || status == 503);
+		}
+	}
 
-	// Record request body size
-	Span::current().record("http.request.body.size", bytes.len());
+	/// Records a request for `operation` started at `started_at` which was shed, if metrics are
+	/// collected. Shed requests never reached the backend, so they say nothing about its health.
+	pub(crate) fn observe_shed_response(&self, operation_name: &str, started_at: Instant) {
+		if let Some(metrics) = self.metrics.as_ref() {
+			metrics.observe_response(operation_name, 503, started_at.elapsed(), 0);
+		}
+	}
 
-	match T::decode(bytes) {
-		Ok(request) => match handler(store.clone(), user_token, request).await {
-			Ok(response) => {
-				let response_bytes = response.encode_to_vec();
-				Span::current().record("http.response.body.size", response_bytes.len());
-				Span::current().record("http.status_code", 200);
-				tracing::info!(
-					http.status_code = 200,
-					operation = operation_name,
-					"Request completed successfully"
+	/// Checks whether a request for `operation_name` is served under the current health of the
+	/// backend, if endpoints are shed. Returns the time after which the client may retry if it is
+	/// shed.
+	pub(crate) fn check_load(&self, operation_name: &str) -> Result<(), Duration> {
+		match self.load_shedder.as_ref() {
+			Some(load_shedder) => load_shedder.check(operation_name),
+			None => Ok(()),
+		}
+	}
+
+	/// Checks whether a request for `operation_name` is served, failing it if it writes while the
+	/// server is in maintenance mode.
+	pub(crate) fn check_maintenance(&self, operation_name: &str) -> Result<(), VssError> {
+		self.maintenance_mode.check(operation_name)
+	}
+
+	/// Returns the service for requests of the client with the given IP address.
+	#[cfg(feature = "grpc")]
+	pub(crate) fn for_peer(&self, peer_ip: IpAddr) -> Self {
+		Self { peer_ip, ..self.clone() }
+	}
+
+	/// Authenticates the caller of a [`KvStore`] request from its headers and checks their session
+	/// limit.
+	///
+	/// Along with [`Self::execute_request`], this forms the part of request handling shared by all
+	/// transports.
+	pub(crate) async fn authenticate(
+		&self, headers_map: &HashMap<String, String>, in_flight: &InFlightGuard,
+	) -> Result<AuthResponse, VssError> {
+		in_flight.set_stage("authorizing");
+		// Create a span for authentication and use .instrument() for async-safety
+		let auth_span = tracing::info_span!("auth.verify", span.type = "auth");
+		let caller = match self.authorizer.verify(headers_map).instrument(auth_span).await {
+			Ok(auth_response) => {
+				tracing::info!("Authentication successful");
+				auth_response
+			},
+			Err(e) => {
+				sentry::capture_message(
+					&format!("Authentication failure: {}", e),
+					sentry::Level::Warning,
 				);
-				Ok(Response::builder()
-					.body(Full::new(Bytes::from(response_bytes)))
-					// unwrap safety: body only errors when previous chained calls failed.
-					.unwrap())
+				tracing::warn!(error = %e, "Authentication failure");
+				return Err(e);
+			},
+		};
+
+		in_flight.set_user(&caller.user_token);
+		Span::current().record("user.hash", user_hash(&caller.user_token));
+		if let Some(sessions) = self.sessions.as_ref() {
+			let user_agent = headers_map.get("user-agent").map(String::as_str);
+			if let Err(e) = sessions.observe_request(&caller.user_token, self.peer_ip, user_agent) {
+				Span::current().record("http.status_code", get_error_status_code(&e));
+				tracing::warn!(error = %e, "Session limit exceeded");
+				return Err(e);
+			}
+		}
+		Ok(caller)
+	}
+
+	/// Takes a request of the given user, or of the client's IP address for requests which are not
+	/// authenticated, from their rate limit, if requests are rate limited. Returns the time after
+	/// which the client may retry if the limit is exceeded.
+	pub(crate) fn check_rate_limit(&self, user_token: Option<&str>) -> Result<(), Duration> {
+		let Some(rate_limiter) = self.rate_limiter.as_ref() else {
+			return Ok(());
+		};
+		let key = match user_token {
+			Some(user_token) => RateLimitKey::User(user_token.to_string()),
+			None => RateLimitKey::Ip(self.peer_ip),
+		};
+		rate_limiter.acquire(key)
+	}
+
+	/// Challenges a client which failed authentication, if an [`AuthChallenger`] is configured.
+	async fn challenge(&self) -> Option<Result<String, VssError>> {
+		let challenger = self.auth_challenger.as_ref()?;
+		let challenge = challenger.challenge().await;
+		if let Err(e) = &challenge {
+			warn!("Failed to issue authentication challenge: {}", e);
+		}
+		Some(challenge)
+	}
+
+	/// Whether the caller's scopes and the [`AccessPolicy`] would let them read the given keys of
+	/// a store, without executing a read.
+	async fn may_read(
+		&self, caller: &AuthResponse, owner_user_token: &str, store_id: &str,
+		keys: &[(String, i64)],
+	) -> bool {
+		let access_request = AccessRequest {
+			caller,
+			owner_user_token,
+			store_id,
+			keys: keys.iter().map(|(key, _)| key.as_str()).collect(),
+			keys_are_prefixes: false,
+			verb: AccessVerb::Read,
+		};
+		caller.permits(AccessVerb::Read) && self.access_policy.check(&access_request).await.is_ok()
+	}
+
+	/// Executes an authenticated [`KvStore`] request against the store it addresses, which may be
+	/// owned by another user the caller collaborates with, if the caller's scopes and stores permit
+	/// it and the [`AccessPolicy`] allows it.
+	/// Returns the response along with a quota warning for the store owner, if any. If a
+	/// conditional write fails with a conflict, `conflict_details` is set to the current versions
+	/// differing from those the request expected.
+	pub(crate) async fn execute_request<
+		T: StoreScopedRequest,
+		R,
+		F: FnOnce(Arc<dyn KvStore>, String, T) -> Fut,
+		Fut: Future<Output = Result<R, VssError>>,
+	>(
+		&self, headers_map: &HashMap<String, String>, in_flight: &InFlightGuard,
+		caller: AuthResponse, request: T, handler: F,
+		conflict_details: &mut Option<ConflictDetails>,
+	) -> Result<(R, Option<String>), VssError> {
+		in_flight.set_stage("checking store access");
+		if !caller.permits(T::VERB) {
+			let e = VssError::AuthError(format!(
+				"The credentials do not permit {} access.",
+				T::VERB.name()
+			));
+			Span::current().record("http.status_code", get_error_status_code(&e));
+			tracing::warn!(error = %e, "Access denied");
+			return Err(e);
+		}
+		if !caller.permits_store(request.store_id()) {
+			let e = VssError::AuthError(format!(
+				"The credentials do not permit access to store {}.",
+				request.store_id()
+			));
+			Span::current().record("http.status_code", get_error_status_code(&e));
+			tracing::warn!(error = %e, "Access denied");
+			return Err(e);
+		}
+		let user_token = headers_map
+			.get(STORE_OWNER_HEADER)
+			.cloned()
+			.unwrap_or_else(|| caller.user_token.clone());
+		let access_request = AccessRequest {
+			caller: &caller,
+			owner_user_token: &user_token,
+			store_id: request.store_id(),
+			keys: request.keys(),
+			keys_are_prefixes: request.keys_are_prefixes(),
+			verb: T::VERB,
+		};
+		if let Err(e) = self.access_policy.check(&access_request).await {
+			Span::current().record("http.status_code", get_error_status_code(&e));
+			tracing::warn!(error = %e, "Access denied");
+			return Err(e);
+		}
+		let access = self
+			.security_notifier
+			.clone()
+			.map(|notifier| (notifier, user_token.clone(), request.store_id().to_string()));
+		// Only writes can push a user over their quota, so only these carry warnings. The
+		// caller's profile says nothing about the limits of a store owner they collaborate with.
+		let limit_profile =
+			caller.limit_profile.clone().filter(|_| user_token == caller.user_token);
+		let quota_user = self
+			.quotas
+			.as_ref()
+			.filter(|_| T::VERB.is_mutation())
+			.map(|quotas| (quotas, user_token.clone(), limit_profile));
+		if let Some((quotas, user_token, limit_profile)) = &quota_user {
+			if quotas.enforces() && request.adds_values() {
+				in_flight.set_stage("checking quota");
+				if let Err(e) = quotas.check(user_token, limit_profile.as_deref()).await {
+					Span::current().record("http.status_code", get_error_status_code(&e));
+					tracing::warn!(error = %e, "Write rejected");
+					return Err(e);
+				}
+			}
+		}
+		let version_checks = request.version_checks().map(|checks| (checks, user_token.clone()));
+		let audit = self.mutation_audit.as_ref().and_then(|audit| {
+			let keys = request.audited_keys()?;
+			Some((audit, keys, user_token.clone(), request.store_id().to_string()))
+		});
+		in_flight.set_stage("executing");
+		let execution = handler(self.store.clone(), user_token, request);
+		let result = match self.config.request_timeout {
+			Some(timeout) => tokio::time::timeout(timeout, execution).await.unwrap_or_else(|_| {
+				Err(VssError::InternalServerError(REQUEST_TIMED_OUT.to_string()))
+			}),
+			None => execution.await,
+		};
+		if let Some((audit, keys, user_token, store_id)) = audit {
+			audit.record(AuditRecord {
+				recorded_at: unix_time_secs(),
+				caller_user_token: Some(caller.user_token.clone()).filter(|c| *c != user_token),
+				user_token,
+				store_id,
+				operation: in_flight.operation().to_string(),
+				keys,
+				source_ip: Some(self.peer_ip.to_string()),
+				auth_method: caller.auth_method.map(str::to_string),
+				succeeded: result.is_ok(),
+			});
+		}
+		match result {
+			Ok(response) => {
+				let quota_warning = match quota_user {
+					Some((quotas, user_token, limit_profile)) => {
+						in_flight.set_stage("checking quota");
+						quotas.warning(&user_token, limit_profile.as_deref()).await
+					},
+					None => None,
+				};
+				if let Some((notifier, user_token, store_id)) = access {
+					let user_agent = headers_map.get("user-agent").cloned();
+					tokio::spawn(async move {
+						notifier.observe_access(&user_token, &store_id, user_agent.as_deref()).await
+					});
+				}
+				Ok((response, quota_warning))
 			},
 			Err(e) => {
 				let status_code = get_error_status_code(&e);
@@ -383,8 +1222,187 @@ async fn handle_request<
 						tracing::warn!(error = %e, http.status_code = status_code, "Request error");
 					},
 				}
-				Ok(build_error_response(e))
+				if let (VssError::ConflictError(_), Some((checks, user_token))) =
+					(&e, version_checks)
+				{
+					in_flight.set_stage("describing conflict");
+					let reveal_values =
+						self.may_read(&caller, &user_token, &checks.store_id, &checks.keys).await;
+					let details = checks
+						.describe_conflict(self.store.as_ref(), &user_token, reveal_values)
+						.await;
+					*conflict_details = Some(details);
+				}
+				Err(e)
 			},
+		}
+	}
+}
+
+/// The encoding of a request or response body of the [`KvStore`] API.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum BodyFormat {
+	Protobuf,
+	Json,
+}
+
+impl BodyFormat {
+	/// Negotiates the formats of the request and response bodies from the `Content-Type` and
+	/// `Accept` headers. Responses are encoded like the request unless JSON or protobuf is
+	/// explicitly accepted.
+	fn negotiate(headers_map: &HashMap<String, String>) -> (Self, Self) {
+		let is_json = |media_type: &str| {
+			let essence = media_type.split(';').next().unwrap_or_default().trim();
+			essence.eq_ignore_ascii_case(JSON_CONTENT_TYPE)
+		};
+		let request_format = match headers_map.get("content-type") {
+			Some(content_type) if is_json(content_type) => BodyFormat::Json,
+			_ => BodyFormat::Protobuf,
+		};
+		let accepted: Vec<&str> =
+			headers_map.get("accept").map(|accept| accept.split(',').collect()).unwrap_or_default();
+		let response_format = if accepted.iter().any(|media_type| is_json(media_type)) {
+			BodyFormat::Json
+		} else if accepted.iter().any(|media_type| media_type.contains("protobuf")) {
+			BodyFormat::Protobuf
+		} else {
+			request_format
+		};
+		(request_format, response_format)
+	}
+}
+
+const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// A response of the [`KvStore`] API, encoded in the negotiated format.
+pub(crate) trait ResponseBody {
+	fn into_body(self, format: BodyFormat) -> VssBody;
+
+	/// The `Content-Type` of the encoded body, if any.
+	fn content_type(format: BodyFormat) -> Option<&'static str> {
+		match format {
+			BodyFormat::Protobuf => None,
+			BodyFormat::Json => Some(JSON_CONTENT_TYPE),
+		}
+	}
+}
+
+impl<M: Message + ToJson> ResponseBody for M {
+	fn into_body(self, format: BodyFormat) -> VssBody {
+		match format {
+			BodyFormat::Protobuf => full_body(self.encode_to_vec()),
+			BodyFormat::Json => full_body(self.to_json()),
+		}
+	}
+}
+
+async fn handle_request<
+	T: Message + Default + StoreScopedRequest + CapturableRequest + ShimmedRequest + FromJson,
+	R: ResponseBody,
+	F: FnOnce(Arc<dyn KvStore>, String, T) -> Fut + Send + 'static,
+	Fut: Future<Output = Result<R, VssError>> + Send,
+>(
+	service: VssService, request: Request<Incoming>, operation_name: &'static str, handler: F,
+) -> Result<<VssService as Service<Request<Incoming>>>::Response, hyper::Error> {
+	let started_at = Instant::now();
+	if let Err(retry_after) = service.check_load(operation_name) {
+		service.observe_shed_response(operation_name, started_at);
+		return Ok(build_shed_response(retry_after));
+	}
+	let in_flight = service.start_request(operation_name);
+	let (parts, body) = request.into_parts();
+	let mut headers_map = parts
+		.headers
+		.iter()
+		// HeaderName converted to a string is in lowercase.
+		.map(|(k, v)| (k.to_string(), v.to_str().unwrap_or_default().to_string()))
+		.collect::<HashMap<String, String>>();
+	debug_assert!(headers_map.keys().all(|key| key.chars().all(|c| !c.is_uppercase())));
+	if let Some(compat) = service.client_compatibility.as_ref() {
+		compat.normalize_headers(&mut headers_map);
+	}
+	if let Err(e) = service.check_maintenance(operation_name) {
+		service.observe_shed_response(operation_name, started_at);
+		return Ok(build_error_response(e, BodyFormat::negotiate(&headers_map).1));
+	}
+	let mut capture =
+		service.request_capture.as_ref().and_then(|c| c.sample(operation_name, &headers_map));
+	let response = serve_request(
+		&service,
+		body,
+		&headers_map,
+		&in_flight,
+		&mut capture,
+		operation_name,
+		handler,
+	)
+	.await;
+	let status = response.status().as_u16();
+	let body_size = response.body().size_hint().exact().unwrap_or_default() as usize;
+	if let Some(capture) = capture.as_mut() {
+		capture.set_response(status, body_size);
+	}
+	service.observe_response(operation_name, status, started_at, body_size);
+	Ok(response)
+}
+
+/// Serves a [`KvStore`] request, filling in its capture along the way if it was sampled.
+async fn serve_request<
+	T: Message + Default + StoreScopedRequest + CapturableRequest + ShimmedRequest + FromJson,
+	R: ResponseBody,
+	F: FnOnce(Arc<dyn KvStore>, String, T) -> Fut + Send + 'static,
+	Fut: Future<Output = Result<R, VssError>> + Send,
+>(
+	service: &VssService, body: Incoming, headers_map: &HashMap<String, String>,
+	in_flight: &InFlightGuard, capture: &mut Option<RequestCaptureGuard>,
+	operation_name: &'static str, handler: F,
+) -> <VssService as Service<Request<Incoming>>>::Response {
+	let (request_format, response_format) = BodyFormat::negotiate(headers_map);
+
+	let caller = match service.authenticate(headers_map, in_flight).await {
+		Ok(caller) => caller,
+		Err(e) => {
+			if let Err(retry_after) = service.check_rate_limit(None) {
+				return build_rate_limited_response(retry_after);
+			}
+			let e = match service.challenge().await {
+				Some(Ok(challenge)) => return build_payment_required_response(challenge),
+				Some(Err(challenge_error)) => challenge_error,
+				None => e,
+			};
+			capture.iter_mut().for_each(|c| c.set_error(&e));
+			return build_error_response(e, response_format);
+		},
+	};
+	if let Err(retry_after) = service.check_rate_limit(Some(&caller.user_token)) {
+		return build_rate_limited_response(retry_after);
+	}
+	capture.iter_mut().for_each(|c| c.set_user(&caller.user_token));
+
+	in_flight.set_stage("reading request body");
+	let content_encoding = headers_map.get("content-encoding").cloned();
+	let bytes = match read_limited_body(body, content_encoding, service.config).await {
+		Ok(bytes) => bytes,
+		Err(response) => return response,
+	};
+
+	// Record request body size
+	Span::current().record("http.request.body.size", bytes.len());
+	capture.iter_mut().for_each(|c| c.set_request_body_size(bytes.len()));
+	service.observe_request_body(operation_name, bytes.len());
+
+	let decoded = match request_format {
+		BodyFormat::Protobuf => T::decode(bytes).map_err(|e| e.to_string()),
+		BodyFormat::Json => T::from_json(&bytes),
+	};
+	let mut request = match decoded {
+		Ok(request) => request,
+		Err(e) if request_format == BodyFormat::Json => {
+			Span::current().record("http.status_code", 400);
+			tracing::warn!(error = %e, http.status_code = 400, "Error parsing JSON request");
+			let e = VssError::InvalidRequestError(format!("Error parsing request: {}", e));
+			capture.iter_mut().for_each(|c| c.set_error(&e));
+			return build_error_response(e, response_format);
 		},
 		Err(e) => {
 			sentry::capture_message(
@@ -394,72 +1412,570 @@ async fn handle_request<
 			Span::current().record("http.status_code", 400);
 			Span::current().record("error", true);
 			tracing::warn!(error = %e, http.status_code = 400, "Error parsing protobuf request");
-			Ok(Response::builder()
+			return Response::builder()
 				.status(StatusCode::BAD_REQUEST)
-				.body(Full::new(Bytes::from(b"Error parsing request".to_vec())))
+				.body(full_body(Bytes::from(b"Error parsing request".to_vec())))
+				// unwrap safety: body only errors when previous chained calls failed.
+				.unwrap();
+		},
+	};
+	if let Some(compat) = service.client_compatibility.as_ref() {
+		compat.apply(&mut request);
+	}
+	capture.iter_mut().for_each(|c| c.set_request(request.store_id(), &request));
+	let mut conflict_details = None;
+	let result = service
+		.execute_request(headers_map, in_flight, caller, request, handler, &mut conflict_details)
+		.await;
+	match result {
+		Ok((response, quota_warning)) => {
+			let body = response.into_body(response_format);
+			// Streamed bodies are of unknown size.
+			if let Some(size) = body.size_hint().exact() {
+				Span::current().record("http.response.body.size", size);
+			}
+			Span::current().record("http.status_code", 200);
+			tracing::info!(
+				http.status_code = 200,
+				operation = operation_name,
+				"Request completed successfully"
+			);
+			let mut builder = Response::builder();
+			if let Some(content_type) = R::content_type(response_format) {
+				builder = builder.header(hyper::header::CONTENT_TYPE, content_type);
+			}
+			if let Some(quota_warning) = quota_warning {
+				builder = builder.header(QUOTA_WARNING_HEADER, quota_warning);
+			}
+			builder
+				.body(body)
+				// unwrap safety: body only errors when previous chained calls failed.
+				.unwrap()
+		},
+		Err(e) => {
+			capture.iter_mut().for_each(|c| c.set_error(&e));
+			build_detailed_error_response(e, conflict_details, response_format)
+		},
+	}
+}
+
+/// Serves a JSON endpoint on behalf of the authenticated caller.
+async fn handle_json_request<
+	T: DeserializeOwned,
+	R: Serialize,
+	F: FnOnce(AuthResponse, T) -> Fut,
+	Fut: Future<Output = Result<R, VssError>>,
+>(
+	service: VssService, request: Request<Incoming>, operation_name: &'static str, handler: F,
+) -> Result<<VssService as Service<Request<Incoming>>>::Response, hyper::Error> {
+	if let Err(retry_after) = service.check_load(operation_name) {
+		return Ok(build_shed_response(retry_after));
+	}
+	let in_flight = service.start_request(operation_name);
+	if let Err(e) = service.check_maintenance(operation_name) {
+		return Ok(build_json_error_response(e));
+	}
+	let (parts, body) = request.into_parts();
+	let headers_map = parts
+		.headers
+		.iter()
+		.map(|(k, v)| (k.to_string(), v.to_str().unwrap_or_default().to_string()))
+		.collect::<HashMap<String, String>>();
+	let auth_response = match service.authenticate(&headers_map, &in_flight).await {
+		Ok(auth_response) => auth_response,
+		Err(e) => {
+			if let Err(retry_after) = service.check_rate_limit(None) {
+				return Ok(build_rate_limited_response(retry_after));
+			}
+			return match service.challenge().await {
+				Some(Ok(challenge)) => Ok(build_payment_required_response(challenge)),
+				Some(Err(challenge_error)) => Ok(build_json_error_response(challenge_error)),
+				None => Ok(build_json_error_response(e)),
+			};
+		},
+	};
+	if let Err(retry_after) = service.check_rate_limit(Some(&auth_response.user_token)) {
+		return Ok(build_rate_limited_response(retry_after));
+	}
+	// These endpoints manage the account, e.g. grant others access, which credentials limited to
+	// some kinds of access or some stores must not be able to.
+	if auth_response.scopes.is_some() || auth_response.store_ids.is_some() {
+		tracing::warn!(operation = operation_name, "Access denied to scoped credentials");
+		return Ok(build_json_error_response(VssError::AuthError(
+			"Scoped credentials only permit object operations.".to_string(),
+		)));
+	}
+	in_flight.set_stage("reading request body");
+	let content_encoding = headers_map.get("content-encoding").cloned();
+	let bytes = match read_limited_body(body, content_encoding, service.config).await {
+		Ok(bytes) => bytes,
+		Err(response) => return Ok(response),
+	};
+	let request = match serde_json::from_slice::<T>(&bytes) {
+		Ok(request) => request,
+		Err(e) => {
+			return Ok(build_json_error_response(VssError::InvalidRequestError(e.to_string())))
+		},
+	};
+	in_flight.set_stage("executing");
+	let result = handler(auth_response, request).await;
+	if let Some(load_shedder) = service.load_shedder.as_ref() {
+		let failed = matches!(
+			result,
+			Err(VssError::InternalServerError(_) | VssError::ServiceUnavailableError(_))
+		);
+		load_shedder.record(failed);
+	}
+	match result {
+		Ok(response) => {
+			tracing::info!(operation = operation_name, "Request completed successfully");
+			// unwrap safety: our response types always serialize successfully.
+			let body = serde_json::to_vec(&response).unwrap();
+			Ok(Response::builder()
+				.header(hyper::header::CONTENT_TYPE, "application/json")
+				.body(full_body(Bytes::from(body)))
 				// unwrap safety: body only errors when previous chained calls failed.
 				.unwrap())
 		},
+		Err(e) => {
+			tracing::warn!(error = %e, operation = operation_name, "Request error");
+			Ok(build_json_error_response(e))
+		},
+	}
+}
+
+/// Serves a JSON endpoint operating on a store, checking and recording it like any [`KvStore`]
+/// request. `resolve` turns the parsed request into the [`StoreScopedRequest`] it makes.
+async fn handle_json_store_request<
+	T: DeserializeOwned,
+	S: StoreScopedRequest,
+	R: Serialize,
+	P: FnOnce(T) -> Result<S, VssError>,
+	F: FnOnce(Arc<dyn KvStore>, String, S) -> Fut,
+	Fut: Future<Output = Result<R, VssError>>,
+>(
+	service: VssService, request: Request<Incoming>, operation_name: &'static str, resolve: P,
+	handler: F,
+) -> Result<<VssService as Service<Request<Incoming>>>::Response, hyper::Error> {
+	let started_at = Instant::now();
+	if let Err(retry_after) = service.check_load(operation_name) {
+		service.observe_shed_response(operation_name, started_at);
+		return Ok(build_shed_response(retry_after));
+	}
+	let in_flight = service.start_request(operation_name);
+	if let Err(e) = service.check_maintenance(operation_name) {
+		service.observe_shed_response(operation_name, started_at);
+		return Ok(build_json_error_response(e));
+	}
+	let (parts, body) = request.into_parts();
+	let headers_map = parts
+		.headers
+		.iter()
+		.map(|(k, v)| (k.to_string(), v.to_str().unwrap_or_default().to_string()))
+		.collect::<HashMap<String, String>>();
+	let response = serve_json_store_request(
+		&service,
+		body,
+		&headers_map,
+		&in_flight,
+		operation_name,
+		resolve,
+		handler,
+	)
+	.await;
+	let status = response.status().as_u16();
+	let body_size = response.body().size_hint().exact().unwrap_or_default() as usize;
+	service.observe_response(operation_name, status, started_at, body_size);
+	Ok(response)
+}
+
+async fn serve_json_store_request<
+	T: DeserializeOwned,
+	S: StoreScopedRequest,
+	R: Serialize,
+	P: FnOnce(T) -> Result<S, VssError>,
+	F: FnOnce(Arc<dyn KvStore>, String, S) -> Fut,
+	Fut: Future<Output = Result<R, VssError>>,
+>(
+	service: &VssService, body: Incoming, headers_map: &HashMap<String, String>,
+	in_flight: &InFlightGuard, operation_name: &'static str, resolve: P, handler: F,
+) -> <VssService as Service<Request<Incoming>>>::Response {
+	let caller = match service.authenticate(headers_map, in_flight).await {
+		Ok(caller) => caller,
+		Err(e) => {
+			if let Err(retry_after) = service.check_rate_limit(None) {
+				return build_rate_limited_response(retry_after);
+			}
+			return match service.challenge().await {
+				Some(Ok(challenge)) => build_payment_required_response(challenge),
+				Some(Err(challenge_error)) => build_json_error_response(challenge_error),
+				None => build_json_error_response(e),
+			};
+		},
+	};
+	if let Err(retry_after) = service.check_rate_limit(Some(&caller.user_token)) {
+		return build_rate_limited_response(retry_after);
+	}
+
+	in_flight.set_stage("reading request body");
+	let content_encoding = headers_map.get("content-encoding").cloned();
+	let bytes = match read_limited_body(body, content_encoding, service.config).await {
+		Ok(bytes) => bytes,
+		Err(response) => return response,
+	};
+	service.observe_request_body(operation_name, bytes.len());
+	let request = match serde_json::from_slice::<T>(&bytes) {
+		Ok(request) => request,
+		Err(e) => return build_json_error_response(VssError::InvalidRequestError(e.to_string())),
+	};
+	let request = match resolve(request) {
+		Ok(request) => request,
+		Err(e) => return build_json_error_response(e),
+	};
+	let result =
+		service.execute_request(headers_map, in_flight, caller, request, handler, &mut None).await;
+	match result {
+		Ok((response, quota_warning)) => {
+			tracing::info!(operation = operation_name, "Request completed successfully");
+			// unwrap safety: our response types always serialize successfully.
+			let body = serde_json::to_vec(&response).unwrap();
+			let mut builder =
+				Response::builder().header(hyper::header::CONTENT_TYPE, JSON_CONTENT_TYPE);
+			if let Some(quota_warning) = quota_warning {
+				builder = builder.header(QUOTA_WARNING_HEADER, quota_warning);
+			}
+			builder
+				.body(full_body(Bytes::from(body)))
+				// unwrap safety: body only errors when previous chained calls failed.
+				.unwrap()
+		},
+		Err(e) => build_json_error_response(e),
+	}
+}
+
+fn delayed_deletions_enabled(
+	delayed_deletions: Option<Arc<DelayedDeletions>>,
+) -> Result<Arc<DelayedDeletions>, VssError> {
+	delayed_deletions.ok_or_else(|| {
+		VssError::InvalidRequestError("Delayed deletion is not enabled.".to_string())
+	})
+}
+
+/// Serves a `GetObjectRequest` authorized by a share token instead of the configured authorizer.
+async fn handle_get_shared_object_request(
+	service: VssService, share_tokens: Arc<ShareTokens>, request: Request<Incoming>,
+) -> Result<<VssService as Service<Request<Incoming>>>::Response, hyper::Error> {
+	// Shared objects are read without authenticating as a user, so their readers are told apart by
+	// their IP address.
+	if let Err(retry_after) = service.check_rate_limit(None) {
+		return Ok(build_rate_limited_response(retry_after));
+	}
+	let (parts, body) = request.into_parts();
+	let authorization = parts
+		.headers
+		.get(hyper::header::AUTHORIZATION)
+		.and_then(|value| value.to_str().ok())
+		.map(str::to_string);
+	let content_encoding = parts
+		.headers
+		.get(hyper::header::CONTENT_ENCODING)
+		.and_then(|value| value.to_str().ok())
+		.map(str::to_string);
+	let bytes = match read_limited_body(body, content_encoding, service.config).await {
+		Ok(bytes) => bytes,
+		Err(response) => return Ok(response),
+	};
+	let request = match GetObjectRequest::decode(bytes) {
+		Ok(request) => request,
+		Err(e) => {
+			return Ok(build_error_response(
+				VssError::InvalidRequestError(format!("Error parsing request: {}", e)),
+				BodyFormat::Protobuf,
+			))
+		},
+	};
+	match share_tokens.get_shared_object(authorization.as_deref(), request).await {
+		Ok(response) => Ok(Response::builder()
+			.body(full_body(Bytes::from(response.encode_to_vec())))
+			// unwrap safety: body only errors when previous chained calls failed.
+			.unwrap()),
+		Err(e) => {
+			tracing::warn!(error = %e, "Shared object request failed");
+			Ok(build_error_response(e, BodyFormat::Protobuf))
+		},
+	}
+}
+
+/// Upgrades a request to a WebSocket pushing the changes of a store to the caller.
+async fn handle_subscribe_request(
+	service: VssService, request: Request<Incoming>,
+) -> Result<<VssService as Service<Request<Incoming>>>::Response, hyper::Error> {
+	let operation_name = "subscribe";
+	let started_at = Instant::now();
+	if let Err(retry_after) = service.check_load(operation_name) {
+		service.observe_shed_response(operation_name, started_at);
+		return Ok(build_shed_response(retry_after));
+	}
+	let in_flight = service.start_request(operation_name);
+	let response = serve_subscribe_request(&service, request, &in_flight).await;
+	service.observe_response(operation_name, response.status().as_u16(), started_at, 0);
+	Ok(response)
+}
+
+async fn serve_subscribe_request(
+	service: &VssService, mut request: Request<Incoming>, in_flight: &InFlightGuard,
+) -> <VssService as Service<Request<Incoming>>>::Response {
+	let mut headers_map = request
+		.headers()
+		.iter()
+		.map(|(k, v)| (k.to_string(), v.to_str().unwrap_or_default().to_string()))
+		.collect::<HashMap<String, String>>();
+	if let Some(compat) = service.client_compatibility.as_ref() {
+		compat.normalize_headers(&mut headers_map);
+	}
+	let (_, response_format) = BodyFormat::negotiate(&headers_map);
+	let Some(broadcaster) = service.change_broadcaster.clone() else {
+		let e = VssError::InvalidRequestError("Change subscriptions are not enabled.".to_string());
+		return build_error_response(e, response_format);
+	};
+	let accept_key = match websocket_accept_key(&headers_map) {
+		Ok(accept_key) => accept_key,
+		Err(e) => return build_error_response(e, response_format),
+	};
+	let caller = match service.authenticate(&headers_map, in_flight).await {
+		Ok(caller) => caller,
+		Err(e) => {
+			if let Err(retry_after) = service.check_rate_limit(None) {
+				return build_rate_limited_response(retry_after);
+			}
+			return build_error_response(e, response_format);
+		},
+	};
+	if let Err(retry_after) = service.check_rate_limit(Some(&caller.user_token)) {
+		return build_rate_limited_response(retry_after);
 	}
+	let params = parse_query(request.uri().query().unwrap_or_default());
+	let store_id = params.get("store_id").copied().unwrap_or_default().to_string();
+	let subscribe_request = SubscribeRequest { store_id: store_id.clone() };
+	let result = service
+		.execute_request(
+			&headers_map,
+			in_flight,
+			caller,
+			subscribe_request,
+			|_, user_token, request| async move {
+				broadcaster.subscribe(&user_token, &request.store_id)
+			},
+			&mut None,
+		)
+		.await;
+	let receiver = match result {
+		Ok((receiver, _)) => receiver,
+		Err(e) => return build_error_response(e, response_format),
+	};
+	tokio::spawn(serve_subscription(hyper::upgrade::on(&mut request), store_id, receiver));
+	tracing::info!(operation = "subscribe", "Subscription opened");
+	Response::builder()
+		.status(StatusCode::SWITCHING_PROTOCOLS)
+		.header(hyper::header::CONNECTION, "upgrade")
+		.header(hyper::header::UPGRADE, "websocket")
+		.header(hyper::header::SEC_WEBSOCKET_ACCEPT, accept_key)
+		.body(full_body(Bytes::new()))
+		// unwrap safety: body only errors when previous chained calls failed.
+		.unwrap()
+}
+
+/// Reads the request body, bounded in size and in the time the client may take to send it, and
+/// decompresses it according to its `Content-Encoding`.
+async fn read_limited_body(
+	body: Incoming, content_encoding: Option<String>, config: VssServiceConfig,
+) -> Result<Bytes, <VssService as Service<Request<Incoming>>>::Response> {
+	let limited_body = Limited::new(body, config.maximum_request_body_size);
+	let (status, message) =
+		match tokio::time::timeout(config.body_read_timeout, limited_body.collect()).await {
+			Ok(Ok(body)) => {
+				let body = body.to_bytes();
+				if content_encoding.is_none() {
+					return Ok(body);
+				}
+				// Decompression is CPU-bound, so it is kept off the threads serving requests.
+				let max_size = config.maximum_request_body_size;
+				let decoded = tokio::task::spawn_blocking(move || {
+					decode_body(body, content_encoding.as_deref(), max_size)
+				})
+				.await;
+				match decoded {
+					Ok(Ok(body)) => return Ok(body),
+					Ok(Err(DecodeError { status, message })) => (status, message),
+					Err(_) => {
+						(StatusCode::INTERNAL_SERVER_ERROR, "Failed to decompress request body")
+					},
+				}
+			},
+			Ok(Err(_)) => (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large"),
+			Err(_) => (StatusCode::REQUEST_TIMEOUT, "Timed out reading request body"),
+		};
+	Span::current().record("http.status_code", status.as_u16());
+	Span::current().record("error", true);
+	tracing::warn!(http.status_code = status.as_u16(), "{}", message);
+	Err(Response::builder()
+		.status(status)
+		.body(full_body(Bytes::from(message)))
+		// unwrap safety: body only errors when previous chained calls failed.
+		.unwrap())
+}
+
+/// Rejects a request exceeding the client's rate limit, telling it when to retry.
+fn build_rate_limited_response(retry_after: Duration) -> Response<VssBody> {
+	// Retry-After is given in whole seconds, so it is rounded up to not have clients retry early.
+	let retry_after_secs = (retry_after.as_secs_f64().ceil() as u64).max(1);
+	Span::current().record("http.status_code", 429);
+	tracing::warn!(http.status_code = 429, retry_after_secs, "Rate limit exceeded");
+	Response::builder()
+		.status(StatusCode::TOO_MANY_REQUESTS)
+		.header(hyper::header::RETRY_AFTER, retry_after_secs)
+		.body(full_body(Bytes::from("Too many requests")))
+		// unwrap safety: body only errors when previous chained calls failed.
+		.unwrap()
+}
+
+/// Challenges a client which failed authentication to acquire credentials.
+fn build_payment_required_response(challenge: String) -> Response<VssBody> {
+	Span::current().record("http.status_code", 402);
+	Response::builder()
+		.status(StatusCode::PAYMENT_REQUIRED)
+		.header(hyper::header::WWW_AUTHENTICATE, challenge)
+		.body(full_body(Bytes::from("Payment required")))
+		// unwrap safety: body only errors when previous chained calls failed.
+		.unwrap()
+}
+
+/// Rejects a request shed while the backend is degraded, telling the client when to retry.
+fn build_shed_response(retry_after: Duration) -> Response<VssBody> {
+	let retry_after_secs = (retry_after.as_secs_f64().ceil() as u64).max(1);
+	Span::current().record("http.status_code", 503);
+	tracing::warn!(http.status_code = 503, retry_after_secs, "Request shed");
+	Response::builder()
+		.status(StatusCode::SERVICE_UNAVAILABLE)
+		.header(hyper::header::RETRY_AFTER, retry_after_secs)
+		.body(full_body(Bytes::from("Service temporarily unavailable")))
+		// unwrap safety: body only errors when previous chained calls failed.
+		.unwrap()
 }
 
 /// Returns the HTTP status code for a given VssError
-fn get_error_status_code(e: &VssError) -> u16 {
+pub(crate) fn get_error_status_code(e: &VssError) -> u16 {
 	match e {
 		VssError::NoSuchKeyError(_) => 404,
 		VssError::ConflictError(_) => 409,
 		VssError::InvalidRequestError(_) => 400,
 		VssError::AuthError(_) => 401,
 		VssError::InternalServerError(_) => 500,
+		VssError::QuotaExceededError(_) => 507,
+		VssError::ServiceUnavailableError(_) => 503,
 	}
 }
 
-fn build_error_response(e: VssError) -> Response<Full<Bytes>> {
-	let (status_code, error_response) = match e {
-		VssError::NoSuchKeyError(msg) => {
-			let status = StatusCode::NOT_FOUND;
-			let error = ErrorResponse {
-				error_code: ErrorCode::NoSuchKeyException.into(),
-				message: msg.to_string(),
-			};
-			(status, error)
-		},
-		VssError::ConflictError(msg) => {
-			let status = StatusCode::CONFLICT;
-			let error = ErrorResponse {
-				error_code: ErrorCode::ConflictException.into(),
-				message: msg.to_string(),
-			};
-			(status, error)
-		},
-		VssError::InvalidRequestError(msg) => {
-			let status = StatusCode::BAD_REQUEST;
-			let error = ErrorResponse {
-				error_code: ErrorCode::InvalidRequestException.into(),
-				message: msg.to_string(),
-			};
-			(status, error)
-		},
-		VssError::AuthError(msg) => {
-			let status = StatusCode::UNAUTHORIZED;
-			let error = ErrorResponse {
-				error_code: ErrorCode::AuthException.into(),
-				message: msg.to_string(),
-			};
-			(status, error)
-		},
-		VssError::InternalServerError(_) => {
-			let status = StatusCode::INTERNAL_SERVER_ERROR;
-			let error = ErrorResponse {
-				error_code: ErrorCode::InternalServerException.into(),
-				message: "Unknown Server Error occurred.".to_string(),
-			};
-			(status, error)
+/// Returns the seconds clients are told to wait before retrying a request which failed with `e`,
+/// if it failed for the service being temporarily unavailable.
+pub(crate) fn retry_after_secs(e: &VssError) -> Option<u64> {
+	match e {
+		VssError::ServiceUnavailableError(msg) if msg == MAINTENANCE_ERROR => {
+			Some(MAINTENANCE_RETRY_AFTER.as_secs())
 		},
+		VssError::ServiceUnavailableError(_) => Some(UNAVAILABLE_RETRY_AFTER.as_secs()),
+		_ => None,
+	}
+}
+
+#[derive(Serialize)]
+struct JsonErrorResponse {
+	error: String,
+}
+
+fn build_json_error_response(e: VssError) -> Response<VssBody> {
+	let message = match &e {
+		VssError::InternalServerError(msg) => internal_error_message(msg).to_string(),
+		VssError::NoSuchKeyError(msg)
+		| VssError::ConflictError(msg)
+		| VssError::InvalidRequestError(msg)
+		| VssError::AuthError(msg)
+		| VssError::QuotaExceededError(msg)
+		| VssError::ServiceUnavailableError(msg) => msg.to_string(),
 	};
-	Response::builder()
-		.status(status_code)
-		.body(Full::new(Bytes::from(error_response.encode_to_vec())))
+	// unwrap safety: the error response always serializes successfully.
+	let body = serde_json::to_vec(&JsonErrorResponse { error: message }).unwrap();
+	let mut builder = Response::builder().status(get_error_status_code(&e));
+	if let Some(retry_after_secs) = retry_after_secs(&e) {
+		builder = builder.header(hyper::header::RETRY_AFTER, retry_after_secs);
+	}
+	builder
+		.header(hyper::header::CONTENT_TYPE, "application/json")
+		.body(full_body(Bytes::from(body)))
 		// unwrap safety: body only errors when previous chained calls failed.
 		.unwrap()
 }
+
+fn build_error_response(e: VssError, format: BodyFormat) -> Response<VssBody> {
+	build_detailed_error_response(e, None, format)
+}
+
+/// Builds the response for an error, describing the conflict if it is one and its details were
+/// read.
+fn build_detailed_error_response(
+	e: VssError, conflict_details: Option<ConflictDetails>, format: BodyFormat,
+) -> Response<VssBody> {
+	let status_code = get_error_status_code(&e);
+	let retry_after = retry_after_secs(&e);
+	let error_response = to_error_response(e, conflict_details);
+	let mut builder = Response::builder().status(status_code);
+	if let Some(retry_after_secs) = retry_after {
+		builder = builder.header(hyper::header::RETRY_AFTER, retry_after_secs);
+	}
+	let response = match format {
+		BodyFormat::Protobuf => {
+			builder.body(full_body(Bytes::from(error_response.encode_to_vec())))
+		},
+		BodyFormat::Json => builder
+			.header(hyper::header::CONTENT_TYPE, JSON_CONTENT_TYPE)
+			.body(full_body(Bytes::from(error_response.to_json()))),
+	};
+	// unwrap safety: body only errors when previous chained calls failed.
+	response.unwrap()
+}
+
+/// Returns the message clients are told of an internal error, which hides its details unless the
+/// request timed out.
+fn internal_error_message(msg: &str) -> &str {
+	if msg == REQUEST_TIMED_OUT {
+		msg
+	} else {
+		"Unknown Server Error occurred."
+	}
+}
+
+/// Converts an error to the [`ErrorResponse`] clients are told, hiding the details of internal
+/// errors.
+pub(crate) fn to_error_response(
+	e: VssError, conflict_details: Option<ConflictDetails>,
+) -> ErrorResponse {
+	let (error_code, message) = match e {
+		VssError::NoSuchKeyError(msg) => (ErrorCode::NoSuchKeyException, msg),
+		VssError::ConflictError(msg) => (ErrorCode::ConflictException, msg),
+		VssError::InvalidRequestError(msg) => (ErrorCode::InvalidRequestException, msg),
+		VssError::AuthError(msg) => (ErrorCode::AuthException, msg),
+		VssError::InternalServerError(msg) => {
+			(ErrorCode::InternalServerException, internal_error_message(&msg).to_string())
+		},
+		VssError::QuotaExceededError(msg) => (ErrorCode::QuotaExceededException, msg),
+		VssError::ServiceUnavailableError(msg) => (ErrorCode::ServiceUnavailableException, msg),
+	};
+	let details =
+		conflict_details.filter(|_| error_code == ErrorCode::ConflictException).unwrap_or_default();
+	ErrorResponse {
+		error_code: error_code.into(),
+		message,
+		conflicting_keys: details.conflicting_keys,
+		global_version: details.global_version,
+	}
+}